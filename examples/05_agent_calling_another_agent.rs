@@ -1,7 +1,6 @@
 use reagent_rs::{AgentBuilder, AsyncToolFn, ToolBuilder, ToolExecutionError};
 use serde_json::Value;
 use std::{error::Error, sync::Arc};
-use tokio::sync::Mutex;
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn Error>> {
@@ -33,15 +32,14 @@ async fn main() -> Result<(), Box<dyn Error>> {
         .build()
         .await?;
 
-    // pass the model ref into the closure, so when the funcion is called adn
-    // the closure triggers the model is invoked
-    let weather_ref = Arc::new(Mutex::new(weather_agent_b));
+    // move agent B onto its own task and pass a handle to it into the closure,
+    // so when the funcion is called the closure triggers the model
+    let weather_handle = weather_agent_b.into_handle();
     let weather_exec: AsyncToolFn = {
-        let weather_ref = weather_ref.clone();
+        let weather_handle = weather_handle.clone();
         Arc::new(move |args: Value| {
-            let weather_ref = weather_ref.clone();
+            let weather_handle = weather_handle.clone();
             Box::pin(async move {
-                let mut agent = weather_ref.lock().await;
                 // get "location" parameter from the args (JSON value)
                 let loc = args
                     .get("location")
@@ -56,8 +54,8 @@ async fn main() -> Result<(), Box<dyn Error>> {
                 let prompt = format!("/no_think What is the weather in {loc}?");
 
                 // invoke it
-                let resp = agent
-                    .invoke_flow(prompt)
+                let resp = weather_handle
+                    .invoke(prompt)
                     .await
                     .map_err(|e| ToolExecutionError::ExecutionFailed(e.to_string()))?;
 
@@ -41,9 +41,18 @@ async fn main() -> Result<(), Box<dyn Error>> {
                 NotificationContent::PromptSuccessResult(_) => "PromptSuccessResult",
                 NotificationContent::PromptErrorResult(_) => "PromptErrorResult",
                 NotificationContent::ToolCallRequest(_) => "ToolCallRequest",
+                NotificationContent::ToolCallArgumentsRepaired(_, _) => "ToolCallArgumentsRepaired",
                 NotificationContent::ToolCallSuccessResult(_) => "ToolCallSuccessResult",
                 NotificationContent::ToolCallErrorResult(_) => "ToolCallErrorResult",
+                NotificationContent::ToolOutputTruncated(_) => "ToolOutputTruncated",
                 NotificationContent::McpToolNotification(_) => "McpToolNotification",
+                NotificationContent::McpServerReady(_) => "McpServerReady",
+                NotificationContent::PhaseChange(_) => "PhaseChange",
+                NotificationContent::InvocationFinished(_) => "InvocationFinished",
+                NotificationContent::ToolsChanged(_) => "ToolsChanged",
+                NotificationContent::Artifact(_) => "Artifact",
+                NotificationContent::Moderated(_) => "Moderated",
+                NotificationContent::PromptVersionChanged(_) => "PromptVersionChanged",
                 NotificationContent::Token(t) => {
                     print!("{}", t.value);
                     "Token"
@@ -0,0 +1,43 @@
+use reagent_rs::{tool, AgentBuilder, ToolExecutionError};
+use std::error::Error;
+
+// `#[tool]` keeps the function as-is and generates `get_current_weather_tool()`
+// which builds the `Tool` with a schema taken from the function signature
+#[tool(description = "Returns a weather forecast for a given location")]
+async fn get_current_weather(
+    #[arg(description = "City name")] location: String,
+    #[arg(description = "Number of days to forecast")] days: Option<u32>,
+) -> Result<String, ToolExecutionError> {
+    // dummy functionality, put your logic here
+    Ok(format!(
+        r#"{{"location":"{location}","days":{},"windy":false,"temperature":18}}"#,
+        days.unwrap_or(1)
+    ))
+}
+
+#[tool(name = "echo", description = "Echos back the input")]
+async fn echo_text(
+    #[arg(description = "Text to echo")] text: String,
+) -> Result<String, ToolExecutionError> {
+    Ok(text)
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn Error>> {
+    reagent_rs::observability::init_default_tracing();
+
+    let mut agent = AgentBuilder::default()
+        .set_model("qwen3:0.6b")
+        .set_system_prompt("You are a helpful assistant.")
+        .add_tool(get_current_weather_tool()?)
+        .add_tool(echo_text_tool()?)
+        .build()
+        .await?;
+
+    let resp = agent
+        .invoke_flow("What is the weather in Koper for the next 3 days?")
+        .await?;
+    println!("Agent: {}", resp.content.unwrap_or_default());
+
+    Ok(())
+}
@@ -0,0 +1,289 @@
+//! Procedural macros for [`reagent-rs`](https://docs.rs/reagent-rs).
+//!
+//! These are re-exported from the main crate, so you normally use them as
+//! `reagent_rs::tool` / `reagent_rs::AgentOutput` instead of depending on this crate directly.
+
+use proc_macro::TokenStream;
+use proc_macro2::Span;
+use quote::{format_ident, quote};
+use syn::{
+    parse::Parser, parse_macro_input, punctuated::Punctuated, spanned::Spanned, Expr, ExprLit,
+    FnArg, GenericArgument, ItemFn, Lit, Meta, Pat, PathArguments, Token, Type,
+};
+
+mod output;
+
+/// Turn an `async fn` into a reagent [`Tool`].
+///
+/// The annotated function is kept as-is and a sibling constructor named
+/// `<fn_name>_tool()` is generated. It returns
+/// `Result<reagent_rs::Tool, reagent_rs::ToolBuilderError>` with a parameter
+/// schema derived from the function signature:
+///
+/// - `String`, `char` map to `"string"`
+/// - integer types map to `"integer"`, `f32`/`f64` to `"number"`
+/// - `bool` maps to `"boolean"`, `Vec<_>` and slices to `"array"`
+/// - `Option<T>` marks the parameter as optional
+/// - anything else maps to `"object"`
+///
+/// Every parameter must implement `serde::de::DeserializeOwned` and the
+/// function must return `Result<String, reagent_rs::ToolExecutionError>`.
+///
+/// Attribute arguments:
+/// - `description = "..."` (required) — description shown to the model
+/// - `name = "..."` (optional) — tool name, defaults to the function name
+///
+/// Parameters can be documented with `#[arg(description = "...")]`.
+///
+/// ```ignore
+/// use reagent_rs::{tool, ToolExecutionError};
+///
+/// #[tool(description = "Returns a weather forecast for a given location")]
+/// async fn get_current_weather(
+///     #[arg(description = "City name")] location: String,
+///     #[arg(description = "Number of days to forecast")] days: Option<u32>,
+/// ) -> Result<String, ToolExecutionError> {
+///     Ok(format!("Sunny in {location} for {} days", days.unwrap_or(1)))
+/// }
+///
+/// let tool = get_current_weather_tool()?;
+/// ```
+#[proc_macro_attribute]
+pub fn tool(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let args = match Punctuated::<Meta, Token![,]>::parse_terminated.parse(attr) {
+        Ok(args) => args,
+        Err(e) => return e.to_compile_error().into(),
+    };
+    let mut function = parse_macro_input!(item as ItemFn);
+
+    match expand_tool(args, &mut function) {
+        Ok(tokens) => tokens.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
+/// Derive [`AgentOutput`] and `FromMessage` for a structured output type.
+///
+/// The type must also derive `serde::Deserialize` and `JsonSchema`; field
+/// doc comments become schema descriptions.
+///
+/// Optional container attributes:
+/// - `#[agent_output(name = "...")]` — schema name, defaults to the snake_cased type name
+/// - `#[agent_output(strict)]` — request strict schema adherence where supported
+#[proc_macro_derive(AgentOutput, attributes(agent_output))]
+pub fn derive_agent_output(item: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(item as syn::DeriveInput);
+    match output::expand_agent_output(input) {
+        Ok(tokens) => tokens.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
+struct ToolParam {
+    ident: syn::Ident,
+    ty: Type,
+    json_type: &'static str,
+    required: bool,
+    description: String,
+}
+
+fn expand_tool(
+    args: Punctuated<Meta, Token![,]>,
+    function: &mut ItemFn,
+) -> syn::Result<proc_macro2::TokenStream> {
+    let mut description = None;
+    let mut name = None;
+
+    for arg in args {
+        let Meta::NameValue(kv) = &arg else {
+            return Err(syn::Error::new(
+                arg.span(),
+                "expected `description = \"...\"` or `name = \"...\"`",
+            ));
+        };
+        let value = string_literal(&kv.value)?;
+        if kv.path.is_ident("description") {
+            description = Some(value);
+        } else if kv.path.is_ident("name") {
+            name = Some(value);
+        } else {
+            return Err(syn::Error::new(
+                kv.path.span(),
+                "unknown `tool` argument, expected `description` or `name`",
+            ));
+        }
+    }
+
+    let Some(description) = description else {
+        return Err(syn::Error::new(
+            Span::call_site(),
+            "`#[tool]` requires `description = \"...\"`",
+        ));
+    };
+
+    if function.sig.asyncness.is_none() {
+        return Err(syn::Error::new(
+            function.sig.fn_token.span(),
+            "`#[tool]` can only be applied to `async fn`",
+        ));
+    }
+    if !function.sig.generics.params.is_empty() {
+        return Err(syn::Error::new(
+            function.sig.generics.span(),
+            "`#[tool]` functions cannot be generic",
+        ));
+    }
+
+    let fn_ident = function.sig.ident.clone();
+    let tool_name = name.unwrap_or_else(|| fn_ident.to_string());
+    let constructor = format_ident!("{}_tool", fn_ident);
+    let vis = function.vis.clone();
+
+    let mut params = Vec::new();
+    for input in function.sig.inputs.iter_mut() {
+        let FnArg::Typed(pat_type) = input else {
+            return Err(syn::Error::new(
+                input.span(),
+                "`#[tool]` functions cannot take `self`",
+            ));
+        };
+        let Pat::Ident(pat_ident) = &*pat_type.pat else {
+            return Err(syn::Error::new(
+                pat_type.pat.span(),
+                "`#[tool]` parameters must be plain identifiers",
+            ));
+        };
+        if let Type::Reference(reference) = &*pat_type.ty {
+            return Err(syn::Error::new(
+                reference.span(),
+                "`#[tool]` parameters must be owned types (e.g. `String` instead of `&str`)",
+            ));
+        }
+
+        let mut param_description = String::new();
+        let mut kept_attrs = Vec::new();
+        for attr in pat_type.attrs.drain(..) {
+            if attr.path().is_ident("arg") {
+                attr.parse_nested_meta(|meta| {
+                    if meta.path.is_ident("description") {
+                        let value: syn::LitStr = meta.value()?.parse()?;
+                        param_description = value.value();
+                        Ok(())
+                    } else {
+                        Err(meta.error("unknown `arg` argument, expected `description`"))
+                    }
+                })?;
+            } else {
+                kept_attrs.push(attr);
+            }
+        }
+        pat_type.attrs = kept_attrs;
+
+        let (json_type, required) = match option_inner(&pat_type.ty) {
+            Some(inner) => (json_type(inner), false),
+            None => (json_type(&pat_type.ty), true),
+        };
+
+        params.push(ToolParam {
+            ident: pat_ident.ident.clone(),
+            ty: (*pat_type.ty).clone(),
+            json_type,
+            required,
+            description: param_description,
+        });
+    }
+
+    let properties = params.iter().map(|p| {
+        let name = p.ident.to_string();
+        let json_type = p.json_type;
+        let description = &p.description;
+        if p.required {
+            quote! { .add_required_property(#name, #json_type, #description) }
+        } else {
+            quote! { .add_property(#name, #json_type, #description) }
+        }
+    });
+
+    let extractions = params.iter().map(|p| {
+        let ident = &p.ident;
+        let ty = &p.ty;
+        let name = ident.to_string();
+        quote! {
+            let #ident: #ty = ::reagent_rs::__private::serde_json::from_value(
+                args.get(#name).cloned().unwrap_or(::reagent_rs::Value::Null),
+            )
+            .map_err(|e| {
+                ::reagent_rs::ToolExecutionError::ArgumentParsingError(
+                    ::std::format!("invalid argument `{}`: {}", #name, e),
+                )
+            })?;
+        }
+    });
+
+    let call_args = params.iter().map(|p| &p.ident);
+    let doc = format!("Build the `Tool` definition for `{fn_ident}`.");
+
+    Ok(quote! {
+        #function
+
+        #[doc = #doc]
+        #vis fn #constructor() -> ::std::result::Result<::reagent_rs::Tool, ::reagent_rs::ToolBuilderError> {
+            ::reagent_rs::ToolBuilder::new()
+                .function_name(#tool_name)
+                .function_description(#description)
+                #(#properties)*
+                .executor_fn(|args: ::reagent_rs::Value| async move {
+                    #(#extractions)*
+                    #fn_ident(#(#call_args),*).await
+                })
+                .build()
+        }
+    })
+}
+
+fn string_literal(expr: &Expr) -> syn::Result<String> {
+    match expr {
+        Expr::Lit(ExprLit {
+            lit: Lit::Str(s), ..
+        }) => Ok(s.value()),
+        other => Err(syn::Error::new(other.span(), "expected a string literal")),
+    }
+}
+
+fn option_inner(ty: &Type) -> Option<&Type> {
+    let Type::Path(path) = ty else {
+        return None;
+    };
+    let segment = path.path.segments.last()?;
+    if segment.ident != "Option" {
+        return None;
+    }
+    let PathArguments::AngleBracketed(args) = &segment.arguments else {
+        return None;
+    };
+    match args.args.first()? {
+        GenericArgument::Type(inner) => Some(inner),
+        _ => None,
+    }
+}
+
+fn json_type(ty: &Type) -> &'static str {
+    match ty {
+        Type::Array(_) | Type::Slice(_) | Type::Tuple(_) => "array",
+        Type::Path(path) => {
+            let Some(segment) = path.path.segments.last() else {
+                return "object";
+            };
+            match segment.ident.to_string().as_str() {
+                "String" | "char" | "PathBuf" => "string",
+                "i8" | "i16" | "i32" | "i64" | "i128" | "isize" | "u8" | "u16" | "u32" | "u64"
+                | "u128" | "usize" => "integer",
+                "f32" | "f64" => "number",
+                "bool" => "boolean",
+                "Vec" | "VecDeque" | "HashSet" | "BTreeSet" => "array",
+                _ => "object",
+            }
+        }
+        _ => "object",
+    }
+}
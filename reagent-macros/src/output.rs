@@ -0,0 +1,68 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::DeriveInput;
+
+pub(crate) fn expand_agent_output(input: DeriveInput) -> syn::Result<TokenStream> {
+    let mut name = None;
+    let mut strict = None;
+
+    for attr in input
+        .attrs
+        .iter()
+        .filter(|a| a.path().is_ident("agent_output"))
+    {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("name") {
+                let value: syn::LitStr = meta.value()?.parse()?;
+                name = Some(value.value());
+                Ok(())
+            } else if meta.path.is_ident("strict") {
+                strict = match meta.value() {
+                    Ok(value) => Some(value.parse::<syn::LitBool>()?.value),
+                    Err(_) => Some(true),
+                };
+                Ok(())
+            } else {
+                Err(meta.error("unknown `agent_output` argument, expected `name` or `strict`"))
+            }
+        })?;
+    }
+
+    let ident = &input.ident;
+    let name = name.unwrap_or_else(|| to_snake_case(&ident.to_string()));
+    let strict = strict.map(|strict| quote! { .strict(#strict) });
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    Ok(quote! {
+        impl #impl_generics ::reagent_rs::FromMessage for #ident #ty_generics #where_clause {
+            fn from_message(
+                message: &::reagent_rs::Message,
+            ) -> ::std::result::Result<Self, ::reagent_rs::AgentError> {
+                ::reagent_rs::parse_structured_output::<Self>(message)
+            }
+        }
+
+        impl #impl_generics ::reagent_rs::AgentOutput for #ident #ty_generics #where_clause {
+            fn response_format() -> ::reagent_rs::SchemaSpec {
+                ::reagent_rs::SchemaSpec::from_type::<Self>()
+                    .with_name(#name)
+                    #strict
+            }
+        }
+    })
+}
+
+fn to_snake_case(name: &str) -> String {
+    let mut out = String::with_capacity(name.len() + 4);
+    for (i, c) in name.chars().enumerate() {
+        if c.is_uppercase() {
+            if i > 0 {
+                out.push('_');
+            }
+            out.extend(c.to_lowercase());
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
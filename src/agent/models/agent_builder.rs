@@ -1,20 +1,41 @@
 use crate::{
     agent::models::{
+        agent::AgentParts,
         configs::{ModelConfig, PromptConfig},
-        error::AgentBuildError,
+        error::{AgentBuildError, Issue},
+        experiment::ExperimentState,
+        schema_instructions,
     },
-    notifications::Notification,
+    notifications::{Notification, TokenBatching},
+    registered_flow,
     services::{
-        llm::{ClientBuilder, ClientConfig, Provider, ResponseFormatConfig, SchemaSpec},
-        mcp::mcp_tool_builder::McpServerType,
+        llm::{
+            models::grammar::grammar_from_schema, ClientBuilder, ClientConfig, InferenceClient,
+            MessageRewriter, Provider, Redactor, RequestLogging, ResponseFormatConfig,
+            SchemaRegistry, SchemaSpec, SecretString,
+        },
+        mcp::{
+            lazy::{connect_mode, McpConnect},
+            mcp_tool_builder::McpServerType,
+        },
+        runtime,
     },
     skills::{build_read_skill_tool, load_skill_sources},
-    templates::Template,
-    Agent, Flow, FlowFuture, Skill, Tool, ToolBuilderError, SKILL_SYSTEM_PROMPT_TEMPLATE,
+    templates::{
+        ContextProvider, ContextProviders, LibraryPrompt, PromptLibrary, SystemPromptBuilder,
+        Template,
+    },
+    Agent, AgentOutput, AgentRegistry, FewShotSet, Flow, FlowFuture, HistoryCompression,
+    HistoryDedup, HistoryObserver, IterationFuture, LongTermMemory, Message, ModelPreset,
+    ModelRouter, Moderator, OnIteration, OpenRouterRoutePrefs, PromptExperiment, Skill,
+    StructuredOutputStrategy, Tool, ToolBuilderError, ToolChoice, ToolOutputLimit,
+    ToolOutputLimits, ToolReliabilityPolicy, ToolRetryPolicy, ToolSimulator,
+    SKILL_SYSTEM_PROMPT_TEMPLATE,
 };
 use futures::future::join_all;
 use rmcp::schemars::JsonSchema;
-use std::{collections::HashMap, path::PathBuf, sync::Arc};
+use serde_json::Value;
+use std::{collections::HashMap, path::PathBuf, sync::Arc, time::Duration};
 use tokio::sync::{mpsc, Mutex};
 
 /// A builder for [`Agent`].
@@ -56,10 +77,26 @@ pub struct AgentBuilder {
     template: Option<Arc<Mutex<Template>>>,
     /// Raw system prompt string seeded into history
     system_prompt: Option<String>,
+    /// System prompt composed of named sections, alternative to `system_prompt`
+    system_prompt_sections: Option<SystemPromptBuilder>,
+    /// Library prompt used as system prompt, alternative to `system_prompt`
+    library_prompt: Option<(PromptLibrary, String)>,
     /// Local tools the agent can call during a flow
     tools: Option<Vec<Tool>>,
+    /// Whether and which tool the model has to call
+    tool_choice: Option<ToolChoice>,
+    /// Allow several tool calls per response, executed concurrently
+    parallel_tool_calls: Option<bool>,
     /// Response schema input plus optional provider hints.
     response_format: ResponseFormatConfig,
+    /// Registered schemas `set_response_format_named` refers to
+    schema_registry: Option<Arc<SchemaRegistry>>,
+    /// GBNF grammar constraining the output
+    grammar: Option<String>,
+    /// JSON schema the grammar is derived from, alternative to `grammar`
+    grammar_schema: Option<serde_json::Value>,
+    /// Model used by `Agent::embed`, defaults to the chat model
+    embedding_model: Option<String>,
     /// MCP tool servers the agent can reach
     mcp_servers: Option<Vec<McpServerType>>,
     /// Individual skill roots or SKILL.md files to load.
@@ -81,13 +118,71 @@ pub struct AgentBuilder {
 
     /// Enable server streaming for token events
     stream: Option<bool>,
+    /// Batching policy for token notifications
+    token_batching: Option<TokenBatching>,
     /// Keep-alive in memory for model after inference
     keep_alive: Option<String>,
+    /// Unload the model after every invocation
+    unload_after_invocation: Option<bool>,
+    /// Ask the provider for the context length if `num_ctx` is not set
+    detect_num_ctx: Option<bool>,
+    /// Upper bound of a detected `num_ctx`
+    max_detected_num_ctx: Option<u32>,
 
     /// Optional mpsc sender for notifications
     notification_channel: Option<mpsc::Sender<Notification>>,
     /// High-level control flow policy
     flow: Option<Flow>,
+    /// JSON Lines file tool invocations are appended to
+    tool_audit_file: Option<PathBuf>,
+    /// Retry and feedback policy for failed tool calls
+    tool_retry_policy: Option<ToolRetryPolicy>,
+    /// How tool statistics steer tool selection
+    tool_reliability: Option<ToolReliabilityPolicy>,
+    /// Replacement of repeated tool outputs in requests
+    history_dedup: Option<HistoryDedup>,
+    /// Fail the build on configuration issues instead of warning
+    strict: Option<bool>,
+    /// Examples inserted after the system prompt
+    few_shot: Option<FewShotSet>,
+    /// Context blocks sent after the system prompt
+    context_providers: Option<ContextProviders>,
+    /// How the response format is requested from the provider
+    structured_output_strategy: Option<StructuredOutputStrategy>,
+    /// Bound on concurrent model requests and tool calls
+    max_concurrency: Option<usize>,
+    /// Per-invocation model selection
+    model_router: Option<ModelRouter>,
+    /// Check of final replies
+    moderator: Option<Arc<dyn Moderator>>,
+    /// Answer tool calls with simulated results
+    dry_run: Option<bool>,
+    /// Stubbed tool results for dry runs
+    tool_simulator: Option<ToolSimulator>,
+    /// Agents looked up by name inside flows
+    registry: Option<AgentRegistry>,
+    /// Told about changes of the history
+    history_observers: Vec<Arc<dyn HistoryObserver>>,
+    /// Title conversations after their first exchange
+    auto_title: Option<bool>,
+    /// Extract the facts of every exchange
+    auto_extract_facts: Option<bool>,
+    /// Limits of the tool outputs kept in the history
+    tool_output_limits: ToolOutputLimits,
+    /// MCP servers connected after the build
+    deferred_mcp_servers: Vec<(McpServerType, McpConnect)>,
+    /// Refresh MCP tools before invocations this long after the last refresh
+    mcp_refresh_interval: Option<Duration>,
+    /// Archive old messages of long histories
+    history_compression: Option<HistoryCompression>,
+    /// System prompt variants tried per invocation
+    experiment: Option<PromptExperiment>,
+    /// Called by the default flow between iterations
+    on_iteration: Option<OnIteration>,
+    /// Name of a registered flow, looked up on build
+    flow_name: Option<String>,
+    /// Long-term memory backed by a vector store
+    memory: Option<LongTermMemory>,
 }
 
 impl AgentBuilder {
@@ -110,6 +205,34 @@ impl AgentBuilder {
         if let Some(extra_headers) = conf.extra_headers {
             self = self.set_extra_headers(extra_headers);
         }
+        if let Some(proxy) = conf.proxy {
+            self = self.set_proxy(proxy);
+        }
+        if let Some(ca_cert_path) = conf.ca_cert_path {
+            self = self.set_ca_cert_path(ca_cert_path);
+        }
+        if let Some(timeout) = conf.timeout {
+            self = self.set_request_timeout(timeout);
+        }
+        if let Some(connect_timeout) = conf.connect_timeout {
+            self = self.set_connect_timeout(connect_timeout);
+        }
+        if let Some(message_rewriter) = conf.message_rewriter {
+            self.client_config = self.client_config.message_rewriter(Some(message_rewriter));
+        }
+        if let Some(redactor) = conf.redactor {
+            self = self.set_redactor(redactor);
+        }
+        #[cfg(any(test, feature = "testing"))]
+        if let Some(script) = conf.script {
+            self.client_config = self.client_config.script(Some(script));
+        }
+        if let Some(prefs) = conf.openrouter_route_prefs {
+            self = self.set_openrouter_route_prefs(prefs);
+        }
+        if let Some(logging) = conf.request_logging {
+            self = self.set_request_logging(logging);
+        }
         self
     }
 
@@ -128,12 +251,20 @@ impl AgentBuilder {
                 self = self.add_tool(tool);
             }
         }
+        if let Some(tool_choice) = conf.tool_choice {
+            self = self.set_tool_choice(tool_choice);
+        }
+        if let Some(parallel_tool_calls) = conf.parallel_tool_calls {
+            self = self.set_parallel_tool_calls(parallel_tool_calls);
+        }
         if let Some(response_format) = conf.response_format {
             self = self.set_response_format_spec(response_format);
         }
         if let Some(mcp_servers) = conf.mcp_servers {
+            let deferred = conf.deferred_mcp_servers.unwrap_or_default();
             for mcp in mcp_servers {
-                self = self.add_mcp_server(mcp);
+                let connect = connect_mode(&deferred, &mcp);
+                self = self.add_mcp_server_with(mcp, connect);
             }
         }
         if let Some(stop_prompt) = conf.stop_prompt {
@@ -153,6 +284,9 @@ impl AgentBuilder {
         }
 
         self = self.set_stream(conf.stream);
+        if let Some(token_batching) = conf.token_batching {
+            self.token_batching = Some(token_batching);
+        }
         self
     }
 
@@ -199,7 +333,28 @@ impl AgentBuilder {
         if let Some(min_p) = conf.min_p {
             self = self.set_min_p(min_p)
         }
+        self.model_config.extra_options.extend(conf.extra_options);
+
+        self
+    }
 
+    /// Apply known-good sampling, stop sequence, context and thinking
+    /// settings for a model family. Settings made after this call take
+    /// precedence.
+    ///
+    /// ```
+    /// use reagent_rs::{AgentBuilder, ModelPreset};
+    ///
+    /// let builder = AgentBuilder::default()
+    ///     .set_model("qwen3:8b")
+    ///     .preset(ModelPreset::Qwen3)
+    ///     .set_num_ctx(32768);
+    /// ```
+    pub fn preset(mut self, preset: ModelPreset) -> Self {
+        self = self.import_model_config(preset.model_config());
+        if let Some(strip) = preset.strip_thinking() {
+            self = self.strip_thinking(strip);
+        }
         self
     }
 
@@ -230,7 +385,7 @@ impl AgentBuilder {
     /// Set the API key used by the provider client.
     pub fn set_api_key<T>(mut self, api_key: T) -> Self
     where
-        T: Into<String>,
+        T: Into<SecretString>,
     {
         self.client_config = self.client_config.api_key(Some(api_key));
         self
@@ -251,6 +406,75 @@ impl AgentBuilder {
         self
     }
 
+    /// Route all requests through a proxy, e.g. `http://proxy.corp:3128`.
+    pub fn set_proxy<T>(mut self, proxy: T) -> Self
+    where
+        T: Into<String>,
+    {
+        self.client_config = self.client_config.proxy(Some(proxy));
+        self
+    }
+
+    /// Trust the root certificates in a PEM file, in addition to the
+    /// system ones.
+    pub fn set_ca_cert_path<T>(mut self, path: T) -> Self
+    where
+        T: Into<PathBuf>,
+    {
+        self.client_config = self.client_config.ca_cert_path(Some(path));
+        self
+    }
+
+    /// Total time allowed per request. For streamed responses this includes
+    /// reading the whole stream.
+    pub fn set_request_timeout(mut self, timeout: Duration) -> Self {
+        self.client_config = self.client_config.timeout(Some(timeout));
+        self
+    }
+
+    /// Time allowed to establish a connection to the provider.
+    pub fn set_connect_timeout(mut self, timeout: Duration) -> Self {
+        self.client_config = self.client_config.connect_timeout(Some(timeout));
+        self
+    }
+
+    /// Adjust the messages of every request before the provider client
+    /// encodes them, e.g. [`ToolResultsAsUser`] for models that reject tool
+    /// messages. The agent's history is not changed.
+    pub fn set_message_rewriter<R>(mut self, rewriter: R) -> Self
+    where
+        R: MessageRewriter + 'static,
+    {
+        self.client_config = self
+            .client_config
+            .message_rewriter(Some(Arc::new(rewriter)));
+        self
+    }
+
+    /// Mask emails, phone numbers and other entities in every request and
+    /// restore them in the responses, e.g. before sending internal data to a
+    /// remote provider. See [`Redactor`].
+    pub fn set_redactor(mut self, redactor: Redactor) -> Self {
+        self.client_config = self.client_config.redactor(Some(redactor));
+        self
+    }
+
+    /// Pin, order or exclude the upstream providers OpenRouter routes
+    /// requests to, and set fallback models and transforms. Ignored by
+    /// other providers. See [`OpenRouterRoutePrefs`].
+    pub fn set_openrouter_route_prefs(mut self, prefs: OpenRouterRoutePrefs) -> Self {
+        self.client_config = self.client_config.openrouter_route_prefs(Some(prefs));
+        self
+    }
+
+    /// Log the request and response payloads of only some model requests,
+    /// e.g. `RequestLogging::sampled(100)`. Their metadata is always logged.
+    /// See [`RequestLogging`].
+    pub fn set_request_logging(mut self, logging: RequestLogging) -> Self {
+        self.client_config = self.client_config.request_logging(Some(logging));
+        self
+    }
+
     /// Set the streaming value for Ollam
     /// Will enable Token Notifications
     pub fn set_stream(mut self, set: bool) -> Self {
@@ -258,6 +482,14 @@ impl AgentBuilder {
         self
     }
 
+    /// Batch streamed tokens into fewer notifications: a batch is sent every
+    /// `max_tokens` tokens or `max_delay` after its first token, whichever
+    /// comes first. Remaining tokens are flushed before `Done`.
+    pub fn set_token_batching(mut self, max_tokens: usize, max_delay: Duration) -> Self {
+        self.token_batching = Some(TokenBatching::new(max_tokens, max_delay));
+        self
+    }
+
     /// Set the sampling temperature.
     pub fn set_temperature(mut self, v: f32) -> Self {
         self.model_config.temperature = Some(v);
@@ -282,21 +514,50 @@ impl AgentBuilder {
         self
     }
 
-    /// Set maximum context length (in tokens/chunks).
+    /// Set maximum context length (in tokens/chunks). Without it, the
+    /// context length of the model is detected, see
+    /// [`set_detect_num_ctx`](Self::set_detect_num_ctx).
     pub fn set_num_ctx(mut self, v: u32) -> Self {
         self.model_config.num_ctx = Some(v);
         self
     }
 
+    /// Whether to set `num_ctx` to the context length the model supports
+    /// when it is not set. Ollama otherwise uses a small default and cuts
+    /// longer prompts without an error. The length is read from Ollama's
+    /// `/api/show` on build. If Ollama cannot be reached, `num_ctx` stays
+    /// unset. On by default.
+    pub fn set_detect_num_ctx(mut self, detect: bool) -> Self {
+        self.detect_num_ctx = Some(detect);
+        self
+    }
+
+    /// Use at most `max` tokens of a detected context length, as models
+    /// often support far longer contexts than fit in memory. A `num_ctx`
+    /// that is set is used as is.
+    pub fn set_max_detected_num_ctx(mut self, max: u32) -> Self {
+        self.max_detected_num_ctx = Some(max);
+        self
+    }
+
     /// Repeat penalty for the last N tokens.
     pub fn set_repeat_last_n(mut self, v: i32) -> Self {
         self.model_config.repeat_last_n = Some(v);
         self
     }
 
-    /// Set keep alive of the model
-    pub fn set_keep_alive(mut self, v: String) -> Self {
-        self.keep_alive = Some(v);
+    /// How long Ollama keeps the model loaded after a request, e.g. `"10m"`,
+    /// `"-1"` for ever or `"0"` to unload it right away.
+    pub fn set_keep_alive(mut self, v: impl Into<String>) -> Self {
+        self.keep_alive = Some(v.into());
+        self
+    }
+
+    /// Unload the model once an invocation finished, instead of after every
+    /// request like a keep alive of `"0"` would, see [`Agent::unload_model`].
+    /// Off by default.
+    pub fn set_unload_after_invocation(mut self, unload: bool) -> Self {
+        self.unload_after_invocation = Some(unload);
         self
     }
 
@@ -336,25 +597,97 @@ impl AgentBuilder {
         self
     }
 
+    /// Provider-specific parameter without a setter of its own, e.g.
+    /// `top_a` for OpenRouter or `num_gpu` for Ollama. It is added to the
+    /// request body as is.
+    pub fn set_extra_option<K, V>(mut self, key: K, value: V) -> Self
+    where
+        K: Into<String>,
+        V: Into<Value>,
+    {
+        self.model_config
+            .extra_options
+            .insert(key.into(), value.into());
+        self
+    }
+
     /// Select the underlying model name.
     pub fn set_model<T: Into<String>>(mut self, model: T) -> Self {
         self.model_config.model = Some(model.into());
         self
     }
 
+    /// Model used by [`Agent::embed`] and [`Agent::embed_batch`]. Without it
+    /// the chat model is used.
+    pub fn set_embedding_model<T: Into<String>>(mut self, model: T) -> Self {
+        self.embedding_model = Some(model.into());
+        self
+    }
+
     /// System prompt that initializes conversation history.
+    /// Replaces any sections set with [`AgentBuilder::set_system_prompt_sections`].
     pub fn set_system_prompt<T: Into<String>>(mut self, prompt: T) -> Self {
         self.system_prompt = Some(prompt.into());
+        self.system_prompt_sections = None;
+        self.library_prompt = None;
+        self
+    }
+
+    /// System prompt composed of named sections, see [`SystemPromptBuilder`].
+    /// Replaces a prompt set with [`AgentBuilder::set_system_prompt`].
+    pub fn set_system_prompt_sections(mut self, sections: SystemPromptBuilder) -> Self {
+        self.system_prompt_sections = Some(sections);
+        self.system_prompt = None;
+        self.library_prompt = None;
         self
     }
 
-    /// Optional prompt to insert on each tool‐call branch.
+    /// Use the active version of prompt `name` of `library` as system
+    /// prompt, and switch to newly activated versions before invocations,
+    /// see [`PromptLibrary`]. The build fails with
+    /// [`AgentBuildError::UnknownPrompt`] if it has no active version.
+    /// Replaces a prompt set with [`AgentBuilder::set_system_prompt`].
+    pub fn set_library_prompt(mut self, library: &PromptLibrary, name: impl Into<String>) -> Self {
+        self.library_prompt = Some((library.clone(), name.into()));
+        self.system_prompt = None;
+        self.system_prompt_sections = None;
+        self
+    }
+
+    /// Set a single section of the system prompt, keeping the others.
+    ///
+    /// Useful to adjust one part of a prebuild's prompt, e.g.
+    /// `.set_system_prompt_section(SystemPromptBuilder::OUTPUT_FORMAT, "...")`.
+    /// If the prompt was set as a plain string, that string becomes the
+    /// [`SystemPromptBuilder::PERSONA`] section.
+    pub fn set_system_prompt_section(
+        mut self,
+        name: impl Into<String>,
+        content: impl Into<String>,
+    ) -> Self {
+        let sections =
+            self.system_prompt_sections
+                .get_or_insert_with(|| match self.system_prompt.take() {
+                    Some(prompt) => SystemPromptBuilder::new().persona(prompt),
+                    None => SystemPromptBuilder::new(),
+                });
+        sections.set_section(name, content);
+        self
+    }
+
+    /// Sections of the system prompt, if it was composed from sections.
+    pub fn system_prompt_sections(&self) -> Option<&SystemPromptBuilder> {
+        self.system_prompt_sections.as_ref()
+    }
+
+    /// User message added after each round of tool results in the default
+    /// flow, e.g. to ask for the final answer with the stopword.
     pub fn set_stop_prompt<T: Into<String>>(mut self, stop_prompt: T) -> Self {
         self.stop_prompt = Some(stop_prompt.into());
         self
     }
 
-    /// Optional stopword to detect end of generation.
+    /// Ends the default flow's tool loop as soon as a reply contains it.
     pub fn set_stopword<T: Into<String>>(mut self, stopword: T) -> Self {
         self.stopword = Some(stopword.into());
         self
@@ -368,6 +701,17 @@ impl AgentBuilder {
 
     pub fn set_flow_fn(mut self, flow: Flow) -> Self {
         self.flow = Some(flow);
+        self.flow_name = None;
+        self
+    }
+
+    /// Use the flow registered under `name` (see [`register_flow`](crate::register_flow)), e.g.
+    /// `plan_and_execute` or `default`. The name is looked up by `build()`,
+    /// which fails with [`AgentBuildError::UnknownFlow`] if nothing is
+    /// registered under it.
+    pub fn set_flow_named(mut self, name: impl Into<String>) -> Self {
+        self.flow_name = Some(name.into());
+        self.flow = None;
         self
     }
 
@@ -410,6 +754,24 @@ impl AgentBuilder {
         self
     }
 
+    /// Add an MCP server endpoint that is connected on first use or in the
+    /// background (see [`McpConnect`]), so the build neither waits for it
+    /// nor fails when it is down.
+    pub fn add_mcp_server_with(mut self, server: McpServerType, connect: McpConnect) -> Self {
+        if connect != McpConnect::Eager {
+            self.deferred_mcp_servers.push((server.clone(), connect));
+        }
+        self.add_mcp_server(server)
+    }
+
+    /// Refresh the tools of the MCP servers (see
+    /// [`Agent::refresh_mcp_tools`]) before an invocation when the last
+    /// refresh is at least `interval` ago.
+    pub fn set_mcp_refresh_interval(mut self, interval: Duration) -> Self {
+        self.mcp_refresh_interval = Some(interval);
+        self
+    }
+
     /// Add a skill by pointing to either `SKILL.md`/`skill.md` or its containing directory.
     pub fn add_skill(mut self, path: impl Into<PathBuf>) -> Self {
         self.skill_paths.push(path.into());
@@ -431,6 +793,15 @@ impl AgentBuilder {
         Ok(self)
     }
 
+    /// Add the deterministic local tools of [`tools::stdlib`](crate::tools::stdlib):
+    /// `current_datetime`, `calculator` and `convert_units`.
+    pub fn add_std_tools(mut self) -> Self {
+        for tool in crate::tools::stdlib::std_tools() {
+            self = self.add_tool(tool);
+        }
+        self
+    }
+
     fn add_bash_skill(mut self) -> Self {
         self.builtin_skills.push(crate::skills::bash_skill());
         self
@@ -480,6 +851,36 @@ impl AgentBuilder {
         self
     }
 
+    /// From a type implementing [`AgentOutput`] (usually via `#[derive(AgentOutput)]`)
+    pub fn set_response_format_output<T: AgentOutput>(mut self) -> Self {
+        self.response_format.set_spec(T::response_format());
+        self
+    }
+
+    /// Let the model answer with one of several schemas, see
+    /// [`SchemaSpec::union`]. Read the answer with
+    /// [`Agent::invoke_flow_structured_output_enum`].
+    pub fn set_response_formats(mut self, variants: impl IntoIterator<Item = SchemaSpec>) -> Self {
+        self.response_format.set_spec(SchemaSpec::union(variants));
+        self
+    }
+
+    /// A schema registered in the [`SchemaRegistry`] set with
+    /// [`set_schema_registry`](Self::set_schema_registry), as `"name@vN"` or
+    /// `"name"` for the latest version. The reference is checked on build and
+    /// the resolved id is attached to the agent's notifications.
+    pub fn set_response_format_named(mut self, reference: impl Into<String>) -> Self {
+        self.response_format.set_named(reference);
+        self
+    }
+
+    /// Registry used to resolve [`set_response_format_named`](Self::set_response_format_named).
+    /// Pass an `Arc` to share one registry between agents.
+    pub fn set_schema_registry(mut self, registry: impl Into<Arc<SchemaRegistry>>) -> Self {
+        self.schema_registry = Some(registry.into());
+        self
+    }
+
     /// Optional hints that apply whether you used *_str, *_value, or *_from
     pub fn set_schema_name(mut self, name: impl Into<String>) -> Self {
         self.response_format.set_name(name);
@@ -491,6 +892,277 @@ impl AgentBuilder {
         self
     }
 
+    /// Choose how the response format is requested. With
+    /// [`StructuredOutputStrategy::Prompt`] (or `Auto` on providers without
+    /// structured output), the schema is described in the system prompt,
+    /// JSON mode is enabled where available and replies are reduced to their
+    /// JSON payload.
+    pub fn set_structured_output_strategy(mut self, strategy: StructuredOutputStrategy) -> Self {
+        self.structured_output_strategy = Some(strategy);
+        self
+    }
+
+    /// Constrain the output with a GBNF grammar, e.g. to enforce CSV or a
+    /// custom DSL. Only supported with Ollama backends that accept grammars,
+    /// and not combinable with a response format.
+    pub fn set_grammar(mut self, grammar: impl Into<String>) -> Self {
+        self.grammar = Some(grammar.into());
+        self.grammar_schema = None;
+        self
+    }
+
+    /// Like [`set_grammar`](Self::set_grammar), with the grammar derived from
+    /// a JSON schema on build. `$ref` is not supported.
+    pub fn set_grammar_from_schema(mut self, schema: serde_json::Value) -> Self {
+        self.grammar_schema = Some(schema);
+        self.grammar = None;
+        self
+    }
+
+    /// Append every tool invocation to `path` as a JSON line, in addition to
+    /// keeping it in memory (see [`Agent::tool_audit`]). The file is created
+    /// if it does not exist.
+    pub fn set_tool_audit_file(mut self, path: impl Into<PathBuf>) -> Self {
+        self.tool_audit_file = Some(path.into());
+        self
+    }
+
+    /// Control whether and which tool the model has to call. See [`ToolChoice`]
+    /// for how it maps to each provider.
+    pub fn set_tool_choice(mut self, tool_choice: ToolChoice) -> Self {
+        self.tool_choice = Some(tool_choice);
+        self
+    }
+
+    /// Allow or forbid several tool calls in one model response. The flag is
+    /// sent to providers that support it (OpenAI, OpenRouter); when disabled,
+    /// tool calls are also executed one after another instead of concurrently.
+    pub fn set_parallel_tool_calls(mut self, parallel: bool) -> Self {
+        self.parallel_tool_calls = Some(parallel);
+        self
+    }
+
+    /// Set how failed tool calls are handled: transient failures can be
+    /// retried and remaining failures reported to the model as corrective
+    /// instructions. See [`ToolRetryPolicy`].
+    pub fn set_tool_retry_policy(mut self, policy: ToolRetryPolicy) -> Self {
+        self.tool_retry_policy = Some(policy);
+        self
+    }
+
+    /// Let recorded tool outcomes steer tool selection: unreliable tools are
+    /// mentioned in the system prompt and sent last. See
+    /// [`ToolReliabilityPolicy`].
+    pub fn set_tool_reliability(mut self, policy: ToolReliabilityPolicy) -> Self {
+        self.tool_reliability = Some(policy);
+        self
+    }
+
+    /// Replace older repeated tool outputs (e.g. the same RAG chunks) with a
+    /// short reference to the latest copy in every request. The stored
+    /// history is left intact. See [`HistoryDedup`].
+    pub fn set_history_dedup(mut self, dedup: HistoryDedup) -> Self {
+        self.history_dedup = Some(dedup);
+        self
+    }
+
+    /// Run at most `max` model requests and tool calls at once, across
+    /// parallel tool calls, best-of-n candidates, parallel plan steps and
+    /// evals of this agent and its clones. Protects small servers from
+    /// overload.
+    pub fn set_max_concurrency(mut self, max: usize) -> Self {
+        self.max_concurrency = Some(max.max(1));
+        self
+    }
+
+    /// Pick the model of every invocation from the router's candidates and
+    /// escalate to better ones on failure, see [`ModelRouter`]. The model
+    /// set with [`set_model`](Self::set_model) defaults to the first
+    /// candidate and is used outside of invocations, e.g. by sub-agents.
+    pub fn set_model_router(mut self, router: ModelRouter) -> Self {
+        self.model_router = Some(router);
+        self
+    }
+
+    /// Check the final reply of the built-in flows before it is returned,
+    /// e.g. with a [`KeywordModerator`](crate::KeywordModerator) or
+    /// [`LlmModerator`](crate::LlmModerator). See [`Agent::moderate`].
+    pub fn set_moderator(mut self, moderator: Arc<dyn Moderator>) -> Self {
+        self.moderator = Some(moderator);
+        self
+    }
+
+    /// Build the agent in dry-run mode, see [`Agent::set_dry_run`].
+    pub fn set_dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = Some(dry_run);
+        self
+    }
+
+    /// Results handed to the model for tool calls in dry-run mode.
+    pub fn set_tool_simulator(mut self, simulator: ToolSimulator) -> Self {
+        self.tool_simulator = Some(simulator);
+        self
+    }
+
+    /// Run `f` between the iterations of the default flow, e.g. to log
+    /// progress, add tools or stop early. See [`OnIteration`].
+    pub fn set_on_iteration<F>(mut self, f: F) -> Self
+    where
+        F: for<'a> Fn(&'a mut Agent, usize, &'a Message) -> IterationFuture<'a>
+            + Send
+            + Sync
+            + 'static,
+    {
+        self.on_iteration = Some(OnIteration::new(f));
+        self
+    }
+
+    /// Give the agent a long-term memory: `remember` and `recall` tools and
+    /// facts stored after every invocation. See [`LongTermMemory`].
+    pub fn set_memory(mut self, memory: LongTermMemory) -> Self {
+        self.memory = Some(memory);
+        self
+    }
+
+    /// Move old messages of long histories into an archive after every
+    /// invocation, see [`HistoryCompression`].
+    pub fn set_history_compression(mut self, compression: HistoryCompression) -> Self {
+        self.history_compression = Some(compression);
+        self
+    }
+
+    /// Try the system prompt variants of `experiment` against each other,
+    /// one per invocation, see [`PromptExperiment`].
+    pub fn set_experiment(mut self, experiment: PromptExperiment) -> Self {
+        self.experiment = Some(experiment);
+        self
+    }
+
+    /// Tell `observer` about every change of the agent's history, see
+    /// [`HistoryObserver`].
+    pub fn add_history_observer(mut self, observer: Arc<dyn HistoryObserver>) -> Self {
+        self.history_observers.push(observer);
+        self
+    }
+
+    /// Title the conversation with a small sub-agent after its first
+    /// exchange, see [`Agent::generate_title`]. Off by default.
+    pub fn set_auto_title(mut self, auto_title: bool) -> Self {
+        self.auto_title = Some(auto_title);
+        self
+    }
+
+    /// Extract the entities and relationships mentioned in every exchange
+    /// with a small sub-agent, see [`Agent::extract_facts`]. Off by default.
+    pub fn set_auto_extract_facts(mut self, auto_extract_facts: bool) -> Self {
+        self.auto_extract_facts = Some(auto_extract_facts);
+        self
+    }
+
+    /// Shorten tool outputs of more than about `max_tokens` tokens, keeping
+    /// their start and end, before they are added to the history. See
+    /// [`set_tool_output_limit`](Self::set_tool_output_limit) for other
+    /// strategies.
+    pub fn set_max_tool_output_tokens(self, max_tokens: usize) -> Self {
+        self.set_tool_output_limit(ToolOutputLimit::new(max_tokens))
+    }
+
+    /// Limit of the outputs of all tools without their own limit, see
+    /// [`ToolOutputLimit`].
+    pub fn set_tool_output_limit(mut self, limit: ToolOutputLimit) -> Self {
+        self.tool_output_limits.default = Some(limit);
+        self
+    }
+
+    /// Limit of the outputs of the tool `name`.
+    pub fn set_tool_output_limit_for(
+        mut self,
+        name: impl Into<String>,
+        limit: ToolOutputLimit,
+    ) -> Self {
+        self.tool_output_limits.tools.insert(name.into(), limit);
+        self
+    }
+
+    /// Registry searched by [`Agent::resolve`] before the global one.
+    pub fn set_registry(mut self, registry: AgentRegistry) -> Self {
+        self.registry = Some(registry);
+        self
+    }
+
+    /// Examples inserted after the system prompt of every request, as user
+    /// and assistant messages. See [`FewShotSet`].
+    pub fn set_few_shot(mut self, examples: FewShotSet) -> Self {
+        self.few_shot = Some(examples);
+        self
+    }
+
+    /// Context blocks fetched on every request and sent as a system message
+    /// after the system prompt. See [`ContextProviders`].
+    pub fn set_context_providers(mut self, providers: ContextProviders) -> Self {
+        self.context_providers = Some(providers);
+        self
+    }
+
+    /// Add a provider to the [`ContextProviders`] of the agent, cached for
+    /// `ttl` if given.
+    pub fn add_context_provider(
+        mut self,
+        provider: impl ContextProvider + 'static,
+        ttl: Option<Duration>,
+    ) -> Self {
+        let providers = self.context_providers.take().unwrap_or_default();
+        self.context_providers = Some(providers.push(Arc::new(provider), ttl));
+        self
+    }
+
+    /// In strict mode, [`build`](Self::build) fails with
+    /// [`AgentBuildError::Invalid`] on the issues found by
+    /// [`validate`](Self::validate) instead of logging them as warnings.
+    pub fn set_strict(mut self, strict: bool) -> Self {
+        self.strict = Some(strict);
+        self
+    }
+
+    /// Check for settings that are accepted but do not work together, e.g.
+    /// streamed structured output on a provider that cannot stream it. Each issue is logged as a warning
+    /// and returned; the builder is left unchanged.
+    pub fn validate(&self) -> Vec<Issue> {
+        let issues = self.issues();
+        for issue in &issues {
+            tracing::warn!(agent = ?self.name, "{issue}");
+        }
+        issues
+    }
+
+    fn issues(&self) -> Vec<Issue> {
+        let mut issues = Vec::new();
+        let provider = self.client_config.provider.clone().unwrap_or_default();
+
+        if self.stream.unwrap_or(false)
+            && (self.response_format.is_set() || self.grammar.is_some())
+            && !matches!(provider, Provider::Ollama | Provider::OpenAi)
+        {
+            issues.push(Issue::new(
+                &["stream", "response_format"],
+                format!(
+                    "{provider:?} does not support streamed structured output; disable streaming or the output may not match the schema"
+                ),
+            ));
+        }
+
+        let has_tools = self.tools.as_ref().is_some_and(|tools| !tools.is_empty())
+            || self.mcp_servers.as_ref().is_some_and(|s| !s.is_empty());
+        if has_tools && self.clear_histroy_on_invoke.unwrap_or(false) {
+            issues.push(Issue::new(
+                &["tools", "clear_history_on_invoke"],
+                "the history is cleared on every invocation, so tool results never carry over to the next prompt",
+            ));
+        }
+
+        issues
+    }
+
     /// Build an [`Agent`] and return also the notification receiver.
     ///
     /// Creates an internal mpsc channel of size 100.
@@ -505,17 +1177,47 @@ impl AgentBuilder {
 
     /// Finalize all settings and produce an [`Agent`], or an error if required fields missing or invalid.
     pub async fn build(self) -> Result<Agent, AgentBuildError> {
-        let model_config = self.model_config;
+        if self.strict.unwrap_or(false) {
+            let issues = self.issues();
+            if !issues.is_empty() {
+                return Err(AgentBuildError::Invalid(issues));
+            }
+        } else {
+            self.validate();
+        }
+
+        let mut model_config = self.model_config;
         let model = model_config
             .model
             .clone()
+            .or_else(|| {
+                let router = self.model_router.as_ref()?;
+                Some(router.models().first()?.model.clone())
+            })
             .ok_or(AgentBuildError::ModelNotSet)?;
 
         let skill_template = Template::simple(SKILL_SYSTEM_PROMPT_TEMPLATE);
 
-        let mut system_prompt = self
-            .system_prompt
-            .unwrap_or_else(|| "You are a helpful agent.".into());
+        let library_prompt = match self.library_prompt {
+            Some((library, name)) => Some(
+                LibraryPrompt::resolve(library, &name)
+                    .ok_or(AgentBuildError::UnknownPrompt(name))?,
+            ),
+            None => None,
+        };
+        let mut system_prompt = match (
+            &library_prompt,
+            self.system_prompt,
+            &self.system_prompt_sections,
+        ) {
+            (Some(prompt), _, _) => prompt.content().to_string(),
+            (None, Some(prompt), _) => prompt,
+            (None, None, Some(sections)) => sections.build(),
+            (None, None, None) => "You are a helpful agent.".into(),
+        };
+        let experiment = self
+            .experiment
+            .map(|experiment| ExperimentState::new(experiment, system_prompt.clone()));
 
         let mut skills = load_skill_sources(&self.skill_paths, &self.skill_collection_paths)?;
         skills.extend(self.builtin_skills);
@@ -556,7 +1258,10 @@ impl AgentBuilder {
         let strip_thinking = self.strip_thinking.unwrap_or(true);
         let clear_histroy_on_invoke = self.clear_histroy_on_invoke.unwrap_or(false);
 
-        let flow = self.flow.unwrap_or(Flow::Default);
+        let flow = match self.flow_name {
+            Some(name) => registered_flow(&name).ok_or(AgentBuildError::UnknownFlow(name))?,
+            None => self.flow.unwrap_or(Flow::Default),
+        };
 
         let name = match self.name {
             Some(n) => n,
@@ -566,53 +1271,148 @@ impl AgentBuilder {
         let stream = self.stream.unwrap_or(false);
 
         let inference_client = self.client_config.build()?;
+        if model_config.num_ctx.is_none() && self.detect_num_ctx.unwrap_or(true) {
+            let detected = detect_num_ctx(&inference_client, &model).await;
+            model_config.num_ctx = match self.max_detected_num_ctx {
+                Some(max) => detected.map(|num_ctx| num_ctx.min(max)),
+                None => detected,
+            };
+        }
 
-        let response_format = self
-            .response_format
+        let mut response_format = self.response_format;
+        let response_schema = response_format
+            .resolve_named(self.schema_registry.as_deref())
+            .map_err(AgentBuildError::InvalidJsonSchema)?;
+        let response_format = response_format
             .resolve()
             .map_err(AgentBuildError::InvalidJsonSchema)?;
 
-        let response_format = match response_format {
-            Some(f) => Some(inference_client.structured_output_format(&f)?),
-            None => None,
+        let strategy = self.structured_output_strategy.unwrap_or_default();
+        let (response_format, prompted_schema) = match response_format {
+            None => (None, None),
+            Some(spec) => match (strategy, inference_client.structured_output_format(&spec)) {
+                (StructuredOutputStrategy::Native, format) => (Some(format?), None),
+                (StructuredOutputStrategy::Auto, Ok(format)) => (Some(format), None),
+                _ => (inference_client.json_mode_format(), Some(spec.schema)),
+            },
         };
+        if let Some(schema) = &prompted_schema {
+            system_prompt = format!("{system_prompt}\n\n{}", schema_instructions(schema));
+        }
+
+        let grammar = match (self.grammar, self.grammar_schema) {
+            (Some(grammar), _) => Some(grammar),
+            (None, Some(schema)) => {
+                Some(grammar_from_schema(&schema).map_err(AgentBuildError::InvalidJsonSchema)?)
+            }
+            (None, None) => None,
+        };
+        if grammar.is_some() {
+            if !matches!(
+                inference_client.get_config().provider,
+                Some(Provider::Ollama)
+            ) {
+                return Err(AgentBuildError::Unsupported(
+                    "Grammars are only supported by the Ollama provider".into(),
+                ));
+            }
+            if response_format.is_some() {
+                return Err(AgentBuildError::Unsupported(
+                    "A grammar cannot be combined with a response format".into(),
+                ));
+            }
+        }
 
-        Agent::try_new(
+        Agent::try_new(AgentParts {
             name,
-            &model,
+            model,
             inference_client,
-            &system_prompt,
-            tools,
+            system_prompt,
+            local_tools: tools,
             response_format,
-            self.stop_prompt,
-            self.stopword,
+            stop_prompt: self.stop_prompt,
+            stopword: self.stopword,
             strip_thinking,
-            model_config.temperature,
-            model_config.top_p,
-            model_config.presence_penalty,
-            model_config.frequency_penalty,
-            model_config.num_ctx,
-            model_config.repeat_last_n,
-            model_config.repeat_penalty,
-            model_config.seed,
-            model_config.stop,
-            model_config.num_predict,
+            temperature: model_config.temperature,
+            top_p: model_config.top_p,
+            presence_penalty: model_config.presence_penalty,
+            frequency_penalty: model_config.frequency_penalty,
+            num_ctx: model_config.num_ctx,
+            repeat_last_n: model_config.repeat_last_n,
+            repeat_penalty: model_config.repeat_penalty,
+            seed: model_config.seed,
+            stop: model_config.stop,
+            num_predict: model_config.num_predict,
             stream,
-            model_config.top_k,
-            model_config.min_p,
-            self.keep_alive,
-            self.notification_channel,
-            self.mcp_servers,
+            token_batching: self.token_batching,
+            top_k: model_config.top_k,
+            min_p: model_config.min_p,
+            extra_options: model_config.extra_options,
+            keep_alive: self.keep_alive,
+            notification_channel: self.notification_channel,
+            mcp_servers: self.mcp_servers,
             flow,
-            self.template,
+            template: self.template,
             skills,
-            self.max_iterations,
-            clear_histroy_on_invoke,
-        )
+            max_iterations: self.max_iterations,
+            clear_history_on_invoke: clear_histroy_on_invoke,
+            tool_audit_file: self.tool_audit_file,
+            tool_retry_policy: self.tool_retry_policy.unwrap_or_default(),
+            tool_choice: self.tool_choice,
+            parallel_tool_calls: self.parallel_tool_calls,
+            response_schema,
+            grammar,
+            embedding_model: self.embedding_model,
+            tool_reliability: self.tool_reliability,
+            history_dedup: self.history_dedup,
+            few_shot: self.few_shot,
+            context_providers: self.context_providers,
+            prompted_schema,
+            max_concurrency: self.max_concurrency,
+            model_router: self.model_router,
+            moderator: self.moderator,
+            dry_run: self.dry_run.unwrap_or_default(),
+            tool_simulator: self.tool_simulator.unwrap_or_default(),
+            registry: self.registry,
+            history_observers: self.history_observers,
+            auto_title: self.auto_title.unwrap_or_default(),
+            auto_extract_facts: self.auto_extract_facts.unwrap_or_default(),
+            on_iteration: self.on_iteration,
+            memory: self.memory,
+            tool_output_limits: self.tool_output_limits,
+            deferred_mcp_servers: self.deferred_mcp_servers,
+            mcp_refresh_interval: self.mcp_refresh_interval,
+            history_compression: self.history_compression,
+            library_prompt,
+            experiment,
+            unload_after_invocation: self.unload_after_invocation.unwrap_or_default(),
+        })
         .await
     }
 }
 
+/// How long the build waits for the provider to tell the context length.
+const NUM_CTX_DETECTION_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Context length of `model`, `None` if the provider does not tell or
+/// cannot be reached in time.
+async fn detect_num_ctx(client: &InferenceClient, model: &str) -> Option<u32> {
+    match runtime::timeout(NUM_CTX_DETECTION_TIMEOUT, client.context_length(model)).await {
+        Ok(Ok(length)) => {
+            tracing::debug!(model, ?length, "Detected the context length");
+            length
+        }
+        Ok(Err(e)) => {
+            tracing::debug!(model, error = %e, "Failed to detect the context length");
+            None
+        }
+        Err(_) => {
+            tracing::debug!(model, "Timed out detecting the context length");
+            None
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use std::sync::Arc;
@@ -621,7 +1421,8 @@ mod tests {
 
     use super::*;
     use crate::{
-        notifications::NotificationContent, Agent, AsyncToolFn, FlowFuture, Message, ToolBuilder,
+        notifications::NotificationContent, Agent, AgentError, AsyncToolFn, FlowFuture, Message,
+        ToolBuilder,
     };
 
     #[tokio::test]
@@ -671,6 +1472,23 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn single_system_prompt_section_can_be_overridden() {
+        let agent = AgentBuilder::default()
+            .set_model("m")
+            .set_system_prompt("You are a weather bot.")
+            .set_system_prompt_section(SystemPromptBuilder::OUTPUT_FORMAT, "Answer in JSON.")
+            .set_system_prompt_section(SystemPromptBuilder::OUTPUT_FORMAT, "Answer in YAML.")
+            .build()
+            .await
+            .unwrap();
+
+        assert_eq!(
+            agent.system_prompt,
+            "You are a weather bot.\n\nAnswer in YAML."
+        );
+    }
+
     #[tokio::test]
     async fn invalid_json_schema_errors() {
         let bad = "not json";
@@ -683,6 +1501,185 @@ mod tests {
         assert!(matches!(err, AgentBuildError::InvalidJsonSchema(_)));
     }
 
+    #[tokio::test]
+    async fn named_response_format_resolves_from_registry() {
+        let mut registry = SchemaRegistry::new();
+        registry
+            .register(
+                "weather",
+                2,
+                SchemaSpec::from_value(serde_json::json!({ "type": "object" })),
+            )
+            .unwrap();
+        let registry = Arc::new(registry);
+
+        let agent = AgentBuilder::default()
+            .set_model("m")
+            .set_schema_registry(registry.clone())
+            .set_response_format_named("weather")
+            .build()
+            .await
+            .unwrap();
+        assert_eq!(agent.response_schema.as_deref(), Some("weather@v2"));
+        assert!(agent.response_format.is_some());
+
+        let err = AgentBuilder::default()
+            .set_model("m")
+            .set_schema_registry(registry)
+            .set_response_format_named("weather@v1")
+            .build()
+            .await
+            .unwrap_err();
+        assert!(matches!(err, AgentBuildError::InvalidJsonSchema(_)));
+    }
+
+    #[tokio::test]
+    async fn grammar_is_derived_from_schema() {
+        let schema = serde_json::json!({ "type": "array", "items": { "type": "integer" } });
+        let agent = AgentBuilder::default()
+            .set_model("m")
+            .set_grammar_from_schema(schema.clone())
+            .build()
+            .await
+            .unwrap();
+        assert!(agent.grammar.unwrap().starts_with("root ::= "));
+
+        let err = AgentBuilder::default()
+            .set_model("m")
+            .set_grammar("root ::= [0-9]+")
+            .set_response_format_value(schema)
+            .build()
+            .await
+            .unwrap_err();
+        assert!(matches!(err, AgentBuildError::Unsupported(_)));
+    }
+
+    #[tokio::test]
+    async fn strict_mode_rejects_conflicting_settings() {
+        let builder = || {
+            let tool = ToolBuilder::new()
+                .function_name("noop")
+                .function_description("Does nothing")
+                .executor_fn(|_| async { Ok(String::new()) })
+                .build()
+                .unwrap();
+            AgentBuilder::default()
+                .set_model("m")
+                .add_tool(tool)
+                .set_clear_history_on_invocation(true)
+        };
+        let issues = builder().validate();
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].settings, ["tools", "clear_history_on_invoke"]);
+
+        let agent = builder().build().await;
+        assert!(agent.is_ok(), "issues only warn by default");
+
+        let err = builder().set_strict(true).build().await.unwrap_err();
+        assert!(matches!(err, AgentBuildError::Invalid(issues) if issues.len() == 1));
+    }
+
+    #[tokio::test]
+    async fn a_stopword_works_without_a_stop_prompt() {
+        let builder = AgentBuilder::default()
+            .set_model("m")
+            .set_stopword("</answer>")
+            .set_strict(true);
+        assert!(builder.validate().is_empty());
+        assert!(builder.build().await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn preset_sets_defaults_that_later_calls_override() {
+        let agent = AgentBuilder::default()
+            .set_model("qwen3:8b")
+            .preset(ModelPreset::Qwen3)
+            .set_num_ctx(4096)
+            .build()
+            .await
+            .unwrap();
+        assert_eq!(agent.temperature, Some(0.6));
+        assert_eq!(agent.top_k, Some(20));
+        assert_eq!(agent.num_ctx, Some(4096));
+        assert_eq!(agent.model, "qwen3:8b");
+        assert!(agent.strip_thinking);
+    }
+
+    #[tokio::test]
+    async fn num_ctx_defaults_to_the_context_length_of_the_model() {
+        use std::io::{Read, Write};
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let base_url = format!("http://{}", listener.local_addr().unwrap());
+        std::thread::spawn(move || {
+            for mut socket in listener.incoming().flatten() {
+                let _ = socket.read(&mut [0; 4096]);
+                let body = r#"{"model_info":{"general.architecture":"qwen3","qwen3.context_length":40960}}"#;
+                let response = format!(
+                    "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{body}",
+                    body.len()
+                );
+                let _ = socket.write_all(response.as_bytes());
+            }
+        });
+        let build = |builder: AgentBuilder| {
+            builder
+                .set_model("qwen3:8b")
+                .set_base_url(base_url.clone())
+                .build()
+        };
+
+        let detected = build(AgentBuilder::default()).await.unwrap();
+        assert_eq!(detected.num_ctx, Some(40960));
+        let capped = build(AgentBuilder::default().set_max_detected_num_ctx(16384))
+            .await
+            .unwrap();
+        assert_eq!(capped.num_ctx, Some(16384));
+        let set = build(AgentBuilder::default().set_num_ctx(8192))
+            .await
+            .unwrap();
+        assert_eq!(set.num_ctx, Some(8192));
+        // an imported config without num_ctx does not turn detection off
+        let imported = build(
+            AgentBuilder::default()
+                .set_detect_num_ctx(true)
+                .import_model_config(ModelConfig::default()),
+        )
+        .await
+        .unwrap();
+        assert_eq!(imported.num_ctx, Some(40960));
+        let imported = build(AgentBuilder::default().import_model_config(ModelConfig {
+            num_ctx: Some(4096),
+            ..Default::default()
+        }))
+        .await
+        .unwrap();
+        assert_eq!(imported.num_ctx, Some(4096));
+        let off = build(AgentBuilder::default().set_detect_num_ctx(false))
+            .await
+            .unwrap();
+        assert_eq!(off.num_ctx, None);
+    }
+
+    #[tokio::test]
+    async fn prompt_strategy_describes_schema_and_uses_json_mode() {
+        let agent = AgentBuilder::default()
+            .set_model("m")
+            .set_system_prompt("You are a test.")
+            .set_response_format_str(r#"{"type":"object","required":["city"]}"#)
+            .set_structured_output_strategy(StructuredOutputStrategy::Prompt)
+            .build()
+            .await
+            .unwrap();
+
+        assert_eq!(agent.response_format, Some(Value::from("json")));
+        assert!(agent.prompted_schema.is_some());
+        assert!(agent
+            .system_prompt
+            .starts_with("You are a test.\n\nRespond only with a JSON"));
+        assert!(agent.system_prompt.contains("\"required\""));
+    }
+
     #[tokio::test]
     async fn add_tools() {
         let weather_exec: AsyncToolFn = {
@@ -762,4 +1759,35 @@ mod tests {
         let resp = a.invoke_flow("abc").await.unwrap();
         assert_eq!(resp.content.unwrap(), "ECHO: abc");
     }
+
+    #[tokio::test]
+    async fn timed_out_flow_returns_partial_result() {
+        fn slow_flow<'a>(agent: &'a mut Agent, prompt: String) -> FlowFuture<'a> {
+            Box::pin(async move {
+                agent.history.push(Message::user(prompt));
+                agent.history.push(Message::assistant("first step done"));
+                agent.record_step("first", "done");
+                tokio::time::sleep(std::time::Duration::from_secs(60)).await;
+                Ok(Message::assistant("never"))
+            })
+        }
+
+        let mut agent = AgentBuilder::default()
+            .set_model("m")
+            .set_flow(slow_flow)
+            .build()
+            .await
+            .unwrap();
+        let err = agent
+            .invoke_flow_with_timeout("go", std::time::Duration::from_millis(20))
+            .await
+            .unwrap_err();
+
+        let AgentError::Aborted(partial) = err else {
+            panic!("expected an aborted flow");
+        };
+        assert_eq!(partial.reason, crate::AbortReason::TimedOut);
+        assert_eq!(partial.messages.len(), 2);
+        assert_eq!(partial.steps, [("first".to_string(), "done".to_string())]);
+    }
 }
@@ -0,0 +1,200 @@
+use std::{
+    fmt,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+use crate::services::{
+    mcp::connection::{McpConnection, McpServerStatus},
+    runtime::{self, Instant, TaskHandle},
+};
+
+/// Number of the invocation that spawned a task, `0` outside invocations,
+/// see [`Agent::await_forwarders`](crate::Agent::await_forwarders).
+pub(crate) type TaskScope = u64;
+
+/// A new [`TaskScope`] for an invocation.
+pub(crate) fn next_task_scope() -> TaskScope {
+    static NEXT: AtomicU64 = AtomicU64::new(1);
+    NEXT.fetch_add(1, Ordering::Relaxed)
+}
+
+/// Background resources owned by an agent: spawned notification forwarders
+/// and running MCP clients. Shared between clones of the same agent; MCP
+/// clients are stopped when the last clone is dropped.
+#[derive(Clone, Default)]
+pub(crate) struct BackgroundTasks {
+    inner: Arc<Mutex<BackgroundTasksInner>>,
+}
+
+#[derive(Default)]
+struct BackgroundTasksInner {
+    tasks: Vec<(TaskScope, TaskHandle)>,
+    mcp_clients: Vec<Arc<McpConnection>>,
+}
+
+impl Drop for BackgroundTasksInner {
+    fn drop(&mut self) {
+        for connection in &self.mcp_clients {
+            connection.close_now();
+        }
+    }
+}
+
+impl BackgroundTasks {
+    pub(crate) fn track_task(&self, scope: TaskScope, task: TaskHandle) {
+        let mut inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        // forget tasks that already finished, so long-lived agents don't pile up handles
+        inner.tasks.retain(|(_, task)| !task.is_finished());
+        inner.tasks.push((scope, task));
+    }
+
+    pub(crate) fn track_mcp_client(&self, connection: Arc<McpConnection>) {
+        let mut inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        inner.mcp_clients.push(connection);
+    }
+
+    pub(crate) fn mcp_connections(&self) -> Vec<Arc<McpConnection>> {
+        let inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        inner.mcp_clients.clone()
+    }
+
+    pub(crate) async fn mcp_status(&self) -> Vec<McpServerStatus> {
+        let connections = self.mcp_connections();
+        let mut status = Vec::with_capacity(connections.len());
+        for connection in connections {
+            status.push(connection.status().await);
+        }
+        status
+    }
+
+    /// Stop all MCP clients (terminating stdio child processes).
+    pub(crate) async fn shutdown_mcp_clients(&self, timeout: Duration) {
+        let clients = {
+            let mut inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+            std::mem::take(&mut inner.mcp_clients)
+        };
+
+        for connection in clients {
+            connection.shutdown(timeout).await;
+        }
+    }
+
+    /// Take the tasks of `scope`, or all tasks without one.
+    fn take_tasks(&self, scope: Option<TaskScope>) -> Vec<(TaskScope, TaskHandle)> {
+        let mut inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        let (taken, kept) = std::mem::take(&mut inner.tasks)
+            .into_iter()
+            .partition(|(task_scope, _)| scope.map_or(true, |scope| scope == *task_scope));
+        inner.tasks = kept;
+        taken
+    }
+
+    /// Wait for the tasks of `scope` (all tasks without one) to finish,
+    /// including tasks tracked while waiting. Returns `false` if some are
+    /// still running after `timeout`; those stay tracked.
+    pub(crate) async fn await_tasks(&self, scope: Option<TaskScope>, timeout: Duration) -> bool {
+        let deadline = Instant::now() + timeout;
+        loop {
+            let tasks = self.take_tasks(scope);
+            if tasks.is_empty() {
+                return true;
+            }
+
+            let mut pending = Vec::new();
+            for (task_scope, mut task) in tasks {
+                if runtime::timeout_at(deadline, &mut task).await.is_err() {
+                    pending.push((task_scope, task));
+                }
+            }
+            if !pending.is_empty() {
+                let mut inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+                inner.tasks.extend(pending);
+                return false;
+            }
+        }
+    }
+
+    /// Abort all tracked tasks.
+    pub(crate) fn abort_tasks(&self) {
+        for (_, task) in self.take_tasks(None) {
+            task.abort();
+        }
+    }
+
+    /// Wait for tracked tasks to finish; tasks still running after `timeout` are aborted.
+    pub(crate) async fn join_tasks(&self, timeout: Duration) {
+        if !self.await_tasks(None, timeout).await {
+            tracing::warn!("Background tasks did not finish in time, aborting");
+            self.abort_tasks();
+        }
+    }
+}
+
+impl fmt::Debug for BackgroundTasks {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        f.debug_struct("BackgroundTasks")
+            .field("tasks", &inner.tasks.len())
+            .field("mcp_clients", &inner.mcp_clients.len())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn join_aborts_tasks_that_do_not_finish() {
+        let background = BackgroundTasks::default();
+        let (sender, mut receiver) = tokio::sync::mpsc::channel::<()>(1);
+
+        background.track_task(0, runtime::spawn(async {}));
+        background.track_task(
+            0,
+            runtime::spawn(async move {
+                receiver.recv().await;
+            }),
+        );
+
+        background.join_tasks(Duration::from_millis(20)).await;
+
+        // aborting the stuck task dropped its receiver
+        let closed = runtime::timeout(Duration::from_secs(1), sender.closed()).await;
+        assert!(closed.is_ok());
+        assert_eq!(
+            format!("{background:?}"),
+            "BackgroundTasks { tasks: 0, mcp_clients: 0 }"
+        );
+    }
+
+    #[tokio::test]
+    async fn await_keeps_tasks_that_are_still_running() {
+        let background = BackgroundTasks::default();
+        let (sender, mut receiver) = tokio::sync::mpsc::channel::<()>(1);
+        background.track_task(
+            0,
+            runtime::spawn(async move {
+                receiver.recv().await;
+            }),
+        );
+
+        assert!(
+            !background
+                .await_tasks(None, Duration::from_millis(20))
+                .await
+        );
+        assert_eq!(
+            format!("{background:?}"),
+            "BackgroundTasks { tasks: 1, mcp_clients: 0 }"
+        );
+
+        // the task finishes once its source closes
+        drop(sender);
+        assert!(background.await_tasks(None, Duration::from_secs(1)).await);
+    }
+}
@@ -0,0 +1,197 @@
+use std::{
+    fmt,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+use serde::Serialize;
+use tokio::sync::Notify;
+
+use crate::{
+    services::runtime::{self, Instant},
+    Message,
+};
+
+/// Named handle for aborting flows, see [`Agent::invoke_flow_in`](crate::Agent::invoke_flow_in).
+///
+/// Clones share the same state, so one clone can cancel flows started with
+/// another, e.g. from a "stop" button handler. The name ends up in the
+/// [`PartialResult`] of aborted flows.
+#[derive(Clone)]
+pub struct CancelScope {
+    name: String,
+    deadline: Option<Instant>,
+    state: Arc<CancelState>,
+}
+
+#[derive(Default)]
+struct CancelState {
+    cancelled: AtomicBool,
+    notify: Notify,
+}
+
+impl CancelScope {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            deadline: None,
+            state: Arc::default(),
+        }
+    }
+
+    /// Abort flows in this scope once `timeout` has passed from now.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.deadline = Some(Instant::now() + timeout);
+        self
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Abort all flows running in this scope.
+    pub fn cancel(&self) {
+        self.state.cancelled.store(true, Ordering::SeqCst);
+        self.state.notify.notify_waiters();
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.state.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// Completes when the scope is cancelled or its deadline passes.
+    pub(crate) async fn aborted(&self) -> AbortReason {
+        let cancelled = async {
+            loop {
+                let notified = self.state.notify.notified();
+                if self.is_cancelled() {
+                    return AbortReason::Cancelled;
+                }
+                notified.await;
+            }
+        };
+        match self.deadline {
+            Some(deadline) => tokio::select! {
+                reason = cancelled => reason,
+                _ = runtime::sleep_until(deadline) => AbortReason::TimedOut,
+            },
+            None => cancelled.await,
+        }
+    }
+}
+
+impl fmt::Debug for CancelScope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CancelScope")
+            .field("name", &self.name)
+            .field("deadline", &self.deadline)
+            .field("cancelled", &self.is_cancelled())
+            .finish()
+    }
+}
+
+/// Why a flow was aborted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AbortReason {
+    Cancelled,
+    TimedOut,
+}
+
+/// What a flow achieved before it was aborted, returned in
+/// [`AgentError::Aborted`](crate::AgentError::Aborted).
+#[derive(Debug, Clone, Serialize)]
+pub struct PartialResult {
+    /// Name of the [`CancelScope`].
+    pub scope: String,
+    pub reason: AbortReason,
+    /// Messages the flow added to the history before the abort.
+    pub messages: Vec<Message>,
+    /// Content of a streamed response that was cut off.
+    pub partial_content: Option<String>,
+    /// Completed steps as (step, result) pairs, for flows that report them
+    /// with [`Agent::record_step`](crate::Agent::record_step) (e.g. plan and execute).
+    pub steps: Vec<(String, String)>,
+}
+
+#[derive(Debug, Default)]
+struct ProgressState {
+    partial_content: Option<String>,
+    steps: Vec<(String, String)>,
+}
+
+/// Progress of the running flow, kept so it can be returned on abort.
+///
+/// Cloning an agent starts a fresh record: clones running in parallel (plan
+/// executors, best-of-n candidates) would otherwise mix their content.
+#[derive(Debug, Default)]
+pub(crate) struct FlowProgress {
+    state: Arc<Mutex<ProgressState>>,
+}
+
+impl Clone for FlowProgress {
+    fn clone(&self) -> Self {
+        Self::default()
+    }
+}
+
+impl FlowProgress {
+    /// Handle writing to the same record, for the streaming invocation.
+    pub(crate) fn handle(&self) -> Self {
+        Self {
+            state: self.state.clone(),
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, ProgressState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub(crate) fn reset(&self) {
+        *self.lock() = ProgressState::default();
+    }
+
+    pub(crate) fn push_token(&self, token: &str) {
+        self.lock()
+            .partial_content
+            .get_or_insert_with(String::new)
+            .push_str(token);
+    }
+
+    /// The streamed response completed or was stored in the history.
+    pub(crate) fn clear_partial(&self) {
+        self.lock().partial_content = None;
+    }
+
+    pub(crate) fn record_step(&self, step: String, result: String) {
+        self.lock().steps.push((step, result));
+    }
+
+    pub(crate) fn take(&self) -> (Option<String>, Vec<(String, String)>) {
+        let mut state = self.lock();
+        (
+            state.partial_content.take(),
+            std::mem::take(&mut state.steps),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn cancel_and_deadline_abort_the_scope() {
+        let scope = CancelScope::new("ingest");
+        let handle = scope.clone();
+        tokio::spawn(async move { handle.cancel() });
+        assert_eq!(scope.aborted().await, AbortReason::Cancelled);
+
+        let scope = CancelScope::new("slow").with_timeout(Duration::from_millis(10));
+        assert_eq!(scope.aborted().await, AbortReason::TimedOut);
+        assert!(!scope.is_cancelled());
+    }
+}
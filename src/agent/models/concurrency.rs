@@ -0,0 +1,87 @@
+use std::{future::Future, sync::Arc};
+
+use tokio::sync::Semaphore;
+
+tokio::task_local! {
+    /// Limits a slot is held of by the running task, see
+    /// [`ConcurrencyLimit::run`].
+    static HELD: Vec<usize>;
+}
+
+/// Bound on the model requests and tool calls an agent runs at once, shared
+/// between clones of the agent (best-of-n candidates, plan executors, eval
+/// cases, ...). See
+/// [`AgentBuilder::set_max_concurrency`](crate::AgentBuilder::set_max_concurrency).
+///
+/// Work started inside a slot runs in it: a tool that invokes a clone of its
+/// agent (or a sub-agent sharing the limit) does not wait for a second slot,
+/// which with a limit of one would never free up.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct ConcurrencyLimit {
+    semaphore: Option<Arc<Semaphore>>,
+}
+
+impl ConcurrencyLimit {
+    pub(crate) fn new(max: Option<usize>) -> Self {
+        Self {
+            semaphore: max.map(|max| Arc::new(Semaphore::new(max.max(1)))),
+        }
+    }
+
+    /// Run `future` in a free slot, waiting for one first. Without a limit,
+    /// or inside a slot of this limit already, it runs right away.
+    pub(crate) async fn run<F: Future>(&self, future: F) -> F::Output {
+        let Some(semaphore) = &self.semaphore else {
+            return future.await;
+        };
+        let id = Arc::as_ptr(semaphore) as usize;
+        let mut held = HELD.try_with(Clone::clone).unwrap_or_default();
+        if held.contains(&id) {
+            return future.await;
+        }
+
+        // the semaphore is never closed
+        let _permit = semaphore.acquire().await;
+        held.push(id);
+        HELD.scope(held, future).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn clones_share_the_limit() {
+        let limit = ConcurrencyLimit::new(Some(1));
+        let clone = limit.clone();
+        let (release, released) = tokio::sync::oneshot::channel::<()>();
+
+        let first = tokio::spawn({
+            let limit = limit.clone();
+            async move { limit.run(released).await }
+        });
+        tokio::task::yield_now().await;
+        let waiting = tokio::time::timeout(Duration::from_millis(20), clone.run(async {})).await;
+        assert!(waiting.is_err(), "second request should wait for the first");
+
+        release.send(()).unwrap();
+        first.await.unwrap().unwrap();
+        clone.run(async {}).await;
+        ConcurrencyLimit::default().run(async {}).await;
+    }
+
+    #[tokio::test]
+    async fn nested_work_runs_in_the_outer_slot() {
+        let limit = ConcurrencyLimit::new(Some(1));
+        let nested = limit.clone();
+        let result = tokio::time::timeout(
+            Duration::from_secs(1),
+            limit.run(async move { nested.run(async { 42 }).await }),
+        )
+        .await;
+        assert_eq!(result, Ok(42));
+    }
+}
@@ -1,10 +1,14 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
 use crate::{
     services::llm::{InferenceOptions, SchemaSpec},
     templates::Template,
-    McpServerType, Tool,
+    McpConnect, McpServerType, TokenBatching, Tool, ToolChoice,
 };
 
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
 pub struct ModelConfig {
     /// Identifier or name of the model to use.
     pub model: Option<String>,
@@ -32,6 +36,9 @@ pub struct ModelConfig {
     pub top_k: Option<u32>,
     /// Minimum probability threshold for token acceptance.
     pub min_p: Option<f32>,
+    /// Provider-specific parameters, see [`InferenceOptions::extra_options`].
+    #[serde(skip_serializing_if = "Map::is_empty")]
+    pub extra_options: Map<String, Value>,
 }
 
 impl From<&ModelConfig> for InferenceOptions {
@@ -50,6 +57,7 @@ impl From<&ModelConfig> for InferenceOptions {
             min_p: config.min_p,
             presence_penalty: config.presence_penalty,
             frequency_penalty: config.frequency_penalty,
+            extra_options: config.extra_options.clone(),
         }
     }
 }
@@ -62,6 +70,10 @@ pub struct PromptConfig {
     pub system_prompt: Option<String>,
     /// Set of local tools the agent can invoke.
     pub tools: Option<Vec<Tool>>,
+    /// Whether and which tool the model has to call.
+    pub tool_choice: Option<ToolChoice>,
+    /// Whether several tool calls may be requested and executed at once.
+    pub parallel_tool_calls: Option<bool>,
     /// The normalized, typed form used by Agent and provider adapters
     pub response_format: Option<SchemaSpec>,
     /// Optional raw JSON string the user gave; parsed and merged at build
@@ -72,6 +84,9 @@ pub struct PromptConfig {
     pub pending_strict: Option<bool>,
     /// External MCP servers providing additional tools.
     pub mcp_servers: Option<Vec<McpServerType>>,
+    /// Servers of `mcp_servers` that are connected after the build, see
+    /// [`McpConnect`].
+    pub deferred_mcp_servers: Option<Vec<(McpServerType, McpConnect)>>,
     /// Prompt injected at the start of tool-call branches.
     pub stop_prompt: Option<String>,
     /// Stopword used to detect end of model output.
@@ -84,4 +99,6 @@ pub struct PromptConfig {
     pub clear_histroy_on_invoke: Option<bool>,
     /// Enable streaming responses (token-by-token).
     pub stream: bool,
+    /// Optional batching of streamed token notifications.
+    pub token_batching: Option<TokenBatching>,
 }
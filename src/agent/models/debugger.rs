@@ -0,0 +1,322 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use serde_json::Value;
+use tokio::sync::{oneshot, watch};
+
+use crate::services::llm::models::message::Message;
+
+/// Where a flow paused, see [`Agent::debug`](crate::Agent::debug).
+#[derive(Debug, Clone, PartialEq)]
+pub enum DebugPoint {
+    /// The model answered in the given iteration of the flow (from 0).
+    ModelResponse(usize),
+    /// A tool called in the given iteration returned, with the tool name.
+    ToolResult(usize, String),
+    /// A point of a custom flow, see [`Agent::debug_pause`](crate::Agent::debug_pause).
+    Custom(String),
+}
+
+/// The agent at one pause of a debugged flow.
+#[derive(Debug, Clone)]
+pub struct DebugSnapshot {
+    /// Number of the pause, counting from 0 since the debugger was attached.
+    pub step: usize,
+    /// Which clone of the agent paused. The agent the debugger was attached
+    /// to is 0, every clone of it gets its own number.
+    pub session: usize,
+    /// Name of the paused agent.
+    pub agent: String,
+    pub point: DebugPoint,
+    pub history: Vec<Message>,
+    pub state: HashMap<String, Value>,
+}
+
+#[derive(Debug, Default)]
+struct DebugState {
+    /// Flows waiting right now, by the step number of their pause.
+    pending: BTreeMap<usize, (DebugSnapshot, oneshot::Sender<()>)>,
+    snapshots: Vec<DebugSnapshot>,
+    /// Steps requested while no flow was paused.
+    credits: usize,
+    resumed: bool,
+}
+
+#[derive(Debug)]
+struct Shared {
+    state: Mutex<DebugState>,
+    sessions: AtomicUsize,
+}
+
+impl Shared {
+    fn lock(&self) -> std::sync::MutexGuard<'_, DebugState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Agent side of a debugger. Clones of the agent share the debugger, but
+/// every clone pauses as its own session, so stepping one never lets
+/// another continue.
+#[derive(Debug)]
+pub(crate) struct Debugger {
+    shared: Arc<Shared>,
+    changed: Arc<watch::Sender<()>>,
+    session: usize,
+}
+
+impl Clone for Debugger {
+    fn clone(&self) -> Self {
+        Self {
+            shared: self.shared.clone(),
+            changed: self.changed.clone(),
+            session: self.shared.sessions.fetch_add(1, Ordering::Relaxed),
+        }
+    }
+}
+
+impl Debugger {
+    pub(crate) fn new() -> (Self, DebugHandle) {
+        let (changed, receiver) = watch::channel(());
+        let shared = Arc::new(Shared {
+            state: Mutex::default(),
+            sessions: AtomicUsize::new(1),
+        });
+        let debugger = Self {
+            shared: shared.clone(),
+            changed: Arc::new(changed),
+            session: 0,
+        };
+        let handle = DebugHandle {
+            shared,
+            changed: receiver,
+            seen: None,
+        };
+        (debugger, handle)
+    }
+
+    /// Publish the snapshot and wait until [`DebugHandle`] lets this pause
+    /// continue. Returns right away once the handle resumed the flows.
+    pub(crate) async fn pause(
+        &self,
+        agent: &str,
+        point: DebugPoint,
+        history: &[Message],
+        state: &HashMap<String, Value>,
+    ) {
+        let (gate, step) = {
+            let mut debug = self.shared.lock();
+            if debug.resumed {
+                return;
+            }
+            let snapshot = DebugSnapshot {
+                step: debug.snapshots.len(),
+                session: self.session,
+                agent: agent.to_string(),
+                point,
+                history: history.to_vec(),
+                state: state.clone(),
+            };
+            debug.snapshots.push(snapshot.clone());
+            if debug.credits > 0 {
+                debug.credits -= 1;
+                return;
+            }
+            let (sender, gate) = oneshot::channel();
+            let step = snapshot.step;
+            debug.pending.insert(step, (snapshot, sender));
+            (gate, step)
+        };
+        tracing::debug!(agent, step, session = self.session, "Flow paused");
+
+        self.changed.send_replace(());
+        // an error means the debugger was dropped with the handle
+        let _ = gate.await;
+        self.shared.lock().pending.remove(&step);
+        self.changed.send_replace(());
+    }
+}
+
+/// Controls flows paused by [`Agent::debug`](crate::Agent::debug).
+///
+/// A flow stops after every model response and tool result and waits for
+/// [`step`](DebugHandle::step). Every pause is kept as a [`DebugSnapshot`],
+/// so earlier states of the history can be inspected after the fact. When
+/// clones of the agent run at the same time, each pause waits on its own and
+/// [`step_at`](DebugHandle::step_at) picks which one continues.
+#[derive(Debug)]
+pub struct DebugHandle {
+    shared: Arc<Shared>,
+    changed: watch::Receiver<()>,
+    seen: Option<usize>,
+}
+
+impl DebugHandle {
+    /// Let a paused flow continue to its next pause: the one of the pause
+    /// last returned by [`next_pause`](Self::next_pause) if it still waits,
+    /// otherwise the longest waiting one. Without a paused flow, the next
+    /// pause continues right away.
+    pub fn step(&self) {
+        let mut debug = self.shared.lock();
+        let step = self
+            .seen
+            .filter(|seen| debug.pending.contains_key(seen))
+            .or_else(|| debug.pending.keys().next().copied());
+        match step {
+            Some(step) => {
+                if let Some((_, gate)) = debug.pending.remove(&step) {
+                    let _ = gate.send(());
+                }
+            }
+            None => debug.credits += 1,
+        }
+    }
+
+    /// Let the flow paused at the pause with the given
+    /// [`DebugSnapshot::step`] number continue. Returns `false` if no flow
+    /// waits there.
+    pub fn step_at(&self, step: usize) -> bool {
+        match self.shared.lock().pending.remove(&step) {
+            Some((_, gate)) => gate.send(()).is_ok(),
+            None => false,
+        }
+    }
+
+    /// Let every flow run to the end without pausing again.
+    pub fn resume(&self) {
+        let mut debug = self.shared.lock();
+        debug.resumed = true;
+        for (_, (_, gate)) in std::mem::take(&mut debug.pending) {
+            let _ = gate.send(());
+        }
+    }
+
+    /// The longest waiting pause, if a flow is paused.
+    pub fn paused(&self) -> Option<DebugSnapshot> {
+        self.shared
+            .lock()
+            .pending
+            .values()
+            .next()
+            .map(|(snapshot, _)| snapshot.clone())
+    }
+
+    /// Wait until a flow pauses at a point not returned before. Returns
+    /// `None` once the agent and all its clones are dropped.
+    pub async fn next_pause(&mut self) -> Option<DebugSnapshot> {
+        loop {
+            self.changed.borrow_and_update();
+            let next = self
+                .shared
+                .lock()
+                .pending
+                .values()
+                .map(|(snapshot, _)| snapshot)
+                .find(|snapshot| self.seen < Some(snapshot.step))
+                .cloned();
+            if let Some(snapshot) = next {
+                self.seen = Some(snapshot.step);
+                return Some(snapshot);
+            }
+            self.changed.changed().await.ok()?;
+        }
+    }
+
+    /// Every pause so far, oldest first.
+    pub fn snapshots(&self) -> Vec<DebugSnapshot> {
+        self.shared.lock().snapshots.clone()
+    }
+
+    /// The pause with the given [`DebugSnapshot::step`] number.
+    pub fn snapshot(&self, step: usize) -> Option<DebugSnapshot> {
+        self.shared.lock().snapshots.get(step).cloned()
+    }
+}
+
+impl Drop for DebugHandle {
+    /// Nothing could step a flow any more, so let them all run to the end.
+    fn drop(&mut self) {
+        self.resume();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::{testing::FlowTestHarness, AgentBuilder, Role};
+
+    #[tokio::test]
+    async fn default_flow_pauses_at_every_step() {
+        let mut harness = FlowTestHarness::new(AgentBuilder::default().set_model("test"))
+            .await
+            .unwrap()
+            .reply_with_tool_call("search", json!({ "query": "reagent" }))
+            .expect_tool_call("search", "A Rust agent library")
+            .reply("Reagent is a Rust agent library.");
+        let mut handle = harness.agent_mut().debug();
+
+        let driver = async {
+            let mut points = Vec::new();
+            while points.len() < 3 {
+                let snapshot = handle.next_pause().await.unwrap();
+                points.push(snapshot.point);
+                handle.step();
+            }
+            points
+        };
+        let (reply, points) = tokio::join!(harness.run("What is reagent?"), driver);
+
+        assert!(reply.is_ok());
+        assert_eq!(
+            points,
+            vec![
+                DebugPoint::ModelResponse(0),
+                DebugPoint::ToolResult(0, "search".into()),
+                DebugPoint::ModelResponse(1),
+            ]
+        );
+        let tool_step = handle.snapshot(1).unwrap();
+        assert_eq!(tool_step.history.last().unwrap().role, Role::Tool);
+        assert_eq!(handle.snapshots().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn clones_pause_on_their_own() {
+        let mut harness = FlowTestHarness::new(AgentBuilder::default().set_model("test"))
+            .await
+            .unwrap();
+        let mut handle = harness.agent_mut().debug();
+        let pause = |point: &str| {
+            let agent = harness.agent().clone();
+            let point = DebugPoint::Custom(point.into());
+            tokio::spawn(async move { agent.debug_pause(point).await })
+        };
+        let first = pause("first");
+        let second = pause("second");
+
+        let mut pauses = [
+            handle.next_pause().await.unwrap(),
+            handle.next_pause().await.unwrap(),
+        ];
+        assert_ne!(pauses[0].session, pauses[1].session);
+        if pauses[0].point != DebugPoint::Custom("first".into()) {
+            pauses.swap(0, 1);
+        }
+        let (waiting, stepped) = (&pauses[0], &pauses[1]);
+
+        assert!(handle.step_at(stepped.step));
+        second.await.unwrap();
+        assert!(!first.is_finished());
+        assert_eq!(handle.paused().unwrap().step, waiting.step);
+
+        handle.step();
+        first.await.unwrap();
+        assert!(handle.paused().is_none());
+    }
+}
@@ -1,4 +1,8 @@
 use crate::{
+    agent::models::{
+        cancel::{AbortReason, PartialResult},
+        handle::Priority,
+    },
     services::{llm::models::errors::InferenceClientError, mcp::error::McpIntegrationError},
     skills::SkillLoadError,
     templates::LoadTemplateError,
@@ -24,6 +28,15 @@ pub enum AgentError {
     Unsupported(String),
     /// Invocation error, usually while building request shape during invocation.
     InvocationError(InvocationError),
+    /// The flow was cancelled or timed out, see
+    /// [`Agent::invoke_flow_in`](crate::Agent::invoke_flow_in).
+    Aborted(Box<PartialResult>),
+    /// A request on an [`AgentHandle`](crate::AgentHandle) did not start
+    /// within the queue timeout of its priority.
+    QueueTimeout(Priority),
+    /// The final reply was blocked by the
+    /// [`Moderator`](crate::Moderator), with its reasons.
+    Blocked(Vec<String>),
 }
 
 impl std::fmt::Display for AgentError {
@@ -37,6 +50,19 @@ impl std::fmt::Display for AgentError {
             AgentError::Deserialization(e) => write!(f, "Deserialization error: {e}"),
             AgentError::Unsupported(e) => write!(f, "Unsupported: {e}"),
             AgentError::InvocationError(e) => write!(f, "Invocation error: {e}"),
+            AgentError::Aborted(partial) => match partial.reason {
+                AbortReason::Cancelled => write!(f, "Flow cancelled in scope `{}`", partial.scope),
+                AbortReason::TimedOut => write!(f, "Flow timed out in scope `{}`", partial.scope),
+            },
+            AgentError::QueueTimeout(priority) => {
+                write!(
+                    f,
+                    "Request with {priority:?} priority timed out in the queue"
+                )
+            }
+            AgentError::Blocked(reasons) => {
+                write!(f, "Reply blocked by moderation: {}", reasons.join(", "))
+            }
         }
     }
 }
@@ -52,6 +78,9 @@ impl std::error::Error for AgentError {
             AgentError::Deserialization(e) => Some(e),
             AgentError::Unsupported(_) => None,
             AgentError::InvocationError(e) => Some(e),
+            AgentError::Aborted(_) => None,
+            AgentError::QueueTimeout(_) => None,
+            AgentError::Blocked(_) => None,
         }
     }
 }
@@ -107,6 +136,18 @@ pub enum AgentBuildError {
     Skill(SkillLoadError),
     /// Failure while loading a prompt template from disk.
     TemplateLoad(LoadTemplateError),
+    /// Settings that do not work together, reported in strict mode. See
+    /// [`AgentBuilder::validate`](crate::AgentBuilder::validate).
+    Invalid(Vec<Issue>),
+    /// The runtime of a [`blocking::Agent`](crate::blocking::Agent) could not be started.
+    Runtime(std::io::Error),
+    /// No flow is registered under the name given to
+    /// [`AgentBuilder::set_flow_named`](crate::AgentBuilder::set_flow_named).
+    UnknownFlow(String),
+    /// The prompt given to
+    /// [`AgentBuilder::set_library_prompt`](crate::AgentBuilder::set_library_prompt)
+    /// has no active version.
+    UnknownPrompt(String),
 }
 
 impl std::fmt::Display for AgentBuildError {
@@ -125,6 +166,18 @@ impl std::fmt::Display for AgentBuildError {
             AgentBuildError::ToolBuild(e) => write!(f, "Tool build error: {e}"),
             AgentBuildError::Skill(e) => write!(f, "Skill error: {e}"),
             AgentBuildError::TemplateLoad(e) => write!(f, "Template load error: {e}"),
+            AgentBuildError::Invalid(issues) => {
+                write!(f, "Invalid configuration:")?;
+                for issue in issues {
+                    write!(f, "\n- {issue}")?;
+                }
+                Ok(())
+            }
+            AgentBuildError::Runtime(e) => write!(f, "Failed to start the runtime: {e}"),
+            AgentBuildError::UnknownFlow(name) => write!(f, "No flow registered as `{name}`"),
+            AgentBuildError::UnknownPrompt(name) => {
+                write!(f, "No active version of prompt `{name}`")
+            }
         }
     }
 }
@@ -141,10 +194,39 @@ impl std::error::Error for AgentBuildError {
             AgentBuildError::ToolBuild(e) => Some(e),
             AgentBuildError::Skill(e) => Some(e),
             AgentBuildError::TemplateLoad(e) => Some(e),
+            AgentBuildError::Invalid(_) => None,
+            AgentBuildError::Runtime(e) => Some(e),
+            AgentBuildError::UnknownFlow(_) => None,
+            AgentBuildError::UnknownPrompt(_) => None,
+        }
+    }
+}
+
+/// A builder setting, or combination of settings, that does not do what it
+/// looks like it does.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Issue {
+    /// Builder settings involved, e.g. `["stream", "response_format"]`.
+    pub settings: Vec<&'static str>,
+    /// What is wrong and how to fix it.
+    pub message: String,
+}
+
+impl Issue {
+    pub(crate) fn new(settings: &[&'static str], message: impl Into<String>) -> Self {
+        Self {
+            settings: settings.to_vec(),
+            message: message.into(),
         }
     }
 }
 
+impl std::fmt::Display for Issue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.settings.join(" + "), self.message)
+    }
+}
+
 impl From<InferenceClientError> for AgentBuildError {
     fn from(err: InferenceClientError) -> Self {
         AgentBuildError::InferenceClient(err)
@@ -0,0 +1,99 @@
+use std::{collections::HashMap, future::Future, pin::Pin};
+
+use tokio::sync::mpsc::{Receiver, UnboundedReceiver};
+
+use crate::{AgentError, Message};
+
+/// Input for a single invocation, produced by an [`EventSource`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AgentEvent {
+    /// Passed to [`Agent::invoke_flow`](crate::Agent::invoke_flow).
+    Prompt(String),
+    /// Passed to [`Agent::invoke_flow_with_template`](crate::Agent::invoke_flow_with_template).
+    TemplateData(HashMap<String, String>),
+}
+
+impl From<String> for AgentEvent {
+    fn from(prompt: String) -> Self {
+        AgentEvent::Prompt(prompt)
+    }
+}
+
+impl From<&str> for AgentEvent {
+    fn from(prompt: &str) -> Self {
+        AgentEvent::Prompt(prompt.to_string())
+    }
+}
+
+impl From<HashMap<String, String>> for AgentEvent {
+    fn from(data: HashMap<String, String>) -> Self {
+        AgentEvent::TemplateData(data)
+    }
+}
+
+pub type EventFuture<'a> = Pin<Box<dyn Future<Output = Option<AgentEvent>> + Send + 'a>>;
+
+/// A stream of invocations for [`Agent::run_from`](crate::Agent::run_from),
+/// e.g. a channel, a file watcher or a message queue consumer.
+///
+/// The next event is only requested once the previous one has been handled,
+/// so a slow agent applies backpressure to the source. Channel receivers
+/// implement it for anything convertible into an [`AgentEvent`].
+pub trait EventSource: Send {
+    /// Wait for the next event. `None` ends the run.
+    fn next_event(&mut self) -> EventFuture<'_>;
+
+    /// Called with the outcome of every event, e.g. to acknowledge or
+    /// requeue a queue message. Does nothing by default.
+    fn on_result(&mut self, _event: &AgentEvent, _result: &Result<Message, AgentError>) {}
+}
+
+impl<T> EventSource for Receiver<T>
+where
+    T: Into<AgentEvent> + Send,
+{
+    fn next_event(&mut self) -> EventFuture<'_> {
+        Box::pin(async move { self.recv().await.map(Into::into) })
+    }
+}
+
+impl<T> EventSource for UnboundedReceiver<T>
+where
+    T: Into<AgentEvent> + Send,
+{
+    fn next_event(&mut self) -> EventFuture<'_> {
+        Box::pin(async move { self.recv().await.map(Into::into) })
+    }
+}
+
+/// Counts of a finished [`Agent::run_from`](crate::Agent::run_from) loop.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RunSummary {
+    /// Events taken from the source.
+    pub processed: usize,
+    /// Events whose invocation returned an error.
+    pub failed: usize,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn channels_yield_events_until_closed() {
+        let (sender, mut receiver) = tokio::sync::mpsc::channel::<&str>(2);
+        sender.send("first").await.unwrap();
+        sender.send("second").await.unwrap();
+        drop(sender);
+
+        assert_eq!(
+            receiver.next_event().await,
+            Some(AgentEvent::Prompt("first".into()))
+        );
+        assert_eq!(
+            receiver.next_event().await,
+            Some(AgentEvent::Prompt("second".into()))
+        );
+        assert_eq!(receiver.next_event().await, None);
+    }
+}
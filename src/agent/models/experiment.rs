@@ -0,0 +1,458 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use serde::{Deserialize, Serialize};
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+use uuid::Uuid;
+
+use crate::{Agent, InvocationSummary, Message, Role, TokenUsage};
+
+/// One prompt of a [`PromptExperiment`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PromptVariant {
+    pub id: String,
+    /// Share of the invocations the variant gets, relative to the weights
+    /// of the other variants.
+    pub weight: u32,
+    /// Replaces the system prompt the agent was built with.
+    pub system_prompt: String,
+}
+
+/// Outcomes of the invocations made with a variant, see
+/// [`PromptExperiment::results`].
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct VariantStats {
+    pub variant: String,
+    pub invocations: u64,
+    pub failures: u64,
+    /// Tokens used by all invocations.
+    pub usage: TokenUsage,
+    pub tool_calls: u64,
+    pub total_duration_ms: u64,
+    /// Scores given with [`Agent::record_score`], e.g. user ratings.
+    pub scores: Vec<f64>,
+}
+
+impl VariantStats {
+    /// Share of the invocations that succeeded, 0 without invocations.
+    pub fn success_rate(&self) -> f64 {
+        if self.invocations == 0 {
+            return 0.0;
+        }
+        (self.invocations - self.failures) as f64 / self.invocations as f64
+    }
+
+    pub fn mean_duration_ms(&self) -> f64 {
+        self.total_duration_ms as f64 / self.invocations.max(1) as f64
+    }
+
+    /// Tokens (prompt and completion) per invocation.
+    pub fn mean_tokens(&self) -> f64 {
+        (self.usage.prompt_tokens + self.usage.completion_tokens) as f64
+            / self.invocations.max(1) as f64
+    }
+
+    pub fn mean_score(&self) -> Option<f64> {
+        if self.scores.is_empty() {
+            return None;
+        }
+        Some(self.scores.iter().sum::<f64>() / self.scores.len() as f64)
+    }
+}
+
+/// Metadata key of the system message holding the variant of its
+/// conversation.
+const VARIANT_METADATA: &str = "experiment_variant";
+
+/// System prompt variants tried against each other in production.
+///
+/// Every conversation of an agent built with
+/// [`AgentBuilder::set_experiment`](crate::AgentBuilder::set_experiment) is
+/// assigned one variant at random, by weight, which replaces the configured
+/// system prompt in its requests, keeping skill descriptions and schema
+/// instructions. The variant sticks until the history is cleared. The variant id is set on the agent's notifications as
+/// [`Notification::variant`](crate::Notification::variant) and on the
+/// invocation span as `experiment.variant`. Outcomes are collected per
+/// variant in [`results`](Self::results); clones share them, so the
+/// experiment can be set on many agents and analyzed in one place.
+///
+/// ```
+/// use reagent_rs::{AgentBuilder, PromptExperiment};
+///
+/// let experiment = PromptExperiment::new("tone")
+///     .variant("formal", 1, "You are a formal assistant.")
+///     .variant("casual", 1, "You are a casual assistant.");
+/// let builder = AgentBuilder::default()
+///     .set_model("qwen3:0.6b")
+///     .set_experiment(experiment.clone());
+/// assert!(experiment.results().iter().all(|stats| stats.invocations == 0));
+/// ```
+#[derive(Debug, Clone)]
+pub struct PromptExperiment {
+    name: String,
+    variants: Vec<PromptVariant>,
+    results: Arc<Mutex<HashMap<String, VariantStats>>>,
+    /// State of the seeded generator, random assignments without one.
+    rng: Option<Arc<AtomicU64>>,
+}
+
+impl PromptExperiment {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            variants: Vec::new(),
+            results: Arc::default(),
+            rng: None,
+        }
+    }
+
+    /// Assign variants with a generator seeded with `seed`, so the same
+    /// conversations get the same variants, e.g. in tests.
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.rng = Some(Arc::new(AtomicU64::new(seed)));
+        self
+    }
+
+    /// Add variant `id` with its relative `weight`.
+    pub fn variant(
+        mut self,
+        id: impl Into<String>,
+        weight: u32,
+        system_prompt: impl Into<String>,
+    ) -> Self {
+        self.variants.push(PromptVariant {
+            id: id.into(),
+            weight,
+            system_prompt: system_prompt.into(),
+        });
+        self
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn variants(&self) -> &[PromptVariant] {
+        &self.variants
+    }
+
+    /// Outcomes of every variant, in the order they were added.
+    pub fn results(&self) -> Vec<VariantStats> {
+        let results = self.lock();
+        self.variants
+            .iter()
+            .map(|variant| {
+                results.get(&variant.id).cloned().unwrap_or(VariantStats {
+                    variant: variant.id.clone(),
+                    ..Default::default()
+                })
+            })
+            .collect()
+    }
+
+    /// Add a score, e.g. a user rating, to the outcomes of `variant`.
+    pub fn record_score(&self, variant: &str, score: f64) {
+        self.stats(variant, |stats| stats.scores.push(score));
+    }
+
+    /// Pick a variant by weight, `None` if all weights are 0.
+    fn assign(&self) -> Option<&PromptVariant> {
+        let total: u64 = self.variants.iter().map(|v| u64::from(v.weight)).sum();
+        if total == 0 {
+            return None;
+        }
+        let mut roll = self.roll() % total;
+        self.variants.iter().find(|variant| {
+            let weight = u64::from(variant.weight);
+            if roll < weight {
+                return true;
+            }
+            roll -= weight;
+            false
+        })
+    }
+
+    fn roll(&self) -> u64 {
+        let Some(rng) = &self.rng else {
+            return Uuid::new_v4().as_u128() as u64;
+        };
+        // splitmix64
+        let mut z = rng
+            .fetch_add(0x9e37_79b9_7f4a_7c15, Ordering::Relaxed)
+            .wrapping_add(0x9e37_79b9_7f4a_7c15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    fn variant_by_id(&self, id: &str) -> Option<&PromptVariant> {
+        self.variants.iter().find(|variant| variant.id == id)
+    }
+
+    fn record(&self, variant: &str, summary: &InvocationSummary) {
+        self.stats(variant, |stats| {
+            stats.invocations += 1;
+            stats.failures += u64::from(!summary.success);
+            stats.usage.requests += summary.usage.requests;
+            stats.usage.prompt_tokens += summary.usage.prompt_tokens;
+            stats.usage.completion_tokens += summary.usage.completion_tokens;
+            stats.tool_calls += summary.tool_calls as u64;
+            stats.total_duration_ms += summary.duration_ms;
+        });
+    }
+
+    fn stats(&self, variant: &str, update: impl FnOnce(&mut VariantStats)) {
+        let mut results = self.lock();
+        let stats = results
+            .entry(variant.to_string())
+            .or_insert_with(|| VariantStats {
+                variant: variant.to_string(),
+                ..Default::default()
+            });
+        update(stats);
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<String, VariantStats>> {
+        self.results.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Experiment of an agent and the variant of its current conversation.
+#[derive(Debug, Clone)]
+pub(crate) struct ExperimentState {
+    experiment: PromptExperiment,
+    /// The system prompt the agent was built with, before skills or schema
+    /// instructions were added, which the variant prompts replace.
+    prompt: String,
+    variant: Option<String>,
+}
+
+impl ExperimentState {
+    /// `experiment` on an agent built with system prompt `prompt`, before
+    /// skills or schema instructions were added to it.
+    pub(crate) fn new(experiment: PromptExperiment, prompt: String) -> Self {
+        Self {
+            experiment,
+            prompt,
+            variant: None,
+        }
+    }
+
+    /// Put the prompt of the current variant in place of `base`, the
+    /// configured prompt, in the system message of a request.
+    pub(crate) fn apply(&self, base: Option<&str>, messages: &mut [Message]) {
+        let Some(variant) = self
+            .variant
+            .as_deref()
+            .and_then(|id| self.experiment.variant_by_id(id))
+        else {
+            return;
+        };
+        let Some(system) = messages.first_mut().filter(|m| m.role == Role::System) else {
+            return;
+        };
+        let base = base.unwrap_or(&self.prompt);
+        let content = system.content.get_or_insert_with(String::new);
+        *content = if !base.is_empty() && content.contains(base) {
+            content.replacen(base, &variant.system_prompt, 1)
+        } else {
+            variant.system_prompt.clone()
+        };
+    }
+}
+
+impl Agent {
+    /// The experiment set with
+    /// [`AgentBuilder::set_experiment`](crate::AgentBuilder::set_experiment).
+    pub fn experiment(&self) -> Option<&PromptExperiment> {
+        self.experiment.as_ref().map(|state| &state.experiment)
+    }
+
+    /// Variant of the running invocation, or of the last one once it
+    /// finished.
+    pub fn variant(&self) -> Option<&str> {
+        self.experiment.as_ref()?.variant.as_deref()
+    }
+
+    /// Score the last invocation, e.g. with a user rating, adding it to the
+    /// outcomes of its variant. Does nothing without an experiment.
+    pub fn record_score(&self, score: f64) {
+        if let Some(state) = &self.experiment {
+            if let Some(variant) = &state.variant {
+                state.experiment.record_score(variant, score);
+            }
+        }
+    }
+
+    /// Assign the conversation a variant, unless it has one already. Its
+    /// prompt is applied to the requests by `prepare_messages`.
+    pub(crate) fn assign_variant(&mut self) {
+        let Some(state) = &mut self.experiment else {
+            return;
+        };
+        // a history cleared on every invocation starts a new conversation
+        let system = self.history.first_mut().filter(|m| m.role == Role::System);
+        let sticky = system
+            .as_ref()
+            .filter(|_| !self.clear_history_on_invoke)
+            .and_then(|m| m.get_metadata(VARIANT_METADATA))
+            .and_then(|id| id.as_str())
+            .and_then(|id| state.experiment.variant_by_id(id));
+        let Some(variant) = sticky.or_else(|| state.experiment.assign()).cloned() else {
+            state.variant = None;
+            return;
+        };
+        let span = tracing::Span::current();
+        span.set_attribute("experiment.name", state.experiment.name.clone());
+        span.set_attribute("experiment.variant", variant.id.clone());
+        state.variant = Some(variant.id.clone());
+
+        let Some(system) = system else {
+            return;
+        };
+        if system
+            .get_metadata(VARIANT_METADATA)
+            .and_then(|id| id.as_str())
+            != Some(&variant.id)
+        {
+            system
+                .metadata
+                .insert(VARIANT_METADATA.into(), variant.id.into());
+            self.sync_history();
+        }
+    }
+
+    /// Add the outcome of the invocation to its variant.
+    pub(crate) fn record_variant(&self, summary: &InvocationSummary) {
+        if let Some(state) = &self.experiment {
+            if let Some(variant) = &state.variant {
+                state.experiment.record(variant, summary);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{testing::FlowTestHarness, AgentBuilder, NotificationContent};
+
+    #[tokio::test]
+    async fn outcomes_are_collected_per_variant() {
+        let experiment = PromptExperiment::new("tone")
+            .variant("formal", 1, "Be formal.")
+            .variant("casual", 1, "Be casual.")
+            .variant("disabled", 0, "Never used.");
+        let builder = AgentBuilder::default()
+            .set_model("test")
+            .set_system_prompt("Be formal.")
+            .set_experiment(experiment.clone());
+        let mut harness = FlowTestHarness::new(builder).await.unwrap();
+        for _ in 0..6 {
+            harness = harness.reply("Hi.");
+        }
+
+        for prompt in 1..=6 {
+            harness.run(prompt.to_string()).await.unwrap();
+            let variant = harness.agent().variant().unwrap().to_string();
+            let expected = if variant == "formal" {
+                "Be formal."
+            } else {
+                "Be casual."
+            };
+            let request = harness.requests().pop().unwrap();
+            assert_eq!(request.messages[0].content.as_deref(), Some(expected));
+            let finished = harness.notifications().last().unwrap();
+            assert!(matches!(
+                finished.content,
+                NotificationContent::InvocationFinished(_)
+            ));
+            assert_eq!(finished.variant.as_deref(), Some(variant.as_str()));
+            harness
+                .agent()
+                .record_score(if variant == "formal" { 1.0 } else { 0.0 });
+        }
+
+        let results = experiment.results();
+        assert_eq!(results.iter().map(|s| s.invocations).sum::<u64>(), 6);
+        assert_eq!(results[2].invocations, 0);
+        for stats in &results[..2] {
+            assert_eq!(stats.usage.requests, stats.invocations);
+            assert_eq!(stats.scores.len() as u64, stats.invocations);
+            assert_eq!(
+                stats.success_rate(),
+                if stats.invocations > 0 { 1.0 } else { 0.0 }
+            );
+        }
+        if results[0].invocations > 0 {
+            assert_eq!(results[0].mean_score(), Some(1.0));
+        }
+    }
+
+    #[tokio::test]
+    async fn variants_stick_to_their_conversation() {
+        let assignments = |seed: u64| async move {
+            let experiment = PromptExperiment::new("tone")
+                .variant("formal", 1, "Be formal.")
+                .variant("casual", 1, "Be casual.")
+                .seed(seed);
+            let builder = AgentBuilder::default()
+                .set_model("test")
+                .set_system_prompt("Be formal.")
+                .set_experiment(experiment);
+            let mut harness = FlowTestHarness::new(builder).await.unwrap();
+            for _ in 0..12 {
+                harness = harness.reply("Hi.");
+            }
+            let mut variants = Vec::new();
+            for conversation in 0..4 {
+                harness.agent_mut().clear_history();
+                for turn in 0..3 {
+                    harness.run(format!("{conversation}.{turn}")).await.unwrap();
+                    let variant = harness.agent().variant().unwrap().to_string();
+                    let request = harness.requests().pop().unwrap();
+                    let expected = format!("Be {variant}.");
+                    assert_eq!(request.messages[0].content.as_deref(), Some(&*expected));
+                    if turn > 0 {
+                        assert_eq!(Some(&variant), variants.last());
+                    }
+                    variants.push(variant);
+                }
+            }
+            variants
+        };
+
+        let variants = assignments(7).await;
+        assert_eq!(variants, assignments(7).await);
+        // conversations are assigned anew
+        assert!(variants.iter().any(|v| v == "formal"));
+        assert!(variants.iter().any(|v| v == "casual"));
+    }
+
+    #[test]
+    fn variants_replace_only_the_configured_prompt() {
+        let experiment = PromptExperiment::new("tone").variant("casual", 1, "Be casual.");
+        let mut state = ExperimentState::new(experiment, "Be formal.".into());
+        state.variant = Some("casual".into());
+        let mut messages = vec![Message::system("Be formal.\n\nSkills: weather")];
+
+        state.apply(None, &mut messages);
+        assert_eq!(
+            messages[0].content.as_deref(),
+            Some("Be casual.\n\nSkills: weather")
+        );
+
+        // the active version of a library prompt is replaced instead
+        let mut messages = vec![Message::system("Be brief.\n\nSkills: weather")];
+        state.apply(Some("Be brief."), &mut messages);
+        assert_eq!(
+            messages[0].content.as_deref(),
+            Some("Be casual.\n\nSkills: weather")
+        );
+    }
+}
@@ -0,0 +1,196 @@
+use std::sync::{Arc, Mutex};
+
+use crate::{Agent, Message, Role};
+
+/// An input with the output the model should produce for it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FewShotExample {
+    pub input: String,
+    pub output: String,
+}
+
+impl FewShotExample {
+    pub fn new(input: impl Into<String>, output: impl Into<String>) -> Self {
+        Self {
+            input: input.into(),
+            output: output.into(),
+        }
+    }
+}
+
+/// Example conversations inserted after the system prompt of every request,
+/// as alternating user and assistant messages. They are not stored in the
+/// agent's history.
+///
+/// By default all examples are sent. With [`top_k`](Self::top_k), only the
+/// examples whose inputs are most similar to the current prompt are sent,
+/// using the agent's embedding model (see
+/// [`Agent::embed`](crate::Agent::embed)). Example embeddings are computed
+/// once and shared between clones of the set.
+///
+/// ```
+/// use reagent_rs::FewShotSet;
+///
+/// let examples = FewShotSet::new()
+///     .add("2 + 2", "4")
+///     .add("What is the capital of France?", "Paris")
+///     .top_k(1);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct FewShotSet {
+    examples: Vec<FewShotExample>,
+    top_k: Option<usize>,
+    /// Embeddings of the example inputs, in example order.
+    embeddings: Arc<Mutex<Option<Vec<Vec<f64>>>>>,
+}
+
+impl FewShotSet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add an example.
+    pub fn add(mut self, input: impl Into<String>, output: impl Into<String>) -> Self {
+        self.examples.push(FewShotExample::new(input, output));
+        self.reset_embeddings();
+        self
+    }
+
+    /// Add several examples.
+    pub fn extend(mut self, examples: impl IntoIterator<Item = FewShotExample>) -> Self {
+        self.examples.extend(examples);
+        self.reset_embeddings();
+        self
+    }
+
+    /// Only send the `k` examples most relevant to the prompt.
+    pub fn top_k(mut self, k: usize) -> Self {
+        self.top_k = Some(k);
+        self
+    }
+
+    pub fn examples(&self) -> &[FewShotExample] {
+        &self.examples
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.examples.is_empty()
+    }
+
+    fn reset_embeddings(&mut self) {
+        self.embeddings = Arc::default();
+    }
+
+    /// Examples to send for `prompt`, most relevant last so they sit closest
+    /// to the prompt. Falls back to the first `k` examples without a prompt
+    /// or if embedding fails.
+    pub(crate) async fn select(&self, agent: &Agent, prompt: Option<&str>) -> Vec<FewShotExample> {
+        let k = match self.top_k {
+            Some(k) if k < self.examples.len() => k,
+            _ => return self.examples.clone(),
+        };
+        let Some(prompt) = prompt else {
+            return self.examples.iter().take(k).cloned().collect();
+        };
+
+        match self.rank(agent, prompt).await {
+            Ok(ranked) => ranked
+                .into_iter()
+                .take(k)
+                .rev()
+                .map(|index| self.examples[index].clone())
+                .collect(),
+            Err(e) => {
+                tracing::warn!(error = %e, "Few-shot selection failed, using the first examples");
+                self.examples.iter().take(k).cloned().collect()
+            }
+        }
+    }
+
+    /// Example indices ordered by similarity of their input to `prompt`.
+    async fn rank(&self, agent: &Agent, prompt: &str) -> Result<Vec<usize>, crate::AgentError> {
+        let cached = self
+            .embeddings
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone();
+        let embeddings = match cached {
+            Some(embeddings) => embeddings,
+            None => {
+                let inputs = self.examples.iter().map(|e| e.input.clone());
+                let embeddings = agent.embed_batch(inputs).await?;
+                *self.embeddings.lock().unwrap_or_else(|e| e.into_inner()) =
+                    Some(embeddings.clone());
+                embeddings
+            }
+        };
+        let query = agent.embed(prompt).await?;
+
+        let mut scored: Vec<(usize, f64)> = embeddings
+            .iter()
+            .map(|embedding| cosine_similarity(&query, embedding))
+            .enumerate()
+            .collect();
+        scored.sort_by(|a, b| b.1.total_cmp(&a.1));
+        Ok(scored.into_iter().map(|(index, _)| index).collect())
+    }
+}
+
+/// Insert `examples` after the leading system messages.
+pub(crate) fn insert_examples(messages: &mut Vec<Message>, examples: &[FewShotExample]) {
+    let position = messages
+        .iter()
+        .position(|m| !matches!(m.role, Role::System | Role::Developer))
+        .unwrap_or(messages.len());
+    let example_messages = examples.iter().flat_map(|example| {
+        [
+            Message::user(example.input.clone()),
+            Message::assistant(example.output.clone()),
+        ]
+    });
+    messages.splice(position..position, example_messages);
+}
+
+/// Latest user message, the prompt examples are selected for.
+pub(crate) fn current_prompt(messages: &[Message]) -> Option<&str> {
+    messages
+        .iter()
+        .rev()
+        .find(|m| m.role == Role::User)
+        .and_then(|m| m.content.as_deref())
+}
+
+pub(crate) fn cosine_similarity(a: &[f64], b: &[f64]) -> f64 {
+    let dot: f64 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let norm = |v: &[f64]| v.iter().map(|x| x * x).sum::<f64>().sqrt();
+    let denominator = norm(a) * norm(b);
+    if denominator == 0.0 {
+        0.0
+    } else {
+        dot / denominator
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn examples_follow_the_system_prompt() {
+        let mut messages = vec![Message::system("Answer briefly."), Message::user("3 + 3")];
+        let set = FewShotSet::new().add("2 + 2", "4").add("1 + 1", "2");
+
+        insert_examples(&mut messages, set.examples());
+
+        let contents: Vec<&str> = messages
+            .iter()
+            .map(|m| m.content.as_deref().unwrap())
+            .collect();
+        assert_eq!(
+            contents,
+            ["Answer briefly.", "2 + 2", "4", "1 + 1", "2", "3 + 3"]
+        );
+        assert_eq!(messages[2].role, Role::Assistant);
+        assert!((cosine_similarity(&[1.0, 0.0], &[2.0, 0.0]) - 1.0).abs() < 1e-9);
+    }
+}
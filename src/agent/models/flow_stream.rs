@@ -0,0 +1,165 @@
+use std::time::Duration;
+
+use futures::Stream;
+use tokio::sync::mpsc::{self, Sender};
+
+use crate::{Agent, AgentError, Message, Notification, NotificationContent, Token, ToolCall};
+
+/// How long forwarders of sub-agents may keep delivering after the flow
+/// returned.
+const FORWARDER_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// An event of a flow run with [`Agent::invoke_flow_stream`].
+#[derive(Debug)]
+pub enum FlowEvent {
+    /// A token of a streamed model response of the agent, only sent with
+    /// [`AgentBuilder::set_stream`](crate::AgentBuilder::set_stream).
+    Token(Token),
+    /// The model of the agent requested a tool call.
+    ToolCall(ToolCall),
+    /// A tool call of the agent returned its result or failed.
+    ToolResult(Result<String, String>),
+    /// Any other notification, including everything forwarded from
+    /// sub-agents.
+    Notification(Box<Notification>),
+    /// The flow returned. Always the last event.
+    Finished(Result<Message, AgentError>),
+}
+
+impl FlowEvent {
+    fn from_notification(notification: Notification, agent: &str) -> Self {
+        if notification.agent != agent {
+            return FlowEvent::Notification(Box::new(notification));
+        }
+        match &notification.content {
+            NotificationContent::Token(token) => FlowEvent::Token(token.clone()),
+            NotificationContent::ToolCallRequest(call) => FlowEvent::ToolCall(call.clone()),
+            NotificationContent::ToolCallSuccessResult(result) => {
+                FlowEvent::ToolResult(Ok(result.clone()))
+            }
+            NotificationContent::ToolCallErrorResult(error) => {
+                FlowEvent::ToolResult(Err(error.clone()))
+            }
+            _ => FlowEvent::Notification(Box::new(notification)),
+        }
+    }
+}
+
+/// Puts the agent's own notification channel back, also when the stream is
+/// dropped before the flow returned.
+struct ChannelSwap<'a> {
+    agent: &'a mut Agent,
+    previous: Option<Sender<Notification>>,
+}
+
+impl Drop for ChannelSwap<'_> {
+    fn drop(&mut self) {
+        self.agent.notification_channel = self.previous.take();
+    }
+}
+
+impl Agent {
+    /// Invoke the flow with `prompt` and receive its tokens, tool calls and
+    /// result as a stream, without setting up a notification channel.
+    ///
+    /// While the stream runs, notifications go to the stream instead of the
+    /// agent's notification channel (MCP servers keep reporting progress to
+    /// the channel they were connected with). Dropping the stream stops the
+    /// flow.
+    ///
+    /// ```no_run
+    /// # async fn run(mut agent: reagent_rs::Agent) {
+    /// use futures::StreamExt;
+    /// use reagent_rs::FlowEvent;
+    ///
+    /// let stream = agent.invoke_flow_stream("What is the weather in Ljubljana?");
+    /// futures::pin_mut!(stream);
+    /// while let Some(event) = stream.next().await {
+    ///     match event {
+    ///         FlowEvent::Token(token) => print!("{}", token.value),
+    ///         FlowEvent::ToolCall(call) => println!("calling {}", call.function.name),
+    ///         FlowEvent::Finished(result) => println!("\n{:?}", result.map(|m| m.content)),
+    ///         _ => {}
+    ///     }
+    /// }
+    /// # }
+    /// ```
+    pub fn invoke_flow_stream(
+        &mut self,
+        prompt: impl Into<String>,
+    ) -> impl Stream<Item = FlowEvent> + '_ {
+        let prompt = prompt.into();
+
+        async_stream::stream! {
+            let name = self.name.clone();
+            let (sender, mut receiver) = mpsc::channel::<Notification>(100);
+            let previous = self.notification_channel.replace(sender);
+
+            let result = {
+                let swap = ChannelSwap { agent: &mut *self, previous };
+                let mut flow = std::pin::pin!(swap.agent.invoke_flow(prompt));
+                loop {
+                    let notification = tokio::select! {
+                        result = &mut flow => break result,
+                        Some(notification) = receiver.recv() => notification,
+                    };
+                    yield FlowEvent::from_notification(notification, &name);
+                }
+            };
+
+            // forwarders of sub-agents may still be delivering
+            {
+                let mut forwarders = std::pin::pin!(self.await_forwarders(FORWARDER_TIMEOUT));
+                loop {
+                    let notification = tokio::select! {
+                        _ = &mut forwarders => break,
+                        Some(notification) = receiver.recv() => notification,
+                    };
+                    yield FlowEvent::from_notification(notification, &name);
+                }
+            }
+            while let Ok(notification) = receiver.try_recv() {
+                yield FlowEvent::from_notification(notification, &name);
+            }
+
+            yield FlowEvent::Finished(result);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::StreamExt;
+
+    use super::*;
+    use crate::{testing::FlowTestHarness, AgentBuilder};
+
+    #[tokio::test]
+    async fn stream_yields_tool_events_and_ends_with_the_result() {
+        let builder = AgentBuilder::default().set_model("test");
+        let mut harness = FlowTestHarness::new(builder)
+            .await
+            .unwrap()
+            .reply_with_tool_call("search", serde_json::json!({ "query": "rust" }))
+            .expect_tool_call("search", "A language")
+            .reply("Rust is a language.");
+
+        let agent = harness.agent_mut();
+        let events: Vec<FlowEvent> = agent.invoke_flow_stream("What is rust?").collect().await;
+
+        assert!(events
+            .iter()
+            .any(|e| matches!(e, FlowEvent::ToolCall(call) if call.function.name == "search")));
+        assert!(events
+            .iter()
+            .any(|e| matches!(e, FlowEvent::ToolResult(Ok(result)) if result == "A language")));
+        match events.last() {
+            Some(FlowEvent::Finished(Ok(message))) => {
+                assert_eq!(message.content.as_deref(), Some("Rust is a language."))
+            }
+            other => panic!("unexpected last event {other:?}"),
+        }
+        // the agent's own channel is back in place
+        assert!(agent.notification_channel.is_some());
+    }
+}
@@ -0,0 +1,491 @@
+use std::{collections::HashMap, time::Duration};
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::{mpsc, oneshot};
+
+use crate::{
+    services::runtime::{self, Instant},
+    Agent, AgentError, AgentEvent, AgentOutput, Message,
+};
+
+/// Requests queued by default, per priority, before `invoke` waits for a
+/// free slot.
+const DEFAULT_QUEUE_CAPACITY: usize = 32;
+
+/// Priority of a request queued on an [`AgentHandle`]. Queued requests with
+/// a higher priority run first; a request that is already running is not
+/// interrupted.
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Default, Serialize, Deserialize,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum Priority {
+    /// Batch jobs and other work nobody is waiting for.
+    Low,
+    #[default]
+    Normal,
+    /// Interactive requests, e.g. a user waiting in a chat.
+    High,
+}
+
+impl Priority {
+    fn index(self) -> usize {
+        match self {
+            Priority::High => 0,
+            Priority::Normal => 1,
+            Priority::Low => 2,
+        }
+    }
+}
+
+/// Number of requests waiting in each queue of an [`AgentHandle`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct QueueStats {
+    pub high: usize,
+    pub normal: usize,
+    pub low: usize,
+}
+
+impl QueueStats {
+    pub fn total(&self) -> usize {
+        self.high + self.normal + self.low
+    }
+}
+
+struct Invocation {
+    event: AgentEvent,
+    priority: Priority,
+    /// Latest time the request may start, from the queue timeout.
+    deadline: Option<Instant>,
+    /// Told when the request starts.
+    started: oneshot::Sender<()>,
+    reply: oneshot::Sender<Result<Message, AgentError>>,
+}
+
+/// Calls answered between requests, ahead of all queued requests.
+enum Control {
+    History(oneshot::Sender<Vec<Message>>),
+    ClearHistory(oneshot::Sender<()>),
+    Shutdown(oneshot::Sender<Agent>),
+}
+
+/// Cloneable handle to an [`Agent`] owned by a background task.
+///
+/// [`Agent::invoke_flow`] takes `&mut self`, so sharing an agent usually means
+/// wrapping it in `Arc<Mutex<Agent>>`. The handle instead queues requests to
+/// the task, which runs them one at a time, and can be used from `&self`
+/// across threads (e.g. as web server state).
+///
+/// Requests are queued by [`Priority`], so interactive requests can skip
+/// ahead of batch jobs. [`set_queue_timeout`](Self::set_queue_timeout) limits
+/// how long requests of a priority wait before they start. [`history`](Self::history),
+/// [`clear_history`](Self::clear_history) and [`shutdown`](Self::shutdown)
+/// skip the queues and only wait for the running request.
+///
+/// ```no_run
+/// # async fn run() -> Result<(), reagent_rs::AgentError> {
+/// use std::time::Duration;
+///
+/// use reagent_rs::{AgentBuilder, Priority};
+///
+/// let agent = AgentBuilder::default().set_model("qwen3:0.6b").build().await?;
+/// let handle = agent
+///     .into_handle()
+///     .set_queue_timeout(Priority::High, Duration::from_secs(5));
+///
+/// let other = handle.clone();
+/// tokio::spawn(async move {
+///     other
+///         .invoke_with_priority(Priority::Low, "Summarize the logs")
+///         .await
+/// });
+///
+/// let reply = handle.invoke_with_priority(Priority::High, "Hello").await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct AgentHandle {
+    name: String,
+    /// One queue per priority, indexed by [`Priority::index`].
+    queues: [mpsc::Sender<Invocation>; 3],
+    control: mpsc::Sender<Control>,
+    queue_timeouts: [Option<Duration>; 3],
+}
+
+impl AgentHandle {
+    /// Move `agent` onto a new task. Must be called within a tokio runtime.
+    pub fn new(agent: Agent) -> Self {
+        Self::with_capacity(agent, DEFAULT_QUEUE_CAPACITY)
+    }
+
+    /// Like [`new`](Self::new), with room for `capacity` queued requests
+    /// per priority.
+    pub fn with_capacity(agent: Agent, capacity: usize) -> Self {
+        let name = agent.name.clone();
+        let (high, high_rx) = mpsc::channel(capacity.max(1));
+        let (normal, normal_rx) = mpsc::channel(capacity.max(1));
+        let (low, low_rx) = mpsc::channel(capacity.max(1));
+        let (control, control_rx) = mpsc::channel(capacity.max(1));
+        runtime::spawn(run_agent(agent, control_rx, [high_rx, normal_rx, low_rx]));
+        Self {
+            name,
+            queues: [high, normal, low],
+            control,
+            queue_timeouts: [None; 3],
+        }
+    }
+
+    /// Fail requests of `priority` with [`AgentError::QueueTimeout`] when
+    /// they could not start within `timeout`, e.g. because the agent is busy
+    /// with requests of a higher priority. Once started, requests run to
+    /// completion. Applies to requests sent through this handle and clones
+    /// made from it afterwards.
+    pub fn set_queue_timeout(mut self, priority: Priority, timeout: Duration) -> Self {
+        self.queue_timeouts[priority.index()] = Some(timeout);
+        self
+    }
+
+    /// Number of requests of `priority` waiting to start.
+    pub fn queue_len(&self, priority: Priority) -> usize {
+        let queue = &self.queues[priority.index()];
+        queue.max_capacity() - queue.capacity()
+    }
+
+    /// Number of requests waiting to start, per priority.
+    pub fn queue_stats(&self) -> QueueStats {
+        QueueStats {
+            high: self.queue_len(Priority::High),
+            normal: self.queue_len(Priority::Normal),
+            low: self.queue_len(Priority::Low),
+        }
+    }
+
+    /// Name of the agent.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Queue a prompt with [`Priority::Normal`], see [`Agent::invoke_flow`].
+    pub async fn invoke(&self, prompt: impl Into<String>) -> Result<Message, AgentError> {
+        self.invoke_event(AgentEvent::Prompt(prompt.into())).await
+    }
+
+    /// Queue a prompt with the given priority.
+    pub async fn invoke_with_priority(
+        &self,
+        priority: Priority,
+        prompt: impl Into<String>,
+    ) -> Result<Message, AgentError> {
+        self.invoke_event_with_priority(priority, AgentEvent::Prompt(prompt.into()))
+            .await
+    }
+
+    /// Queue template data, see [`Agent::invoke_flow_with_template`].
+    pub async fn invoke_with_template(
+        &self,
+        template_data: HashMap<String, String>,
+    ) -> Result<Message, AgentError> {
+        self.invoke_event(AgentEvent::TemplateData(template_data))
+            .await
+    }
+
+    /// Queue a prompt and parse the reply, see [`Agent::invoke_flow_output`].
+    pub async fn invoke_output<O: AgentOutput>(
+        &self,
+        prompt: impl Into<String>,
+    ) -> Result<O, AgentError> {
+        let message = self.invoke(prompt).await?;
+        O::from_message(&message)
+    }
+
+    /// Queue an [`AgentEvent`] with [`Priority::Normal`].
+    pub async fn invoke_event(&self, event: AgentEvent) -> Result<Message, AgentError> {
+        self.invoke_event_with_priority(Priority::Normal, event)
+            .await
+    }
+
+    /// Queue an [`AgentEvent`] with the given priority.
+    pub async fn invoke_event_with_priority(
+        &self,
+        priority: Priority,
+        event: AgentEvent,
+    ) -> Result<Message, AgentError> {
+        let deadline = self.queue_timeouts[priority.index()].map(|t| Instant::now() + t);
+        let (started, has_started) = oneshot::channel();
+        let (reply, response) = oneshot::channel();
+        let command = Invocation {
+            event,
+            priority,
+            deadline,
+            started,
+            reply,
+        };
+
+        let queue = &self.queues[priority.index()];
+        match deadline {
+            // waiting for a free slot counts towards the queue timeout
+            Some(deadline) => runtime::timeout_at(deadline, queue.send(command))
+                .await
+                .map_err(|_| AgentError::QueueTimeout(priority))?
+                .map_err(|_| stopped())?,
+            None => queue.send(command).await.map_err(|_| stopped())?,
+        }
+        if let Some(deadline) = deadline {
+            // so does waiting in the queue. Dropping `response` tells the
+            // task to skip the request
+            if runtime::timeout_at(deadline, has_started).await.is_err() {
+                return Err(AgentError::QueueTimeout(priority));
+            }
+        }
+        response.await.map_err(|_| stopped())?
+    }
+
+    /// Copy of the conversation history, once the running request is done.
+    /// Queued requests are not waited for, so a busy queue cannot hold this
+    /// or the calls below back.
+    pub async fn history(&self) -> Result<Vec<Message>, AgentError> {
+        let (reply, response) = oneshot::channel();
+        self.send(Control::History(reply)).await?;
+        response.await.map_err(|_| stopped())
+    }
+
+    /// Reset the history to the system prompt, see [`Agent::clear_history`].
+    pub async fn clear_history(&self) -> Result<(), AgentError> {
+        let (reply, response) = oneshot::channel();
+        self.send(Control::ClearHistory(reply)).await?;
+        response.await.map_err(|_| stopped())
+    }
+
+    /// Stop the task once the running request is done and return the agent.
+    /// Requests still queued, and later ones through other clones of the
+    /// handle, fail.
+    pub async fn shutdown(self) -> Result<Agent, AgentError> {
+        let (reply, response) = oneshot::channel();
+        self.send(Control::Shutdown(reply)).await?;
+        response.await.map_err(|_| stopped())
+    }
+
+    async fn send(&self, command: Control) -> Result<(), AgentError> {
+        self.control.send(command).await.map_err(|_| stopped())
+    }
+}
+
+impl Agent {
+    /// Move the agent onto a task and return an [`AgentHandle`] to it.
+    pub fn into_handle(self) -> AgentHandle {
+        AgentHandle::new(self)
+    }
+}
+
+fn stopped() -> AgentError {
+    AgentError::Runtime("Agent task has stopped".into())
+}
+
+async fn run_agent(
+    mut agent: Agent,
+    mut control: mpsc::Receiver<Control>,
+    queues: [mpsc::Receiver<Invocation>; 3],
+) {
+    let [mut high, mut normal, mut low] = queues;
+    loop {
+        // all channels close together, when the last handle is dropped
+        let invocation = tokio::select! {
+            biased;
+            Some(command) = control.recv() => {
+                match command {
+                    Control::History(reply) => {
+                        let _ = reply.send(agent.history.clone());
+                    }
+                    Control::ClearHistory(reply) => {
+                        agent.clear_history();
+                        let _ = reply.send(());
+                    }
+                    Control::Shutdown(reply) => {
+                        let _ = reply.send(agent);
+                        return;
+                    }
+                }
+                continue;
+            }
+            Some(invocation) = high.recv() => invocation,
+            Some(invocation) = normal.recv() => invocation,
+            Some(invocation) = low.recv() => invocation,
+            else => return,
+        };
+
+        let Invocation {
+            event,
+            priority,
+            deadline,
+            started,
+            reply,
+        } = invocation;
+        // the caller gave up waiting, e.g. on its queue timeout
+        if reply.is_closed() {
+            continue;
+        }
+        if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+            let _ = reply.send(Err(AgentError::QueueTimeout(priority)));
+            continue;
+        }
+        let _ = started.send(());
+        let result = match event {
+            AgentEvent::Prompt(prompt) => agent.invoke_flow(prompt).await,
+            AgentEvent::TemplateData(data) => agent.invoke_flow_with_template(data).await,
+        };
+        // the caller may have given up waiting
+        let _ = reply.send(result);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::{AgentBuilder, FlowFuture, Role};
+
+    #[tokio::test]
+    async fn handle_serves_requests_and_returns_the_agent() {
+        let agent = AgentBuilder::default()
+            .set_model("test-model")
+            .set_system_prompt("You are a test.")
+            .build()
+            .await
+            .unwrap();
+        let handle = agent.into_handle();
+        let other = handle.clone();
+
+        assert_eq!(other.history().await.unwrap().len(), 1);
+        other.clear_history().await.unwrap();
+
+        let agent = handle.shutdown().await.unwrap();
+        assert_eq!(agent.model, "test-model");
+        assert!(other.history().await.is_err());
+    }
+
+    #[tokio::test]
+    async fn higher_priorities_run_first() {
+        // the first request waits for the gate, so the others queue up
+        let gate = Arc::new(tokio::sync::Semaphore::new(0));
+        let (running, mut first_running) = mpsc::unbounded_channel();
+        let flow_gate = gate.clone();
+        let agent = AgentBuilder::default()
+            .set_model("test-model")
+            .set_flow(move |agent: &mut Agent, prompt: String| -> FlowFuture<'_> {
+                let gate = flow_gate.clone();
+                let running = running.clone();
+                Box::pin(async move {
+                    if prompt == "first" {
+                        let _ = running.send(());
+                        let _ = gate.acquire().await;
+                    }
+                    agent.history.push(Message::user(prompt.clone()));
+                    Ok(Message::assistant(prompt))
+                })
+            })
+            .build()
+            .await
+            .unwrap();
+        let handle = agent
+            .into_handle()
+            .set_queue_timeout(Priority::Low, Duration::from_millis(20));
+
+        let first = tokio::spawn({
+            let handle = handle.clone();
+            async move { handle.invoke("first").await }
+        });
+        first_running.recv().await.unwrap();
+
+        let queued = handle
+            .clone()
+            .set_queue_timeout(Priority::Low, Duration::from_secs(60));
+        let low = tokio::spawn({
+            let handle = queued.clone();
+            async move { handle.invoke_with_priority(Priority::Low, "low").await }
+        });
+        let timed_out = tokio::spawn({
+            let handle = handle.clone();
+            async move { handle.invoke_with_priority(Priority::Low, "late").await }
+        });
+        let high = tokio::spawn({
+            let handle = handle.clone();
+            async move { handle.invoke_with_priority(Priority::High, "high").await }
+        });
+        while handle.queue_stats().total() < 3 {
+            tokio::task::yield_now().await;
+        }
+        assert_eq!(
+            handle.queue_stats(),
+            QueueStats {
+                high: 1,
+                normal: 0,
+                low: 2
+            }
+        );
+
+        // the queue timeout passes while the first request still runs
+        assert!(matches!(
+            timed_out.await.unwrap(),
+            Err(AgentError::QueueTimeout(Priority::Low))
+        ));
+        gate.add_permits(1);
+        first.await.unwrap().unwrap();
+        high.await.unwrap().unwrap();
+        low.await.unwrap().unwrap();
+
+        let prompts: Vec<_> = handle
+            .history()
+            .await
+            .unwrap()
+            .into_iter()
+            .filter(|m| m.role == Role::User)
+            .filter_map(|m| m.content)
+            .collect();
+        assert_eq!(prompts, ["first", "high", "low"]);
+    }
+
+    #[tokio::test]
+    async fn control_calls_skip_queued_requests() {
+        fn slow_flow<'a>(agent: &'a mut Agent, prompt: String) -> FlowFuture<'a> {
+            Box::pin(async move {
+                tokio::time::sleep(Duration::from_millis(50)).await;
+                agent.history.push(Message::user(prompt.clone()));
+                Ok(Message::assistant(prompt))
+            })
+        }
+
+        let agent = AgentBuilder::default()
+            .set_model("test-model")
+            .set_flow(slow_flow)
+            .build()
+            .await
+            .unwrap();
+        let handle = agent.into_handle();
+
+        let queued: Vec<_> = ["first", "second", "third"]
+            .into_iter()
+            .map(|prompt| {
+                let handle = handle.clone();
+                tokio::spawn(
+                    async move { handle.invoke_with_priority(Priority::Low, prompt).await },
+                )
+            })
+            .collect();
+        tokio::time::sleep(Duration::from_millis(10)).await;
+
+        // answered after the running request, not after the whole queue
+        let (history, agent) = tokio::time::timeout(
+            Duration::from_millis(90),
+            futures::future::join(handle.history(), handle.clone().shutdown()),
+        )
+        .await
+        .expect("control calls waited behind the queued requests");
+        // system prompt and the first request
+        assert_eq!(history.unwrap().len(), 2);
+        assert_eq!(agent.unwrap().history.len(), 2);
+        let results = futures::future::join_all(queued).await;
+        assert_eq!(results.iter().filter(|r| matches!(r, Ok(Ok(_)))).count(), 1);
+    }
+}
@@ -0,0 +1,325 @@
+use std::{
+    collections::HashMap,
+    fmt,
+    future::Future,
+    pin::Pin,
+    sync::{Arc, Mutex},
+};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use uuid::Uuid;
+
+use crate::{
+    agent::models::tool_audit::unix_millis, services::runtime::SystemTime, Agent, AgentError,
+    Message, Role,
+};
+
+/// Metadata key of the message that replaces archived messages in the
+/// history, holding the id of their [`HistoryCheckpoint`].
+pub const CHECKPOINT_METADATA: &str = "checkpoint";
+
+pub type HistoryArchiveFuture<'a, T> =
+    Pin<Box<dyn Future<Output = Result<T, AgentError>> + Send + 'a>>;
+
+/// Messages evicted from an agent's history by [`HistoryCompression`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoryCheckpoint {
+    pub id: String,
+    /// Name of the agent the messages were taken from.
+    pub agent: String,
+    pub created_at_ms: u64,
+    pub messages: Vec<Message>,
+    /// Summary that replaced the messages in the history, if one was written.
+    pub summary: Option<String>,
+}
+
+/// Storage for [`HistoryCheckpoint`]s, so transcripts stay complete after
+/// their history was compressed. Implement it to keep checkpoints in a
+/// database or object store; [`InMemoryHistoryArchive`] keeps them in the
+/// process.
+pub trait HistoryArchive: Send + Sync {
+    fn store(&self, checkpoint: HistoryCheckpoint) -> HistoryArchiveFuture<'_, ()>;
+
+    /// The checkpoint with `id`, if there is one.
+    fn load<'a>(&'a self, id: &'a str) -> HistoryArchiveFuture<'a, Option<HistoryCheckpoint>>;
+}
+
+impl fmt::Debug for dyn HistoryArchive {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("HistoryArchive")
+    }
+}
+
+/// [`HistoryArchive`] in memory.
+#[derive(Debug, Clone, Default)]
+pub struct InMemoryHistoryArchive {
+    checkpoints: Arc<Mutex<HashMap<String, HistoryCheckpoint>>>,
+}
+
+impl InMemoryHistoryArchive {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Ids of the stored checkpoints.
+    pub fn ids(&self) -> Vec<String> {
+        self.lock().keys().cloned().collect()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<String, HistoryCheckpoint>> {
+        self.checkpoints.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl HistoryArchive for InMemoryHistoryArchive {
+    fn store(&self, checkpoint: HistoryCheckpoint) -> HistoryArchiveFuture<'_, ()> {
+        self.lock().insert(checkpoint.id.clone(), checkpoint);
+        Box::pin(async { Ok(()) })
+    }
+
+    fn load<'a>(&'a self, id: &'a str) -> HistoryArchiveFuture<'a, Option<HistoryCheckpoint>> {
+        let checkpoint = self.lock().get(id).cloned();
+        Box::pin(async { Ok(checkpoint) })
+    }
+}
+
+/// Keeps the history of an agent short by moving old messages into a
+/// [`HistoryArchive`].
+///
+/// Once the history holds more than `max_messages` messages after an
+/// invocation, all but the system prompt and the newest `keep_recent`
+/// messages are stored as a [`HistoryCheckpoint`] and replaced by a single
+/// system message referencing it (see [`CHECKPOINT_METADATA`]), with a
+/// summary of them when [`summarize`](Self::summarize) is on.
+/// [`Agent::full_history`] puts the archived messages back for audits.
+///
+/// ```
+/// use std::sync::Arc;
+/// use reagent_rs::{AgentBuilder, HistoryCompression, InMemoryHistoryArchive};
+///
+/// let compression = HistoryCompression::new(Arc::new(InMemoryHistoryArchive::new()), 40)
+///     .keep_recent(10)
+///     .summarize(true);
+/// let builder = AgentBuilder::default()
+///     .set_model("qwen3:0.6b")
+///     .set_history_compression(compression);
+/// ```
+#[derive(Clone)]
+pub struct HistoryCompression {
+    archive: Arc<dyn HistoryArchive>,
+    max_messages: usize,
+    keep_recent: usize,
+    summarize: bool,
+}
+
+impl HistoryCompression {
+    /// Compress histories longer than `max_messages`, keeping the newest
+    /// half.
+    pub fn new(archive: Arc<dyn HistoryArchive>, max_messages: usize) -> Self {
+        Self {
+            archive,
+            max_messages,
+            keep_recent: max_messages / 2,
+            summarize: false,
+        }
+    }
+
+    /// Number of the newest messages that stay in the history.
+    pub fn keep_recent(mut self, keep_recent: usize) -> Self {
+        self.keep_recent = keep_recent;
+        self
+    }
+
+    /// Replace the archived messages by a summary of them, written by the
+    /// agent's summary agent (see
+    /// [`StatelessPrebuild::SUMMARY_AGENT`](crate::StatelessPrebuild::SUMMARY_AGENT)).
+    pub fn summarize(mut self, summarize: bool) -> Self {
+        self.summarize = summarize;
+        self
+    }
+
+    pub fn archive(&self) -> &Arc<dyn HistoryArchive> {
+        &self.archive
+    }
+}
+
+impl fmt::Debug for HistoryCompression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HistoryCompression")
+            .field("max_messages", &self.max_messages)
+            .field("keep_recent", &self.keep_recent)
+            .field("summarize", &self.summarize)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Agent {
+    /// Archive the older messages of the history as set by
+    /// [`AgentBuilder::set_history_compression`](crate::AgentBuilder::set_history_compression),
+    /// if it is longer than allowed. Returns the id of the new checkpoint.
+    pub async fn compress_history(&mut self) -> Result<Option<String>, AgentError> {
+        let Some(compression) = self.history_compression.clone() else {
+            return Ok(None);
+        };
+        if self.history.len() <= compression.max_messages {
+            return Ok(None);
+        }
+
+        let start = usize::from(self.history.first().is_some_and(|m| m.role == Role::System));
+        let mut end = self
+            .history
+            .len()
+            .saturating_sub(compression.keep_recent)
+            .max(start);
+        // tool results stay with the call they answer
+        while self
+            .history
+            .get(end)
+            .is_some_and(|message| message.role == Role::Tool)
+        {
+            end += 1;
+        }
+        if end <= start || end >= self.history.len() {
+            return Ok(None);
+        }
+
+        let messages = self.history[start..end].to_vec();
+        let summary = if compression.summarize {
+            Some(self.summarize_messages(&messages).await?.summary)
+        } else {
+            None
+        };
+        let checkpoint = HistoryCheckpoint {
+            id: Uuid::new_v4().to_string(),
+            agent: self.name.clone(),
+            created_at_ms: unix_millis(SystemTime::now()),
+            messages,
+            summary,
+        };
+        let id = checkpoint.id.clone();
+        let content = match &checkpoint.summary {
+            Some(summary) => format!("Summary of the earlier conversation: {summary}"),
+            None => format!(
+                "{} earlier messages were archived.",
+                checkpoint.messages.len()
+            ),
+        };
+        compression.archive.store(checkpoint).await?;
+
+        let marker = Message::system(content).with_metadata(CHECKPOINT_METADATA, id.clone());
+        self.history.splice(start..end, [marker]);
+        self.sync_history();
+        tracing::info!(
+            agent = self.name.as_str(),
+            checkpoint = id.as_str(),
+            "History compressed"
+        );
+        Ok(Some(id))
+    }
+
+    /// Compress the history after an invocation. Failures are only logged.
+    pub(crate) async fn auto_compress_history(&mut self) {
+        if let Err(e) = self.compress_history().await {
+            tracing::warn!(agent = self.name.as_str(), error = %e, "Could not compress the history");
+        }
+    }
+
+    /// The messages archived in the checkpoint with `id`.
+    pub async fn restore_checkpoint(&self, id: &str) -> Result<Vec<Message>, AgentError> {
+        let Some(compression) = &self.history_compression else {
+            return Err(AgentError::Runtime(
+                "The agent has no history archive".into(),
+            ));
+        };
+        match compression.archive.load(id).await? {
+            Some(checkpoint) => Ok(checkpoint.messages),
+            None => Err(AgentError::Runtime(format!(
+                "There is no history checkpoint `{id}`"
+            ))),
+        }
+    }
+
+    /// The history with every checkpoint message replaced by the messages
+    /// it archived, also those of earlier checkpoints, for audits.
+    pub async fn full_history(&self) -> Result<Vec<Message>, AgentError> {
+        let mut history = Vec::new();
+        let mut pending: Vec<Message> = self.history.iter().rev().cloned().collect();
+        while let Some(message) = pending.pop() {
+            match message
+                .get_metadata(CHECKPOINT_METADATA)
+                .and_then(Value::as_str)
+            {
+                Some(id) => pending.extend(self.restore_checkpoint(id).await?.into_iter().rev()),
+                None => history.push(message),
+            }
+        }
+        Ok(history)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{testing::FlowTestHarness, AgentBuilder};
+
+    #[tokio::test]
+    async fn compressed_histories_stay_auditable() {
+        let archive = Arc::new(InMemoryHistoryArchive::new());
+        let builder = AgentBuilder::default()
+            .set_model("test")
+            .set_history_compression(HistoryCompression::new(archive.clone(), 4).keep_recent(2));
+        let mut harness = FlowTestHarness::new(builder)
+            .await
+            .unwrap()
+            .reply("One.")
+            .reply("Two.")
+            .reply("Three.");
+
+        for prompt in ["1", "2", "3"] {
+            harness.run(prompt).await.unwrap();
+        }
+
+        // system prompt, checkpoint of the first two exchanges, last exchange
+        let history = harness.history();
+        assert_eq!(history.len(), 4);
+        assert_eq!(history[3].content.as_deref(), Some("Three."));
+        assert!(history[1].get_metadata(CHECKPOINT_METADATA).is_some());
+        assert_eq!(archive.ids().len(), 2);
+
+        let full = harness.agent().full_history().await.unwrap();
+        let contents: Vec<&str> = full.iter().filter_map(|m| m.content.as_deref()).collect();
+        assert_eq!(contents[1..], ["1", "One.", "2", "Two.", "3", "Three."]);
+    }
+
+    #[tokio::test]
+    async fn summaries_carry_over_earlier_checkpoints() {
+        let archive = Arc::new(InMemoryHistoryArchive::new());
+        let compression = HistoryCompression::new(archive, 4)
+            .keep_recent(2)
+            .summarize(true);
+        let builder = AgentBuilder::default()
+            .set_model("test")
+            .set_history_compression(compression);
+        let mut harness = FlowTestHarness::new(builder)
+            .await
+            .unwrap()
+            .reply("One.")
+            .reply("Two.")
+            .reply(r#"{"title": "Counting", "summary": "The user counted to one."}"#)
+            .reply("Three.")
+            .reply(r#"{"title": "Counting", "summary": "The user counted to two."}"#);
+
+        for prompt in ["1", "2", "3"] {
+            harness.run(prompt).await.unwrap();
+        }
+
+        let transcript = harness.requests()[4].messages[1].content.clone().unwrap();
+        assert!(transcript
+            .starts_with("Earlier: Summary of the earlier conversation: The user counted to one."));
+        assert_eq!(
+            harness.history()[1].content.as_deref(),
+            Some("Summary of the earlier conversation: The user counted to two.")
+        );
+    }
+}
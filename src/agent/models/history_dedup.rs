@@ -0,0 +1,192 @@
+use std::collections::HashSet;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    services::llm::message::{Message, TOOL_NAME_METADATA},
+    Role,
+};
+
+/// Opt-in pass replacing repeated tool outputs in the messages sent to the
+/// model with a short reference to the latest copy.
+///
+/// Repeated calls of the same tool (e.g. a RAG lookup) often return the
+/// same, large output. Older copies of a tool result are replaced when they
+/// equal a later one (ignoring whitespace) or, for multi-line outputs, when
+/// at least `similarity` of their lines are shared. Outputs shorter than
+/// `min_chars` are always kept.
+///
+/// Set on the agent with
+/// [`AgentBuilder::set_history_dedup`](crate::AgentBuilder::set_history_dedup),
+/// where it applies to every request and leaves the stored history intact,
+/// or run [`apply`](Self::apply) on a history yourself.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct HistoryDedup {
+    pub min_chars: usize,
+    pub similarity: f32,
+}
+
+impl Default for HistoryDedup {
+    fn default() -> Self {
+        Self {
+            min_chars: 200,
+            similarity: 0.9,
+        }
+    }
+}
+
+impl HistoryDedup {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Outputs shorter than this are never replaced.
+    pub fn min_chars(mut self, min_chars: usize) -> Self {
+        self.min_chars = min_chars;
+        self
+    }
+
+    /// Share of common lines (0.0 - 1.0) from which two outputs count as
+    /// near-identical. `1.0` only replaces exact repeats.
+    pub fn similarity(mut self, similarity: f32) -> Self {
+        self.similarity = similarity.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Replace older repeated tool outputs in `messages`. Returns the number
+    /// of replaced messages.
+    pub fn apply(&self, messages: &mut [Message]) -> usize {
+        // outputs kept so far, newest first: (message index, normalized, lines)
+        let mut kept: Vec<(usize, String, HashSet<String>)> = Vec::new();
+        let mut replaced = 0;
+
+        for index in (0..messages.len()).rev() {
+            let message = &messages[index];
+            if message.role != Role::Tool {
+                continue;
+            }
+            let Some(content) = message
+                .content
+                .as_deref()
+                .filter(|c| c.len() >= self.min_chars)
+            else {
+                continue;
+            };
+
+            let normalized = normalize(content);
+            let lines = lines(content);
+            // an exact repeat is preferred over a near-identical one
+            let duplicate_of = kept
+                .iter()
+                .find(|(_, other, _)| *other == normalized)
+                .map(|(later, _, _)| (*later, true))
+                .or_else(|| {
+                    kept.iter()
+                        .filter(|_| self.similarity < 1.0)
+                        .find(|(_, _, other)| jaccard(&lines, other) >= self.similarity)
+                        .map(|(later, _, _)| (*later, false))
+                });
+
+            match duplicate_of {
+                Some((later, exact)) => {
+                    let reference = reference(&messages[later], exact);
+                    messages[index].content = Some(reference);
+                    replaced += 1;
+                }
+                None => kept.push((index, normalized, lines)),
+            }
+        }
+
+        replaced
+    }
+}
+
+fn normalize(content: &str) -> String {
+    content.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Distinct non-empty lines, trimmed.
+fn lines(content: &str) -> HashSet<String> {
+    content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// Shared lines relative to all lines. Single line outputs only count as
+/// similar when identical, which is handled before.
+fn jaccard(a: &HashSet<String>, b: &HashSet<String>) -> f32 {
+    if a.len() < 2 || b.len() < 2 {
+        return 0.0;
+    }
+    let shared = a.intersection(b).count();
+    let total = a.union(b).count();
+    shared as f32 / total as f32
+}
+
+fn reference(later: &Message, exact: bool) -> String {
+    let tool = later
+        .get_metadata(TOOL_NAME_METADATA)
+        .and_then(|name| name.as_str())
+        .map(|name| format!("`{name}`"))
+        .unwrap_or_else(|| "a tool".to_string());
+    let call = later
+        .tool_call_id
+        .as_deref()
+        .map(|id| format!(" (call {id})"))
+        .unwrap_or_default();
+    let relation = if exact {
+        "identical to"
+    } else {
+        "nearly identical to"
+    };
+    format!("[Output omitted: {relation} the later result of {tool}{call}.]")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tool_result(content: &str, call_id: &str) -> Message {
+        Message::tool(content, call_id).with_metadata(TOOL_NAME_METADATA, "rag_lookup")
+    }
+
+    #[test]
+    fn older_repeats_reference_the_latest_copy() {
+        let document = "chunk one\nchunk two\nchunk three\nchunk four\nchunk five";
+        let mut messages = vec![
+            Message::user("question"),
+            tool_result(document, "call_1"),
+            tool_result("something else\nentirely", "call_2"),
+            tool_result(&document.replace("five", "5"), "call_3"),
+            tool_result(&format!("{document}\n"), "call_4"),
+        ];
+
+        let replaced = HistoryDedup::new()
+            .min_chars(10)
+            .similarity(0.6)
+            .apply(&mut messages);
+
+        assert_eq!(replaced, 2);
+        assert_eq!(
+            messages[1].content.as_deref(),
+            Some("[Output omitted: identical to the later result of `rag_lookup` (call call_4).]")
+        );
+        assert_eq!(
+            messages[3].content.as_deref(),
+            Some("[Output omitted: nearly identical to the later result of `rag_lookup` (call call_4).]")
+        );
+        assert_eq!(messages[1].tool_call_id.as_deref(), Some("call_1"));
+        assert_eq!(
+            messages[2].content.as_deref(),
+            Some("something else\nentirely")
+        );
+        assert!(messages[4]
+            .content
+            .as_deref()
+            .unwrap()
+            .starts_with("chunk one"));
+    }
+}
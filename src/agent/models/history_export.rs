@@ -0,0 +1,203 @@
+use serde_json::{json, Value};
+
+use crate::{services::llm::message::Message, Role, ToolCall};
+
+/// Conversation formats supported by [`Agent::export_history_as`](crate::Agent::export_history_as).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HistoryFormat {
+    /// OpenAI chat fine-tuning record: `{"messages": [...]}` on a single line,
+    /// with tool calls and tool results in OpenAI's shape.
+    OpenAiJsonl,
+    /// ShareGPT record: `{"conversations": [{"from": ..., "value": ...}]}` on a
+    /// single line. Tool calls use `function_call` turns and tool results
+    /// `observation` turns.
+    ShareGpt,
+    /// ChatML text (`<|im_start|>role ... <|im_end|>`). Tool calls are written
+    /// as `<tool_call>` blocks and tool results as `<tool_response>` blocks.
+    ChatMl,
+}
+
+/// Render `messages` as a single record in `format`. Thinking is not exported.
+pub(crate) fn export_messages(messages: &[Message], format: HistoryFormat) -> String {
+    match format {
+        HistoryFormat::OpenAiJsonl => to_openai(messages).to_string(),
+        HistoryFormat::ShareGpt => to_sharegpt(messages).to_string(),
+        HistoryFormat::ChatMl => to_chatml(messages),
+    }
+}
+
+fn to_openai(messages: &[Message]) -> Value {
+    let messages: Vec<Value> = messages
+        .iter()
+        .map(|message| {
+            let mut out = json!({
+                "role": openai_role(&message.role),
+                "content": message.content,
+            });
+            if let Some(calls) = message.tool_calls.as_ref().filter(|c| !c.is_empty()) {
+                out["tool_calls"] = calls.iter().map(openai_tool_call).collect();
+            }
+            if message.role == Role::Tool {
+                if let Some(id) = &message.tool_call_id {
+                    out["tool_call_id"] = json!(id);
+                }
+            }
+            out
+        })
+        .collect();
+
+    json!({ "messages": messages })
+}
+
+fn openai_role(role: &Role) -> &'static str {
+    match role {
+        Role::System | Role::Developer => "system",
+        Role::User => "user",
+        Role::Assistant => "assistant",
+        Role::Tool => "tool",
+    }
+}
+
+fn openai_tool_call(call: &ToolCall) -> Value {
+    json!({
+        "id": call.id,
+        "type": "function",
+        "function": {
+            "name": call.function.name,
+            // OpenAI expects the arguments as a JSON encoded string
+            "arguments": call.function.arguments.to_string(),
+        }
+    })
+}
+
+fn to_sharegpt(messages: &[Message]) -> Value {
+    let mut turns = Vec::new();
+    for message in messages {
+        let from = match message.role {
+            Role::System | Role::Developer => "system",
+            Role::User => "human",
+            Role::Assistant => "gpt",
+            Role::Tool => "observation",
+        };
+        if let Some(content) = message.content.as_deref().filter(|c| !c.is_empty()) {
+            turns.push(json!({ "from": from, "value": content }));
+        }
+        for call in message.tool_calls.iter().flatten() {
+            turns.push(json!({
+                "from": "function_call",
+                "value": tool_call_body(call).to_string(),
+            }));
+        }
+    }
+
+    json!({ "conversations": turns })
+}
+
+fn to_chatml(messages: &[Message]) -> String {
+    let mut out = String::new();
+    for message in messages {
+        let role = match message.role {
+            Role::System | Role::Developer => "system",
+            Role::User => "user",
+            Role::Assistant => "assistant",
+            Role::Tool => "tool",
+        };
+        let mut content = message.content.clone().unwrap_or_default();
+        if message.role == Role::Tool {
+            content = format!("<tool_response>\n{content}\n</tool_response>");
+        }
+        for call in message.tool_calls.iter().flatten() {
+            if !content.is_empty() {
+                content.push('\n');
+            }
+            content.push_str(&format!(
+                "<tool_call>\n{}\n</tool_call>",
+                tool_call_body(call)
+            ));
+        }
+        out.push_str(&format!("<|im_start|>{role}\n{content}<|im_end|>\n"));
+    }
+    out
+}
+
+fn tool_call_body(call: &ToolCall) -> Value {
+    json!({
+        "name": call.function.name,
+        "arguments": call.function.arguments,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ToolCallFunction, ToolType};
+
+    fn conversation() -> Vec<Message> {
+        let mut call = Message::assistant("");
+        call.content = None;
+        call.tool_calls = Some(vec![ToolCall {
+            id: Some("call_1".into()),
+            tool_type: ToolType::Function,
+            function: ToolCallFunction {
+                name: "get_weather".into(),
+                arguments: json!({ "city": "Koper" }),
+            },
+        }]);
+
+        vec![
+            Message::system("You are a weather bot."),
+            Message::user("Weather in Koper?"),
+            call,
+            Message::tool("sunny", "call_1"),
+            Message::assistant("It is sunny."),
+        ]
+    }
+
+    #[test]
+    fn openai_export_encodes_tool_call_arguments_as_string() {
+        let line = export_messages(&conversation(), HistoryFormat::OpenAiJsonl);
+        assert!(!line.contains('\n'));
+
+        let record: Value = serde_json::from_str(&line).unwrap();
+        let messages = record["messages"].as_array().unwrap();
+        assert_eq!(messages.len(), 5);
+        assert_eq!(messages[2]["content"], Value::Null);
+        assert_eq!(
+            messages[2]["tool_calls"][0]["function"]["arguments"],
+            r#"{"city":"Koper"}"#
+        );
+        assert_eq!(messages[3]["tool_call_id"], "call_1");
+    }
+
+    #[test]
+    fn sharegpt_export_uses_function_call_and_observation_turns() {
+        let record: Value =
+            serde_json::from_str(&export_messages(&conversation(), HistoryFormat::ShareGpt))
+                .unwrap();
+        let from: Vec<&str> = record["conversations"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|turn| turn["from"].as_str().unwrap())
+            .collect();
+
+        assert_eq!(
+            from,
+            vec!["system", "human", "function_call", "observation", "gpt"]
+        );
+    }
+
+    #[test]
+    fn chatml_export_wraps_tool_calls_and_results() {
+        let text = export_messages(&conversation(), HistoryFormat::ChatMl);
+
+        assert!(text.starts_with("<|im_start|>system\nYou are a weather bot.<|im_end|>\n"));
+        let call = tool_call_body(&conversation()[2].tool_calls.as_ref().unwrap()[0]);
+        assert!(text.contains(&format!(
+            "<|im_start|>assistant\n<tool_call>\n{call}\n</tool_call><|im_end|>"
+        )));
+        assert!(
+            text.contains("<|im_start|>tool\n<tool_response>\nsunny\n</tool_response><|im_end|>")
+        );
+    }
+}
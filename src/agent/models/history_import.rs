@@ -0,0 +1,251 @@
+use serde_json::Value;
+
+use crate::{
+    services::llm::message::{Message, TOOL_NAME_METADATA},
+    AgentError, Role, ToolCall, ToolCallFunction, ToolType,
+};
+
+/// Parse an OpenAI-style conversation into crate messages.
+///
+/// Accepts a bare message array or a `{"messages": [...]}` record, as written
+/// by [`HistoryFormat::OpenAiJsonl`](crate::HistoryFormat::OpenAiJsonl).
+pub(crate) fn import_messages(value: &Value) -> Result<Vec<Message>, AgentError> {
+    let messages = match value {
+        Value::Array(messages) => messages,
+        Value::Object(record) => match record.get("messages") {
+            Some(Value::Array(messages)) => messages,
+            _ => {
+                return Err(AgentError::Runtime(
+                    "Expected a `messages` array in the conversation record".into(),
+                ))
+            }
+        },
+        _ => {
+            return Err(AgentError::Runtime(
+                "Expected an array of messages or a `{\"messages\": [...]}` record".into(),
+            ))
+        }
+    };
+
+    messages
+        .iter()
+        .enumerate()
+        .map(|(index, message)| {
+            import_message(message)
+                .map_err(|e| AgentError::Runtime(format!("Message {index}: {e}")))
+        })
+        .collect()
+}
+
+/// Parse JSON Lines holding either one message per line or one conversation
+/// per line. In the latter case the last conversation is returned, so a file
+/// collected with [`Agent::append_history_as`](crate::Agent::append_history_as)
+/// continues the most recent session.
+pub(crate) fn import_jsonl(text: &str) -> Result<Vec<Message>, AgentError> {
+    let lines = text
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(serde_json::from_str::<Value>)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(AgentError::Deserialization)?;
+
+    if lines.iter().all(|line| line.get("role").is_some()) {
+        return import_messages(&Value::Array(lines));
+    }
+
+    match lines.last() {
+        Some(conversation) => import_messages(conversation),
+        None => Ok(Vec::new()),
+    }
+}
+
+fn import_message(message: &Value) -> Result<Message, String> {
+    let role = match message.get("role").and_then(Value::as_str) {
+        Some("system") => Role::System,
+        Some("developer") => Role::Developer,
+        Some("user") => Role::User,
+        Some("assistant") => Role::Assistant,
+        Some("tool") | Some("function") => Role::Tool,
+        Some(other) => return Err(format!("unknown role `{other}`")),
+        None => return Err("missing `role`".into()),
+    };
+
+    let (content, images) = import_content(message.get("content"));
+    let mut out = Message::user(String::new());
+    out.role = role;
+    out.content = content;
+    out.images = (!images.is_empty()).then_some(images);
+
+    if let Some(thinking) = message.get("reasoning_content").and_then(Value::as_str) {
+        out.thinking = Some(thinking.to_string());
+    }
+
+    let mut calls = Vec::new();
+    if let Some(tool_calls) = message.get("tool_calls").and_then(Value::as_array) {
+        for call in tool_calls {
+            let function = call.get("function").ok_or("tool call without `function`")?;
+            calls.push(import_tool_call(
+                call.get("id").and_then(Value::as_str),
+                function,
+            )?);
+        }
+    }
+    // Legacy single `function_call` on assistant messages.
+    if let Some(function) = message.get("function_call") {
+        calls.push(import_tool_call(None, function)?);
+    }
+    if !calls.is_empty() {
+        out.tool_calls = Some(calls);
+    }
+
+    if out.role == Role::Tool {
+        out.tool_call_id = message
+            .get("tool_call_id")
+            .and_then(Value::as_str)
+            .map(str::to_string);
+        if let Some(name) = message.get("name").and_then(Value::as_str) {
+            out = out.with_metadata(TOOL_NAME_METADATA, name);
+        }
+    }
+
+    Ok(out)
+}
+
+/// Content is either a string or an array of parts. Text parts are joined,
+/// base64 data URL images are kept as images.
+fn import_content(content: Option<&Value>) -> (Option<String>, Vec<String>) {
+    let mut images = Vec::new();
+    let text = match content {
+        Some(Value::String(text)) => Some(text.clone()),
+        Some(Value::Array(parts)) => {
+            let mut texts = Vec::new();
+            for part in parts {
+                match part.get("type").and_then(Value::as_str) {
+                    Some("text") => {
+                        if let Some(text) = part.get("text").and_then(Value::as_str) {
+                            texts.push(text);
+                        }
+                    }
+                    Some("image_url") => {
+                        let url = part
+                            .get("image_url")
+                            .and_then(|image| image.get("url").or(Some(image)))
+                            .and_then(Value::as_str);
+                        if let Some((_, data)) = url.and_then(|url| url.split_once(";base64,")) {
+                            images.push(data.to_string());
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            Some(texts.join("\n"))
+        }
+        _ => None,
+    };
+    (text, images)
+}
+
+fn import_tool_call(id: Option<&str>, function: &Value) -> Result<ToolCall, String> {
+    let name = function
+        .get("name")
+        .and_then(Value::as_str)
+        .ok_or("tool call without function `name`")?;
+    let arguments = match function.get("arguments") {
+        // OpenAI sends the arguments as a JSON encoded string
+        Some(Value::String(arguments)) if arguments.trim().is_empty() => {
+            Value::Object(Default::default())
+        }
+        Some(Value::String(arguments)) => {
+            serde_json::from_str(arguments).unwrap_or_else(|_| Value::String(arguments.to_string()))
+        }
+        Some(arguments) => arguments.clone(),
+        None => Value::Object(Default::default()),
+    };
+
+    Ok(ToolCall {
+        id: id.map(str::to_string),
+        tool_type: ToolType::Function,
+        function: ToolCallFunction {
+            name: name.to_string(),
+            arguments,
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::agent::models::history_export::{export_messages, HistoryFormat};
+
+    #[test]
+    fn openai_messages_are_mapped_to_crate_messages() {
+        let messages = import_messages(&json!([
+            { "role": "system", "content": "You are a weather bot." },
+            { "role": "user", "content": [{ "type": "text", "text": "Weather in Koper?" }] },
+            {
+                "role": "assistant",
+                "content": null,
+                "tool_calls": [{
+                    "id": "call_1",
+                    "type": "function",
+                    "function": { "name": "get_weather", "arguments": "{\"city\":\"Koper\"}" }
+                }]
+            },
+            { "role": "tool", "tool_call_id": "call_1", "name": "get_weather", "content": "sunny" },
+            { "role": "assistant", "content": "It is sunny." }
+        ]))
+        .unwrap();
+
+        assert_eq!(messages.len(), 5);
+        assert_eq!(messages[1].content.as_deref(), Some("Weather in Koper?"));
+        assert_eq!(messages[2].content, None);
+        let call = &messages[2].tool_calls.as_ref().unwrap()[0];
+        assert_eq!(call.id.as_deref(), Some("call_1"));
+        assert_eq!(call.function.arguments, json!({ "city": "Koper" }));
+        assert_eq!(messages[3].role, Role::Tool);
+        assert_eq!(messages[3].tool_call_id.as_deref(), Some("call_1"));
+        assert_eq!(
+            messages[3].get_metadata(TOOL_NAME_METADATA),
+            Some(&json!("get_weather"))
+        );
+    }
+
+    #[test]
+    fn exported_jsonl_round_trips() {
+        let mut call = Message::assistant("");
+        call.content = None;
+        call.tool_calls = Some(vec![ToolCall {
+            id: Some("call_1".into()),
+            tool_type: ToolType::Function,
+            function: ToolCallFunction {
+                name: "get_weather".into(),
+                arguments: json!({ "city": "Koper" }),
+            },
+        }]);
+        let original = vec![
+            Message::system("You are a weather bot."),
+            Message::user("Weather in Koper?"),
+            call,
+            Message::tool("sunny", "call_1"),
+        ];
+        let file = format!(
+            "{}\n{}\n",
+            export_messages(&original[..2], HistoryFormat::OpenAiJsonl),
+            export_messages(&original, HistoryFormat::OpenAiJsonl)
+        );
+
+        let imported = import_jsonl(&file).unwrap();
+        assert_eq!(
+            export_messages(&imported, HistoryFormat::OpenAiJsonl),
+            export_messages(&original, HistoryFormat::OpenAiJsonl)
+        );
+    }
+
+    #[test]
+    fn unknown_roles_are_rejected() {
+        let err = import_messages(&json!({ "messages": [{ "role": "narrator" }] })).unwrap_err();
+        assert!(err.to_string().contains("unknown role `narrator`"));
+    }
+}
@@ -0,0 +1,164 @@
+use std::{collections::HashSet, fmt, sync::Arc};
+
+use crate::{Agent, Message};
+
+/// Receives changes of an agent's history as they happen, e.g. to mirror
+/// the conversation into a database without diffing `agent.history` after
+/// every call. Register it with
+/// [`AgentBuilder::add_history_observer`](crate::AgentBuilder::add_history_observer).
+///
+/// Changes are reported before and after every model request, at the end of
+/// every invocation and when the history is cleared. Code that edits
+/// `agent.history` directly can report its changes right away with
+/// [`Agent::sync_history`].
+///
+/// Observers are called on the flow's task, so slow work (e.g. writing to a
+/// remote store) should be handed off.
+pub trait HistoryObserver: Send + Sync {
+    /// `message` was appended to (or inserted into) the history of `agent`.
+    fn on_message_added(&self, agent: &str, message: &Message);
+
+    /// The message with `id` was removed from the history, e.g. by a
+    /// moderator or model router.
+    fn on_message_removed(&self, _agent: &str, _id: &str) {}
+
+    /// The history was reset to the system prompt, which is reported as
+    /// added right after.
+    fn on_history_cleared(&self, _agent: &str) {}
+}
+
+impl fmt::Debug for dyn HistoryObserver {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("HistoryObserver")
+    }
+}
+
+/// Observers of an agent with the ids of the messages they were told about.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct HistoryObservers {
+    observers: Vec<Arc<dyn HistoryObserver>>,
+    seen: Vec<String>,
+}
+
+impl HistoryObservers {
+    pub(crate) fn new(observers: Vec<Arc<dyn HistoryObserver>>) -> Self {
+        Self {
+            observers,
+            seen: Vec::new(),
+        }
+    }
+
+    pub(crate) fn add(&mut self, observer: Arc<dyn HistoryObserver>) {
+        self.observers.push(observer);
+    }
+
+    /// Report the messages added to and removed from `history` since the
+    /// last call.
+    fn sync(&mut self, agent: &str, history: &[Message]) {
+        if self.observers.is_empty() {
+            return;
+        }
+
+        let current: HashSet<&str> = history.iter().map(|m| m.id.as_str()).collect();
+        let seen: HashSet<&str> = self.seen.iter().map(String::as_str).collect();
+        for id in self.seen.iter().filter(|id| !current.contains(id.as_str())) {
+            for observer in &self.observers {
+                observer.on_message_removed(agent, id);
+            }
+        }
+        for message in history.iter().filter(|m| !seen.contains(m.id.as_str())) {
+            for observer in &self.observers {
+                observer.on_message_added(agent, message);
+            }
+        }
+
+        self.seen = history.iter().map(|m| m.id.clone()).collect();
+    }
+
+    /// Ids of the messages the observers were told about, replaced by
+    /// `seen`, e.g. to switch to another conversation.
+    pub(crate) fn replace_seen(&mut self, seen: Vec<String>) -> Vec<String> {
+        std::mem::replace(&mut self.seen, seen)
+    }
+
+    fn cleared(&mut self, agent: &str) {
+        for observer in &self.observers {
+            observer.on_history_cleared(agent);
+        }
+        self.seen.clear();
+    }
+}
+
+impl Agent {
+    /// Report changes of the history to the [`HistoryObserver`]s now,
+    /// instead of at the next model request.
+    pub fn sync_history(&mut self) {
+        self.history_observers.sync(&self.name, &self.history);
+    }
+
+    /// Register an observer, see [`HistoryObserver`]. It is told about the
+    /// current history at the next sync.
+    pub fn add_history_observer(&mut self, observer: Arc<dyn HistoryObserver>) {
+        self.history_observers.add(observer);
+        // the new observer has not seen anything yet
+        self.history_observers.seen.clear();
+    }
+
+    pub(crate) fn notify_history_cleared(&mut self) {
+        self.history_observers.cleared(&self.name);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+    use crate::{testing::FlowTestHarness, AgentBuilder};
+
+    #[derive(Default)]
+    struct Recorder {
+        events: Mutex<Vec<String>>,
+    }
+
+    impl HistoryObserver for Recorder {
+        fn on_message_added(&self, _agent: &str, message: &Message) {
+            let event = format!("added {:?}", message.role);
+            self.events.lock().unwrap().push(event);
+        }
+
+        fn on_history_cleared(&self, _agent: &str) {
+            self.events.lock().unwrap().push("cleared".into());
+        }
+    }
+
+    #[tokio::test]
+    async fn observers_see_every_change_once() {
+        let recorder = Arc::new(Recorder::default());
+        let builder = AgentBuilder::default()
+            .set_model("test")
+            .add_history_observer(recorder.clone());
+        let mut harness = FlowTestHarness::new(builder)
+            .await
+            .unwrap()
+            .reply_with_tool_call("search", serde_json::json!({ "query": "rust" }))
+            .expect_tool_call("search", "A language")
+            .reply("Rust is a language.");
+
+        harness.run("What is rust?").await.unwrap();
+        harness.agent_mut().clear_history();
+
+        assert_eq!(
+            *recorder.events.lock().unwrap(),
+            vec![
+                "added System",
+                "added User",
+                "added Assistant",
+                "added Tool",
+                "added Assistant",
+                "cleared",
+                "added System",
+            ]
+        );
+    }
+}
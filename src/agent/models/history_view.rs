@@ -0,0 +1,162 @@
+use serde::Serialize;
+
+use crate::{agent::prepare_messages, Agent, Message, Role};
+
+/// Tokens added per message for the role and formatting.
+const MESSAGE_OVERHEAD: usize = 4;
+
+/// Estimated number of tokens of `messages`: about four characters of
+/// content, thinking and tool calls per token plus a few tokens per
+/// message. Images are not counted.
+pub fn estimate_tokens(messages: &[Message]) -> usize {
+    messages
+        .iter()
+        .map(|message| {
+            let text = [&message.content, &message.thinking]
+                .into_iter()
+                .flatten()
+                .map(|text| text.chars().count())
+                .sum::<usize>();
+            let calls = message
+                .tool_calls
+                .iter()
+                .flatten()
+                .map(|call| call.function.name.len() + call.function.arguments.to_string().len())
+                .sum::<usize>();
+            (text + calls).div_ceil(4) + MESSAGE_OVERHEAD
+        })
+        .sum()
+}
+
+/// The messages the model sees, see [`Agent::history_view`].
+#[derive(Debug, Clone, Serialize)]
+pub struct HistoryView {
+    /// The messages in the order they are sent.
+    pub messages: Vec<Message>,
+    /// Estimated tokens of `messages`, see [`estimate_tokens`].
+    pub estimated_tokens: usize,
+    /// Messages left out at the start of the conversation.
+    pub omitted: usize,
+}
+
+impl Agent {
+    /// The messages the next request would send, trimmed to the newest ones
+    /// that fit into `max_tokens` (e.g. the context size), for front-ends
+    /// showing what the model actually sees.
+    ///
+    /// The history is prepared like for a request (deduplication, tool call
+    /// pairing, context providers and few-shot examples), then rewritten and
+    /// masked like by the client (see
+    /// [`AgentBuilder::set_message_rewriter`](crate::AgentBuilder::set_message_rewriter)
+    /// and [`Redactor`](crate::Redactor)), so the window is trimmed by what
+    /// the provider receives. The view sends no requests: few-shot examples
+    /// are not ranked by embeddings. The leading system messages are always
+    /// kept, and the window never starts with tool results whose calls were
+    /// left out. Token counts are estimates, see [`estimate_tokens`].
+    pub async fn history_view(&self, max_tokens: usize) -> HistoryView {
+        let mut messages = self.history.clone();
+        let mut tools = self.tools.clone();
+        prepare_messages(self, &mut messages, tools.as_mut(), true).await;
+        if let Ok(client) = self.client() {
+            client.preview_messages(&self.model, &mut messages);
+        }
+
+        let pinned = messages
+            .iter()
+            .take_while(|m| matches!(m.role, Role::System | Role::Developer))
+            .count();
+        let mut budget = max_tokens.saturating_sub(estimate_tokens(&messages[..pinned]));
+        let mut start = messages.len();
+        while start > pinned {
+            let cost = estimate_tokens(&messages[start - 1..start]);
+            if cost > budget {
+                break;
+            }
+            budget -= cost;
+            start -= 1;
+        }
+        while messages.get(start).is_some_and(|m| m.role == Role::Tool) {
+            start += 1;
+        }
+
+        messages.drain(pinned..start);
+        HistoryView {
+            estimated_tokens: estimate_tokens(&messages),
+            omitted: start - pinned,
+            messages,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{AgentBuilder, Provider, Redactor};
+
+    #[tokio::test]
+    async fn view_keeps_the_system_prompt_and_newest_messages() {
+        let mut agent = AgentBuilder::default()
+            .set_model("test")
+            .set_system_prompt("You are helpful.")
+            .build()
+            .await
+            .unwrap();
+        agent.history.extend([
+            Message::user("a".repeat(400)),
+            Message::assistant("b".repeat(400)),
+            Message::user("Short question?"),
+            Message::assistant("Short answer."),
+        ]);
+
+        let full = agent.history_view(10_000).await;
+        assert_eq!(full.omitted, 0);
+        assert_eq!(full.estimated_tokens, estimate_tokens(&agent.history));
+
+        let window = agent.history_view(40).await;
+        assert_eq!(window.omitted, 2);
+        let contents: Vec<_> = window
+            .messages
+            .iter()
+            .map(|m| m.content.as_deref().unwrap())
+            .collect();
+        assert_eq!(
+            contents,
+            ["You are helpful.", "Short question?", "Short answer."]
+        );
+        assert!(window.estimated_tokens <= 40);
+    }
+
+    #[tokio::test]
+    async fn view_shows_what_the_provider_receives() {
+        let rewriter = |_: &Provider, _: &str, messages: &mut Vec<Message>| {
+            messages.retain(|m| m.role != Role::Assistant);
+        };
+        let redactor = Redactor::new().emails();
+        let mut agent = AgentBuilder::default()
+            .set_model("test")
+            .set_system_prompt("You are helpful.")
+            .set_message_rewriter(rewriter)
+            .set_redactor(redactor.clone())
+            .build()
+            .await
+            .unwrap();
+        agent.history.extend([
+            Message::user("Mail alice@corp.internal"),
+            Message::assistant("b".repeat(400)),
+            Message::user("Done?"),
+        ]);
+
+        // the long reply is never sent, so it does not push out the rest
+        let view = agent.history_view(40).await;
+        assert_eq!(view.omitted, 0);
+        let contents: Vec<_> = view
+            .messages
+            .iter()
+            .map(|m| m.content.as_deref().unwrap())
+            .collect();
+        assert_eq!(contents, ["You are helpful.", "Mail [EMAIL_1]", "Done?"]);
+
+        // the preview did not hand out the placeholder
+        assert_eq!(redactor.unmask("[EMAIL_1]"), "[EMAIL_1]");
+    }
+}
@@ -0,0 +1,424 @@
+use std::{
+    fmt,
+    future::Future,
+    pin::Pin,
+    sync::{Arc, Mutex, OnceLock},
+};
+
+use regex::Regex;
+use serde_json::Value;
+
+use crate::{
+    agent::models::few_shot::cosine_similarity, services::llm::models::embedding::EmbedRequest,
+    Agent, AgentError, Message, Tool, ToolBuilder, ToolExecutionError,
+};
+
+/// How many memories [`LongTermMemory::recall`] returns by default.
+const DEFAULT_TOP_K: usize = 5;
+
+pub type VectorStoreFuture<'a, T> =
+    Pin<Box<dyn Future<Output = Result<T, AgentError>> + Send + 'a>>;
+
+/// A text with its embedding, as kept in a [`VectorStore`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct VectorRecord {
+    pub id: String,
+    pub text: String,
+    pub embedding: Vec<f64>,
+}
+
+/// Storage for embedded texts, searched by similarity. Implement it to keep
+/// an agent's [`LongTermMemory`] in a vector database;
+/// [`InMemoryVectorStore`] keeps it in the process.
+pub trait VectorStore: Send + Sync {
+    /// Store `record`, replacing a record with the same id.
+    fn upsert(&self, record: VectorRecord) -> VectorStoreFuture<'_, ()>;
+
+    /// The `k` records most similar to `embedding`, most similar first, with
+    /// their similarity.
+    fn search<'a>(
+        &'a self,
+        embedding: &'a [f64],
+        k: usize,
+    ) -> VectorStoreFuture<'a, Vec<(VectorRecord, f64)>>;
+}
+
+impl fmt::Debug for dyn VectorStore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("VectorStore")
+    }
+}
+
+/// [`VectorStore`] in memory, ranking records by cosine similarity.
+#[derive(Debug, Clone, Default)]
+pub struct InMemoryVectorStore {
+    records: Arc<Mutex<Vec<VectorRecord>>>,
+}
+
+impl InMemoryVectorStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn records(&self) -> Vec<VectorRecord> {
+        self.records
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+}
+
+impl VectorStore for InMemoryVectorStore {
+    fn upsert(&self, record: VectorRecord) -> VectorStoreFuture<'_, ()> {
+        let mut records = self.records.lock().unwrap_or_else(|e| e.into_inner());
+        match records.iter_mut().find(|r| r.id == record.id) {
+            Some(existing) => *existing = record,
+            None => records.push(record),
+        }
+        Box::pin(async { Ok(()) })
+    }
+
+    fn search<'a>(
+        &'a self,
+        embedding: &'a [f64],
+        k: usize,
+    ) -> VectorStoreFuture<'a, Vec<(VectorRecord, f64)>> {
+        let mut scored: Vec<(VectorRecord, f64)> = self
+            .records()
+            .into_iter()
+            .map(|record| {
+                let score = cosine_similarity(embedding, &record.embedding);
+                (record, score)
+            })
+            .collect();
+        scored.sort_by(|a, b| b.1.total_cmp(&a.1));
+        scored.truncate(k);
+        Box::pin(async { Ok(scored) })
+    }
+}
+
+pub type EmbedFuture = Pin<Box<dyn Future<Output = Result<Vec<f64>, AgentError>> + Send>>;
+/// Embeds a text for a [`LongTermMemory`].
+pub type EmbedFn = Arc<dyn Fn(String) -> EmbedFuture + Send + Sync>;
+/// Picks the facts worth remembering from a prompt and the reply to it.
+pub type FactExtractor = Arc<dyn Fn(&str, &Message) -> Vec<String> + Send + Sync>;
+
+/// Long-term memory of an agent, kept in a [`VectorStore`].
+///
+/// An agent with a memory (see
+/// [`AgentBuilder::set_memory`](crate::AgentBuilder::set_memory)) gets a
+/// `remember` tool to store facts and a `recall` tool to look them up by
+/// similarity. With [`auto_extract`](Self::auto_extract) or an
+/// [`extractor`](Self::extractor), the facts found in every successful
+/// invocation are stored as well. The default extractor keeps the
+/// statements of the prompt that tell something about the user, like "My
+/// name is Ana" or "I prefer short answers".
+///
+/// Texts are embedded with the agent's embedding model (see
+/// [`Agent::embed`]) unless [`embed_with`](Self::embed_with) is used.
+///
+/// ```
+/// use std::sync::Arc;
+/// use reagent_rs::{AgentBuilder, InMemoryVectorStore, LongTermMemory};
+///
+/// let memory = LongTermMemory::new(Arc::new(InMemoryVectorStore::new())).top_k(3);
+/// let builder = AgentBuilder::default()
+///     .set_model("qwen3:0.6b")
+///     .set_embedding_model("nomic-embed-text")
+///     .set_memory(memory);
+/// ```
+#[derive(Clone)]
+pub struct LongTermMemory {
+    store: Arc<dyn VectorStore>,
+    top_k: usize,
+    extractor: Option<FactExtractor>,
+    embed: Option<EmbedFn>,
+}
+
+impl LongTermMemory {
+    pub fn new(store: Arc<dyn VectorStore>) -> Self {
+        Self {
+            store,
+            top_k: DEFAULT_TOP_K,
+            extractor: None,
+            embed: None,
+        }
+    }
+
+    /// Number of memories returned by a recall, 5 by default.
+    pub fn top_k(mut self, k: usize) -> Self {
+        self.top_k = k.max(1);
+        self
+    }
+
+    /// Store the facts `extractor` returns for every prompt and reply,
+    /// turning on [`auto_extract`](Self::auto_extract).
+    pub fn extractor<F>(mut self, extractor: F) -> Self
+    where
+        F: Fn(&str, &Message) -> Vec<String> + Send + Sync + 'static,
+    {
+        self.extractor = Some(Arc::new(extractor));
+        self
+    }
+
+    /// Whether facts are extracted after every invocation, off by default.
+    /// Without it, only the `remember` tool stores facts.
+    pub fn auto_extract(mut self, enabled: bool) -> Self {
+        if !enabled {
+            self.extractor = None;
+        } else if self.extractor.is_none() {
+            self.extractor = Some(Arc::new(extract_user_facts));
+        }
+        self
+    }
+
+    /// Embed texts with `embed` instead of the agent's embedding model.
+    pub fn embed_with<F, Fut>(mut self, embed: F) -> Self
+    where
+        F: Fn(String) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<Vec<f64>, AgentError>> + Send + 'static,
+    {
+        self.embed = Some(Arc::new(move |text| Box::pin(embed(text))));
+        self
+    }
+
+    /// Store `fact`. Storing the same fact again replaces it.
+    pub async fn remember(&self, fact: impl Into<String>) -> Result<(), AgentError> {
+        let text = fact.into().trim().to_string();
+        let embedding = self.embed(text.clone()).await?;
+        let record = VectorRecord {
+            id: fact_id(&text),
+            text,
+            embedding,
+        };
+        self.store.upsert(record).await
+    }
+
+    /// The stored facts most similar to `query`, most similar first.
+    pub async fn recall(&self, query: impl Into<String>) -> Result<Vec<String>, AgentError> {
+        let embedding = self.embed(query.into()).await?;
+        let records = self.store.search(&embedding, self.top_k).await?;
+        Ok(records.into_iter().map(|(record, _)| record.text).collect())
+    }
+
+    async fn embed(&self, text: String) -> Result<Vec<f64>, AgentError> {
+        let Some(embed) = &self.embed else {
+            return Err(AgentError::Runtime(
+                "The memory has no embedding model, add it to an agent or use `embed_with`".into(),
+            ));
+        };
+        embed(text).await
+    }
+
+    /// The memory with texts embedded by `agent`, unless it has its own
+    /// embedding function.
+    pub(crate) fn bind(mut self, agent: &Agent) -> Self {
+        if self.embed.is_none() {
+            let client = agent.inference_client.clone();
+            let model = agent.embedding_model().to_string();
+            let keep_alive = agent.keep_alive.clone();
+            self.embed = Some(Arc::new(move |text| {
+                let client = client.clone();
+                let mut request = EmbedRequest::new(model.clone(), [text]);
+                request.keep_alive = keep_alive.clone();
+                Box::pin(async move {
+                    client
+                        .embed(request)
+                        .await?
+                        .embeddings
+                        .pop()
+                        .ok_or_else(|| AgentError::Runtime("Embedding response was empty".into()))
+                })
+            }));
+        }
+        self
+    }
+
+    /// Store the facts the extractor finds in `prompt` and `reply`.
+    pub(crate) async fn extract(&self, prompt: &str, reply: &Message) {
+        let Some(extractor) = &self.extractor else {
+            return;
+        };
+        for fact in extractor(prompt, reply) {
+            if let Err(e) = self.remember(fact).await {
+                tracing::warn!(error = %e, "Failed to store an extracted fact");
+            }
+        }
+    }
+
+    /// The `remember` and `recall` tools.
+    pub(crate) fn tools(&self) -> Vec<Tool> {
+        let memory = self.clone();
+        let remember = ToolBuilder::new()
+            .function_name("remember")
+            .function_description(
+                "Stores a fact in your long-term memory, so you can recall it in later \
+                 conversations. Use it for facts about the user and their preferences.",
+            )
+            .add_required_property(
+                "fact",
+                "string",
+                "The fact as a self-contained sentence, e.g. `The user's dog is called Rex.`",
+            )
+            .executor_fn(move |args: Value| {
+                let memory = memory.clone();
+                async move {
+                    let fact = string_argument(&args, "fact")?;
+                    memory
+                        .remember(fact)
+                        .await
+                        .map_err(|e| ToolExecutionError::ExecutionFailed(e.to_string()))?;
+                    Ok("Remembered.".to_string())
+                }
+            })
+            .build()
+            .expect("the remember tool is fully defined");
+
+        let memory = self.clone();
+        let recall = ToolBuilder::new()
+            .function_name("recall")
+            .function_description(
+                "Searches your long-term memory for facts related to a query, e.g. what you \
+                 know about the user.",
+            )
+            .add_required_property("query", "string", "What to look for.")
+            .executor_fn(move |args: Value| {
+                let memory = memory.clone();
+                async move {
+                    let query = string_argument(&args, "query")?;
+                    let facts = memory
+                        .recall(query)
+                        .await
+                        .map_err(|e| ToolExecutionError::ExecutionFailed(e.to_string()))?;
+                    if facts.is_empty() {
+                        return Ok("No memories found.".to_string());
+                    }
+                    Ok(facts
+                        .iter()
+                        .map(|fact| format!("- {fact}"))
+                        .collect::<Vec<_>>()
+                        .join("\n"))
+                }
+            })
+            .build()
+            .expect("the recall tool is fully defined");
+
+        vec![remember, recall]
+    }
+}
+
+impl fmt::Debug for LongTermMemory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LongTermMemory")
+            .field("store", &self.store)
+            .field("top_k", &self.top_k)
+            .field("extractor", &self.extractor.as_ref().map(|_| "<fn>"))
+            .field("embed", &self.embed.as_ref().map(|_| "<fn>"))
+            .finish()
+    }
+}
+
+fn string_argument(args: &Value, name: &str) -> Result<String, ToolExecutionError> {
+    args.get(name)
+        .and_then(Value::as_str)
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(str::to_string)
+        .ok_or_else(|| {
+            ToolExecutionError::ArgumentParsingError(format!(
+                "a non-empty string `{name}` argument is required"
+            ))
+        })
+}
+
+/// FNV-1a of the lowercased text, which unlike the std hashers stays the
+/// same across processes and Rust versions, so a fact stored again replaces
+/// the record a previous run stored.
+fn fact_id(text: &str) -> String {
+    let hash = text
+        .to_lowercase()
+        .bytes()
+        .fold(0xcbf2_9ce4_8422_2325_u64, |hash, byte| {
+            (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+        });
+    format!("{hash:016x}")
+}
+
+/// Statements (not questions) of the prompt in which the user tells
+/// something about themselves.
+fn extract_user_facts(prompt: &str, _reply: &Message) -> Vec<String> {
+    static SENTENCE: OnceLock<Regex> = OnceLock::new();
+    static PERSONAL: OnceLock<Regex> = OnceLock::new();
+    let sentence =
+        SENTENCE.get_or_init(|| Regex::new(r"[^.!?\n]+[.!?]?").expect("valid sentence regex"));
+    let personal = PERSONAL.get_or_init(|| {
+        Regex::new(
+            r"(?i)\b(my|i am|i'm|i live|i work|i prefer|i like|i love|i hate|i have|call me|remember that)\b",
+        )
+        .expect("valid personal statement regex")
+    });
+
+    sentence
+        .find_iter(prompt)
+        .map(|m| m.as_str().trim())
+        .filter(|s| !s.ends_with('?') && personal.is_match(s))
+        .map(str::to_string)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Embeds texts by which of a few words they contain.
+    fn keyword_embedding(text: String) -> EmbedFuture {
+        let text = text.to_lowercase();
+        let embedding = ["dog", "coffee", "berlin"]
+            .iter()
+            .map(|word| f64::from(u8::from(text.contains(word))))
+            .collect();
+        Box::pin(async move { Ok(embedding) })
+    }
+
+    #[tokio::test]
+    async fn facts_are_recalled_by_similarity() {
+        let store = InMemoryVectorStore::new();
+        let memory = LongTermMemory::new(Arc::new(store.clone()))
+            .top_k(1)
+            .embed_with(keyword_embedding);
+
+        memory
+            .remember("The user's dog is called Rex.")
+            .await
+            .unwrap();
+        memory
+            .remember("The user drinks coffee black.")
+            .await
+            .unwrap();
+        memory
+            .remember("The user drinks coffee black.")
+            .await
+            .unwrap();
+        assert_eq!(store.records().len(), 2);
+
+        let recalled = memory.recall("What is my dog's name?").await.unwrap();
+        assert_eq!(recalled, ["The user's dog is called Rex."]);
+
+        let prompt = "I live in Berlin. What's the weather like? My dog hates rain.";
+        memory.extract(prompt, &Message::assistant("Sunny.")).await;
+        assert_eq!(store.records().len(), 2);
+        let memory = memory.auto_extract(true);
+        memory.extract(prompt, &Message::assistant("Sunny.")).await;
+        let texts: Vec<String> = store.records().into_iter().map(|r| r.text).collect();
+        assert!(texts.contains(&"I live in Berlin.".to_string()));
+        assert!(texts.contains(&"My dog hates rain.".to_string()));
+        assert_eq!(texts.len(), 4);
+    }
+
+    #[test]
+    fn fact_ids_are_stable() {
+        assert_eq!(fact_id(""), "cbf29ce484222325");
+        assert_eq!(fact_id("A"), "af63dc4c8601ec8c");
+        assert_eq!(fact_id("a"), fact_id("A"));
+    }
+}
@@ -1,9 +1,75 @@
 mod agent;
 mod agent_builder;
+mod background;
+mod cancel;
+mod concurrency;
 mod configs;
+mod debugger;
+mod definition;
 mod error;
+mod event_source;
+mod experiment;
+mod few_shot;
+mod flow_stream;
+mod handle;
+mod history_compression;
+mod history_dedup;
+mod history_export;
+mod history_import;
+mod history_observer;
+mod history_view;
+mod memory;
+mod moderation;
+mod output;
+mod output_strategy;
+mod preset;
+mod registry;
+mod replay;
+mod router;
+mod snapshot;
+mod supervisor;
+mod tenant;
+mod tool_audit;
 
 pub use agent::*;
 pub use agent_builder::*;
+pub(crate) use cancel::FlowProgress;
+pub use cancel::{AbortReason, CancelScope, PartialResult};
 pub use configs::*;
+pub use debugger::{DebugHandle, DebugPoint, DebugSnapshot};
+pub use definition::{AgentDefinition, ClientDefinition, PromptDefinition, ToolDescriptor};
 pub use error::*;
+pub use event_source::{AgentEvent, EventFuture, EventSource, RunSummary};
+pub use experiment::{PromptExperiment, PromptVariant, VariantStats};
+pub(crate) use few_shot::{current_prompt, insert_examples};
+pub use few_shot::{FewShotExample, FewShotSet};
+pub use flow_stream::FlowEvent;
+pub use handle::{AgentHandle, Priority, QueueStats};
+pub use history_compression::{
+    HistoryArchive, HistoryArchiveFuture, HistoryCheckpoint, HistoryCompression,
+    InMemoryHistoryArchive, CHECKPOINT_METADATA,
+};
+pub use history_dedup::HistoryDedup;
+pub use history_export::HistoryFormat;
+pub use history_observer::HistoryObserver;
+pub use history_view::{estimate_tokens, HistoryView};
+pub use memory::{
+    EmbedFn, EmbedFuture, FactExtractor, InMemoryVectorStore, LongTermMemory, VectorRecord,
+    VectorStore, VectorStoreFuture,
+};
+pub use moderation::{
+    KeywordAction, KeywordModerator, LlmModerator, ModerationAction, ModerationFuture,
+    ModerationVerdict, Moderator, MODERATION_METADATA,
+};
+pub use output::*;
+pub use output_strategy::StructuredOutputStrategy;
+pub(crate) use output_strategy::{repair_structured_output, schema_instructions};
+pub use preset::ModelPreset;
+pub use registry::{AgentFactory, AgentRegistry};
+pub use replay::*;
+pub use router::{DifficultyClassifier, EscalationCheck, ModelProfile, ModelRouter, RoutingPolicy};
+pub use snapshot::{AgentSnapshot, McpServerSnapshot, TokenUsage, ToolSnapshot};
+pub use supervisor::{AlertFn, NotificationCheck, Supervisor, SupervisorAction, SupervisorAlert};
+pub use tenant::TenantContext;
+pub(crate) use tool_audit::{hash_arguments, unix_millis};
+pub use tool_audit::{ToolAuditEntry, ToolStats};
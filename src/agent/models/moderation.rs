@@ -0,0 +1,318 @@
+use std::{fmt, future::Future, pin::Pin};
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::{Agent, AgentBuilder, AgentError, AgentHandle, Message, StatelessPrebuild};
+
+/// Metadata key under which the reasons of an annotated or rewritten reply
+/// are stored.
+pub const MODERATION_METADATA: &str = "moderation";
+
+/// Future returned by [`Moderator::moderate`].
+pub type ModerationFuture<'a> =
+    Pin<Box<dyn Future<Output = Result<ModerationVerdict, AgentError>> + Send + 'a>>;
+
+/// Checks the final reply of a flow before it is returned, see
+/// [`AgentBuilder::set_moderator`](crate::AgentBuilder::set_moderator).
+pub trait Moderator: Send + Sync {
+    /// Decide what happens to `reply`, the answer to `prompt`.
+    fn moderate<'a>(&'a self, prompt: &'a str, reply: &'a Message) -> ModerationFuture<'a>;
+}
+
+impl fmt::Debug for dyn Moderator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Moderator")
+    }
+}
+
+/// What happens to a moderated reply.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ModerationAction {
+    /// Return the reply as it is.
+    Allow,
+    /// Return the reply with the reasons in its
+    /// [`MODERATION_METADATA`] metadata.
+    Annotate,
+    /// Replace the content of the reply.
+    Rewrite(String),
+    /// Drop the reply from the history and fail the flow with
+    /// [`AgentError::Blocked`].
+    Block,
+}
+
+/// Decision of a [`Moderator`], emitted as
+/// [`NotificationContent::Moderated`](crate::NotificationContent::Moderated)
+/// unless the reply is allowed.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ModerationVerdict {
+    pub action: ModerationAction,
+    pub reasons: Vec<String>,
+}
+
+impl ModerationVerdict {
+    pub fn new(action: ModerationAction, reasons: Vec<String>) -> Self {
+        Self { action, reasons }
+    }
+
+    pub fn allow() -> Self {
+        Self::new(ModerationAction::Allow, Vec::new())
+    }
+}
+
+/// What a [`KeywordModerator`] does with a reply that matches one of its
+/// rules.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeywordAction {
+    Block,
+    Annotate,
+    /// Replace the matches with `***`.
+    Mask,
+}
+
+/// Moderates replies with keyword and regex rules.
+///
+/// ```
+/// use reagent_rs::{KeywordAction, KeywordModerator};
+///
+/// let moderator = KeywordModerator::new(KeywordAction::Mask)
+///     .keyword("confidential")
+///     .pattern("api key", r"sk-[A-Za-z0-9]{20,}")
+///     .unwrap();
+/// ```
+#[derive(Debug, Clone)]
+pub struct KeywordModerator {
+    rules: Vec<(String, Regex)>,
+    action: KeywordAction,
+}
+
+impl KeywordModerator {
+    pub fn new(action: KeywordAction) -> Self {
+        Self {
+            rules: Vec::new(),
+            action,
+        }
+    }
+
+    /// Match `keyword` as a whole word, ignoring case.
+    pub fn keyword(mut self, keyword: &str) -> Self {
+        let pattern = format!(r"(?i)\b{}\b", regex::escape(keyword));
+        let pattern = Regex::new(&pattern).expect("escaped keyword is a valid pattern");
+        self.rules.push((keyword.to_string(), pattern));
+        self
+    }
+
+    /// Match `pattern`, reported as `label` in the reasons.
+    pub fn pattern(mut self, label: &str, pattern: &str) -> Result<Self, regex::Error> {
+        self.rules.push((label.to_string(), Regex::new(pattern)?));
+        Ok(self)
+    }
+
+    fn verdict(&self, text: &str) -> ModerationVerdict {
+        let matched: Vec<&(String, Regex)> = self
+            .rules
+            .iter()
+            .filter(|(_, pattern)| pattern.is_match(text))
+            .collect();
+        if matched.is_empty() {
+            return ModerationVerdict::allow();
+        }
+
+        let reasons = matched
+            .iter()
+            .map(|(label, _)| format!("matched `{label}`"))
+            .collect();
+        let action = match self.action {
+            KeywordAction::Block => ModerationAction::Block,
+            KeywordAction::Annotate => ModerationAction::Annotate,
+            KeywordAction::Mask => {
+                let mut masked = text.to_string();
+                for (_, pattern) in matched {
+                    masked = pattern.replace_all(&masked, "***").into_owned();
+                }
+                ModerationAction::Rewrite(masked)
+            }
+        };
+        ModerationVerdict::new(action, reasons)
+    }
+}
+
+impl Moderator for KeywordModerator {
+    fn moderate<'a>(&'a self, _prompt: &'a str, reply: &'a Message) -> ModerationFuture<'a> {
+        let verdict = self.verdict(reply.content.as_deref().unwrap_or_default());
+        Box::pin(std::future::ready(Ok(verdict)))
+    }
+}
+
+const JUDGE_SYSTEM_PROMPT: &str = r#"You are a **Moderation Agent**. You will be given a user's request and the response an assistant wants to send.
+
+Your task is to decide whether the response may be sent:
+- "allow": the response is fine.
+- "annotate": the response may be sent, but should be flagged for review.
+- "rewrite": the response must be changed; provide the full corrected response in "rewrite".
+- "block": the response must not be sent at all.
+
+List the policy problems you found in "reasons" (empty when allowed).
+
+**Output Format:**
+Respond only with a JSON object. Do not add any explanations.
+
+**Example:**
+{
+    "action": "rewrite",
+    "reasons": ["contains a phone number of a private person"],
+    "rewrite": "You can reach the office through the contact form."
+}
+"#;
+
+const JUDGE_RESPONSE_FORMAT: &str = r#"
+{
+    "type": "object",
+    "properties": {
+        "action": { "type": "string", "enum": ["allow", "annotate", "rewrite", "block"] },
+        "reasons": { "type": "array", "items": { "type": "string" } },
+        "rewrite": { "type": "string" }
+    },
+    "required": ["action", "reasons"]
+}
+"#;
+
+#[derive(Deserialize)]
+struct JudgeVerdict {
+    action: String,
+    #[serde(default)]
+    reasons: Vec<String>,
+    rewrite: Option<String>,
+}
+
+/// Moderates replies with a judge agent that answers in JSON.
+///
+/// ```no_run
+/// use reagent_rs::LlmModerator;
+///
+/// async {
+///     let judge = LlmModerator::builder()
+///         .set_model("qwen3:8b")
+///         .build()
+///         .await
+///         .unwrap();
+///     let moderator = LlmModerator::new(judge);
+/// };
+/// ```
+#[derive(Debug, Clone)]
+pub struct LlmModerator {
+    judge: AgentHandle,
+}
+
+impl LlmModerator {
+    /// Builder with the system prompt and response format of the judge, set
+    /// the model and provider before building.
+    pub fn builder() -> AgentBuilder {
+        StatelessPrebuild::reply_without_tools()
+            .set_name("moderator")
+            .set_system_prompt(JUDGE_SYSTEM_PROMPT)
+            .set_temperature(0.0)
+            .set_response_format_str(JUDGE_RESPONSE_FORMAT)
+    }
+
+    pub fn new(judge: Agent) -> Self {
+        Self {
+            judge: judge.into_handle(),
+        }
+    }
+}
+
+impl Moderator for LlmModerator {
+    fn moderate<'a>(&'a self, prompt: &'a str, reply: &'a Message) -> ModerationFuture<'a> {
+        Box::pin(async move {
+            let content = reply.content.as_deref().unwrap_or_default();
+            let verdict = self
+                .judge
+                .invoke(format!(
+                    "# User request:\n\n{prompt}\n\n# Response:\n\n{content}"
+                ))
+                .await?;
+            parse_judge_verdict(&verdict)
+        })
+    }
+}
+
+fn parse_judge_verdict(verdict: &Message) -> Result<ModerationVerdict, AgentError> {
+    let content = verdict.content.as_deref().unwrap_or_default();
+    let verdict: JudgeVerdict = serde_json::from_str(content)
+        .map_err(|e| AgentError::Runtime(format!("Moderator failed to return valid JSON: {e}")))?;
+
+    let action = match (verdict.action.as_str(), verdict.rewrite) {
+        ("allow", _) => ModerationAction::Allow,
+        ("annotate", _) => ModerationAction::Annotate,
+        ("rewrite", Some(rewrite)) => ModerationAction::Rewrite(rewrite),
+        ("block", _) => ModerationAction::Block,
+        ("rewrite", None) => {
+            return Err(AgentError::Runtime(
+                "Moderator asked for a rewrite without providing one".into(),
+            ))
+        }
+        (other, _) => {
+            return Err(AgentError::Runtime(format!(
+                "Moderator returned an unknown action `{other}`"
+            )))
+        }
+    };
+    Ok(ModerationVerdict::new(action, verdict.reasons))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{AgentBuilder, FlowFuture, NotificationContent};
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn moderators_rewrite_and_block_final_replies() {
+        fn leaky<'a>(agent: &'a mut Agent, prompt: String) -> FlowFuture<'a> {
+            Box::pin(async move {
+                let reply = Message::assistant(format!("The password is {prompt}"));
+                agent.history.push(reply.clone());
+                agent.moderate(&prompt, reply).await
+            })
+        }
+
+        let (mut agent, mut notifications) = AgentBuilder::default()
+            .set_model("m")
+            .set_flow(leaky)
+            .set_moderator(Arc::new(
+                KeywordModerator::new(KeywordAction::Mask).keyword("hunter2"),
+            ))
+            .build_with_notification()
+            .await
+            .unwrap();
+
+        let reply = agent.invoke_flow("hunter2").await.unwrap();
+        assert_eq!(reply.content.as_deref(), Some("The password is ***"));
+        assert_eq!(agent.history.last().unwrap().content, reply.content);
+        let verdict = std::iter::from_fn(|| notifications.try_recv().ok())
+            .find_map(|n| match n.content {
+                NotificationContent::Moderated(verdict) => Some(verdict),
+                _ => None,
+            })
+            .unwrap();
+        assert_eq!(verdict.reasons, ["matched `hunter2`"]);
+
+        agent.moderator = Some(Arc::new(
+            KeywordModerator::new(KeywordAction::Block).keyword("password"),
+        ));
+        let history_len = agent.history.len();
+        let err = agent.invoke_flow("swordfish").await.unwrap_err();
+        assert!(matches!(err, AgentError::Blocked(reasons) if reasons == ["matched `password`"]));
+        // the blocked reply is dropped from the history
+        assert_eq!(agent.history.len(), history_len);
+
+        let judged =
+            Message::assistant(r#"{"action": "rewrite", "reasons": ["pii"], "rewrite": "ok"}"#);
+        assert_eq!(
+            parse_judge_verdict(&judged).unwrap().action,
+            ModerationAction::Rewrite("ok".into())
+        );
+    }
+}
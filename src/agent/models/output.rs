@@ -0,0 +1,124 @@
+use serde::de::DeserializeOwned;
+
+use crate::{services::llm::SchemaSpec, AgentError, Message};
+
+/// Parse a typed value out of a model [`Message`].
+///
+/// Usually implemented with `#[derive(AgentOutput)]`, which routes through
+/// [`parse_structured_output`].
+pub trait FromMessage: Sized {
+    fn from_message(message: &Message) -> Result<Self, AgentError>;
+}
+
+/// A type that can be requested from an agent as structured output.
+///
+/// Derive it together with `Deserialize` and `JsonSchema`:
+///
+/// ```
+/// use reagent_rs::{AgentOutput, AgentBuilder, JsonSchema};
+/// use serde::Deserialize;
+///
+/// /// Current weather conditions
+/// #[derive(Debug, Deserialize, JsonSchema, AgentOutput)]
+/// #[agent_output(name = "weather", strict)]
+/// struct Weather {
+///     /// Whether it is windy outside
+///     windy: bool,
+///     /// Temperature in degrees Celsius
+///     temperature: i32,
+/// }
+///
+/// let builder = AgentBuilder::default()
+///     .set_model("qwen3:0.6b")
+///     .set_response_format_output::<Weather>();
+/// ```
+///
+/// Field doc comments end up as `description`s in the generated schema,
+/// which helps smaller models fill in the right values.
+pub trait AgentOutput: FromMessage {
+    /// The schema (plus provider hints) used as the agent's response format.
+    fn response_format() -> SchemaSpec;
+}
+
+/// Deserialize the content of a message into `O`.
+///
+/// More forgiving than a plain `serde_json::from_str`: `<think>` blocks,
+/// markdown code fences and text surrounding the JSON payload are ignored.
+pub fn parse_structured_output<O: DeserializeOwned>(message: &Message) -> Result<O, AgentError> {
+    let Some(content) = &message.content else {
+        return Err(AgentError::Runtime("Agent did not produce content".into()));
+    };
+
+    match serde_json::from_str::<O>(content) {
+        Ok(out) => Ok(out),
+        Err(e) => match extract_json_payload(content) {
+            Some(payload) => {
+                serde_json::from_str::<O>(payload).map_err(AgentError::Deserialization)
+            }
+            None => Err(AgentError::Deserialization(e)),
+        },
+    }
+}
+
+pub(crate) fn extract_json_payload(content: &str) -> Option<&str> {
+    let content = match content.rfind("</think>") {
+        Some(pos) => &content[pos + "</think>".len()..],
+        None => content,
+    };
+
+    let start = content.find(['{', '['])?;
+    let closing = if content[start..].starts_with('{') {
+        '}'
+    } else {
+        ']'
+    };
+    let end = content.rfind(closing)?;
+    (end > start).then(|| &content[start..=end])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::JsonSchema;
+    use serde::Deserialize;
+
+    /// A city forecast
+    #[derive(Debug, Deserialize, JsonSchema, crate::AgentOutput)]
+    #[agent_output(strict)]
+    struct CityForecast {
+        /// Name of the city
+        city: String,
+        /// Temperature in degrees Celsius
+        temperature: i32,
+    }
+
+    #[test]
+    fn derive_uses_doc_comments_and_type_name() {
+        let spec = CityForecast::response_format();
+
+        assert_eq!(spec.name.as_deref(), Some("city_forecast"));
+        assert_eq!(spec.strict, Some(true));
+        assert_eq!(
+            spec.schema["properties"]["city"]["description"],
+            "Name of the city"
+        );
+    }
+
+    #[test]
+    fn parses_fenced_output_after_thinking() {
+        let message = Message::assistant(
+            "<think>hmm</think>\nHere you go:\n```json\n{\"city\":\"Koper\",\"temperature\":21}\n```",
+        );
+
+        let forecast = CityForecast::from_message(&message).unwrap();
+        assert_eq!(forecast.city, "Koper");
+        assert_eq!(forecast.temperature, 21);
+    }
+
+    #[test]
+    fn invalid_output_is_a_deserialization_error() {
+        let message = Message::assistant("no json here");
+        let err = CityForecast::from_message(&message).unwrap_err();
+        assert!(matches!(err, AgentError::Deserialization(_)));
+    }
+}
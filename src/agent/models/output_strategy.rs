@@ -0,0 +1,90 @@
+use serde_json::Value;
+
+use crate::{agent::models::output::extract_json_payload, Message};
+
+/// How a response format is requested from the provider, see
+/// [`AgentBuilder::set_structured_output_strategy`](crate::AgentBuilder::set_structured_output_strategy).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum StructuredOutputStrategy {
+    /// Use the provider's structured output support. Building fails on
+    /// providers without it.
+    #[default]
+    Native,
+    /// Describe the schema in the system prompt, enable the provider's JSON
+    /// mode where available, and validate and repair the replies.
+    Prompt,
+    /// `Native` where the provider supports it, `Prompt` otherwise.
+    Auto,
+}
+
+/// Instructions appended to the system prompt in `Prompt` mode.
+pub(crate) fn schema_instructions(schema: &Value) -> String {
+    let schema = serde_json::to_string_pretty(schema).unwrap_or_else(|_| schema.to_string());
+    format!(
+        "Respond only with a JSON value that matches this JSON schema, without any other text:\n```json\n{schema}\n```"
+    )
+}
+
+/// Reduce the content of `message` to its JSON payload and check it against
+/// the top level of `schema`. Returns the problems that remain; the content
+/// is only replaced when a payload was found.
+pub(crate) fn repair_structured_output(message: &mut Message, schema: &Value) -> Vec<String> {
+    let Some(content) = message.content.as_deref() else {
+        return vec!["the reply has no content".into()];
+    };
+
+    let value = match serde_json::from_str::<Value>(content) {
+        Ok(value) => value,
+        Err(e) => {
+            let Some(payload) = extract_json_payload(content) else {
+                return vec![format!("the reply is not JSON: {e}")];
+            };
+            match serde_json::from_str::<Value>(payload) {
+                Ok(value) => {
+                    message.content = Some(payload.to_string());
+                    value
+                }
+                Err(e) => return vec![format!("the reply is not valid JSON: {e}")],
+            }
+        }
+    };
+
+    let mut issues = Vec::new();
+    let expects_object = schema.get("type").and_then(Value::as_str) == Some("object");
+    match value.as_object() {
+        Some(object) => {
+            let required = schema.get("required").and_then(Value::as_array);
+            for key in required.into_iter().flatten().filter_map(Value::as_str) {
+                if !object.contains_key(key) {
+                    issues.push(format!("missing required property `{key}`"));
+                }
+            }
+        }
+        None if expects_object => issues.push("expected a JSON object".into()),
+        None => {}
+    }
+    issues
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn prose_around_the_payload_is_removed() {
+        let schema = json!({
+            "type": "object",
+            "properties": { "city": { "type": "string" }, "temperature": { "type": "integer" } },
+            "required": ["city", "temperature"]
+        });
+        let mut message =
+            Message::assistant("Sure! Here it is:\n```json\n{\"city\": \"Koper\"}\n```");
+
+        let issues = repair_structured_output(&mut message, &schema);
+
+        assert_eq!(message.content.as_deref(), Some("{\"city\": \"Koper\"}"));
+        assert_eq!(issues, ["missing required property `temperature`"]);
+    }
+}
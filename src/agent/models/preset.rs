@@ -0,0 +1,75 @@
+use crate::agent::models::configs::ModelConfig;
+
+/// Known-good defaults for popular open model families, applied with
+/// [`AgentBuilder::preset`](crate::AgentBuilder::preset).
+///
+/// Sampling values follow the model authors' recommendations. Context sizes
+/// are conservative so the models fit on consumer GPUs; raise them with
+/// `set_num_ctx` where memory allows. The model name itself is not set.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ModelPreset {
+    /// Qwen3 in thinking mode.
+    Qwen3,
+    /// Llama 3.1 instruct.
+    Llama31,
+    /// Mistral Small and Nemo instruct.
+    Mistral,
+    /// Gemma 3 instruct.
+    Gemma3,
+    /// DeepSeek-R1 and its distills.
+    DeepSeekR1,
+}
+
+impl ModelPreset {
+    /// Sampling, stop sequence and context settings of the preset.
+    pub fn model_config(&self) -> ModelConfig {
+        match self {
+            ModelPreset::Qwen3 => ModelConfig {
+                temperature: Some(0.6),
+                top_p: Some(0.95),
+                top_k: Some(20),
+                min_p: Some(0.0),
+                num_ctx: Some(16384),
+                stop: Some("<|im_end|>".into()),
+                ..Default::default()
+            },
+            ModelPreset::Llama31 => ModelConfig {
+                temperature: Some(0.6),
+                top_p: Some(0.9),
+                num_ctx: Some(16384),
+                stop: Some("<|eot_id|>".into()),
+                ..Default::default()
+            },
+            ModelPreset::Mistral => ModelConfig {
+                temperature: Some(0.15),
+                top_p: Some(1.0),
+                num_ctx: Some(16384),
+                ..Default::default()
+            },
+            ModelPreset::Gemma3 => ModelConfig {
+                temperature: Some(1.0),
+                top_p: Some(0.95),
+                top_k: Some(64),
+                min_p: Some(0.0),
+                num_ctx: Some(8192),
+                stop: Some("<end_of_turn>".into()),
+                ..Default::default()
+            },
+            ModelPreset::DeepSeekR1 => ModelConfig {
+                temperature: Some(0.6),
+                top_p: Some(0.95),
+                num_ctx: Some(16384),
+                ..Default::default()
+            },
+        }
+    }
+
+    /// Whether `<think>` blocks should be stripped from the output, for
+    /// reasoning models. `None` keeps the builder's setting.
+    pub fn strip_thinking(&self) -> Option<bool> {
+        match self {
+            ModelPreset::Qwen3 | ModelPreset::DeepSeekR1 => Some(true),
+            ModelPreset::Llama31 | ModelPreset::Mistral | ModelPreset::Gemma3 => None,
+        }
+    }
+}
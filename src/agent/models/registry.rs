@@ -0,0 +1,510 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt,
+    pin::pin,
+    sync::{Arc, Mutex, OnceLock},
+};
+
+use futures::future::{select, Either};
+use tokio::sync::{
+    mpsc::{self, Receiver},
+    oneshot,
+};
+
+use crate::{
+    services::runtime::{self, TaskHandle},
+    Agent, AgentBuildError, AgentBuilder, AgentError, Message, Notification, NotificationHandler,
+};
+
+/// Builds the builder of a registered agent, see
+/// [`AgentRegistry::register_builder`].
+pub type AgentFactory = Arc<dyn Fn() -> AgentBuilder + Send + Sync>;
+
+#[derive(Clone)]
+enum Registered {
+    Agent(Box<Agent>),
+    Builder(AgentFactory),
+    Pool(AgentPool),
+}
+
+/// Agents built ahead of time from a factory, see
+/// [`AgentRegistry::register_pool`].
+#[derive(Clone)]
+struct AgentPool {
+    factory: AgentFactory,
+    size: usize,
+    state: Arc<Mutex<PoolState>>,
+}
+
+#[derive(Default)]
+struct PoolState {
+    warm: VecDeque<(Agent, Receiver<Notification>)>,
+    /// Agents being built in the background.
+    pending: usize,
+    refills: Vec<TaskHandle>,
+    closed: bool,
+}
+
+/// Returns a leased agent to its pool once the last clone of it is
+/// dropped, see [`AgentRegistry::register_pool`].
+pub(crate) struct PoolLease {
+    /// The agent as it was handed out, without the lease itself.
+    agent: Option<Agent>,
+    returned: Option<oneshot::Sender<Agent>>,
+}
+
+impl Drop for PoolLease {
+    fn drop(&mut self) {
+        if let (Some(agent), Some(returned)) = (self.agent.take(), self.returned.take()) {
+            let _ = returned.send(agent);
+        }
+    }
+}
+
+impl fmt::Debug for PoolLease {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PoolLease").finish_non_exhaustive()
+    }
+}
+
+impl AgentPool {
+    fn lock(&self) -> std::sync::MutexGuard<'_, PoolState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn warm(&self) -> usize {
+        self.lock().warm.len()
+    }
+
+    /// A warm agent, or a freshly built one when all are leased. Either way
+    /// the pool is topped up in the background, and the agent comes back
+    /// with a fresh history once it is dropped.
+    async fn lease(&self) -> Result<(Agent, Receiver<Notification>), AgentBuildError> {
+        let warm = self.lock().warm.pop_front();
+        let (mut agent, notifications) = match warm {
+            Some(leased) => leased,
+            None => (self.factory)().build_with_notification().await?,
+        };
+        self.refill();
+
+        let (returned, on_return) = oneshot::channel();
+        agent.pool_lease = Some(Arc::new(PoolLease {
+            agent: Some(agent.clone()),
+            returned: Some(returned),
+        }));
+        Ok((agent, self.relay(notifications, on_return)))
+    }
+
+    /// Forward the notifications of a leased agent to the returned
+    /// receiver until the agent comes back, then put it back in the pool
+    /// with its own receiver, so the next lease gets them instead.
+    fn relay(
+        &self,
+        mut notifications: Receiver<Notification>,
+        on_return: oneshot::Receiver<Agent>,
+    ) -> Receiver<Notification> {
+        let (sender, receiver) = mpsc::channel(100);
+        let pool = self.clone();
+        // dropping the handle detaches the relay, it ends with the lease
+        drop(runtime::spawn(async move {
+            let mut on_return = pin!(on_return);
+            loop {
+                let next = match select(pin!(notifications.recv()), &mut on_return).await {
+                    Either::Left((notification, _)) => Either::Left(notification),
+                    Either::Right((agent, _)) => Either::Right(agent.ok()),
+                };
+                match next {
+                    Either::Left(Some(notification)) => {
+                        let _ = sender.send(notification).await;
+                    }
+                    Either::Right(Some(agent)) => {
+                        while let Ok(notification) = notifications.try_recv() {
+                            let _ = sender.send(notification).await;
+                        }
+                        drop(sender);
+                        pool.give_back(agent, notifications);
+                        return;
+                    }
+                    Either::Left(None) | Either::Right(None) => return,
+                }
+            }
+        }));
+        receiver
+    }
+
+    /// Keep a returned agent, with its history reset to the system prompt,
+    /// unless `size` agents are idle already or the pool is closed.
+    fn give_back(&self, mut agent: Agent, notifications: Receiver<Notification>) {
+        let mut state = self.lock();
+        if state.closed || state.warm.len() >= self.size {
+            return;
+        }
+        agent.history = vec![Message::system(agent.system_prompt.clone())];
+        state.warm.push_back((agent, notifications));
+    }
+
+    fn refill(&self) {
+        let mut state = self.lock();
+        if state.closed {
+            return;
+        }
+        let missing = self.size.saturating_sub(state.warm.len() + state.pending);
+        state.pending += missing;
+        state.refills.retain(|refill| !refill.is_finished());
+        for _ in 0..missing {
+            let pool = self.clone();
+            state.refills.push(runtime::spawn(async move {
+                let built = (pool.factory)().build_with_notification().await;
+                let mut state = pool.lock();
+                state.pending -= 1;
+                match built {
+                    Ok(agent) if !state.closed && state.warm.len() < pool.size => {
+                        state.warm.push_back(agent)
+                    }
+                    Ok(_) => {}
+                    Err(e) => tracing::warn!(error = %e, "Failed to refill agent pool"),
+                }
+            }));
+        }
+    }
+
+    /// Stop the refills and drop the idle agents. Leased agents are dropped
+    /// instead of returned.
+    fn close(&self) {
+        let (refills, warm) = {
+            let mut state = self.lock();
+            state.closed = true;
+            (
+                std::mem::take(&mut state.refills),
+                std::mem::take(&mut state.warm),
+            )
+        };
+        for refill in refills {
+            refill.abort();
+        }
+        drop(warm);
+    }
+}
+
+/// Agents registered by name and looked up inside flows with
+/// [`Agent::resolve`], instead of flows building their sub-agents
+/// themselves.
+///
+/// Registered agents are cloned on every lookup, so they share the
+/// notification channel, tools and usage counters of the original. Builders
+/// are built anew for every lookup, and their notifications are forwarded
+/// through the resolving agent. Pools hand out agents that were built ahead
+/// of time, see [`register_pool`](Self::register_pool).
+///
+/// An agent looks names up in its own registry (see
+/// [`AgentBuilder::set_registry`]) and then in the process-wide
+/// [`AgentRegistry::global`] one. Clones of a registry share its agents.
+///
+/// ```
+/// use reagent_rs::{AgentBuilder, AgentRegistry};
+///
+/// let registry = AgentRegistry::new();
+/// registry.register_builder("planner", || {
+///     AgentBuilder::default()
+///         .set_model("qwen3:0.6b")
+///         .set_system_prompt("You write plans.")
+/// });
+/// assert!(registry.contains("planner"));
+/// ```
+#[derive(Clone, Default)]
+pub struct AgentRegistry {
+    agents: Arc<Mutex<HashMap<String, Registered>>>,
+}
+
+impl fmt::Debug for AgentRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AgentRegistry")
+            .field("names", &self.names())
+            .finish()
+    }
+}
+
+impl AgentRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The registry shared by the whole process.
+    pub fn global() -> &'static AgentRegistry {
+        static GLOBAL: OnceLock<AgentRegistry> = OnceLock::new();
+        GLOBAL.get_or_init(AgentRegistry::new)
+    }
+
+    /// Register a built agent under `name`, replacing an earlier one.
+    pub fn register(&self, name: impl Into<String>, agent: Agent) {
+        self.insert(name.into(), Registered::Agent(Box::new(agent)));
+    }
+
+    /// Register a factory whose builder is built on every lookup of `name`,
+    /// replacing an earlier entry.
+    pub fn register_builder<F>(&self, name: impl Into<String>, factory: F)
+    where
+        F: Fn() -> AgentBuilder + Send + Sync + 'static,
+    {
+        self.insert(name.into(), Registered::Builder(Arc::new(factory)));
+    }
+
+    /// Keep `size` agents built by `factory` warm and hand one out on every
+    /// lookup of `name`, replacing an earlier entry.
+    ///
+    /// Building a sub-agent connects its MCP servers and loads its prompts,
+    /// which flows that resolve sub-agents per invocation (like
+    /// [`StatefullPrebuild::plan_and_execute`](crate::StatefullPrebuild::plan_and_execute))
+    /// otherwise pay for on every call. While an agent is leased, the pool
+    /// builds a replacement in the background; when all agents are leased,
+    /// lookups build one on the spot. Once the last clone of a leased agent
+    /// is dropped, it goes back to the pool with its history reset to the
+    /// system prompt, unless `size` agents are idle already.
+    ///
+    /// Unregistering `name`, or registering something else under it, stops
+    /// the refills and drops the idle agents.
+    ///
+    /// The first `size` agents are built before this returns, so broken
+    /// configurations fail here.
+    pub async fn register_pool<F>(
+        &self,
+        name: impl Into<String>,
+        size: usize,
+        factory: F,
+    ) -> Result<(), AgentBuildError>
+    where
+        F: Fn() -> AgentBuilder + Send + Sync + 'static,
+    {
+        let mut warm = VecDeque::with_capacity(size);
+        for _ in 0..size {
+            warm.push_back(factory().build_with_notification().await?);
+        }
+        let pool = AgentPool {
+            factory: Arc::new(factory),
+            size,
+            state: Arc::new(Mutex::new(PoolState {
+                warm,
+                ..PoolState::default()
+            })),
+        };
+        self.insert(name.into(), Registered::Pool(pool));
+        Ok(())
+    }
+
+    /// Number of agents waiting in the pool registered as `name`, `None` if
+    /// `name` is not a pool.
+    pub fn warm_agents(&self, name: &str) -> Option<usize> {
+        match self.get(name)? {
+            Registered::Pool(pool) => Some(pool.warm()),
+            _ => None,
+        }
+    }
+
+    /// Remove `name`, returns whether it was registered.
+    pub fn unregister(&self, name: &str) -> bool {
+        let removed = self.lock().remove(name);
+        if let Some(Registered::Pool(pool)) = &removed {
+            pool.close();
+        }
+        removed.is_some()
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.lock().contains_key(name)
+    }
+
+    /// Registered names, sorted.
+    pub fn names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.lock().keys().cloned().collect();
+        names.sort();
+        names
+    }
+
+    fn insert(&self, name: String, entry: Registered) {
+        let replaced = self.lock().insert(name, entry);
+        if let Some(Registered::Pool(pool)) = replaced {
+            pool.close();
+        }
+    }
+
+    fn get(&self, name: &str) -> Option<Registered> {
+        self.lock().get(name).cloned()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<String, Registered>> {
+        self.agents.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl Agent {
+    /// The agent registered as `name` in this agent's registry or the
+    /// global one, see [`AgentRegistry`].
+    pub async fn resolve(&self, name: &str) -> Result<Agent, AgentError> {
+        self.try_resolve(name)
+            .await?
+            .ok_or_else(|| AgentError::Runtime(format!("No agent is registered as `{name}`")))
+    }
+
+    /// Like [`resolve`](Agent::resolve), `None` if nothing is registered as
+    /// `name`, e.g. to fall back to a default sub-agent.
+    pub async fn try_resolve(&self, name: &str) -> Result<Option<Agent>, AgentError> {
+        let entry = self
+            .registry
+            .as_ref()
+            .and_then(|registry| registry.get(name))
+            .or_else(|| AgentRegistry::global().get(name));
+
+        match entry {
+            None => Ok(None),
+            Some(Registered::Agent(agent)) => Ok(Some(*agent)),
+            Some(Registered::Builder(factory)) => {
+                let (agent, notifications) = factory().build_with_notification().await?;
+                self.forward_notifications(notifications);
+                Ok(Some(agent))
+            }
+            Some(Registered::Pool(pool)) => {
+                let (agent, notifications) = pool.lease().await?;
+                self.forward_notifications(notifications);
+                Ok(Some(agent))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn own_registry_comes_before_the_global_one() {
+        AgentRegistry::global().register_builder("registry-test-writer", || {
+            AgentBuilder::default().set_model("test").set_name("global")
+        });
+        let registry = AgentRegistry::new();
+        registry.register_builder("registry-test-writer", || {
+            AgentBuilder::default().set_model("test").set_name("own")
+        });
+
+        let agent = AgentBuilder::default()
+            .set_model("test")
+            .set_registry(registry.clone())
+            .build()
+            .await
+            .unwrap();
+        assert_eq!(
+            agent.resolve("registry-test-writer").await.unwrap().name,
+            "own"
+        );
+
+        registry.unregister("registry-test-writer");
+        assert_eq!(
+            agent.resolve("registry-test-writer").await.unwrap().name,
+            "global"
+        );
+        assert!(agent.try_resolve("missing").await.unwrap().is_none());
+        assert!(agent.resolve("missing").await.is_err());
+        AgentRegistry::global().unregister("registry-test-writer");
+    }
+
+    async fn settle(done: impl Fn() -> bool) {
+        for _ in 0..100 {
+            if done() {
+                break;
+            }
+            runtime::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        // let the tasks that made it true finish
+        runtime::sleep(std::time::Duration::from_millis(10)).await;
+    }
+
+    #[tokio::test]
+    async fn pooled_agents_are_built_ahead_and_refilled() {
+        let builds = Arc::new(AtomicUsize::new(0));
+        let registry = AgentRegistry::new();
+        let counter = builds.clone();
+        registry
+            .register_pool("executor", 2, move || {
+                let n = counter.fetch_add(1, Ordering::SeqCst);
+                AgentBuilder::default()
+                    .set_model("test")
+                    .set_name(format!("executor-{n}"))
+            })
+            .await
+            .unwrap();
+        assert_eq!(builds.load(Ordering::SeqCst), 2);
+        assert_eq!(registry.warm_agents("executor"), Some(2));
+
+        let agent = AgentBuilder::default()
+            .set_model("test")
+            .set_registry(registry.clone())
+            .build()
+            .await
+            .unwrap();
+        let first = agent.resolve("executor").await.unwrap();
+        let second = agent.resolve("executor").await.unwrap();
+        assert_eq!(first.name, "executor-0");
+        assert_eq!(second.name, "executor-1");
+
+        // the leased agents are replaced in the background
+        settle(|| registry.warm_agents("executor") == Some(2)).await;
+        assert_eq!(registry.warm_agents("executor"), Some(2));
+        assert_eq!(builds.load(Ordering::SeqCst), 4);
+        assert_eq!(registry.warm_agents("missing"), None);
+
+        // returned agents beyond the size of the pool are dropped
+        drop((first, second));
+        runtime::sleep(std::time::Duration::from_millis(50)).await;
+        assert_eq!(registry.warm_agents("executor"), Some(2));
+    }
+
+    #[tokio::test]
+    async fn dropped_leases_return_with_a_fresh_history() {
+        let builds = Arc::new(AtomicUsize::new(0));
+        let registry = AgentRegistry::new();
+        let counter = builds.clone();
+        registry
+            .register_pool("executor", 1, move || {
+                // only the first build works, so refills can't take the
+                // place of the returned agent
+                match counter.fetch_add(1, Ordering::SeqCst) {
+                    0 => AgentBuilder::default().set_model("test"),
+                    _ => AgentBuilder::default(),
+                }
+                .set_system_prompt("You execute steps.")
+            })
+            .await
+            .unwrap();
+        let agent = AgentBuilder::default()
+            .set_model("test")
+            .set_registry(registry.clone())
+            .build()
+            .await
+            .unwrap();
+
+        let mut leased = agent.resolve("executor").await.unwrap();
+        leased.history.push(Message::user("Step one"));
+        let clone = leased.clone();
+        settle(|| builds.load(Ordering::SeqCst) == 2).await;
+        assert_eq!(registry.warm_agents("executor"), Some(0));
+
+        drop(leased);
+        runtime::sleep(std::time::Duration::from_millis(50)).await;
+        assert_eq!(registry.warm_agents("executor"), Some(0));
+        drop(clone);
+        settle(|| registry.warm_agents("executor") == Some(1)).await;
+
+        let again = agent.resolve("executor").await.unwrap();
+        assert_eq!(again.history.len(), 1);
+        assert_eq!(
+            again.history[0].content.as_deref(),
+            Some("You execute steps.")
+        );
+        drop(again);
+        settle(|| registry.warm_agents("executor") == Some(1)).await;
+
+        registry.unregister("executor");
+        assert_eq!(registry.warm_agents("executor"), None);
+    }
+}
@@ -0,0 +1,328 @@
+use std::time::Duration;
+
+use crate::{
+    agent::models::configs::ModelConfig,
+    services::{
+        llm::{message::Message, ClientBuilder, ClientConfig},
+        runtime::Instant,
+    },
+    Agent, AgentError, ChatResponse, InvocationBuilder, Role,
+};
+
+/// Model (and optionally provider) a conversation is replayed against.
+// only constructed once per replay, boxing would just make matching awkward
+#[allow(clippy::large_enum_variant)]
+#[derive(Debug, Clone)]
+pub enum ReplayTarget {
+    /// Same provider and settings, different model.
+    Model(String),
+    /// Override model settings and, optionally, the client (provider, base URL, keys).
+    /// Unset fields of the [`ModelConfig`] are inherited from the agent.
+    Config {
+        client: Option<ClientConfig>,
+        model: ModelConfig,
+    },
+}
+
+impl From<&str> for ReplayTarget {
+    fn from(model: &str) -> Self {
+        ReplayTarget::Model(model.to_string())
+    }
+}
+
+impl From<String> for ReplayTarget {
+    fn from(model: String) -> Self {
+        ReplayTarget::Model(model)
+    }
+}
+
+impl From<ModelConfig> for ReplayTarget {
+    fn from(model: ModelConfig) -> Self {
+        ReplayTarget::Config {
+            client: None,
+            model,
+        }
+    }
+}
+
+impl From<(ClientConfig, ModelConfig)> for ReplayTarget {
+    fn from((client, model): (ClientConfig, ModelConfig)) -> Self {
+        ReplayTarget::Config {
+            client: Some(client),
+            model,
+        }
+    }
+}
+
+/// A single model response produced during a replay.
+#[derive(Debug, Clone)]
+pub struct ReplayResponse {
+    pub model: String,
+    pub message: Message,
+    /// Wall-clock time of the request.
+    pub latency: Duration,
+    /// Prompt tokens, if reported by the provider.
+    pub prompt_tokens: Option<u32>,
+    /// Generated tokens, if reported by the provider.
+    pub completion_tokens: Option<u32>,
+}
+
+impl ReplayResponse {
+    fn new(response: ChatResponse, latency: Duration) -> Self {
+        Self {
+            model: response.model,
+            message: response.message,
+            latency,
+            prompt_tokens: response.prompt_eval_count,
+            completion_tokens: response.eval_count,
+        }
+    }
+}
+
+/// Difference between the replayed and the baseline response (`replay - baseline`).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ReplayDiff {
+    /// Latency difference in milliseconds; negative means the replay was faster.
+    pub latency_ms: i128,
+    /// `None` if either side did not report the token count.
+    pub prompt_tokens: Option<i64>,
+    /// `None` if either side did not report the token count.
+    pub completion_tokens: Option<i64>,
+}
+
+/// One recorded user turn with the responses of both models.
+#[derive(Debug, Clone)]
+pub struct ReplayTurn {
+    pub prompt: String,
+    /// Assistant message that originally answered the turn, if any.
+    pub recorded: Option<Message>,
+    /// Fresh response of the original agent.
+    pub baseline: ReplayResponse,
+    /// Response of the replay target.
+    pub replay: ReplayResponse,
+}
+
+impl ReplayTurn {
+    pub fn diff(&self) -> ReplayDiff {
+        ReplayDiff {
+            latency_ms: self.replay.latency.as_millis() as i128
+                - self.baseline.latency.as_millis() as i128,
+            prompt_tokens: token_diff(self.baseline.prompt_tokens, self.replay.prompt_tokens),
+            completion_tokens: token_diff(
+                self.baseline.completion_tokens,
+                self.replay.completion_tokens,
+            ),
+        }
+    }
+}
+
+/// Result of [`Agent::replay_history_with`].
+#[derive(Debug, Clone, Default)]
+pub struct ReplayReport {
+    pub turns: Vec<ReplayTurn>,
+}
+
+impl ReplayReport {
+    /// Sum of the per-turn differences.
+    pub fn total_diff(&self) -> ReplayDiff {
+        let sum = |tokens: fn(&ReplayDiff) -> Option<i64>| {
+            self.turns
+                .iter()
+                .map(|turn| tokens(&turn.diff()))
+                .sum::<Option<i64>>()
+        };
+        ReplayDiff {
+            latency_ms: self.turns.iter().map(|turn| turn.diff().latency_ms).sum(),
+            prompt_tokens: sum(|diff| diff.prompt_tokens),
+            completion_tokens: sum(|diff| diff.completion_tokens),
+        }
+    }
+}
+
+impl Agent {
+    /// Re-run the user turns recorded in the history against another model
+    /// or provider, for A/B comparisons on real conversations.
+    ///
+    /// Every user turn is answered twice from the same recorded context
+    /// (everything in the history up to and including that turn): once by
+    /// this agent and once by `target`, so latency and token usage are
+    /// measured under the same conditions. Only a single completion is
+    /// requested per turn; tool calls are returned, not executed.
+    ///
+    /// The agent itself (history included) is not modified and no
+    /// notifications are emitted.
+    ///
+    /// ```no_run
+    /// # async fn run(agent: &reagent_rs::Agent) -> Result<(), reagent_rs::AgentError> {
+    /// let report = agent.replay_history_with("qwen3:8b").await?;
+    /// for turn in &report.turns {
+    ///     println!("{:?}: {:?}", turn.prompt, turn.diff());
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn replay_history_with(
+        &self,
+        target: impl Into<ReplayTarget>,
+    ) -> Result<ReplayReport, AgentError> {
+        let mut baseline_agent = self.clone();
+        baseline_agent.notification_channel = None;
+
+        let mut replay_agent = baseline_agent.clone();
+        match target.into() {
+            ReplayTarget::Model(model) => replay_agent.model = model,
+            ReplayTarget::Config { client, model } => {
+                if let Some(client) = client {
+                    replay_agent.inference_client = client.build()?;
+                }
+                replay_agent.apply_model_config(model);
+            }
+        }
+
+        let mut turns = Vec::new();
+        for (index, message) in self.history.iter().enumerate() {
+            if message.role != Role::User {
+                continue;
+            }
+            let context = self.history[..=index].to_vec();
+
+            let baseline = replay_turn(&mut baseline_agent, context.clone()).await?;
+            let replay = replay_turn(&mut replay_agent, context).await?;
+
+            turns.push(ReplayTurn {
+                prompt: message.content.clone().unwrap_or_default(),
+                recorded: recorded_answer(&self.history[index + 1..]),
+                baseline,
+                replay,
+            });
+        }
+
+        Ok(ReplayReport { turns })
+    }
+
+    /// Override model settings with the ones set in `config`.
+    fn apply_model_config(&mut self, config: ModelConfig) {
+        let ModelConfig {
+            model,
+            temperature,
+            top_p,
+            presence_penalty,
+            frequency_penalty,
+            num_ctx,
+            repeat_last_n,
+            repeat_penalty,
+            seed,
+            stop,
+            num_predict,
+            top_k,
+            min_p,
+            extra_options,
+        } = config;
+
+        if let Some(model) = model {
+            self.model = model;
+        }
+        self.temperature = temperature.or(self.temperature);
+        self.top_p = top_p.or(self.top_p);
+        self.presence_penalty = presence_penalty.or(self.presence_penalty);
+        self.frequency_penalty = frequency_penalty.or(self.frequency_penalty);
+        self.num_ctx = num_ctx.or(self.num_ctx);
+        self.repeat_last_n = repeat_last_n.or(self.repeat_last_n);
+        self.repeat_penalty = repeat_penalty.or(self.repeat_penalty);
+        self.seed = seed.or(self.seed);
+        self.stop = stop.or(self.stop.take());
+        self.num_predict = num_predict.or(self.num_predict);
+        self.top_k = top_k.or(self.top_k);
+        self.min_p = min_p.or(self.min_p);
+        self.extra_options.extend(extra_options);
+    }
+}
+
+async fn replay_turn(
+    agent: &mut Agent,
+    context: Vec<Message>,
+) -> Result<ReplayResponse, AgentError> {
+    let start = Instant::now();
+    let response = InvocationBuilder::default()
+        .messages(context)
+        .invoke_with(agent)
+        .await?;
+    Ok(ReplayResponse::new(response, start.elapsed()))
+}
+
+/// The last assistant message before the next user turn.
+fn recorded_answer(following: &[Message]) -> Option<Message> {
+    following
+        .iter()
+        .take_while(|message| message.role != Role::User)
+        .filter(|message| message.role == Role::Assistant)
+        .last()
+        .cloned()
+}
+
+fn token_diff(baseline: Option<u32>, replay: Option<u32>) -> Option<i64> {
+    Some(replay? as i64 - baseline? as i64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn response(latency_ms: u64, prompt_tokens: Option<u32>, completion: u32) -> ReplayResponse {
+        ReplayResponse {
+            model: "model".into(),
+            message: Message::assistant("answer"),
+            latency: Duration::from_millis(latency_ms),
+            prompt_tokens,
+            completion_tokens: Some(completion),
+        }
+    }
+
+    #[test]
+    fn recorded_answer_is_last_assistant_message_of_the_turn() {
+        let history = vec![
+            Message::assistant("calling tool"),
+            Message::tool("42", "call_1"),
+            Message::assistant("the answer is 42"),
+            Message::user("next question"),
+            Message::assistant("unrelated"),
+        ];
+
+        let answer = recorded_answer(&history).unwrap();
+        assert_eq!(answer.content.as_deref(), Some("the answer is 42"));
+        assert!(recorded_answer(&history[3..]).is_none());
+    }
+
+    #[test]
+    fn diffs_are_replay_minus_baseline() {
+        let turn = |baseline, replay| ReplayTurn {
+            prompt: "question".into(),
+            recorded: None,
+            baseline,
+            replay,
+        };
+        let report = ReplayReport {
+            turns: vec![
+                turn(response(100, Some(10), 50), response(40, Some(12), 30)),
+                turn(response(100, None, 20), response(150, Some(8), 25)),
+            ],
+        };
+
+        assert_eq!(
+            report.turns[0].diff(),
+            ReplayDiff {
+                latency_ms: -60,
+                prompt_tokens: Some(2),
+                completion_tokens: Some(-20),
+            }
+        );
+        assert_eq!(
+            report.total_diff(),
+            ReplayDiff {
+                latency_ms: -10,
+                prompt_tokens: None,
+                completion_tokens: Some(-15),
+            }
+        );
+    }
+}
@@ -0,0 +1,291 @@
+use std::{fmt, sync::Arc, time::Duration};
+
+use crate::{AgentError, Message};
+
+/// A model a [`ModelRouter`] can pick, tagged with what a request to it
+/// costs, how long it takes and how good the answers are.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ModelProfile {
+    pub model: String,
+    /// Relative cost of a request, e.g. the price per million tokens.
+    pub cost: f64,
+    /// Typical time until the response is complete.
+    pub latency: Duration,
+    /// Relative answer quality, 0.0 - 1.0.
+    pub quality: f32,
+}
+
+impl ModelProfile {
+    pub fn new(model: impl Into<String>) -> Self {
+        Self {
+            model: model.into(),
+            cost: 0.0,
+            latency: Duration::ZERO,
+            quality: 0.5,
+        }
+    }
+
+    pub fn cost(mut self, cost: f64) -> Self {
+        self.cost = cost;
+        self
+    }
+
+    pub fn latency(mut self, latency: Duration) -> Self {
+        self.latency = latency;
+        self
+    }
+
+    pub fn quality(mut self, quality: f32) -> Self {
+        self.quality = quality.clamp(0.0, 1.0);
+        self
+    }
+}
+
+/// Estimates how hard a prompt is, from 0.0 (trivial) to 1.0 (hardest).
+pub type DifficultyClassifier = Arc<dyn Fn(&str) -> f32 + Send + Sync>;
+
+/// Escalation check on the reply of a model, see
+/// [`ModelRouter::escalate_when`].
+pub type EscalationCheck = Arc<dyn Fn(&Message) -> bool + Send + Sync>;
+
+/// How a [`ModelRouter`] picks the first model of an invocation.
+#[derive(Clone)]
+pub enum RoutingPolicy {
+    /// The cheapest model.
+    CheapestFirst,
+    /// The best model whose latency is within the SLA, or the fastest model
+    /// when none is.
+    LatencySla(Duration),
+    /// The cheapest model whose quality is at least the estimated
+    /// difficulty of the prompt, or the best model when none is.
+    Difficulty(DifficultyClassifier),
+}
+
+impl RoutingPolicy {
+    /// Route by the difficulty `classifier` estimates for the prompt, e.g.
+    /// from its length, keywords or an embedding classifier.
+    pub fn difficulty<F>(classifier: F) -> Self
+    where
+        F: Fn(&str) -> f32 + Send + Sync + 'static,
+    {
+        Self::Difficulty(Arc::new(classifier))
+    }
+}
+
+impl fmt::Debug for RoutingPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::CheapestFirst => f.write_str("CheapestFirst"),
+            Self::LatencySla(sla) => f.debug_tuple("LatencySla").field(sla).finish(),
+            Self::Difficulty(_) => f.write_str("Difficulty(..)"),
+        }
+    }
+}
+
+/// Picks the model of every invocation from registered candidates, and
+/// escalates to better models when a cheaper one fails.
+///
+/// The [`RoutingPolicy`] picks the first model. When its flow fails, or
+/// [`escalate_when`](Self::escalate_when) rejects the reply, the messages it
+/// added are dropped and the prompt is retried with the next better model
+/// (by quality, then cost) until one succeeds or no better model is left.
+///
+/// Set on the agent with
+/// [`AgentBuilder::set_model_router`](crate::AgentBuilder::set_model_router).
+///
+/// ```
+/// use std::time::Duration;
+///
+/// use reagent_rs::{ModelProfile, ModelRouter, RoutingPolicy};
+///
+/// let router = ModelRouter::new(RoutingPolicy::CheapestFirst)
+///     .add_model(ModelProfile::new("qwen3:0.6b").cost(0.1).quality(0.3))
+///     .add_model(ModelProfile::new("qwen3:32b").cost(2.0).quality(0.8))
+///     .escalate_when(|reply| reply.content.as_deref().unwrap_or("").is_empty());
+///
+/// assert_eq!(router.route("Hi"), ["qwen3:0.6b", "qwen3:32b"]);
+/// ```
+#[derive(Clone)]
+pub struct ModelRouter {
+    models: Vec<ModelProfile>,
+    policy: RoutingPolicy,
+    escalate_on_error: bool,
+    escalate_when: Option<EscalationCheck>,
+}
+
+impl ModelRouter {
+    pub fn new(policy: RoutingPolicy) -> Self {
+        Self {
+            models: Vec::new(),
+            policy,
+            escalate_on_error: true,
+            escalate_when: None,
+        }
+    }
+
+    pub fn add_model(mut self, model: ModelProfile) -> Self {
+        self.models.push(model);
+        self
+    }
+
+    /// Whether a failed flow is retried with a better model (default: yes).
+    pub fn escalate_on_error(mut self, escalate: bool) -> Self {
+        self.escalate_on_error = escalate;
+        self
+    }
+
+    /// Retry with a better model when `check` returns `true` for a reply.
+    pub fn escalate_when<F>(mut self, check: F) -> Self
+    where
+        F: Fn(&Message) -> bool + Send + Sync + 'static,
+    {
+        self.escalate_when = Some(Arc::new(check));
+        self
+    }
+
+    pub fn models(&self) -> &[ModelProfile] {
+        &self.models
+    }
+
+    /// Models to try for `prompt`: the one picked by the policy, followed by
+    /// the models to escalate to.
+    pub fn route(&self, prompt: &str) -> Vec<String> {
+        let Some(first) = self.pick(prompt) else {
+            return Vec::new();
+        };
+
+        let mut better: Vec<&ModelProfile> = self
+            .models
+            .iter()
+            .filter(|m| m.quality > first.quality)
+            .collect();
+        better.sort_by(|a, b| {
+            a.quality
+                .total_cmp(&b.quality)
+                .then(a.cost.total_cmp(&b.cost))
+        });
+
+        let mut route = vec![first.model.clone()];
+        for model in better {
+            if !route.contains(&model.model) {
+                route.push(model.model.clone());
+            }
+        }
+        route
+    }
+
+    fn pick(&self, prompt: &str) -> Option<&ModelProfile> {
+        match &self.policy {
+            RoutingPolicy::CheapestFirst => cheapest(self.models.iter()),
+            RoutingPolicy::LatencySla(sla) => {
+                best(self.models.iter().filter(|m| m.latency <= *sla))
+                    .or_else(|| self.models.iter().min_by_key(|m| m.latency))
+            }
+            RoutingPolicy::Difficulty(classifier) => {
+                let difficulty = classifier(prompt);
+                cheapest(self.models.iter().filter(|m| m.quality >= difficulty))
+                    .or_else(|| best(self.models.iter()))
+            }
+        }
+    }
+
+    pub(crate) fn should_escalate(&self, result: &Result<Message, AgentError>) -> bool {
+        match result {
+            Ok(reply) => self
+                .escalate_when
+                .as_ref()
+                .is_some_and(|check| check(reply)),
+            // an aborted flow was stopped on purpose, a better model won't help
+            Err(AgentError::Aborted(_)) => false,
+            Err(_) => self.escalate_on_error,
+        }
+    }
+}
+
+fn cheapest<'a>(models: impl Iterator<Item = &'a ModelProfile>) -> Option<&'a ModelProfile> {
+    models.min_by(|a, b| a.cost.total_cmp(&b.cost))
+}
+
+fn best<'a>(models: impl Iterator<Item = &'a ModelProfile>) -> Option<&'a ModelProfile> {
+    models.max_by(|a, b| a.quality.total_cmp(&b.quality))
+}
+
+impl fmt::Debug for ModelRouter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ModelRouter")
+            .field("models", &self.models)
+            .field("policy", &self.policy)
+            .field("escalate_on_error", &self.escalate_on_error)
+            .field("escalate_when", &self.escalate_when.is_some())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Agent, AgentBuilder, FlowFuture};
+
+    fn router(policy: RoutingPolicy) -> ModelRouter {
+        ModelRouter::new(policy)
+            .add_model(
+                ModelProfile::new("small")
+                    .cost(0.1)
+                    .latency(Duration::from_millis(300))
+                    .quality(0.3),
+            )
+            .add_model(
+                ModelProfile::new("medium")
+                    .cost(1.0)
+                    .latency(Duration::from_secs(1))
+                    .quality(0.6),
+            )
+            .add_model(
+                ModelProfile::new("large")
+                    .cost(5.0)
+                    .latency(Duration::from_secs(4))
+                    .quality(0.9),
+            )
+    }
+
+    #[tokio::test]
+    async fn routes_by_policy_and_escalates_on_errors() {
+        assert_eq!(
+            router(RoutingPolicy::CheapestFirst).route("x"),
+            ["small", "medium", "large"]
+        );
+        assert_eq!(
+            router(RoutingPolicy::LatencySla(Duration::from_secs(2))).route("x"),
+            ["medium", "large"]
+        );
+        let by_length = RoutingPolicy::difficulty(|prompt| prompt.len() as f32 / 100.0);
+        assert_eq!(
+            router(by_length.clone()).route(&"x".repeat(50)),
+            ["medium", "large"]
+        );
+        assert_eq!(router(by_length).route(&"x".repeat(200)), ["large"]);
+
+        fn small_fails<'a>(agent: &'a mut Agent, _prompt: String) -> FlowFuture<'a> {
+            Box::pin(async move {
+                agent.history.push(Message::assistant(agent.model.clone()));
+                if agent.model == "small" {
+                    return Err(AgentError::Runtime("too hard".into()));
+                }
+                Ok(Message::assistant(agent.model.clone()))
+            })
+        }
+
+        let mut agent = AgentBuilder::default()
+            .set_model_router(router(RoutingPolicy::CheapestFirst))
+            .set_flow(small_fails)
+            .build()
+            .await
+            .unwrap();
+        let reply = agent.invoke_flow("x").await.unwrap();
+
+        assert_eq!(reply.content.as_deref(), Some("medium"));
+        assert_eq!(agent.model, "small");
+        // the failed attempt is dropped from the history
+        assert_eq!(agent.history.len(), 2);
+    }
+}
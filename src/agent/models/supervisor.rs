@@ -0,0 +1,405 @@
+use std::{fmt, pin::pin, sync::Arc};
+
+use futures::future::{select, Either};
+use tokio::sync::mpsc::{Receiver, Sender};
+
+use crate::{
+    Agent, AgentError, CancelScope, ErrorDetails, Message, Notification, NotificationContent,
+};
+
+/// Condition of a [`Supervisor`] policy, see
+/// [`Supervisor::on_notification`].
+pub type NotificationCheck = Arc<dyn Fn(&Notification) -> bool + Send + Sync>;
+
+/// Callback told about every policy a [`Supervisor`] applied, see
+/// [`Supervisor::on_alert`].
+pub type AlertFn = Arc<dyn Fn(&SupervisorAlert) + Send + Sync>;
+
+/// What a [`Supervisor`] does once the condition of a policy is met.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SupervisorAction {
+    /// Abort the running flow with [`AgentError::Aborted`].
+    Kill,
+    /// Switch the agent to this model. A flow that failed is run again with
+    /// it, the messages of the failed run dropped.
+    Escalate(String),
+    /// Only call the [`on_alert`](Supervisor::on_alert) callback.
+    Alert,
+}
+
+/// A policy a [`Supervisor`] applied, given to its
+/// [`on_alert`](Supervisor::on_alert) callback.
+#[derive(Debug, Clone)]
+pub struct SupervisorAlert {
+    pub action: SupervisorAction,
+    /// Which condition was met, e.g. "3 tool failures in a row".
+    pub reason: String,
+    /// Agents the notification that met the condition passed through, see
+    /// [`Notification::path`].
+    pub path: Vec<String>,
+    /// Details of the failure that met the condition, if it was one.
+    pub error: Option<ErrorDetails>,
+}
+
+#[derive(Clone)]
+enum Trigger {
+    ToolFailures(usize),
+    PromptFailures(usize),
+    Custom(NotificationCheck),
+}
+
+struct Policy {
+    trigger: Trigger,
+    action: SupervisorAction,
+    count: usize,
+}
+
+impl Policy {
+    /// Count `notification` and return the reason when the policy applies.
+    fn observe(&mut self, notification: &Notification) -> Option<String> {
+        let (limit, what) = match &self.trigger {
+            Trigger::ToolFailures(limit) => (*limit, "tool failures"),
+            Trigger::PromptFailures(limit) => (*limit, "failed model requests"),
+            Trigger::Custom(check) => {
+                return check(notification).then(|| "custom condition".to_string());
+            }
+        };
+        let (failed, succeeded) = match (&self.trigger, &notification.content) {
+            (Trigger::ToolFailures(_), NotificationContent::ToolCallErrorResult(_)) => {
+                (true, false)
+            }
+            (Trigger::ToolFailures(_), NotificationContent::ToolCallSuccessResult(_)) => {
+                (false, true)
+            }
+            (Trigger::PromptFailures(_), NotificationContent::PromptErrorResult(_)) => {
+                (true, false)
+            }
+            (Trigger::PromptFailures(_), NotificationContent::PromptSuccessResult(_)) => {
+                (false, true)
+            }
+            _ => (false, false),
+        };
+        if succeeded {
+            self.count = 0;
+        }
+        if !failed {
+            return None;
+        }
+        self.count += 1;
+        if self.count < limit {
+            return None;
+        }
+        self.count = 0;
+        Some(format!("{limit} {what} in a row"))
+    }
+}
+
+/// Watches the notifications of an agent and applies policies to its flows:
+/// abort them after repeated tool failures, escalate to a bigger model, or
+/// alert through a callback. A building block for agents running
+/// unattended.
+///
+/// The supervisor takes the receiver of the agent's notification channel
+/// and reads it while [`invoke`](Self::invoke) runs a flow. Policies apply
+/// as the notifications come in, so a killed flow stops at its next await
+/// point (a model request or tool call). Failures are counted in a row and
+/// the counts start over with every invocation.
+///
+/// ```no_run
+/// use reagent_rs::{AgentBuilder, Supervisor, SupervisorAction};
+///
+/// # async fn run() -> Result<(), Box<dyn std::error::Error>> {
+/// let (mut agent, notifications) = AgentBuilder::default()
+///     .set_model("qwen3:0.6b")
+///     .build_with_notification()
+///     .await?;
+///
+/// let mut supervisor = Supervisor::new(notifications)
+///     .on_tool_failures(3, SupervisorAction::Kill)
+///     .on_prompt_failures(2, SupervisorAction::Escalate("qwen3:32b".into()))
+///     .on_alert(|alert| eprintln!("{:?}: {}", alert.action, alert.reason));
+///
+/// let reply = supervisor.invoke(&mut agent, "Summarize the logs").await?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct Supervisor {
+    receiver: Receiver<Notification>,
+    forward: Option<Sender<Notification>>,
+    policies: Vec<Policy>,
+    on_alert: Option<AlertFn>,
+    escalation: Option<String>,
+}
+
+impl Supervisor {
+    pub fn new(notifications: Receiver<Notification>) -> Self {
+        Self {
+            receiver: notifications,
+            forward: None,
+            policies: Vec::new(),
+            on_alert: None,
+            escalation: None,
+        }
+    }
+
+    /// Apply `action` after `limit` failed tool calls in a row.
+    pub fn on_tool_failures(self, limit: usize, action: SupervisorAction) -> Self {
+        self.policy(Trigger::ToolFailures(limit.max(1)), action)
+    }
+
+    /// Apply `action` after `limit` failed model requests in a row.
+    pub fn on_prompt_failures(self, limit: usize, action: SupervisorAction) -> Self {
+        self.policy(Trigger::PromptFailures(limit.max(1)), action)
+    }
+
+    /// Apply `action` to every notification `check` accepts.
+    pub fn on_notification<F>(self, check: F, action: SupervisorAction) -> Self
+    where
+        F: Fn(&Notification) -> bool + Send + Sync + 'static,
+    {
+        self.policy(Trigger::Custom(Arc::new(check)), action)
+    }
+
+    /// Call `callback` whenever a policy is applied.
+    pub fn on_alert<F>(mut self, callback: F) -> Self
+    where
+        F: Fn(&SupervisorAlert) + Send + Sync + 'static,
+    {
+        self.on_alert = Some(Arc::new(callback));
+        self
+    }
+
+    /// Pass every notification on to `sender` after the policies saw it.
+    pub fn forward_to(mut self, sender: Sender<Notification>) -> Self {
+        self.forward = Some(sender);
+        self
+    }
+
+    fn policy(mut self, trigger: Trigger, action: SupervisorAction) -> Self {
+        self.policies.push(Policy {
+            trigger,
+            action,
+            count: 0,
+        });
+        self
+    }
+
+    /// Run the flow of `agent` under the supervisor's policies.
+    ///
+    /// After an [`Escalate`](SupervisorAction::Escalate) policy applied, the
+    /// agent keeps the new model (unless it routes with a
+    /// [`ModelRouter`](crate::ModelRouter)), and a failed flow runs again
+    /// with it.
+    pub async fn invoke(
+        &mut self,
+        agent: &mut Agent,
+        prompt: impl Into<String>,
+    ) -> Result<Message, AgentError> {
+        let prompt = prompt.into();
+        for policy in &mut self.policies {
+            policy.count = 0;
+        }
+
+        let start = agent.history.len();
+        loop {
+            let scope = CancelScope::new(agent.name.clone());
+            let result = {
+                let mut flow = pin!(agent.invoke_flow_in(&scope, prompt.clone()));
+                loop {
+                    let next = match select(&mut flow, pin!(self.receiver.recv())).await {
+                        Either::Left((result, _)) => break result,
+                        Either::Right((notification, _)) => notification,
+                    };
+                    match next {
+                        Some(notification) => self.observe(notification, &scope).await,
+                        // the channel closed, nothing left to watch
+                        None => break flow.await,
+                    }
+                }
+            };
+            while let Ok(notification) = self.receiver.try_recv() {
+                self.observe(notification, &scope).await;
+            }
+
+            let Some(model) = self.escalation.take() else {
+                return result;
+            };
+            if model == agent.model {
+                return result;
+            }
+            tracing::info!(agent = %agent.name, from = %agent.model, to = %model, "Supervisor escalates to a bigger model");
+            agent.model = model;
+            if result.is_ok() || scope.is_cancelled() {
+                return result;
+            }
+            // drop what the failed run added
+            agent.history.truncate(start);
+        }
+    }
+
+    async fn observe(&mut self, notification: Notification, scope: &CancelScope) {
+        for policy in &mut self.policies {
+            let Some(reason) = policy.observe(&notification) else {
+                continue;
+            };
+            tracing::warn!(action = ?policy.action, reason = reason.as_str(), "Supervisor policy applied");
+            match &policy.action {
+                SupervisorAction::Kill => scope.cancel(),
+                SupervisorAction::Escalate(model) => self.escalation = Some(model.clone()),
+                SupervisorAction::Alert => {}
+            }
+            if let Some(on_alert) = &self.on_alert {
+                on_alert(&SupervisorAlert {
+                    action: policy.action.clone(),
+                    reason,
+                    path: notification.path.clone(),
+                    error: notification.error.clone(),
+                });
+            }
+        }
+
+        if let Some(forward) = &self.forward {
+            if forward.send(notification).await.is_err() {
+                self.forward = None;
+            }
+        }
+    }
+}
+
+impl fmt::Debug for Supervisor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Supervisor")
+            .field(
+                "actions",
+                &self
+                    .policies
+                    .iter()
+                    .map(|policy| &policy.action)
+                    .collect::<Vec<_>>(),
+            )
+            .field("escalation", &self.escalation)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    };
+
+    use serde_json::json;
+    use tokio::sync::Notify;
+
+    use super::*;
+    use crate::{
+        testing::ModelScript, AgentBuilder, ClientConfig, ToolBuilder, ToolCall, ToolCallFunction,
+        ToolExecutionError, ToolType,
+    };
+
+    fn tool_call(name: &str) -> Message {
+        let mut message = Message::assistant(String::new());
+        message.tool_calls = Some(vec![ToolCall {
+            id: Some(format!("call_{name}")),
+            tool_type: ToolType::Function,
+            function: ToolCallFunction {
+                name: name.into(),
+                arguments: json!({}),
+            },
+        }]);
+        message
+    }
+
+    async fn build(builder: AgentBuilder, script: &ModelScript) -> (Agent, Receiver<Notification>) {
+        builder
+            .set_model("small")
+            .import_client_config(ClientConfig {
+                script: Some(script.clone()),
+                ..Default::default()
+            })
+            .build_with_notification()
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn repeated_tool_failures_kill_the_flow() {
+        let killed = Arc::new(Notify::new());
+        let calls = Arc::new(AtomicUsize::new(0));
+        let search_killed = killed.clone();
+        let search = ToolBuilder::new()
+            .function_name("search")
+            .function_description("Searches the web")
+            .executor_fn(move |_| {
+                let killed = search_killed.clone();
+                let call = calls.fetch_add(1, Ordering::SeqCst);
+                async move {
+                    // the third search runs until the supervisor killed the flow
+                    if call == 2 {
+                        killed.notified().await;
+                    }
+                    Err(ToolExecutionError::ExecutionFailed("down".into()))
+                }
+            })
+            .build()
+            .unwrap();
+        let script = ModelScript::default();
+        for _ in 0..3 {
+            script.push(tool_call("search"));
+        }
+        script.push(Message::assistant("Gave up."));
+        let (mut agent, notifications) =
+            build(AgentBuilder::default().add_tool(search), &script).await;
+        let alerts = Arc::new(Mutex::new(Vec::new()));
+        let seen = alerts.clone();
+        let mut supervisor = Supervisor::new(notifications)
+            .on_tool_failures(2, SupervisorAction::Kill)
+            .on_alert(move |alert| {
+                seen.lock().unwrap().push(alert.clone());
+                killed.notify_one();
+            });
+
+        let result = supervisor.invoke(&mut agent, "Search").await;
+
+        assert!(matches!(result, Err(AgentError::Aborted(_))));
+        // killed while the third search ran
+        assert_eq!(script.remaining(), 1);
+        let alerts = alerts.lock().unwrap();
+        assert_eq!(alerts.len(), 1);
+        assert_eq!(alerts[0].action, SupervisorAction::Kill);
+        assert_eq!(alerts[0].path, [agent.name.clone()]);
+        assert_eq!(
+            alerts[0].error.as_ref().unwrap().tool.as_deref(),
+            Some("search")
+        );
+    }
+
+    #[tokio::test]
+    async fn failed_requests_escalate_to_a_bigger_model() {
+        // the first request fails, as the script is empty
+        let script = ModelScript::default();
+        let (mut agent, notifications) = build(AgentBuilder::default(), &script).await;
+        let bigger_model = script.clone();
+        let mut supervisor = Supervisor::new(notifications)
+            .on_prompt_failures(1, SupervisorAction::Escalate("big".into()))
+            .on_alert(move |_| bigger_model.push(Message::assistant("Hi from big.")));
+
+        let reply = supervisor.invoke(&mut agent, "Hello").await.unwrap();
+
+        assert_eq!(reply.content.as_deref(), Some("Hi from big."));
+        assert_eq!(agent.model, "big");
+        let requests = script.requests();
+        assert_eq!(requests.len(), 2);
+        assert_eq!(requests[1].base.model, "big");
+        // the failed run left nothing behind
+        assert_eq!(
+            agent
+                .history
+                .iter()
+                .filter(|m| m.role == crate::Role::User)
+                .count(),
+            1
+        );
+    }
+}
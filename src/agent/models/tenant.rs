@@ -0,0 +1,359 @@
+use std::{
+    collections::HashMap,
+    fmt,
+    sync::{Arc, Mutex},
+};
+
+use serde_json::Value;
+
+use crate::{
+    agent::models::snapshot::UsageTracker,
+    services::llm::{ClientConfig, InferenceClient, InferenceClientError, SecretString},
+    Agent, AgentError, Message, TokenUsage,
+};
+
+/// Tenants whose clients are kept, the least recently used one is dropped
+/// beyond this.
+const MAX_TENANT_CLIENTS: usize = 256;
+
+/// Credentials and usage of one tenant of a service built on a shared
+/// agent, see [`Agent::invoke_flow_for_tenant`].
+///
+/// Overrides left unset fall back to the agent's [`ClientConfig`]. The
+/// tokens of the tenant's invocations are counted in [`usage`](Self::usage),
+/// shared by clones of the context, and still in [`Agent::usage`].
+///
+/// ```
+/// use reagent_rs::TenantContext;
+///
+/// let tenant = TenantContext::new("acme")
+///     .api_key("sk-acme")
+///     .organization("org-acme");
+/// assert_eq!(tenant.usage().requests, 0);
+/// ```
+#[derive(Clone)]
+pub struct TenantContext {
+    pub id: String,
+    overrides: TenantOverrides,
+    usage: UsageTracker,
+}
+
+#[derive(Clone, Default, PartialEq, Eq)]
+struct TenantOverrides {
+    api_key: Option<SecretString>,
+    organization: Option<String>,
+    base_url: Option<String>,
+}
+
+impl TenantContext {
+    pub fn new(id: impl Into<String>) -> Self {
+        Self {
+            id: id.into(),
+            overrides: TenantOverrides::default(),
+            usage: UsageTracker::default(),
+        }
+    }
+
+    pub fn api_key(mut self, api_key: impl Into<SecretString>) -> Self {
+        self.overrides.api_key = Some(api_key.into());
+        self
+    }
+
+    pub fn organization(mut self, organization: impl Into<String>) -> Self {
+        self.overrides.organization = Some(organization.into());
+        self
+    }
+
+    pub fn base_url(mut self, base_url: impl Into<String>) -> Self {
+        self.overrides.base_url = Some(base_url.into());
+        self
+    }
+
+    /// Tokens used by the invocations made for this tenant.
+    pub fn usage(&self) -> TokenUsage {
+        self.usage.get()
+    }
+
+    pub(crate) fn usage_tracker(&self) -> &UsageTracker {
+        &self.usage
+    }
+
+    /// `config` with the tenant's overrides applied.
+    fn apply(&self, config: &ClientConfig) -> ClientConfig {
+        let overrides = self.overrides.clone();
+        ClientConfig {
+            api_key: overrides.api_key.or_else(|| config.api_key.clone()),
+            organization: overrides
+                .organization
+                .or_else(|| config.organization.clone()),
+            base_url: overrides.base_url.or_else(|| config.base_url.clone()),
+            ..config.clone()
+        }
+    }
+}
+
+impl fmt::Debug for TenantContext {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TenantContext")
+            .field("id", &self.id)
+            .field("organization", &self.overrides.organization)
+            .field("base_url", &self.overrides.base_url)
+            .field("usage", &self.usage)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Clients built for tenants, shared between clones of an agent so each
+/// tenant's HTTP client is only built once. Holds the clients of the
+/// [`MAX_TENANT_CLIENTS`] most recently served tenants.
+#[derive(Clone, Default)]
+pub(crate) struct TenantClients {
+    clients: Arc<Mutex<TenantClientCache>>,
+}
+
+#[derive(Default)]
+struct TenantClientCache {
+    /// Overrides the client was built with, the client and when it was
+    /// last used.
+    clients: HashMap<String, (TenantOverrides, InferenceClient, u64)>,
+    uses: u64,
+}
+
+impl TenantClients {
+    /// The client of `tenant`, built from `base` if its overrides changed
+    /// or it has none yet.
+    fn get(
+        &self,
+        tenant: &TenantContext,
+        base: &InferenceClient,
+    ) -> Result<InferenceClient, InferenceClientError> {
+        let mut cache = self.clients.lock().unwrap_or_else(|e| e.into_inner());
+        cache.uses += 1;
+        let now = cache.uses;
+        if let Some((overrides, client, used)) = cache.clients.get_mut(&tenant.id) {
+            if *overrides == tenant.overrides {
+                *used = now;
+                return Ok(client.clone());
+            }
+        }
+        let client = InferenceClient::try_from(tenant.apply(base.get_config()))?;
+        cache.clients.insert(
+            tenant.id.clone(),
+            (tenant.overrides.clone(), client.clone(), now),
+        );
+        if cache.clients.len() > MAX_TENANT_CLIENTS {
+            let oldest = cache
+                .clients
+                .iter()
+                .min_by_key(|(_, (_, _, used))| *used)
+                .map(|(id, _)| id.clone());
+            if let Some(oldest) = oldest {
+                cache.clients.remove(&oldest);
+            }
+        }
+        Ok(client)
+    }
+}
+
+impl fmt::Debug for TenantClients {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let cache = self.clients.lock().unwrap_or_else(|e| e.into_inner());
+        f.debug_list().entries(cache.clients.keys()).finish()
+    }
+}
+
+/// History and state of the conversation with a tenant, kept while the
+/// agent serves others.
+#[derive(Debug, Clone)]
+pub(crate) struct TenantConversation {
+    history: Vec<Message>,
+    state: HashMap<String, Value>,
+    /// Messages the history observers were told about.
+    observed: Vec<String>,
+}
+
+impl Agent {
+    /// Works like [`invoke_flow`](Agent::invoke_flow), with the model
+    /// requests sent with the API key, organization and base URL of
+    /// `tenant` and their tokens counted in [`TenantContext::usage`], so one
+    /// agent can serve many tenants. The sub-agents of built-in flows (e.g.
+    /// the summarizer) do the same; tools get the tenant in their
+    /// [`ToolContext`](crate::ToolContext) to pass it on.
+    ///
+    /// Every tenant has a conversation of its own: the invocation runs on
+    /// the tenant's history and state (see
+    /// [`tenant_history`](Agent::tenant_history)), which start out with
+    /// just the system prompt. The agent's own history and state are back
+    /// in place once it returns.
+    pub async fn invoke_flow_for_tenant(
+        &mut self,
+        tenant: &TenantContext,
+        prompt: impl Into<String>,
+    ) -> Result<Message, AgentError> {
+        let conversation = self
+            .tenant_conversations
+            .remove(&tenant.id)
+            .unwrap_or_else(|| TenantConversation {
+                history: vec![Message::system(self.system_prompt.clone())],
+                state: HashMap::new(),
+                observed: Vec::new(),
+            });
+        let outer = self.swap_conversation(conversation);
+        let outer_tenant = self.tenant.replace(tenant.clone());
+        let result = self.invoke_flow(prompt).await;
+        self.tenant = outer_tenant;
+        let conversation = self.swap_conversation(outer);
+        self.tenant_conversations
+            .insert(tenant.id.clone(), conversation);
+        result
+    }
+
+    /// History of the conversation with the tenant `id`, `None` before the
+    /// agent was invoked for it, see
+    /// [`invoke_flow_for_tenant`](Agent::invoke_flow_for_tenant).
+    pub fn tenant_history(&self, id: &str) -> Option<&[Message]> {
+        self.tenant_conversations
+            .get(id)
+            .map(|conversation| conversation.history.as_slice())
+    }
+
+    /// Drop the conversation with the tenant `id`, e.g. when its session
+    /// ends. Returns whether there was one.
+    pub fn forget_tenant(&mut self, id: &str) -> bool {
+        self.tenant_conversations.remove(id).is_some()
+    }
+
+    /// Make `conversation` the current one and return the one it replaces.
+    fn swap_conversation(&mut self, conversation: TenantConversation) -> TenantConversation {
+        TenantConversation {
+            history: std::mem::replace(&mut self.history, conversation.history),
+            state: std::mem::replace(&mut self.state, conversation.state),
+            observed: self.history_observers.replace_seen(conversation.observed),
+        }
+    }
+
+    /// Tenant the running invocation is made for, see
+    /// [`invoke_flow_for_tenant`](Agent::invoke_flow_for_tenant).
+    pub fn tenant(&self) -> Option<&TenantContext> {
+        self.tenant.as_ref()
+    }
+
+    /// Client of the running invocation: the tenant's, if it is made for
+    /// one.
+    pub(crate) fn client(&self) -> Result<InferenceClient, InferenceClientError> {
+        match &self.tenant {
+            Some(tenant) => self.tenant_clients.get(tenant, &self.inference_client),
+            None => Ok(self.inference_client.clone()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{testing::FlowTestHarness, AgentBuilder, Role};
+
+    #[tokio::test]
+    async fn tenants_have_their_own_client_and_usage() {
+        let builder = AgentBuilder::default()
+            .set_model("test")
+            .set_api_key("sk-platform");
+        let mut harness = FlowTestHarness::new(builder)
+            .await
+            .unwrap()
+            .reply("For acme.")
+            .reply("For globex.")
+            .reply("For acme again.");
+        let acme = TenantContext::new("acme").api_key("sk-acme");
+        let globex = TenantContext::new("globex").organization("org-globex");
+
+        let agent = harness.agent_mut();
+        agent.invoke_flow_for_tenant(&acme, "1").await.unwrap();
+        agent.invoke_flow_for_tenant(&globex, "2").await.unwrap();
+        agent.invoke_flow_for_tenant(&acme, "3").await.unwrap();
+
+        assert_eq!(acme.usage().requests, 2);
+        assert_eq!(globex.usage().requests, 1);
+        assert_eq!(agent.usage().requests, 3);
+        assert!(agent.tenant().is_none());
+        let config = agent.client().unwrap().get_config().clone();
+        assert_eq!(config.api_key, Some("sk-platform".into()));
+
+        let acme_config = agent
+            .tenant_clients
+            .get(&acme, &agent.inference_client)
+            .unwrap()
+            .get_config()
+            .clone();
+        assert_eq!(acme_config.api_key, Some("sk-acme".into()));
+        let globex_config = agent
+            .tenant_clients
+            .get(&globex, &agent.inference_client)
+            .unwrap()
+            .get_config()
+            .clone();
+        assert_eq!(globex_config.api_key, Some("sk-platform".into()));
+        assert_eq!(globex_config.organization.as_deref(), Some("org-globex"));
+    }
+
+    #[tokio::test]
+    async fn tenants_have_their_own_conversation() {
+        let builder = AgentBuilder::default()
+            .set_model("test")
+            .set_auto_title(true);
+        let mut harness = FlowTestHarness::new(builder)
+            .await
+            .unwrap()
+            .reply("Hello, acme.")
+            .reply(r#"{"title": "Acme says hi", "summary": "Greetings."}"#)
+            .reply("Hello, globex.")
+            .reply(r#"{"title": "Globex says hi", "summary": "Greetings."}"#)
+            .reply("Still acme.");
+        let acme = TenantContext::new("acme").api_key("sk-acme");
+        let globex = TenantContext::new("globex").api_key("sk-globex");
+
+        let agent = harness.agent_mut();
+        agent
+            .invoke_flow_for_tenant(&acme, "I am acme.")
+            .await
+            .unwrap();
+        agent
+            .invoke_flow_for_tenant(&globex, "I am globex.")
+            .await
+            .unwrap();
+        agent
+            .invoke_flow_for_tenant(&acme, "Who am I?")
+            .await
+            .unwrap();
+
+        // the title requests of the summarizer count for the tenant
+        assert_eq!(acme.usage().requests, 3);
+        assert_eq!(globex.usage().requests, 2);
+
+        let requests = harness.requests();
+        let sent = |i: usize| {
+            requests[i]
+                .messages
+                .iter()
+                .filter_map(|m| m.content.clone())
+                .collect::<Vec<_>>()
+                .join("\n")
+        };
+        assert!(!sent(2).contains("I am acme."));
+        assert!(sent(4).contains("I am acme."));
+        assert!(!sent(4).contains("globex"));
+
+        let agent = harness.agent();
+        assert_eq!(agent.history.len(), 1);
+        assert_eq!(agent.title(), None);
+        let acme_history = agent.tenant_history("acme").unwrap();
+        assert_eq!(
+            acme_history.iter().filter(|m| m.role == Role::User).count(),
+            2
+        );
+        assert_eq!(
+            agent.tenant_conversations["globex"].state["title"],
+            "Globex says hi"
+        );
+    }
+}
@@ -0,0 +1,232 @@
+use std::{
+    collections::hash_map::DefaultHasher,
+    collections::HashMap,
+    fmt,
+    fs::OpenOptions,
+    hash::{Hash, Hasher},
+    io::Write,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::services::runtime::{self, SystemTime};
+
+/// One tool invocation as recorded by the agent.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ToolAuditEntry {
+    /// Name of the tool the model asked for.
+    pub tool_name: String,
+    /// Tool call id from the model response, if the provider sent one.
+    pub call_id: Option<String>,
+    /// Hash of the call arguments, so repeated calls can be spotted
+    /// without storing (possibly sensitive) argument values.
+    pub args_hash: String,
+    /// Unix timestamp in milliseconds at which the call started.
+    pub started_at_ms: u64,
+    /// Time spent executing the tool, in milliseconds.
+    pub duration_ms: u64,
+    /// Length of the tool output (or error message) in bytes.
+    pub result_size: usize,
+    pub success: bool,
+    /// Error message for failed calls.
+    pub error: Option<String>,
+}
+
+/// Aggregated numbers for a single tool, see [`Agent::tool_stats`](crate::Agent::tool_stats).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ToolStats {
+    pub calls: usize,
+    pub failures: usize,
+    pub total_duration: Duration,
+    pub total_result_size: usize,
+}
+
+impl ToolStats {
+    /// Share of failed calls, 0.0 for a tool that was never called.
+    pub fn failure_rate(&self) -> f32 {
+        if self.calls == 0 {
+            return 0.0;
+        }
+        self.failures as f32 / self.calls as f32
+    }
+
+    /// Share of successful calls, 1.0 for a tool that was never called.
+    pub fn success_rate(&self) -> f32 {
+        1.0 - self.failure_rate()
+    }
+
+    /// Mean time spent per call.
+    pub fn average_duration(&self) -> Duration {
+        match u32::try_from(self.calls) {
+            Ok(calls) if calls > 0 => self.total_duration / calls,
+            _ => Duration::ZERO,
+        }
+    }
+}
+
+/// Audit trail of tool invocations, shared between clones of the same agent.
+/// Entries are optionally appended to a JSON Lines file as they are recorded.
+#[derive(Clone, Default)]
+pub(crate) struct ToolAudit {
+    entries: Arc<Mutex<Vec<ToolAuditEntry>>>,
+    file: Option<PathBuf>,
+}
+
+impl ToolAudit {
+    pub(crate) fn new(file: Option<PathBuf>) -> Self {
+        Self {
+            entries: Arc::default(),
+            file,
+        }
+    }
+
+    /// Keep `entry` and append it to the file, on a blocking thread.
+    pub(crate) async fn record(&self, entry: ToolAuditEntry) {
+        self.entries
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(entry.clone());
+        if let Some(path) = &self.file {
+            let written = runtime::blocking({
+                let path = path.clone();
+                move || append_line(&path, &entry)
+            });
+            if let Err(e) = written.await {
+                tracing::warn!(
+                    error = %e,
+                    path = %path.display(),
+                    "Failed to persist tool audit entry"
+                );
+            }
+        }
+    }
+
+    pub(crate) fn entries(&self) -> Vec<ToolAuditEntry> {
+        self.entries
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    pub(crate) fn stats(&self) -> HashMap<String, ToolStats> {
+        let entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        let mut stats: HashMap<String, ToolStats> = HashMap::new();
+        for entry in entries.iter() {
+            let tool = stats.entry(entry.tool_name.clone()).or_default();
+            tool.calls += 1;
+            if !entry.success {
+                tool.failures += 1;
+            }
+            tool.total_duration += Duration::from_millis(entry.duration_ms);
+            tool.total_result_size += entry.result_size;
+        }
+        stats
+    }
+
+    pub(crate) fn clear(&self) {
+        self.entries
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clear();
+    }
+}
+
+impl fmt::Debug for ToolAudit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        f.debug_struct("ToolAudit")
+            .field("entries", &entries.len())
+            .field("file", &self.file)
+            .finish()
+    }
+}
+
+/// Hex hash of the serialized arguments.
+pub(crate) fn hash_arguments(arguments: &Value) -> String {
+    let mut hasher = DefaultHasher::new();
+    arguments.to_string().hash(&mut hasher);
+    format!("{:016x}", hasher.finish())
+}
+
+pub(crate) fn unix_millis(time: SystemTime) -> u64 {
+    time.duration_since(SystemTime::UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+fn append_line(path: &Path, entry: &ToolAuditEntry) -> std::io::Result<()> {
+    let line = serde_json::to_string(entry)? + "\n";
+    // one write, so lines of concurrent tool calls don't interleave
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    file.write_all(line.as_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(tool_name: &str, success: bool, duration_ms: u64) -> ToolAuditEntry {
+        ToolAuditEntry {
+            tool_name: tool_name.into(),
+            call_id: None,
+            args_hash: hash_arguments(&serde_json::json!({ "a": 1 })),
+            started_at_ms: 0,
+            duration_ms,
+            result_size: 4,
+            success,
+            error: (!success).then(|| "boom".into()),
+        }
+    }
+
+    #[tokio::test]
+    async fn stats_are_aggregated_per_tool() {
+        let audit = ToolAudit::default();
+        audit.record(entry("bash", true, 10)).await;
+        audit.record(entry("bash", false, 5)).await;
+        audit.record(entry("search", true, 1)).await;
+
+        let stats = audit.stats();
+        assert_eq!(
+            stats["bash"],
+            ToolStats {
+                calls: 2,
+                failures: 1,
+                total_duration: Duration::from_millis(15),
+                total_result_size: 8,
+            }
+        );
+        assert_eq!(stats["search"].calls, 1);
+    }
+
+    #[tokio::test]
+    async fn entries_are_appended_to_file() {
+        let path =
+            std::env::temp_dir().join(format!("reagent-audit-{}.jsonl", uuid::Uuid::new_v4()));
+        let audit = ToolAudit::new(Some(path.clone()));
+        audit.record(entry("bash", true, 10)).await;
+        audit.record(entry("bash", false, 5)).await;
+
+        let persisted: Vec<ToolAuditEntry> = std::fs::read_to_string(&path)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(persisted, audit.entries());
+    }
+
+    #[test]
+    fn equal_arguments_hash_equally() {
+        let args = serde_json::json!({ "command": "ls" });
+        assert_eq!(hash_arguments(&args), hash_arguments(&args.clone()));
+        assert_ne!(
+            hash_arguments(&args),
+            hash_arguments(&serde_json::json!({ "command": "pwd" }))
+        );
+    }
+}
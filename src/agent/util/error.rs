@@ -1,4 +1,4 @@
-use crate::services::llm::InferenceClientError;
+use crate::services::llm::{message::Message, InferenceClientError};
 
 #[derive(Debug)]
 pub enum InvocationError {
@@ -6,6 +6,13 @@ pub enum InvocationError {
     InferenceError(InferenceClientError),
     /// Provided JSON schema for response format could not be parsed.
     InvalidJsonSchema(String),
+    /// Streamed generation broke off after part of the response was received.
+    /// The partial message is appended to the agent history, so it can be
+    /// resumed with [`Agent::continue_last`](crate::Agent::continue_last).
+    Interrupted {
+        partial: Box<Message>,
+        error: InferenceClientError,
+    },
 }
 
 impl From<InferenceClientError> for InvocationError {
@@ -24,6 +31,9 @@ impl std::fmt::Display for InvocationError {
                 write!(f, "Client error during inference: {inference_client_error}")
             }
             InvocationError::InvalidJsonSchema(e) => write!(f, "Invalid JSON schema provided: {e}"),
+            InvocationError::Interrupted { error, .. } => {
+                write!(f, "Stream interrupted after a partial response: {error}")
+            }
         }
     }
 }
@@ -5,18 +5,59 @@ use serde_json::Value;
 use tokio::sync::mpsc::Sender;
 
 use crate::{
+    agent::models::{current_prompt, insert_examples, repair_structured_output},
     services::llm::{
-        message::Message, BaseRequest, ClientBuilder, InferenceOptions, ResponseFormatConfig,
-        SchemaSpec,
+        assign_tool_call_ids, message::Message, repair_tool_call_pairing, BaseRequest,
+        ClientBuilder, InferenceOptions, ResponseFormatConfig, SchemaSpec, SecretString,
     },
+    templates::insert_context,
+    tools::repair_tool_call_arguments,
     Agent, ChatRequest, ChatResponse, ClientConfig, InvocationError, InvocationRequest,
-    Notification, Provider, Tool,
+    Notification, NotificationHandler, Provider, TokenBatching, Tool, ToolChoice,
 };
 
+/// Apply what the agent changes in the history of every request: scratch
+/// notes are dropped, then tool reliability hints, deduplication, tool call
+/// pairing, context and few-shot examples are applied. A `preview` makes no
+/// requests of its own.
+pub(crate) async fn prepare_messages(
+    agent: &Agent,
+    messages: &mut Vec<Message>,
+    tools: Option<&mut Vec<Tool>>,
+    preview: bool,
+) {
+    messages.retain(|m| !m.is_scratch());
+    if let Some(experiment) = &agent.experiment {
+        let base = agent.library_prompt.as_ref().map(|prompt| prompt.content());
+        experiment.apply(base, messages);
+    }
+    if let (Some(policy), Some(tools)) = (&agent.tool_reliability, tools) {
+        policy.apply(&agent.tool_stats(), tools, messages);
+    }
+    if let Some(dedup) = &agent.history_dedup {
+        dedup.apply(messages);
+    }
+    for issue in repair_tool_call_pairing(messages) {
+        tracing::warn!(agent = agent.name.as_str(), "Tool call pairing: {issue}");
+    }
+    if let Some(providers) = agent.context_providers.as_ref().filter(|p| !p.is_empty()) {
+        if let Some(context) = providers.message(current_prompt(messages)).await {
+            insert_context(messages, context);
+        }
+    }
+    if let Some(few_shot) = agent.few_shot.as_ref().filter(|set| !set.is_empty()) {
+        // a preview sends no embedding requests, it takes the first examples
+        let prompt = current_prompt(messages).filter(|_| !preview);
+        let examples = few_shot.select(agent, prompt).await;
+        insert_examples(messages, &examples);
+    }
+}
+
 #[derive(Debug, Clone, Default)]
 pub struct InvocationBuilder {
     model: Option<String>,
     format: Option<Value>,
+    grammar: Option<String>,
     stream: Option<bool>,
     keep_alive: Option<String>,
 
@@ -25,6 +66,8 @@ pub struct InvocationBuilder {
     // payload
     messages: Option<Vec<Message>>,
     tools: Option<Vec<Tool>>,
+    tool_choice: Option<ToolChoice>,
+    parallel_tool_calls: Option<bool>,
 
     // flattened options: None means inherit, Some(_) means override
     opts: InferenceOptions,
@@ -35,6 +78,8 @@ pub struct InvocationBuilder {
     client_config: ClientConfig,
     /// Notification channel to send notifications to
     notification_channel: Option<Sender<Notification>>,
+    /// Batching policy for streamed token notifications
+    token_batching: Option<TokenBatching>,
 
     /// Response schema input plus optional provider hints.
     response_format: ResponseFormatConfig,
@@ -49,10 +94,21 @@ impl InvocationBuilder {
         self.format = Some(v);
         self
     }
+    /// GBNF grammar constraining the output (Ollama only).
+    pub fn grammar(mut self, v: impl Into<String>) -> Self {
+        self.grammar = Some(v.into());
+        self
+    }
     pub fn stream(mut self, v: bool) -> Self {
         self.stream = Some(v);
         self
     }
+
+    /// Batch streamed token notifications, see [`TokenBatching`].
+    pub fn token_batching(mut self, v: TokenBatching) -> Self {
+        self.token_batching = Some(v);
+        self
+    }
     pub fn keep_alive(mut self, v: impl Into<String>) -> Self {
         self.keep_alive = Some(v.into());
         self
@@ -84,6 +140,16 @@ impl InvocationBuilder {
         self.tools = Some(tools);
         self
     }
+    /// Control whether and which tool the model has to call in this turn.
+    pub fn tool_choice(mut self, v: ToolChoice) -> Self {
+        self.tool_choice = Some(v);
+        self
+    }
+    /// Allow or forbid several tool calls in the response.
+    pub fn parallel_tool_calls(mut self, v: bool) -> Self {
+        self.parallel_tool_calls = Some(v);
+        self
+    }
     pub fn num_ctx(mut self, v: u32) -> Self {
         self.opts.num_ctx = Some(v);
         self
@@ -136,6 +202,10 @@ impl InvocationBuilder {
         self.opts.max_tokens = Some(v);
         self
     }
+    pub fn extra_option(mut self, key: impl Into<String>, value: impl Into<Value>) -> Self {
+        self.opts.extra_options.insert(key.into(), value.into());
+        self
+    }
     pub fn strip_thinking(mut self, strip_thinking: bool) -> Self {
         self.strip_thinking = Some(strip_thinking);
         self
@@ -167,7 +237,7 @@ impl InvocationBuilder {
     }
 
     /// Set the API key used by the provider client.
-    pub fn set_api_key(mut self, api_key: impl Into<String>) -> Self {
+    pub fn set_api_key(mut self, api_key: impl Into<SecretString>) -> Self {
         self.client_config = self.client_config.api_key(Some(api_key));
         self
     }
@@ -184,6 +254,30 @@ impl InvocationBuilder {
         self
     }
 
+    /// Route requests through a proxy, e.g. `http://proxy.corp:3128`.
+    pub fn set_proxy(mut self, proxy: impl Into<String>) -> Self {
+        self.client_config = self.client_config.proxy(Some(proxy));
+        self
+    }
+
+    /// Trust the root certificates in a PEM file, in addition to the system ones.
+    pub fn set_ca_cert_path(mut self, path: impl Into<std::path::PathBuf>) -> Self {
+        self.client_config = self.client_config.ca_cert_path(Some(path));
+        self
+    }
+
+    /// Total time allowed for the request, including a streamed response.
+    pub fn set_request_timeout(mut self, timeout: std::time::Duration) -> Self {
+        self.client_config = self.client_config.timeout(Some(timeout));
+        self
+    }
+
+    /// Time allowed to establish a connection to the provider.
+    pub fn set_connect_timeout(mut self, timeout: std::time::Duration) -> Self {
+        self.client_config = self.client_config.connect_timeout(Some(timeout));
+        self
+    }
+
     pub fn notification_channel(
         mut self,
         notification_channel: Option<Sender<Notification>>,
@@ -228,6 +322,11 @@ impl InvocationBuilder {
 
     pub async fn invoke_with(self, agent: &mut Agent) -> Result<ChatResponse, InvocationError> {
         let model = self.model.or(Some(agent.model.clone()));
+        // replies are only repaired against the agent's own response format
+        let prompted_schema = agent
+            .prompted_schema
+            .clone()
+            .filter(|_| self.format.is_none() && !self.response_format.is_set());
         let format = match self.format {
             Some(format) => Some(format),
             None => match self
@@ -235,20 +334,30 @@ impl InvocationBuilder {
                 .resolve()
                 .map_err(InvocationError::InvalidJsonSchema)?
             {
-                Some(spec) => Some(agent.inference_client.structured_output_format(&spec)?),
+                Some(spec) => Some(agent.client()?.structured_output_format(&spec)?),
                 None => agent.response_format.clone(),
             },
         };
         let stream = self.stream.or(Some(agent.stream));
         let keep_alive = self.keep_alive.or(agent.keep_alive.clone());
-        let messages = self
+        if let Some((user, start)) = &agent.speaker {
+            user.claim_messages(&mut agent.history, *start);
+        }
+        agent.sync_history();
+        let mut messages = self
             .messages
             .or(Some(agent.history.clone()))
             .unwrap_or_default();
-        let tools = match self.use_tools {
+        let mut tools = match self.use_tools {
             Some(false) => None,
             Some(true) | None => self.tools.or(agent.tools.clone()),
         };
+        prepare_messages(agent, &mut messages, tools.as_mut(), false).await;
+        let tool_choice = match self.use_tools {
+            Some(false) => None,
+            Some(true) | None => self.tool_choice.or(agent.tool_choice.clone()),
+        };
+        let parallel_tool_calls = self.parallel_tool_calls.or(agent.parallel_tool_calls);
 
         let name = self
             .name
@@ -264,32 +373,82 @@ impl InvocationBuilder {
             return Err(InvocationError::ModelNotDefined);
         };
 
+        let client = agent.client()?;
+        if !agent.models_used.iter().any(|(used, _)| *used == model) {
+            agent.models_used.push((model.clone(), client.clone()));
+        }
+
         let request = ChatRequest {
             base: BaseRequest {
                 model,
                 format,
+                grammar: self.grammar.or(agent.grammar.clone()),
                 options,
                 stream,
                 keep_alive,
             },
             messages,
             tools,
+            tool_choice,
+            parallel_tool_calls,
         };
 
         let invcation_request = InvocationRequest::new(
             self.strip_thinking.unwrap_or(agent.strip_thinking),
             request,
-            agent.inference_client.clone(),
+            client,
             agent.notification_channel.clone(),
             name,
-        );
+        )
+        .with_token_batching(self.token_batching.or(agent.token_batching))
+        .with_progress(agent.progress.handle());
 
+        // boxed, the requests are large futures
+        let limit = &agent.concurrency;
         let response = match &invcation_request.request.base.stream {
-            Some(true) => super::invocations::invoke_streaming(invcation_request).await?,
-            _ => super::invocations::invoke_nonstreaming(invcation_request).await?,
+            Some(true) => {
+                let request = super::invocations::invoke_streaming(invcation_request);
+                limit.run(Box::pin(request)).await
+            }
+            _ => {
+                let request = super::invocations::invoke_nonstreaming(invcation_request);
+                limit.run(Box::pin(request)).await
+            }
         };
 
+        // keep what was generated before an interruption, so it can be continued
+        if let Err(InvocationError::Interrupted { partial, .. }) = &response {
+            agent.history.push(partial.as_ref().clone());
+        }
+        let mut response = response?;
+
+        let has_tool_calls = response
+            .message
+            .tool_calls
+            .as_ref()
+            .is_some_and(|calls| !calls.is_empty());
+        if let (Some(schema), false) = (&prompted_schema, has_tool_calls) {
+            for issue in repair_structured_output(&mut response.message, schema) {
+                tracing::warn!(agent = agent.name.as_str(), "Structured output: {issue}");
+            }
+        }
+
+        assign_tool_call_ids(&mut response.message);
+        for (call, original) in repair_tool_call_arguments(&mut response.message) {
+            tracing::warn!(
+                agent = agent.name.as_str(),
+                tool = call.function.name.as_str(),
+                "Repaired malformed tool call arguments"
+            );
+            agent.notify_tool_arguments_repaired(call, original).await;
+        }
+        agent.usage.record(&response);
+        agent.invocation_stats.record(&response);
+        if let Some(tenant) = &agent.tenant {
+            tenant.usage_tracker().record(&response);
+        }
         agent.history.push(response.message.clone());
+        agent.sync_history();
 
         Ok(response)
     }
@@ -308,6 +467,7 @@ impl InvocationBuilder {
             Some(true) => self.tools.take(),
             None => self.tools.take(),
         };
+        let tool_choice = self.tool_choice.take().filter(|_| tools.is_some());
 
         let client = self.client_config.build()?;
 
@@ -325,12 +485,20 @@ impl InvocationBuilder {
             base: BaseRequest {
                 model,
                 format,
+                grammar: self.grammar.take(),
                 options,
                 stream: self.stream,
                 keep_alive: self.keep_alive.take(),
             },
-            messages: self.messages.unwrap_or_default(),
+            messages: self
+                .messages
+                .unwrap_or_default()
+                .into_iter()
+                .filter(|m| !m.is_scratch())
+                .collect(),
             tools,
+            tool_choice,
+            parallel_tool_calls: self.parallel_tool_calls,
         };
 
         let invcation_request = InvocationRequest::new(
@@ -339,7 +507,8 @@ impl InvocationBuilder {
             client,
             self.notification_channel.take(),
             name,
-        );
+        )
+        .with_token_batching(self.token_batching);
 
         let response = match &invcation_request.request.base.stream {
             Some(true) => super::invocations::invoke_streaming(invcation_request).await?,
@@ -1,12 +1,18 @@
 use tokio::sync::mpsc::Sender;
 
-use crate::{services::llm::InferenceClient, ChatRequest, Notification, NotificationOutputChannel};
+use crate::{
+    agent::models::FlowProgress, services::llm::InferenceClient, ChatRequest, Notification,
+    NotificationOutputChannel, TokenBatching,
+};
 
 pub struct InvocationRequest {
     pub strip_thinking: bool,
     pub request: ChatRequest,
     pub client: InferenceClient,
     pub notification_channel: NotificationOutputChannel,
+    pub token_batching: Option<TokenBatching>,
+    /// Receives the content of streamed responses while they arrive.
+    pub(crate) progress: Option<FlowProgress>,
 }
 
 impl InvocationRequest {
@@ -23,6 +29,20 @@ impl InvocationRequest {
             request,
             client,
             notification_channel,
+            token_batching: None,
+            progress: None,
         }
     }
+
+    /// Batch token notifications of streamed responses.
+    pub fn with_token_batching(mut self, token_batching: Option<TokenBatching>) -> Self {
+        self.token_batching = token_batching;
+        self
+    }
+
+    /// Track streamed content so it can be returned if the flow is aborted.
+    pub(crate) fn with_progress(mut self, progress: FlowProgress) -> Self {
+        self.progress = Some(progress);
+        self
+    }
 }
@@ -4,13 +4,17 @@ use tracing::{error, span, Level, Span};
 use tracing_opentelemetry::OpenTelemetrySpanExt;
 
 use crate::{
-    notifications::Token,
-    services::llm::{
-        message::Message,
-        models::chat::{ChatResponse, ChatStreamChunk},
-        InferenceClientError,
+    notifications::{Token, TokenBatcher},
+    services::{
+        llm::{
+            message::Message,
+            models::chat::{ChatResponse, ChatStreamChunk},
+            span_content, InferenceClientError, RequestLogging,
+        },
+        runtime,
     },
-    ChatRequest, InvocationError, InvocationRequest, NotificationHandler, ToolCall,
+    ChatRequest, ErrorDetails, InvocationError, InvocationRequest, NotificationHandler,
+    NotificationOutputChannel, ToolCall,
 };
 
 #[derive(Debug, Serialize)]
@@ -28,13 +32,15 @@ pub(super) async fn invoke_nonstreaming(
         request,
         client,
         notification_channel,
+        ..
     } = invocation_request;
 
     notification_channel
         .notify_prompt_request(request.clone())
         .await;
 
-    let gen_span = set_telemetry_request_attributes(&request);
+    let logging = client.get_config().request_logging.clone();
+    let gen_span = set_telemetry_request_attributes(&request, logging.as_ref());
     let _guard = gen_span.enter();
 
     let raw = client.chat(request).await;
@@ -43,14 +49,14 @@ pub(super) async fn invoke_nonstreaming(
         Ok(resp) => resp,
         Err(e) => {
             notification_channel
-                .notify_prompt_error(e.to_string())
+                .notify_prompt_failure(ErrorDetails::from_inference(&e))
                 .await;
             extract_error_telemetry(&gen_span, e.to_string().as_str());
             return Err(e.into());
         }
     };
 
-    extract_response_telemetry(&gen_span, &resp);
+    extract_response_telemetry(&gen_span, &resp, logging.as_ref());
 
     notification_channel
         .notify_prompt_success(resp.clone())
@@ -71,20 +77,23 @@ pub(super) async fn invoke_streaming(
         request,
         client,
         notification_channel,
+        token_batching,
+        progress,
     } = invocation_request;
 
     notification_channel
         .notify_prompt_request(request.clone())
         .await;
 
-    let gen_span = set_telemetry_request_attributes(&request);
+    let logging = client.get_config().request_logging.clone();
+    let gen_span = set_telemetry_request_attributes(&request, logging.as_ref());
     let _guard = gen_span.enter();
 
     let stream = match client.chat_stream(request).await {
         Ok(s) => s,
         Err(e) => {
             notification_channel
-                .notify_prompt_error(e.to_string())
+                .notify_prompt_failure(ErrorDetails::from_inference(&e))
                 .await;
             extract_error_telemetry(&gen_span, e.to_string().as_str());
             return Err(e.into());
@@ -97,15 +106,37 @@ pub(super) async fn invoke_streaming(
     let mut latest_message: Option<Message> = None;
     let mut tool_calls: Option<Vec<ToolCall>> = None;
     let mut done_chunk: Option<ChatStreamChunk> = None;
+    let mut batcher = token_batching.map(TokenBatcher::new);
+
+    loop {
+        // while a batch is pending, wait for the next chunk only until the
+        // batch has to be sent
+        let next = match batcher.as_ref().and_then(TokenBatcher::deadline) {
+            Some(deadline) => match runtime::timeout_at(deadline, stream.next()).await {
+                Ok(next) => next,
+                Err(_) => {
+                    flush_tokens(&notification_channel, batcher.as_mut()).await;
+                    continue;
+                }
+            },
+            None => stream.next().await,
+        };
+        let Some(chunk_res) = next else {
+            break;
+        };
 
-    while let Some(chunk_res) = stream.next().await {
         let chunk = match chunk_res {
             Ok(c) => c,
             Err(e) => {
+                flush_tokens(&notification_channel, batcher.as_mut()).await;
                 notification_channel
-                    .notify_prompt_error(e.to_string())
+                    .notify_prompt_failure(ErrorDetails::from_inference(&e))
                     .await;
-                return Err(e.into());
+                // the partial content is returned in the error instead
+                if let Some(progress) = &progress {
+                    progress.clear_partial();
+                }
+                return Err(interrupted(e, latest_message, full_content));
             }
         };
 
@@ -123,12 +154,18 @@ pub(super) async fn invoke_streaming(
             }
 
             if let Some(tok) = &msg.content {
-                notification_channel
-                    .notify_token(Token {
-                        tag: None,
-                        value: tok.clone(),
-                    })
-                    .await;
+                if let Some(progress) = &progress {
+                    progress.push_token(tok);
+                }
+                let batch = match batcher.as_mut() {
+                    Some(batcher) => batcher.push(tok),
+                    None => Some(tok.clone()),
+                };
+                if let Some(value) = batch {
+                    notification_channel
+                        .notify_token(Token { tag: None, value })
+                        .await;
+                }
                 match full_content.as_mut() {
                     None => full_content = Some(tok.to_owned()),
                     Some(content) => content.push_str(tok),
@@ -139,10 +176,20 @@ pub(super) async fn invoke_streaming(
         }
     }
 
+    // tokens still buffered have to go out before the invocation ends
+    flush_tokens(&notification_channel, batcher.as_mut()).await;
+    if let Some(progress) = &progress {
+        progress.clear_partial();
+    }
+
     let Some(chunk) = done_chunk else {
         let error_message = "stream ended without a final `done` chunk";
         extract_error_telemetry(&gen_span, error_message);
-        return Err(InferenceClientError::Api(error_message.into()).into());
+        return Err(interrupted(
+            InferenceClientError::Api(error_message.into()),
+            latest_message,
+            full_content,
+        ));
     };
 
     let mut final_msg = latest_message.unwrap_or_else(|| Message::assistant(String::new()));
@@ -163,7 +210,7 @@ pub(super) async fn invoke_streaming(
         eval_duration: chunk.eval_duration,
     };
 
-    extract_response_telemetry(&gen_span, &response);
+    extract_response_telemetry(&gen_span, &response, logging.as_ref());
 
     notification_channel
         .notify_prompt_success(response.clone())
@@ -176,6 +223,38 @@ pub(super) async fn invoke_streaming(
     Ok(response)
 }
 
+async fn flush_tokens(
+    notification_channel: &NotificationOutputChannel,
+    batcher: Option<&mut TokenBatcher>,
+) {
+    if let Some(value) = batcher.and_then(TokenBatcher::flush) {
+        notification_channel
+            .notify_token(Token { tag: None, value })
+            .await;
+    }
+}
+
+/// Error for a stream that broke off. If any text was generated before that,
+/// the partial message is kept so the generation can be continued.
+/// Incomplete tool calls are dropped, they can't be executed anyway.
+fn interrupted(
+    error: InferenceClientError,
+    latest_message: Option<Message>,
+    content: Option<String>,
+) -> InvocationError {
+    let Some(content) = content else {
+        return error.into();
+    };
+
+    let mut partial = latest_message.unwrap_or_else(|| Message::assistant(String::new()));
+    partial.content = Some(content);
+    partial.tool_calls = None;
+    InvocationError::Interrupted {
+        partial: Box::new(partial),
+        error,
+    }
+}
+
 fn strip_thinking_from_response(response: &mut ChatResponse) {
     if let Some(content) = response.message.content.clone() {
         if let Some(after) = content.split("</think>").nth(1) {
@@ -194,7 +273,11 @@ fn extract_error_telemetry(gen_span: &Span, error_message: &str) {
     error!("stream ended without a final `done` chunk");
 }
 
-fn extract_response_telemetry(gen_span: &Span, response: &ChatResponse) {
+fn extract_response_telemetry(
+    gen_span: &Span,
+    response: &ChatResponse,
+    logging: Option<&RequestLogging>,
+) {
     let prompt_tokens = response.prompt_eval_count.unwrap_or(0);
     let completion_tokens = response.eval_count.unwrap_or(0);
     let total_tokens = prompt_tokens + completion_tokens;
@@ -207,13 +290,19 @@ fn extract_response_telemetry(gen_span: &Span, response: &ChatResponse) {
 
     gen_span.set_attribute(
         "langfuse.observation.output",
-        serde_json::to_string_pretty(&response.message.content)
-            .unwrap_or(format!("{:#?}", response)),
+        span_content(
+            logging,
+            serde_json::to_string_pretty(&response.message.content)
+                .unwrap_or(format!("{:#?}", response)),
+        ),
     );
     gen_span.set_attribute("gen_ai.completion.0.role", "assistant");
     gen_span.set_attribute(
         "gen_ai.completion.0.content",
-        response.message.content.clone().unwrap_or_default(),
+        span_content(
+            logging,
+            response.message.content.clone().unwrap_or_default(),
+        ),
     );
 
     gen_span.set_attribute(
@@ -240,14 +329,18 @@ fn extract_response_telemetry(gen_span: &Span, response: &ChatResponse) {
     gen_span.set_attribute("otel.status_code", "OK");
 }
 
-fn set_telemetry_request_attributes(request: &ChatRequest) -> Span {
+fn set_telemetry_request_attributes(
+    request: &ChatRequest,
+    logging: Option<&RequestLogging>,
+) -> Span {
+    let input =
+        serde_json::to_string(&request.messages).unwrap_or(format!("{:#?}", request.messages));
     let gen_span = span!(
         Level::INFO,
         "Chat Request",
         "langfuse.observation.type" = "generation",
         "langfuse.observation.model.name" = request.base.model.as_str(),
-        "langfuse.observation.input" =
-            serde_json::to_string(&request.messages).unwrap_or(format!("{:#?}", request.messages)),
+        "langfuse.observation.input" = span_content(logging, input),
         "langfuse.observation.model.parameters" = serde_json::to_string(&request.base.options)
             .unwrap_or(format!("{:#?}", request.base.options)),
     );
@@ -0,0 +1,100 @@
+//! Blocking wrapper around [`Agent`](crate::Agent), for CLI tools, scripts
+//! and tests that do not want to set up an async runtime.
+//!
+//! ```no_run
+//! use reagent_rs::{blocking, AgentBuilder};
+//!
+//! fn main() -> Result<(), Box<dyn std::error::Error>> {
+//!     let mut agent = blocking::Agent::build(AgentBuilder::default().set_model("qwen3:0.6b"))?;
+//!     let reply = agent.invoke_flow("Say hello")?;
+//!     println!("{:?}", reply.content);
+//!     Ok(())
+//! }
+//! ```
+
+use serde::{de::DeserializeOwned, Serialize};
+use tokio::runtime::Runtime;
+
+use crate::{AgentBuildError, AgentBuilder, AgentError, Message};
+
+/// An [`Agent`](crate::Agent) with its own tokio runtime. Calls block the
+/// current thread until the flow is done.
+///
+/// Like `reqwest::blocking`, it must not be used from within an async
+/// runtime: creating, calling or dropping it there panics.
+///
+/// Dropping it shuts the agent down (see
+/// [`Agent::shutdown`](crate::Agent::shutdown)), stopping MCP servers.
+pub struct Agent {
+    // declared first, so it is dropped while the runtime still runs
+    agent: crate::Agent,
+    runtime: Runtime,
+}
+
+impl Agent {
+    /// Build the agent on a new runtime. MCP clients and notification
+    /// forwarders run on that runtime for the lifetime of the agent.
+    pub fn build(builder: AgentBuilder) -> Result<Self, AgentBuildError> {
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .enable_all()
+            .build()
+            .map_err(AgentBuildError::Runtime)?;
+        let agent = runtime.block_on(builder.build())?;
+        Ok(Self { agent, runtime })
+    }
+
+    /// See [`Agent::invoke_flow`](crate::Agent::invoke_flow).
+    pub fn invoke_flow(&mut self, prompt: impl Into<String>) -> Result<Message, AgentError> {
+        self.runtime.block_on(self.agent.invoke_flow(prompt))
+    }
+
+    /// See [`Agent::invoke_flow_structured_output`](crate::Agent::invoke_flow_structured_output).
+    pub fn invoke_flow_structured_output<T, O>(&mut self, prompt: T) -> Result<O, AgentError>
+    where
+        T: Into<String>,
+        O: DeserializeOwned + Serialize,
+    {
+        self.runtime
+            .block_on(self.agent.invoke_flow_structured_output(prompt))
+    }
+
+    /// The wrapped agent, e.g. to read its history.
+    pub fn inner(&self) -> &crate::Agent {
+        &self.agent
+    }
+
+    pub fn inner_mut(&mut self) -> &mut crate::Agent {
+        &mut self.agent
+    }
+}
+
+impl Drop for Agent {
+    fn drop(&mut self) {
+        self.runtime.block_on(self.agent.shutdown());
+    }
+}
+
+impl std::fmt::Debug for Agent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Agent").field("agent", &self.agent).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::FlowFuture;
+
+    #[test]
+    fn invokes_without_an_async_context() {
+        fn echo_flow<'a>(_agent: &'a mut crate::Agent, prompt: String) -> FlowFuture<'a> {
+            Box::pin(async move { Ok(Message::assistant(format!("ECHO: {prompt}"))) })
+        }
+
+        let mut agent =
+            Agent::build(AgentBuilder::default().set_model("m").set_flow(echo_flow)).unwrap();
+        let reply = agent.invoke_flow("abc").unwrap();
+
+        assert_eq!(reply.content.as_deref(), Some("ECHO: abc"));
+    }
+}
@@ -0,0 +1,58 @@
+use regex::Regex;
+
+/// What a response has to satisfy for an [`EvalCase`] to pass.
+#[derive(Debug, Clone)]
+pub enum Expectation {
+    /// Response (trimmed) must be exactly this string.
+    Exact(String),
+    /// Response must contain this string.
+    Contains(String),
+    /// Response must match this regular expression.
+    Regex(Regex),
+    /// Response is scored by an LLM judge against this rubric.
+    Rubric(String),
+}
+
+/// A single evaluation case: the prompt given to the agent and what the
+/// response is expected to look like.
+#[derive(Debug, Clone)]
+pub struct EvalCase {
+    /// Name used in the report. Defaults to the input.
+    pub name: String,
+    /// Prompt the agent is invoked with.
+    pub input: String,
+    pub expectation: Expectation,
+}
+
+impl EvalCase {
+    pub fn new(input: impl Into<String>, expectation: Expectation) -> Self {
+        let input = input.into();
+        Self {
+            name: input.clone(),
+            input,
+            expectation,
+        }
+    }
+
+    pub fn exact(input: impl Into<String>, expected: impl Into<String>) -> Self {
+        Self::new(input, Expectation::Exact(expected.into()))
+    }
+
+    pub fn contains(input: impl Into<String>, expected: impl Into<String>) -> Self {
+        Self::new(input, Expectation::Contains(expected.into()))
+    }
+
+    pub fn regex(input: impl Into<String>, pattern: &str) -> Result<Self, regex::Error> {
+        Ok(Self::new(input, Expectation::Regex(Regex::new(pattern)?)))
+    }
+
+    pub fn rubric(input: impl Into<String>, rubric: impl Into<String>) -> Self {
+        Self::new(input, Expectation::Rubric(rubric.into()))
+    }
+
+    /// Set the name shown in the report.
+    pub fn set_name(mut self, name: impl Into<String>) -> Self {
+        self.name = name.into();
+        self
+    }
+}
@@ -0,0 +1,225 @@
+use std::{fmt, sync::Arc};
+
+use crate::{Agent, AgentError, StatefullPrebuild};
+
+/// Default prompt the simulated user starts the dialog with.
+const DEFAULT_OPENING: &str = "The assistant is waiting for your first message.";
+
+/// Ends a dialog when it returns true for a reply of the agent under test.
+pub type StopCheck = Arc<dyn Fn(&str) -> bool + Send + Sync>;
+
+/// One exchange of a [`SimulatedDialog`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DialogTurn {
+    /// Message of the simulated user.
+    pub user: String,
+    /// Reply of the agent under test.
+    pub reply: String,
+}
+
+/// Why a [`SimulatedDialog`] ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DialogOutcome {
+    /// The simulated user wrote
+    /// [`StatefullPrebuild::GOAL_REACHED`](crate::StatefullPrebuild::GOAL_REACHED).
+    GoalReached,
+    /// The [`stop_when`](DialogDriver::stop_when) check matched a reply.
+    Stopped,
+    /// The dialog ran for the maximum number of turns.
+    MaxTurns,
+}
+
+/// Conversation between a simulated user and an agent, see [`DialogDriver`].
+#[derive(Debug, Clone)]
+pub struct SimulatedDialog {
+    pub turns: Vec<DialogTurn>,
+    pub outcome: DialogOutcome,
+}
+
+impl SimulatedDialog {
+    pub fn goal_reached(&self) -> bool {
+        self.outcome == DialogOutcome::GoalReached
+    }
+}
+
+impl fmt::Display for SimulatedDialog {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for turn in &self.turns {
+            writeln!(f, "User: {}", turn.user)?;
+            writeln!(f, "Agent: {}", turn.reply)?;
+        }
+        write!(f, "-- {:?} after {} turns", self.outcome, self.turns.len())
+    }
+}
+
+/// Runs multi-turn conversations between a simulated user, usually built
+/// with [`StatefullPrebuild::simulated_user`], and the agent under test.
+///
+/// The simulated user is invoked with the opening prompt, its message is
+/// sent to the agent, and the agent's reply goes back to the simulated user,
+/// until the simulated user reports its goal reached, the
+/// [`stop_when`](Self::stop_when) check matches or `max_turns` exchanges
+/// happened.
+///
+/// ```no_run
+/// use reagent_rs::{evals::DialogDriver, AgentBuilder, StatefullPrebuild};
+///
+/// # async fn run() -> Result<(), Box<dyn std::error::Error>> {
+/// let mut support = AgentBuilder::default().set_model("qwen3:8b").build().await?;
+/// let mut customer = StatefullPrebuild::simulated_user(
+///     "An impatient customer who bought a broken kettle.",
+///     "Get a refund for order 1234.",
+/// )
+/// .set_model("qwen3:8b")
+/// .build()
+/// .await?;
+///
+/// let dialog = DialogDriver::new(8).run(&mut customer, &mut support).await?;
+/// println!("{dialog}");
+/// assert!(dialog.goal_reached());
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct DialogDriver {
+    max_turns: usize,
+    opening: String,
+    stop_when: Option<StopCheck>,
+}
+
+impl DialogDriver {
+    pub fn new(max_turns: usize) -> Self {
+        Self {
+            max_turns,
+            opening: DEFAULT_OPENING.into(),
+            stop_when: None,
+        }
+    }
+
+    /// Prompt the simulated user is invoked with to write its first message.
+    pub fn opening(mut self, opening: impl Into<String>) -> Self {
+        self.opening = opening.into();
+        self
+    }
+
+    /// End the dialog once `check` returns true for a reply of the agent.
+    pub fn stop_when<F>(mut self, check: F) -> Self
+    where
+        F: Fn(&str) -> bool + Send + Sync + 'static,
+    {
+        self.stop_when = Some(Arc::new(check));
+        self
+    }
+
+    /// Let `user` talk to `agent`. Both keep the conversation in their
+    /// history.
+    pub async fn run(
+        &self,
+        user: &mut Agent,
+        agent: &mut Agent,
+    ) -> Result<SimulatedDialog, AgentError> {
+        let mut turns = Vec::new();
+        let mut prompt = self.opening.clone();
+
+        for _ in 0..self.max_turns {
+            let message = user.invoke_flow(prompt).await?.content.unwrap_or_default();
+            if message.contains(StatefullPrebuild::GOAL_REACHED) {
+                return Ok(SimulatedDialog {
+                    turns,
+                    outcome: DialogOutcome::GoalReached,
+                });
+            }
+
+            let reply = agent
+                .invoke_flow(message.clone())
+                .await?
+                .content
+                .unwrap_or_default();
+            let stop = self.stop_when.as_ref().is_some_and(|check| check(&reply));
+            turns.push(DialogTurn {
+                user: message,
+                reply: reply.clone(),
+            });
+            if stop {
+                return Ok(SimulatedDialog {
+                    turns,
+                    outcome: DialogOutcome::Stopped,
+                });
+            }
+            prompt = reply;
+        }
+
+        Ok(SimulatedDialog {
+            turns,
+            outcome: DialogOutcome::MaxTurns,
+        })
+    }
+}
+
+impl fmt::Debug for DialogDriver {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DialogDriver")
+            .field("max_turns", &self.max_turns)
+            .field("opening", &self.opening)
+            .field("stop_when", &self.stop_when.is_some())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{testing::FlowTestHarness, AgentBuilder};
+
+    #[tokio::test]
+    async fn dialogs_end_when_the_goal_is_reached() {
+        let mut user = FlowTestHarness::new(
+            StatefullPrebuild::simulated_user(
+                "A customer with a broken kettle.",
+                "Get a refund for order 1234.",
+            )
+            .set_model("test"),
+        )
+        .await
+        .unwrap()
+        .reply("My kettle broke, I want my money back.")
+        .reply("Order 1234.")
+        .reply(StatefullPrebuild::GOAL_REACHED);
+        let mut agent = FlowTestHarness::new(AgentBuilder::default().set_model("test"))
+            .await
+            .unwrap()
+            .reply("Sorry to hear that, what is the order number?")
+            .reply("Order 1234 is refunded.");
+
+        let dialog = DialogDriver::new(5)
+            .run(user.agent_mut(), agent.agent_mut())
+            .await
+            .unwrap();
+
+        assert!(dialog.goal_reached());
+        assert_eq!(dialog.turns.len(), 2);
+        assert_eq!(dialog.turns[1].user, "Order 1234.");
+        // the simulated user sees the agent's replies as prompts
+        let last = user.requests().pop().unwrap();
+        let prompt = last.messages.last().unwrap();
+        assert_eq!(prompt.content.as_deref(), Some("Order 1234 is refunded."));
+        user.verify();
+        agent.verify();
+
+        let mut agent = FlowTestHarness::new(AgentBuilder::default().set_model("test"))
+            .await
+            .unwrap()
+            .reply("I can't help with that.");
+        let mut user =
+            FlowTestHarness::new(StatefullPrebuild::simulated_user("", "").set_model("test"))
+                .await
+                .unwrap()
+                .reply("Refund please.");
+        let dialog = DialogDriver::new(5)
+            .stop_when(|reply| reply.contains("can't help"))
+            .run(user.agent_mut(), agent.agent_mut())
+            .await
+            .unwrap();
+        assert_eq!(dialog.outcome, DialogOutcome::Stopped);
+    }
+}
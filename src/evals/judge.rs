@@ -0,0 +1,45 @@
+use serde::Deserialize;
+
+use crate::{AgentBuilder, AgentOutput, JsonSchema, StatelessPrebuild};
+
+const JUDGE_SYSTEM_PROMPT: &str = r#"You are a strict and impartial **Evaluation Judge**. You will be given a user's request, the response an AI assistant gave to it and a grading rubric.
+
+Your task is to decide how well the response satisfies the rubric.
+
+**Rules:**
+1.  Judge only against the rubric. Do not reward style, length or effort the rubric does not ask for.
+2.  Give a `score` between 0.0 (does not satisfy the rubric at all) and 1.0 (fully satisfies the rubric).
+3.  Explain your decision in one or two sentences in `reasoning`.
+
+**Output Format:**
+Respond only with a JSON object with the keys "score" and "reasoning". Do not add any other text.
+"#;
+
+/// Verdict an LLM judge returns for a rubric case.
+#[derive(Debug, Clone, Deserialize, JsonSchema, AgentOutput)]
+#[schemars(crate = "rmcp::schemars")]
+#[agent_output(name = "judge_verdict")]
+pub struct JudgeVerdict {
+    /// How well the response satisfies the rubric, from 0.0 to 1.0
+    pub score: f32,
+    /// Short explanation of the score
+    pub reasoning: String,
+}
+
+/// Builder for a judge agent that scores rubric cases.
+///
+/// Configure the model (and provider) as usual and pass the built agent to
+/// [`EvalSuite::set_judge`](super::EvalSuite::set_judge).
+pub fn judge_builder() -> AgentBuilder {
+    StatelessPrebuild::reply_without_tools()
+        .set_system_prompt(JUDGE_SYSTEM_PROMPT)
+        .set_response_format_output::<JudgeVerdict>()
+        .set_temperature(0.0)
+        .set_name("Eval-judge")
+}
+
+pub(super) fn judge_prompt(input: &str, response: &str, rubric: &str) -> String {
+    format!(
+        "# User request:\n\n{input}\n\n# Assistant response:\n\n{response}\n\n# Rubric:\n\n{rubric}\n"
+    )
+}
@@ -0,0 +1,44 @@
+//! Batch evaluation of agents.
+//!
+//! Define [`EvalCase`]s, run them against an [`Agent`](crate::Agent) with an
+//! [`EvalSuite`] and inspect the resulting [`EvalReport`]. Cases are checked
+//! with exact / substring / regex matchers, or scored against a rubric by an
+//! LLM judge (see [`judge_builder`]).
+//!
+//! ```no_run
+//! use reagent_rs::{evals::{self, EvalCase, EvalSuite}, AgentBuilder};
+//!
+//! # async fn run() -> Result<(), Box<dyn std::error::Error>> {
+//! let mut agent = AgentBuilder::default().set_model("qwen3:0.6b").build().await?;
+//! let judge = evals::judge_builder().set_model("qwen3:8b").build().await?;
+//!
+//! let report = EvalSuite::default()
+//!     .add_case(EvalCase::contains("What is the capital of Slovenia?", "Ljubljana"))
+//!     .add_case(EvalCase::regex("What is 6 * 7?", r"\b42\b")?)
+//!     .add_case(EvalCase::rubric(
+//!         "Explain recursion to a child.",
+//!         "Uses simple words and at least one everyday analogy.",
+//!     ))
+//!     .set_judge(judge)
+//!     .run(&agent)
+//!     .await;
+//!
+//! println!("{report}");
+//! assert!(report.all_passed());
+//! # Ok(())
+//! # }
+//! ```
+
+mod case;
+mod dialog;
+mod judge;
+mod report;
+mod suite;
+
+pub use self::{
+    case::{EvalCase, Expectation},
+    dialog::{DialogDriver, DialogOutcome, DialogTurn, SimulatedDialog, StopCheck},
+    judge::{judge_builder, JudgeVerdict},
+    report::{EvalReport, EvalResult},
+    suite::EvalSuite,
+};
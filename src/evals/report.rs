@@ -0,0 +1,88 @@
+use std::{fmt, time::Duration};
+
+/// Outcome of a single [`EvalCase`](super::EvalCase).
+#[derive(Debug, Clone)]
+pub struct EvalResult {
+    pub name: String,
+    pub input: String,
+    /// Agent's response, `None` if the invocation failed.
+    pub response: Option<String>,
+    /// `1.0`/`0.0` for matchers, the judge's score for rubric cases.
+    pub score: f32,
+    pub passed: bool,
+    /// Judge's explanation for rubric cases.
+    pub reasoning: Option<String>,
+    /// Error that occurred while invoking the agent or the judge.
+    pub error: Option<String>,
+    /// Time the agent took to respond.
+    pub duration: Duration,
+}
+
+/// Results of running an [`EvalSuite`](super::EvalSuite), in case order.
+#[derive(Debug, Clone, Default)]
+pub struct EvalReport {
+    pub results: Vec<EvalResult>,
+}
+
+impl EvalReport {
+    pub fn passed(&self) -> usize {
+        self.results.iter().filter(|r| r.passed).count()
+    }
+
+    pub fn failed(&self) -> usize {
+        self.results.len() - self.passed()
+    }
+
+    /// Fraction of passed cases, `0.0` for an empty report.
+    pub fn pass_rate(&self) -> f32 {
+        if self.results.is_empty() {
+            return 0.0;
+        }
+        self.passed() as f32 / self.results.len() as f32
+    }
+
+    /// Mean score over all cases, `0.0` for an empty report.
+    pub fn mean_score(&self) -> f32 {
+        if self.results.is_empty() {
+            return 0.0;
+        }
+        self.results.iter().map(|r| r.score).sum::<f32>() / self.results.len() as f32
+    }
+
+    pub fn all_passed(&self) -> bool {
+        self.results.iter().all(|r| r.passed)
+    }
+
+    pub fn failures(&self) -> impl Iterator<Item = &EvalResult> {
+        self.results.iter().filter(|r| !r.passed)
+    }
+}
+
+impl fmt::Display for EvalReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for result in &self.results {
+            let status = if result.passed { "PASS" } else { "FAIL" };
+            write!(
+                f,
+                "[{status}] {} (score {:.2}, {}ms)",
+                result.name,
+                result.score,
+                result.duration.as_millis()
+            )?;
+            if let Some(error) = &result.error {
+                write!(f, " error: {error}")?;
+            } else if let Some(reasoning) = &result.reasoning {
+                write!(f, " {reasoning}")?;
+            }
+            writeln!(f)?;
+        }
+        write!(
+            f,
+            "{}/{} passed ({:.1}%), mean score {:.2}",
+            self.passed(),
+            self.results.len(),
+            self.pass_rate() * 100.0,
+            self.mean_score()
+        )
+    }
+}
@@ -0,0 +1,245 @@
+use futures::{stream, StreamExt};
+use std::time::Duration;
+
+use crate::{services::runtime::Instant, Agent, AgentError};
+
+use super::{judge::judge_prompt, EvalCase, EvalReport, EvalResult, Expectation, JudgeVerdict};
+
+/// Default minimum judge score for a rubric case to pass.
+const DEFAULT_PASS_THRESHOLD: f32 = 0.7;
+
+/// A batch of [`EvalCase`]s to run against an agent.
+///
+/// Every case runs on a fresh clone of the agent (history cleared), so cases
+/// don't influence each other and the original agent is left untouched.
+#[derive(Debug, Clone)]
+pub struct EvalSuite {
+    cases: Vec<EvalCase>,
+    judge: Option<Agent>,
+    pass_threshold: f32,
+    concurrency: usize,
+}
+
+impl Default for EvalSuite {
+    fn default() -> Self {
+        Self {
+            cases: Vec::new(),
+            judge: None,
+            pass_threshold: DEFAULT_PASS_THRESHOLD,
+            concurrency: 1,
+        }
+    }
+}
+
+impl EvalSuite {
+    pub fn new(cases: Vec<EvalCase>) -> Self {
+        Self {
+            cases,
+            ..Default::default()
+        }
+    }
+
+    pub fn add_case(mut self, case: EvalCase) -> Self {
+        self.cases.push(case);
+        self
+    }
+
+    /// Agent used to score [`Expectation::Rubric`] cases.
+    /// Usually built with [`judge_builder`](super::judge_builder).
+    pub fn set_judge(mut self, judge: Agent) -> Self {
+        self.judge = Some(judge);
+        self
+    }
+
+    /// Minimum judge score (0.0 - 1.0) for a rubric case to pass. Matcher
+    /// cases pass only when they match.
+    pub fn set_pass_threshold(mut self, threshold: f32) -> Self {
+        self.pass_threshold = threshold;
+        self
+    }
+
+    /// How many cases are evaluated at the same time (default 1).
+    pub fn set_concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    /// Run all cases against `agent`.
+    ///
+    /// Failing invocations don't abort the run; they are recorded as failed
+    /// results with the error attached.
+    pub async fn run(&self, agent: &Agent) -> EvalReport {
+        let results = stream::iter(self.cases.iter())
+            .map(|case| self.run_case(agent, case))
+            .buffered(self.concurrency)
+            .collect()
+            .await;
+        EvalReport { results }
+    }
+
+    async fn run_case(&self, agent: &Agent, case: &EvalCase) -> EvalResult {
+        let mut case_agent = agent.clone();
+        case_agent.clear_history();
+
+        let start = Instant::now();
+        let response = case_agent.invoke_flow(case.input.clone()).await;
+        let duration = start.elapsed();
+
+        let response = match response {
+            Ok(message) => answer_text(message.content.as_deref().unwrap_or_default()),
+            Err(e) => return failed_result(case, None, duration, e),
+        };
+
+        // the threshold only grades judge scores, matchers pass or fail
+        let (score, passed, reasoning) = match &case.expectation {
+            Expectation::Rubric(rubric) => match self.judge(case, &response, rubric).await {
+                Ok(verdict) => (
+                    verdict.score,
+                    verdict.score >= self.pass_threshold,
+                    Some(verdict.reasoning),
+                ),
+                Err(e) => return failed_result(case, Some(response), duration, e),
+            },
+            expectation => {
+                let score = match_score(expectation, &response);
+                (score, score == 1.0, None)
+            }
+        };
+
+        EvalResult {
+            name: case.name.clone(),
+            input: case.input.clone(),
+            response: Some(response),
+            score,
+            passed,
+            reasoning,
+            error: None,
+            duration,
+        }
+    }
+
+    async fn judge(
+        &self,
+        case: &EvalCase,
+        response: &str,
+        rubric: &str,
+    ) -> Result<JudgeVerdict, AgentError> {
+        let Some(judge) = &self.judge else {
+            return Err(AgentError::Runtime(
+                "Rubric case requires a judge, set one with `EvalSuite::set_judge`".into(),
+            ));
+        };
+        let mut judge = judge.clone();
+        judge.clear_history();
+        judge
+            .invoke_flow_output::<JudgeVerdict>(judge_prompt(&case.input, response, rubric))
+            .await
+    }
+}
+
+fn failed_result(
+    case: &EvalCase,
+    response: Option<String>,
+    duration: Duration,
+    error: AgentError,
+) -> EvalResult {
+    EvalResult {
+        name: case.name.clone(),
+        input: case.input.clone(),
+        response,
+        score: 0.0,
+        passed: false,
+        reasoning: None,
+        error: Some(error.to_string()),
+        duration,
+    }
+}
+
+/// `1.0` if the response satisfies a matcher expectation, `0.0` otherwise.
+fn match_score(expectation: &Expectation, response: &str) -> f32 {
+    let matched = match expectation {
+        Expectation::Exact(expected) => response == expected.trim(),
+        Expectation::Contains(expected) => response.contains(expected.as_str()),
+        Expectation::Regex(regex) => regex.is_match(response),
+        Expectation::Rubric(_) => false,
+    };
+    if matched {
+        1.0
+    } else {
+        0.0
+    }
+}
+
+/// The part of the response that is evaluated: everything after the
+/// reasoning block, trimmed.
+fn answer_text(content: &str) -> String {
+    match content.rfind("</think>") {
+        Some(pos) => &content[pos + "</think>".len()..],
+        None => content,
+    }
+    .trim()
+    .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matchers_score_answer_after_thinking() {
+        let response = answer_text("<think>6 * 7 = 42</think>\n The answer is 42. ");
+
+        assert_eq!(response, "The answer is 42.");
+        assert_eq!(
+            match_score(&Expectation::Exact(" The answer is 42.".into()), &response),
+            1.0
+        );
+        assert_eq!(
+            match_score(&Expectation::Contains("43".into()), &response),
+            0.0
+        );
+
+        let case = EvalCase::regex("What is 6 * 7?", r"\b42\b").unwrap();
+        assert_eq!(match_score(&case.expectation, &response), 1.0);
+    }
+
+    #[tokio::test]
+    async fn the_pass_threshold_only_applies_to_judged_cases() {
+        let builder = crate::AgentBuilder::default().set_model("test");
+        let harness = crate::testing::FlowTestHarness::new(builder)
+            .await
+            .unwrap()
+            .reply("The answer is 43.");
+        let suite = EvalSuite::new(vec![EvalCase::contains("What is 6 * 7?", "42")])
+            .set_pass_threshold(0.0);
+
+        let report = suite.run(harness.agent()).await;
+        assert_eq!(report.results[0].score, 0.0);
+        assert!(!report.results[0].passed);
+    }
+
+    #[test]
+    fn report_summarizes_results() {
+        let result = |passed: bool| EvalResult {
+            name: "case".into(),
+            input: "input".into(),
+            response: None,
+            score: if passed { 1.0 } else { 0.0 },
+            passed,
+            reasoning: None,
+            error: None,
+            duration: Duration::from_millis(5),
+        };
+        let report = EvalReport {
+            results: vec![result(true), result(false), result(true), result(true)],
+        };
+
+        assert_eq!(report.passed(), 3);
+        assert_eq!(report.failed(), 1);
+        assert_eq!(report.pass_rate(), 0.75);
+        assert!(!report.all_passed());
+        assert!(report
+            .to_string()
+            .ends_with("3/4 passed (75.0%), mean score 0.75"));
+    }
+}
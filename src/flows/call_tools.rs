@@ -4,7 +4,7 @@ use crate::{
 };
 
 pub async fn call_tools_flow(agent: &mut Agent, prompt: String) -> Result<Message, AgentError> {
-    agent.history.push(Message::user(prompt));
+    agent.history.push(Message::user(prompt.clone()));
     let response = InvocationBuilder::default().invoke_with(agent).await?;
     if let Some(tool_calls) = response
         .message
@@ -17,8 +17,7 @@ pub async fn call_tools_flow(agent: &mut Agent, prompt: String) -> Result<Messag
         }
     }
 
-    agent
-        .notify_done(true, response.message.content.clone())
-        .await;
-    Ok(response.message)
+    let message = agent.moderate(&prompt, response.message).await?;
+    agent.notify_done(true, message.content.clone()).await;
+    Ok(message)
 }
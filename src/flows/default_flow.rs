@@ -1,17 +1,27 @@
 use crate::{
-    call_tools, services::llm::message::Message, Agent, AgentError, InvocationBuilder,
-    NotificationHandler, ToolCall,
+    call_tools, services::llm::message::Message, Agent, AgentError, DebugPoint, InvocationBuilder,
+    IterationControl, NotificationHandler, Role, ToolCall, TOOL_NAME_METADATA,
 };
 
 const DEFAULT_MAX_ITERATIONS: usize = 50;
 
+/// Call the model and its tools in a loop until it replies without tool
+/// calls, its reply contains the agent's [`stopword`](Agent::stopword) or
+/// `max_iterations` is reached. After each round of tool results, the
+/// [`stop_prompt`](Agent::stop_prompt) is added as a user message, e.g. to
+/// ask the model to continue or to give its final answer with the stopword.
+/// Then the agent's [`on_iteration`](Agent::on_iteration) callback runs and
+/// can end the flow early, with the last response that has content and
+/// without the stop prompt it would have answered.
 pub async fn default_flow(agent: &mut Agent, prompt: String) -> Result<Message, AgentError> {
-    agent.history.push(Message::user(prompt));
+    agent.history.push(Message::user(prompt.clone()));
     let max_iterations = agent
         .max_iterations
         .unwrap_or(DEFAULT_MAX_ITERATIONS)
         .max(1);
     let mut response = None;
+    let mut last_content = None;
+    let mut stopped = false;
 
     for iteration in 0..max_iterations {
         let allow_tools = iteration + 1 < max_iterations;
@@ -19,21 +29,68 @@ pub async fn default_flow(agent: &mut Agent, prompt: String) -> Result<Message,
             .use_tools(allow_tools)
             .invoke_with(agent)
             .await?;
-        let tool_calls = executable_tool_calls(&current.message, allow_tools);
-        response = Some(current);
+        let tool_calls = executable_tool_calls(&current.message, allow_tools)
+            .filter(|_| !contains_stopword(&current.message, agent.stopword.as_deref()));
+        let current = response.insert(current);
+        if current
+            .message
+            .content
+            .as_deref()
+            .is_some_and(|content| !content.trim().is_empty())
+        {
+            last_content = Some(current.message.clone());
+        }
+        agent
+            .debug_pause(DebugPoint::ModelResponse(iteration))
+            .await;
 
         let Some(tool_calls) = tool_calls else {
             break;
         };
 
         for tool_msg in call_tools(agent, &tool_calls).await {
+            let tool = tool_msg
+                .get_metadata(TOOL_NAME_METADATA)
+                .and_then(|name| name.as_str())
+                .unwrap_or_default()
+                .to_string();
             agent.history.push(tool_msg);
+            agent
+                .debug_pause(DebugPoint::ToolResult(iteration, tool))
+                .await;
+        }
+        agent.apply_tool_state();
+        let stop_prompt = agent.stop_prompt.clone().map(|prompt| {
+            agent.history.push(Message::user(prompt));
+            agent.history.len() - 1
+        });
+
+        if let Some(on_iteration) = agent.on_iteration.clone() {
+            let control = on_iteration.call(agent, iteration, &current.message).await;
+            if control == IterationControl::Stop {
+                // nothing answers the stop prompt any more
+                let unanswered = stop_prompt.filter(|&index| {
+                    agent.history.get(index).is_some_and(|message| {
+                        message.role == Role::User && message.content == agent.stop_prompt
+                    })
+                });
+                if let Some(index) = unanswered {
+                    agent.history.remove(index);
+                }
+                stopped = true;
+                break;
+            }
         }
     }
 
-    let message = response
+    let last = response
         .expect("default flow always performs at least one iteration")
         .message;
+    let message = match last_content {
+        Some(content) if stopped => content,
+        _ => last,
+    };
+    let message = agent.moderate(&prompt, message).await?;
 
     agent.notify_done(true, message.content.clone()).await;
     Ok(message)
@@ -47,10 +104,168 @@ fn executable_tool_calls(message: &Message, allow_tools: bool) -> Option<Vec<Too
         .cloned()
 }
 
+fn contains_stopword(message: &Message, stopword: Option<&str>) -> bool {
+    match (stopword, message.content.as_deref()) {
+        (Some(stopword), Some(content)) => !stopword.is_empty() && content.contains(stopword),
+        _ => false,
+    }
+}
+
 #[cfg(test)]
 mod tests {
+    use serde_json::json;
+
     use super::*;
-    use crate::{ToolCall, ToolCallFunction, ToolType};
+    use crate::{
+        testing::FlowTestHarness, AgentBuilder, Role, ToolCall, ToolCallFunction, ToolType,
+    };
+
+    fn react_agent() -> AgentBuilder {
+        AgentBuilder::default()
+            .set_model("test")
+            .set_stop_prompt("Continue, or answer with FINAL ANSWER: <answer>.")
+            .set_stopword("FINAL ANSWER:")
+    }
+
+    #[tokio::test]
+    async fn stop_prompt_follows_each_round_of_tool_results() {
+        let mut harness = FlowTestHarness::new(react_agent())
+            .await
+            .unwrap()
+            .reply_with_tool_call("search", json!({ "query": "rust release" }))
+            .expect_tool_call("search", "Rust 1.0 was released in 2015.")
+            .reply_with_tool_call("search", json!({ "query": "rust 2021 edition" }))
+            .expect_tool_call("search", "The 2021 edition shipped with Rust 1.56.")
+            .reply("FINAL ANSWER: 2015");
+
+        let reply = harness.run("When was Rust released?").await.unwrap();
+        assert_eq!(reply.content.as_deref(), Some("FINAL ANSWER: 2015"));
+        harness.verify();
+
+        let roles: Vec<_> = harness.history().iter().map(|m| m.role.clone()).collect();
+        assert_eq!(
+            roles,
+            [
+                Role::System,
+                Role::User,
+                Role::Assistant,
+                Role::Tool,
+                Role::User,
+                Role::Assistant,
+                Role::Tool,
+                Role::User,
+                Role::Assistant,
+            ]
+        );
+        assert_eq!(
+            harness.history()[4].content.as_deref(),
+            Some("Continue, or answer with FINAL ANSWER: <answer>.")
+        );
+    }
+
+    #[tokio::test]
+    async fn stopword_ends_the_loop_before_tool_calls() {
+        let mut message = Message::assistant("FINAL ANSWER: 2015");
+        message.tool_calls = Some(vec![ToolCall {
+            id: Some("call_1".into()),
+            tool_type: ToolType::Function,
+            function: ToolCallFunction {
+                name: "search".into(),
+                arguments: json!({ "query": "more" }),
+            },
+        }]);
+        let mut harness = FlowTestHarness::new(react_agent())
+            .await
+            .unwrap()
+            .reply_message(message);
+
+        let reply = harness.run("When was Rust released?").await.unwrap();
+        assert_eq!(reply.content.as_deref(), Some("FINAL ANSWER: 2015"));
+        assert!(harness.tool_calls().is_empty());
+        assert_eq!(harness.requests().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn on_iteration_runs_between_iterations_and_can_stop() {
+        let builder =
+            AgentBuilder::default()
+                .set_model("test")
+                .set_on_iteration(|agent, iteration, last| {
+                    Box::pin(async move {
+                        let calls = last.tool_calls.as_ref().map_or(0, Vec::len);
+                        agent
+                            .state
+                            .insert(format!("iteration_{iteration}"), json!(calls));
+                        if iteration == 1 {
+                            IterationControl::Stop
+                        } else {
+                            IterationControl::Continue
+                        }
+                    })
+                });
+        let mut harness = FlowTestHarness::new(builder)
+            .await
+            .unwrap()
+            .reply_with_tool_call("search", json!({ "query": "rust" }))
+            .expect_tool_call("search", "A language")
+            .reply_with_tool_call("search", json!({ "query": "rust release" }))
+            .expect_tool_call("search", "2015")
+            .reply("never requested");
+
+        harness.run("What is rust?").await.unwrap();
+        assert_eq!(harness.requests().len(), 2);
+        assert_eq!(harness.agent().state["iteration_0"], 1);
+        assert_eq!(harness.agent().state["iteration_1"], 1);
+    }
+
+    #[tokio::test]
+    async fn stopping_returns_the_last_content_without_the_stop_prompt() {
+        let call = |content: &str, query: &str| {
+            let mut message = Message::assistant(content);
+            message.tool_calls = Some(vec![ToolCall {
+                id: Some(format!("call_{query}")),
+                tool_type: ToolType::Function,
+                function: ToolCallFunction {
+                    name: "search".into(),
+                    arguments: json!({ "query": query }),
+                },
+            }]);
+            message
+        };
+        let builder = react_agent().set_on_iteration(|_agent, iteration, _last| {
+            Box::pin(async move {
+                match iteration {
+                    0 => IterationControl::Continue,
+                    _ => IterationControl::Stop,
+                }
+            })
+        });
+        let mut harness = FlowTestHarness::new(builder)
+            .await
+            .unwrap()
+            .reply_message(call("Rust 1.0 came out in 2015.", "rust release"))
+            .expect_tool_call("search", "Rust 1.0 was released in 2015.")
+            .reply_message(call("", "rust 2021 edition"))
+            .expect_tool_call("search", "The 2021 edition shipped with Rust 1.56.");
+
+        let reply = harness.run("When was Rust released?").await.unwrap();
+
+        assert_eq!(reply.content.as_deref(), Some("Rust 1.0 came out in 2015."));
+        assert_eq!(harness.history().last().unwrap().role, Role::Tool);
+        harness.verify();
+    }
+
+    #[tokio::test]
+    async fn reply_without_tool_calls_ends_the_loop_without_stopword() {
+        let mut harness = FlowTestHarness::new(react_agent())
+            .await
+            .unwrap()
+            .reply("Rust was released in 2015.");
+
+        harness.run("When was Rust released?").await.unwrap();
+        assert_eq!(harness.requests().len(), 1);
+        assert_eq!(harness.history().len(), 3);
+    }
 
     #[test]
     fn empty_tool_calls_do_not_request_tools() {
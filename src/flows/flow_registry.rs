@@ -0,0 +1,131 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex, OnceLock},
+};
+
+use crate::{
+    call_tools_flow, flow, prebuilds::plan_and_execute_default_flow, reply_without_tools_flow,
+    Agent, Flow, FlowFn, FlowFuture,
+};
+
+/// Name of the built-in [`Flow::Default`].
+pub const DEFAULT_FLOW: &str = "default";
+
+/// Flows registered by name, with the built-in ones registered up front.
+fn flows() -> &'static Mutex<HashMap<String, FlowFn>> {
+    static FLOWS: OnceLock<Mutex<HashMap<String, FlowFn>>> = OnceLock::new();
+    FLOWS.get_or_init(|| {
+        let builtin: [(&str, FlowFn); 3] = [
+            (
+                "reply_without_tools",
+                Arc::new(flow!(reply_without_tools_flow)),
+            ),
+            ("call_tools", Arc::new(flow!(call_tools_flow))),
+            (
+                "plan_and_execute",
+                Arc::new(flow!(plan_and_execute_default_flow)),
+            ),
+        ];
+        Mutex::new(
+            builtin
+                .into_iter()
+                .map(|(name, flow)| (name.to_string(), flow))
+                .collect(),
+        )
+    })
+}
+
+/// Register a flow under `name` for the whole process, replacing an earlier
+/// one, so agents can select it with
+/// [`AgentBuilder::set_flow_named`](crate::AgentBuilder::set_flow_named) or
+/// by the `flow` of an [`AgentDefinition`](crate::AgentDefinition).
+///
+/// `reply_without_tools`, `call_tools` and `plan_and_execute` are registered
+/// already; `default` always means the built-in default flow.
+///
+/// ```
+/// use reagent_rs::{flow, register_flow, registered_flow, Agent, AgentError, Message};
+///
+/// async fn echo_flow(_agent: &mut Agent, prompt: String) -> Result<Message, AgentError> {
+///     Ok(Message::assistant(prompt))
+/// }
+///
+/// register_flow("echo", flow!(echo_flow));
+/// assert!(registered_flow("echo").is_some());
+/// ```
+pub fn register_flow<F>(name: impl Into<String>, f: F)
+where
+    F: for<'a> Fn(&'a mut Agent, String) -> FlowFuture<'a> + Send + Sync + 'static,
+{
+    flows()
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .insert(name.into(), Arc::new(f));
+}
+
+/// The flow registered under `name`, see [`register_flow`].
+pub fn registered_flow(name: &str) -> Option<Flow> {
+    if name == DEFAULT_FLOW {
+        return Some(Flow::Default);
+    }
+    flows()
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .get(name)
+        .map(|f| Flow::Named(name.to_string(), f.clone()))
+}
+
+/// Names of all registered flows, sorted.
+pub fn registered_flows() -> Vec<String> {
+    let mut names: Vec<String> = flows()
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .keys()
+        .cloned()
+        .collect();
+    names.push(DEFAULT_FLOW.to_string());
+    names.sort();
+    names
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{AgentBuildError, AgentBuilder, AgentError, Message};
+
+    async fn shout_flow(_agent: &mut Agent, prompt: String) -> Result<Message, AgentError> {
+        Ok(Message::assistant(prompt.to_uppercase()))
+    }
+
+    #[tokio::test]
+    async fn agents_select_registered_flows_by_name() {
+        register_flow("shout", flow!(shout_flow));
+        assert!(registered_flows().contains(&"plan_and_execute".to_string()));
+
+        let mut agent = AgentBuilder::default()
+            .set_model("test")
+            .set_flow_named("shout")
+            .build()
+            .await
+            .unwrap();
+        let reply = agent.invoke_flow("hello").await.unwrap();
+        assert_eq!(reply.content.as_deref(), Some("HELLO"));
+
+        let definition = agent.export_definition().await;
+        assert_eq!(definition.flow, "shout");
+        let mut copy = AgentBuilder::from_definition(definition)
+            .build()
+            .await
+            .unwrap();
+        let reply = copy.invoke_flow("again").await.unwrap();
+        assert_eq!(reply.content.as_deref(), Some("AGAIN"));
+
+        let err = AgentBuilder::default()
+            .set_model("test")
+            .set_flow_named("missing")
+            .build()
+            .await
+            .unwrap_err();
+        assert!(matches!(err, AgentBuildError::UnknownFlow(name) if name == "missing"));
+    }
+}
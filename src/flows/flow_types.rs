@@ -6,6 +6,11 @@ pub type FlowFuture<'a> = Pin<Box<dyn Future<Output = Result<Message, AgentError
 
 pub type FlowFn = Arc<dyn for<'a> Fn(&'a mut Agent, String) -> FlowFuture<'a> + Send + Sync>;
 
+pub type IterationFuture<'a> = Pin<Box<dyn Future<Output = IterationControl> + Send + 'a>>;
+
+pub type OnIterationFn =
+    Arc<dyn for<'a> Fn(&'a mut Agent, usize, &'a Message) -> IterationFuture<'a> + Send + Sync>;
+
 /// A user-facing enum defining how an [`Agent`] executes a flow
 /// after receiving a prompt.
 ///
@@ -17,6 +22,7 @@ pub type FlowFn = Arc<dyn for<'a> Fn(&'a mut Agent, String) -> FlowFuture<'a> +
 /// - [`Flow::Default`] — use the built-in default flow.
 /// - [`Flow::Custom`] — supply a function pointer with the correct signature.
 /// - [`Flow::CustomClosure`] — supply a closure wrapped in an `Arc`.
+/// - [`Flow::Named`] — a flow registered by name, see [`register_flow`](crate::register_flow).
 #[derive(Clone)]
 pub enum Flow {
     /// Use the built-in default flow.
@@ -25,6 +31,8 @@ pub enum Flow {
     ///
     /// Function must match `for<'a> fn(&'a mut Agent, String) -> FlowFuture<'a>`.
     Func(FlowFn),
+    /// A flow looked up by name, see [`registered_flow`](crate::registered_flow).
+    Named(String, FlowFn),
 }
 
 impl Flow {
@@ -36,6 +44,59 @@ impl Flow {
     }
 }
 
+/// Whether the default flow continues after an [`OnIteration`] callback.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum IterationControl {
+    /// Call the model again.
+    #[default]
+    Continue,
+    /// End the flow with the last response that has content. The stop
+    /// prompt added for the next iteration is removed again.
+    Stop,
+}
+
+/// Callback of the default flow, run after each round of tool results and
+/// before the model is called again. It gets the agent (to log, add tools
+/// or change the history), the number of the finished iteration starting at
+/// 0 and the model's last response.
+///
+/// ```
+/// use reagent_rs::{AgentBuilder, IterationControl};
+///
+/// let builder = AgentBuilder::default().set_on_iteration(|agent, iteration, _last| {
+///     Box::pin(async move {
+///         if iteration >= 2 && agent.history.len() > 20 {
+///             IterationControl::Stop
+///         } else {
+///             IterationControl::Continue
+///         }
+///     })
+/// });
+/// ```
+#[derive(Clone)]
+pub struct OnIteration(OnIterationFn);
+
+impl OnIteration {
+    pub fn new<F>(f: F) -> Self
+    where
+        F: for<'a> Fn(&'a mut Agent, usize, &'a Message) -> IterationFuture<'a>
+            + Send
+            + Sync
+            + 'static,
+    {
+        OnIteration(Arc::new(f))
+    }
+
+    pub(crate) fn call<'a>(
+        &self,
+        agent: &'a mut Agent,
+        iteration: usize,
+        last_response: &'a Message,
+    ) -> IterationFuture<'a> {
+        (self.0)(agent, iteration, last_response)
+    }
+}
+
 // ------------ custom debugs ------------
 
 impl fmt::Debug for Flow {
@@ -43,10 +104,17 @@ impl fmt::Debug for Flow {
         match self {
             Flow::Default => write!(f, "Simple"),
             Flow::Func(_) => write!(f, "CustomFlow(<fn>)"),
+            Flow::Named(name, _) => write!(f, "NamedFlow({name})"),
         }
     }
 }
 
+impl fmt::Debug for OnIteration {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "OnIteration(<fn>)")
+    }
+}
+
 pub trait FlowCallable: Send + Sync + 'static {
     // family of futures tied to the borrow of &mut Agent
     type Fut<'a>: Future<Output = Result<Message, AgentError>> + Send + 'a
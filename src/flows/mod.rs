@@ -1,11 +1,17 @@
 mod call_tools;
 mod default_flow;
+mod flow_registry;
 mod flow_types;
 mod reply_without_tools;
+mod state_machine;
 
 pub use self::{
-    call_tools::call_tools_flow, default_flow::default_flow, flow_types::*,
+    call_tools::call_tools_flow,
+    default_flow::default_flow,
+    flow_registry::{register_flow, registered_flow, registered_flows, DEFAULT_FLOW},
+    flow_types::*,
     reply_without_tools::reply_without_tools_flow,
+    state_machine::{StateMachine, StateSpec, STATE_MACHINE_PATH},
 };
 
 #[macro_export]
@@ -6,14 +6,13 @@ pub async fn reply_without_tools_flow(
     agent: &mut Agent,
     prompt: String,
 ) -> Result<Message, AgentError> {
-    agent.history.push(Message::user(prompt));
+    agent.history.push(Message::user(prompt.clone()));
     let response = InvocationBuilder::default()
         .use_tools(false)
         .invoke_with(agent)
         .await?;
 
-    agent
-        .notify_done(true, response.message.content.clone())
-        .await;
-    Ok(response.message)
+    let message = agent.moderate(&prompt, response.message).await?;
+    agent.notify_done(true, message.content.clone()).await;
+    Ok(message)
 }
@@ -0,0 +1,328 @@
+use std::{collections::HashMap, fmt, hash::Hash, sync::Arc};
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::{
+    parse_structured_output, services::llm::message::Message, Agent, AgentError, Flow,
+    InvocationBuilder, NotificationHandler,
+};
+
+/// Key of the states visited by the last [`StateMachine`] run in
+/// [`Agent::state`], as a list of state names.
+pub const STATE_MACHINE_PATH: &str = "state_machine_path";
+
+const DEFAULT_MAX_STEPS: usize = 20;
+
+/// Prompt and outgoing transitions of one state of a [`StateMachine`].
+/// A state without transitions is terminal: its reply ends the flow.
+#[derive(Debug, Clone)]
+pub struct StateSpec<S> {
+    prompt: String,
+    transitions: Vec<(S, String)>,
+}
+
+impl<S> StateSpec<S> {
+    /// State whose step is guided by `prompt`.
+    pub fn new(prompt: impl Into<String>) -> Self {
+        Self {
+            prompt: prompt.into(),
+            transitions: Vec::new(),
+        }
+    }
+
+    /// Allow moving on to `to`, described to the model as `when`.
+    pub fn transition(mut self, to: S, when: impl Into<String>) -> Self {
+        self.transitions.push((to, when.into()));
+        self
+    }
+
+    pub fn is_terminal(&self) -> bool {
+        self.transitions.is_empty()
+    }
+}
+
+/// Reply of a non-terminal state.
+#[derive(Deserialize)]
+struct StepOutput {
+    reply: String,
+    next_state: String,
+}
+
+/// A flow as a state machine over typed states, an alternative to writing
+/// the loop of a custom flow by hand.
+///
+/// In each state the model gets the state's prompt and answers with a reply
+/// and the next state, constrained by a response format to the transitions
+/// of the state. A next state that is not one of them fails the flow, so an
+/// agent can only take the paths that were declared. The reply of the first
+/// terminal state reached is the result of the flow. Every transition is
+/// recorded as a step (see [`Agent::record_step`]) and the visited states
+/// are stored under [`STATE_MACHINE_PATH`].
+///
+/// States are usually a unit enum; they are named by their serde
+/// representation, which has to be a string.
+///
+/// ```
+/// use reagent_rs::{AgentBuilder, StateMachine, StateSpec};
+/// use serde::{Deserialize, Serialize};
+///
+/// #[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+/// #[serde(rename_all = "snake_case")]
+/// enum Support {
+///     Triage,
+///     Refund,
+///     Answer,
+/// }
+///
+/// let machine = StateMachine::new(Support::Triage)
+///     .state(
+///         Support::Triage,
+///         StateSpec::new("Decide what the customer needs.")
+///             .transition(Support::Refund, "they want their money back")
+///             .transition(Support::Answer, "they have a question"),
+///     )
+///     .state(Support::Refund, StateSpec::new("Explain the refund process."))
+///     .state(Support::Answer, StateSpec::new("Answer the question."));
+/// assert!(machine.validate().is_ok());
+///
+/// let builder = AgentBuilder::default()
+///     .set_model("qwen3:0.6b")
+///     .set_flow_fn(machine.into_flow());
+/// ```
+#[derive(Debug, Clone)]
+pub struct StateMachine<S> {
+    initial: S,
+    states: HashMap<S, StateSpec<S>>,
+    max_steps: usize,
+}
+
+impl<S> StateMachine<S>
+where
+    S: Clone + Eq + Hash + fmt::Debug + Serialize + Send + Sync + 'static,
+{
+    /// Machine starting in `initial`.
+    pub fn new(initial: S) -> Self {
+        Self {
+            initial,
+            states: HashMap::new(),
+            max_steps: DEFAULT_MAX_STEPS,
+        }
+    }
+
+    /// Define `state`, replacing an earlier definition.
+    pub fn state(mut self, state: S, spec: StateSpec<S>) -> Self {
+        self.states.insert(state, spec);
+        self
+    }
+
+    /// Number of transitions after which the flow fails, 20 by default.
+    pub fn max_steps(mut self, max_steps: usize) -> Self {
+        self.max_steps = max_steps.max(1);
+        self
+    }
+
+    /// Check that every state, and every state a transition leads to, is
+    /// defined and named by a string.
+    pub fn validate(&self) -> Result<(), AgentError> {
+        let targets = self
+            .states
+            .values()
+            .flat_map(|spec| spec.transitions.iter().map(|(to, _)| to));
+        for state in std::iter::once(&self.initial).chain(targets) {
+            state_name(state)?;
+            if !self.states.contains_key(state) {
+                return Err(AgentError::Runtime(format!(
+                    "State {state:?} is not defined"
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Use the machine as the flow of an agent.
+    pub fn into_flow(self) -> Flow {
+        let machine = Arc::new(self);
+        Flow::from_fn(move |agent, prompt| {
+            let machine = machine.clone();
+            Box::pin(async move { machine.run(agent, prompt).await })
+        })
+    }
+
+    /// Run the machine on `prompt`, see [`StateMachine`].
+    pub async fn run(&self, agent: &mut Agent, prompt: String) -> Result<Message, AgentError> {
+        self.validate()?;
+        agent.history.push(Message::user(prompt.clone()));
+
+        let mut current = self.initial.clone();
+        let mut path = vec![state_name(&current)?];
+        agent.state.insert(STATE_MACHINE_PATH.into(), json!(path));
+
+        for _ in 0..self.max_steps {
+            let spec = &self.states[&current];
+            if spec.is_terminal() {
+                agent.history.push(Message::user(spec.prompt.clone()));
+                let response = InvocationBuilder::default()
+                    .use_tools(false)
+                    .invoke_with(agent)
+                    .await?;
+                let message = agent.moderate(&prompt, response.message).await?;
+                agent.notify_done(true, message.content.clone()).await;
+                return Ok(message);
+            }
+
+            let names = spec
+                .transitions
+                .iter()
+                .map(|(to, _)| state_name(to))
+                .collect::<Result<Vec<_>, _>>()?;
+            agent
+                .history
+                .push(Message::user(transition_prompt(spec, &names)));
+            let response = InvocationBuilder::default()
+                .use_tools(false)
+                .set_response_format_value(transition_schema(&names))
+                .set_schema_name("state_transition")
+                .invoke_with(agent)
+                .await?;
+            let output: StepOutput = parse_structured_output(&response.message)?;
+
+            let Some(index) = names.iter().position(|name| *name == output.next_state) else {
+                return Err(AgentError::Runtime(format!(
+                    "Transition from `{}` to `{}` is not allowed, expected one of: {}",
+                    path[path.len() - 1],
+                    output.next_state,
+                    names.join(", ")
+                )));
+            };
+            let from = std::mem::replace(&mut current, spec.transitions[index].0.clone());
+            tracing::debug!(from = ?from, to = ?current, "State machine transition");
+            agent.record_step(
+                format!("{} -> {}", path[path.len() - 1], output.next_state),
+                output.reply,
+            );
+            path.push(output.next_state);
+            agent.state.insert(STATE_MACHINE_PATH.into(), json!(path));
+        }
+
+        Err(AgentError::Runtime(format!(
+            "State machine did not reach a terminal state within {} steps",
+            self.max_steps
+        )))
+    }
+}
+
+fn state_name<S: Serialize + fmt::Debug>(state: &S) -> Result<String, AgentError> {
+    match serde_json::to_value(state) {
+        Ok(Value::String(name)) => Ok(name),
+        _ => Err(AgentError::Runtime(format!(
+            "State {state:?} must serialize to a string"
+        ))),
+    }
+}
+
+fn transition_prompt<S>(spec: &StateSpec<S>, names: &[String]) -> String {
+    let transitions = spec
+        .transitions
+        .iter()
+        .zip(names)
+        .map(|((_, when), name)| format!("- `{name}`: {when}"))
+        .collect::<Vec<_>>()
+        .join("\n");
+    format!(
+        "{}\n\nRespond with a JSON object with your `reply` and the `next_state`, one of:\n{transitions}",
+        spec.prompt
+    )
+}
+
+fn transition_schema(names: &[String]) -> Value {
+    json!({
+        "type": "object",
+        "properties": {
+            "reply": { "type": "string" },
+            "next_state": { "type": "string", "enum": names }
+        },
+        "required": ["reply", "next_state"]
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{testing::FlowTestHarness, AgentBuilder};
+
+    #[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+    #[serde(rename_all = "snake_case")]
+    enum Support {
+        Triage,
+        Refund,
+        Answer,
+    }
+
+    fn machine() -> StateMachine<Support> {
+        StateMachine::new(Support::Triage)
+            .state(
+                Support::Triage,
+                StateSpec::new("Decide what the customer needs.")
+                    .transition(Support::Refund, "they want their money back")
+                    .transition(Support::Answer, "they have a question"),
+            )
+            .state(
+                Support::Refund,
+                StateSpec::new("Explain the refund process."),
+            )
+            .state(Support::Answer, StateSpec::new("Answer the question."))
+    }
+
+    #[tokio::test]
+    async fn transitions_follow_the_structured_replies() {
+        let builder = AgentBuilder::default()
+            .set_model("test")
+            .set_flow_fn(machine().into_flow());
+        let mut harness = FlowTestHarness::new(builder)
+            .await
+            .unwrap()
+            .reply(r#"{"reply": "The customer wants a refund.", "next_state": "refund"}"#)
+            .reply("Send the item back within 30 days.");
+
+        let reply = harness
+            .run("My blender broke, I want my money back")
+            .await
+            .unwrap();
+        assert_eq!(
+            reply.content.as_deref(),
+            Some("Send the item back within 30 days.")
+        );
+        assert_eq!(
+            harness.agent().state[STATE_MACHINE_PATH],
+            json!(["triage", "refund"])
+        );
+        let format = harness.requests()[0].base.format.clone().unwrap();
+        assert!(format.to_string().contains("answer"));
+    }
+
+    #[tokio::test]
+    async fn undeclared_transitions_fail_the_flow() {
+        let builder = AgentBuilder::default()
+            .set_model("test")
+            .set_flow_fn(machine().into_flow());
+        let mut harness = FlowTestHarness::new(builder)
+            .await
+            .unwrap()
+            .reply(r#"{"reply": "Escalating.", "next_state": "manager"}"#);
+
+        let err = harness
+            .run("I want to talk to a manager")
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(err, AgentError::Runtime(msg) if msg.contains("`manager` is not allowed"))
+        );
+
+        let incomplete = StateMachine::new(Support::Triage).state(
+            Support::Triage,
+            StateSpec::new("Triage.").transition(Support::Refund, "refund"),
+        );
+        assert!(incomplete.validate().is_err());
+    }
+}
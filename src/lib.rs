@@ -64,13 +64,23 @@
 
 #![forbid(unsafe_code)]
 
+// lets macro-generated `::reagent_rs::` paths resolve inside this crate too
+extern crate self as reagent_rs;
+
 pub mod agent;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod blocking;
+pub mod evals;
 pub mod flows;
 pub mod notifications;
 pub mod observability;
 pub mod prebuilds;
+#[cfg(all(feature = "pyo3", not(target_arch = "wasm32")))]
+pub mod python;
 pub mod skills;
 pub mod templates;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
 pub mod tools;
 
 mod services;
@@ -84,23 +94,46 @@ pub use crate::templates::*;
 pub use crate::tools::*;
 
 pub use crate::observability::init_default_tracing;
-pub use crate::services::llm::{ClientConfig, Provider};
+pub use crate::services::llm::{
+    repair_tool_call_pairing, ClientConfig, MessageRewriter, Provider, Redactor, RequestLogging,
+    SchemaRegistry, SchemaRegistryError, SchemaSpec, SecretString, ToolResultsAsUser,
+    UNION_CONTENT, UNION_TAG,
+};
 
 pub use crate::services::llm::models::base::Role;
 pub use crate::services::llm::models::chat::{ChatRequest, ChatResponse};
-pub use crate::services::llm::models::message::Message;
+pub use crate::services::llm::models::embedding::{EmbedRequest, EmbedResponse};
+pub use crate::services::llm::models::image::ImageInput;
+pub use crate::services::llm::models::message::{
+    ChatUser, Message, SCRATCH_METADATA, TOOL_NAME_METADATA, USER_ID_METADATA, USER_NAME_METADATA,
+};
+pub use crate::services::llm::providers::openrouter::OpenRouterRoutePrefs;
 
+pub use crate::services::mcp::connection::McpServerStatus;
 pub use crate::services::mcp::error::McpIntegrationError;
+pub use crate::services::mcp::lazy::McpConnect;
 pub use crate::services::mcp::mcp_tool_builder::McpServerType;
+pub use crate::services::mcp::refresh::{ToolsChange, ToolsRefresh};
+pub use crate::services::mcp::stdio_command::{CommandLineSyntax, StdioCommand};
+pub use crate::services::runtime::TaskHandle;
+#[cfg(not(target_arch = "wasm32"))]
+pub use crate::services::runtime::{set_runtime, Runtime, TokioRuntime};
 
 pub mod prelude {
     pub use crate::{
-        flow, Agent, AgentBuildError, AgentBuilder, AgentError, ChatRequest, ChatResponse,
-        ClientConfig, Flow, LoadTemplateError, McpIntegrationError, McpServerType, Message,
-        Notification, NotificationContent, Provider, Role, Skill, SkillLoadError, SkillResource,
-        SkillResourceKind, Template, TemplateDataSource, Tool, ToolBuilder, ToolExecutionError,
+        flow, tool, Agent, AgentBuildError, AgentBuilder, AgentError, AgentOutput, ChatRequest,
+        ChatResponse, ClientConfig, Flow, LoadTemplateError, McpIntegrationError, McpServerType,
+        Message, Notification, NotificationContent, Provider, Role, Skill, SkillLoadError,
+        SkillResource, SkillResourceKind, StdioCommand, Template, TemplateDataSource, Tool,
+        ToolBuilder, ToolExecutionError,
     };
 }
 
+pub use reagent_macros::{tool, AgentOutput};
 pub use rmcp::schemars::JsonSchema;
 pub use serde_json::Value;
+
+#[doc(hidden)]
+pub mod __private {
+    pub use serde_json;
+}
@@ -0,0 +1,129 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{services::llm::InferenceClientError, ToolExecutionError};
+
+/// What kind of failure an [`ErrorDetails`] describes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorKind {
+    /// The request to the provider could not be sent or got no answer.
+    Request,
+    /// The provider answered with an error.
+    Api,
+    /// A request or response could not be (de)serialized.
+    Serialization,
+    /// The client is misconfigured.
+    Config,
+    /// The provider does not support what was asked for.
+    Unsupported,
+    /// The model called a tool with arguments it could not parse.
+    ToolArguments,
+    /// A tool failed while running.
+    ToolExecution,
+    /// The model called a tool the agent does not have.
+    ToolNotFound,
+    /// A tool call was refused because the tool's rate limit or quota was
+    /// reached, see [`ToolLimits`](crate::ToolLimits).
+    ToolQuota,
+}
+
+/// Structured description of a failed model request or tool call, attached
+/// to [`PromptErrorResult`](crate::NotificationContent::PromptErrorResult)
+/// and [`ToolCallErrorResult`](crate::NotificationContent::ToolCallErrorResult)
+/// notifications as [`Notification::error`](crate::Notification::error).
+/// The sub-agent that failed is the last entry of
+/// [`Notification::path`](crate::Notification::path).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ErrorDetails {
+    pub kind: ErrorKind,
+    pub message: String,
+    /// HTTP status code the provider answered with.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<u16>,
+    /// How often the request or tool call was tried, retries included.
+    pub attempts: usize,
+    /// Name of the tool that failed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool: Option<String>,
+}
+
+impl ErrorDetails {
+    pub fn new(kind: ErrorKind, message: impl Into<String>) -> Self {
+        Self {
+            kind,
+            message: message.into(),
+            status: None,
+            attempts: 1,
+            tool: None,
+        }
+    }
+
+    pub fn status(mut self, status: u16) -> Self {
+        self.status = Some(status);
+        self
+    }
+
+    pub fn attempts(mut self, attempts: usize) -> Self {
+        self.attempts = attempts;
+        self
+    }
+
+    pub fn tool(mut self, tool: impl Into<String>) -> Self {
+        self.tool = Some(tool.into());
+        self
+    }
+
+    /// Whether trying again later may succeed: requests that got no answer,
+    /// rate limits (429), server errors (5xx) and failed tool runs.
+    pub fn is_transient(&self) -> bool {
+        match self.kind {
+            ErrorKind::Request | ErrorKind::ToolExecution => true,
+            ErrorKind::Api => matches!(self.status, Some(429 | 500..=599)),
+            _ => false,
+        }
+    }
+
+    pub(crate) fn from_inference(error: &InferenceClientError) -> Self {
+        let kind = match error {
+            InferenceClientError::Request(_) => ErrorKind::Request,
+            InferenceClientError::Api(_) | InferenceClientError::Status(..) => ErrorKind::Api,
+            InferenceClientError::Serialization(_) => ErrorKind::Serialization,
+            InferenceClientError::Config(_) => ErrorKind::Config,
+            InferenceClientError::Unsupported(_) => ErrorKind::Unsupported,
+        };
+        Self {
+            status: error.status(),
+            ..Self::new(kind, error.to_string())
+        }
+    }
+
+    pub(crate) fn from_tool(tool: &str, error: &ToolExecutionError, attempts: usize) -> Self {
+        let kind = match error {
+            ToolExecutionError::ArgumentParsingError(_) => ErrorKind::ToolArguments,
+            ToolExecutionError::ExecutionFailed(_) => ErrorKind::ToolExecution,
+            ToolExecutionError::ToolNotFound(_) => ErrorKind::ToolNotFound,
+        };
+        Self::new(kind, error.to_string())
+            .attempts(attempts)
+            .tool(tool)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn provider_status_decides_whether_errors_are_transient() {
+        let limited = InferenceClientError::Status(429, "Too many requests".into());
+        let details = ErrorDetails::from_inference(&limited);
+        assert_eq!(details.kind, ErrorKind::Api);
+        assert_eq!(details.status, Some(429));
+        assert!(details.is_transient());
+
+        let unauthorized = InferenceClientError::Status(401, "Invalid key".into());
+        assert!(!ErrorDetails::from_inference(&unauthorized).is_transient());
+        let config = InferenceClientError::Config("No model".into());
+        assert!(!ErrorDetails::from_inference(&config).is_transient());
+    }
+}
@@ -4,25 +4,59 @@ use tokio::sync::mpsc::{Receiver, Sender};
 use tokio_stream::wrappers::ReceiverStream;
 
 use crate::{
-    ChatRequest, ChatResponse, Notification, NotificationContent, Response, Success, Token,
-    ToolCall,
+    services::runtime::{self, TaskHandle},
+    Artifact, ChatRequest, ChatResponse, ErrorDetails, InvocationSummary, McpServerStatus,
+    ModerationVerdict, Notification, NotificationContent, PhaseChange, PromptVersionChange,
+    Response, Success, Token, ToolCall, ToolOutputTruncation, ToolsChange,
 };
 
 pub trait NotificationHandler {
     fn get_outgoing_channel(&self) -> &Option<Sender<Notification>>;
     fn get_channel_name(&self) -> &String;
 
+    /// Take ownership of a spawned background task (e.g. a notification
+    /// forwarder), so it can be awaited on shutdown. Detached by default.
+    fn track_background_task(&self, _task: TaskHandle) {}
+
+    /// Id of the registered response schema, attached to every notification.
+    fn get_response_schema(&self) -> Option<&str> {
+        None
+    }
+
+    /// Experiment variant of the running invocation, attached to every
+    /// notification.
+    fn get_variant(&self) -> Option<&str> {
+        None
+    }
+
     /// Send a notification with the given content.
     ///
     /// Returns `true` if successfully delivered, `false` otherwise.
     async fn notify(&self, content: NotificationContent) -> bool {
+        self.notify_with_error(content, None).await
+    }
+
+    /// Send a notification with the given content and the details of the
+    /// failure it reports.
+    ///
+    /// Returns `true` if successfully delivered, `false` otherwise.
+    async fn notify_with_error(
+        &self,
+        content: NotificationContent,
+        error: Option<ErrorDetails>,
+    ) -> bool {
         if self.get_outgoing_channel().is_none() {
             return false;
         }
         let notification_channel = self.get_outgoing_channel().as_ref().unwrap();
 
         match notification_channel
-            .send(Notification::new(self.get_channel_name().clone(), content))
+            .send(
+                Notification::new(self.get_channel_name().clone(), content)
+                    .with_schema(self.get_response_schema().map(str::to_string))
+                    .with_variant(self.get_variant().map(str::to_string))
+                    .with_error(error),
+            )
             .await
         {
             Ok(_) => true,
@@ -38,13 +72,21 @@ pub trait NotificationHandler {
     fn forward_notifications(&self, mut from_channel: Receiver<Notification>) {
         if let Some(notification_channel) = &self.get_outgoing_channel() {
             let to_sender = notification_channel.clone();
-            tokio::spawn(async move {
+            let name = self.get_channel_name().clone();
+            self.track_background_task(runtime::spawn(async move {
                 while let Some(msg) = from_channel.recv().await {
-                    if to_sender.send(msg.unwrap()).await.is_err() {
+                    let mut msg = msg.unwrap();
+                    // the invocation of a sub-agent is part of ours
+                    if matches!(msg.content, NotificationContent::InvocationFinished(_)) {
+                        continue;
+                    }
+                    msg.path.insert(0, name.clone());
+                    if to_sender.send(msg).await.is_err() {
+                        tracing::debug!("Notification receiver dropped, stopping forwarder");
                         break;
                     }
                 }
-            });
+            }));
         }
     }
 
@@ -58,6 +100,7 @@ pub trait NotificationHandler {
             Some(s) => s.clone(),
             None => return,
         };
+        let name = self.get_channel_name().clone();
 
         let mut merged = SelectAll::new();
         for rx in channels {
@@ -65,18 +108,30 @@ pub trait NotificationHandler {
             merged.push(stream);
         }
 
-        tokio::spawn(async move {
-            while let Some(notification) = merged.next().await {
+        self.track_background_task(runtime::spawn(async move {
+            while let Some(mut notification) = merged.next().await {
+                if matches!(
+                    notification.content,
+                    NotificationContent::InvocationFinished(_)
+                ) {
+                    continue;
+                }
+                notification.path.insert(0, name.clone());
                 if to_sender.send(notification).await.is_err() {
+                    tracing::debug!("Notification receiver dropped, stopping forwarder");
                     break;
                 }
             }
-        });
+        }));
     }
 
     async fn notify_done(&self, success: Success, resp: Response) -> bool {
         self.notify(NotificationContent::Done(success, resp)).await
     }
+    async fn notify_finished(&self, summary: InvocationSummary) -> bool {
+        self.notify(NotificationContent::InvocationFinished(summary))
+            .await
+    }
     async fn notify_prompt_request(&self, req: ChatRequest) -> bool {
         self.notify(NotificationContent::PromptRequest(req)).await
     }
@@ -88,10 +143,22 @@ pub trait NotificationHandler {
         self.notify(NotificationContent::PromptErrorResult(error_message))
             .await
     }
+    /// Like [`notify_prompt_error`](Self::notify_prompt_error), with the
+    /// details of the failure attached.
+    async fn notify_prompt_failure(&self, details: ErrorDetails) -> bool {
+        let content = NotificationContent::PromptErrorResult(details.message.clone());
+        self.notify_with_error(content, Some(details)).await
+    }
     async fn notify_tool_request(&self, tool_call: ToolCall) -> bool {
         self.notify(NotificationContent::ToolCallRequest(tool_call))
             .await
     }
+    async fn notify_tool_arguments_repaired(&self, tool_call: ToolCall, original: String) -> bool {
+        self.notify(NotificationContent::ToolCallArgumentsRepaired(
+            tool_call, original,
+        ))
+        .await
+    }
     async fn notify_tool_success(&self, tool_result: String) -> bool {
         self.notify(NotificationContent::ToolCallSuccessResult(tool_result))
             .await
@@ -100,13 +167,43 @@ pub trait NotificationHandler {
         self.notify(NotificationContent::ToolCallErrorResult(error_message))
             .await
     }
+    /// Like [`notify_tool_error`](Self::notify_tool_error), with the
+    /// details of the failure attached.
+    async fn notify_tool_failure(&self, details: ErrorDetails) -> bool {
+        let content = NotificationContent::ToolCallErrorResult(details.message.clone());
+        self.notify_with_error(content, Some(details)).await
+    }
+    async fn notify_tool_output_truncated(&self, truncation: ToolOutputTruncation) -> bool {
+        self.notify(NotificationContent::ToolOutputTruncated(truncation))
+            .await
+    }
     async fn notify_token(&self, token: Token) -> bool {
         self.notify(NotificationContent::Token(token)).await
     }
+    async fn notify_phase_change(&self, phase: PhaseChange) -> bool {
+        self.notify(NotificationContent::PhaseChange(phase)).await
+    }
     async fn notify_mcp_tool_notification(&self, notification: String) -> bool {
         self.notify(NotificationContent::McpToolNotification(notification))
             .await
     }
+    async fn notify_mcp_server_ready(&self, status: McpServerStatus) -> bool {
+        self.notify(NotificationContent::McpServerReady(status))
+            .await
+    }
+    async fn notify_tools_changed(&self, change: ToolsChange) -> bool {
+        self.notify(NotificationContent::ToolsChanged(change)).await
+    }
+    async fn notify_artifact(&self, artifact: Artifact) -> bool {
+        self.notify(NotificationContent::Artifact(artifact)).await
+    }
+    async fn notify_moderated(&self, verdict: ModerationVerdict) -> bool {
+        self.notify(NotificationContent::Moderated(verdict)).await
+    }
+    async fn notify_prompt_version_changed(&self, change: PromptVersionChange) -> bool {
+        self.notify(NotificationContent::PromptVersionChanged(change))
+            .await
+    }
     async fn notify_custom(&self, custom_val: Value) -> bool {
         self.notify(NotificationContent::Custom(custom_val)).await
     }
@@ -1,6 +1,13 @@
+mod error_details;
 mod handler;
 mod inference_channel;
 mod notification;
 mod notiifcation_content;
+mod ordering;
+mod token_batching;
 
-pub use self::{handler::*, inference_channel::*, notification::*, notiifcation_content::*};
+pub(crate) use self::token_batching::TokenBatcher;
+pub use self::{
+    error_details::*, handler::*, inference_channel::*, notification::*, notiifcation_content::*,
+    ordering::ordered_notifications, token_batching::TokenBatching,
+};
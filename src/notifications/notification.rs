@@ -1,33 +1,82 @@
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::sync::atomic::{AtomicU64, Ordering};
 
 use serde::{Deserialize, Serialize};
 
 use crate::{
     notifications::notiifcation_content::{McpEnvelope, McpRaw},
-    NotificationContent,
+    services::runtime::SystemTime,
+    ErrorDetails, NotificationContent,
 };
 
+/// Source of [`Notification::sequence`], shared by all agents in the process.
+static NEXT_SEQUENCE: AtomicU64 = AtomicU64::new(0);
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Notification {
     pub agent: String,
     pub content: NotificationContent,
     pub mcp_envelope: Option<McpEnvelope>,
     pub timestamp_millis: u128,
+    /// Id (`name@vN`) of the registered response schema the agent uses, see
+    /// [`SchemaRegistry`](crate::SchemaRegistry).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub schema: Option<String>,
+    /// Increases with every notification created in the process, so it
+    /// orders notifications of different agents by when they happened, see
+    /// [`ordered_notifications`](crate::ordered_notifications).
+    #[serde(default)]
+    pub sequence: u64,
+    /// Names of the agents the notification passed through, from the
+    /// top-level agent down to the one that sent it (`agent`).
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub path: Vec<String>,
+    /// Kind, provider status code and attempt count of a failed model
+    /// request or tool call, set on error notifications.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<ErrorDetails>,
+    /// Id of the variant of the agent's
+    /// [`PromptExperiment`](crate::PromptExperiment) the invocation was
+    /// assigned.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub variant: Option<String>,
 }
 
 impl Notification {
     pub fn new(agent: String, content: NotificationContent) -> Self {
         Self {
+            path: vec![agent.clone()],
             agent,
             content,
             mcp_envelope: None,
             timestamp_millis: SystemTime::now()
-                .duration_since(UNIX_EPOCH)
+                .duration_since(SystemTime::UNIX_EPOCH)
                 .expect("Time should go forward")
                 .as_millis(),
+            schema: None,
+            sequence: NEXT_SEQUENCE.fetch_add(1, Ordering::Relaxed),
+            error: None,
+            variant: None,
         }
     }
 
+    /// Tag the notification with a response schema id.
+    pub fn with_schema(mut self, schema: Option<String>) -> Self {
+        self.schema = schema;
+        self
+    }
+
+    /// Tag the notification with an experiment variant.
+    pub fn with_variant(mut self, variant: Option<String>) -> Self {
+        self.variant = variant;
+        self
+    }
+
+    /// Attach the details of the failure the notification reports.
+    pub fn with_error(mut self, error: Option<ErrorDetails>) -> Self {
+        self.error = error;
+        self
+    }
+
     pub fn unwrap(self) -> Self {
         if let NotificationContent::McpToolNotification(ref mcp_string) = self.content {
             if let Ok(raw) = serde_json::from_str::<McpRaw>(mcp_string) {
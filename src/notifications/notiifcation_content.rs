@@ -3,20 +3,50 @@ use serde_json::Value;
 
 use crate::{
     services::llm::models::chat::{ChatRequest, ChatResponse},
-    ToolCall,
+    Artifact, McpServerStatus, Message, ModerationVerdict, PromptVersionChange, TokenUsage,
+    ToolCall, ToolOutputTruncation, ToolsChange,
 };
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum NotificationContent {
+    /// Sent by flows when they finish.
+    /// [`InvocationFinished`](Self::InvocationFinished) follows with the
+    /// details.
     Done(Success, Response),
+    /// An invocation finished, with its final message, usage and duration,
+    /// see [`InvocationSummary`].
+    InvocationFinished(InvocationSummary),
     PromptRequest(ChatRequest),
     PromptSuccessResult(ChatResponse),
     PromptErrorResult(String),
     ToolCallRequest(ToolCall),
+    /// The arguments of a tool call were not valid JSON and were repaired,
+    /// see [`parse_lenient_json`](crate::parse_lenient_json). Holds the
+    /// repaired call and the original arguments.
+    ToolCallArgumentsRepaired(ToolCall, String),
     ToolCallSuccessResult(String),
     ToolCallErrorResult(String),
+    /// A tool output over its limit was shortened before it was added to
+    /// the history, see [`ToolOutputLimit`](crate::ToolOutputLimit).
+    ToolOutputTruncated(ToolOutputTruncation),
     Token(Token),
+    /// A flow moved to another phase, see [`PhaseChange`].
+    PhaseChange(PhaseChange),
     McpToolNotification(String),
+    /// An MCP server connected in the background listed its tools, which
+    /// the next invocation uses, see [`McpConnect`](crate::McpConnect).
+    McpServerReady(McpServerStatus),
+    /// Tools of an MCP server were added, removed or changed, see
+    /// [`Agent::refresh_mcp_tools`](crate::Agent::refresh_mcp_tools).
+    ToolsChanged(ToolsChange),
+    /// A file emitted by a tool, see [`emit_artifact`](crate::emit_artifact).
+    Artifact(Artifact),
+    /// A final reply was annotated, rewritten or blocked, see
+    /// [`Agent::moderate`](crate::Agent::moderate).
+    Moderated(ModerationVerdict),
+    /// The agent switched to another version of its library prompt, see
+    /// [`PromptLibrary`](crate::PromptLibrary).
+    PromptVersionChanged(PromptVersionChange),
     Custom(Value),
 }
 
@@ -28,6 +58,49 @@ pub struct Token {
     pub value: String,
 }
 
+/// Outcome of an invocation, sent as
+/// [`NotificationContent::InvocationFinished`] once it returned.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InvocationSummary {
+    pub success: bool,
+    /// The final message, `None` when the invocation failed.
+    pub message: Option<Message>,
+    /// Tokens used during the invocation, by the sub-agents and clones it
+    /// ran as well.
+    pub usage: TokenUsage,
+    pub duration_ms: u64,
+    /// Tool calls executed during the invocation, by its sub-agents and
+    /// clones as well.
+    pub tool_calls: usize,
+    /// Why the invocation failed.
+    pub error: Option<String>,
+}
+
+/// A flow moved to another phase, e.g. from planning to executing a step,
+/// so UIs can show progress. Sent by the prebuilt flows, and by custom flows
+/// with [`NotificationHandler::notify_phase_change`](crate::NotificationHandler::notify_phase_change).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PhaseChange {
+    /// Name of the phase, e.g. `plan` or `execute`.
+    pub name: String,
+    /// What the phase works on, e.g. the plan step being executed.
+    pub detail: Option<String>,
+}
+
+impl PhaseChange {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            detail: None,
+        }
+    }
+
+    pub fn detail(mut self, detail: impl Into<String>) -> Self {
+        self.detail = Some(detail.into());
+        self
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct McpEnvelope {
@@ -0,0 +1,131 @@
+use std::{cmp::Ordering, collections::BinaryHeap, time::Duration};
+
+use futures::{stream::SelectAll, Stream, StreamExt};
+use tokio::sync::mpsc::Receiver;
+use tokio_stream::wrappers::ReceiverStream;
+
+use crate::{
+    services::runtime::{self, Instant},
+    Notification,
+};
+
+/// A received notification waiting for earlier ones.
+struct Pending {
+    received: Instant,
+    notification: Notification,
+}
+
+impl PartialEq for Pending {
+    fn eq(&self, other: &Self) -> bool {
+        self.notification.sequence == other.notification.sequence
+    }
+}
+
+impl Eq for Pending {}
+
+impl PartialOrd for Pending {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Pending {
+    // reversed, so the heap pops the lowest sequence number first
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.notification.sequence.cmp(&self.notification.sequence)
+    }
+}
+
+/// Merge notification channels into one stream ordered by
+/// [`Notification::sequence`].
+///
+/// Notifications forwarded from sub-agents can arrive after later
+/// notifications of their parent. Each notification is held for up to
+/// `window` after it arrived, so notifications that happened earlier but
+/// arrive within the window are emitted first. Notifications that arrive
+/// later than that are emitted as they come. Everything buffered is emitted
+/// when all channels are closed.
+///
+/// ```no_run
+/// # async fn run(receiver: tokio::sync::mpsc::Receiver<reagent_rs::Notification>) {
+/// use std::time::Duration;
+///
+/// use futures::StreamExt;
+/// use reagent_rs::ordered_notifications;
+///
+/// let stream = ordered_notifications([receiver], Duration::from_millis(50));
+/// futures::pin_mut!(stream);
+/// while let Some(notification) = stream.next().await {
+///     println!("#{} {}: {:?}", notification.sequence, notification.path.join("/"), notification.content);
+/// }
+/// # }
+/// ```
+pub fn ordered_notifications<I>(receivers: I, window: Duration) -> impl Stream<Item = Notification>
+where
+    I: IntoIterator<Item = Receiver<Notification>>,
+{
+    let mut merged: SelectAll<_> = receivers.into_iter().map(ReceiverStream::new).collect();
+
+    async_stream::stream! {
+        let mut pending = BinaryHeap::<Pending>::new();
+        let mut open = true;
+
+        while open || !pending.is_empty() {
+            let deadline = pending.peek().map(|p| p.received + window);
+            let received = match deadline {
+                _ if !open => None,
+                Some(deadline) => tokio::select! {
+                    next = merged.next() => Some(next),
+                    _ = runtime::sleep_until(deadline) => None,
+                },
+                None => Some(merged.next().await),
+            };
+
+            match received {
+                Some(Some(notification)) => pending.push(Pending {
+                    received: Instant::now(),
+                    notification,
+                }),
+                Some(None) => open = false,
+                None => {}
+            }
+
+            // emit in sequence order, as far as the oldest buffered
+            // notification allows
+            let now = Instant::now();
+            while let Some(next) = pending.peek() {
+                if open && next.received + window > now {
+                    break;
+                }
+                if let Some(next) = pending.pop() {
+                    yield next.notification;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::NotificationContent;
+
+    #[tokio::test]
+    async fn late_notifications_are_put_back_in_order() {
+        let (parent, parent_rx) = tokio::sync::mpsc::channel(8);
+        let (child, child_rx) = tokio::sync::mpsc::channel(8);
+
+        let early = Notification::new("child".into(), NotificationContent::Done(true, None));
+        let late = Notification::new("parent".into(), NotificationContent::Done(true, None));
+        parent.send(late).await.unwrap();
+        child.send(early).await.unwrap();
+        drop((parent, child));
+
+        let agents: Vec<String> =
+            ordered_notifications([parent_rx, child_rx], Duration::from_millis(50))
+                .map(|n| n.agent)
+                .collect()
+                .await;
+        assert_eq!(agents, ["child", "parent"]);
+    }
+}
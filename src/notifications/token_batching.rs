@@ -0,0 +1,105 @@
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::services::runtime::Instant;
+
+/// Batching policy for [`Token`](crate::Token) notifications.
+///
+/// Instead of sending every streamed token as its own notification, tokens
+/// are buffered and sent as one notification once `max_tokens` tokens were
+/// collected or `max_delay` passed since the first buffered token, whichever
+/// happens first. Buffered tokens are always flushed before the invocation
+/// finishes, so they arrive before any `Done` notification.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct TokenBatching {
+    pub max_tokens: usize,
+    pub max_delay: Duration,
+}
+
+impl TokenBatching {
+    pub fn new(max_tokens: usize, max_delay: Duration) -> Self {
+        Self {
+            max_tokens: max_tokens.max(1),
+            max_delay,
+        }
+    }
+}
+
+/// Buffers streamed tokens according to a [`TokenBatching`] policy.
+#[derive(Debug)]
+pub(crate) struct TokenBatcher {
+    policy: TokenBatching,
+    buffer: String,
+    tokens: usize,
+    first_token_at: Option<Instant>,
+}
+
+impl TokenBatcher {
+    pub(crate) fn new(policy: TokenBatching) -> Self {
+        Self {
+            policy,
+            buffer: String::new(),
+            tokens: 0,
+            first_token_at: None,
+        }
+    }
+
+    /// Buffer a token. Returns the batch if it should be sent now.
+    pub(crate) fn push(&mut self, token: &str) -> Option<String> {
+        self.buffer.push_str(token);
+        self.tokens += 1;
+        let first_token_at = *self.first_token_at.get_or_insert_with(Instant::now);
+
+        if self.tokens >= self.policy.max_tokens
+            || first_token_at.elapsed() >= self.policy.max_delay
+        {
+            self.flush()
+        } else {
+            None
+        }
+    }
+
+    /// Take everything buffered so far.
+    pub(crate) fn flush(&mut self) -> Option<String> {
+        self.tokens = 0;
+        self.first_token_at = None;
+        if self.buffer.is_empty() {
+            None
+        } else {
+            Some(std::mem::take(&mut self.buffer))
+        }
+    }
+
+    /// When the current batch has to be sent at the latest.
+    pub(crate) fn deadline(&self) -> Option<Instant> {
+        self.first_token_at
+            .map(|first_token_at| first_token_at + self.policy.max_delay)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn flushes_every_n_tokens() {
+        let mut batcher = TokenBatcher::new(TokenBatching::new(3, Duration::from_secs(60)));
+
+        assert_eq!(batcher.push("a"), None);
+        assert_eq!(batcher.push("b"), None);
+        assert!(batcher.deadline().is_some());
+        assert_eq!(batcher.push("c").as_deref(), Some("abc"));
+        assert!(batcher.deadline().is_none());
+
+        assert_eq!(batcher.push("d"), None);
+        assert_eq!(batcher.flush().as_deref(), Some("d"));
+        assert_eq!(batcher.flush(), None);
+    }
+
+    #[test]
+    fn flushes_after_delay() {
+        let mut batcher = TokenBatcher::new(TokenBatching::new(100, Duration::ZERO));
+        assert_eq!(batcher.push("a").as_deref(), Some("a"));
+    }
+}
@@ -1,3 +1,5 @@
+// the exporter runs on tokio's runtime
+#[cfg(not(target_arch = "wasm32"))]
 pub mod langfuse;
 mod logging;
 
@@ -1,5 +1,12 @@
 mod statefull;
 mod stateless;
 
+pub use statefull::best_of_n::CandidateScorer;
+pub(crate) use statefull::plan_and_execute::plan_and_execute_default_flow;
+pub use statefull::plan_and_execute::{StepCache, StepCacheKey};
+pub use statefull::speculative::DraftVerifier;
 pub use statefull::StatefullPrebuild;
+pub use stateless::conversation_summary::{ConversationSummary, SUMMARY_STATE, TITLE_STATE};
+pub use stateless::fact_extraction::{Entity, Facts, Relationship, FACTS_STATE};
+pub use stateless::vision_describe::ImageDescription;
 pub use stateless::StatelessPrebuild;
@@ -0,0 +1,300 @@
+use futures::future::join_all;
+use serde_json::{json, Value};
+use std::sync::Arc;
+use tokio::sync::mpsc::Receiver;
+use tracing::instrument;
+
+use crate::{
+    prebuilds::{statefull::plan_and_execute::extract_configurations, StatefullPrebuild},
+    services::llm::message::Message,
+    Agent, AgentBuildError, AgentBuilder, AgentError, FlowFuture, InvocationBuilder, Notification,
+    NotificationHandler, PhaseChange, StatelessPrebuild,
+};
+
+/// Scores a candidate response. Higher is better.
+pub type CandidateScorer = Arc<dyn Fn(&str) -> f32 + Send + Sync>;
+
+/// Temperature used for the first candidate if the agent has none set.
+const DEFAULT_BASE_TEMPERATURE: f32 = 0.7;
+/// How much the temperature increases for every following candidate.
+const TEMPERATURE_STEP: f32 = 0.1;
+/// Upper bound for the candidate temperature.
+const MAX_TEMPERATURE: f32 = 1.5;
+
+const JUDGE_SYSTEM_PROMPT: &str = r#"You are an impartial **Judge Agent**. You will be given a user's request and several numbered candidate responses to it.
+
+Your task is to score every candidate on a scale from 0 to 10, where 10 is a perfect response.
+
+**Scoring criteria:**
+1.  **Correctness:** Is the information accurate and free of errors?
+2.  **Completeness:** Does it fully address every part of the request?
+3.  **Clarity:** Is it well-structured and easy to follow?
+
+**Output Format:**
+Respond only with a JSON object with a single key, "scores", whose value is an array of numbers. The array must contain exactly one score per candidate, in the same order as the candidates were given. Do not add any explanations.
+
+**Example (for three candidates):**
+{
+    "scores": [7, 9.5, 3]
+}
+"#;
+
+const JUDGE_RESPONSE_FORMAT: &str = r#"
+{
+    "type": "object",
+    "properties": {
+        "scores": {
+            "type": "array",
+            "items": { "type": "number" }
+        }
+    },
+    "required": ["scores"]
+}
+"#;
+
+impl StatefullPrebuild {
+    /// Samples `n` candidate responses in parallel (each with a different
+    /// temperature and seed), lets a judge sub-agent score them and replies
+    /// with the best one.
+    ///
+    /// Every candidate and the final scores are emitted as
+    /// [`NotificationContent::Custom`](crate::NotificationContent::Custom) notifications.
+    /// The `sample` and `score` phases are announced with
+    /// [`NotificationContent::PhaseChange`](crate::NotificationContent::PhaseChange).
+    pub fn best_of_n(n: usize) -> AgentBuilder {
+        best_of_n_builder(n, None)
+    }
+
+    /// Same as [`StatefullPrebuild::best_of_n`], but candidates are scored
+    /// with the given function instead of a judge sub-agent.
+    pub fn best_of_n_with_scorer<F>(n: usize, scorer: F) -> AgentBuilder
+    where
+        F: Fn(&str) -> f32 + Send + Sync + 'static,
+    {
+        best_of_n_builder(n, Some(Arc::new(scorer)))
+    }
+}
+
+fn best_of_n_builder(n: usize, scorer: Option<CandidateScorer>) -> AgentBuilder {
+    let n = n.max(1);
+    StatefullPrebuild::reply_without_tools()
+        .set_flow(move |agent: &mut Agent, prompt: String| -> FlowFuture<'_> {
+            Box::pin(best_of_n_flow(agent, prompt, n, scorer.clone()))
+        })
+        .set_name("Statefull_prebuild-best_of_n")
+}
+
+#[instrument(level = "debug", skip(agent, prompt, scorer))]
+async fn best_of_n_flow(
+    agent: &mut Agent,
+    prompt: String,
+    n: usize,
+    scorer: Option<CandidateScorer>,
+) -> Result<Message, AgentError> {
+    agent.history.push(Message::user(prompt.clone()));
+    agent
+        .notify_phase_change(PhaseChange::new("sample").detail(format!("{n} candidates")))
+        .await;
+
+    // every candidate is generated by its own clone of the agent, so the
+    // invocations can run concurrently. Clones share the notification channel.
+    let mut candidate_agents: Vec<Agent> = (0..n)
+        .map(|i| {
+            let mut candidate = agent.clone();
+            let (temperature, seed) = candidate_sampling(agent.temperature, agent.seed, i);
+            candidate.temperature = Some(temperature);
+            candidate.seed = Some(seed);
+            candidate.name = format!("{}-candidate_{i}", agent.name);
+            candidate
+        })
+        .collect();
+
+    let responses = join_all(candidate_agents.iter_mut().map(|candidate| {
+        InvocationBuilder::default()
+            .use_tools(false)
+            .invoke_with(candidate)
+    }))
+    .await;
+
+    // keep the successful candidates, a single failed sample should not
+    // fail the whole invocation
+    let mut candidates: Vec<(usize, Message)> = Vec::new();
+    let mut last_error = None;
+    for ((index, candidate_agent), response) in candidate_agents.iter().enumerate().zip(responses) {
+        match response {
+            Ok(response) => {
+                agent
+                    .notify_custom(json!({
+                        "best_of_n_candidate": {
+                            "index": index,
+                            "temperature": candidate_agent.temperature,
+                            "seed": candidate_agent.seed,
+                            "content": response.message.content,
+                        }
+                    }))
+                    .await;
+                candidates.push((index, response.message));
+            }
+            Err(e) => {
+                tracing::warn!(error = %e, candidate = index, "Best-of-n candidate failed");
+                last_error = Some(e);
+            }
+        }
+    }
+
+    if candidates.is_empty() {
+        return Err(match last_error {
+            Some(e) => e.into(),
+            None => AgentError::Runtime("No candidates were generated".into()),
+        });
+    }
+
+    let contents: Vec<String> = candidates
+        .iter()
+        .map(|(_, c)| c.content.clone().unwrap_or_default())
+        .collect();
+
+    agent.notify_phase_change(PhaseChange::new("score")).await;
+    let scores = match scorer {
+        Some(scorer) => contents.iter().map(|c| scorer(c)).collect(),
+        None => {
+            let (mut judge_agent, judge_notification_channel) = create_judge_agent(agent).await?;
+            agent.forward_notifications(judge_notification_channel);
+            let judgement = judge_agent
+                .invoke_flow(judge_prompt(&prompt, &contents))
+                .await?;
+            get_scores_from_response(&judgement, contents.len())?
+        }
+    };
+
+    // scores are reported together with the index of the candidate they belong to
+    let scored: Vec<Value> = candidates
+        .iter()
+        .zip(&scores)
+        .map(|((index, _), score)| json!({ "index": index, "score": score }))
+        .collect();
+    let (selected, response) = candidates.swap_remove(best_candidate(&scores));
+    agent
+        .notify_custom(json!({
+            "best_of_n_result": {
+                "scores": scored,
+                "selected": selected,
+            }
+        }))
+        .await;
+
+    agent.history.push(response.clone());
+    let response = agent.moderate(&prompt, response).await?;
+    agent.notify_done(true, response.content.clone()).await;
+    Ok(response)
+}
+
+/// Temperature and seed for the `index`-th candidate.
+fn candidate_sampling(
+    base_temperature: Option<f32>,
+    base_seed: Option<i32>,
+    index: usize,
+) -> (f32, i32) {
+    let temperature =
+        base_temperature.unwrap_or(DEFAULT_BASE_TEMPERATURE) + TEMPERATURE_STEP * index as f32;
+    let seed = base_seed.unwrap_or(0).wrapping_add(index as i32);
+    (temperature.min(MAX_TEMPERATURE), seed)
+}
+
+/// Index of the highest score. Ties go to the earlier candidate.
+fn best_candidate(scores: &[f32]) -> usize {
+    scores
+        .iter()
+        .enumerate()
+        .fold(None, |best: Option<(usize, f32)>, (i, &score)| match best {
+            Some((_, best_score)) if best_score >= score || score.is_nan() => best,
+            _ => Some((i, score)),
+        })
+        .map(|(i, _)| i)
+        .unwrap_or(0)
+}
+
+fn judge_prompt(prompt: &str, candidates: &[String]) -> String {
+    let mut judge_prompt = format!("# User request:\n\n{prompt}\n\n");
+    for (i, candidate) in candidates.iter().enumerate() {
+        judge_prompt.push_str(&format!("# Candidate {i}:\n\n{candidate}\n\n"));
+    }
+    judge_prompt
+}
+
+fn get_scores_from_response(
+    judgement: &Message,
+    candidates: usize,
+) -> Result<Vec<f32>, AgentError> {
+    let content = judgement.content.clone().unwrap_or_default();
+
+    let judgement: Value = serde_json::from_str(&content)
+        .map_err(|e| AgentError::Runtime(format!("Judge failed to return valid JSON: {e}")))?;
+
+    let scores = judgement.get("scores").ok_or_else(|| {
+        AgentError::Runtime("JSON object is missing the required 'scores' key.".to_string())
+    })?;
+
+    let scores: Vec<f32> = serde_json::from_value(scores.clone()).map_err(|e| {
+        AgentError::Runtime(format!(
+            "The 'scores' key is not a valid array of numbers: {e}"
+        ))
+    })?;
+
+    if scores.len() != candidates {
+        return Err(AgentError::Runtime(format!(
+            "Judge returned {} scores for {candidates} candidates",
+            scores.len()
+        )));
+    }
+
+    Ok(scores)
+}
+
+async fn create_judge_agent(
+    ref_agent: &Agent,
+) -> Result<(Agent, Receiver<Notification>), AgentBuildError> {
+    let (client_config, model_config, prompt_config) = extract_configurations(ref_agent).await;
+
+    StatelessPrebuild::reply_without_tools()
+        // we transfer the settings set to the top-level agent
+        .import_client_config(client_config)
+        .import_model_config(model_config)
+        .import_prompt_config(prompt_config)
+        .set_name("Statefull_prebuild-best_of_n-judge")
+        .set_system_prompt(JUDGE_SYSTEM_PROMPT)
+        // scoring should be as deterministic as possible
+        .set_temperature(0.0)
+        .set_response_format_str(JUDGE_RESPONSE_FORMAT)
+        .build_with_notification()
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn candidates_get_increasing_temperature_and_distinct_seeds() {
+        assert_eq!(candidate_sampling(None, None, 0), (0.7, 0));
+        assert_eq!(candidate_sampling(Some(0.2), Some(41), 1), (0.3, 42));
+        assert_eq!(candidate_sampling(Some(1.4), None, 5).0, MAX_TEMPERATURE);
+    }
+
+    #[test]
+    fn best_candidate_prefers_earlier_on_ties() {
+        assert_eq!(best_candidate(&[1.0, 3.0, 3.0]), 1);
+        assert_eq!(best_candidate(&[f32::NAN, 2.0]), 1);
+        assert_eq!(best_candidate(&[]), 0);
+    }
+
+    #[test]
+    fn judge_scores_must_match_candidates() {
+        let judgement = Message::assistant(r#"{"scores":[7, 9.5]}"#);
+        assert_eq!(
+            get_scores_from_response(&judgement, 2).unwrap(),
+            vec![7.0, 9.5]
+        );
+        assert!(get_scores_from_response(&judgement, 3).is_err());
+    }
+}
@@ -1,5 +1,8 @@
+pub mod best_of_n;
 pub mod call_tools;
 pub mod plan_and_execute;
 pub mod reply_without_tools;
+pub mod simulated_user;
+pub mod speculative;
 
 pub struct StatefullPrebuild;
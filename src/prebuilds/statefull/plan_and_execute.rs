@@ -1,73 +1,73 @@
+use futures::future::{join_all, BoxFuture};
+use serde::{Deserialize, Serialize};
 use serde_json::Value;
-use std::collections::HashMap;
+use std::{collections::HashMap, fmt, future::Future, sync::Arc, time::Duration};
 use tokio::sync::mpsc::Receiver;
 use tracing::instrument;
 
 use crate::{
-    flow,
     prebuilds::{StatefullPrebuild, StatelessPrebuild},
-    services::llm::{message::Message, ClientConfig},
-    templates::Template,
-    Agent, AgentBuildError, AgentBuilder, AgentError, InvocationBuilder, ModelConfig, Notification,
-    NotificationHandler, PromptConfig,
+    services::{
+        llm::{message::Message, ClientConfig},
+        runtime::Instant,
+    },
+    templates::{SystemPromptBuilder, Template},
+    Agent, AgentBuildError, AgentBuilder, AgentError, FlowFuture, InvocationBuilder, ModelConfig,
+    Notification, NotificationHandler, PhaseChange, PromptConfig, DEFAULT_SHUTDOWN_TIMEOUT,
 };
 
-const PLAN_AND_EXECUTE_SYSTEM_PROMPT: &str = r#"You are a **Chief Analyst and Reporter Agent**. Your job is to turn an execution log into a clear, well‑structured report for the end user.
+// the top-level reporter prompt is split into sections, so users can replace
+// one of them with `AgentBuilder::set_system_prompt_section`
+const REPORTER_PERSONA: &str = r#"You are a **Chief Analyst and Reporter Agent**. Your job is to turn an execution log into a clear, well‑structured report for the end user.
 
-    ### What you will receive
-    * A conversation history in which
-    1. **`User` messages** describe tasks that were executed.
-    2. **`Assistant` messages** contain the raw results, observations, and any source URLs.
+### What you will receive
+* A conversation history in which
+1. **`User` messages** describe tasks that were executed.
+2. **`Assistant` messages** contain the raw results, observations, and any source URLs.
 
-    ### Your final task
-    Write **one cohesive report** that directly answers the user’s original objective.
-    The final `User` message in the log restates that objective and tells you to begin.
+### Your final task
+Write **one cohesive report** that directly answers the user’s original objective.
+The final `User` message in the log restates that objective and tells you to begin."#;
 
-    ---
-
-    ## Report structure
+const REPORTER_OUTPUT_FORMAT: &str = r#"## Report structure
 
-    1. **Direct summary**
-    Open with a single concise paragraph (no heading) that answers the core question.
+1. **Direct summary**
+Open with a single concise paragraph (no heading) that answers the core question.
 
-    2. **Markdown body**
-    Use headings (`##`), sub‑headings (`###`), **bold** for emphasis, and bulleted or numbered lists to organise the rest of the content.
+2. **Markdown body**
+Use headings (`##`), sub‑headings (`###`), **bold** for emphasis, and bulleted or numbered lists to organise the rest of the content.
 
-    3. **Narrative from data**
-    Weave the key findings into a logical story. Do **not** simply list results.
+3. **Narrative from data**
+Weave the key findings into a logical story. Do **not** simply list results.
 
-    4. **Citations**
-    * Extract source URLs from the execution log.
-    * Attach an inline citation immediately after each sourced fact, using a numbered link: `[1](http://example.com)`.
-    * End the report with a `## References` section listing the full URLs in numeric order.
+4. **Citations**
+* Extract source URLs from the execution log.
+* Attach an inline citation immediately after each sourced fact, using a numbered link: `[1](http://example.com)`.
+* End the report with a `## References` section listing the full URLs in numeric order.
 
-    *Citation example*
+*Citation example*
 
-    > The programme coordinator is Dr. Jane Doe [1](http://example.com/dr‑jane‑doe).
-    > Admission requires a completed bachelor’s degree [2](http://example.com/admission‑requirements).
-    >
-    > ## References
-    > [1] http://example.com/dr‑jane‑doe
-    > [2] http://example.com/admission‑requirements
+> The programme coordinator is Dr. Jane Doe [1](http://example.com/dr‑jane‑doe).
+> Admission requires a completed bachelor’s degree [2](http://example.com/admission‑requirements).
+>
+> ## References
+> [1] http://example.com/dr‑jane‑doe
+> [2] http://example.com/admission‑requirements
 
-    5. **Next steps**
-    After the references, add `### Next Steps` with one or two helpful follow‑up questions or actions.
+5. **Next steps**
+After the references, add `### Next Steps` with one or two helpful follow‑up questions or actions."#;
 
-    ---
+const REPORTER_CONSTRAINTS: &str = r#"## Critical constraints
 
-    ## Critical constraints
-
-    * **Never mention your internal process or the tools used**; focus solely on providing the user with the
-    information that was uncovered and the user might want to know.
-    * **Base every statement strictly on the log content**.
-    * Deliver the entire report as a single, self‑contained message.
-
-    "#;
+* **Never mention your internal process or the tools used**; focus solely on providing the user with the
+information that was uncovered and the user might want to know.
+* **Base every statement strictly on the log content**.
+* Deliver the entire report as a single, self‑contained message."#;
 
 const PLANNER_SYSTEM_PROMPT: &str = r#"You are a meticulous Tactical Planner Agent. You will be given a high-level **strategy** and the original user **objective**. Your **sole purpose** is to convert that strategy into a detailed, step-by-step plan in a strict JSON format.
 
     **Your Task:**
-    Based on the provided strategy, create a JSON object with a single key, "steps", whose value is an array of strings representing the plan. Do not add any explanations or introductory text. Your entire response must be only the JSON object.
+    Based on the provided strategy, create a JSON object with a single key, "steps", whose value is an array of step objects representing the plan. Each step object has a "task" string and a "depends_on" array listing the zero-based indices of earlier steps whose results the task needs. Do not add any explanations or introductory text. Your entire response must be only the JSON object.
 
     **Core Principle: The Executor is Blind**
     The Executor agent who runs these steps has **no knowledge** of the strategy or objective. Therefore, each step you create must be **100% self-contained and specific**, derived from the strategy and objective.
@@ -76,7 +76,8 @@ const PLANNER_SYSTEM_PROMPT: &str = r#"You are a meticulous Tactical Planner Age
     1.  **Translate Strategy to Tactics:** Convert each phase of the high-level strategy into one or more concrete, executable sub-tasks.
     2.  **Create Self-Contained Steps:** For each sub-task, formulate a precise, imperative instruction for the Executor. Embed all relevant keywords and context from the user's objective directly into the step's instruction.
     3.  **Specify Expected Output:** For each step, explicitly state what piece of information the Executor agent must find and return.
-    4.  **Final Answer:** The very last step in the plan must **always** be: "Synthesize all the gathered information and provide the final, comprehensive answer to the user's objective."
+    4.  **Declare Dependencies:** If a step needs the result of an earlier step, list that step's index in "depends_on". Steps with an empty "depends_on" are independent and may be executed at the same time, so only leave it empty when the step truly needs nothing from the others.
+    5.  **Final Answer:** The very last step in the plan must **always** be: "Synthesize all the gathered information and provide the final, comprehensive answer to the user's objective." and it depends on every other step.
 
     **Crucial Constraint: No Generic Steps**
     A step like `"Use query_memory to find relevant information"` is useless.
@@ -94,10 +95,10 @@ const PLANNER_SYSTEM_PROMPT: &str = r#"You are a meticulous Tactical Planner Age
     **Correct JSON Plan Output:**
     {
     "steps": [
-        "Use the search_tool to find the exact date of the first moon landing and return the full date.",
-        "Using the date from the previous step, use the search_tool to find who was the monarch of the United Kingdom at that specific time and return their common name.",
-        "Using the name of the monarch from the previous step, use the search_tool to find their full given name and return that name.",
-        "Synthesize the gathered information and provide the final answer to the user's objective."
+        { "task": "Use the search_tool to find the exact date of the first moon landing and return the full date.", "depends_on": [] },
+        { "task": "Using the date from the previous step, use the search_tool to find who was the monarch of the United Kingdom at that specific time and return their common name.", "depends_on": [0] },
+        { "task": "Using the name of the monarch from the previous step, use the search_tool to find their full given name and return that name.", "depends_on": [1] },
+        { "task": "Synthesize the gathered information and provide the final answer to the user's objective.", "depends_on": [0, 1, 2] }
     ]
     }
     "#;
@@ -150,7 +151,8 @@ The Executor agent who runs your new plan still has **no knowledge** of the orig
 **Rules for the New Plan:**
 1.  **Create Self-Contained and Enriched Steps:** Every step in your new plan must be a precise, imperative instruction with all necessary context and newly acquired data embedded.
 2.  **Do Not Repeat Completed Steps:** Your new plan must only contain steps that have **not** yet been executed.
-3.  **Output Format:** Your response **must** be a JSON object with a single `steps` key. If the objective is complete, the value should be an empty array.
+3.  **Declare Dependencies:** Every step is an object with a `task` string and a `depends_on` array holding the zero-based indices of steps in your new plan whose results it needs. Steps with an empty `depends_on` may be executed at the same time.
+4.  **Output Format:** Your response **must** be a JSON object with a single `steps` key. If the objective is complete, the value should be an empty array.
 
 ---
 
@@ -164,9 +166,9 @@ The Executor agent who runs your new plan still has **no knowledge** of the orig
 **Correct New JSON Plan Output:**
 {
   "steps": [
-    "Use the get_web_page_content tool to search the official FAMNIT website for the 'Computer Science Department' page to identify and return the full name of the department head.",
-    "Using the name of the department head found in the previous step, use the ask_staff_expert tool to find the email address for that person and return the email.",
-    "Synthesize the gathered information and provide the final answer to the user's objective."
+    { "task": "Use the get_web_page_content tool to search the official FAMNIT website for the 'Computer Science Department' page to identify and return the full name of the department head.", "depends_on": [] },
+    { "task": "Using the name of the department head found in the previous step, use the ask_staff_expert tool to find the email address for that person and return the email.", "depends_on": [0] },
+    { "task": "Synthesize the gathered information and provide the final answer to the user's objective.", "depends_on": [0, 1] }
   ]
 }
 
@@ -195,9 +197,9 @@ The Executor agent who runs your new plan still has **no knowledge** of the orig
 **Correct New JSON Plan Output:**
 {
   "steps": [
-    "Using the now known date of July 20, 1969, use the search_tool to find who was the monarch of the United Kingdom at that specific time and return their common name.",
-    "Using the name of the monarch from the previous step, use the search_tool to find their full given name and return that name.",
-    "Synthesize the gathered information and provide the final answer to the user's objective."
+    { "task": "Using the now known date of July 20, 1969, use the search_tool to find who was the monarch of the United Kingdom at that specific time and return their common name.", "depends_on": [] },
+    { "task": "Using the name of the monarch from the previous step, use the search_tool to find their full given name and return that name.", "depends_on": [0] },
+    { "task": "Synthesize the gathered information and provide the final answer to the user's objective.", "depends_on": [0, 1] }
   ]
 }
 
@@ -217,6 +219,35 @@ The Executor agent who runs your new plan still has **no knowledge** of the orig
 }
 "#;
 
+// plan returned by the planner and re-planner: list of steps, each with
+// the indices of the steps it depends on
+const PLAN_RESPONSE_FORMAT: &str = r#"
+{
+    "type": "object",
+    "properties": {
+        "steps": {
+            "type": "array",
+            "items": {
+                "type": "object",
+                "properties": {
+                    "task": { "type": "string" },
+                    "depends_on": {
+                        "type": "array",
+                        "items": { "type": "integer" }
+                    }
+                },
+                "required": ["task", "depends_on"]
+            }
+        }
+    },
+    "required": ["steps"]
+}
+"#;
+
+/// How many independent plan steps may be executed at the same time
+/// by default.
+const DEFAULT_MAX_PARALLEL_STEPS: usize = 4;
+
 const EXECUTOR_SYSTEM_PROMPT: &str = r#"You are given a task and a set of tools. Complete the task.
     Your response must be exhaustive. Hoever respond only with verifiable information that you have recieved int the
     context. If possible cite sources of data and provide references. Answer in markdown in the folowind structure:
@@ -232,22 +263,233 @@ const EXECUTOR_SYSTEM_PROMPT: &str = r#"You are given a task and a set of tools.
     "#;
 
 impl StatefullPrebuild {
+    /// Registry name of the sub-agent that drafts how to tackle the task in
+    /// [`StatefullPrebuild::plan_and_execute`], see
+    /// [`AgentRegistry`](crate::AgentRegistry). It is invoked with a
+    /// template filled with `tools` and `prompt`.
+    pub const BLUEPRINT_AGENT: &'static str = "plan_and_execute.blueprint";
+    /// Registry name of the sub-agent that turns the draft into plan steps.
+    /// It is invoked with a template filled with `tools` and `prompt` (the
+    /// draft) and has to reply with a JSON array of steps.
+    pub const PLANNER_AGENT: &'static str = "plan_and_execute.planner";
+    /// Registry name of the sub-agent that updates the remaining plan. It is
+    /// invoked with a template filled with `tools`, `prompt`, `plan` and
+    /// `past_steps` and has to reply with a JSON array of steps.
+    pub const REPLANNER_AGENT: &'static str = "plan_and_execute.replanner";
+    /// Registry name of the sub-agent that executes a plan step, invoked
+    /// with the step as prompt.
+    pub const EXECUTOR_AGENT: &'static str = "plan_and_execute.executor";
+
+    /// System prompt of the top-level [`StatefullPrebuild::plan_and_execute`]
+    /// agent, which writes the final report. It has a persona, output format
+    /// and constraints section; override one of them on the returned builder
+    /// with [`AgentBuilder::set_system_prompt_section`].
+    pub fn plan_and_execute_prompt() -> SystemPromptBuilder {
+        SystemPromptBuilder::new()
+            .persona(REPORTER_PERSONA)
+            .output_format(REPORTER_OUTPUT_FORMAT)
+            .constraints(REPORTER_CONSTRAINTS)
+    }
+
+    /// Drafts how to tackle the task, turns the draft into a plan and
+    /// executes its steps with sub-agents, re-planning after every round,
+    /// and finally writes a report.
+    ///
+    /// The phases are announced with
+    /// [`NotificationContent::PhaseChange`](crate::NotificationContent::PhaseChange):
+    /// `blueprint`, `plan`, `execute` (with `Step k: <step>` as detail, sent
+    /// as each step starts), `replan` and `report`.
     pub fn plan_and_execute() -> AgentBuilder {
-        // this is the builder for the top-level agent
-        StatefullPrebuild::reply_without_tools()
-            .set_temperature(0.7)
-            .set_min_p(0.0)
-            .set_top_p(0.8)
-            .set_top_k(20)
-            .set_max_iterations(3)
-            .set_system_prompt(PLAN_AND_EXECUTE_SYSTEM_PROMPT)
-            .set_flow(flow!(plan_and_execute_flow))
-            .set_name("Statefull_prebuild-plan_and_execute")
+        StatefullPrebuild::plan_and_execute_with_parallelism(DEFAULT_MAX_PARALLEL_STEPS)
+    }
+
+    /// Same as [`StatefullPrebuild::plan_and_execute`], but executes at most
+    /// `max_parallel_steps` independent plan steps concurrently.
+    /// Use `1` to execute the plan strictly sequentially.
+    pub fn plan_and_execute_with_parallelism(max_parallel_steps: usize) -> AgentBuilder {
+        plan_and_execute_builder(max_parallel_steps, None)
+    }
+
+    /// Same as [`StatefullPrebuild::plan_and_execute_with_parallelism`], but
+    /// executor results are memoized with `step_cache`, so a step the
+    /// re-planner repeats reuses the earlier observation instead of running
+    /// its tools again.
+    ///
+    /// ```
+    /// use std::time::Duration;
+    /// use reagent_rs::{StatefullPrebuild, StepCache};
+    ///
+    /// let builder = StatefullPrebuild::plan_and_execute_with_step_cache(
+    ///     4,
+    ///     StepCache::new().ttl(Duration::from_secs(300)),
+    /// );
+    /// ```
+    pub fn plan_and_execute_with_step_cache(
+        max_parallel_steps: usize,
+        step_cache: StepCache,
+    ) -> AgentBuilder {
+        plan_and_execute_builder(max_parallel_steps, Some(step_cache))
+    }
+}
+
+fn plan_and_execute_builder(
+    max_parallel_steps: usize,
+    step_cache: Option<StepCache>,
+) -> AgentBuilder {
+    let max_parallel_steps = max_parallel_steps.max(1);
+
+    // this is the builder for the top-level agent
+    StatefullPrebuild::reply_without_tools()
+        .set_temperature(0.7)
+        .set_min_p(0.0)
+        .set_top_p(0.8)
+        .set_top_k(20)
+        .set_max_iterations(3)
+        .set_system_prompt_sections(StatefullPrebuild::plan_and_execute_prompt())
+        .set_flow(move |agent: &mut Agent, prompt: String| -> FlowFuture<'_> {
+            Box::pin(plan_and_execute_flow(
+                agent,
+                prompt,
+                max_parallel_steps,
+                step_cache.clone(),
+            ))
+        })
+        .set_name("Statefull_prebuild-plan_and_execute")
+}
+
+/// Turns a plan step into the key its result is cached under, see
+/// [`StepCache::key_fn`].
+pub type StepCacheKey = Arc<dyn Fn(&str) -> String + Send + Sync>;
+
+/// Memoization of executor results in
+/// [`StatefullPrebuild::plan_and_execute_with_step_cache`].
+///
+/// The cache lives for one run of the flow. Steps are keyed by their text,
+/// trimmed, lowercased and with whitespace collapsed, unless a different
+/// key function is set. Without a TTL, results are kept for the whole run.
+#[derive(Clone)]
+pub struct StepCache {
+    ttl: Option<Duration>,
+    key: StepCacheKey,
+}
+
+impl StepCache {
+    pub fn new() -> Self {
+        Self {
+            ttl: None,
+            key: Arc::new(normalize_step),
+        }
+    }
+
+    /// Reuse a result only while it is younger than `ttl`.
+    pub fn ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = Some(ttl);
+        self
+    }
+
+    /// Key steps with `key`; steps with the same key share their result.
+    pub fn key_fn<F>(mut self, key: F) -> Self
+    where
+        F: Fn(&str) -> String + Send + Sync + 'static,
+    {
+        self.key = Arc::new(key);
+        self
     }
 }
 
-#[instrument(level = "debug", skip(agent, prompt))]
-async fn plan_and_execute_flow(agent: &mut Agent, prompt: String) -> Result<Message, AgentError> {
+impl Default for StepCache {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for StepCache {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("StepCache")
+            .field("ttl", &self.ttl)
+            .field("key", &"<fn>")
+            .finish()
+    }
+}
+
+fn normalize_step(step: &str) -> String {
+    step.split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+/// Results of the executed steps of one run, keyed as configured by a
+/// [`StepCache`].
+struct StepResults {
+    cache: StepCache,
+    results: HashMap<String, (Instant, String)>,
+}
+
+impl StepResults {
+    fn new(cache: StepCache) -> Self {
+        Self {
+            cache,
+            results: HashMap::new(),
+        }
+    }
+
+    fn get(&mut self, step: &str) -> Option<String> {
+        let key = (self.cache.key)(step);
+        let (stored, observation) = self.results.get(&key)?;
+        if let Some(ttl) = self.cache.ttl {
+            if stored.elapsed() > ttl {
+                self.results.remove(&key);
+                return None;
+            }
+        }
+        Some(observation.clone())
+    }
+
+    fn insert(&mut self, step: &str, observation: String) {
+        let key = (self.cache.key)(step);
+        self.results.insert(key, (Instant::now(), observation));
+    }
+}
+
+/// A single step of the plan.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+struct PlanStep {
+    /// Self-contained instruction for the executor.
+    task: String,
+    /// Indices (within the same plan) of steps whose results this step needs.
+    depends_on: Vec<usize>,
+}
+
+/// Steps as they may come back from the planner: either plain strings
+/// (executed sequentially) or objects with explicit dependencies.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RawPlanStep {
+    Text(String),
+    Step {
+        task: String,
+        #[serde(default)]
+        depends_on: Option<Vec<usize>>,
+    },
+}
+
+/// The plan and execute flow with the default parallelism, as registered
+/// under `plan_and_execute` (see [`register_flow`](crate::register_flow)).
+pub(crate) async fn plan_and_execute_default_flow(
+    agent: &mut Agent,
+    prompt: String,
+) -> Result<Message, AgentError> {
+    plan_and_execute_flow(agent, prompt, DEFAULT_MAX_PARALLEL_STEPS, None).await
+}
+
+#[instrument(level = "debug", skip(agent, prompt, step_cache))]
+async fn plan_and_execute_flow(
+    agent: &mut Agent,
+    prompt: String,
+    max_parallel_steps: usize,
+    step_cache: Option<StepCache>,
+) -> Result<Message, AgentError> {
     // ------ setup before agent flow loops ------
 
     // history of the steps that were executed by the agent and the step results
@@ -258,22 +500,38 @@ async fn plan_and_execute_flow(agent: &mut Agent, prompt: String) -> Result<Mess
     // system prompt + (steps, results) + summary response to user
     let mut past_steps: Vec<(String, String)> = Vec::new();
 
+    // results of executed steps, reused when the re-planner repeats a step
+    let mut step_results = step_cache.map(StepResults::new);
+
     // creating subagents
     // subagents are created on invocation and are therefore "stateless" inside
-    // the top-level agent.
-    let (mut blueprint_agent, blueprint_notification_channel) =
-        create_blueprint_agent(agent).await?;
-    let (mut planner_agent, planner_notification_channel) = create_planner_agent(agent).await?;
-    let (mut replanner_agent, replanner_notification_channel) =
-        create_replanner_agent(agent).await?;
-    let (mut executor_agent, executor_notification_channel) = create_executor_agent(agent).await?;
-
-    // you can forward notifications that one agent procuces to the other agent,
-    // such that multi-agent flows have the same output from the top-level agent
-    agent.forward_notifications(blueprint_notification_channel);
-    agent.forward_notifications(planner_notification_channel);
-    agent.forward_notifications(replanner_notification_channel);
-    agent.forward_notifications(executor_notification_channel);
+    // the top-level agent. Agents registered under the sub-agent names are
+    // used instead of the default ones.
+    let mut blueprint_agent = resolve_or(
+        agent,
+        StatefullPrebuild::BLUEPRINT_AGENT,
+        create_blueprint_agent,
+    )
+    .await?;
+    let mut planner_agent = resolve_or(
+        agent,
+        StatefullPrebuild::PLANNER_AGENT,
+        create_planner_agent,
+    )
+    .await?;
+    let mut replanner_agent = resolve_or(
+        agent,
+        StatefullPrebuild::REPLANNER_AGENT,
+        create_replanner_agent,
+    )
+    .await?;
+    let executor_agent = resolve_or(
+        agent,
+        StatefullPrebuild::EXECUTOR_AGENT,
+        create_executor_agent,
+    )
+    .await?;
+    let mut executor_agents: Vec<Agent> = Vec::new();
 
     // ------ here actual agent flow starts ------
 
@@ -281,9 +539,12 @@ async fn plan_and_execute_flow(agent: &mut Agent, prompt: String) -> Result<Mess
     //
     // fist we build the draft (blueprint) of how to tackle the user problem
     // we do this by invoking the blueprint sub-agent
+    agent
+        .notify_phase_change(PhaseChange::new("blueprint"))
+        .await;
     let blueprint = blueprint_agent
         .invoke_flow_with_template(HashMap::from([
-            ("tools", format!("{:#?}", agent.tools)),
+            ("tools", agent.tools_prompt_summary()),
             ("prompt", prompt.clone()),
         ]))
         .await?;
@@ -297,9 +558,10 @@ async fn plan_and_execute_flow(agent: &mut Agent, prompt: String) -> Result<Mess
     //
     // from the blueprint we attempt to create the step-by-step plan of the
     // how to solve the user task
+    agent.notify_phase_change(PhaseChange::new("plan")).await;
     let plan_content = planner_agent
         .invoke_flow_with_template(HashMap::from([
-            ("tools", format!("{:#?}", agent.tools)),
+            ("tools", agent.tools_prompt_summary()),
             ("prompt", blueprint),
         ]))
         .await?;
@@ -322,22 +584,81 @@ async fn plan_and_execute_flow(agent: &mut Agent, prompt: String) -> Result<Mess
             break;
         }
 
-        // extract what step we need to do
-        let current_step = plan.remove(0);
+        // extract which steps we can do right now: the ones that don't
+        // depend on any other step that is still in the plan
+        let wave = next_wave(&plan, max_parallel_steps);
+        let current_steps: Vec<String> = wave.iter().map(|&i| plan[i].task.clone()).collect();
+        plan = without_steps(plan, &wave);
+
+        // steps that were executed before (and are still fresh) reuse their
+        // observation, the rest goes to the executors
+        let cached: Vec<Option<String>> = current_steps
+            .iter()
+            .map(|step| step_results.as_mut().and_then(|results| results.get(step)))
+            .collect();
+        let pending = cached.iter().filter(|cached| cached.is_none()).count();
+
+        // every concurrently executed step needs its own executor, since
+        // invoking an agent borrows it mutably. Clones share the notification
+        // channel, so their notifications are already forwarded.
+        while executor_agents.len() < pending {
+            executor_agents.push(executor_agent.clone());
+        }
+
+        // execute the steps
+        // for this we use the executor sub-agents with clean history every iteration.
+        // Each step announces its phase when it starts.
+        let first_step = past_steps.len() + 1;
+        let announcer = &*agent;
+        let mut executors = executor_agents.iter_mut();
+        let responses = join_all(current_steps.iter().zip(&cached).enumerate().map(
+            |(k, (step, cached))| {
+                let executor = cached.is_none().then(|| {
+                    executors
+                        .next()
+                        .expect("every pending step has an executor")
+                });
+                async move {
+                    let detail = format!("Step {}: {step}", first_step + k);
+                    announcer
+                        .notify_phase_change(PhaseChange::new("execute").detail(detail))
+                        .await;
+                    match executor {
+                        Some(executor) => Some(executor.invoke_flow(step.clone()).await),
+                        None => None,
+                    }
+                }
+            },
+        ))
+        .await;
 
-        // put the step instruction to the overarching agent history (so the top-level agent remembers the step)
-        agent.history.push(Message::user(current_step.clone()));
+        // merge the results in plan order, so the history does not depend on
+        // which step finished first
+        for ((current_step, cached), response) in
+            current_steps.into_iter().zip(cached).zip(responses)
+        {
+            let response = match (cached, response) {
+                (Some(observation), _) => {
+                    tracing::debug!(step = %current_step, "Reusing cached step result");
+                    Message::assistant(observation)
+                }
+                (None, response) => response.expect("every pending step has a response")?,
+            };
 
-        // execute the step
-        // for this we use the executor sub-agent with clean history every iteration
-        let response = executor_agent.invoke_flow(current_step.clone()).await?;
+            // put the step instruction to the overarching agent history (so the top-level agent remembers the step)
+            agent.history.push(Message::user(current_step.clone()));
 
-        // top-level agent remembers the response (result of step)
-        agent.history.push(response.clone());
+            // top-level agent remembers the response (result of step)
+            agent.history.push(response.clone());
 
-        // also save the (step, result) to the past_steps
-        let observation = response.content.clone().unwrap_or_default();
-        past_steps.push((current_step, observation));
+            // also save the (step, result) to the past_steps
+            let observation = response.content.clone().unwrap_or_default();
+            if let Some(results) = step_results.as_mut() {
+                results.insert(&current_step, observation.clone());
+            }
+            agent.record_step(current_step.clone(), observation.clone());
+            past_steps.push((current_step, observation));
+        }
 
         // parse past steps to a string to pass to the next agent
         let past_steps_str = past_steps
@@ -349,11 +670,15 @@ async fn plan_and_execute_flow(agent: &mut Agent, prompt: String) -> Result<Mess
         // use replaner to adapt the plan to executed steps and their results
         // the replanner also resets history on each iteration, so we pass the
         // "past_steps" to show histroical progress
+        agent.notify_phase_change(PhaseChange::new("replan")).await;
         let new_plan_content = replanner_agent
             .invoke_flow_with_template(HashMap::from([
-                ("tools", format!("{:#?}", agent.tools)),
+                ("tools", agent.tools_prompt_summary()),
                 ("prompt", prompt.clone()),
-                ("plan", format!("{plan:#?}")),
+                (
+                    "plan",
+                    serde_json::to_string_pretty(&plan).unwrap_or_else(|_| format!("{plan:#?}")),
+                ),
                 ("past_steps", past_steps_str),
             ]))
             .await?;
@@ -363,21 +688,34 @@ async fn plan_and_execute_flow(agent: &mut Agent, prompt: String) -> Result<Mess
         plan = get_plan_from_response(&new_plan_content)?;
     }
 
+    // the sub-agents are done. Dropping them closes their notification
+    // channels, so their last notifications are forwarded before our Done
+    drop((
+        blueprint_agent,
+        planner_agent,
+        replanner_agent,
+        executor_agent,
+        executor_agents,
+    ));
+    if !agent.await_forwarders(DEFAULT_SHUTDOWN_TIMEOUT).await {
+        tracing::warn!("Notification forwarders are still running");
+    }
+
     if past_steps.last().is_some() {
         // summarize the history and provide the user a nice answer to
         // the prompt
         // this is the only real invocation of the top-level agent
         // everything else is sub-agents
+        agent.notify_phase_change(PhaseChange::new("report")).await;
         agent.history.push(Message::user(prompt.to_string()));
         let response = InvocationBuilder::default()
             .use_tools(false)
             .invoke_with(agent)
             .await?;
 
-        agent
-            .notify_done(true, response.message.content.clone())
-            .await;
-        Ok(response.message)
+        let message = agent.moderate(&prompt, response.message).await?;
+        agent.notify_done(true, message.content.clone()).await;
+        Ok(message)
     } else {
         agent
             .notify_done(
@@ -391,8 +729,62 @@ async fn plan_and_execute_flow(agent: &mut Agent, prompt: String) -> Result<Mess
     }
 }
 
-fn get_plan_from_response(plan_response: &Message) -> Result<Vec<String>, AgentError> {
-    // parse the Vec<String> (plan steps) from the agent response
+/// Pick the indices of steps that can be executed right now (at most `max_parallel`),
+/// in plan order. A step is ready when none of its dependencies is still in the plan.
+fn next_wave(plan: &[PlanStep], max_parallel: usize) -> Vec<usize> {
+    let mut wave = Vec::new();
+    for (i, step) in plan.iter().enumerate() {
+        if wave.len() >= max_parallel {
+            break;
+        }
+        let blocked = step
+            .depends_on
+            .iter()
+            .any(|&dependency| dependency < plan.len() && dependency != i);
+        if !blocked {
+            wave.push(i);
+        }
+    }
+
+    // cyclic dependencies: fall back to executing the first step
+    if wave.is_empty() && !plan.is_empty() {
+        wave.push(0);
+    }
+    wave
+}
+
+/// Remove the steps at `removed` from the plan and re-map the dependencies
+/// of the remaining steps to their new indices. Dependencies on removed
+/// steps are satisfied and dropped.
+fn without_steps(plan: Vec<PlanStep>, removed: &[usize]) -> Vec<PlanStep> {
+    // new index of every step that stays in the plan
+    let mut new_index = Vec::with_capacity(plan.len());
+    let mut kept = 0;
+    for i in 0..plan.len() {
+        if removed.contains(&i) {
+            new_index.push(None);
+        } else {
+            new_index.push(Some(kept));
+            kept += 1;
+        }
+    }
+
+    plan.into_iter()
+        .enumerate()
+        .filter(|(i, _)| !removed.contains(i))
+        .map(|(_, step)| PlanStep {
+            depends_on: step
+                .depends_on
+                .iter()
+                .filter_map(|&dependency| new_index.get(dependency).copied().flatten())
+                .collect(),
+            ..step
+        })
+        .collect()
+}
+
+fn get_plan_from_response(plan_response: &Message) -> Result<Vec<PlanStep>, AgentError> {
+    // parse the Vec<PlanStep> (plan steps) from the agent response
 
     let original_plan_string = plan_response.content.clone().unwrap_or_default();
 
@@ -403,15 +795,67 @@ fn get_plan_from_response(plan_response: &Message) -> Result<Vec<String>, AgentE
         AgentError::Runtime("JSON object is missing the required 'steps' key.".to_string())
     })?;
 
-    let plan: Vec<String> = serde_json::from_value(plan.clone()).map_err(|e| {
+    let plan: Vec<RawPlanStep> = serde_json::from_value(plan.clone()).map_err(|e| {
         AgentError::Runtime(format!(
-            "The 'steps' key is not a valid array of strings: {e}"
+            "The 'steps' key is not a valid array of steps: {e}"
         ))
     })?;
 
+    // steps without explicit dependencies depend on the previous step,
+    // which keeps plain string plans sequential
+    let plan = plan
+        .into_iter()
+        .enumerate()
+        .map(|(i, step)| {
+            let previous = || i.checked_sub(1).into_iter().collect();
+            match step {
+                RawPlanStep::Text(task) => PlanStep {
+                    task,
+                    depends_on: previous(),
+                },
+                RawPlanStep::Step { task, depends_on } => PlanStep {
+                    task,
+                    depends_on: depends_on.unwrap_or_else(previous),
+                },
+            }
+        })
+        .collect();
+
     Ok(plan)
 }
 
+/// The agent registered as `name` (see [`Agent::resolve`]), or the one built
+/// by `default`, set up as sub-agent of `agent` (e.g. in dry-run mode when
+/// `agent` is). The notifications of a default agent are forwarded through
+/// `agent`, such that multi-agent flows have the same output from the
+/// top-level agent.
+///
+/// The future is boxed and `default` called inside it: building agents makes
+/// for large futures, which would otherwise take up the stack of the flow
+/// while the sub-agents run.
+fn resolve_or<'a, F, Fut>(
+    agent: &'a Agent,
+    name: &'a str,
+    default: F,
+) -> BoxFuture<'a, Result<Agent, AgentError>>
+where
+    F: FnOnce(&'a Agent) -> Fut + Send + 'a,
+    Fut: Future<Output = Result<(Agent, Receiver<Notification>), AgentBuildError>> + Send + 'a,
+{
+    Box::pin(async move {
+        let mut sub_agent = match agent.try_resolve(name).await? {
+            Some(registered) => registered,
+            None => {
+                let (sub_agent, notifications) = default(agent).await?;
+                agent.forward_notifications(notifications);
+                sub_agent
+            }
+        };
+        agent.configure_sub_agent(&mut sub_agent);
+        Ok(sub_agent)
+    })
+}
+
 async fn create_planner_agent(
     ref_agent: &Agent,
 ) -> Result<(Agent, Receiver<Notification>), AgentBuildError> {
@@ -443,22 +887,7 @@ async fn create_planner_agent(
         .set_name("Statefull_prebuild-plan_and_execute-planner")
         // set response format to be array of strings
         // [plan_steps]
-        .set_response_format_str(
-            r#"
-        {
-            "type": "object",
-            "properties": {
-                "steps": {
-                    "type": "array",
-                    "items": {
-                        "type": "string"
-                    }
-                }
-            },
-            "required": ["steps"]
-        }
-        "#,
-        )
+        .set_response_format_str(PLAN_RESPONSE_FORMAT)
         .set_system_prompt(PLANNER_SYSTEM_PROMPT)
         .set_template(template)
         // clear histroy every time we invoke the agent
@@ -546,22 +975,7 @@ async fn create_replanner_agent(
         .set_template(template)
         // set response format to be array of strings
         // [new_plan_steps]
-        .set_response_format_str(
-            r#"
-        {
-            "type": "object",
-            "properties": {
-                "steps": {
-                    "type": "array",
-                    "items": {
-                        "type": "string"
-                    }
-                }
-            },
-            "required": ["steps"]
-        }
-        "#,
-        )
+        .set_response_format_str(PLAN_RESPONSE_FORMAT)
         // clear histroy every time we invoke the agent
         .set_clear_history_on_invocation(true)
         .build_with_notification()
@@ -588,9 +1002,208 @@ async fn create_executor_agent(
         .await
 }
 
-async fn extract_configurations(agent: &Agent) -> (ClientConfig, ModelConfig, PromptConfig) {
+pub(super) async fn extract_configurations(
+    agent: &Agent,
+) -> (ClientConfig, ModelConfig, PromptConfig) {
     let client_config = agent.export_client_config();
     let model_config = agent.export_model_config();
     let prompt_config = agent.export_prompt_config().await.unwrap_or_default();
     (client_config, model_config, prompt_config)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{testing::FlowTestHarness, NotificationContent, Role, ToolSimulator};
+
+    fn step(task: &str, depends_on: &[usize]) -> PlanStep {
+        PlanStep {
+            task: task.into(),
+            depends_on: depends_on.to_vec(),
+        }
+    }
+
+    #[test]
+    fn string_steps_are_sequential() {
+        let message = Message::assistant(r#"{"steps":["a","b"]}"#);
+        let plan = get_plan_from_response(&message).unwrap();
+
+        assert_eq!(plan, vec![step("a", &[]), step("b", &[0])]);
+        assert_eq!(next_wave(&plan, 4), vec![0]);
+    }
+
+    #[test]
+    fn independent_steps_form_one_wave() {
+        let message = Message::assistant(
+            r#"{"steps":[
+                {"task":"a","depends_on":[]},
+                {"task":"b","depends_on":[]},
+                {"task":"c","depends_on":[]},
+                {"task":"summary","depends_on":[0,1,2]}
+            ]}"#,
+        );
+        let plan = get_plan_from_response(&message).unwrap();
+
+        assert_eq!(next_wave(&plan, 4), vec![0, 1, 2]);
+        assert_eq!(next_wave(&plan, 2), vec![0, 1]);
+    }
+
+    #[test]
+    fn cyclic_dependencies_fall_back_to_first_step() {
+        let plan = vec![step("a", &[1]), step("b", &[0])];
+        assert_eq!(next_wave(&plan, 4), vec![0]);
+    }
+
+    #[test]
+    fn dependencies_follow_the_steps_when_the_plan_changes() {
+        let plan = vec![step("a", &[]), step("b", &[]), step("c", &[1])];
+        assert_eq!(next_wave(&plan, 1), vec![0]);
+
+        // "c" still waits for "b", which moved to index 0
+        let plan = without_steps(plan, &[0]);
+        assert_eq!(plan, vec![step("b", &[]), step("c", &[0])]);
+        assert_eq!(next_wave(&plan, 4), vec![0]);
+
+        let plan = without_steps(plan, &[0]);
+        assert_eq!(plan, vec![step("c", &[])]);
+    }
+
+    #[tokio::test]
+    async fn replanning_away_a_dependency_unblocks_its_dependents() {
+        let builder = StatefullPrebuild::plan_and_execute_with_parallelism(1).set_model("test");
+        // blueprint, planner, executor "a", replanner (drops "b"),
+        // executor "c", replanner, report
+        let mut harness = FlowTestHarness::new(builder)
+            .await
+            .unwrap()
+            .reply("Do a, b and c.")
+            .reply(
+                r#"{"steps":[
+                    {"task":"a","depends_on":[]},
+                    {"task":"b","depends_on":[]},
+                    {"task":"c","depends_on":[1]}
+                ]}"#,
+            )
+            .reply("Result of a.")
+            .reply(r#"{"steps":[{"task":"c","depends_on":[]}]}"#)
+            .reply("Result of c.")
+            .reply(r#"{"steps":[]}"#)
+            .reply("Done.");
+
+        harness.run("Do it.").await.unwrap();
+
+        // the replanner sees the remaining plan with re-mapped dependencies
+        let remaining = serde_json::to_string_pretty(&[step("b", &[]), step("c", &[0])]).unwrap();
+        let replanner_request = &harness.requests()[3];
+        assert!(replanner_request
+            .messages
+            .iter()
+            .any(|m| m.content.as_deref().is_some_and(|c| c.contains(&remaining))));
+
+        harness.assert_history_contains(Role::Assistant, "Result of c.");
+        assert!(!harness
+            .history()
+            .iter()
+            .any(|m| m.content.as_deref() == Some("b")));
+    }
+
+    #[tokio::test]
+    async fn repeated_steps_reuse_cached_results() {
+        let builder = StatefullPrebuild::plan_and_execute_with_step_cache(1, StepCache::new())
+            .set_model("test");
+        // blueprint, planner, executor, replanner (repeats the step),
+        // replanner, report. The repeated step is not executed again.
+        let mut harness = FlowTestHarness::new(builder)
+            .await
+            .unwrap()
+            .reply("Look up the capital.")
+            .reply(r#"{"steps":["Search for the capital of Slovenia."]}"#)
+            .reply("The capital is Ljubljana.")
+            .reply(r#"{"steps":["search for the  capital of Slovenia. "]}"#)
+            .reply(r#"{"steps":[]}"#)
+            .reply("Ljubljana");
+
+        let reply = harness
+            .run("What is the capital of Slovenia?")
+            .await
+            .unwrap();
+        assert_eq!(reply.content.as_deref(), Some("Ljubljana"));
+        assert_eq!(harness.requests().len(), 6);
+        let observations = harness
+            .history()
+            .iter()
+            .filter(|m| m.content.as_deref() == Some("The capital is Ljubljana."))
+            .count();
+        assert_eq!(observations, 2);
+
+        let phases: Vec<PhaseChange> = harness
+            .notifications()
+            .iter()
+            .filter_map(|notification| match &notification.content {
+                NotificationContent::PhaseChange(phase) => Some(phase.clone()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            phases,
+            [
+                PhaseChange::new("blueprint"),
+                PhaseChange::new("plan"),
+                PhaseChange::new("execute").detail("Step 1: Search for the capital of Slovenia."),
+                PhaseChange::new("replan"),
+                PhaseChange::new("execute").detail("Step 2: search for the  capital of Slovenia. "),
+                PhaseChange::new("replan"),
+                PhaseChange::new("report"),
+            ]
+        );
+        harness.verify();
+    }
+
+    #[tokio::test]
+    async fn dry_runs_do_not_execute_the_tools_of_the_executor() {
+        let delete = crate::ToolBuilder::new()
+            .function_name("delete_file")
+            .function_description("Deletes a file")
+            .add_required_property("path", "string", "File to delete")
+            .executor_fn(|_| async { panic!("the tool was executed in a dry run") })
+            .build()
+            .unwrap();
+        let builder = StatefullPrebuild::plan_and_execute_with_parallelism(1)
+            .set_model("test")
+            .set_dry_run(true)
+            .set_tool_simulator(ToolSimulator::new().fixture("delete_file", "Would delete"));
+        // blueprint, planner, executor (calls the tool, then answers),
+        // replanner, report
+        let mut harness = FlowTestHarness::new(builder)
+            .await
+            .unwrap()
+            .reply("Delete the file.")
+            .reply(r#"{"steps":["Delete notes.txt."]}"#)
+            .reply_with_tool_call("delete_file", serde_json::json!({ "path": "notes.txt" }))
+            .reply("The file is deleted.")
+            .reply(r#"{"steps":[]}"#)
+            .reply("Done.");
+        // the harness stubs the tools, put the real one back
+        harness.agent_mut().tools = Some(vec![delete]);
+
+        let reply = harness.run("Delete notes.txt").await.unwrap();
+        assert_eq!(reply.content.as_deref(), Some("Done."));
+        let tool_result = &harness.requests()[3].messages;
+        assert!(tool_result
+            .iter()
+            .any(|m| m.role == crate::Role::Tool && m.content.as_deref() == Some("Would delete")));
+        harness.verify();
+    }
+
+    #[test]
+    fn expired_results_are_not_reused() {
+        let mut results = StepResults::new(StepCache::new().ttl(Duration::ZERO));
+        results.insert("a", "result".into());
+        std::thread::sleep(Duration::from_millis(1));
+        assert_eq!(results.get("a"), None);
+
+        let mut results = StepResults::new(StepCache::new().key_fn(|step| step.len().to_string()));
+        results.insert("abc", "result".into());
+        assert_eq!(results.get("xyz").as_deref(), Some("result"));
+    }
+}
@@ -0,0 +1,38 @@
+use crate::{prebuilds::StatefullPrebuild, AgentBuilder, SystemPromptBuilder};
+
+const SIMULATED_USER_RULES: &str = r#"You are role-playing the user of an AI assistant, to test it. Stay in character for the whole conversation:
+
+- Write only what the user would type next, as the user. Never act as the assistant, never answer your own questions.
+- Every message you receive is the assistant's last reply.
+- Pursue your goal one step at a time, the way a real person with your persona would: give details only when they are asked for, react to what the assistant said, complain or ask again when it did not help.
+- Keep your messages short."#;
+
+impl StatefullPrebuild {
+    /// Marker the [`simulated_user`](Self::simulated_user) writes once its
+    /// goal is reached, which ends the dialog of a
+    /// [`DialogDriver`](crate::evals::DialogDriver).
+    pub const GOAL_REACHED: &'static str = "GOAL_REACHED";
+
+    /// Agent that plays a user with `persona` pursuing `goal`, to test
+    /// another agent in multi-turn conversations driven by a
+    /// [`DialogDriver`](crate::evals::DialogDriver). It is invoked with the
+    /// replies of the agent under test and answers with the user's next
+    /// message, or [`GOAL_REACHED`](Self::GOAL_REACHED) once the goal is met.
+    ///
+    /// The system prompt has a persona, constraints (the goal) and output
+    /// format section; override one of them on the returned builder with
+    /// [`AgentBuilder::set_system_prompt_section`].
+    pub fn simulated_user(persona: impl Into<String>, goal: impl Into<String>) -> AgentBuilder {
+        let prompt = SystemPromptBuilder::new()
+            .section("rules", SIMULATED_USER_RULES)
+            .persona(format!("Your persona: {}", persona.into()))
+            .constraints(format!("Your goal: {}", goal.into()))
+            .output_format(format!(
+                "Once the assistant fully achieved your goal, reply with `{}` and nothing else.",
+                Self::GOAL_REACHED
+            ));
+        StatefullPrebuild::reply_without_tools()
+            .set_system_prompt_sections(prompt)
+            .set_name("Statefull_prebuild-simulated_user")
+    }
+}
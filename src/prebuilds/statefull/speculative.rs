@@ -0,0 +1,307 @@
+use serde_json::{json, Value};
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc,
+};
+use tokio::sync::mpsc::Receiver;
+use tracing::instrument;
+
+use crate::{
+    prebuilds::{statefull::plan_and_execute::extract_configurations, StatefullPrebuild},
+    services::llm::message::Message,
+    Agent, AgentBuildError, AgentBuilder, AgentError, FlowFuture, InvocationBuilder, Notification,
+    NotificationHandler, PhaseChange, StatelessPrebuild,
+};
+
+/// Scores a draft response to a prompt from 0.0 (useless) to 1.0 (perfect).
+/// Called with the prompt and the draft.
+pub type DraftVerifier = Arc<dyn Fn(&str, &str) -> f32 + Send + Sync>;
+
+const VERIFIER_SYSTEM_PROMPT: &str = r#"You are a strict **Verifier Agent**. You will be given a user's request and a draft response written by a smaller model.
+
+Your task is to decide how well the draft answers the request, on a scale from 0 to 10, where 10 means the draft can be sent to the user as it is.
+
+**Scoring criteria:**
+1.  **Correctness:** Is the information accurate and free of errors?
+2.  **Completeness:** Does it fully address every part of the request?
+3.  **Clarity:** Is it well-structured and easy to follow?
+
+**Output Format:**
+Respond only with a JSON object with a single key, "score", whose value is a number. Do not add any explanations.
+
+**Example:**
+{
+    "score": 8
+}
+"#;
+
+const VERIFIER_RESPONSE_FORMAT: &str = r#"
+{
+    "type": "object",
+    "properties": {
+        "score": { "type": "number" }
+    },
+    "required": ["score"]
+}
+"#;
+
+/// Accepted and rejected drafts of one speculative agent and its clones.
+#[derive(Debug, Default)]
+struct AcceptanceStats {
+    accepted: AtomicU64,
+    rejected: AtomicU64,
+}
+
+impl AcceptanceStats {
+    fn record(&self, accepted: bool) -> Value {
+        let counter = if accepted {
+            &self.accepted
+        } else {
+            &self.rejected
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+        self.get()
+    }
+
+    fn get(&self) -> Value {
+        let accepted = self.accepted.load(Ordering::Relaxed);
+        let rejected = self.rejected.load(Ordering::Relaxed);
+        json!({
+            "accepted": accepted,
+            "rejected": rejected,
+            "acceptance_rate": accepted as f64 / (accepted + rejected).max(1) as f64,
+        })
+    }
+}
+
+impl StatefullPrebuild {
+    /// Lets `draft_model` answer first and only invokes the agent's own
+    /// (larger) model when the draft is rejected.
+    ///
+    /// A verifier sub-agent running the agent's model scores the draft; it
+    /// is accepted when the score (0.0 - 1.0) reaches `threshold`. Verifying
+    /// is usually much cheaper than answering, as the verifier replies with
+    /// a single number. A draft the verifier fails to score is unverified
+    /// and never accepted: the agent's model answers instead, and the
+    /// decision notification carries the error.
+    ///
+    /// Every decision is emitted as a
+    /// [`NotificationContent::Custom`](crate::NotificationContent::Custom)
+    /// notification with the score, the threshold and the acceptance rate so
+    /// far, to tune the threshold. The `draft`, `verify` and, for rejected
+    /// drafts, `answer` phases are announced with
+    /// [`NotificationContent::PhaseChange`](crate::NotificationContent::PhaseChange).
+    pub fn speculative(draft_model: impl Into<String>, threshold: f32) -> AgentBuilder {
+        speculative_builder(draft_model.into(), threshold, None)
+    }
+
+    /// Same as [`StatefullPrebuild::speculative`], but drafts are scored with
+    /// the given rules instead of a verifier sub-agent.
+    pub fn speculative_with_verifier<F>(
+        draft_model: impl Into<String>,
+        threshold: f32,
+        verifier: F,
+    ) -> AgentBuilder
+    where
+        F: Fn(&str, &str) -> f32 + Send + Sync + 'static,
+    {
+        speculative_builder(draft_model.into(), threshold, Some(Arc::new(verifier)))
+    }
+}
+
+fn speculative_builder(
+    draft_model: String,
+    threshold: f32,
+    verifier: Option<DraftVerifier>,
+) -> AgentBuilder {
+    let stats = Arc::new(AcceptanceStats::default());
+    StatefullPrebuild::reply_without_tools()
+        .set_flow(move |agent: &mut Agent, prompt: String| -> FlowFuture<'_> {
+            Box::pin(speculative_flow(
+                agent,
+                prompt,
+                draft_model.clone(),
+                threshold,
+                verifier.clone(),
+                stats.clone(),
+            ))
+        })
+        .set_name("Statefull_prebuild-speculative")
+}
+
+#[instrument(level = "debug", skip(agent, prompt, verifier, stats))]
+async fn speculative_flow(
+    agent: &mut Agent,
+    prompt: String,
+    draft_model: String,
+    threshold: f32,
+    verifier: Option<DraftVerifier>,
+    stats: Arc<AcceptanceStats>,
+) -> Result<Message, AgentError> {
+    agent.history.push(Message::user(prompt.clone()));
+
+    // the draft is generated by a clone, so a rejected draft never reaches
+    // the agent's history
+    agent
+        .notify_phase_change(PhaseChange::new("draft").detail(draft_model.clone()))
+        .await;
+    let mut drafter = agent.clone();
+    drafter.model = draft_model.clone();
+    drafter.name = format!("{}-draft", agent.name);
+    let draft = InvocationBuilder::default()
+        .use_tools(false)
+        .invoke_with(&mut drafter)
+        .await;
+
+    let (draft, score) = match draft {
+        Ok(response) => {
+            let content = response.message.content.clone().unwrap_or_default();
+            agent.notify_phase_change(PhaseChange::new("verify")).await;
+            let score = match &verifier {
+                Some(verifier) => Ok(verifier(&prompt, &content)),
+                None => verify_with_agent(agent, &prompt, &content).await,
+            };
+            (Some(response.message), score)
+        }
+        Err(e) => {
+            tracing::warn!(error = %e, model = %draft_model, "Speculative draft failed");
+            (None, Ok(0.0))
+        }
+    };
+
+    let accepted = draft.is_some() && score.as_ref().is_ok_and(|score| *score >= threshold);
+    let stats = match &score {
+        Ok(_) => stats.record(accepted),
+        // an unverified draft says nothing about the threshold
+        Err(e) => {
+            tracing::warn!(error = %e, "Verifying the speculative draft failed");
+            stats.get()
+        }
+    };
+    agent
+        .notify_custom(json!({
+            "speculative_draft": {
+                "model": draft_model,
+                "content": draft.as_ref().and_then(|d| d.content.clone()),
+                "score": score.as_ref().ok(),
+                "verified": score.is_ok(),
+                "error": score.as_ref().err().map(ToString::to_string),
+                "threshold": threshold,
+                "accepted": accepted,
+                "stats": stats,
+            }
+        }))
+        .await;
+
+    let response = match draft {
+        Some(draft) if accepted => {
+            agent.history.push(draft.clone());
+            draft
+        }
+        _ => {
+            agent.notify_phase_change(PhaseChange::new("answer")).await;
+            InvocationBuilder::default()
+                .use_tools(false)
+                .invoke_with(agent)
+                .await?
+                .message
+        }
+    };
+
+    let response = agent.moderate(&prompt, response).await?;
+    agent.notify_done(true, response.content.clone()).await;
+    Ok(response)
+}
+
+async fn verify_with_agent(agent: &Agent, prompt: &str, draft: &str) -> Result<f32, AgentError> {
+    let (mut verifier_agent, verifier_notification_channel) = create_verifier_agent(agent).await?;
+    agent.forward_notifications(verifier_notification_channel);
+    let verdict = verifier_agent
+        .invoke_flow(format!(
+            "# User request:\n\n{prompt}\n\n# Draft response:\n\n{draft}"
+        ))
+        .await?;
+    get_score_from_response(&verdict)
+}
+
+/// Score of the verifier, scaled to 0.0 - 1.0.
+fn get_score_from_response(verdict: &Message) -> Result<f32, AgentError> {
+    let content = verdict.content.clone().unwrap_or_default();
+
+    let verdict: Value = serde_json::from_str(&content)
+        .map_err(|e| AgentError::Runtime(format!("Verifier failed to return valid JSON: {e}")))?;
+
+    let score = verdict
+        .get("score")
+        .and_then(Value::as_f64)
+        .ok_or_else(|| AgentError::Runtime("The 'score' key is missing or not a number".into()))?;
+
+    Ok((score as f32 / 10.0).clamp(0.0, 1.0))
+}
+
+async fn create_verifier_agent(
+    ref_agent: &Agent,
+) -> Result<(Agent, Receiver<Notification>), AgentBuildError> {
+    let (client_config, model_config, prompt_config) = extract_configurations(ref_agent).await;
+
+    StatelessPrebuild::reply_without_tools()
+        // we transfer the settings set to the top-level agent
+        .import_client_config(client_config)
+        .import_model_config(model_config)
+        .import_prompt_config(prompt_config)
+        .set_name("Statefull_prebuild-speculative-verifier")
+        .set_system_prompt(VERIFIER_SYSTEM_PROMPT)
+        .set_temperature(0.0)
+        .set_response_format_str(VERIFIER_RESPONSE_FORMAT)
+        .build_with_notification()
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{testing::FlowTestHarness, NotificationContent};
+
+    #[test]
+    fn verifier_scores_are_scaled_and_counted() {
+        let verdict = Message::assistant(r#"{"score": 7.5}"#);
+        assert_eq!(get_score_from_response(&verdict).unwrap(), 0.75);
+        assert!(get_score_from_response(&Message::assistant(r#"{"ok": true}"#)).is_err());
+
+        let stats = AcceptanceStats::default();
+        stats.record(true);
+        assert_eq!(
+            stats.record(false),
+            json!({ "accepted": 1, "rejected": 1, "acceptance_rate": 0.5 })
+        );
+    }
+
+    #[tokio::test]
+    async fn drafts_the_verifier_fails_to_score_are_not_accepted() {
+        let mut harness =
+            FlowTestHarness::new(StatefullPrebuild::speculative("draft", 0.7).set_model("test"))
+                .await
+                .unwrap()
+                .reply("Paris, probably.")
+                .reply("I think it is fine")
+                .reply("The capital of France is Paris.");
+
+        let reply = harness.run("What is the capital of France?").await.unwrap();
+
+        assert_eq!(
+            reply.content.as_deref(),
+            Some("The capital of France is Paris.")
+        );
+        harness.assert_notified(None, |content| match content {
+            NotificationContent::Custom(value) => {
+                let decision = &value["speculative_draft"];
+                decision["verified"] == json!(false)
+                    && decision["accepted"] == json!(false)
+                    && decision["error"].is_string()
+                    && decision["stats"]["rejected"] == json!(0)
+            }
+            _ => false,
+        });
+        harness.verify();
+    }
+}
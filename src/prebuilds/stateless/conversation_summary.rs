@@ -0,0 +1,233 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::{
+    flow, parse_structured_output, prebuilds::StatelessPrebuild, reply_without_tools_flow,
+    services::llm::SchemaSpec, Agent, AgentBuilder, AgentError, AgentOutput, FromMessage,
+    InvocationBuilder, Message, NotificationHandler, Role, CHECKPOINT_METADATA,
+};
+
+const SUMMARY_SYSTEM_PROMPT: &str = r#"You name and summarize conversations between a user and an assistant.
+
+**Output Format:**
+Respond only with a JSON object with the keys:
+- "title": a short title of at most six words, without quotes or trailing punctuation,
+- "summary": one to three sentences on what the conversation is about and what was concluded.
+"#;
+
+/// Key of the conversation title in [`Agent::state`].
+pub const TITLE_STATE: &str = "title";
+/// Key of the conversation summary in [`Agent::state`].
+pub const SUMMARY_STATE: &str = "summary";
+
+/// Structured output of the [`StatelessPrebuild::conversation_summary`] agent.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ConversationSummary {
+    /// Short title, e.g. for a chat list.
+    pub title: String,
+    /// A few sentences on the conversation.
+    pub summary: String,
+}
+
+impl FromMessage for ConversationSummary {
+    fn from_message(message: &Message) -> Result<Self, AgentError> {
+        parse_structured_output(message)
+    }
+}
+
+impl AgentOutput for ConversationSummary {
+    fn response_format() -> SchemaSpec {
+        SchemaSpec::from_value(json!({
+            "type": "object",
+            "properties": {
+                "title": { "type": "string" },
+                "summary": { "type": "string" }
+            },
+            "required": ["title", "summary"]
+        }))
+        .with_name("conversation_summary")
+    }
+}
+
+impl StatelessPrebuild {
+    /// Name under which [`Agent::summarize_conversation`] looks up the agent
+    /// writing titles and summaries (see [`Agent::resolve`]). Without one,
+    /// it builds [`conversation_summary`](Self::conversation_summary) with
+    /// the client and model of the summarized agent.
+    pub const SUMMARY_AGENT: &'static str = "conversation_summary";
+
+    /// Agent writing a title and a summary of a conversation, passed as a
+    /// transcript in a single request without tools. Small models are
+    /// usually good enough.
+    pub fn conversation_summary() -> AgentBuilder {
+        AgentBuilder::default()
+            .set_flow(flow!(reply_without_tools_flow))
+            .set_system_prompt(SUMMARY_SYSTEM_PROMPT)
+            .set_response_format_output::<ConversationSummary>()
+            .set_clear_history_on_invocation(true)
+            .remove_tools()
+            .set_name("Stateless_prebuild-conversation_summary")
+    }
+}
+
+impl Agent {
+    /// Title of the conversation, set by [`generate_title`](Self::generate_title),
+    /// [`summarize_conversation`](Self::summarize_conversation) or
+    /// [`AgentBuilder::set_auto_title`].
+    pub fn title(&self) -> Option<&str> {
+        self.state.get(TITLE_STATE).and_then(Value::as_str)
+    }
+
+    /// Summary of the conversation, set by
+    /// [`summarize_conversation`](Self::summarize_conversation).
+    pub fn summary(&self) -> Option<&str> {
+        self.state.get(SUMMARY_STATE).and_then(Value::as_str)
+    }
+
+    /// Generate a title for the conversation so far and store it as
+    /// [`title`](Self::title).
+    pub async fn generate_title(&mut self) -> Result<String, AgentError> {
+        Ok(self.summarize_conversation().await?.title)
+    }
+
+    /// Have a sub-agent (see [`StatelessPrebuild::SUMMARY_AGENT`]) title and
+    /// summarize the conversation so far. Both are stored in
+    /// [`state`](Agent::state), under [`TITLE_STATE`] and [`SUMMARY_STATE`],
+    /// so they are saved with it.
+    pub async fn summarize_conversation(&mut self) -> Result<ConversationSummary, AgentError> {
+        let history = self.history.clone();
+        let summary = self.summarize_messages(&history).await?;
+
+        self.state
+            .insert(TITLE_STATE.into(), Value::String(summary.title.clone()));
+        self.state
+            .insert(SUMMARY_STATE.into(), Value::String(summary.summary.clone()));
+        Ok(summary)
+    }
+
+    /// Title and summarize `messages` like
+    /// [`summarize_conversation`](Self::summarize_conversation), without
+    /// storing the result.
+    pub(crate) async fn summarize_messages(
+        &mut self,
+        messages: &[Message],
+    ) -> Result<ConversationSummary, AgentError> {
+        let transcript = transcript(messages);
+        if transcript.is_empty() {
+            return Err(AgentError::Runtime(
+                "There is no conversation to summarize".into(),
+            ));
+        }
+
+        let response = self
+            .ask_helper_agent(
+                StatelessPrebuild::SUMMARY_AGENT,
+                StatelessPrebuild::conversation_summary,
+                transcript,
+            )
+            .await?;
+        ConversationSummary::from_message(&response)
+    }
+
+    /// Send `request` to the agent registered as `name` (see
+    /// [`Agent::resolve`]), or else to the one `builder` returns, built with
+    /// the client and model of this agent, and set up as its sub-agent (see
+    /// [`Agent::invoke_flow_for_tenant`]). Backs the small sub-agents that
+    /// work on this agent's conversation, like the summarizer.
+    pub(crate) async fn ask_helper_agent(
+        &self,
+        name: &str,
+        builder: fn() -> AgentBuilder,
+        request: String,
+    ) -> Result<Message, AgentError> {
+        let mut helper = match self.try_resolve(name).await? {
+            Some(helper) => helper,
+            None => {
+                let (helper, notifications) = builder()
+                    .import_client_config(self.export_client_config())
+                    .set_model(self.model.clone())
+                    .build_with_notification()
+                    .await?;
+                self.forward_notifications(notifications);
+                helper
+            }
+        };
+        self.configure_sub_agent(&mut helper);
+        // a single request instead of the helper's flow, which could e.g.
+        // title itself
+        helper.clear_history();
+        helper.history.push(Message::user(request));
+        let response = InvocationBuilder::default()
+            .use_tools(false)
+            .invoke_with(&mut helper)
+            .await?;
+        Ok(response.message)
+    }
+
+    /// Title the conversation after its first exchange, when
+    /// [`AgentBuilder::set_auto_title`] is on. Failures are only logged.
+    pub(crate) async fn auto_title(&mut self) {
+        let answered = self.history.iter().any(|m| m.role == Role::Assistant);
+        if !self.auto_title || self.title().is_some() || !answered {
+            return;
+        }
+        if let Err(e) = self.generate_title().await {
+            tracing::warn!(agent = self.name.as_str(), error = %e, "Could not title the conversation");
+        }
+    }
+}
+
+/// User and assistant messages as `Role: content` lines. The markers of
+/// earlier history checkpoints are kept, so their summaries carry over.
+fn transcript(messages: &[Message]) -> String {
+    messages
+        .iter()
+        .filter(|m| !m.is_scratch())
+        .filter_map(|m| {
+            let content = m.content.as_deref()?.trim();
+            let role = match m.role {
+                Role::User => "User",
+                Role::Assistant => "Assistant",
+                Role::System if m.get_metadata(CHECKPOINT_METADATA).is_some() => "Earlier",
+                _ => return None,
+            };
+            (!content.is_empty()).then(|| format!("{role}: {content}"))
+        })
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::FlowTestHarness;
+
+    #[tokio::test]
+    async fn conversations_are_titled_after_the_first_exchange() {
+        let builder = AgentBuilder::default()
+            .set_model("test")
+            .set_auto_title(true);
+        let mut harness = FlowTestHarness::new(builder)
+            .await
+            .unwrap()
+            .reply("Rust is a systems programming language.")
+            .reply(r#"{"title": "What Rust is", "summary": "The user asked about Rust."}"#)
+            .reply("It was first released in 2015.");
+
+        harness.run("What is rust?").await.unwrap();
+        assert_eq!(harness.agent().title(), Some("What Rust is"));
+        assert_eq!(
+            harness.agent().summary(),
+            Some("The user asked about Rust.")
+        );
+
+        // later exchanges keep the title
+        harness.run("When was it released?").await.unwrap();
+        assert_eq!(harness.agent().title(), Some("What Rust is"));
+        assert!(harness.requests()[1].messages[1]
+            .content
+            .as_deref()
+            .unwrap()
+            .starts_with("User: What is rust?"));
+    }
+}
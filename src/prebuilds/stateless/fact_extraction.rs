@@ -0,0 +1,378 @@
+use std::{collections::BTreeMap, fmt};
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::{
+    flow, parse_structured_output, prebuilds::StatelessPrebuild, reply_without_tools_flow,
+    services::llm::SchemaSpec, Agent, AgentBuilder, AgentError, AgentOutput, FromMessage, Message,
+    Role,
+};
+
+const FACTS_SYSTEM_PROMPT: &str = r#"You keep track of the facts mentioned in a conversation between a user and an assistant.
+
+You are given the facts known so far and the latest exchange. List the entities (people, places, organizations, projects, things) the exchange mentions, with their attributes, and the relationships between them.
+Only list facts that are stated in the exchange and are new or changed compared to the known facts. Use the names of known entities when the exchange refers to them.
+
+**Output Format:**
+Respond only with a JSON object with the keys:
+- "entities": a list of objects with "name", "kind" (e.g. "person", "place") and "attributes" (an object of short string values),
+- "relationships": a list of objects with "subject", "relation" and "object", where subject and object are entity names.
+Respond with empty lists when there is nothing new.
+"#;
+
+/// Key of the conversation facts in [`Agent::state`].
+pub const FACTS_STATE: &str = "facts";
+
+/// Entities and relationships mentioned in a conversation, maintained by
+/// [`Agent::extract_facts`].
+///
+/// The facts are stored in [`Agent::state`] under [`FACTS_STATE`], so tools
+/// can read them from [`ToolContext::state`](crate::ToolContext), and
+/// templates get them as the `{{facts}}` placeholder (see [`Facts`]'s
+/// `Display`).
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Facts {
+    #[serde(default)]
+    pub entities: Vec<Entity>,
+    #[serde(default)]
+    pub relationships: Vec<Relationship>,
+}
+
+/// Something the conversation is about, e.g. a person or a place.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Entity {
+    pub name: String,
+    /// E.g. `person`, `place` or `organization`.
+    #[serde(default)]
+    pub kind: String,
+    #[serde(default)]
+    pub attributes: BTreeMap<String, String>,
+}
+
+/// `subject relation object`, e.g. `Ana works_at Acme`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Relationship {
+    pub subject: String,
+    pub relation: String,
+    pub object: String,
+}
+
+impl Facts {
+    pub fn is_empty(&self) -> bool {
+        self.entities.is_empty() && self.relationships.is_empty()
+    }
+
+    /// Entity named `name`, ignoring case.
+    pub fn entity(&self, name: &str) -> Option<&Entity> {
+        self.entities
+            .iter()
+            .find(|e| e.name.eq_ignore_ascii_case(name))
+    }
+
+    /// Relationships `name` takes part in, as subject or object.
+    pub fn relationships_of<'a>(
+        &'a self,
+        name: &'a str,
+    ) -> impl Iterator<Item = &'a Relationship> + 'a {
+        self.relationships.iter().filter(move |r| {
+            r.subject.eq_ignore_ascii_case(name) || r.object.eq_ignore_ascii_case(name)
+        })
+    }
+
+    /// Add the facts of `other`. Entities with the same name are merged,
+    /// with the attributes of `other` replacing older values; duplicate
+    /// relationships are dropped.
+    pub fn merge(&mut self, other: Facts) {
+        for entity in other.entities {
+            let existing = self
+                .entities
+                .iter_mut()
+                .find(|e| e.name.eq_ignore_ascii_case(&entity.name));
+            match existing {
+                Some(existing) => {
+                    if !entity.kind.is_empty() {
+                        existing.kind = entity.kind;
+                    }
+                    existing.attributes.extend(entity.attributes);
+                }
+                None => self.entities.push(entity),
+            }
+        }
+        for relationship in other.relationships {
+            let known = self.relationships.iter().any(|r| {
+                r.subject.eq_ignore_ascii_case(&relationship.subject)
+                    && r.relation.eq_ignore_ascii_case(&relationship.relation)
+                    && r.object.eq_ignore_ascii_case(&relationship.object)
+            });
+            if !known {
+                self.relationships.push(relationship);
+            }
+        }
+    }
+}
+
+impl fmt::Display for Facts {
+    /// One line per entity and relationship, e.g. `- Ana (person): city: Koper`
+    /// and `- Ana works_at Acme`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for entity in &self.entities {
+            write!(f, "- {}", entity.name)?;
+            if !entity.kind.is_empty() {
+                write!(f, " ({})", entity.kind)?;
+            }
+            let attributes = entity
+                .attributes
+                .iter()
+                .map(|(key, value)| format!("{key}: {value}"))
+                .collect::<Vec<_>>();
+            if !attributes.is_empty() {
+                write!(f, ": {}", attributes.join(", "))?;
+            }
+            writeln!(f)?;
+        }
+        for r in &self.relationships {
+            writeln!(f, "- {} {} {}", r.subject, r.relation, r.object)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromMessage for Facts {
+    fn from_message(message: &Message) -> Result<Self, AgentError> {
+        parse_structured_output(message)
+    }
+}
+
+impl AgentOutput for Facts {
+    fn response_format() -> SchemaSpec {
+        SchemaSpec::from_value(json!({
+            "type": "object",
+            "properties": {
+                "entities": {
+                    "type": "array",
+                    "items": {
+                        "type": "object",
+                        "properties": {
+                            "name": { "type": "string" },
+                            "kind": { "type": "string" },
+                            "attributes": {
+                                "type": "object",
+                                "additionalProperties": { "type": "string" }
+                            }
+                        },
+                        "required": ["name", "kind", "attributes"]
+                    }
+                },
+                "relationships": {
+                    "type": "array",
+                    "items": {
+                        "type": "object",
+                        "properties": {
+                            "subject": { "type": "string" },
+                            "relation": { "type": "string" },
+                            "object": { "type": "string" }
+                        },
+                        "required": ["subject", "relation", "object"]
+                    }
+                }
+            },
+            "required": ["entities", "relationships"]
+        }))
+        .with_name("facts")
+    }
+}
+
+impl StatelessPrebuild {
+    /// Name under which [`Agent::extract_facts`] looks up the agent
+    /// extracting facts (see [`Agent::resolve`]). Without one, it builds
+    /// [`fact_extraction`](Self::fact_extraction) with the client and model
+    /// of the agent.
+    pub const FACT_AGENT: &'static str = "fact_extraction";
+
+    /// Agent listing the entities and relationships mentioned in an
+    /// exchange, given the facts known so far, in a single request without
+    /// tools.
+    pub fn fact_extraction() -> AgentBuilder {
+        AgentBuilder::default()
+            .set_flow(flow!(reply_without_tools_flow))
+            .set_system_prompt(FACTS_SYSTEM_PROMPT)
+            .set_response_format_output::<Facts>()
+            .set_clear_history_on_invocation(true)
+            .remove_tools()
+            .set_name("Stateless_prebuild-fact_extraction")
+    }
+}
+
+impl Agent {
+    /// Facts of the conversation, maintained by
+    /// [`extract_facts`](Self::extract_facts). `None` until facts are
+    /// extracted.
+    pub fn facts(&self) -> Option<Facts> {
+        self.state
+            .get(FACTS_STATE)
+            .and_then(|facts| serde_json::from_value(facts.clone()).ok())
+    }
+
+    /// Have a sub-agent (see [`StatelessPrebuild::FACT_AGENT`]) extract the
+    /// facts of the latest exchange and merge them into
+    /// [`facts`](Self::facts). Only the exchange and the known facts are
+    /// sent, not the whole history. Returns the updated facts.
+    pub async fn extract_facts(&mut self) -> Result<Facts, AgentError> {
+        self.extract_facts_of(None).await
+    }
+
+    /// Extract the facts of the exchange started by `prompt`, or of the last
+    /// one without a prompt.
+    async fn extract_facts_of(&mut self, prompt: Option<&str>) -> Result<Facts, AgentError> {
+        let exchange = self.latest_exchange(prompt);
+        if exchange.is_empty() {
+            return Err(AgentError::Runtime(
+                "There is no exchange to extract facts from".into(),
+            ));
+        }
+        let mut facts = self.facts().unwrap_or_default();
+
+        let known = if facts.is_empty() {
+            "(none)".to_string()
+        } else {
+            facts.to_string()
+        };
+        let response = self
+            .ask_helper_agent(
+                StatelessPrebuild::FACT_AGENT,
+                StatelessPrebuild::fact_extraction,
+                format!("Known facts:\n{known}\n\nLatest exchange:\n{exchange}"),
+            )
+            .await?;
+        facts.merge(Facts::from_message(&response)?);
+
+        self.state.insert(
+            FACTS_STATE.into(),
+            serde_json::to_value(&facts).unwrap_or(Value::Null),
+        );
+        Ok(facts)
+    }
+
+    /// Extract the facts of an exchange after it, when
+    /// [`AgentBuilder::set_auto_extract_facts`] is on. Failures are only
+    /// logged.
+    pub(crate) async fn auto_extract_facts(&mut self, prompt: &str) {
+        if !self.auto_extract_facts {
+            return;
+        }
+        if let Err(e) = self.extract_facts_of(Some(prompt)).await {
+            tracing::warn!(agent = self.name.as_str(), error = %e, "Could not extract facts");
+        }
+    }
+
+    /// The user message of `prompt` (or else the last one the user sent)
+    /// and the assistant replies after it, as `Role: content` lines. The
+    /// stop prompt is sent by the flow, not the user, so it neither starts
+    /// an exchange nor is part of one.
+    fn latest_exchange(&self, prompt: Option<&str>) -> String {
+        let is_stop_prompt = |m: &Message| {
+            m.role == Role::User && self.stop_prompt.is_some() && m.content == self.stop_prompt
+        };
+        let is_prompt = |m: &Message| {
+            m.role == Role::User
+                && prompt.is_some_and(|prompt| m.content.as_deref() == Some(prompt))
+        };
+        let Some(start) = self.history.iter().rposition(is_prompt).or_else(|| {
+            self.history
+                .iter()
+                .rposition(|m| m.role == Role::User && !is_stop_prompt(m))
+        }) else {
+            return String::new();
+        };
+        self.history[start..]
+            .iter()
+            .filter(|m| matches!(m.role, Role::User | Role::Assistant) && !m.is_scratch())
+            .filter(|m| !is_stop_prompt(m))
+            .filter_map(|m| {
+                let content = m.content.as_deref()?.trim();
+                let role = if m.role == Role::User {
+                    "User"
+                } else {
+                    "Assistant"
+                };
+                (!content.is_empty()).then(|| format!("{role}: {content}"))
+            })
+            .collect::<Vec<_>>()
+            .join("\n\n")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::FlowTestHarness;
+
+    #[tokio::test]
+    async fn facts_are_merged_after_every_exchange() {
+        let builder = AgentBuilder::default()
+            .set_model("test")
+            .set_auto_extract_facts(true);
+        let mut harness = FlowTestHarness::new(builder)
+            .await
+            .unwrap()
+            .reply("Nice to meet you, Ana.")
+            .reply(
+                r#"{"entities": [{"name": "Ana", "kind": "person", "attributes": {"city": "Koper"}}],
+                    "relationships": []}"#,
+            )
+            .reply("Acme sounds like a great place.")
+            .reply(
+                r#"{"entities": [
+                        {"name": "ana", "kind": "", "attributes": {"job": "engineer"}},
+                        {"name": "Acme", "kind": "organization", "attributes": {}}],
+                    "relationships": [{"subject": "Ana", "relation": "works_at", "object": "Acme"}]}"#,
+            );
+
+        harness.run("I'm Ana and I live in Koper.").await.unwrap();
+        harness.run("I work at Acme as an engineer.").await.unwrap();
+
+        let facts = harness.agent().facts().unwrap();
+        let ana = facts.entity("Ana").unwrap();
+        assert_eq!(ana.kind, "person");
+        assert_eq!(ana.attributes["city"], "Koper");
+        assert_eq!(ana.attributes["job"], "engineer");
+        assert_eq!(facts.relationships_of("acme").count(), 1);
+        assert_eq!(
+            facts.to_string(),
+            "- Ana (person): city: Koper, job: engineer\n\
+             - Acme (organization)\n\
+             - Ana works_at Acme\n"
+        );
+
+        // only the known facts and the latest exchange are sent
+        let request = harness.requests()[3].messages[1].content.clone().unwrap();
+        assert!(request.starts_with("Known facts:\n- Ana (person): city: Koper\n"));
+        assert!(request.contains("User: I work at Acme"));
+        assert!(!request.contains("I live in Koper"));
+    }
+
+    #[tokio::test]
+    async fn exchanges_start_at_the_prompt_not_the_stop_prompt() {
+        let builder = AgentBuilder::default()
+            .set_model("test")
+            .set_stop_prompt("Continue, or answer with FINAL ANSWER: <answer>.")
+            .set_stopword("FINAL ANSWER:")
+            .set_auto_extract_facts(true);
+        let mut harness = FlowTestHarness::new(builder)
+            .await
+            .unwrap()
+            .reply_with_tool_call("lookup", json!({ "name": "Ana" }))
+            .expect_tool_call("lookup", "Ana lives in Koper.")
+            .reply("FINAL ANSWER: Ana lives in Koper.")
+            .reply(r#"{"entities": [], "relationships": []}"#);
+
+        harness.run("Where does Ana live?").await.unwrap();
+
+        let request = harness.requests()[2].messages[1].content.clone().unwrap();
+        assert!(request.contains("User: Where does Ana live?"));
+        assert!(request.contains("Assistant: FINAL ANSWER: Ana lives in Koper."));
+        assert!(!request.contains("Continue, or answer"));
+        harness.verify();
+    }
+}
@@ -1,4 +1,8 @@
 pub mod call_tools;
+pub mod conversation_summary;
+pub mod fact_extraction;
 pub mod reply_without_tools;
+pub mod tool_output_summary;
+pub mod vision_describe;
 
 pub struct StatelessPrebuild;
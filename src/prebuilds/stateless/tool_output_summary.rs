@@ -0,0 +1,26 @@
+use crate::{flow, prebuilds::StatelessPrebuild, reply_without_tools_flow, AgentBuilder};
+
+const TOOL_OUTPUT_SUMMARY_SYSTEM_PROMPT: &str = r#"You shorten the output of tools called by an assistant.
+
+Summarize the given output in the requested length. Keep the names, numbers, identifiers and error messages the assistant needs to continue its task, and leave out repetitions and boilerplate.
+Respond only with the summary.
+"#;
+
+impl StatelessPrebuild {
+    /// Name under which agents look up the agent summarizing oversized tool
+    /// outputs (see [`Agent::resolve`](crate::Agent::resolve) and
+    /// [`TruncationStrategy::Summarize`](crate::TruncationStrategy::Summarize)).
+    /// Without one, they build [`tool_output_summary`](Self::tool_output_summary)
+    /// with their client and model.
+    pub const TOOL_OUTPUT_SUMMARY_AGENT: &'static str = "tool_output_summary";
+
+    /// Agent summarizing a tool output in a single request without tools.
+    pub fn tool_output_summary() -> AgentBuilder {
+        AgentBuilder::default()
+            .set_flow(flow!(reply_without_tools_flow))
+            .set_system_prompt(TOOL_OUTPUT_SUMMARY_SYSTEM_PROMPT)
+            .set_clear_history_on_invocation(true)
+            .remove_tools()
+            .set_name("Stateless_prebuild-tool_output_summary")
+    }
+}
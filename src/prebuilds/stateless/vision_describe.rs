@@ -0,0 +1,117 @@
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::{
+    flow, parse_structured_output, prebuilds::StatelessPrebuild, reply_without_tools_flow,
+    services::llm::SchemaSpec, Agent, AgentBuilder, AgentError, AgentOutput, FromMessage,
+    ImageInput, InvocationBuilder, Message, NotificationHandler,
+};
+
+const VISION_SYSTEM_PROMPT: &str = r#"You are an image description assistant. You will be given an image and possibly an instruction about it.
+
+Describe the image accurately. Only describe what is visible, do not guess about things outside of the frame.
+
+**Output Format:**
+Respond only with a JSON object with the keys:
+- "caption": one or two sentences describing the image,
+- "tags": short lowercase keywords for the main objects, scene and style,
+- "text": any text legible in the image, or null if there is none.
+"#;
+
+const DESCRIBE_PROMPT: &str = "Describe this image.";
+
+/// Structured output of the [`StatelessPrebuild::vision_describe`] agent.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ImageDescription {
+    /// One or two sentence description of the image.
+    pub caption: String,
+    /// Keywords for the objects, scene and style.
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Text legible in the image.
+    #[serde(default)]
+    pub text: Option<String>,
+}
+
+impl FromMessage for ImageDescription {
+    fn from_message(message: &Message) -> Result<Self, AgentError> {
+        parse_structured_output(message)
+    }
+}
+
+impl AgentOutput for ImageDescription {
+    fn response_format() -> SchemaSpec {
+        SchemaSpec::from_value(json!({
+            "type": "object",
+            "properties": {
+                "caption": { "type": "string" },
+                "tags": { "type": "array", "items": { "type": "string" } },
+                "text": { "type": ["string", "null"] }
+            },
+            "required": ["caption", "tags", "text"]
+        }))
+        .with_name("image_description")
+    }
+}
+
+impl StatelessPrebuild {
+    /// Agent describing images with a vision model (e.g. `llava` or
+    /// `qwen2.5vl` on Ollama, or an OpenRouter vision model).
+    ///
+    /// Pass images with [`Agent::describe_image`], which returns an
+    /// [`ImageDescription`] with a caption and tags.
+    pub fn vision_describe() -> AgentBuilder {
+        AgentBuilder::default()
+            .set_flow(flow!(reply_without_tools_flow))
+            .set_system_prompt(VISION_SYSTEM_PROMPT)
+            .set_response_format_output::<ImageDescription>()
+            .set_clear_history_on_invocation(true)
+            .remove_tools()
+            .set_name("Stateless_prebuild-vision_describe")
+    }
+}
+
+impl Agent {
+    /// Caption and tag an image, given as a file path, raw bytes or base64.
+    ///
+    /// Meant for agents built with [`StatelessPrebuild::vision_describe`].
+    pub async fn describe_image(
+        &mut self,
+        image: impl Into<ImageInput>,
+    ) -> Result<ImageDescription, AgentError> {
+        self.describe_images(DESCRIBE_PROMPT, [image]).await
+    }
+
+    /// Like [`describe_image`](Self::describe_image), with several images
+    /// and a custom instruction, e.g. to focus on a detail.
+    pub async fn describe_images<I, T>(
+        &mut self,
+        instruction: impl Into<String>,
+        images: I,
+    ) -> Result<ImageDescription, AgentError>
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<ImageInput>,
+    {
+        let instruction = instruction.into();
+        let mut message = Message::user(instruction.clone());
+        for image in images {
+            message = message
+                .with_image(image)
+                .map_err(|e| AgentError::Runtime(format!("Could not read image: {e}")))?;
+        }
+
+        if self.clear_history_on_invoke {
+            self.clear_history();
+        }
+        self.history.push(message);
+
+        let response = InvocationBuilder::default()
+            .use_tools(false)
+            .invoke_with(self)
+            .await?;
+        let message = self.moderate(&instruction, response.message).await?;
+        self.notify_done(true, message.content.clone()).await;
+        ImageDescription::from_message(&message)
+    }
+}
@@ -0,0 +1,292 @@
+//! Python bindings, enabled with the `pyo3` feature.
+//!
+//! The `reagent` Python module exposes `AgentBuilder`, `Agent` and `Tool`.
+//! Agents run on a [`blocking::Agent`](crate::blocking::Agent); the GIL is
+//! released while a flow runs, so Python tools can be called from it.
+//!
+//! To build a wheel, create a `cdylib` crate (e.g. with maturin) that depends
+//! on `reagent-rs` with the `pyo3` feature and re-exports this module:
+//!
+//! ```ignore
+//! pub use reagent_rs::python::reagent;
+//! ```
+//!
+//! ```python
+//! import reagent
+//!
+//! def get_weather(city):
+//!     return f"Sunny in {city}"
+//!
+//! weather = reagent.Tool(
+//!     "get_weather",
+//!     "Current weather in a city",
+//!     get_weather,
+//!     properties=[("city", "string", "Name of the city")],
+//!     required=["city"],
+//! )
+//! agent = (
+//!     reagent.AgentBuilder()
+//!     .set_model("qwen3:0.6b")
+//!     .add_tool(weather)
+//!     .build()
+//! )
+//! print(agent.invoke("What is the weather in Koper?"))
+//! ```
+
+use std::sync::Arc;
+
+use pyo3::{create_exception, exceptions::PyException, prelude::*, types::PyDict};
+use serde_json::Value;
+
+use crate::{blocking, McpServerType, Provider, ToolBuilder, ToolExecutionError};
+
+create_exception!(reagent, ReagentError, PyException);
+
+fn to_py_err(e: impl std::fmt::Display) -> PyErr {
+    ReagentError::new_err(e.to_string())
+}
+
+fn json_to_py(py: Python<'_>, value: &Value) -> PyResult<PyObject> {
+    let json = py.import_bound("json")?;
+    Ok(json.call_method1("loads", (value.to_string(),))?.unbind())
+}
+
+/// Call `func` with the tool arguments as keyword arguments and return its
+/// result as a string.
+fn call_python(func: &PyObject, arguments: &Value) -> Result<String, ToolExecutionError> {
+    Python::with_gil(|py| {
+        let kwargs = json_to_py(py, arguments)?
+            .into_bound(py)
+            .downcast_into::<PyDict>()
+            .ok();
+        let result = func.call_bound(py, (), kwargs.as_ref())?;
+        Ok(result.bind(py).str()?.to_string())
+    })
+    .map_err(|e: PyErr| ToolExecutionError::ExecutionFailed(e.to_string()))
+}
+
+/// A tool executed by a Python callable.
+#[pyclass(name = "Tool")]
+#[derive(Clone)]
+pub struct PyTool {
+    tool: crate::Tool,
+}
+
+#[pymethods]
+impl PyTool {
+    /// `properties` lists the parameters as `(name, type, description)`.
+    #[new]
+    #[pyo3(signature = (name, description, func, properties = None, required = None))]
+    fn new(
+        name: String,
+        description: String,
+        func: PyObject,
+        properties: Option<Vec<(String, String, String)>>,
+        required: Option<Vec<String>>,
+    ) -> PyResult<Self> {
+        let required = required.unwrap_or_default();
+        let mut builder = ToolBuilder::new()
+            .function_name(name)
+            .function_description(description);
+        for (name, property_type, description) in properties.unwrap_or_default() {
+            builder = if required.contains(&name) {
+                builder.add_required_property(name, property_type, description)
+            } else {
+                builder.add_property(name, property_type, description)
+            };
+        }
+
+        let func = Arc::new(func);
+        let tool = builder
+            .executor_fn(move |arguments: Value| {
+                let func = func.clone();
+                async move {
+                    // Python code may block, keep it off the async workers
+                    tokio::task::spawn_blocking(move || call_python(&func, &arguments))
+                        .await
+                        .map_err(|e| ToolExecutionError::ExecutionFailed(e.to_string()))?
+                }
+            })
+            .build()
+            .map_err(to_py_err)?;
+        Ok(Self { tool })
+    }
+
+    #[getter]
+    fn name(&self) -> String {
+        self.tool.function.name.clone()
+    }
+}
+
+/// Builder for [`Agent`](PyAgent), see [`AgentBuilder`](crate::AgentBuilder).
+#[pyclass(name = "AgentBuilder")]
+pub struct PyAgentBuilder {
+    builder: Option<crate::AgentBuilder>,
+}
+
+impl PyAgentBuilder {
+    fn update(
+        &mut self,
+        f: impl FnOnce(crate::AgentBuilder) -> crate::AgentBuilder,
+    ) -> PyResult<()> {
+        let builder = self.take()?;
+        self.builder = Some(f(builder));
+        Ok(())
+    }
+
+    fn take(&mut self) -> PyResult<crate::AgentBuilder> {
+        self.builder
+            .take()
+            .ok_or_else(|| ReagentError::new_err("the builder was already built"))
+    }
+}
+
+#[pymethods]
+impl PyAgentBuilder {
+    #[new]
+    fn new() -> Self {
+        Self {
+            builder: Some(crate::AgentBuilder::default()),
+        }
+    }
+
+    fn set_name(mut slf: PyRefMut<'_, Self>, name: String) -> PyResult<PyRefMut<'_, Self>> {
+        slf.update(|b| b.set_name(name))?;
+        Ok(slf)
+    }
+
+    fn set_model(mut slf: PyRefMut<'_, Self>, model: String) -> PyResult<PyRefMut<'_, Self>> {
+        slf.update(|b| b.set_model(model))?;
+        Ok(slf)
+    }
+
+    fn set_system_prompt(
+        mut slf: PyRefMut<'_, Self>,
+        prompt: String,
+    ) -> PyResult<PyRefMut<'_, Self>> {
+        slf.update(|b| b.set_system_prompt(prompt))?;
+        Ok(slf)
+    }
+
+    /// One of `ollama`, `openai`, `openrouter`, `mistral` or `anthropic`.
+    fn set_provider(mut slf: PyRefMut<'_, Self>, provider: String) -> PyResult<PyRefMut<'_, Self>> {
+        let provider = match provider.to_ascii_lowercase().as_str() {
+            "ollama" => Provider::Ollama,
+            "openai" => Provider::OpenAi,
+            "openrouter" => Provider::OpenRouter,
+            "mistral" => Provider::Mistral,
+            "anthropic" => Provider::Anthropic,
+            _ => {
+                return Err(ReagentError::new_err(format!(
+                    "Unknown provider `{provider}`"
+                )))
+            }
+        };
+        slf.update(|b| b.set_provider(provider))?;
+        Ok(slf)
+    }
+
+    fn set_base_url(mut slf: PyRefMut<'_, Self>, url: String) -> PyResult<PyRefMut<'_, Self>> {
+        slf.update(|b| b.set_base_url(url))?;
+        Ok(slf)
+    }
+
+    fn set_api_key(mut slf: PyRefMut<'_, Self>, key: String) -> PyResult<PyRefMut<'_, Self>> {
+        slf.update(|b| b.set_api_key(key))?;
+        Ok(slf)
+    }
+
+    fn set_temperature(
+        mut slf: PyRefMut<'_, Self>,
+        temperature: f32,
+    ) -> PyResult<PyRefMut<'_, Self>> {
+        slf.update(|b| b.set_temperature(temperature))?;
+        Ok(slf)
+    }
+
+    fn add_tool<'py>(
+        mut slf: PyRefMut<'py, Self>,
+        tool: PyRef<'_, PyTool>,
+    ) -> PyResult<PyRefMut<'py, Self>> {
+        let tool = tool.tool.clone();
+        slf.update(|b| b.add_tool(tool))?;
+        Ok(slf)
+    }
+
+    /// `transport` is `streamable_http` (default), `sse`, or `stdio`, in
+    /// which case `target` is the command line to start the server.
+    #[pyo3(signature = (target, transport = "streamable_http"))]
+    fn add_mcp_server<'py>(
+        mut slf: PyRefMut<'py, Self>,
+        target: String,
+        transport: &str,
+    ) -> PyResult<PyRefMut<'py, Self>> {
+        let server = match transport {
+            "streamable_http" => McpServerType::streamable_http(target),
+            "sse" => McpServerType::sse(target),
+            "stdio" => McpServerType::stdio(target),
+            _ => {
+                return Err(ReagentError::new_err(format!(
+                    "Unknown MCP transport `{transport}`"
+                )))
+            }
+        };
+        slf.update(|b| b.add_mcp_server(server))?;
+        Ok(slf)
+    }
+
+    fn build(&mut self, py: Python<'_>) -> PyResult<PyAgent> {
+        let builder = self.take()?;
+        let agent = py
+            .allow_threads(|| blocking::Agent::build(builder))
+            .map_err(to_py_err)?;
+        Ok(PyAgent { agent })
+    }
+}
+
+/// An agent driven from Python, see [`blocking::Agent`].
+#[pyclass(name = "Agent")]
+pub struct PyAgent {
+    agent: blocking::Agent,
+}
+
+#[pymethods]
+impl PyAgent {
+    /// Run the flow and return the content of the reply.
+    fn invoke(&mut self, py: Python<'_>, prompt: String) -> PyResult<Option<String>> {
+        let agent = &mut self.agent;
+        let reply = py
+            .allow_threads(|| agent.invoke_flow(prompt))
+            .map_err(to_py_err)?;
+        Ok(reply.content)
+    }
+
+    /// Run the flow and return the reply parsed as JSON.
+    fn invoke_structured(&mut self, py: Python<'_>, prompt: String) -> PyResult<PyObject> {
+        let agent = &mut self.agent;
+        let value: Value = py
+            .allow_threads(|| agent.invoke_flow_structured_output(prompt))
+            .map_err(to_py_err)?;
+        json_to_py(py, &value)
+    }
+
+    /// The conversation as a list of message dicts.
+    fn history(&self, py: Python<'_>) -> PyResult<PyObject> {
+        let history = serde_json::to_value(&self.agent.inner().history).map_err(to_py_err)?;
+        json_to_py(py, &history)
+    }
+
+    fn clear_history(&mut self) {
+        self.agent.inner_mut().clear_history();
+    }
+}
+
+/// The `reagent` Python module.
+#[pymodule]
+pub fn reagent(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<PyAgentBuilder>()?;
+    m.add_class::<PyAgent>()?;
+    m.add_class::<PyTool>()?;
+    m.add("ReagentError", m.py().get_type_bound::<ReagentError>())?;
+    Ok(())
+}
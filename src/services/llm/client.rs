@@ -1,25 +1,30 @@
 use std::{pin::Pin, sync::Arc};
 
 use futures::Stream;
+use serde::{Deserialize, Serialize};
 
 use crate::{
     services::llm::{
+        message::{prefix_sender_names, Message},
         models::{
             chat::{ChatRequest, ChatResponse, ChatStreamChunk},
-            embedding::{EmbeddingsRequest, EmbeddingsResponse},
+            embedding::{EmbedRequest, EmbedResponse, EmbeddingsRequest, EmbeddingsResponse},
             errors::InferenceClientError,
         },
-        SchemaSpec, StructuredOuputFormat,
+        RequestLog, SchemaSpec, StructuredOuputFormat,
     },
     ClientConfig,
 };
 
+#[cfg(any(test, feature = "testing"))]
+use super::providers::scripted::ScriptedClient;
 use super::providers::{
     anthropic::AnthropicClient, mistral::MistralClient, ollama::OllamaClient, openai::OpenAiClient,
     openrouter::OpenRouterClient,
 };
 
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum Provider {
     #[default]
     Ollama,
@@ -36,6 +41,8 @@ enum ClientInner {
     Mistral(MistralClient),
     Anthropic(AnthropicClient),
     OpenRouter(OpenRouterClient),
+    #[cfg(any(test, feature = "testing"))]
+    Scripted(ScriptedClient),
 }
 
 #[derive(Clone, Debug)]
@@ -63,14 +70,84 @@ impl InferenceClient {
         }
     }
 
+    /// Apply the configured [`MessageRewriter`](super::MessageRewriter), name
+    /// the senders of user messages in their content for providers without a
+    /// `name` field, and mask personal data with the [`Redactor`](super::Redactor).
+    fn rewrite(&self, mut req: ChatRequest) -> ChatRequest {
+        self.rewrite_messages(&req.base.model, &mut req.messages);
+        if let Some(redactor) = &self.config.redactor {
+            redactor.mask_messages(&mut req.messages);
+        }
+        req
+    }
+
+    /// `messages` as the provider would receive them when sent to `model`,
+    /// without recording masked values, see [`Agent::history_view`](crate::Agent::history_view).
+    pub(crate) fn preview_messages(&self, model: &str, messages: &mut Vec<Message>) {
+        self.rewrite_messages(model, messages);
+        if let Some(redactor) = &self.config.redactor {
+            redactor.preview_messages(messages);
+        }
+    }
+
+    fn rewrite_messages(&self, model: &str, messages: &mut Vec<Message>) {
+        if let (Some(rewriter), Some(provider)) =
+            (&self.config.message_rewriter, &self.config.provider)
+        {
+            rewriter.rewrite(provider, model, messages);
+        }
+        if !matches!(
+            &*self.inner,
+            ClientInner::OpenAi(_) | ClientInner::OpenRouter(_)
+        ) {
+            prefix_sender_names(messages);
+        }
+    }
+
+    /// Response format asking for any JSON output, for providers with a
+    /// JSON mode.
+    pub fn json_mode_format(&self) -> Option<serde_json::Value> {
+        match self.get_config().provider {
+            Some(Provider::Ollama) => Some(serde_json::json!("json")),
+            Some(Provider::OpenAi) | Some(Provider::OpenRouter) => {
+                Some(serde_json::json!({ "type": "json_object" }))
+            }
+            _ => None,
+        }
+    }
+
+    /// Start the [`RequestLog`] of `req`, see [`RequestLogging`](super::RequestLogging).
+    fn request_log(&self, req: &ChatRequest) -> RequestLog {
+        RequestLog::start(
+            self.config.request_logging.as_ref(),
+            self.config.provider.clone(),
+            &req.base.model,
+            req,
+        )
+    }
+
     pub async fn chat(&self, req: ChatRequest) -> Result<ChatResponse, InferenceClientError> {
-        match &*self.inner {
-            ClientInner::Ollama(c) => c.chat(req).await,
-            ClientInner::OpenAi(c) => c.chat(req).await,
-            ClientInner::Mistral(c) => c.chat(req).await,
-            ClientInner::Anthropic(c) => c.chat(req).await,
-            ClientInner::OpenRouter(c) => c.chat(req).await,
+        let req = self.rewrite(req);
+        let log = self.request_log(&req);
+        let response = log
+            .scope(async {
+                match &*self.inner {
+                    ClientInner::Ollama(c) => c.chat(req).await,
+                    ClientInner::OpenAi(c) => c.chat(req).await,
+                    ClientInner::Mistral(c) => c.chat(req).await,
+                    ClientInner::Anthropic(c) => c.chat(req).await,
+                    ClientInner::OpenRouter(c) => c.chat(req).await,
+                    #[cfg(any(test, feature = "testing"))]
+                    ClientInner::Scripted(c) => c.chat(req).await,
+                }
+            })
+            .await;
+        log.finish(&response);
+        let mut response = response?;
+        if let Some(redactor) = &self.config.redactor {
+            redactor.unmask_message(&mut response.message);
         }
+        Ok(response)
     }
 
     pub async fn chat_stream(
@@ -80,13 +157,32 @@ impl InferenceClient {
         Pin<Box<dyn Stream<Item = Result<ChatStreamChunk, InferenceClientError>> + Send + 'static>>,
         InferenceClientError,
     > {
-        match &*self.inner {
-            ClientInner::Ollama(c) => c.chat_stream(req).await,
-            ClientInner::OpenAi(c) => c.chat_stream(req).await,
-            ClientInner::Mistral(c) => c.chat_stream(req).await,
-            ClientInner::Anthropic(c) => c.chat_stream(req).await,
-            ClientInner::OpenRouter(c) => c.chat_stream(req).await,
-        }
+        let req = self.rewrite(req);
+        let log = self.request_log(&req);
+        let stream = log
+            .scope(async {
+                match &*self.inner {
+                    ClientInner::Ollama(c) => c.chat_stream(req).await,
+                    ClientInner::OpenAi(c) => c.chat_stream(req).await,
+                    ClientInner::Mistral(c) => c.chat_stream(req).await,
+                    ClientInner::Anthropic(c) => c.chat_stream(req).await,
+                    ClientInner::OpenRouter(c) => c.chat_stream(req).await,
+                    #[cfg(any(test, feature = "testing"))]
+                    ClientInner::Scripted(c) => c.chat_stream(req).await,
+                }
+            })
+            .await;
+        let stream = match stream {
+            Ok(stream) => log.track_stream(stream),
+            Err(e) => {
+                log.fail(&e);
+                return Err(e);
+            }
+        };
+        Ok(match &self.config.redactor {
+            Some(redactor) => redactor.unmask_stream(stream),
+            None => stream,
+        })
     }
 
     pub async fn embeddings(
@@ -99,6 +195,45 @@ impl InferenceClient {
             ClientInner::Mistral(c) => c.embeddings(req).await,
             ClientInner::Anthropic(c) => c.embeddings(req).await,
             ClientInner::OpenRouter(c) => c.embeddings(req).await,
+            #[cfg(any(test, feature = "testing"))]
+            ClientInner::Scripted(c) => c.embeddings(req).await,
+        }
+    }
+
+    /// Embed several inputs in one request.
+    pub async fn embed(&self, req: EmbedRequest) -> Result<EmbedResponse, InferenceClientError> {
+        match &*self.inner {
+            ClientInner::Ollama(c) => c.embed(req).await,
+            ClientInner::OpenAi(c) => c.embed(req).await,
+            ClientInner::Mistral(c) => c.embed(req).await,
+            ClientInner::Anthropic(c) => c.embed(req).await,
+            ClientInner::OpenRouter(c) => c.embed(req).await,
+            #[cfg(any(test, feature = "testing"))]
+            ClientInner::Scripted(c) => c.embed(req).await,
+        }
+    }
+
+    /// Context length `model` supports, if the provider tells. Only Ollama
+    /// does, other providers return `None`.
+    pub async fn context_length(&self, model: &str) -> Result<Option<u32>, InferenceClientError> {
+        match &*self.inner {
+            ClientInner::Ollama(c) => c.context_length(model).await,
+            _ => Ok(None),
+        }
+    }
+
+    /// Free the memory `model` takes on the server. Only Ollama loads
+    /// models on demand, other providers return
+    /// [`InferenceClientError::Unsupported`].
+    pub async fn unload(&self, model: &str) -> Result<(), InferenceClientError> {
+        match &*self.inner {
+            ClientInner::Ollama(c) => c.unload(model).await,
+            #[cfg(any(test, feature = "testing"))]
+            ClientInner::Scripted(c) => c.unload(model).await,
+            _ => Err(InferenceClientError::Unsupported(format!(
+                "{:?} does not unload models",
+                self.config.provider.clone().unwrap_or_default()
+            ))),
         }
     }
 }
@@ -111,6 +246,13 @@ impl TryFrom<ClientConfig> for InferenceClient {
         let Some(provider) = cfg.provider.clone() else {
             return Err(InferenceClientError::Config("Provider not defined".into()));
         };
+        #[cfg(any(test, feature = "testing"))]
+        if let Some(script) = cfg.script.clone() {
+            return Ok(Self {
+                config,
+                inner: Arc::new(ClientInner::Scripted(ScriptedClient::new(script))),
+            });
+        }
         let inner = match provider {
             Provider::Ollama => ClientInner::Ollama(OllamaClient::new(cfg)?),
             Provider::OpenAi => ClientInner::OpenAi(OpenAiClient::new(cfg)?),
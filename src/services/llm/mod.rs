@@ -2,7 +2,19 @@ pub mod client;
 pub mod client_config;
 pub mod models;
 pub mod providers;
+pub mod redactor;
+mod request_logging;
+pub mod rewriter;
+mod secret;
+mod tool_pairing;
 
 pub use client::{InferenceClient, Provider};
 pub use client_config::*;
 pub use models::*;
+pub use redactor::Redactor;
+pub use request_logging::RequestLogging;
+pub(crate) use request_logging::{payload_sampled, span_content, RequestLog};
+pub use rewriter::{MessageRewriter, ToolResultsAsUser};
+pub use secret::SecretString;
+pub(crate) use tool_pairing::assign_tool_call_ids;
+pub use tool_pairing::repair_tool_call_pairing;
@@ -1,5 +1,7 @@
 use serde::{Deserialize, Serialize, Serializer};
-use serde_json::Value;
+use serde_json::{Map, Value};
+
+use super::errors::InferenceClientError;
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 #[serde(rename_all = "lowercase")]
@@ -16,6 +18,10 @@ pub struct BaseRequest {
     pub model: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub format: Option<Value>,
+    /// GBNF grammar constraining the output, for Ollama backends that
+    /// support grammars.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub grammar: Option<String>,
 
     #[serde(flatten)]
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -56,6 +62,11 @@ pub struct InferenceOptions {
     pub presence_penalty: Option<f32>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub frequency_penalty: Option<f32>,
+    /// Provider-specific parameters without a field of their own, e.g.
+    /// `top_a` for OpenRouter or `num_gpu` for Ollama. They are added to the
+    /// request body as is and take precedence over fields of the same name.
+    #[serde(default, flatten)]
+    pub extra_options: Map<String, Value>,
 }
 
 impl InferenceOptions {
@@ -73,6 +84,7 @@ impl InferenceOptions {
             && self.min_p.is_none()
             && self.presence_penalty.is_none()
             && self.frequency_penalty.is_none()
+            && self.extra_options.is_empty()
     }
 
     pub fn into_option(self) -> Option<Self> {
@@ -84,6 +96,8 @@ impl InferenceOptions {
     }
 
     pub fn merge_over(self, defaults: Self) -> Self {
+        let mut extra_options = defaults.extra_options;
+        extra_options.extend(self.extra_options);
         Self {
             num_ctx: self.num_ctx.or(defaults.num_ctx),
             repeat_last_n: self.repeat_last_n.or(defaults.repeat_last_n),
@@ -98,10 +112,25 @@ impl InferenceOptions {
             min_p: self.min_p.or(defaults.min_p),
             presence_penalty: self.presence_penalty.or(defaults.presence_penalty),
             frequency_penalty: self.frequency_penalty.or(defaults.frequency_penalty),
+            extra_options,
         }
     }
 }
 
+/// Serialize a provider request body with `extra_options` added as
+/// top-level fields, see [`InferenceOptions::extra_options`].
+pub(crate) fn body_with_extra_options<B: Serialize>(
+    body: &B,
+    extra_options: &Map<String, Value>,
+) -> Result<Value, InferenceClientError> {
+    let mut value = serde_json::to_value(body)
+        .map_err(|e| InferenceClientError::Serialization(e.to_string()))?;
+    if let Value::Object(fields) = &mut value {
+        fields.extend(extra_options.clone());
+    }
+    Ok(value)
+}
+
 fn serialize_options_as_map<S>(
     options: &Option<InferenceOptions>,
     serializer: S,
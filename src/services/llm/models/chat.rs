@@ -1,17 +1,39 @@
 use serde::{Deserialize, Serialize};
 
 use crate::{
-    services::llm::{message::Message, models::base::BaseRequest},
-    Agent, Tool,
+    services::llm::{
+        message::{serialize_without_metadata, Message},
+        models::base::BaseRequest,
+    },
+    Agent, Tool, ToolChoice,
 };
 
 #[derive(Serialize, Debug, Clone, Deserialize)]
 pub struct ChatRequest {
     #[serde(flatten)]
     pub base: BaseRequest,
+    #[serde(serialize_with = "serialize_without_metadata")]
     pub messages: Vec<Message>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub tools: Option<Vec<Tool>>,
+    /// Not part of the Ollama request body, see [`ChatRequest::emulate_tool_choice`].
+    #[serde(skip)]
+    pub tool_choice: Option<ToolChoice>,
+    /// Whether the model may request several tool calls in one response.
+    /// Only sent to providers that support the flag.
+    #[serde(skip)]
+    pub parallel_tool_calls: Option<bool>,
+}
+
+impl ChatRequest {
+    /// Apply `tool_choice` by narrowing `tools`, for providers that have no
+    /// `tool_choice` field of their own.
+    pub(crate) fn emulate_tool_choice(mut self) -> Self {
+        if let Some(choice) = self.tool_choice.take() {
+            self.tools = choice.restrict_tools(self.tools.take());
+        }
+        self
+    }
 }
 
 impl From<&Agent> for ChatRequest {
@@ -20,12 +42,15 @@ impl From<&Agent> for ChatRequest {
             base: BaseRequest {
                 model: val.model.clone(),
                 format: val.response_format.clone(),
+                grammar: val.grammar.clone(),
                 options: val.inference_options().into_option(),
                 stream: Some(val.stream),
                 keep_alive: val.keep_alive.clone(),
             },
             messages: val.history.clone(),
             tools: val.tools.clone(),
+            tool_choice: val.tool_choice.clone(),
+            parallel_tool_calls: val.parallel_tool_calls,
         }
     }
 }
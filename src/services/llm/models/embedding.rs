@@ -15,3 +15,104 @@ pub struct EmbeddingsRequest {
 pub struct EmbeddingsResponse {
     pub embedding: Vec<f64>,
 }
+
+/// Batch embedding request (Ollama `/api/embed`, OpenAI `/embeddings`).
+#[derive(Serialize, Debug, Clone)]
+pub struct EmbedRequest {
+    pub model: String,
+    pub input: Vec<String>,
+    /// Truncate inputs that exceed the context length instead of failing.
+    /// Ollama only, defaults to true there.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub truncate: Option<bool>,
+    /// Size of the returned vectors, for models that support shortening them.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dimensions: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub options: Option<HashMap<String, serde_json::Value>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub keep_alive: Option<String>,
+}
+
+impl EmbedRequest {
+    pub fn new<I, S>(model: impl Into<String>, input: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        Self {
+            model: model.into(),
+            input: input.into_iter().map(Into::into).collect(),
+            truncate: None,
+            dimensions: None,
+            options: None,
+            keep_alive: None,
+        }
+    }
+
+    pub fn truncate(mut self, truncate: bool) -> Self {
+        self.truncate = Some(truncate);
+        self
+    }
+
+    pub fn dimensions(mut self, dimensions: u32) -> Self {
+        self.dimensions = Some(dimensions);
+        self
+    }
+}
+
+/// One embedding per input, in input order.
+#[derive(Deserialize, Debug, Clone)]
+pub struct EmbedResponse {
+    #[serde(default)]
+    pub model: String,
+    pub embeddings: Vec<Vec<f64>>,
+    #[serde(default)]
+    pub total_duration: Option<u64>,
+    #[serde(default)]
+    pub load_duration: Option<u64>,
+    #[serde(default)]
+    pub prompt_eval_count: Option<u32>,
+}
+
+impl EmbedResponse {
+    /// Length of the returned vectors, if any were returned.
+    pub fn dimensions(&self) -> Option<usize> {
+        self.embeddings.first().map(Vec::len)
+    }
+}
+
+impl From<EmbeddingsRequest> for EmbedRequest {
+    fn from(req: EmbeddingsRequest) -> Self {
+        Self {
+            model: req.model,
+            input: vec![req.input],
+            truncate: None,
+            dimensions: None,
+            options: req.options,
+            keep_alive: req.keep_alive,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn embed_request_serializes_batch_input() {
+        let request = EmbedRequest::new("nomic-embed-text", ["a", "b"])
+            .truncate(false)
+            .dimensions(256);
+
+        assert_eq!(
+            serde_json::to_value(&request).unwrap(),
+            serde_json::json!({
+                "model": "nomic-embed-text",
+                "input": ["a", "b"],
+                "truncate": false,
+                "dimensions": 256
+            })
+        );
+    }
+}
@@ -2,12 +2,16 @@
 ///
 /// These errors typically arise when building requests, communicating with the
 /// API, serializing/deserializing payloads, or due to misconfiguration.
+/// New variants may be added, so matches need a wildcard arm.
 #[derive(Debug)]
+#[non_exhaustive]
 pub enum InferenceClientError {
     /// Failure constructing or sending a request (e.g. network issue).
     Request(String),
     /// Error returned directly from the model provider’s API.
     Api(String),
+    /// Error returned by the provider’s API with a non-success HTTP status code.
+    Status(u16, String),
     /// Failure serializing a request or deserializing a response.
     Serialization(String),
     /// Invalid or missing client configuration.
@@ -16,11 +20,22 @@ pub enum InferenceClientError {
     Unsupported(String),
 }
 
+impl InferenceClientError {
+    /// HTTP status code of a [`Status`](Self::Status) error.
+    pub fn status(&self) -> Option<u16> {
+        match self {
+            InferenceClientError::Status(status, _) => Some(*status),
+            _ => None,
+        }
+    }
+}
+
 impl std::fmt::Display for InferenceClientError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             InferenceClientError::Request(s) => write!(f, "Request Error: {s}"),
             InferenceClientError::Api(s) => write!(f, "API Error: {s}"),
+            InferenceClientError::Status(_, s) => write!(f, "API Error: {s}"),
             InferenceClientError::Serialization(s) => write!(f, "Serialization Error: {s}"),
             InferenceClientError::Config(s) => write!(f, "Config Error: {s}"),
             InferenceClientError::Unsupported(s) => write!(f, "Unsupported: {s}"),
@@ -0,0 +1,313 @@
+use serde_json::Value;
+
+const WS: &str = r#"[ \t\n]*"#;
+const PRIMITIVES: &[(&str, &str)] = &[
+    ("ws", WS),
+    (
+        "string",
+        r#""\"" ( [^"\\\x7F\x00-\x1F] | "\\" ( ["\\/bfnrt] | "u" [0-9a-fA-F] [0-9a-fA-F] [0-9a-fA-F] [0-9a-fA-F] ) )* "\"" ws"#,
+    ),
+    (
+        "number",
+        r#""-"? ( [0-9] | [1-9] [0-9]* ) ( "." [0-9]+ )? ( [eE] [-+]? [0-9]+ )? ws"#,
+    ),
+    ("integer", r#""-"? ( [0-9] | [1-9] [0-9]* ) ws"#),
+    ("boolean", r#"( "true" | "false" ) ws"#),
+    ("null", r#""null" ws"#),
+    (
+        "value",
+        r#"( object | array | string | number | boolean | null )"#,
+    ),
+    (
+        "object",
+        r#""{" ws ( string ":" ws value ( "," ws string ":" ws value )* )? "}" ws"#,
+    ),
+    ("array", r#""[" ws ( value ( "," ws value )* )? "]" ws"#),
+];
+
+/// Derive a GBNF grammar from a JSON Schema, for backends that constrain
+/// sampling with grammars.
+///
+/// Supports `type` (single or array), `properties` (emitted in declaration
+/// order, optional ones after the required ones), `items`, `enum`, `const`
+/// and `anyOf`/`oneOf`. Schemas without a `type` accept any JSON value.
+/// `$ref` is not supported, inline the referenced schema instead.
+pub(crate) fn grammar_from_schema(schema: &Value) -> Result<String, String> {
+    let mut grammar = GrammarWriter::default();
+    let root = grammar.visit(schema, "root")?;
+    if root != "root" {
+        grammar.add_rule("root", root);
+    }
+    Ok(grammar.render())
+}
+
+#[derive(Default)]
+struct GrammarWriter {
+    rules: Vec<(String, String)>,
+    primitives: Vec<&'static str>,
+}
+
+impl GrammarWriter {
+    /// Rule body (or rule reference) matching `schema`.
+    fn visit(&mut self, schema: &Value, name: &str) -> Result<String, String> {
+        let Some(node) = schema.as_object() else {
+            return match schema {
+                Value::Bool(true) => Ok(self.primitive("value")),
+                _ => Err(format!("`{name}` is not a schema object")),
+            };
+        };
+
+        if node.contains_key("$ref") {
+            return Err(format!(
+                "`{name}` uses `$ref`, which is not supported; inline the referenced schema"
+            ));
+        }
+
+        if let Some(value) = node.get("const") {
+            self.primitive("ws");
+            return Ok(format!("{} ws", literal(value)));
+        }
+
+        if let Some(Value::Array(values)) = node.get("enum") {
+            self.primitive("ws");
+            let alternatives: Vec<String> = values.iter().map(literal).collect();
+            return Ok(self.add_rule(name, format!("( {} ) ws", alternatives.join(" | "))));
+        }
+
+        if let Some(Value::Array(members)) = node.get("anyOf").or_else(|| node.get("oneOf")) {
+            let alternatives = members
+                .iter()
+                .enumerate()
+                .map(|(i, member)| self.visit(member, &format!("{name}-{i}")))
+                .collect::<Result<Vec<_>, _>>()?;
+            return Ok(self.add_rule(name, alternatives.join(" | ")));
+        }
+
+        match node.get("type") {
+            Some(Value::String(ty)) => self.visit_type(node, ty, name),
+            Some(Value::Array(types)) => {
+                let alternatives = types
+                    .iter()
+                    .map(|ty| match ty.as_str() {
+                        Some(ty) => self.visit_type(node, ty, &format!("{name}-{ty}")),
+                        None => Err(format!("`{name}` has a non-string `type`")),
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok(self.add_rule(name, alternatives.join(" | ")))
+            }
+            _ => Ok(self.primitive("value")),
+        }
+    }
+
+    fn visit_type(
+        &mut self,
+        node: &serde_json::Map<String, Value>,
+        ty: &str,
+        name: &str,
+    ) -> Result<String, String> {
+        match ty {
+            "string" => Ok(self.primitive("string")),
+            "number" => Ok(self.primitive("number")),
+            "integer" => Ok(self.primitive("integer")),
+            "boolean" => Ok(self.primitive("boolean")),
+            "null" => Ok(self.primitive("null")),
+            "array" => match node.get("items") {
+                Some(items) => {
+                    let item = self.visit(items, &format!("{name}-item"))?;
+                    self.primitive("ws");
+                    Ok(self.add_rule(
+                        name,
+                        format!(r#""[" ws ( {item} ( "," ws {item} )* )? "]" ws"#),
+                    ))
+                }
+                None => Ok(self.primitive("array")),
+            },
+            "object" => match node.get("properties") {
+                Some(Value::Object(properties)) if !properties.is_empty() => {
+                    self.visit_object(node, properties, name)
+                }
+                _ => Ok(self.primitive("object")),
+            },
+            other => Err(format!("`{name}` has unsupported type `{other}`")),
+        }
+    }
+
+    fn visit_object(
+        &mut self,
+        node: &serde_json::Map<String, Value>,
+        properties: &serde_json::Map<String, Value>,
+        name: &str,
+    ) -> Result<String, String> {
+        let required: Vec<&str> = match node.get("required") {
+            Some(Value::Array(required)) => required.iter().filter_map(Value::as_str).collect(),
+            _ => Vec::new(),
+        };
+        self.primitive("ws");
+
+        let mut pairs = Vec::new();
+        let mut optional = Vec::new();
+        for (key, property) in properties {
+            let value = self.visit(property, &format!("{name}-{}", rule_name(key)))?;
+            let pair = format!(
+                r#"{} ws ":" ws {value}"#,
+                literal(&Value::String(key.clone()))
+            );
+            if required.contains(&key.as_str()) {
+                pairs.push(pair);
+            } else {
+                optional.push(pair);
+            }
+        }
+
+        let mut body = pairs.join(r#" "," ws "#);
+        if pairs.is_empty() {
+            // Without a required property to anchor the commas, optional ones
+            // can only be left out from the end.
+            let mut tail = String::new();
+            for pair in optional.iter().rev() {
+                tail = if tail.is_empty() {
+                    format!("( {pair} )?")
+                } else {
+                    format!(r#"( {pair} ( "," ws {} )? )?"#, strip_optional(&tail))
+                };
+            }
+            body = tail;
+        } else {
+            for pair in optional {
+                body.push_str(&format!(r#" ( "," ws {pair} )?"#));
+            }
+        }
+
+        Ok(self.add_rule(name, format!(r#""{{" ws {body} "}}" ws"#)))
+    }
+
+    fn primitive(&mut self, name: &'static str) -> String {
+        if !self.primitives.contains(&name) {
+            self.primitives.push(name);
+            // rules the primitive depends on
+            let dependencies: &[&'static str] = match name {
+                "value" => &["object", "array", "string", "number", "boolean", "null"],
+                "object" => &["string", "value"],
+                "array" => &["value"],
+                _ => &[],
+            };
+            for dependency in dependencies {
+                self.primitive(dependency);
+            }
+            if name != "ws" {
+                self.primitive("ws");
+            }
+        }
+        name.to_string()
+    }
+
+    /// Add rule `name` and return a reference to it. Names already taken get
+    /// a numeric suffix.
+    fn add_rule(&mut self, name: &str, body: String) -> String {
+        let base = rule_name(name);
+        let mut name = base.clone();
+        let mut n = 1;
+        while self.rules.iter().any(|(existing, _)| *existing == name)
+            || PRIMITIVES.iter().any(|(primitive, _)| *primitive == name)
+        {
+            name = format!("{base}{n}");
+            n += 1;
+        }
+        self.rules.push((name.clone(), body));
+        name
+    }
+
+    fn render(&self) -> String {
+        let mut out = String::new();
+        let mut rules: Vec<&(String, String)> = self.rules.iter().collect();
+        // root first, the rest in definition order
+        rules.sort_by_key(|(name, _)| name != "root");
+        for (name, body) in rules {
+            out.push_str(&format!("{name} ::= {body}\n"));
+        }
+        for (name, body) in PRIMITIVES {
+            if self.primitives.contains(name) {
+                out.push_str(&format!("{name} ::= {body}\n"));
+            }
+        }
+        out
+    }
+}
+
+/// `( x )?` -> `x`
+fn strip_optional(rule: &str) -> &str {
+    rule.strip_prefix("( ")
+        .and_then(|r| r.strip_suffix(" )?"))
+        .unwrap_or(rule)
+}
+
+/// GBNF rule names may only contain letters, digits and dashes.
+fn rule_name(name: &str) -> String {
+    name.chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '-' })
+        .collect()
+}
+
+/// GBNF literal matching the JSON encoding of `value`.
+fn literal(value: &Value) -> String {
+    let json = value.to_string();
+    let mut out = String::with_capacity(json.len() + 2);
+    out.push('"');
+    for c in json.chars() {
+        match c {
+            '"' => out.push_str(r#"\""#),
+            '\\' => out.push_str(r"\\"),
+            '\n' => out.push_str(r"\n"),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn object_properties_become_ordered_pairs() {
+        let grammar = grammar_from_schema(&json!({
+            "type": "object",
+            "properties": {
+                "city": { "type": "string" },
+                "unit": { "enum": ["c", "f"] }
+            },
+            "required": ["city"]
+        }))
+        .unwrap();
+
+        assert!(grammar.starts_with(
+            r#"root ::= "{" ws "\"city\"" ws ":" ws string ( "," ws "\"unit\"" ws ":" ws root-unit )? "}" ws"#
+        ));
+        assert!(grammar.contains(r#"root-unit ::= ( "\"c\"" | "\"f\"" ) ws"#));
+        assert!(grammar.contains("string ::= "));
+        assert!(!grammar.contains("number ::= "));
+    }
+
+    #[test]
+    fn arrays_and_unions_are_supported() {
+        let grammar = grammar_from_schema(&json!({
+            "type": "array",
+            "items": { "type": ["integer", "null"] }
+        }))
+        .unwrap();
+
+        assert!(
+            grammar.starts_with(r#"root ::= "[" ws ( root-item ( "," ws root-item )* )? "]" ws"#)
+        );
+        assert!(grammar.contains("root-item ::= integer | null\n"));
+    }
+
+    #[test]
+    fn references_are_rejected() {
+        let err = grammar_from_schema(&json!({ "$ref": "#/$defs/weather" })).unwrap_err();
+        assert!(err.contains("$ref"));
+    }
+}
@@ -0,0 +1,95 @@
+use std::path::{Path, PathBuf};
+
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+
+/// An image attached to a message, see [`Message::with_image`](crate::Message::with_image).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ImageInput {
+    /// Image file, read when the message is built.
+    Path(PathBuf),
+    /// Raw image bytes (PNG, JPEG, GIF or WebP).
+    Bytes(Vec<u8>),
+    /// Already base64 encoded image, optionally as a `data:` URL.
+    Base64(String),
+}
+
+impl ImageInput {
+    /// Base64 encoding of the image, without a `data:` URL prefix.
+    pub fn to_base64(&self) -> Result<String, std::io::Error> {
+        match self {
+            ImageInput::Path(path) => Ok(STANDARD.encode(std::fs::read(path)?)),
+            ImageInput::Bytes(bytes) => Ok(STANDARD.encode(bytes)),
+            ImageInput::Base64(data) => Ok(match data.split_once(";base64,") {
+                Some((_, data)) => data.to_string(),
+                None => data.clone(),
+            }),
+        }
+    }
+}
+
+impl From<PathBuf> for ImageInput {
+    fn from(path: PathBuf) -> Self {
+        ImageInput::Path(path)
+    }
+}
+
+impl From<&Path> for ImageInput {
+    fn from(path: &Path) -> Self {
+        ImageInput::Path(path.to_path_buf())
+    }
+}
+
+impl From<Vec<u8>> for ImageInput {
+    fn from(bytes: Vec<u8>) -> Self {
+        ImageInput::Bytes(bytes)
+    }
+}
+
+impl From<&[u8]> for ImageInput {
+    fn from(bytes: &[u8]) -> Self {
+        ImageInput::Bytes(bytes.to_vec())
+    }
+}
+
+/// `data:` URL for a base64 encoded image, as expected by OpenAI-compatible
+/// vision models. The media type is sniffed from the leading bytes.
+pub(crate) fn image_data_url(base64: &str) -> String {
+    if base64.starts_with("data:") {
+        return base64.to_string();
+    }
+    // base64 of the PNG, JPEG, GIF and WebP magic numbers
+    let media_type = if base64.starts_with("iVBORw0KGgo") {
+        "image/png"
+    } else if base64.starts_with("R0lGOD") {
+        "image/gif"
+    } else if base64.starts_with("UklGR") {
+        "image/webp"
+    } else {
+        "image/jpeg"
+    };
+    format!("data:{media_type};base64,{base64}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PNG_HEADER: &[u8] = &[0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A];
+
+    #[test]
+    fn bytes_are_encoded_and_data_urls_sniff_the_media_type() {
+        let encoded = ImageInput::from(PNG_HEADER).to_base64().unwrap();
+        assert_eq!(encoded, "iVBORw0KGgo=");
+        assert_eq!(
+            image_data_url(&encoded),
+            "data:image/png;base64,iVBORw0KGgo="
+        );
+        assert_eq!(
+            image_data_url("/9j/4AAQ"),
+            "data:image/jpeg;base64,/9j/4AAQ"
+        );
+
+        let data_url = ImageInput::Base64("data:image/png;base64,iVBORw0KGgo=".into());
+        assert_eq!(data_url.to_base64().unwrap(), encoded);
+    }
+}
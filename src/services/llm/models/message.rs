@@ -1,7 +1,71 @@
-use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize, Serializer};
+use serde_json::Value;
 use uuid::Uuid;
 
-use crate::{Role, ToolCall};
+use crate::{ImageInput, Role, ToolCall};
+
+/// Metadata key set on tool result messages, holding the name of the tool.
+pub const TOOL_NAME_METADATA: &str = "tool_name";
+/// Metadata key set on user messages of a [`ChatUser`], holding their id.
+pub const USER_ID_METADATA: &str = "user_id";
+/// Metadata key set on user messages of a [`ChatUser`] with a display name.
+pub const USER_NAME_METADATA: &str = "user_name";
+/// Metadata key marking a [`Message::scratch`] note.
+pub const SCRATCH_METADATA: &str = "scratch";
+
+/// An end-user talking to the agent, e.g. one member of a group chat.
+///
+/// User messages are attributed to them through their metadata. OpenAI
+/// compatible providers receive the user as the `name` of the message, other
+/// providers as a `Name: ` prefix of its content.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChatUser {
+    pub id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+}
+
+impl ChatUser {
+    pub fn new(id: impl Into<String>) -> Self {
+        Self {
+            id: id.into(),
+            name: None,
+        }
+    }
+
+    /// Name shown to the model instead of the id.
+    pub fn with_name(mut self, name: impl Into<String>) -> Self {
+        self.name = Some(name.into());
+        self
+    }
+
+    /// The display name, or the id without one.
+    pub fn display_name(&self) -> &str {
+        self.name.as_deref().unwrap_or(&self.id)
+    }
+
+    /// `user_id` and `user_name` template variables.
+    pub fn template_data(&self) -> HashMap<String, String> {
+        HashMap::from([
+            (USER_ID_METADATA.to_string(), self.id.clone()),
+            (
+                USER_NAME_METADATA.to_string(),
+                self.display_name().to_string(),
+            ),
+        ])
+    }
+
+    /// Attribute the user messages from `start` on that belong to no one yet.
+    pub(crate) fn claim_messages(&self, messages: &mut [Message], start: usize) {
+        for message in messages.iter_mut().skip(start) {
+            if message.role == Role::User && message.sender().is_none() {
+                message.set_sender(self);
+            }
+        }
+    }
+}
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Message {
@@ -18,6 +82,11 @@ pub struct Message {
     pub tool_calls: Option<Vec<ToolCall>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub tool_call_id: Option<String>,
+    /// Annotations for the application (source tool, timestamps, trace ids, ...).
+    ///
+    /// Saved with the history, but never sent to the model.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub metadata: HashMap<String, Value>,
 }
 
 impl Message {
@@ -30,6 +99,7 @@ impl Message {
             images: None,
             tool_calls: None,
             tool_call_id,
+            metadata: HashMap::new(),
         }
     }
 
@@ -52,8 +122,226 @@ impl Message {
     {
         Self::new(Role::Tool, content.into(), Some(tool_call_id.into()))
     }
+
+    /// A note of a flow, e.g. an intermediate result or a decision it made.
+    ///
+    /// Scratch messages stay in the history, so they are saved, exported and
+    /// seen by history observers, but they are dropped from every request to
+    /// the model and never use context tokens.
+    pub fn scratch<T: Into<String>>(content: T) -> Self {
+        Self::new(Role::Assistant, content.into(), None).with_metadata(SCRATCH_METADATA, true)
+    }
+
+    /// Whether this is a [`scratch`](Self::scratch) note.
+    pub fn is_scratch(&self) -> bool {
+        self.get_metadata(SCRATCH_METADATA)
+            .and_then(Value::as_bool)
+            .unwrap_or(false)
+    }
+
+    /// Attach a metadata entry, replacing an existing one with the same key.
+    pub fn with_metadata(mut self, key: impl Into<String>, value: impl Into<Value>) -> Self {
+        self.metadata.insert(key.into(), value.into());
+        self
+    }
+
+    /// Attach an image for vision models. Files are read and encoded right
+    /// away, so a missing file surfaces here rather than at inference time.
+    pub fn with_image(mut self, image: impl Into<ImageInput>) -> Result<Self, std::io::Error> {
+        let encoded = image.into().to_base64()?;
+        self.images.get_or_insert_with(Vec::new).push(encoded);
+        Ok(self)
+    }
+
+    /// Metadata entry for `key`, if set.
+    pub fn get_metadata(&self, key: &str) -> Option<&Value> {
+        self.metadata.get(key)
+    }
+
+    /// A user message written by `user`.
+    pub fn user_from<T: Into<String>>(user: &ChatUser, content: T) -> Self {
+        let mut message = Self::user(content);
+        message.set_sender(user);
+        message
+    }
+
+    /// The end-user the message is attributed to, see [`ChatUser`].
+    pub fn sender(&self) -> Option<ChatUser> {
+        let id = self.get_metadata(USER_ID_METADATA)?.as_str()?;
+        let name = self
+            .get_metadata(USER_NAME_METADATA)
+            .and_then(Value::as_str)
+            .map(str::to_string);
+        Some(ChatUser {
+            id: id.to_string(),
+            name,
+        })
+    }
+
+    fn set_sender(&mut self, user: &ChatUser) {
+        self.metadata
+            .insert(USER_ID_METADATA.into(), user.id.clone().into());
+        if let Some(name) = &user.name {
+            self.metadata
+                .insert(USER_NAME_METADATA.into(), name.clone().into());
+        }
+    }
+
+    /// `name` of the message for OpenAI compatible APIs, which reject
+    /// whitespace and `<`, `|`, `\`, `/` or `>` in it.
+    pub(crate) fn sender_name_field(&self) -> Option<String> {
+        if self.role != Role::User {
+            return None;
+        }
+        let sender = self.sender()?;
+        let name: String = sender
+            .display_name()
+            .chars()
+            .map(|c| {
+                if c.is_whitespace() || "<|\\/>".contains(c) {
+                    '_'
+                } else {
+                    c
+                }
+            })
+            .take(64)
+            .collect();
+        Some(name)
+    }
+}
+
+/// Prefix attributed user messages with `Name: `, for providers without a
+/// `name` field on messages.
+pub(crate) fn prefix_sender_names(messages: &mut [Message]) {
+    for message in messages.iter_mut().filter(|m| m.role == Role::User) {
+        let Some(sender) = message.sender() else {
+            continue;
+        };
+        let content = message.content.take().unwrap_or_default();
+        message.content = Some(format!("{}: {content}", sender.display_name()));
+    }
+}
+
+/// Message as sent to providers that take the crate's message shape
+/// directly (Ollama): everything but the metadata.
+#[derive(Serialize)]
+struct ProviderMessage<'a> {
+    role: &'a Role,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    content: &'a Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    thinking: &'a Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    images: &'a Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tool_calls: &'a Option<Vec<ToolCall>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tool_call_id: &'a Option<String>,
+}
+
+impl<'a> From<&'a Message> for ProviderMessage<'a> {
+    fn from(message: &'a Message) -> Self {
+        Self {
+            role: &message.role,
+            content: &message.content,
+            thinking: &message.thinking,
+            images: &message.images,
+            tool_calls: &message.tool_calls,
+            tool_call_id: &message.tool_call_id,
+        }
+    }
+}
+
+/// Serialize messages without their metadata.
+pub(crate) fn serialize_without_metadata<S>(
+    messages: &[Message],
+    serializer: S,
+) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    serializer.collect_seq(messages.iter().map(ProviderMessage::from))
 }
 
 fn new_uuid() -> String {
     Uuid::new_v4().to_string()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{services::llm::models::base::BaseRequest, ChatRequest};
+
+    #[test]
+    fn metadata_is_persisted_but_not_sent_to_providers() {
+        let message = Message::tool("42", "call_1").with_metadata(TOOL_NAME_METADATA, "answer");
+
+        let saved = serde_json::to_value(&message).unwrap();
+        assert_eq!(saved["metadata"]["tool_name"], "answer");
+        let restored: Message = serde_json::from_value(saved).unwrap();
+        assert_eq!(
+            restored.get_metadata(TOOL_NAME_METADATA),
+            Some(&Value::from("answer"))
+        );
+
+        let request = ChatRequest {
+            base: BaseRequest::default(),
+            messages: vec![message],
+            tools: None,
+            tool_choice: None,
+            parallel_tool_calls: None,
+        };
+        let body = serde_json::to_value(&request).unwrap();
+        assert_eq!(body["messages"][0]["content"], "42");
+        assert!(body["messages"][0].get("metadata").is_none());
+    }
+
+    #[test]
+    fn user_messages_carry_their_sender() {
+        let alice = ChatUser::new("u1").with_name("Alice Smith");
+        let mut messages = vec![
+            Message::user("hi"),
+            Message::user_from(&ChatUser::new("u2"), "hey"),
+            Message::assistant("hello"),
+            Message::user("how are you?"),
+        ];
+        alice.claim_messages(&mut messages, 1);
+
+        assert!(messages[0].sender().is_none());
+        assert_eq!(messages[1].sender(), Some(ChatUser::new("u2")));
+        assert_eq!(messages[3].sender(), Some(alice));
+        assert_eq!(
+            messages[3].sender_name_field().as_deref(),
+            Some("Alice_Smith")
+        );
+
+        prefix_sender_names(&mut messages);
+        assert_eq!(messages[1].content.as_deref(), Some("u2: hey"));
+        assert_eq!(
+            messages[3].content.as_deref(),
+            Some("Alice Smith: how are you?")
+        );
+    }
+
+    #[tokio::test]
+    async fn scratch_messages_stay_out_of_requests() {
+        let builder = crate::AgentBuilder::default().set_model("test");
+        let mut harness = crate::testing::FlowTestHarness::new(builder)
+            .await
+            .unwrap()
+            .reply("Paris.")
+            .reply("About 2 million.");
+
+        harness.run("Capital of France?").await.unwrap();
+        harness
+            .agent_mut()
+            .history
+            .push(Message::scratch("user asks about French geography"));
+        harness.run("How many people live there?").await.unwrap();
+
+        assert!(harness.history().iter().any(Message::is_scratch));
+        let sent = &harness.requests()[1].messages;
+        assert_eq!(sent.len(), 4);
+        assert!(sent.iter().all(|m| !m.is_scratch()));
+    }
+}
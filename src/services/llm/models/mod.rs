@@ -2,9 +2,14 @@ pub mod base;
 pub mod chat;
 pub mod embedding;
 pub mod errors;
+pub(crate) mod grammar;
+pub mod image;
 pub mod message;
+pub(crate) mod schema_normalizer;
+pub mod schema_registry;
 pub mod sturctured_output;
 
 pub use base::*;
 pub use errors::*;
+pub use schema_registry::*;
 pub use sturctured_output::*;
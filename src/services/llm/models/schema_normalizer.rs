@@ -0,0 +1,333 @@
+use serde_json::{json, Map, Value};
+
+/// Schema flavour accepted by a provider's structured output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum SchemaDialect {
+    /// Ollama converts the schema into a sampling grammar.
+    Ollama,
+    /// OpenAI-compatible `json_schema` response format (OpenAI, OpenRouter).
+    /// Strict mode additionally requires every property to be listed in
+    /// `required` and `additionalProperties: false` on all objects.
+    OpenAi { strict: bool },
+}
+
+/// Rewrite `schema` into constructs `dialect` accepts:
+///
+/// - `oneOf` becomes `anyOf`, exclusivity is not enforced by the backends,
+/// - `allOf` members are merged into the enclosing schema,
+/// - OpenAPI style `nullable: true` becomes a `"null"` type or `anyOf` branch,
+/// - nested `anyOf`s are flattened, and unions of bare types are collapsed
+///   into a `type` array,
+/// - for strict OpenAI schemas, optional properties become required but
+///   nullable and objects get `additionalProperties: false`.
+pub(crate) fn normalize_schema(schema: &Value, dialect: SchemaDialect) -> Value {
+    let mut schema = schema.clone();
+    normalize(&mut schema, dialect);
+    schema
+}
+
+fn normalize(schema: &mut Value, dialect: SchemaDialect) {
+    let Some(node) = schema.as_object_mut() else {
+        return;
+    };
+
+    // With both present the alternatives can't be combined without changing
+    // the meaning, so `oneOf` is only renamed when it stands alone.
+    if !node.contains_key("anyOf") {
+        if let Some(one_of) = node.remove("oneOf") {
+            node.insert("anyOf".into(), one_of);
+        }
+    }
+
+    merge_all_of(node);
+
+    let nullable = matches!(node.remove("nullable"), Some(Value::Bool(true)));
+
+    for child in children(node) {
+        normalize(child, dialect);
+    }
+
+    flatten_any_of(node);
+
+    if nullable {
+        make_nullable(schema);
+    }
+
+    if let SchemaDialect::OpenAi { strict: true } = dialect {
+        if let Some(node) = schema.as_object_mut() {
+            make_strict(node);
+        }
+    }
+}
+
+/// Mutable references to all direct subschemas.
+fn children(node: &mut Map<String, Value>) -> Vec<&mut Value> {
+    let mut children = Vec::new();
+    for (key, value) in node.iter_mut() {
+        match (key.as_str(), value) {
+            ("properties" | "definitions" | "$defs" | "patternProperties", Value::Object(map)) => {
+                children.extend(map.values_mut())
+            }
+            ("anyOf" | "oneOf" | "prefixItems" | "items", Value::Array(items)) => {
+                children.extend(items.iter_mut())
+            }
+            ("items" | "additionalProperties" | "not", value) if value.is_object() => {
+                children.push(value)
+            }
+            _ => {}
+        }
+    }
+    children
+}
+
+/// Merge the members of `allOf` into `node`: properties and required fields
+/// are combined, other keywords are taken from the first member setting them.
+fn merge_all_of(node: &mut Map<String, Value>) {
+    let Some(Value::Array(members)) = node.remove("allOf") else {
+        return;
+    };
+
+    for member in members {
+        let Value::Object(member) = member else {
+            continue;
+        };
+        for (key, value) in member {
+            match (node.get_mut(&key), value) {
+                (Some(Value::Object(existing)), Value::Object(added)) if key == "properties" => {
+                    for (name, property) in added {
+                        existing.entry(name).or_insert(property);
+                    }
+                }
+                (Some(Value::Array(existing)), Value::Array(added)) if key == "required" => {
+                    for field in added {
+                        if !existing.contains(&field) {
+                            existing.push(field);
+                        }
+                    }
+                }
+                (Some(_), _) => {}
+                (None, value) => {
+                    node.insert(key, value);
+                }
+            }
+        }
+    }
+}
+
+/// Splice nested `anyOf`s into their parent and collapse a union of bare
+/// `{"type": ...}` schemas into a single `type` array.
+fn flatten_any_of(node: &mut Map<String, Value>) {
+    let Some(Value::Array(members)) = node.remove("anyOf") else {
+        return;
+    };
+
+    let mut flat = Vec::new();
+    for member in members {
+        match member {
+            Value::Object(mut inner) if inner.len() == 1 && inner.contains_key("anyOf") => {
+                if let Some(Value::Array(nested)) = inner.remove("anyOf") {
+                    flat.extend(nested);
+                }
+            }
+            member => flat.push(member),
+        }
+    }
+    let mut flat = unique(flat);
+
+    let bare_types = flat
+        .iter()
+        .map(|member| match member.as_object() {
+            Some(m) if m.len() == 1 => match m.get("type") {
+                Some(Value::String(t)) => Some(vec![Value::String(t.clone())]),
+                Some(Value::Array(types)) => Some(types.clone()),
+                _ => None,
+            },
+            _ => None,
+        })
+        .collect::<Option<Vec<_>>>();
+
+    if node.contains_key("type") || flat.is_empty() {
+        node.insert("anyOf".into(), Value::Array(flat));
+    } else if let Some(types) = bare_types {
+        let mut types = unique(types.into_iter().flatten().collect());
+        let value = if types.len() == 1 {
+            types.remove(0)
+        } else {
+            Value::Array(types)
+        };
+        node.insert("type".into(), value);
+    } else if flat.len() == 1 && flat[0].is_object() {
+        if let Some(Value::Object(only)) = flat.pop() {
+            for (key, value) in only {
+                node.entry(key).or_insert(value);
+            }
+        }
+    } else {
+        node.insert("anyOf".into(), Value::Array(flat));
+    }
+}
+
+fn unique(values: Vec<Value>) -> Vec<Value> {
+    let mut out = Vec::with_capacity(values.len());
+    for value in values {
+        if !out.contains(&value) {
+            out.push(value);
+        }
+    }
+    out
+}
+
+/// Allow `null` in addition to what `schema` already accepts.
+fn make_nullable(schema: &mut Value) {
+    let Some(node) = schema.as_object_mut() else {
+        return;
+    };
+    let null = Value::String("null".into());
+
+    if let Some(Value::Array(values)) = node.get_mut("enum") {
+        if !values.contains(&Value::Null) {
+            values.push(Value::Null);
+        }
+    }
+
+    if let Some(ty) = node.get_mut("type") {
+        match ty {
+            Value::Array(types) if !types.contains(&null) => types.push(null),
+            Value::Array(_) => {}
+            _ if *ty != "null" => {
+                let single = ty.take();
+                *ty = Value::Array(vec![single, null]);
+            }
+            _ => {}
+        }
+        return;
+    }
+
+    let null_member = json!({ "type": "null" });
+    if let Some(Value::Array(members)) = node.get_mut("anyOf") {
+        if !members.contains(&null_member) {
+            members.push(null_member);
+        }
+        return;
+    }
+
+    if !node.contains_key("enum") {
+        let inner = std::mem::take(node);
+        node.insert(
+            "anyOf".into(),
+            Value::Array(vec![Value::Object(inner), null_member]),
+        );
+    }
+}
+
+/// OpenAI strict mode: every property is required, optional ones become
+/// nullable instead, and no additional properties are allowed.
+fn make_strict(node: &mut Map<String, Value>) {
+    let required = match node.get("required") {
+        Some(Value::Array(required)) => required.clone(),
+        _ => Vec::new(),
+    };
+    let Some(Value::Object(properties)) = node.get_mut("properties") else {
+        return;
+    };
+
+    let mut all = Vec::with_capacity(properties.len());
+    for (name, property) in properties.iter_mut() {
+        let name = Value::String(name.clone());
+        if !required.contains(&name) {
+            make_nullable(property);
+        }
+        all.push(name);
+    }
+
+    node.insert("required".into(), Value::Array(all));
+    node.entry("additionalProperties")
+        .or_insert(Value::Bool(false));
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn one_of_and_nullable_are_rewritten() {
+        let schema = json!({
+            "type": "object",
+            "properties": {
+                "unit": { "type": "string", "enum": ["c", "f"], "nullable": true },
+                "value": { "oneOf": [{ "type": "integer" }, { "type": "number" }] },
+                "place": {
+                    "oneOf": [
+                        { "type": "object", "properties": { "city": { "type": "string" } } },
+                        { "type": "string" }
+                    ]
+                }
+            }
+        });
+
+        let normalized = normalize_schema(&schema, SchemaDialect::Ollama);
+        let properties = &normalized["properties"];
+
+        assert_eq!(
+            properties["unit"],
+            json!({ "type": ["string", "null"], "enum": ["c", "f", null] })
+        );
+        assert_eq!(
+            properties["value"],
+            json!({ "type": ["integer", "number"] })
+        );
+        assert_eq!(properties["place"]["anyOf"].as_array().unwrap().len(), 2);
+        assert!(properties["place"].get("oneOf").is_none());
+    }
+
+    #[test]
+    fn all_of_members_are_merged() {
+        let schema = json!({
+            "allOf": [
+                { "type": "object", "properties": { "a": { "type": "string" } }, "required": ["a"] },
+                { "properties": { "b": { "type": "number" } }, "required": ["b"] }
+            ]
+        });
+
+        let normalized = normalize_schema(&schema, SchemaDialect::Ollama);
+
+        assert_eq!(
+            normalized,
+            json!({
+                "type": "object",
+                "properties": { "a": { "type": "string" }, "b": { "type": "number" } },
+                "required": ["a", "b"]
+            })
+        );
+    }
+
+    #[test]
+    fn strict_mode_requires_all_properties() {
+        let schema = json!({
+            "type": "object",
+            "properties": {
+                "city": { "type": "string" },
+                "country": { "$ref": "#/$defs/country" }
+            },
+            "required": ["city"]
+        });
+
+        let normalized = normalize_schema(&schema, SchemaDialect::OpenAi { strict: true });
+
+        assert_eq!(normalized["required"], json!(["city", "country"]));
+        assert_eq!(normalized["additionalProperties"], json!(false));
+        assert_eq!(
+            normalized["properties"]["city"],
+            json!({ "type": "string" })
+        );
+        assert_eq!(
+            normalized["properties"]["country"],
+            json!({ "anyOf": [{ "$ref": "#/$defs/country" }, { "type": "null" }] })
+        );
+
+        let lenient = normalize_schema(&schema, SchemaDialect::OpenAi { strict: false });
+        assert_eq!(lenient, schema);
+    }
+}
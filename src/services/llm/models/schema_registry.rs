@@ -0,0 +1,269 @@
+use std::collections::{BTreeMap, HashMap};
+
+use serde_json::Value;
+
+use super::sturctured_output::SchemaSpec;
+
+/// Errors raised when registering or resolving schemas in a [`SchemaRegistry`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SchemaRegistryError {
+    /// The reference is not of the form `name`, `name@vN` or `name@N`.
+    InvalidReference(String),
+    /// No schema is registered under the reference.
+    UnknownSchema(String),
+    /// The name and version are already taken. Versions are immutable,
+    /// register a new version instead.
+    DuplicateVersion(String),
+    /// The schema is not a usable JSON Schema object.
+    InvalidSchema { id: String, reason: String },
+}
+
+impl std::fmt::Display for SchemaRegistryError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SchemaRegistryError::InvalidReference(r) => {
+                write!(f, "Invalid schema reference `{r}`, expected `name@vN`")
+            }
+            SchemaRegistryError::UnknownSchema(r) => write!(f, "Unknown schema `{r}`"),
+            SchemaRegistryError::DuplicateVersion(id) => {
+                write!(f, "Schema `{id}` is already registered")
+            }
+            SchemaRegistryError::InvalidSchema { id, reason } => {
+                write!(f, "Invalid schema `{id}`: {reason}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for SchemaRegistryError {}
+
+/// Response schemas registered by name and version, so several agents of a
+/// long-lived service can refer to the same schema and evolve it together.
+///
+/// Schemas are checked when registered and referenced from the
+/// [`AgentBuilder`](crate::AgentBuilder) as `"name@vN"`, or by plain `"name"`
+/// for the latest version. The resolved id is attached to the agent's
+/// notifications as [`Notification::schema`](crate::Notification::schema).
+///
+/// ```
+/// use reagent_rs::{SchemaRegistry, SchemaSpec};
+/// use serde_json::json;
+///
+/// let mut registry = SchemaRegistry::new();
+/// registry
+///     .register("weather", 1, SchemaSpec::from_value(json!({ "type": "object" })))
+///     .unwrap();
+/// registry
+///     .register("weather", 2, SchemaSpec::from_value(json!({
+///         "type": "object",
+///         "properties": { "celsius": { "type": "number" } },
+///         "required": ["celsius"]
+///     })))
+///     .unwrap();
+///
+/// let (id, _) = registry.resolve("weather").unwrap();
+/// assert_eq!(id, "weather@v2");
+/// assert!(registry.resolve("weather@v3").is_err());
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct SchemaRegistry {
+    schemas: HashMap<String, BTreeMap<u32, SchemaSpec>>,
+}
+
+impl SchemaRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register `spec` as version `version` of `name`. The schema is
+    /// validated, and an already registered version is never replaced.
+    pub fn register(
+        &mut self,
+        name: impl Into<String>,
+        version: u32,
+        spec: SchemaSpec,
+    ) -> Result<(), SchemaRegistryError> {
+        let name = name.into();
+        let id = schema_id(&name, version);
+        if name.is_empty() || name.contains('@') {
+            return Err(SchemaRegistryError::InvalidReference(id));
+        }
+        validate_schema(&spec.schema).map_err(|reason| SchemaRegistryError::InvalidSchema {
+            id: id.clone(),
+            reason,
+        })?;
+
+        let versions = self.schemas.entry(name).or_default();
+        if versions.contains_key(&version) {
+            return Err(SchemaRegistryError::DuplicateVersion(id));
+        }
+        versions.insert(version, spec);
+        Ok(())
+    }
+
+    /// Look up a schema by `"name@vN"`, `"name@N"` or `"name"` (latest
+    /// version). Returns the canonical `"name@vN"` id together with the spec.
+    /// Specs registered without a name get the schema name.
+    pub fn resolve(&self, reference: &str) -> Result<(String, SchemaSpec), SchemaRegistryError> {
+        let (name, version) = parse_reference(reference)?;
+        let versions = self
+            .schemas
+            .get(name)
+            .ok_or_else(|| SchemaRegistryError::UnknownSchema(reference.to_string()))?;
+        let (version, spec) = match version {
+            Some(version) => versions.get_key_value(&version),
+            None => versions.last_key_value(),
+        }
+        .ok_or_else(|| SchemaRegistryError::UnknownSchema(reference.to_string()))?;
+
+        let mut spec = spec.clone();
+        if spec.name.is_none() {
+            spec.name = Some(name.to_string());
+        }
+        Ok((schema_id(name, *version), spec))
+    }
+
+    /// Registered versions of `name`, in ascending order.
+    pub fn versions(&self, name: &str) -> Vec<u32> {
+        self.schemas
+            .get(name)
+            .map(|versions| versions.keys().copied().collect())
+            .unwrap_or_default()
+    }
+
+    /// Ids (`"name@vN"`) of all registered schemas, sorted.
+    pub fn ids(&self) -> Vec<String> {
+        let mut ids: Vec<String> = self
+            .schemas
+            .iter()
+            .flat_map(|(name, versions)| versions.keys().map(|v| schema_id(name, *v)))
+            .collect();
+        ids.sort();
+        ids
+    }
+}
+
+fn schema_id(name: &str, version: u32) -> String {
+    format!("{name}@v{version}")
+}
+
+fn parse_reference(reference: &str) -> Result<(&str, Option<u32>), SchemaRegistryError> {
+    let invalid = || SchemaRegistryError::InvalidReference(reference.to_string());
+    match reference.split_once('@') {
+        None if !reference.is_empty() => Ok((reference, None)),
+        None => Err(invalid()),
+        Some((name, version)) => {
+            let version = version.strip_prefix('v').unwrap_or(version);
+            match version.parse() {
+                Ok(version) if !name.is_empty() => Ok((name, Some(version))),
+                _ => Err(invalid()),
+            }
+        }
+    }
+}
+
+/// Shallow sanity check of a JSON Schema root, catching the mistakes that
+/// would otherwise only surface as provider errors at inference time.
+fn validate_schema(schema: &Value) -> Result<(), String> {
+    let Some(root) = schema.as_object() else {
+        return Err("schema must be a JSON object".into());
+    };
+
+    match root.get("type") {
+        None | Some(Value::String(_)) => {}
+        Some(Value::Array(types)) if types.iter().all(Value::is_string) => {}
+        Some(_) => return Err("`type` must be a string or an array of strings".into()),
+    }
+
+    let properties = match root.get("properties") {
+        None => None,
+        Some(Value::Object(properties)) => Some(properties),
+        Some(_) => return Err("`properties` must be an object".into()),
+    };
+
+    match root.get("required") {
+        None => {}
+        Some(Value::Array(required)) => {
+            for field in required {
+                let Some(field) = field.as_str() else {
+                    return Err("`required` must be an array of strings".into());
+                };
+                if properties.is_some_and(|p| !p.contains_key(field)) {
+                    return Err(format!("required field `{field}` is not in `properties`"));
+                }
+            }
+        }
+        Some(_) => return Err("`required` must be an array of strings".into()),
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn object_schema() -> SchemaSpec {
+        SchemaSpec::from_value(json!({
+            "type": "object",
+            "properties": { "city": { "type": "string" } },
+            "required": ["city"]
+        }))
+    }
+
+    #[test]
+    fn references_resolve_to_exact_or_latest_version() {
+        let mut registry = SchemaRegistry::new();
+        registry.register("weather", 1, object_schema()).unwrap();
+        registry
+            .register("weather", 2, object_schema().with_name("weather_v2"))
+            .unwrap();
+
+        let (id, spec) = registry.resolve("weather@v1").unwrap();
+        assert_eq!(id, "weather@v1");
+        assert_eq!(spec.name.as_deref(), Some("weather"));
+
+        let (id, spec) = registry.resolve("weather").unwrap();
+        assert_eq!(id, "weather@v2");
+        assert_eq!(spec.name.as_deref(), Some("weather_v2"));
+
+        assert_eq!(registry.resolve("weather@2").unwrap().0, "weather@v2");
+        assert_eq!(registry.versions("weather"), vec![1, 2]);
+        assert_eq!(
+            registry.resolve("weather@latest").unwrap_err(),
+            SchemaRegistryError::InvalidReference("weather@latest".into())
+        );
+    }
+
+    #[test]
+    fn versions_cannot_be_replaced() {
+        let mut registry = SchemaRegistry::new();
+        registry.register("weather", 1, object_schema()).unwrap();
+
+        assert_eq!(
+            registry.register("weather", 1, object_schema()),
+            Err(SchemaRegistryError::DuplicateVersion("weather@v1".into()))
+        );
+    }
+
+    #[test]
+    fn invalid_schemas_are_rejected_on_registration() {
+        let mut registry = SchemaRegistry::new();
+        let missing_property = SchemaSpec::from_value(json!({
+            "type": "object",
+            "properties": {},
+            "required": ["city"]
+        }));
+
+        assert!(matches!(
+            registry.register("weather", 1, missing_property),
+            Err(SchemaRegistryError::InvalidSchema { .. })
+        ));
+        assert!(registry
+            .register("weather", 1, SchemaSpec::from_value(json!("object")))
+            .is_err());
+        assert!(registry.ids().is_empty());
+    }
+}
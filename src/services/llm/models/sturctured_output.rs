@@ -1,5 +1,12 @@
 use rmcp::schemars::{gen::SchemaSettings, schema::RootSchema, JsonSchema, SchemaGenerator};
-use serde_json::Value;
+use serde_json::{json, Value};
+
+use super::schema_registry::SchemaRegistry;
+
+/// Property holding the variant name in a [`SchemaSpec::union`] answer.
+pub const UNION_TAG: &str = "type";
+/// Property holding the value in a [`SchemaSpec::union`] answer.
+pub const UNION_CONTENT: &str = "content";
 
 #[derive(Clone, Debug)]
 pub struct SchemaSpec {
@@ -43,12 +50,42 @@ impl SchemaSpec {
         }
         Self::from_value(schema)
     }
+
+    /// A schema the model answers with exactly one of `variants`, as
+    /// `{"type": "<variant name>", "content": <value>}`.
+    ///
+    /// Variants are named by their [`name`](SchemaSpec::with_name), or
+    /// `variant_<index>` without one. This is the adjacently tagged layout of
+    /// serde, so the answer deserializes into an enum with
+    /// `#[serde(tag = "type", content = "content")]` whose variants are
+    /// renamed to the schema names. Providers that require an object at the
+    /// root of strict schemas (OpenAI) do not accept it in strict mode.
+    pub fn union(variants: impl IntoIterator<Item = SchemaSpec>) -> Self {
+        let variants: Vec<Value> = variants
+            .into_iter()
+            .enumerate()
+            .map(|(index, spec)| {
+                let name = spec.name.unwrap_or_else(|| format!("variant_{index}"));
+                json!({
+                    "type": "object",
+                    "properties": {
+                        UNION_TAG: { "type": "string", "enum": [name] },
+                        UNION_CONTENT: spec.schema,
+                    },
+                    "required": [UNION_TAG, UNION_CONTENT],
+                    "additionalProperties": false,
+                })
+            })
+            .collect();
+        Self::from_value(json!({ "anyOf": variants }))
+    }
 }
 
 #[derive(Clone, Debug, Default)]
 pub struct ResponseFormatConfig {
     spec: Option<SchemaSpec>,
     raw: Option<String>,
+    named: Option<String>,
     name: Option<String>,
     strict: Option<bool>,
 }
@@ -70,6 +107,11 @@ impl ResponseFormatConfig {
         self.spec = Some(spec);
     }
 
+    /// Reference to a schema in a [`SchemaRegistry`], e.g. `"weather@v2"`.
+    pub fn set_named(&mut self, reference: impl Into<String>) {
+        self.named = Some(reference.into());
+    }
+
     pub fn set_name(&mut self, name: impl Into<String>) {
         self.name = Some(name.into());
     }
@@ -78,7 +120,42 @@ impl ResponseFormatConfig {
         self.strict = Some(strict);
     }
 
+    /// Whether any schema source was set.
+    pub fn is_set(&self) -> bool {
+        self.spec.is_some() || self.raw.is_some() || self.named.is_some()
+    }
+
+    /// Replace a [`set_named`](Self::set_named) reference by the spec
+    /// registered in `registry`. Returns the id of the resolved schema.
+    pub fn resolve_named(
+        &mut self,
+        registry: Option<&SchemaRegistry>,
+    ) -> Result<Option<String>, String> {
+        let Some(reference) = self.named.take() else {
+            return Ok(None);
+        };
+        if self.spec.is_some() || self.raw.is_some() {
+            return Err(format!(
+                "Response format `{reference}` was set together with another schema source. Use only one source."
+            ));
+        }
+        let Some(registry) = registry else {
+            return Err(format!(
+                "Response format `{reference}` refers to a registered schema, but no schema registry was set."
+            ));
+        };
+
+        let (id, spec) = registry.resolve(&reference).map_err(|e| e.to_string())?;
+        self.spec = Some(spec);
+        Ok(Some(id))
+    }
+
     pub fn resolve(self) -> Result<Option<SchemaSpec>, String> {
+        if let Some(reference) = self.named {
+            return Err(format!(
+                "Response format `{reference}` refers to a registered schema that was not resolved."
+            ));
+        }
         if self.spec.is_some() && self.raw.is_some() {
             return Err(
                 "Both set_structured_output_* and set_response_format_str were called. Use only one source."
@@ -109,3 +186,60 @@ impl ResponseFormatConfig {
 pub trait StructuredOuputFormat {
     fn format(spec: &SchemaSpec) -> serde_json::Value;
 }
+
+#[cfg(test)]
+mod tests {
+    use serde::Deserialize;
+
+    use super::*;
+    use crate::{testing::FlowTestHarness, AgentBuilder, StructuredOutputStrategy};
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    #[serde(tag = "type", content = "content", rename_all = "snake_case")]
+    enum Answer {
+        Forecast { temperature: i32 },
+        Clarification { question: String },
+    }
+
+    fn object(property: &str, kind: &str) -> Value {
+        json!({
+            "type": "object",
+            "properties": { property: { "type": kind } },
+            "required": [property],
+        })
+    }
+
+    #[tokio::test]
+    async fn union_answers_deserialize_into_tagged_enums() {
+        let variants = [
+            SchemaSpec::from_value(object("temperature", "integer")).with_name("forecast"),
+            SchemaSpec::from_value(object("question", "string")).with_name("clarification"),
+        ];
+        let union = SchemaSpec::union(variants.clone());
+        assert_eq!(
+            union.schema["anyOf"][1]["properties"]["type"]["enum"][0],
+            "clarification"
+        );
+
+        let builder = AgentBuilder::default()
+            .set_model("test")
+            .set_structured_output_strategy(StructuredOutputStrategy::Prompt)
+            .set_response_formats(variants);
+        let mut harness = FlowTestHarness::new(builder)
+            .await
+            .unwrap()
+            .reply(r#"{"type": "clarification", "content": {"question": "Which city?"}}"#);
+
+        let answer: Answer = harness
+            .agent_mut()
+            .invoke_flow_structured_output_enum("What is the weather?")
+            .await
+            .unwrap();
+        assert_eq!(
+            answer,
+            Answer::Clarification {
+                question: "Which city?".into()
+            }
+        );
+    }
+}
@@ -4,7 +4,7 @@ use std::pin::Pin;
 use crate::{
     services::llm::models::{
         chat::{ChatRequest, ChatResponse, ChatStreamChunk},
-        embedding::{EmbeddingsRequest, EmbeddingsResponse},
+        embedding::{EmbedRequest, EmbedResponse, EmbeddingsRequest, EmbeddingsResponse},
         errors::InferenceClientError,
     },
     ClientConfig,
@@ -48,4 +48,10 @@ impl AnthropicClient {
             "Anthropic embeddings not implemented yet".into(),
         ))
     }
+
+    pub async fn embed(&self, _req: EmbedRequest) -> Result<EmbedResponse, InferenceClientError> {
+        Err(InferenceClientError::Unsupported(
+            "Anthropic embeddings not implemented yet".into(),
+        ))
+    }
 }
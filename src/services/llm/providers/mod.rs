@@ -2,4 +2,7 @@ pub mod anthropic;
 pub mod mistral;
 pub mod ollama;
 pub mod openai;
+mod openai_compat;
 pub mod openrouter;
+#[cfg(any(test, feature = "testing"))]
+pub mod scripted;
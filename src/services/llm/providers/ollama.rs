@@ -8,12 +8,13 @@ use tracing::{error, span, Instrument, Level, Span};
 use tracing_opentelemetry::OpenTelemetrySpanExt;
 
 use crate::services::llm::models::chat::ChatStreamChunk;
+use crate::services::llm::models::schema_normalizer::{normalize_schema, SchemaDialect};
 use crate::services::llm::models::{
     chat::{ChatRequest, ChatResponse},
-    embedding::{EmbeddingsRequest, EmbeddingsResponse},
+    embedding::{EmbedRequest, EmbedResponse, EmbeddingsRequest, EmbeddingsResponse},
     errors::InferenceClientError,
 };
-use crate::services::llm::StructuredOuputFormat;
+use crate::services::llm::{payload_sampled, StructuredOuputFormat};
 use crate::ClientConfig;
 
 #[derive(Debug, Clone)]
@@ -24,6 +25,7 @@ pub struct OllamaClient {
 
 impl OllamaClient {
     pub fn new(cfg: ClientConfig) -> Result<Self, InferenceClientError> {
+        let http = cfg.http_client_builder()?;
         let base_url = cfg
             .base_url
             .clone()
@@ -31,12 +33,7 @@ impl OllamaClient {
 
         let mut headers = HeaderMap::new();
         if let Some(api_key) = cfg.api_key {
-            headers.insert(
-                AUTHORIZATION,
-                HeaderValue::from_str(&format!("Bearer {api_key}")).map_err(|e| {
-                    InferenceClientError::Config(format!("Invalid api_key header: {e}"))
-                })?,
-            );
+            headers.insert(AUTHORIZATION, api_key.bearer_header()?);
         }
         headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
 
@@ -45,14 +42,16 @@ impl OllamaClient {
                 let name = HeaderName::from_bytes(k.as_bytes()).map_err(|_| {
                     InferenceClientError::Config(format!("Invalid header name: {k}"))
                 })?;
-                let value = HeaderValue::from_str(&v).map_err(|_| {
+                let mut value = HeaderValue::from_str(&v).map_err(|_| {
                     InferenceClientError::Config(format!("Invalid header value for {k}"))
                 })?;
+                // extra headers often carry credentials
+                value.set_sensitive(true);
                 headers.insert(name, value);
             }
         }
 
-        let client = Client::builder().default_headers(headers).build()?;
+        let client = http.default_headers(headers).build()?;
 
         Ok(Self { client, base_url })
     }
@@ -74,8 +73,11 @@ impl OllamaClient {
             "server.address" = self.base_url.as_str(),
         );
 
-        if let Ok(body) = serde_json::to_string(request_body) {
-            span.set_attribute("langfuse.observation.input", body);
+        let log_payload = payload_sampled();
+        if log_payload {
+            if let Ok(body) = serde_json::to_string(request_body) {
+                span.set_attribute("langfuse.observation.input", body);
+            }
         }
 
         async {
@@ -110,16 +112,19 @@ impl OllamaClient {
                 Span::current()
                     .set_attribute("langfuse.observation.status_message", error_text.clone());
 
-                return Err(InferenceClientError::Api(format!(
-                    "Ollama request failed: {status} - {error_text}"
-                )));
+                return Err(InferenceClientError::Status(
+                    status.as_u16(),
+                    format!("Ollama request failed: {status} - {error_text}"),
+                ));
             }
 
             let response_text = response.text().await.map_err(|e| {
                 InferenceClientError::Api(format!("Failed to read response text: {e}"))
             })?;
 
-            Span::current().set_attribute("langfuse.observation.output", response_text.clone());
+            if log_payload {
+                Span::current().set_attribute("langfuse.observation.output", response_text.clone());
+            }
 
             match serde_json::from_str::<R>(&response_text) {
                 Ok(parsed) => Ok(parsed),
@@ -161,8 +166,11 @@ impl OllamaClient {
             "url.full" = url.as_str(),
         );
 
-        if let Ok(b) = serde_json::to_string(body) {
-            span.set_attribute("langfuse.observation.input", b);
+        let log_payload = payload_sampled();
+        if log_payload {
+            if let Ok(b) = serde_json::to_string(body) {
+                span.set_attribute("langfuse.observation.input", b);
+            }
         }
 
         let stream_span = span.clone();
@@ -185,7 +193,10 @@ impl OllamaClient {
             Span::current().set_attribute("http.response.status_code", status.as_u16() as i64);
 
             if !status.is_success() {
-                return Err(InferenceClientError::Api(format!("HTTP {}", status)));
+                return Err(InferenceClientError::Status(
+                    status.as_u16(),
+                    format!("HTTP {}", status),
+                ));
             }
             Ok(resp)
         }
@@ -227,8 +238,10 @@ impl OllamaClient {
 
                     match serde_json::from_slice::<R>(line) {
                         Ok(parsed) => {
-                            chunks.push(parsed.clone());
-                            stream_span.set_attribute("langfuse.observation.output", serde_json::to_string_pretty(&chunks).unwrap_or_default());
+                            if log_payload {
+                                chunks.push(parsed.clone());
+                                stream_span.set_attribute("langfuse.observation.output", serde_json::to_string_pretty(&chunks).unwrap_or_default());
+                            }
                             yield parsed
                         },
                         Err(e) => {
@@ -251,7 +264,7 @@ impl OllamaClient {
     }
 
     pub async fn chat(&self, request: ChatRequest) -> Result<ChatResponse, InferenceClientError> {
-        self.post("/api/chat", &request).await
+        self.post("/api/chat", &request.emulate_tool_choice()).await
     }
 
     pub async fn chat_stream(
@@ -261,23 +274,73 @@ impl OllamaClient {
         Pin<Box<dyn Stream<Item = Result<ChatStreamChunk, InferenceClientError>> + Send + 'static>>,
         InferenceClientError,
     > {
-        self.post_stream("/api/chat", &req).await
+        self.post_stream("/api/chat", &req.emulate_tool_choice())
+            .await
     }
 
+    /// Embed a single input, through `/api/embed` (the legacy
+    /// `/api/embeddings` endpoint is deprecated).
     pub async fn embeddings(
         &self,
         request: EmbeddingsRequest,
     ) -> Result<EmbeddingsResponse, InferenceClientError> {
-        self.post("/api/embeddings", &request).await
+        let response = self.embed(request.into()).await?;
+        match response.embeddings.into_iter().next() {
+            Some(embedding) => Ok(EmbeddingsResponse { embedding }),
+            None => Err(InferenceClientError::Api(
+                "Ollama embed response did not include embeddings".into(),
+            )),
+        }
+    }
+
+    pub async fn embed(
+        &self,
+        request: EmbedRequest,
+    ) -> Result<EmbedResponse, InferenceClientError> {
+        self.post("/api/embed", &request).await
+    }
+
+    /// Context length `model` was trained for, from `/api/show`.
+    pub async fn context_length(&self, model: &str) -> Result<Option<u32>, InferenceClientError> {
+        let request = serde_json::json!({ "model": model });
+        let info: serde_json::Value = self.post("/api/show", &request).await?;
+        Ok(context_length_from_show(&info))
+    }
+
+    /// Unload `model` from memory, with a `keep_alive` of 0 and no prompt.
+    pub async fn unload(&self, model: &str) -> Result<(), InferenceClientError> {
+        let request = serde_json::json!({ "model": model, "keep_alive": 0 });
+        self.post::<_, serde_json::Value>("/api/generate", &request)
+            .await
+            .map(|_| ())
     }
 }
 
 impl StructuredOuputFormat for OllamaClient {
     fn format(spec: &crate::services::llm::SchemaSpec) -> serde_json::Value {
-        spec.schema.clone()
+        normalize_schema(&spec.schema, SchemaDialect::Ollama)
     }
 }
 
+/// `<architecture>.context_length` of the `model_info` of an `/api/show`
+/// response.
+fn context_length_from_show(info: &serde_json::Value) -> Option<u32> {
+    let model_info = info.get("model_info")?.as_object()?;
+    let length = match model_info
+        .get("general.architecture")
+        .and_then(|arch| arch.as_str())
+    {
+        Some(arch) => model_info.get(&format!("{arch}.context_length"))?,
+        None => {
+            model_info
+                .iter()
+                .find(|(key, _)| key.ends_with(".context_length"))?
+                .1
+        }
+    };
+    u32::try_from(length.as_u64()?).ok()
+}
+
 fn extract_error_telemetry(gen_span: &Span, error_message: &str) {
     gen_span.set_attribute("otel.status_code", "ERROR");
     gen_span.set_attribute("error.message", error_message.to_string());
@@ -286,3 +349,54 @@ fn extract_error_telemetry(gen_span: &Span, error_message: &str) {
     });
     error!("stream ended without a final `done` chunk");
 }
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::services::llm::SchemaSpec;
+
+    #[test]
+    fn format_normalizes_one_of_and_nullable() {
+        let spec = SchemaSpec::from_value(json!({
+            "type": "object",
+            "properties": {
+                "value": { "oneOf": [{ "type": "integer" }, { "type": "string" }] },
+                "note": { "type": "string", "nullable": true }
+            }
+        }));
+
+        let format = OllamaClient::format(&spec);
+
+        assert_eq!(
+            format["properties"]["value"],
+            json!({ "type": ["integer", "string"] })
+        );
+        assert_eq!(
+            format["properties"]["note"],
+            json!({ "type": ["string", "null"] })
+        );
+        assert!(format.get("additionalProperties").is_none());
+    }
+
+    #[test]
+    fn context_length_is_read_from_the_model_info() {
+        let show = json!({
+            "details": { "family": "qwen3" },
+            "model_info": {
+                "general.architecture": "qwen3",
+                "qwen3.attention.head_count": 16,
+                "qwen3.context_length": 40960
+            }
+        });
+        assert_eq!(context_length_from_show(&show), Some(40960));
+
+        let without_architecture = json!({ "model_info": { "llama.context_length": 131072 } });
+        assert_eq!(
+            context_length_from_show(&without_architecture),
+            Some(131072)
+        );
+        assert_eq!(context_length_from_show(&json!({ "model_info": {} })), None);
+    }
+}
@@ -4,18 +4,23 @@ use reqwest::{
     Client,
 };
 use serde::{Deserialize, Serialize};
-use serde_json::Value;
+use serde_json::{Map, Value};
 use std::{collections::BTreeMap, pin::Pin};
 use tracing::{debug, instrument};
 
+use super::openai_compat::{
+    finalize_tool_calls, merge_tool_call_deltas, parse_tool_arguments, PartialToolCall,
+    StreamToolCall,
+};
 use crate::{
     services::llm::{
         message::Message,
         models::{
-            base::{InferenceOptions, Role},
+            base::{body_with_extra_options, InferenceOptions, Role},
             chat::{ChatRequest, ChatResponse, ChatStreamChunk},
-            embedding::{EmbeddingsRequest, EmbeddingsResponse},
+            embedding::{EmbedRequest, EmbedResponse, EmbeddingsRequest, EmbeddingsResponse},
             errors::InferenceClientError,
+            schema_normalizer::{normalize_schema, SchemaDialect},
         },
         StructuredOuputFormat,
     },
@@ -30,6 +35,7 @@ pub struct OpenAiClient {
 
 impl OpenAiClient {
     pub fn new(cfg: ClientConfig) -> Result<Self, InferenceClientError> {
+        let http = cfg.http_client_builder()?;
         let base_url = cfg
             .base_url
             .unwrap_or_else(|| "https://api.openai.com/v1".to_string());
@@ -38,12 +44,7 @@ impl OpenAiClient {
         headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
 
         if let Some(api_key) = cfg.api_key {
-            headers.insert(
-                AUTHORIZATION,
-                HeaderValue::from_str(&format!("Bearer {api_key}")).map_err(|e| {
-                    InferenceClientError::Config(format!("Invalid api_key header: {e}"))
-                })?,
-            );
+            headers.insert(AUTHORIZATION, api_key.bearer_header()?);
         }
 
         if let Some(organization) = cfg.organization {
@@ -60,14 +61,16 @@ impl OpenAiClient {
                 let name = HeaderName::from_bytes(k.as_bytes()).map_err(|_| {
                     InferenceClientError::Config(format!("Invalid header name: {k}"))
                 })?;
-                let value = HeaderValue::from_str(&v).map_err(|_| {
+                let mut value = HeaderValue::from_str(&v).map_err(|_| {
                     InferenceClientError::Config(format!("Invalid header value for {k}"))
                 })?;
+                // extra headers often carry credentials
+                value.set_sensitive(true);
                 headers.insert(name, value);
             }
         }
 
-        let client = Client::builder().default_headers(headers).build()?;
+        let client = http.default_headers(headers).build()?;
 
         Ok(Self { client, base_url })
     }
@@ -113,12 +116,11 @@ impl OpenAiClient {
         let text = resp.text().await?;
 
         if !status.is_success() {
-            if let Some(e) = parse_openai_error(&text) {
-                return Err(e);
-            }
-            return Err(InferenceClientError::Api(format!(
-                "Request failed: {status} - {text}"
-            )));
+            let message = match parse_openai_error(&text) {
+                Some(InferenceClientError::Api(message)) => message,
+                _ => format!("Request failed: {status} - {text}"),
+            };
+            return Err(InferenceClientError::Status(status.as_u16(), message));
         }
 
         if let Some(e) = parse_openai_error(&text) {
@@ -139,7 +141,7 @@ impl OpenAiClient {
         let resp = self
             .client
             .post(self.endpoint_url("/chat/completions"))
-            .json(&body)
+            .json(&body_with_extra_options(&body, &body.extra_options)?)
             .send()
             .await?;
         Ok(resp)
@@ -148,6 +150,7 @@ impl OpenAiClient {
     pub async fn chat(&self, req: ChatRequest) -> Result<ChatResponse, InferenceClientError> {
         let mut body = OpenAiChatRequest::from(req);
         body.stream = Some(false);
+        let body = body_with_extra_options(&body, &body.extra_options)?;
         let text = self.post_json("/chat/completions", &body).await?;
 
         let response: OpenAiChatResponse = serde_json::from_str(&text).map_err(|e| {
@@ -191,12 +194,11 @@ impl OpenAiClient {
 
         if !status.is_success() {
             let text = resp.text().await.unwrap_or_default();
-            if let Some(e) = parse_openai_error(&text) {
-                return Err(e);
-            }
-            return Err(InferenceClientError::Api(format!(
-                "Request failed: {status} - {text}"
-            )));
+            let message = match parse_openai_error(&text) {
+                Some(InferenceClientError::Api(message)) => message,
+                _ => format!("Request failed: {status} - {text}"),
+            };
+            return Err(InferenceClientError::Status(status.as_u16(), message));
         }
 
         let byte_stream = resp.bytes_stream();
@@ -327,23 +329,36 @@ impl OpenAiClient {
         &self,
         req: EmbeddingsRequest,
     ) -> Result<EmbeddingsResponse, InferenceClientError> {
+        let response = self.embed(req.into()).await?;
+
+        let Some(embedding) = response.embeddings.into_iter().next() else {
+            return Err(InferenceClientError::Api(
+                "OpenAI embeddings response did not include data".into(),
+            ));
+        };
+
+        Ok(EmbeddingsResponse { embedding })
+    }
+
+    pub async fn embed(&self, req: EmbedRequest) -> Result<EmbedResponse, InferenceClientError> {
         let body = OpenAiEmbeddingsRequest {
             model: req.model,
             input: req.input,
+            dimensions: req.dimensions,
         };
         let text = self.post_json("/embeddings", &body).await?;
         let response: OpenAiEmbeddingsResponse = serde_json::from_str(&text).map_err(|e| {
             InferenceClientError::Serialization(format!("decode error: {e}; raw: {text}"))
         })?;
 
-        let Some(first) = response.data.into_iter().next() else {
-            return Err(InferenceClientError::Api(
-                "OpenAI embeddings response did not include data".into(),
-            ));
-        };
-
-        Ok(EmbeddingsResponse {
-            embedding: first.embedding,
+        let mut data = response.data;
+        data.sort_by_key(|d| d.index);
+        Ok(EmbedResponse {
+            model: response.model,
+            embeddings: data.into_iter().map(|d| d.embedding).collect(),
+            total_duration: None,
+            load_duration: None,
+            prompt_eval_count: response.usage.map(|u| u.prompt_tokens),
         })
     }
 }
@@ -380,8 +395,17 @@ struct OpenAiChatRequest {
     #[serde(skip_serializing_if = "Option::is_none")]
     tools: Option<Vec<Tool>>,
 
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tool_choice: Option<Value>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    parallel_tool_calls: Option<bool>,
+
     #[serde(skip_serializing_if = "Option::is_none")]
     response_format: Option<Value>,
+
+    #[serde(skip)]
+    extra_options: Map<String, Value>,
 }
 
 impl From<ChatRequest> for OpenAiChatRequest {
@@ -390,8 +414,19 @@ impl From<ChatRequest> for OpenAiChatRequest {
             base,
             messages,
             tools,
+            tool_choice,
+            parallel_tool_calls,
         } = value;
         let params = OpenAiClient::map_options(&base.options);
+        // the API rejects `tool_choice` and `parallel_tool_calls` on requests without tools
+        let tool_choice = tool_choice
+            .filter(|_| tools.is_some())
+            .map(|choice| choice.to_openai_value());
+        let parallel_tool_calls = parallel_tool_calls.filter(|_| tools.is_some());
+        let extra_options = base
+            .options
+            .map(|options| options.extra_options)
+            .unwrap_or_default();
 
         Self {
             model: base.model,
@@ -405,7 +440,10 @@ impl From<ChatRequest> for OpenAiChatRequest {
             seed: params.seed,
             stream: base.stream,
             tools,
+            tool_choice,
+            parallel_tool_calls,
             response_format: base.format,
+            extra_options,
         }
     }
 }
@@ -433,6 +471,9 @@ struct OpenAiMessage {
 
     #[serde(default, skip_serializing_if = "Option::is_none")]
     tool_call_id: Option<String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    name: Option<String>,
 }
 
 impl From<&Message> for OpenAiMessage {
@@ -451,6 +492,7 @@ impl From<&Message> for OpenAiMessage {
                 .as_ref()
                 .map(|calls| calls.iter().map(OpenAiToolCall::from).collect()),
             tool_call_id: message.tool_call_id.clone(),
+            name: message.sender_name_field(),
         }
     }
 }
@@ -533,44 +575,35 @@ struct OpenAiStreamDelta {
     #[serde(rename = "role")]
     _role: Option<String>,
     content: Option<String>,
-    tool_calls: Option<Vec<OpenAiStreamToolCall>>,
-}
-
-#[derive(Deserialize)]
-struct OpenAiStreamToolCall {
-    index: usize,
-    id: Option<String>,
-    #[serde(rename = "type")]
-    _tool_type: Option<String>,
-    function: Option<OpenAiStreamToolCallFunction>,
-}
-
-#[derive(Deserialize)]
-struct OpenAiStreamToolCallFunction {
-    name: Option<String>,
-    arguments: Option<String>,
-}
-
-#[derive(Default)]
-struct PartialToolCall {
-    id: Option<String>,
-    name: Option<String>,
-    arguments: String,
+    tool_calls: Option<Vec<StreamToolCall>>,
 }
 
 #[derive(Serialize)]
 struct OpenAiEmbeddingsRequest {
     model: String,
-    input: String,
+    input: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    dimensions: Option<u32>,
 }
 
 #[derive(Deserialize)]
 struct OpenAiEmbeddingsResponse {
+    #[serde(default)]
+    model: String,
     data: Vec<OpenAiEmbeddingData>,
+    #[serde(default)]
+    usage: Option<OpenAiEmbeddingUsage>,
+}
+
+#[derive(Deserialize)]
+struct OpenAiEmbeddingUsage {
+    prompt_tokens: u32,
 }
 
 #[derive(Deserialize)]
 struct OpenAiEmbeddingData {
+    #[serde(default)]
+    index: usize,
     embedding: Vec<f64>,
 }
 
@@ -617,47 +650,6 @@ fn tool_call_from_openai(call: OpenAiToolCall) -> ToolCall {
     }
 }
 
-fn merge_tool_call_deltas(
-    partials: &mut BTreeMap<usize, PartialToolCall>,
-    deltas: Vec<OpenAiStreamToolCall>,
-) {
-    for delta in deltas {
-        let partial = partials.entry(delta.index).or_default();
-        if let Some(id) = delta.id {
-            partial.id = Some(id);
-        }
-        if let Some(function) = delta.function {
-            if let Some(name) = function.name {
-                partial.name = Some(name);
-            }
-            if let Some(arguments) = function.arguments {
-                partial.arguments.push_str(&arguments);
-            }
-        }
-    }
-}
-
-fn finalize_tool_calls(partials: &BTreeMap<usize, PartialToolCall>) -> Vec<ToolCall> {
-    partials
-        .values()
-        .map(|partial| ToolCall {
-            id: partial.id.clone(),
-            tool_type: ToolType::Function,
-            function: ToolCallFunction {
-                name: partial.name.clone().unwrap_or_default(),
-                arguments: parse_tool_arguments(&partial.arguments),
-            },
-        })
-        .collect()
-}
-
-fn parse_tool_arguments(arguments: &str) -> Value {
-    if arguments.trim().is_empty() {
-        return Value::Object(Default::default());
-    }
-    serde_json::from_str(arguments).unwrap_or_else(|_| Value::String(arguments.to_string()))
-}
-
 fn done_stream_chunk(
     model: String,
     created_at: String,
@@ -707,7 +699,12 @@ impl StructuredOuputFormat for OpenAiClient {
             "json_schema": {
                 "name": spec.name.clone().unwrap_or_else(|| "schema".to_string()),
                 "strict": spec.strict.unwrap_or(false),
-                "schema": spec.schema
+                "schema": normalize_schema(
+                    &spec.schema,
+                    SchemaDialect::OpenAi {
+                        strict: spec.strict.unwrap_or(false),
+                    },
+                ),
             }
         })
     }
@@ -718,6 +715,29 @@ mod tests {
     use super::*;
     use crate::services::llm::models::base::BaseRequest;
 
+    #[test]
+    fn strict_format_requires_all_properties() {
+        let spec = crate::services::llm::SchemaSpec::from_value(serde_json::json!({
+            "type": "object",
+            "properties": {
+                "city": { "type": "string" },
+                "unit": { "oneOf": [{ "const": "c" }, { "const": "f" }] }
+            },
+            "required": ["city"]
+        }))
+        .strict(true);
+
+        let format = OpenAiClient::format(&spec);
+        let schema = &format["json_schema"]["schema"];
+
+        assert_eq!(schema["required"], serde_json::json!(["city", "unit"]));
+        assert_eq!(schema["additionalProperties"], false);
+        assert_eq!(
+            schema["properties"]["unit"]["anyOf"],
+            serde_json::json!([{ "const": "c" }, { "const": "f" }, { "type": "null" }])
+        );
+    }
+
     #[test]
     fn openai_client_allows_local_endpoint_without_api_key() {
         let client = OpenAiClient::new(ClientConfig {
@@ -735,6 +755,7 @@ mod tests {
             base: BaseRequest {
                 model: "DeepSeek-V4-Flash".into(),
                 format: None,
+                grammar: None,
                 options: Some(InferenceOptions {
                     temperature: Some(0.2),
                     top_k: Some(40),
@@ -746,6 +767,8 @@ mod tests {
             },
             messages: vec![Message::system("Be concise."), Message::user("Say hi.")],
             tools: None,
+            tool_choice: None,
+            parallel_tool_calls: None,
         };
 
         let body = serde_json::to_value(OpenAiChatRequest::from(request)).unwrap();
@@ -757,6 +780,67 @@ mod tests {
         assert!(body.get("keep_alive").is_none());
     }
 
+    #[test]
+    fn extra_options_are_added_to_the_body() {
+        let mut options = InferenceOptions {
+            temperature: Some(0.2),
+            ..Default::default()
+        };
+        options
+            .extra_options
+            .insert("reasoning_effort".into(), "low".into());
+        options
+            .extra_options
+            .insert("temperature".into(), 0.0.into());
+        let request = ChatRequest {
+            base: BaseRequest {
+                model: "o4-mini".into(),
+                options: Some(options),
+                ..Default::default()
+            },
+            messages: vec![Message::user("Say hi.")],
+            tools: None,
+            tool_choice: None,
+            parallel_tool_calls: None,
+        };
+
+        let body = OpenAiChatRequest::from(request);
+        let body = body_with_extra_options(&body, &body.extra_options).unwrap();
+
+        assert_eq!(body["reasoning_effort"], "low");
+        assert_eq!(body["temperature"], 0.0);
+        assert!(body.get("extra_options").is_none());
+    }
+
+    #[test]
+    fn tool_choice_is_only_sent_with_tools() {
+        let tool = crate::ToolBuilder::new()
+            .function_name("get_weather")
+            .function_description("Returns the weather")
+            .executor_fn(|_| async { Ok::<_, crate::ToolExecutionError>(String::new()) })
+            .build()
+            .unwrap();
+        let request = |tools: Option<Vec<Tool>>| ChatRequest {
+            base: BaseRequest {
+                model: "gpt-4o-mini".into(),
+                ..Default::default()
+            },
+            messages: vec![Message::user("Weather in Koper?")],
+            tools,
+            tool_choice: Some(crate::ToolChoice::named("get_weather")),
+            parallel_tool_calls: Some(false),
+        };
+
+        let body =
+            serde_json::to_value(OpenAiChatRequest::from(request(Some(vec![tool])))).unwrap();
+        assert_eq!(body["tool_choice"]["function"]["name"], "get_weather");
+        assert_eq!(body["parallel_tool_calls"], false);
+
+        let body = serde_json::to_value(OpenAiChatRequest::from(request(None))).unwrap();
+        assert!(body.get("tool_choice").is_none());
+        assert!(body.get("parallel_tool_calls").is_none());
+    }
+
     #[test]
     fn response_tool_arguments_are_parsed_as_json() {
         let call = OpenAiToolCall {
@@ -0,0 +1,73 @@
+//! Pieces of the OpenAI chat completions format shared by the providers
+//! that speak it.
+
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+use crate::{ToolCall, ToolCallFunction, ToolType};
+
+/// Tool calls arrive in pieces, keyed by `index`: the id and name first,
+/// then the arguments string in fragments.
+#[derive(Deserialize)]
+pub(super) struct StreamToolCall {
+    index: usize,
+    id: Option<String>,
+    function: Option<StreamToolCallFunction>,
+}
+
+#[derive(Deserialize)]
+struct StreamToolCallFunction {
+    name: Option<String>,
+    arguments: Option<String>,
+}
+
+#[derive(Default)]
+pub(super) struct PartialToolCall {
+    id: Option<String>,
+    name: Option<String>,
+    arguments: String,
+}
+
+pub(super) fn merge_tool_call_deltas(
+    partials: &mut BTreeMap<usize, PartialToolCall>,
+    deltas: Vec<StreamToolCall>,
+) {
+    for delta in deltas {
+        let partial = partials.entry(delta.index).or_default();
+        if let Some(id) = delta.id {
+            partial.id = Some(id);
+        }
+        if let Some(function) = delta.function {
+            if let Some(name) = function.name {
+                partial.name = Some(name);
+            }
+            if let Some(arguments) = function.arguments {
+                partial.arguments.push_str(&arguments);
+            }
+        }
+    }
+}
+
+pub(super) fn finalize_tool_calls(partials: &BTreeMap<usize, PartialToolCall>) -> Vec<ToolCall> {
+    partials
+        .values()
+        .map(|partial| ToolCall {
+            id: partial.id.clone(),
+            tool_type: ToolType::Function,
+            function: ToolCallFunction {
+                name: partial.name.clone().unwrap_or_default(),
+                arguments: parse_tool_arguments(&partial.arguments),
+            },
+        })
+        .collect()
+}
+
+/// Arguments that are not valid JSON are kept as a string, so they can be
+/// repaired before the tool is called.
+pub(super) fn parse_tool_arguments(arguments: &str) -> Value {
+    if arguments.trim().is_empty() {
+        return Value::Object(Default::default());
+    }
+    serde_json::from_str(arguments).unwrap_or_else(|_| Value::String(arguments.to_string()))
+}
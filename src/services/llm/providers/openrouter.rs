@@ -4,31 +4,164 @@ use reqwest::{
     Client,
 };
 use serde::{Deserialize, Serialize};
-use std::pin::Pin;
+use serde_json::{Map, Value};
+use std::{collections::BTreeMap, pin::Pin};
 use tracing::{debug, instrument};
 
+use super::openai_compat::{
+    finalize_tool_calls, merge_tool_call_deltas, parse_tool_arguments, PartialToolCall,
+    StreamToolCall,
+};
+use crate::services::llm::models::base::body_with_extra_options;
 use crate::services::llm::models::errors::InferenceClientError;
+use crate::services::llm::models::image::image_data_url;
+use crate::services::llm::models::schema_normalizer::{normalize_schema, SchemaDialect};
 use crate::services::llm::{
     message::Message,
-    models::embedding::{EmbeddingsRequest, EmbeddingsResponse},
+    models::embedding::{EmbedRequest, EmbedResponse, EmbeddingsRequest, EmbeddingsResponse},
 };
 use crate::services::llm::{
     models::chat::{ChatRequest, ChatResponse, ChatStreamChunk},
     StructuredOuputFormat,
 };
+use crate::services::runtime::Instant;
 use crate::{
     services::llm::models::base::{InferenceOptions, Role},
-    ClientConfig,
+    ClientConfig, ToolCall, ToolCallFunction, ToolType,
 };
 
 #[derive(Debug, Clone)]
 pub struct OpenRouterClient {
     client: Client,
     base_url: String,
+    route_prefs: Option<OpenRouterRoutePrefs>,
+}
+
+/// Which upstream providers OpenRouter may route a request to, fallback
+/// models and prompt transforms, sent with every request. Unset fields are
+/// left to OpenRouter's defaults.
+///
+/// ```
+/// use reagent_rs::OpenRouterRoutePrefs;
+///
+/// let prefs = OpenRouterRoutePrefs::default()
+///     .order(["anthropic", "google-vertex"])
+///     .ignore(["deepinfra"])
+///     .allow_fallbacks(false)
+///     .transforms(["middle-out"]);
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct OpenRouterRoutePrefs {
+    /// Providers to try first, in order.
+    pub order: Option<Vec<String>>,
+    /// Whether other providers may be used when the preferred ones fail.
+    pub allow_fallbacks: Option<bool>,
+    /// Only route to these providers.
+    pub only: Option<Vec<String>>,
+    /// Never route to these providers.
+    pub ignore: Option<Vec<String>>,
+    /// Only route to providers supporting every parameter of the request.
+    pub require_parameters: Option<bool>,
+    /// `"allow"` or `"deny"` providers that store or train on prompts.
+    pub data_collection: Option<String>,
+    /// Prefer the cheapest (`"price"`), fastest (`"throughput"`) or
+    /// quickest to respond (`"latency"`) provider.
+    pub sort: Option<String>,
+    /// Models to fall back to when the agent's model is unavailable.
+    pub models: Option<Vec<String>>,
+    /// Prompt transforms, e.g. `"middle-out"` to compress prompts that
+    /// exceed the context.
+    pub transforms: Option<Vec<String>>,
+}
+
+impl OpenRouterRoutePrefs {
+    pub fn order<I, S>(mut self, providers: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.order = Some(providers.into_iter().map(Into::into).collect());
+        self
+    }
+
+    pub fn allow_fallbacks(mut self, allow: bool) -> Self {
+        self.allow_fallbacks = Some(allow);
+        self
+    }
+
+    pub fn only<I, S>(mut self, providers: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.only = Some(providers.into_iter().map(Into::into).collect());
+        self
+    }
+
+    pub fn ignore<I, S>(mut self, providers: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.ignore = Some(providers.into_iter().map(Into::into).collect());
+        self
+    }
+
+    pub fn require_parameters(mut self, require: bool) -> Self {
+        self.require_parameters = Some(require);
+        self
+    }
+
+    pub fn deny_data_collection(mut self) -> Self {
+        self.data_collection = Some("deny".into());
+        self
+    }
+
+    pub fn sort(mut self, sort: impl Into<String>) -> Self {
+        self.sort = Some(sort.into());
+        self
+    }
+
+    pub fn fallback_models<I, S>(mut self, models: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.models = Some(models.into_iter().map(Into::into).collect());
+        self
+    }
+
+    pub fn transforms<I, S>(mut self, transforms: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.transforms = Some(transforms.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Set the `provider`, `models` and `transforms` fields of `body`.
+    fn apply(&self, body: &mut OrChatRequest) {
+        let provider = OrProviderPrefs {
+            order: self.order.clone(),
+            allow_fallbacks: self.allow_fallbacks,
+            only: self.only.clone(),
+            ignore: self.ignore.clone(),
+            require_parameters: self.require_parameters,
+            data_collection: self.data_collection.clone(),
+            sort: self.sort.clone(),
+        };
+        if provider != OrProviderPrefs::default() {
+            body.provider = Some(provider);
+        }
+        body.models = self.models.clone();
+        body.transforms = self.transforms.clone();
+    }
 }
 
 impl OpenRouterClient {
     pub fn new(cfg: ClientConfig) -> Result<Self, InferenceClientError> {
+        let http = cfg.http_client_builder()?;
         let api_key = cfg
             .api_key
             .ok_or_else(|| InferenceClientError::Config("OpenRouter requires api_key".into()))?;
@@ -37,12 +170,7 @@ impl OpenRouterClient {
             .unwrap_or_else(|| "https://openrouter.ai/api/v1".to_string());
 
         let mut headers = HeaderMap::new();
-        headers.insert(
-            AUTHORIZATION,
-            HeaderValue::from_str(&format!("Bearer {api_key}")).map_err(|e| {
-                InferenceClientError::Config(format!("Invalid api_key header: {e}"))
-            })?,
-        );
+        headers.insert(AUTHORIZATION, api_key.bearer_header()?);
         headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
 
         if let Some(extra) = cfg.extra_headers {
@@ -50,21 +178,27 @@ impl OpenRouterClient {
                 let name = HeaderName::from_bytes(k.as_bytes()).map_err(|_| {
                     InferenceClientError::Config(format!("Invalid header name: {k}"))
                 })?;
-                let value = HeaderValue::from_str(&v).map_err(|_| {
+                let mut value = HeaderValue::from_str(&v).map_err(|_| {
                     InferenceClientError::Config(format!("Invalid header value for {k}"))
                 })?;
+                // extra headers often carry credentials
+                value.set_sensitive(true);
                 headers.insert(name, value);
             }
         }
 
-        let client = Client::builder().default_headers(headers).build()?;
+        let client = http.default_headers(headers).build()?;
 
-        Ok(Self { client, base_url })
+        Ok(Self {
+            client,
+            base_url,
+            route_prefs: cfg.openrouter_route_prefs,
+        })
     }
 
-    fn map_messages(msgs: &[Message]) -> Vec<OrMessage> {
+    fn map_messages(msgs: &[Message]) -> Vec<OrRequestMessage> {
         msgs.iter()
-            .map(|m| OrMessage {
+            .map(|m| OrRequestMessage {
                 role: match m.role {
                     Role::System => "system".to_string(),
                     Role::Developer => "system".to_string(),
@@ -72,11 +206,34 @@ impl OpenRouterClient {
                     Role::Assistant => "assistant".to_string(),
                     Role::Tool => "tool".to_string(),
                 },
-                content: m.content.clone().unwrap_or_default(),
+                content: Self::map_content(m),
+                name: m.sender_name_field(),
+                tool_calls: m
+                    .tool_calls
+                    .as_ref()
+                    .map(|calls| calls.iter().map(OrToolCall::from).collect()),
+                tool_call_id: m.tool_call_id.clone(),
             })
             .collect()
     }
 
+    /// Messages with images are sent as content parts, text first.
+    fn map_content(m: &Message) -> OrContent {
+        let text = m.content.clone().unwrap_or_default();
+        match &m.images {
+            Some(images) if !images.is_empty() => {
+                let mut parts = vec![OrContentPart::Text { text }];
+                parts.extend(images.iter().map(|image| OrContentPart::ImageUrl {
+                    image_url: OrImageUrl {
+                        url: image_data_url(image),
+                    },
+                }));
+                OrContent::Parts(parts)
+            }
+            _ => OrContent::Text(text),
+        }
+    }
+
     fn map_options(opts: &Option<InferenceOptions>) -> OrParams {
         let mut p = OrParams::default();
         if let Some(o) = opts {
@@ -101,23 +258,35 @@ impl OpenRouterClient {
         let url = format!("{}/chat/completions", self.base_url.trim_end_matches('/'));
         let mut body = OrChatRequest::from(req);
         body.stream = Some(stream);
-        let resp = self.client.post(url).json(&body).send().await?;
+        if stream {
+            // usage is reported in the last chunk before `[DONE]`
+            body.usage = Some(OrUsageConfig { include: true });
+        }
+        if let Some(prefs) = &self.route_prefs {
+            prefs.apply(&mut body);
+        }
+        let resp = self
+            .client
+            .post(url)
+            .json(&body_with_extra_options(&body, &body.extra_options)?)
+            .send()
+            .await?;
         Ok(resp)
     }
 
     pub async fn chat(&self, req: ChatRequest) -> Result<ChatResponse, InferenceClientError> {
+        let started = Instant::now();
         let resp = self.chat_inner(req, false).await?;
         let status = resp.status();
         let text = resp.text().await?;
 
         // HTTP error
         if !status.is_success() {
-            if let Some(e) = parse_oopen_router_error(&text) {
-                return Err(e);
-            }
-            return Err(InferenceClientError::Api(format!(
-                "Request failed: {status} - {text}"
-            )));
+            let message = match parse_oopen_router_error(&text) {
+                Some(InferenceClientError::Api(message)) => message,
+                _ => format!("Request failed: {status} - {text}"),
+            };
+            return Err(InferenceClientError::Status(status.as_u16(), message));
         }
 
         // HTTP 200 but body is an error envelope
@@ -129,23 +298,26 @@ impl OpenRouterClient {
             InferenceClientError::Serialization(format!("decode error: {e}; raw: {text}"))
         })?;
 
-        let message = or
-            .choices
-            .first()
-            .map(|c| Message::assistant(c.message.content.clone()))
-            .unwrap_or(Message::assistant(String::new()));
+        let mut choices = or.choices.into_iter();
+        let (message, done_reason) = match choices.next() {
+            Some(choice) => (
+                message_from_openrouter(choice.message),
+                choice.finish_reason,
+            ),
+            None => (Message::assistant(String::new()), None),
+        };
 
         Ok(ChatResponse {
             model: or.model,
             created_at: or.created.to_string(),
             message,
             done: true,
-            done_reason: or.choices.first().and_then(|c| c.finish_reason.clone()),
-            total_duration: None,
+            done_reason,
+            total_duration: Some(elapsed_nanos(started)),
             load_duration: None,
-            prompt_eval_count: None,
+            prompt_eval_count: or.usage.as_ref().map(|u| u.prompt_tokens),
             prompt_eval_duration: None,
-            eval_count: None,
+            eval_count: or.usage.as_ref().map(|u| u.completion_tokens),
             eval_duration: None,
         })
     }
@@ -158,22 +330,28 @@ impl OpenRouterClient {
         InferenceClientError,
     > {
         use async_stream::try_stream;
+        let started = Instant::now();
         let resp = self.chat_inner(req, true).await?;
         let status = resp.status();
 
         if !status.is_success() {
             let text = resp.text().await.unwrap_or_default();
-            if let Some(e) = parse_oopen_router_error(&text) {
-                return Err(e);
-            }
-            return Err(InferenceClientError::Api(format!(
-                "Request failed: {status} - {text}"
-            )));
+            let message = match parse_oopen_router_error(&text) {
+                Some(InferenceClientError::Api(message)) => message,
+                _ => format!("Request failed: {status} - {text}"),
+            };
+            return Err(InferenceClientError::Status(status.as_u16(), message));
         }
 
         let byte_stream = resp.bytes_stream();
         let s = try_stream! {
             let mut buf = Vec::<u8>::new();
+            let mut partial_tool_calls = BTreeMap::<usize, PartialToolCall>::new();
+            let mut latest_model = String::new();
+            let mut latest_created = String::new();
+            let mut done_reason: Option<String> = None;
+            let mut usage: Option<OrUsage> = None;
+            let mut first_token: Option<Instant> = None;
             futures::pin_mut!(byte_stream);
 
             while let Some(chunk) = byte_stream.next().await {
@@ -190,19 +368,21 @@ impl OpenRouterClient {
                     let data = line[5..].trim();
 
                     if data.contains("[DONE]") {
-                        yield ChatStreamChunk {
-                            model: String::new(),
-                            created_at: String::new(),
-                            message: None,
-                            done: true,
-                            done_reason: Some("stop".into()),
-                            total_duration: None,
-                            load_duration: None,
-                            prompt_eval_count: None,
-                            prompt_eval_duration: None,
-                            eval_count: None,
-                            eval_duration: None,
-                        };
+                        if !partial_tool_calls.is_empty() {
+                            yield tool_calls_chunk(
+                                &partial_tool_calls,
+                                latest_model.clone(),
+                                latest_created.clone(),
+                            );
+                        }
+                        yield final_chunk(
+                            latest_model,
+                            latest_created,
+                            done_reason.or_else(|| Some("stop".into())),
+                            usage.as_ref(),
+                            started,
+                            first_token,
+                        );
                         return;
                     }
 
@@ -216,16 +396,34 @@ impl OpenRouterClient {
                         Err(e) => { debug!(err = %e, raw = %data, "stream json decode error"); continue; }
                     };
 
+                    if let Some(model) = parsed.model {
+                        latest_model = model;
+                    }
+                    if let Some(created) = parsed.created {
+                        latest_created = created.to_string();
+                    }
+                    if parsed.usage.is_some() {
+                        usage = parsed.usage;
+                    }
+                    if parsed.choices.is_empty() {
+                        continue;
+                    }
+                    first_token.get_or_insert_with(Instant::now);
+
                     let mut out_msg: Option<Message> = None;
-                    if let Some(choice) = parsed.choices.first() {
-                        if let Some(content) = choice.delta.content.clone() {
-                            out_msg = Some(Message::assistant(content));
+                    if let Some(choice) = parsed.choices.into_iter().next() {
+                        if let Some(reason) = choice.finish_reason {
+                            done_reason = Some(reason);
+                        }
+                        if let Some(tool_calls) = choice.delta.tool_calls {
+                            merge_tool_call_deltas(&mut partial_tool_calls, tool_calls);
                         }
+                        out_msg = choice.delta.content.map(Message::assistant);
                     }
 
                     yield ChatStreamChunk {
-                        model: parsed.model,
-                        created_at: parsed.created.to_string(),
+                        model: latest_model.clone(),
+                        created_at: latest_created.clone(),
                         message: out_msg,
                         done: false,
                         done_reason: None,
@@ -239,19 +437,21 @@ impl OpenRouterClient {
                 }
             }
 
-            yield ChatStreamChunk {
-                model: String::new(),
-                created_at: String::new(),
-                message: None,
-                done: true,
-                done_reason: Some("eof".into()),
-                total_duration: None,
-                load_duration: None,
-                prompt_eval_count: None,
-                prompt_eval_duration: None,
-                eval_count: None,
-                eval_duration: None,
-            };
+            if !partial_tool_calls.is_empty() {
+                yield tool_calls_chunk(
+                    &partial_tool_calls,
+                    latest_model.clone(),
+                    latest_created.clone(),
+                );
+            }
+            yield final_chunk(
+                latest_model,
+                latest_created,
+                done_reason.or_else(|| Some("eof".into())),
+                usage.as_ref(),
+                started,
+                first_token,
+            );
         };
 
         Ok(Box::pin(s))
@@ -266,12 +466,18 @@ impl OpenRouterClient {
             "OpenRouter embeddings are not available".into(),
         ))
     }
+
+    pub async fn embed(&self, _req: EmbedRequest) -> Result<EmbedResponse, InferenceClientError> {
+        Err(InferenceClientError::Unsupported(
+            "OpenRouter embeddings are not available".into(),
+        ))
+    }
 }
 
 #[derive(Serialize, Default)]
 struct OrChatRequest {
     model: String,
-    messages: Vec<OrMessage>,
+    messages: Vec<OrRequestMessage>,
 
     #[serde(skip_serializing_if = "Option::is_none")]
     temperature: Option<f32>,
@@ -335,6 +541,50 @@ struct OrChatRequest {
 
     #[serde(skip_serializing_if = "Option::is_none")]
     verbosity: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    provider: Option<OrProviderPrefs>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    models: Option<Vec<String>>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    transforms: Option<Vec<String>>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    usage: Option<OrUsageConfig>,
+
+    #[serde(skip)]
+    extra_options: Map<String, Value>,
+}
+
+#[derive(Serialize, Default)]
+struct OrUsageConfig {
+    include: bool,
+}
+
+#[derive(Serialize, Default, PartialEq)]
+struct OrProviderPrefs {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    order: Option<Vec<String>>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    allow_fallbacks: Option<bool>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    only: Option<Vec<String>>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    ignore: Option<Vec<String>>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    require_parameters: Option<bool>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    data_collection: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    sort: Option<String>,
 }
 impl From<ChatRequest> for OrChatRequest {
     fn from(value: ChatRequest) -> Self {
@@ -342,8 +592,14 @@ impl From<ChatRequest> for OrChatRequest {
             base,
             messages,
             tools,
+            tool_choice,
+            parallel_tool_calls,
         } = value;
         let params = OpenRouterClient::map_options(&base.options);
+        let tool_choice = tool_choice
+            .filter(|_| tools.is_some())
+            .map(|choice| choice.to_openai_value());
+        let parallel_tool_calls = parallel_tool_calls.filter(|_| tools.is_some());
 
         let stop_vec = match base.options.as_ref().and_then(|o| o.stop.clone()) {
             Some(s) => Some(vec![s]), // until you migrate to Vec<String> in your shared model
@@ -352,7 +608,7 @@ impl From<ChatRequest> for OrChatRequest {
 
         Self {
             model: base.model,
-            messages: OpenRouterClient::map_messages(&messages), // enhance for multimodal
+            messages: OpenRouterClient::map_messages(&messages),
             temperature: params.temperature,
             top_p: params.top_p,
             top_k: params.top_k,
@@ -369,11 +625,19 @@ impl From<ChatRequest> for OrChatRequest {
             stop: stop_vec,
             stream: base.stream,
             tools: tools.and_then(|t| serde_json::to_value(t).ok()),
-            tool_choice: None,
-            parallel_tool_calls: None,
+            tool_choice,
+            parallel_tool_calls,
             response_format: base.format,
             structured_outputs: None,
             verbosity: None,
+            provider: None,
+            models: None,
+            transforms: None,
+            usage: None,
+            extra_options: base
+                .options
+                .map(|options| options.extra_options)
+                .unwrap_or_default(),
         }
     }
 }
@@ -389,10 +653,73 @@ struct OrParams {
     stop: Option<String>,
 }
 
+#[derive(Serialize)]
+struct OrRequestMessage {
+    role: String,
+    content: OrContent,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tool_calls: Option<Vec<OrToolCall>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tool_call_id: Option<String>,
+}
+
+#[derive(Serialize)]
+#[serde(untagged)]
+enum OrContent {
+    Text(String),
+    Parts(Vec<OrContentPart>),
+}
+
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum OrContentPart {
+    Text { text: String },
+    ImageUrl { image_url: OrImageUrl },
+}
+
+#[derive(Serialize)]
+struct OrImageUrl {
+    url: String,
+}
+
 #[derive(Serialize, Deserialize, Clone)]
+struct OrToolCall {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    id: Option<String>,
+    #[serde(rename = "type", default = "function_tool_type")]
+    tool_type: String,
+    function: OrToolCallFunction,
+}
+
+impl From<&ToolCall> for OrToolCall {
+    fn from(call: &ToolCall) -> Self {
+        Self {
+            id: call.id.clone(),
+            tool_type: function_tool_type(),
+            function: OrToolCallFunction {
+                name: call.function.name.clone(),
+                arguments: call.function.arguments.to_string(),
+            },
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+struct OrToolCallFunction {
+    name: String,
+    // a JSON encoded string, as in the OpenAI API
+    #[serde(default)]
+    arguments: String,
+}
+
+#[derive(Deserialize)]
 struct OrMessage {
-    role: String,
-    content: String,
+    #[serde(rename = "role")]
+    _role: Option<String>,
+    content: Option<String>,
+    tool_calls: Option<Vec<OrToolCall>>,
 }
 
 #[derive(Deserialize)]
@@ -409,26 +736,120 @@ struct OrChatResponse {
     object: String,
     created: u64,
     choices: Vec<OrChoice>,
+    usage: Option<OrUsage>,
+}
+
+#[derive(Deserialize)]
+struct OrUsage {
+    prompt_tokens: u32,
+    completion_tokens: u32,
 }
 
 #[derive(Deserialize)]
 struct OrDelta {
+    #[serde(rename = "role")]
     _role: Option<String>,
     content: Option<String>,
+    tool_calls: Option<Vec<StreamToolCall>>,
 }
 
 #[derive(Deserialize)]
 struct OrDeltaChoice {
     delta: OrDelta,
-    _finish_reason: Option<String>,
+    finish_reason: Option<String>,
 }
 
 #[derive(Deserialize)]
 struct OrStreamChunk {
-    _id: String,
-    created: u64,
-    model: String,
+    #[serde(rename = "id")]
+    _id: Option<String>,
+    created: Option<u64>,
+    model: Option<String>,
+    #[serde(default)]
     choices: Vec<OrDeltaChoice>,
+    usage: Option<OrUsage>,
+}
+
+fn function_tool_type() -> String {
+    "function".to_string()
+}
+
+fn message_from_openrouter(message: OrMessage) -> Message {
+    let mut out = Message::assistant(String::new());
+    out.content = message.content;
+    out.tool_calls = message
+        .tool_calls
+        .filter(|calls| !calls.is_empty())
+        .map(|calls| {
+            calls
+                .into_iter()
+                .map(|call| ToolCall {
+                    id: call.id,
+                    tool_type: ToolType::Function,
+                    function: ToolCallFunction {
+                        name: call.function.name,
+                        arguments: parse_tool_arguments(&call.function.arguments),
+                    },
+                })
+                .collect()
+        });
+    out
+}
+
+/// Chunk with the tool calls assembled from the deltas, sent before the
+/// final chunk.
+fn tool_calls_chunk(
+    partials: &BTreeMap<usize, PartialToolCall>,
+    model: String,
+    created_at: String,
+) -> ChatStreamChunk {
+    let mut message = Message::assistant(String::new());
+    message.content = None;
+    message.tool_calls = Some(finalize_tool_calls(partials));
+    ChatStreamChunk {
+        model,
+        created_at,
+        message: Some(message),
+        done: false,
+        done_reason: None,
+        total_duration: None,
+        load_duration: None,
+        prompt_eval_count: None,
+        prompt_eval_duration: None,
+        eval_count: None,
+        eval_duration: None,
+    }
+}
+
+/// Last chunk of a stream, with the token counts of the usage block.
+/// OpenRouter reports no timings, so the total duration is measured from
+/// sending the request and the eval duration from the first delta.
+fn final_chunk(
+    model: String,
+    created_at: String,
+    done_reason: Option<String>,
+    usage: Option<&OrUsage>,
+    started: Instant,
+    first_token: Option<Instant>,
+) -> ChatStreamChunk {
+    ChatStreamChunk {
+        model,
+        created_at,
+        message: None,
+        done: true,
+        done_reason,
+        total_duration: Some(elapsed_nanos(started)),
+        load_duration: None,
+        prompt_eval_count: usage.map(|u| u.prompt_tokens),
+        prompt_eval_duration: None,
+        eval_count: usage.map(|u| u.completion_tokens),
+        eval_duration: first_token.map(elapsed_nanos),
+    }
+}
+
+/// Durations are reported in nanoseconds, as by Ollama.
+fn elapsed_nanos(since: Instant) -> u64 {
+    u64::try_from(since.elapsed().as_nanos()).unwrap_or(u64::MAX)
 }
 
 #[derive(Deserialize, Debug)]
@@ -468,8 +889,194 @@ impl StructuredOuputFormat for OpenRouterClient {
             "json_schema": {
                 "name": spec.name.clone().unwrap_or_else(|| "schema".to_string()),
                 "strict": spec.strict.unwrap_or(false),
-                "schema": spec.schema
+                "schema": normalize_schema(
+                    &spec.schema,
+                    SchemaDialect::OpenAi {
+                        strict: spec.strict.unwrap_or(false),
+                    },
+                ),
             }
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::services::llm::SchemaSpec;
+
+    #[test]
+    fn format_merges_all_of_without_strict_rewrites() {
+        let spec = SchemaSpec::from_value(json!({
+            "allOf": [
+                { "type": "object", "properties": { "city": { "type": "string" } } },
+                { "properties": { "days": { "type": "integer", "nullable": true } } }
+            ]
+        }));
+
+        let format = OpenRouterClient::format(&spec);
+        let schema = &format["json_schema"]["schema"];
+
+        assert_eq!(format["json_schema"]["strict"], false);
+        assert_eq!(
+            schema,
+            &json!({
+                "type": "object",
+                "properties": {
+                    "city": { "type": "string" },
+                    "days": { "type": ["integer", "null"] }
+                }
+            })
+        );
+    }
+    #[test]
+    fn route_prefs_are_sent_with_the_request() {
+        let mut body = OrChatRequest {
+            model: "openai/gpt-4o".into(),
+            ..Default::default()
+        };
+        OpenRouterRoutePrefs::default()
+            .order(["openai", "azure"])
+            .allow_fallbacks(false)
+            .deny_data_collection()
+            .fallback_models(["anthropic/claude-3.5-sonnet"])
+            .transforms(["middle-out"])
+            .apply(&mut body);
+        let json = serde_json::to_value(&body).unwrap();
+
+        assert_eq!(
+            json["provider"],
+            json!({
+                "order": ["openai", "azure"],
+                "allow_fallbacks": false,
+                "data_collection": "deny"
+            })
+        );
+        assert_eq!(json["models"], json!(["anthropic/claude-3.5-sonnet"]));
+        assert_eq!(json["transforms"], json!(["middle-out"]));
+
+        let mut body = OrChatRequest::default();
+        OpenRouterRoutePrefs::default().apply(&mut body);
+        let json = serde_json::to_value(&body).unwrap();
+        assert!(json.get("provider").is_none());
+    }
+
+    #[test]
+    fn response_tool_calls_are_parsed() {
+        let response: OrChatResponse = serde_json::from_value(json!({
+            "id": "gen-1",
+            "provider": "OpenAI",
+            "model": "openai/gpt-4o",
+            "object": "chat.completion",
+            "created": 1,
+            "choices": [{
+                "finish_reason": "tool_calls",
+                "message": {
+                    "role": "assistant",
+                    "content": null,
+                    "tool_calls": [{
+                        "id": "call_1",
+                        "type": "function",
+                        "function": { "name": "get_weather", "arguments": "{\"city\":\"Ljubljana\"}" }
+                    }]
+                }
+            }]
+        }))
+        .unwrap();
+
+        let message = message_from_openrouter(response.choices.into_iter().next().unwrap().message);
+        let calls = message.tool_calls.unwrap();
+        assert_eq!(message.content, None);
+        assert_eq!(calls[0].id.as_deref(), Some("call_1"));
+        assert_eq!(calls[0].function.name, "get_weather");
+        assert_eq!(calls[0].function.arguments, json!({ "city": "Ljubljana" }));
+    }
+
+    #[test]
+    fn streamed_tool_call_deltas_are_assembled() {
+        let chunks = [
+            r#"{"id":"gen-1","model":"m","created":1,"choices":[{"delta":{"role":"assistant","tool_calls":[{"index":0,"id":"call_1","type":"function","function":{"name":"get_weather","arguments":""}}]}}]}"#,
+            r#"{"id":"gen-1","model":"m","created":1,"choices":[{"delta":{"tool_calls":[{"index":0,"function":{"arguments":"{\"city\":"}}]}}]}"#,
+            r#"{"id":"gen-1","model":"m","created":1,"choices":[{"delta":{"tool_calls":[{"index":0,"function":{"arguments":"\"Ljubljana\"}"}}]},"finish_reason":"tool_calls"}]}"#,
+        ];
+        let mut partials = BTreeMap::new();
+        for chunk in chunks {
+            let chunk: OrStreamChunk = serde_json::from_str(chunk).unwrap();
+            for choice in chunk.choices {
+                merge_tool_call_deltas(&mut partials, choice.delta.tool_calls.unwrap());
+            }
+        }
+
+        let chunk = tool_calls_chunk(&partials, "m".into(), "1".into());
+        let calls = chunk.message.unwrap().tool_calls.unwrap();
+        assert_eq!(calls.len(), 1);
+        assert_eq!(calls[0].id.as_deref(), Some("call_1"));
+        assert_eq!(calls[0].function.arguments, json!({ "city": "Ljubljana" }));
+    }
+
+    #[test]
+    fn tool_messages_are_sent_with_their_calls() {
+        let mut call = Message::assistant(String::new());
+        call.tool_calls = Some(vec![ToolCall {
+            id: Some("call_1".into()),
+            tool_type: ToolType::Function,
+            function: ToolCallFunction {
+                name: "get_weather".into(),
+                arguments: json!({ "city": "Ljubljana" }),
+            },
+        }]);
+        let result = Message::tool("sunny", "call_1");
+
+        let json = serde_json::to_value(OpenRouterClient::map_messages(&[call, result])).unwrap();
+        assert_eq!(
+            json[0]["tool_calls"][0]["function"]["arguments"],
+            r#"{"city":"Ljubljana"}"#
+        );
+        assert_eq!(json[1]["tool_call_id"], "call_1");
+    }
+
+    #[test]
+    fn stream_usage_is_reported_in_the_final_chunk() {
+        let chunk: OrStreamChunk = serde_json::from_str(
+            r#"{"id":"gen-1","model":"m","created":1,"choices":[],"usage":{"prompt_tokens":12,"completion_tokens":34,"total_tokens":46}}"#,
+        )
+        .unwrap();
+        let started = Instant::now();
+
+        let done = final_chunk(
+            "m".into(),
+            "1".into(),
+            Some("stop".into()),
+            chunk.usage.as_ref(),
+            started,
+            Some(started),
+        );
+        assert!(done.done);
+        assert_eq!(done.prompt_eval_count, Some(12));
+        assert_eq!(done.eval_count, Some(34));
+        assert!(done.total_duration.is_some() && done.eval_duration.is_some());
+    }
+
+    #[test]
+    fn images_are_sent_as_content_parts() {
+        let mut message = Message::user("What is this?");
+        message.images = Some(vec!["iVBORw0KGgo=".into()]);
+
+        let mapped = OpenRouterClient::map_messages(&[Message::system("sys"), message]);
+        let json = serde_json::to_value(&mapped).unwrap();
+
+        assert_eq!(json[0], json!({ "role": "system", "content": "sys" }));
+        assert_eq!(
+            json[1]["content"],
+            json!([
+                { "type": "text", "text": "What is this?" },
+                {
+                    "type": "image_url",
+                    "image_url": { "url": "data:image/png;base64,iVBORw0KGgo=" }
+                }
+            ])
+        );
+    }
+}
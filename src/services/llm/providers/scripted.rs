@@ -0,0 +1,134 @@
+use futures::Stream;
+use std::{
+    collections::VecDeque,
+    pin::Pin,
+    sync::{Arc, Mutex},
+};
+
+use crate::services::llm::models::{
+    chat::{ChatRequest, ChatResponse, ChatStreamChunk},
+    embedding::{EmbedRequest, EmbedResponse, EmbeddingsRequest, EmbeddingsResponse},
+    errors::InferenceClientError,
+    message::Message,
+};
+
+/// Model replies handed out in order instead of asking a provider, for
+/// deterministic tests of flows, see
+/// [`FlowTestHarness`](crate::testing::FlowTestHarness).
+///
+/// Clones share the replies, so sub-agents built from the
+/// [`ClientConfig`](crate::ClientConfig) of an agent take their replies from
+/// the same script.
+#[derive(Debug, Clone, Default)]
+pub struct ModelScript {
+    replies: Arc<Mutex<VecDeque<Message>>>,
+    requests: Arc<Mutex<Vec<ChatRequest>>>,
+    unloads: Arc<Mutex<Vec<String>>>,
+}
+
+impl ModelScript {
+    /// Queue `reply` as the response to the next unanswered request.
+    pub fn push(&self, reply: Message) {
+        self.replies.lock().unwrap().push_back(reply);
+    }
+
+    /// Replies that were not requested yet.
+    pub fn remaining(&self) -> usize {
+        self.replies.lock().unwrap().len()
+    }
+
+    /// Every request answered so far, oldest first.
+    pub fn requests(&self) -> Vec<ChatRequest> {
+        self.requests.lock().unwrap().clone()
+    }
+
+    /// Models unloaded so far, oldest first.
+    pub fn unloads(&self) -> Vec<String> {
+        self.unloads.lock().unwrap().clone()
+    }
+
+    fn answer(&self, req: ChatRequest) -> Result<Message, InferenceClientError> {
+        let reply = self.replies.lock().unwrap().pop_front();
+        self.requests.lock().unwrap().push(req);
+        reply
+            .ok_or_else(|| InferenceClientError::Api("The model script has no replies left".into()))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ScriptedClient {
+    script: ModelScript,
+}
+
+impl ScriptedClient {
+    pub fn new(script: ModelScript) -> Self {
+        Self { script }
+    }
+
+    pub async fn chat(&self, req: ChatRequest) -> Result<ChatResponse, InferenceClientError> {
+        let model = req.base.model.clone();
+        let message = self.script.answer(req)?;
+        Ok(ChatResponse {
+            model,
+            created_at: String::new(),
+            message,
+            done: true,
+            done_reason: Some("stop".into()),
+            total_duration: None,
+            load_duration: None,
+            prompt_eval_count: None,
+            prompt_eval_duration: None,
+            eval_count: None,
+            eval_duration: None,
+        })
+    }
+
+    /// The whole reply arrives as one chunk, followed by the `done` chunk.
+    pub async fn chat_stream(
+        &self,
+        req: ChatRequest,
+    ) -> Result<
+        Pin<Box<dyn Stream<Item = Result<ChatStreamChunk, InferenceClientError>> + Send + 'static>>,
+        InferenceClientError,
+    > {
+        let response = self.chat(req).await?;
+        let chunk = |message: Option<Message>, done: bool| ChatStreamChunk {
+            model: response.model.clone(),
+            created_at: String::new(),
+            message,
+            done,
+            done_reason: done.then(|| "stop".to_string()),
+            total_duration: None,
+            load_duration: None,
+            prompt_eval_count: None,
+            prompt_eval_duration: None,
+            eval_count: None,
+            eval_duration: None,
+        };
+        let chunks = vec![
+            Ok(chunk(Some(response.message.clone()), false)),
+            Ok(chunk(None, true)),
+        ];
+        Ok(Box::pin(futures::stream::iter(chunks)))
+    }
+
+    pub async fn embeddings(
+        &self,
+        _req: EmbeddingsRequest,
+    ) -> Result<EmbeddingsResponse, InferenceClientError> {
+        Err(InferenceClientError::Unsupported(
+            "Scripted models do not produce embeddings".into(),
+        ))
+    }
+
+    pub async fn embed(&self, _req: EmbedRequest) -> Result<EmbedResponse, InferenceClientError> {
+        Err(InferenceClientError::Unsupported(
+            "Scripted models do not produce embeddings".into(),
+        ))
+    }
+
+    pub async fn unload(&self, model: &str) -> Result<(), InferenceClientError> {
+        self.script.unloads.lock().unwrap().push(model.to_string());
+        Ok(())
+    }
+}
@@ -0,0 +1,364 @@
+use std::{
+    collections::HashMap,
+    fmt,
+    pin::Pin,
+    sync::{Arc, Mutex},
+};
+
+use futures::{Stream, StreamExt};
+use regex::Regex;
+use serde_json::Value;
+
+use crate::services::llm::{
+    message::Message,
+    models::{chat::ChatStreamChunk, errors::InferenceClientError},
+};
+
+const EMAIL_PATTERN: &str = r"[A-Za-z0-9._%+-]+@[A-Za-z0-9.-]+\.[A-Za-z]{2,}";
+const PHONE_PATTERN: &str = r"(?:\+\d{1,3}[\s.-]?)?\(?\d{2,4}\)?[\s.-]?\d{3,4}[\s.-]?\d{3,4}";
+
+/// Longest text held back while streaming in case it is the start of a
+/// placeholder split across chunks.
+const MAX_PLACEHOLDER_LEN: usize = 48;
+
+type ChunkStream =
+    Pin<Box<dyn Stream<Item = Result<ChatStreamChunk, InferenceClientError>> + Send + 'static>>;
+
+#[derive(Clone)]
+struct Entity {
+    label: String,
+    pattern: Regex,
+}
+
+/// Placeholders handed out so far, in both directions.
+#[derive(Clone, Default)]
+struct Vault {
+    placeholders: HashMap<String, String>,
+    values: HashMap<String, String>,
+    counts: HashMap<String, usize>,
+}
+
+/// Masks personal data in requests and restores it in responses, e.g. to
+/// keep internal data away from a remote provider.
+///
+/// Matches of the registered entities (emails, phone numbers, custom
+/// patterns) are replaced with placeholders such as `[EMAIL_1]` in every
+/// request. The same value always gets the same placeholder, so the model
+/// can refer to it across turns. Placeholders in replies and tool call
+/// arguments are replaced with the original values, so the history and the
+/// tools see the real data.
+///
+/// Set on the agent with
+/// [`AgentBuilder::set_redactor`](crate::AgentBuilder::set_redactor).
+/// Clones share their placeholders.
+///
+/// ```
+/// use reagent_rs::Redactor;
+///
+/// let redactor = Redactor::new()
+///     .emails()
+///     .add_pattern("ticket", r"TCK-\d+")
+///     .unwrap();
+///
+/// let masked = redactor.mask("Mail alice@corp.internal about TCK-1042");
+/// assert_eq!(masked, "Mail [EMAIL_1] about [TICKET_1]");
+/// assert_eq!(redactor.unmask(&masked), "Mail alice@corp.internal about TCK-1042");
+/// ```
+#[derive(Clone)]
+pub struct Redactor {
+    entities: Arc<Vec<Entity>>,
+    vault: Arc<Mutex<Vault>>,
+    unmask_responses: bool,
+}
+
+impl Default for Redactor {
+    /// Masks emails and phone numbers.
+    fn default() -> Self {
+        Self::new().emails().phone_numbers()
+    }
+}
+
+impl Redactor {
+    /// A redactor without entities, add them with the methods below.
+    pub fn new() -> Self {
+        Self {
+            entities: Arc::new(Vec::new()),
+            vault: Arc::default(),
+            unmask_responses: true,
+        }
+    }
+
+    pub fn emails(self) -> Self {
+        self.with_entity(
+            "email",
+            Regex::new(EMAIL_PATTERN).expect("valid email pattern"),
+        )
+    }
+
+    pub fn phone_numbers(self) -> Self {
+        self.with_entity(
+            "phone",
+            Regex::new(PHONE_PATTERN).expect("valid phone pattern"),
+        )
+    }
+
+    /// Mask matches of `pattern` as `[LABEL_n]`. Entities are masked in the
+    /// order they were added.
+    pub fn add_pattern(self, label: &str, pattern: &str) -> Result<Self, regex::Error> {
+        Ok(self.with_entity(label, Regex::new(pattern)?))
+    }
+
+    /// Whether placeholders in responses are replaced with the original
+    /// values (default: yes). Without it, the history keeps the
+    /// placeholders.
+    pub fn unmask_responses(mut self, unmask: bool) -> Self {
+        self.unmask_responses = unmask;
+        self
+    }
+
+    fn with_entity(mut self, label: &str, pattern: Regex) -> Self {
+        let label = label
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() {
+                    c.to_ascii_uppercase()
+                } else {
+                    '_'
+                }
+            })
+            .collect();
+        Arc::make_mut(&mut self.entities).push(Entity { label, pattern });
+        self
+    }
+
+    /// Replace every entity in `text` with its placeholder.
+    pub fn mask(&self, text: &str) -> String {
+        let mut vault = self.vault.lock().unwrap_or_else(|e| e.into_inner());
+        self.mask_with(&mut vault, text)
+    }
+
+    fn mask_with(&self, vault: &mut Vault, text: &str) -> String {
+        let mut masked = text.to_string();
+        for entity in self.entities.iter() {
+            masked = entity
+                .pattern
+                .replace_all(&masked, |caps: &regex::Captures| {
+                    vault.placeholder(&entity.label, &caps[0])
+                })
+                .into_owned();
+        }
+        masked
+    }
+
+    /// Replace the placeholders in `text` with the original values.
+    /// Unknown placeholders are left as they are.
+    pub fn unmask(&self, text: &str) -> String {
+        let vault = self.vault.lock().unwrap_or_else(|e| e.into_inner());
+        let mut unmasked = String::with_capacity(text.len());
+        let mut rest = text;
+        while let Some(start) = rest.find('[') {
+            unmasked.push_str(&rest[..start]);
+            let candidate = &rest[start..];
+            let value = candidate
+                .find(']')
+                .and_then(|end| Some((vault.values.get(&candidate[..=end])?, end)));
+            match value {
+                Some((value, end)) => {
+                    unmasked.push_str(value);
+                    rest = &candidate[end + 1..];
+                }
+                None => {
+                    unmasked.push('[');
+                    rest = &candidate[1..];
+                }
+            }
+        }
+        unmasked.push_str(rest);
+        unmasked
+    }
+
+    /// Mask the content and tool call arguments of outgoing messages.
+    pub(crate) fn mask_messages(&self, messages: &mut [Message]) {
+        for message in messages {
+            if let Some(content) = &message.content {
+                message.content = Some(self.mask(content));
+            }
+            for call in message.tool_calls.iter_mut().flatten() {
+                map_strings(&mut call.function.arguments, &|s| self.mask(s));
+            }
+        }
+    }
+
+    /// Mask `messages` like [`mask_messages`](Self::mask_messages), without
+    /// handing out placeholders for new values, e.g. to preview a request.
+    pub(crate) fn preview_messages(&self, messages: &mut [Message]) {
+        let vault = Mutex::new(self.vault.lock().unwrap_or_else(|e| e.into_inner()).clone());
+        let mask = |text: &str| {
+            let mut vault = vault.lock().unwrap_or_else(|e| e.into_inner());
+            self.mask_with(&mut vault, text)
+        };
+        for message in messages {
+            if let Some(content) = &message.content {
+                message.content = Some(mask(content));
+            }
+            for call in message.tool_calls.iter_mut().flatten() {
+                map_strings(&mut call.function.arguments, &mask);
+            }
+        }
+    }
+
+    /// Unmask the content and tool call arguments of a reply.
+    pub(crate) fn unmask_message(&self, message: &mut Message) {
+        if !self.unmask_responses {
+            return;
+        }
+        if let Some(content) = &message.content {
+            message.content = Some(self.unmask(content));
+        }
+        for call in message.tool_calls.iter_mut().flatten() {
+            map_strings(&mut call.function.arguments, &|s| self.unmask(s));
+        }
+    }
+
+    /// Unmask a streamed reply. A placeholder may be split over several
+    /// chunks, so text that could start one is held back until the next
+    /// chunk, and sent at the latest before the final chunk.
+    pub(crate) fn unmask_stream(&self, stream: ChunkStream) -> ChunkStream {
+        if !self.unmask_responses {
+            return stream;
+        }
+        let redactor = self.clone();
+        Box::pin(async_stream::stream! {
+            let mut stream = stream;
+            let mut pending = String::new();
+            while let Some(chunk) = stream.next().await {
+                let mut chunk = match chunk {
+                    Ok(chunk) => chunk,
+                    Err(e) => {
+                        yield Err(e);
+                        continue;
+                    }
+                };
+
+                if chunk.done && !pending.is_empty() {
+                    let mut flushed = chunk.clone();
+                    flushed.done = false;
+                    flushed.done_reason = None;
+                    flushed.total_duration = None;
+                    flushed.load_duration = None;
+                    flushed.prompt_eval_count = None;
+                    flushed.prompt_eval_duration = None;
+                    flushed.eval_count = None;
+                    flushed.eval_duration = None;
+                    flushed.message = Some(Message::assistant(redactor.unmask(&pending)));
+                    pending.clear();
+                    yield Ok(flushed);
+                }
+
+                if let Some(message) = chunk.message.as_mut() {
+                    if let Some(content) = message.content.take() {
+                        pending.push_str(&content);
+                        let ready = ready_len(&pending);
+                        message.content = Some(redactor.unmask(&pending[..ready]));
+                        pending.replace_range(..ready, "");
+                    }
+                    for call in message.tool_calls.iter_mut().flatten() {
+                        map_strings(&mut call.function.arguments, &|s| redactor.unmask(s));
+                    }
+                }
+                yield Ok(chunk);
+            }
+        })
+    }
+}
+
+/// Length of the prefix of `pending` that cannot be part of a placeholder
+/// continued in the next chunk.
+fn ready_len(pending: &str) -> usize {
+    match pending.rfind('[') {
+        Some(start)
+            if !pending[start..].contains(']') && pending.len() - start <= MAX_PLACEHOLDER_LEN =>
+        {
+            start
+        }
+        _ => pending.len(),
+    }
+}
+
+impl Vault {
+    fn placeholder(&mut self, label: &str, value: &str) -> String {
+        if let Some(placeholder) = self.placeholders.get(value) {
+            return placeholder.clone();
+        }
+        let count = self.counts.entry(label.to_string()).or_default();
+        *count += 1;
+        let placeholder = format!("[{label}_{count}]");
+        self.placeholders
+            .insert(value.to_string(), placeholder.clone());
+        self.values.insert(placeholder.clone(), value.to_string());
+        placeholder
+    }
+}
+
+fn map_strings(value: &mut Value, f: &dyn Fn(&str) -> String) {
+    match value {
+        Value::String(s) => *s = f(s),
+        Value::Array(items) => items.iter_mut().for_each(|item| map_strings(item, f)),
+        Value::Object(map) => map.values_mut().for_each(|item| map_strings(item, f)),
+        _ => {}
+    }
+}
+
+impl fmt::Debug for Redactor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let labels: Vec<&str> = self.entities.iter().map(|e| e.label.as_str()).collect();
+        f.debug_struct("Redactor")
+            .field("entities", &labels)
+            .field("unmask_responses", &self.unmask_responses)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn masks_requests_and_unmasks_split_placeholders() {
+        let redactor = Redactor::default();
+        let mut messages = vec![Message::user(
+            "Call +386 41 123 456 or write to bob@example.com, bob@example.com",
+        )];
+        redactor.mask_messages(&mut messages);
+        assert_eq!(
+            messages[0].content.as_deref(),
+            Some("Call [PHONE_1] or write to [EMAIL_1], [EMAIL_1]")
+        );
+
+        let chunk = |content: &str, done: bool| ChatStreamChunk {
+            model: "m".into(),
+            created_at: String::new(),
+            message: Some(Message::assistant(content)),
+            done,
+            done_reason: None,
+            total_duration: None,
+            load_duration: None,
+            prompt_eval_count: None,
+            prompt_eval_duration: None,
+            eval_count: None,
+            eval_duration: None,
+        };
+        let chunks = vec![
+            Ok(chunk("Mailing [EMA", false)),
+            Ok(chunk("IL_1] now [", false)),
+            Ok(chunk("", true)),
+        ];
+        let stream: ChunkStream = Box::pin(futures::stream::iter(chunks));
+        let text: String = redactor
+            .unmask_stream(stream)
+            .filter_map(|chunk| async move { chunk.ok()?.message?.content })
+            .collect()
+            .await;
+        assert_eq!(text, "Mailing bob@example.com now [");
+    }
+}
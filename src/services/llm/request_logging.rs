@@ -0,0 +1,248 @@
+use std::{
+    future::Future,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+};
+
+use futures::{Stream, StreamExt};
+use serde::Serialize;
+use tracing::Level;
+
+use crate::{
+    services::{
+        llm::models::{chat::ChatStreamChunk, errors::InferenceClientError},
+        runtime::Instant,
+    },
+    Provider,
+};
+
+tokio::task_local! {
+    static PAYLOAD_SAMPLED: bool;
+}
+
+/// How the provider clients log their requests.
+///
+/// The metadata of every model request (provider, model, request and
+/// response size, latency, number of streamed chunks) is logged at debug
+/// level. Full request and response payloads, which get large with long
+/// histories and token streams, are only logged for 1 in `payload_every`
+/// requests, also as the span attributes of the Ollama client. Clones share
+/// the request count, so agents built from the same
+/// [`ClientConfig`](crate::ClientConfig) sample together.
+///
+/// With [`redact_content`](Self::redact_content) prompts and replies are
+/// kept out of the logs and the tracing spans (e.g. the input and output
+/// exported to Langfuse) altogether.
+#[derive(Debug, Clone)]
+pub struct RequestLogging {
+    payload_every: u64,
+    redact_content: bool,
+    requests: Arc<AtomicU64>,
+}
+
+impl RequestLogging {
+    /// Log the payloads of every request, the default.
+    pub fn all() -> Self {
+        Self::sampled(1)
+    }
+
+    /// Log the payloads of 1 in `every` requests, starting with the first.
+    pub fn sampled(every: u64) -> Self {
+        Self {
+            payload_every: every,
+            redact_content: false,
+            requests: Arc::default(),
+        }
+    }
+
+    /// Log no payloads, only the metadata.
+    pub fn metadata_only() -> Self {
+        Self::sampled(0)
+    }
+
+    /// Log no payloads, and replace the prompts, replies, tool arguments and
+    /// template data of the agent's spans with their size, e.g.
+    /// `[redacted: 120 chars]`. Models, usage and timings are still traced.
+    pub fn redact_content(mut self) -> Self {
+        self.redact_content = true;
+        self
+    }
+
+    pub fn redacts_content(&self) -> bool {
+        self.redact_content
+    }
+
+    /// Count a request and tell whether its payloads are logged.
+    pub(crate) fn sample(&self) -> bool {
+        let request = self.requests.fetch_add(1, Ordering::Relaxed);
+        !self.redact_content && self.payload_every != 0 && request % self.payload_every == 0
+    }
+}
+
+/// `content` for a span attribute, or only its size if `logging` redacts
+/// content.
+pub(crate) fn span_content(logging: Option<&RequestLogging>, content: String) -> String {
+    if logging.is_some_and(RequestLogging::redacts_content) {
+        format!("[redacted: {} chars]", content.chars().count())
+    } else {
+        content
+    }
+}
+
+impl Default for RequestLogging {
+    fn default() -> Self {
+        Self::all()
+    }
+}
+
+/// Whether the payloads of the running provider request are logged, for
+/// the span attributes of the provider clients.
+pub(crate) fn payload_sampled() -> bool {
+    PAYLOAD_SAMPLED.try_with(|sampled| *sampled).unwrap_or(true)
+}
+
+/// Metadata of a model request, logged once it completed.
+pub(crate) struct RequestLog {
+    provider: Option<Provider>,
+    model: String,
+    started: Instant,
+    sampled: bool,
+    request_bytes: usize,
+    request: Option<String>,
+    response_bytes: usize,
+    response: Option<String>,
+    chunks: usize,
+    error: Option<String>,
+}
+
+impl RequestLog {
+    pub(crate) fn start(
+        logging: Option<&RequestLogging>,
+        provider: Option<Provider>,
+        model: &str,
+        request: &impl Serialize,
+    ) -> Self {
+        let sampled = logging.map_or(true, RequestLogging::sample);
+        let mut log = Self {
+            provider,
+            model: model.to_string(),
+            started: Instant::now(),
+            sampled,
+            request_bytes: 0,
+            request: None,
+            response_bytes: 0,
+            response: None,
+            chunks: 0,
+            error: None,
+        };
+        if tracing::enabled!(Level::DEBUG) {
+            let body = serde_json::to_string(request).unwrap_or_default();
+            log.request_bytes = body.len();
+            log.request = sampled.then_some(body);
+        }
+        log
+    }
+
+    /// Run the provider call, which reads [`payload_sampled`].
+    pub(crate) async fn scope<F: Future>(&self, future: F) -> F::Output {
+        PAYLOAD_SAMPLED.scope(self.sampled, future).await
+    }
+
+    pub(crate) fn finish<T: Serialize>(mut self, result: &Result<T, InferenceClientError>) {
+        match result {
+            Ok(response) => {
+                self.record(response);
+                self.emit();
+            }
+            Err(e) => self.fail(e),
+        }
+    }
+
+    pub(crate) fn fail(mut self, error: &InferenceClientError) {
+        self.error = Some(error.to_string());
+        self.emit();
+    }
+
+    /// Log the request once `stream` completed or was dropped.
+    pub(crate) fn track_stream(
+        self,
+        stream: Pin<Box<dyn Stream<Item = Result<ChatStreamChunk, InferenceClientError>> + Send>>,
+    ) -> Pin<Box<dyn Stream<Item = Result<ChatStreamChunk, InferenceClientError>> + Send>> {
+        let mut log = LogOnDrop(self);
+        Box::pin(stream.inspect(move |chunk| {
+            let log = &mut log.0;
+            log.chunks += 1;
+            match chunk {
+                Ok(chunk) => log.record(chunk),
+                Err(e) => log.error = Some(e.to_string()),
+            }
+        }))
+    }
+
+    fn record(&mut self, response: &impl Serialize) {
+        if !tracing::enabled!(Level::DEBUG) {
+            return;
+        }
+        let body = serde_json::to_string(response).unwrap_or_default();
+        self.response_bytes += body.len();
+        if self.sampled {
+            let payload = self.response.get_or_insert_with(String::new);
+            if !payload.is_empty() {
+                payload.push('\n');
+            }
+            payload.push_str(&body);
+        }
+    }
+
+    fn emit(&self) {
+        tracing::debug!(
+            provider = ?self.provider,
+            model = self.model.as_str(),
+            request_bytes = self.request_bytes,
+            response_bytes = self.response_bytes,
+            chunks = self.chunks,
+            latency_ms = self.started.elapsed().as_millis() as u64,
+            error = self.error.as_deref(),
+            request = self.request.as_deref(),
+            response = self.response.as_deref(),
+            "Model request"
+        );
+    }
+}
+
+struct LogOnDrop(RequestLog);
+
+impl Drop for LogOnDrop {
+    fn drop(&mut self) {
+        self.0.emit();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn payloads_are_sampled_across_clones() {
+        let logging = RequestLogging::sampled(3);
+        let clone = logging.clone();
+        let sampled: Vec<bool> = (0..6)
+            .map(|i| if i % 2 == 0 { &logging } else { &clone }.sample())
+            .collect();
+        assert_eq!(sampled, [true, false, false, true, false, false]);
+        assert!(!RequestLogging::metadata_only().sample());
+    }
+
+    #[test]
+    fn redacted_content_is_never_logged() {
+        let logging = RequestLogging::all().redact_content();
+        assert!(!logging.sample());
+        let content = span_content(Some(&logging), "my password is hunter2".into());
+        assert_eq!(content, "[redacted: 22 chars]");
+        let content = span_content(None, "hello".into());
+        assert_eq!(content, "hello");
+    }
+}
@@ -0,0 +1,170 @@
+use std::fmt;
+
+use crate::{
+    services::llm::message::{Message, TOOL_NAME_METADATA},
+    Provider, Role,
+};
+
+/// Hook adjusting the messages of every request right before the provider
+/// client encodes them, e.g. for backends that reject `tool` messages or
+/// expect `tool_call_id`s in a particular format.
+///
+/// Set with [`AgentBuilder::set_message_rewriter`](crate::AgentBuilder::set_message_rewriter).
+/// Only the outgoing request is changed, never the agent's history. Closures
+/// taking the same arguments implement the trait:
+///
+/// ```
+/// use reagent_rs::{Message, Provider, Role};
+///
+/// let rewriter = |provider: &Provider, _model: &str, messages: &mut Vec<Message>| {
+///     if matches!(provider, Provider::OpenRouter) {
+///         messages.retain(|m| m.role != Role::Developer);
+///     }
+/// };
+/// # let _: &dyn reagent_rs::MessageRewriter = &rewriter;
+/// ```
+pub trait MessageRewriter: Send + Sync {
+    /// Rewrite `messages` sent to `model` on `provider`.
+    fn rewrite(&self, provider: &Provider, model: &str, messages: &mut Vec<Message>);
+}
+
+impl<F> MessageRewriter for F
+where
+    F: Fn(&Provider, &str, &mut Vec<Message>) + Send + Sync,
+{
+    fn rewrite(&self, provider: &Provider, model: &str, messages: &mut Vec<Message>) {
+        self(provider, model, messages)
+    }
+}
+
+impl fmt::Debug for dyn MessageRewriter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("MessageRewriter")
+    }
+}
+
+/// Sends tool results as user messages, for models that do not accept the
+/// `tool` role. The tool name and call id are kept in the text. The tool
+/// calls of assistant messages are inlined into their text the same way, so
+/// no call is left without its result.
+///
+/// Limited to models whose name contains one of the given patterns, or all
+/// models when none are given.
+#[derive(Debug, Clone, Default)]
+pub struct ToolResultsAsUser {
+    models: Vec<String>,
+}
+
+impl ToolResultsAsUser {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Only rewrite requests to models whose name contains `pattern`.
+    pub fn for_model(mut self, pattern: impl Into<String>) -> Self {
+        self.models.push(pattern.into());
+        self
+    }
+
+    fn applies_to(&self, model: &str) -> bool {
+        self.models.is_empty() || self.models.iter().any(|p| model.contains(p.as_str()))
+    }
+}
+
+impl MessageRewriter for ToolResultsAsUser {
+    fn rewrite(&self, _provider: &Provider, model: &str, messages: &mut Vec<Message>) {
+        if !self.applies_to(model) {
+            return;
+        }
+        for message in messages.iter_mut() {
+            if message.role == Role::Assistant {
+                inline_tool_calls(message);
+                continue;
+            }
+            if message.role != Role::Tool {
+                continue;
+            }
+            let tool = message
+                .get_metadata(TOOL_NAME_METADATA)
+                .and_then(|name| name.as_str())
+                .unwrap_or("tool")
+                .to_string();
+            let call = message
+                .tool_call_id
+                .take()
+                .map(|id| format!(" (call {id})"))
+                .unwrap_or_default();
+            let content = message.content.take().unwrap_or_default();
+            message.content = Some(format!("Result of `{tool}`{call}:\n{content}"));
+            message.role = Role::User;
+        }
+    }
+}
+
+/// Move the tool calls of an assistant message into its text.
+fn inline_tool_calls(message: &mut Message) {
+    let Some(calls) = message.tool_calls.take().filter(|calls| !calls.is_empty()) else {
+        return;
+    };
+    let mut lines: Vec<String> = message.content.take().into_iter().collect();
+    for call in calls {
+        let id = call
+            .id
+            .map(|id| format!(" (call {id})"))
+            .unwrap_or_default();
+        lines.push(format!(
+            "Calling `{}`{id} with {}",
+            call.function.name, call.function.arguments
+        ));
+    }
+    message.content = Some(lines.join("\n"));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ToolCall, ToolCallFunction, ToolType};
+    use serde_json::json;
+
+    #[test]
+    fn tool_results_become_user_messages_for_matching_models() {
+        let mut call = Message::assistant("Let me check.");
+        call.tool_calls = Some(vec![ToolCall {
+            id: Some("call_1".into()),
+            tool_type: ToolType::Function,
+            function: ToolCallFunction {
+                name: "get_weather".into(),
+                arguments: json!({"city": "Ljubljana"}),
+            },
+        }]);
+        let mut messages = vec![
+            Message::user("weather?"),
+            call,
+            Message::tool("sunny", "call_1").with_metadata(TOOL_NAME_METADATA, "get_weather"),
+        ];
+        let rewriter = ToolResultsAsUser::new().for_model("gemma");
+
+        rewriter.rewrite(&Provider::OpenRouter, "openai/gpt-4o", &mut messages);
+        assert!(messages[1].tool_calls.is_some());
+        assert_eq!(messages[2].role, Role::Tool);
+
+        rewriter.rewrite(
+            &Provider::OpenRouter,
+            "google/gemma-3-27b-it",
+            &mut messages,
+        );
+        assert!(messages[1].tool_calls.is_none());
+        assert_eq!(
+            messages[1].content.as_deref(),
+            Some(
+                "Let me check.\nCalling `get_weather` (call call_1) with {\"city\":\"Ljubljana\"}"
+            )
+        );
+        assert_eq!(messages[2].role, Role::User);
+        assert_eq!(messages[2].tool_call_id, None);
+        assert_eq!(
+            messages[2].content.as_deref(),
+            Some("Result of `get_weather` (call call_1):\nsunny")
+        );
+    }
+}
@@ -0,0 +1,62 @@
+use std::fmt;
+
+use reqwest::header::HeaderValue;
+
+use crate::services::llm::InferenceClientError;
+
+/// A string that is never shown in `Debug` output, for API keys and other
+/// credentials. Read it with [`expose_secret`](Self::expose_secret) where
+/// it is actually sent.
+///
+/// ```
+/// use reagent_rs::SecretString;
+///
+/// let key = SecretString::from("sk-live-1234");
+/// assert_eq!(format!("{key:?}"), "[REDACTED]");
+/// assert_eq!(key.expose_secret(), "sk-live-1234");
+/// ```
+#[derive(Clone, Default, PartialEq, Eq)]
+pub struct SecretString(String);
+
+impl SecretString {
+    pub fn new(secret: impl Into<String>) -> Self {
+        Self(secret.into())
+    }
+
+    pub fn expose_secret(&self) -> &str {
+        &self.0
+    }
+
+    /// `Authorization` header with the secret as bearer token, marked
+    /// sensitive so the HTTP client does not show it either.
+    pub(crate) fn bearer_header(&self) -> Result<HeaderValue, InferenceClientError> {
+        let mut value = HeaderValue::from_str(&format!("Bearer {}", self.0))
+            .map_err(|e| InferenceClientError::Config(format!("Invalid api_key header: {e}")))?;
+        value.set_sensitive(true);
+        Ok(value)
+    }
+}
+
+impl fmt::Debug for SecretString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("[REDACTED]")
+    }
+}
+
+impl From<String> for SecretString {
+    fn from(secret: String) -> Self {
+        Self(secret)
+    }
+}
+
+impl From<&str> for SecretString {
+    fn from(secret: &str) -> Self {
+        Self(secret.to_string())
+    }
+}
+
+impl From<&String> for SecretString {
+    fn from(secret: &String) -> Self {
+        Self(secret.clone())
+    }
+}
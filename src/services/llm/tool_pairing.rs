@@ -0,0 +1,196 @@
+use uuid::Uuid;
+
+use crate::{
+    services::llm::message::{Message, TOOL_NAME_METADATA},
+    Role,
+};
+
+/// Content of the tool message added for a call that was never answered.
+const UNANSWERED_CALL: &str = "The tool call was not executed.";
+
+/// A new id in the `call_...` format of OpenAI-compatible providers.
+pub(crate) fn new_tool_call_id() -> String {
+    format!("call_{}", Uuid::new_v4().simple())
+}
+
+/// Give the tool calls of `message` that have no id (as sent by some Ollama
+/// models) a new one, so their results can be paired with them.
+pub(crate) fn assign_tool_call_ids(message: &mut Message) {
+    for call in message.tool_calls.iter_mut().flatten() {
+        if call.id.as_deref().map_or(true, str::is_empty) {
+            call.id = Some(new_tool_call_id());
+        }
+    }
+}
+
+/// Make every tool message answer a tool call of the assistant message
+/// before it, as OpenAI-compatible providers require. Returns what was
+/// repaired.
+///
+/// Tool calls without an id get one. A tool message whose `tool_call_id`
+/// matches no open call is paired with the open call of the same tool (see
+/// [`TOOL_NAME_METADATA`]) or the first open call; without open calls it is
+/// sent as a user message. Calls left unanswered get a placeholder result.
+pub fn repair_tool_call_pairing(messages: &mut Vec<Message>) -> Vec<String> {
+    let mut issues = Vec::new();
+    // (id, tool name) of calls of the last assistant message without a result
+    let mut open: Vec<(String, String)> = Vec::new();
+    let mut index = 0;
+
+    while index < messages.len() {
+        if messages[index].role != Role::Tool {
+            index += answer_open_calls(messages, index, &mut open, &mut issues);
+        }
+        let Some(message) = messages.get_mut(index) else {
+            break;
+        };
+
+        match message.role {
+            Role::Assistant => {
+                for call in message.tool_calls.iter_mut().flatten() {
+                    if call.id.as_deref().map_or(true, str::is_empty) {
+                        call.id = Some(new_tool_call_id());
+                        issues.push(format!(
+                            "assigned an id to a call of `{}`",
+                            call.function.name
+                        ));
+                    }
+                    if let Some(id) = &call.id {
+                        open.push((id.clone(), call.function.name.clone()));
+                    }
+                }
+            }
+            Role::Tool => {
+                let id = message.tool_call_id.clone().unwrap_or_default();
+                let name = message
+                    .get_metadata(TOOL_NAME_METADATA)
+                    .and_then(|name| name.as_str())
+                    .map(str::to_string);
+
+                if let Some(position) = open.iter().position(|(open_id, _)| *open_id == id) {
+                    open.remove(position);
+                } else if !open.is_empty() {
+                    let position = open
+                        .iter()
+                        .position(|(_, tool)| Some(tool) == name.as_ref())
+                        .unwrap_or(0);
+                    let (open_id, tool) = open.remove(position);
+                    issues.push(format!(
+                        "paired the result with id `{id}` with the call `{open_id}` of `{tool}`"
+                    ));
+                    message.tool_call_id = Some(open_id);
+                } else {
+                    let tool = name.unwrap_or_else(|| "tool".to_string());
+                    let content = message.content.take().unwrap_or_default();
+                    message.content = Some(format!("Result of `{tool}`:\n{content}"));
+                    message.role = Role::User;
+                    message.tool_call_id = None;
+                    issues.push(format!(
+                        "sent the result of `{tool}` without a call as a user message"
+                    ));
+                }
+            }
+            _ => {}
+        }
+        index += 1;
+    }
+    let end = messages.len();
+    answer_open_calls(messages, end, &mut open, &mut issues);
+
+    issues
+}
+
+/// Insert placeholder results for the `open` calls at `index`, returns how
+/// many messages were inserted.
+fn answer_open_calls(
+    messages: &mut Vec<Message>,
+    index: usize,
+    open: &mut Vec<(String, String)>,
+    issues: &mut Vec<String>,
+) -> usize {
+    let count = open.len();
+    for (offset, (id, tool)) in open.drain(..).enumerate() {
+        issues.push(format!("answered the unanswered call `{id}` of `{tool}`"));
+        messages.insert(
+            index + offset,
+            Message::tool(UNANSWERED_CALL, id).with_metadata(TOOL_NAME_METADATA, tool),
+        );
+    }
+    count
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::{testing::FlowTestHarness, AgentBuilder, ToolCall, ToolCallFunction, ToolType};
+
+    fn call(id: Option<&str>, name: &str) -> ToolCall {
+        ToolCall {
+            id: id.map(str::to_string),
+            tool_type: ToolType::Function,
+            function: ToolCallFunction {
+                name: name.into(),
+                arguments: json!({}),
+            },
+        }
+    }
+
+    fn assistant(calls: Vec<ToolCall>) -> Message {
+        let mut message = Message::assistant(String::new());
+        message.tool_calls = Some(calls);
+        message
+    }
+
+    #[test]
+    fn results_are_paired_with_their_calls() {
+        let mut messages = vec![
+            Message::user("weather and time?"),
+            assistant(vec![call(None, "get_weather"), call(Some("a"), "get_time")]),
+            Message::tool("noon", "get_time").with_metadata(TOOL_NAME_METADATA, "get_time"),
+            Message::tool("sunny", "get_weather").with_metadata(TOOL_NAME_METADATA, "get_weather"),
+            assistant(vec![call(Some("b"), "get_weather")]),
+            Message::user("and?"),
+            Message::tool("stray", "x"),
+        ];
+
+        let issues = repair_tool_call_pairing(&mut messages);
+
+        let weather_id = messages[1].tool_calls.as_ref().unwrap()[0].id.clone();
+        assert!(weather_id.as_deref().unwrap().starts_with("call_"));
+        assert_eq!(messages[2].tool_call_id.as_deref(), Some("a"));
+        assert_eq!(messages[3].tool_call_id, weather_id);
+        assert_eq!(messages[5].tool_call_id.as_deref(), Some("b"));
+        assert_eq!(messages[5].content.as_deref(), Some(UNANSWERED_CALL));
+        assert_eq!(messages[6].role, Role::User);
+        assert_eq!(messages[7].role, Role::User);
+        assert_eq!(
+            messages[7].content.as_deref(),
+            Some("Result of `tool`:\nstray")
+        );
+        assert_eq!(issues.len(), 5);
+    }
+
+    #[tokio::test]
+    async fn calls_without_ids_get_one_before_they_are_executed() {
+        let builder = AgentBuilder::default().set_model("test");
+        let mut harness = FlowTestHarness::new(builder)
+            .await
+            .unwrap()
+            .reply_message(assistant(vec![call(None, "search")]))
+            .expect_tool_call("search", "A language")
+            .reply("Rust is a language.");
+
+        harness.run("What is rust?").await.unwrap();
+
+        let history = harness.history();
+        let id = history[2].tool_calls.as_ref().unwrap()[0].id.clone();
+        assert!(id.is_some());
+        assert_eq!(history[3].tool_call_id, id);
+        assert!(harness.requests()[1]
+            .messages
+            .iter()
+            .any(|m| m.role == Role::Tool && m.tool_call_id == id));
+    }
+}
@@ -0,0 +1,219 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::{mpsc::Sender, Mutex, MutexGuard};
+
+use crate::{notifications::Notification, services::runtime};
+
+use super::{
+    error::McpIntegrationError,
+    mcp_tool_builder::{
+        get_mcp_sse_tools, get_mcp_stdio_tools, get_mcp_streamable_http_tools, McpClient,
+        McpServerType, McpService,
+    },
+};
+
+/// State of an MCP server of an agent, see
+/// [`Agent::mcp_status`](crate::Agent::mcp_status).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct McpServerStatus {
+    pub server: McpServerType,
+    /// Whether the client is running and its transport is open. A server
+    /// that exited is restarted on the next call of one of its tools.
+    pub connected: bool,
+    /// Number of tools the server listed when it was last started or
+    /// refreshed.
+    pub tools: usize,
+    /// How often the server was restarted after it exited.
+    pub restarts: usize,
+}
+
+/// Connection to one MCP server, shared by its tools and the agent that
+/// tracks it. Stdio servers that exit are started again when one of their
+/// tools is called.
+pub(crate) struct McpConnection {
+    server: McpServerType,
+    notification_channel: Option<Sender<Notification>>,
+    client: McpClient,
+    tools: AtomicUsize,
+    restarts: AtomicUsize,
+    /// Set on shutdown, after which the server is not restarted.
+    closed: AtomicBool,
+}
+
+impl McpConnection {
+    /// Start the server and list its tools.
+    pub(crate) async fn connect(
+        server: McpServerType,
+        notification_channel: Option<Sender<Notification>>,
+    ) -> Result<(Arc<Self>, Vec<rmcp::model::Tool>), McpIntegrationError> {
+        let (service, tools) = start(server.clone(), notification_channel.clone()).await?;
+        let connection = Self {
+            server,
+            notification_channel,
+            client: Arc::new(Mutex::new(Some(service))),
+            tools: AtomicUsize::new(tools.len()),
+            restarts: AtomicUsize::new(0),
+            closed: AtomicBool::new(false),
+        };
+        Ok((Arc::new(connection), tools))
+    }
+
+    /// The client, `None` after shutdown. A server that exited is restarted
+    /// first.
+    pub(crate) async fn client(&self) -> MutexGuard<'_, Option<McpService>> {
+        let mut client = self.client.lock().await;
+        let exited = client
+            .as_ref()
+            .is_some_and(|service| service.is_transport_closed());
+        if !exited || self.closed.load(Ordering::SeqCst) {
+            return client;
+        }
+
+        tracing::warn!(server = ?self.server, "MCP server exited, restarting it");
+        match start(self.server.clone(), self.notification_channel.clone()).await {
+            Ok((service, tools)) => {
+                *client = Some(service);
+                self.tools.store(tools.len(), Ordering::SeqCst);
+                self.restarts.fetch_add(1, Ordering::SeqCst);
+            }
+            Err(e) => {
+                tracing::warn!(server = ?self.server, error = %e, "Could not restart MCP server")
+            }
+        }
+        client
+    }
+
+    pub(crate) fn server(&self) -> &McpServerType {
+        &self.server
+    }
+
+    /// List the server's tools again.
+    pub(crate) async fn list_tools(&self) -> Result<Vec<rmcp::model::Tool>, McpIntegrationError> {
+        let client = self.client().await;
+        let Some(service) = client.as_ref() else {
+            return Err(McpIntegrationError::Connection(
+                "The MCP client was shut down".into(),
+            ));
+        };
+        let tools = service
+            .list_tools(Default::default())
+            .await
+            .map_err(|e| McpIntegrationError::Discovery(e.to_string()))?
+            .tools;
+        drop(client);
+
+        self.tools.store(tools.len(), Ordering::SeqCst);
+        Ok(tools)
+    }
+
+    pub(crate) async fn status(&self) -> McpServerStatus {
+        let connected = self
+            .client
+            .lock()
+            .await
+            .as_ref()
+            .is_some_and(|service| !service.is_transport_closed());
+        McpServerStatus {
+            server: self.server.clone(),
+            connected,
+            tools: self.tools.load(Ordering::SeqCst),
+            restarts: self.restarts.load(Ordering::SeqCst),
+        }
+    }
+
+    /// Stop the client, terminating a stdio server's child process, and
+    /// wait at most `timeout` for it.
+    pub(crate) async fn shutdown(&self, timeout: Duration) {
+        self.closed.store(true, Ordering::SeqCst);
+        let Some(service) = self.client.lock().await.take() else {
+            return;
+        };
+        match runtime::timeout(timeout, service.cancel()).await {
+            Ok(Ok(reason)) => tracing::debug!(?reason, "MCP client stopped"),
+            Ok(Err(e)) => tracing::warn!(error = %e, "MCP client task failed on shutdown"),
+            Err(_) => tracing::warn!("MCP client did not stop in time"),
+        }
+    }
+
+    /// Stop the client without waiting, for `Drop`. Dropping the service
+    /// cancels it, which kills a stdio server's child process. Skipped when
+    /// a tool call holds the client; the service then stops once the last
+    /// tool is dropped.
+    pub(crate) fn close_now(&self) {
+        self.closed.store(true, Ordering::SeqCst);
+        if let Ok(mut client) = self.client.try_lock() {
+            client.take();
+        }
+    }
+}
+
+async fn start(
+    server: McpServerType,
+    notification_channel: Option<Sender<Notification>>,
+) -> Result<(McpService, Vec<rmcp::model::Tool>), McpIntegrationError> {
+    let (client, tools) = match server {
+        McpServerType::Sse(url) => get_mcp_sse_tools(url, notification_channel).await?,
+        McpServerType::StreamableHttp(url) => {
+            get_mcp_streamable_http_tools(url, notification_channel).await?
+        }
+        McpServerType::Stdio(command) => get_mcp_stdio_tools(command, notification_channel).await?,
+    };
+    let service = client.lock().await.take().ok_or_else(|| {
+        McpIntegrationError::Connection("The MCP client stopped while starting".into())
+    })?;
+    Ok((service, tools))
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::{AgentBuilder, StdioCommand};
+
+    /// Answers `initialize`, `tools/list` and one `tools/call`, then exits.
+    const ONE_SHOT_SERVER: &str = r#"
+read line
+echo '{"jsonrpc":"2.0","id":0,"result":{"protocolVersion":"2024-11-05","capabilities":{"tools":{}},"serverInfo":{"name":"one-shot","version":"1"}}}'
+read line
+read line
+echo '{"jsonrpc":"2.0","id":1,"result":{"tools":[{"name":"ping","description":"Answers pong","inputSchema":{"type":"object","properties":{}}}]}}'
+read line
+echo '{"jsonrpc":"2.0","id":2,"result":{"content":[{"type":"text","text":"pong"}]}}'
+"#;
+
+    #[tokio::test]
+    async fn exited_servers_are_restarted() {
+        let server = McpServerType::stdio(StdioCommand::new("sh").args(["-c", ONE_SHOT_SERVER]));
+        let mut agent = AgentBuilder::default()
+            .set_model("test")
+            .add_mcp_server(server.clone())
+            .build()
+            .await
+            .unwrap();
+        let ping = agent.get_tool_ref_by_name("ping").unwrap().clone();
+
+        assert_eq!(ping.execute(json!({})).await.unwrap().trim(), "pong");
+        // the server exits after its only call
+        while agent.mcp_status().await[0].connected {
+            runtime::sleep(Duration::from_millis(10)).await;
+        }
+
+        assert_eq!(ping.execute(json!({})).await.unwrap().trim(), "pong");
+        let status = agent.mcp_status().await;
+        assert_eq!(status[0].server, server);
+        assert_eq!(status[0].tools, 1);
+        assert_eq!(status[0].restarts, 1);
+
+        agent.shutdown().await;
+        assert!(agent.mcp_status().await.is_empty());
+        assert!(ping.execute(json!({})).await.is_err());
+    }
+}
@@ -1,4 +1,4 @@
-use std::fmt;
+use std::{fmt, time::Duration};
 
 /// Errors that can occur while integrating with an MCP server.
 ///
@@ -17,6 +17,8 @@ pub enum McpIntegrationError {
     ToolConversion(String),
     /// Provided MCP schema was missing or invalid (e.g. not a JSON object).
     InvalidSchema(String),
+    /// The MCP server did not start within the configured startup timeout.
+    StartupTimeout(Duration),
 }
 
 impl fmt::Display for McpIntegrationError {
@@ -38,6 +40,9 @@ impl fmt::Display for McpIntegrationError {
                     "MCP action input schema is missing or not an object: {s}"
                 )
             }
+            McpIntegrationError::StartupTimeout(timeout) => {
+                write!(f, "MCP server did not start within {timeout:?}")
+            }
         }
     }
 }
@@ -50,6 +55,7 @@ impl std::error::Error for McpIntegrationError {
             McpIntegrationError::Discovery(_) => None,
             McpIntegrationError::ToolConversion(_) => None,
             McpIntegrationError::InvalidSchema(_) => None,
+            McpIntegrationError::StartupTimeout(_) => None,
         }
     }
 }
@@ -0,0 +1,298 @@
+use std::sync::{Arc, Mutex};
+
+use serde::{Deserialize, Serialize};
+
+use tokio::sync::mpsc::Sender;
+
+use crate::{
+    notifications::{Notification, NotificationContent},
+    services::runtime,
+    Agent, Tool,
+};
+
+use super::{
+    connection::{McpConnection, McpServerStatus},
+    mcp_tool_builder::{get_mcp_tools, McpServerType},
+};
+
+/// When an agent connects to an MCP server and lists its tools, see
+/// [`AgentBuilder::add_mcp_server_with`](crate::AgentBuilder::add_mcp_server_with).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum McpConnect {
+    /// While the agent is built, which fails when the server is down.
+    #[default]
+    Eager,
+    /// At the start of the agent's first invocation. A server that is down
+    /// is tried again on the next one, without its tools in the meantime.
+    Lazy,
+    /// In a background task started by the build, sending
+    /// [`NotificationContent::McpServerReady`] once the tools are there.
+    /// Invocations before that run without them; a failed connection is
+    /// retried like a [`Lazy`](Self::Lazy) one.
+    Background,
+}
+
+/// How `server` is connected given the servers that are `deferred` by
+/// [`AgentBuilder::add_mcp_server_with`](crate::AgentBuilder::add_mcp_server_with).
+pub(crate) fn connect_mode(
+    deferred: &[(McpServerType, McpConnect)],
+    server: &McpServerType,
+) -> McpConnect {
+    deferred
+        .iter()
+        .find(|(deferred, _)| deferred == server)
+        .map_or(McpConnect::Eager, |(_, connect)| *connect)
+}
+
+#[derive(Clone)]
+enum LazyState {
+    Pending,
+    Connecting,
+    Ready(Vec<Tool>),
+}
+
+struct LazyServer {
+    server: McpServerType,
+    connect: McpConnect,
+    state: LazyState,
+}
+
+/// MCP servers of an agent that are not connected by its build, shared
+/// between the agent's clones.
+#[derive(Clone, Default)]
+pub(crate) struct LazyMcpServers {
+    servers: Arc<Mutex<Vec<LazyServer>>>,
+}
+
+impl LazyMcpServers {
+    pub(crate) fn new(servers: Vec<(McpServerType, McpConnect)>) -> Self {
+        let servers = servers
+            .into_iter()
+            .filter(|(_, connect)| *connect != McpConnect::Eager)
+            .map(|(server, connect)| LazyServer {
+                server,
+                connect,
+                state: LazyState::Pending,
+            })
+            .collect();
+        Self {
+            servers: Arc::new(Mutex::new(servers)),
+        }
+    }
+
+    /// Whether `server` is connected lazily instead of by the build.
+    pub(crate) fn is_deferred(&self, server: &McpServerType) -> bool {
+        self.lock().iter().any(|lazy| &lazy.server == server)
+    }
+
+    /// The servers with how they are connected, e.g. to export them.
+    pub(crate) fn servers(&self) -> Vec<(McpServerType, McpConnect)> {
+        self.lock()
+            .iter()
+            .map(|lazy| (lazy.server.clone(), lazy.connect))
+            .collect()
+    }
+
+    /// Start connecting the [`McpConnect::Background`] servers of `agent`.
+    pub(crate) fn spawn_background(&self, agent: &Agent) {
+        let mut servers = self.lock();
+        for lazy in servers.iter_mut() {
+            if lazy.connect != McpConnect::Background || !matches!(lazy.state, LazyState::Pending) {
+                continue;
+            }
+            lazy.state = LazyState::Connecting;
+
+            let this = self.clone();
+            let server = lazy.server.clone();
+            let background = agent.background.clone();
+            let channel = agent.notification_channel.clone();
+            let name = agent.name.clone();
+            agent.background.track_task(
+                0,
+                runtime::spawn(async move {
+                    let track = |connection| background.track_mcp_client(connection);
+                    let Some(tools) = this.connect(server.clone(), channel.clone(), track).await
+                    else {
+                        return;
+                    };
+                    if let Some(channel) = channel {
+                        let status = McpServerStatus {
+                            server,
+                            connected: true,
+                            tools: tools.len(),
+                            restarts: 0,
+                        };
+                        let notification =
+                            Notification::new(name, NotificationContent::McpServerReady(status));
+                        let _ = channel.send(notification).await;
+                    }
+                }),
+            );
+        }
+    }
+
+    /// Connect the servers that are still pending and return the tools of
+    /// all connected ones.
+    pub(crate) async fn tools(&self, agent: &Agent) -> Vec<(McpServerType, Vec<Tool>)> {
+        let pending: Vec<McpServerType> = {
+            let mut servers = self.lock();
+            servers
+                .iter_mut()
+                .filter(|lazy| matches!(lazy.state, LazyState::Pending))
+                .map(|lazy| {
+                    lazy.state = LazyState::Connecting;
+                    lazy.server.clone()
+                })
+                .collect()
+        };
+        for server in pending {
+            let track = |connection| agent.background.track_mcp_client(connection);
+            self.connect(server, agent.notification_channel.clone(), track)
+                .await;
+        }
+
+        self.lock()
+            .iter()
+            .filter_map(|lazy| match &lazy.state {
+                LazyState::Ready(tools) => Some((lazy.server.clone(), tools.clone())),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Replace the tools of `server` after they were refreshed, see
+    /// [`Agent::refresh_mcp_tools`].
+    pub(crate) fn replace_tools(&self, server: &McpServerType, tools: Vec<Tool>) {
+        let mut servers = self.lock();
+        if let Some(lazy) = servers.iter_mut().find(|lazy| &lazy.server == server) {
+            if matches!(lazy.state, LazyState::Ready(_)) {
+                lazy.state = LazyState::Ready(tools);
+            }
+        }
+    }
+
+    /// Servers that are not connected yet, for
+    /// [`Agent::mcp_status`](crate::Agent::mcp_status).
+    pub(crate) fn pending_status(&self) -> Vec<McpServerStatus> {
+        self.lock()
+            .iter()
+            .filter(|lazy| !matches!(lazy.state, LazyState::Ready(_)))
+            .map(|lazy| McpServerStatus {
+                server: lazy.server.clone(),
+                connected: false,
+                tools: 0,
+                restarts: 0,
+            })
+            .collect()
+    }
+
+    async fn connect(
+        &self,
+        server: McpServerType,
+        channel: Option<Sender<Notification>>,
+        track: impl FnOnce(Arc<McpConnection>),
+    ) -> Option<Vec<Tool>> {
+        let result = get_mcp_tools(server.clone(), channel).await;
+        let mut servers = self.lock();
+        let lazy = servers.iter_mut().find(|lazy| lazy.server == server)?;
+        match result {
+            Ok((connection, tools)) => {
+                track(connection);
+                lazy.state = LazyState::Ready(tools.clone());
+                Some(tools)
+            }
+            Err(e) => {
+                tracing::warn!(server = ?server, error = %e, "Could not connect to MCP server, retrying on the next invocation");
+                lazy.state = LazyState::Pending;
+                None
+            }
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Vec<LazyServer>> {
+        self.servers.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl std::fmt::Debug for LazyMcpServers {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_list()
+            .entries(
+                self.lock()
+                    .iter()
+                    .map(|lazy| (lazy.server.without_secrets(), lazy.connect)),
+            )
+            .finish()
+    }
+}
+
+impl Agent {
+    /// Add the tools of lazily connected MCP servers (see [`McpConnect`]),
+    /// connecting the ones that are still pending.
+    pub(crate) async fn connect_lazy_mcp_servers(&mut self) {
+        for (server, server_tools) in self.lazy_mcp.tools(self).await {
+            // a server this agent already has tools of was refreshed by now
+            if self.mcp_tool_names(&server).is_some() {
+                continue;
+            }
+            self.set_mcp_tool_names(&server, &server_tools);
+            let tools = self.tools.get_or_insert_with(Vec::new);
+            for tool in server_tools {
+                if !tools.iter().any(|t| t.name() == tool.name()) {
+                    tools.push(tool);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::{testing::FlowTestHarness, AgentBuilder, StdioCommand};
+
+    /// Answers `initialize` and `tools/list`, then waits for stdin to close.
+    const SERVER: &str = r#"
+read line
+echo '{"jsonrpc":"2.0","id":0,"result":{"protocolVersion":"2024-11-05","capabilities":{"tools":{}},"serverInfo":{"name":"lazy","version":"1"}}}'
+read line
+read line
+echo '{"jsonrpc":"2.0","id":1,"result":{"tools":[{"name":"ping","description":"Answers pong","inputSchema":{"type":"object","properties":{}}}]}}'
+read line
+"#;
+
+    #[tokio::test]
+    async fn lazy_servers_connect_on_first_invocation() {
+        let down = McpServerType::stdio("reagent-missing-mcp-server");
+        let server = McpServerType::stdio(StdioCommand::new("sh").args(["-c", SERVER]));
+        let builder = AgentBuilder::default()
+            .set_model("test")
+            .add_mcp_server_with(down.clone(), McpConnect::Lazy)
+            .add_mcp_server_with(server.clone(), McpConnect::Lazy);
+        let mut harness = FlowTestHarness::new(builder).await.unwrap().reply("Hi.");
+
+        assert!(harness.agent().get_tool_ref_by_name("ping").is_none());
+        let status = harness.agent().mcp_status().await;
+        assert!(status.len() == 2 && status.iter().all(|s| !s.connected));
+
+        harness.run("Hello").await.unwrap();
+
+        let tools = harness.requests()[0].tools.clone().unwrap();
+        assert!(tools.iter().any(|tool| tool.name() == "ping"));
+        let status = harness.agent().mcp_status().await;
+        assert_eq!(status[0].server, server);
+        assert!(status[0].connected);
+        // a server that is down is retried on the next invocation
+        assert_eq!(
+            status[1],
+            McpServerStatus {
+                server: down,
+                connected: false,
+                tools: 0,
+                restarts: 0,
+            }
+        );
+        harness.agent_mut().shutdown().await;
+    }
+}
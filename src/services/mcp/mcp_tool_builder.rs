@@ -1,25 +1,24 @@
 use std::sync::Arc;
 
+use serde::{Deserialize, Serialize};
 use serde_json::Value;
-use tokio::{
-    process::Command,
-    sync::{mpsc::Sender, Mutex},
-};
+use tokio::sync::{mpsc::Sender, Mutex};
 use tracing::{info, trace};
 
 use crate::{
     notifications::{Notification, NotificationContent},
+    services::{llm::client_config::without_credentials, runtime},
     Tool, ToolBuilder, ToolExecutionError,
 };
 
-use super::error::McpIntegrationError;
+use super::{connection::McpConnection, error::McpIntegrationError, stdio_command::StdioCommand};
 use crate::AsyncToolFn;
+#[cfg(not(target_arch = "wasm32"))]
+use rmcp::transport::TokioChildProcess;
 use rmcp::{
     model::{CallToolRequestParam, CallToolResult, JsonObject},
     service::RunningService,
-    transport::{
-        ConfigureCommandExt, SseClientTransport, StreamableHttpClientTransport, TokioChildProcess,
-    },
+    transport::{SseClientTransport, StreamableHttpClientTransport},
     ClientHandler, ServiceExt,
 };
 
@@ -30,12 +29,13 @@ use rmcp::{
 /// - `Sse` for Server-Sent Events
 /// - `Stdio` for a child process over standard I/O
 /// - `StreamableHttp` for HTTP transport with streaming
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum McpServerType {
     /// Connect via Server-Sent Events at the provided URL.
     Sse(String),
     /// Spawn and connect to a process over stdin/stdout pipes.
-    Stdio(String),
+    Stdio(StdioCommand),
     /// Connect via a streaming HTTP endpoint.
     StreamableHttp(String),
 }
@@ -47,7 +47,10 @@ impl McpServerType {
     }
 
     /// Creates an stdio-based MCP server type with the given command.
-    pub fn stdio<S: Into<String>>(cmd: S) -> Self {
+    ///
+    /// Accepts either a [`StdioCommand`] or a command line string, which is
+    /// parsed with [`StdioCommand::parse`].
+    pub fn stdio<C: Into<StdioCommand>>(cmd: C) -> Self {
         McpServerType::Stdio(cmd.into())
     }
 
@@ -55,12 +58,53 @@ impl McpServerType {
     pub fn streamable_http<S: Into<String>>(url: S) -> Self {
         McpServerType::StreamableHttp(url.into())
     }
+
+    /// The server without the secrets it may hold, to log or export it:
+    /// credentials in its URL or in URLs among the arguments of its
+    /// command, and the environment variables of its command.
+    pub(crate) fn without_secrets(&self) -> McpServerType {
+        let url = |url: &str| without_credentials(url).unwrap_or_default();
+        match self {
+            McpServerType::Sse(sse) => McpServerType::Sse(url(sse)),
+            McpServerType::StreamableHttp(http) => McpServerType::StreamableHttp(url(http)),
+            McpServerType::Stdio(command) => McpServerType::Stdio(StdioCommand {
+                args: command
+                    .args
+                    .iter()
+                    .map(|arg| arg_without_credentials(arg))
+                    .collect(),
+                env: Default::default(),
+                ..command.clone()
+            }),
+        }
+    }
+}
+
+/// `arg` with the credentials of a URL it holds, e.g. `--url=https://..`,
+/// removed.
+fn arg_without_credentials(arg: &str) -> String {
+    let (flag, value) = match arg.split_once('=') {
+        Some((flag, value)) if !flag.contains("://") => (Some(flag), value),
+        _ => (None, arg),
+    };
+    if !value.contains("://") {
+        return arg.to_string();
+    }
+    let value = without_credentials(value).unwrap_or_default();
+    match flag {
+        Some(flag) => format!("{flag}={value}"),
+        None => value,
+    }
 }
 
+/// A running MCP client instance.
+pub type McpService = RunningService<rmcp::RoleClient, AgentMcpHandler>;
+
 /// A handle to a running MCP client instance, wrapped in an async lock.
 ///
 /// This client manages communication with a remote MCP server.
-pub type McpClient = Arc<Mutex<RunningService<rmcp::RoleClient, AgentMcpHandler>>>;
+/// `None` once the client has been shut down.
+pub type McpClient = Arc<Mutex<Option<McpService>>>;
 
 #[derive(Clone)]
 pub struct AgentMcpHandler {
@@ -104,37 +148,50 @@ impl ClientHandler for AgentMcpHandler {
 /// - `mcp_server_type` - The transport type and connection info for the MCP server.
 /// - `notification_channel` - Optional channel to forward MCP notifications back to the agent.
 ///
+/// Returns the [`McpConnection`] as well, so its owner can shut it down.
+/// It restarts the server when it exited.
+///
 /// # Errors
 /// Returns [`McpIntegrationError`] if the connection, discovery, or tool conversion fails.
-pub async fn get_mcp_tools(
+pub(crate) async fn get_mcp_tools(
     mcp_server_type: McpServerType,
     notification_channel: Option<Sender<Notification>>,
-) -> Result<Vec<Tool>, McpIntegrationError> {
-    let (mcp_client, mcp_raw_tools) = match mcp_server_type {
-        McpServerType::Sse(url) => get_mcp_sse_tools(url, notification_channel).await?,
-        McpServerType::StreamableHttp(url) => {
-            get_mcp_streamable_http_tools(url, notification_channel).await?
-        }
-        McpServerType::Stdio(command) => get_mcp_stdio_tools(command, notification_channel).await?,
-    };
+) -> Result<(Arc<McpConnection>, Vec<Tool>), McpIntegrationError> {
+    let (connection, mcp_raw_tools) =
+        McpConnection::connect(mcp_server_type, notification_channel).await?;
 
     trace!(
         "Discovered {} raw tools from MCP server. Converting...",
         mcp_raw_tools.len()
     );
+    let agent_tools = convert_mcp_tools(&connection, mcp_raw_tools)?;
+    Ok((connection, agent_tools))
+}
+
+/// Convert MCP tool definitions into agent tools that call them through
+/// `connection`.
+pub(crate) fn convert_mcp_tools(
+    connection: &Arc<McpConnection>,
+    mcp_raw_tools: Vec<rmcp::model::Tool>,
+) -> Result<Vec<Tool>, McpIntegrationError> {
     let mut agent_tools = Vec::new();
 
     for mcp_tool_def in mcp_raw_tools {
-        let arc_mcp_client = Arc::clone(&mcp_client);
+        let arc_mcp_connection = Arc::clone(connection);
         let tool_namme_cow = mcp_tool_def.name.clone();
 
         // MCP tool executor closure
         let executor: AsyncToolFn = Arc::new(move |args: Value| {
-            let mcp_client_ref = Arc::clone(&arc_mcp_client);
+            let mcp_connection_ref = Arc::clone(&arc_mcp_connection);
             let tool_name = tool_namme_cow.clone().into_owned();
 
             Box::pin(async move {
-                let inner_mcp_client = mcp_client_ref.lock().await;
+                let inner_mcp_client = mcp_connection_ref.client().await;
+                let Some(inner_mcp_client) = inner_mcp_client.as_ref() else {
+                    return Err(ToolExecutionError::ExecutionFailed(format!(
+                        "MCP tool '{tool_name}' is unavailable, the MCP client was shut down"
+                    )));
+                };
 
                 // call remote tool
                 let result = match inner_mcp_client
@@ -259,7 +316,7 @@ where
         Ok(l) => l,
         Err(e) => return Err(McpIntegrationError::Discovery(e.to_string())),
     };
-    Ok((Arc::new(Mutex::new(client)), tool_list.tools))
+    Ok((Arc::new(Mutex::new(Some(client))), tool_list.tools))
 }
 
 /// Connect to an MCP server over Streamable HTTP and fetch its tools.
@@ -290,36 +347,32 @@ where
         .await
         .map_err(|e| McpIntegrationError::Discovery(e.to_string()))?;
 
-    Ok((Arc::new(Mutex::new(client)), tool_list.tools))
+    Ok((Arc::new(Mutex::new(Some(client))), tool_list.tools))
 }
 
 /// Connect to an MCP server spawned as a child process and fetch its tools.
 ///
-/// The child process is started with the given command string, and the
-/// connection is established over stdin/stdout pipes.
+/// The child process is started from the given [`StdioCommand`] (program,
+/// arguments, environment and working directory, through the shell if it
+/// asks for it), and the connection is
+/// established over stdin/stdout pipes.
 ///
 /// Returns both a [`McpClient`] and the raw tool definitions discovered.
 ///
 /// # Errors
-/// Returns [`McpIntegrationError`] if the process fails to start or tool discovery fails.
-pub async fn get_mcp_stdio_tools<T>(
-    full_command: T,
+/// Returns [`McpIntegrationError`] if the process fails to start, does not
+/// start within the command's startup timeout, or tool discovery fails.
+#[cfg(not(target_arch = "wasm32"))]
+pub async fn get_mcp_stdio_tools(
+    command: StdioCommand,
     notification_channel: Option<Sender<Notification>>,
-) -> Result<(McpClient, Vec<rmcp::model::Tool>), McpIntegrationError>
-where
-    T: Into<String>,
-{
-    let full_command_string = full_command.into();
-    let mut command_args = full_command_string.split(" ");
-    let first = command_args.next();
-    if first.is_none() {
+) -> Result<(McpClient, Vec<rmcp::model::Tool>), McpIntegrationError> {
+    if command.program.is_empty() {
         return Err(McpIntegrationError::Connection("Invalid command.".into()));
     }
-    let transport = match TokioChildProcess::new(Command::new(first.unwrap()).configure(|cmd| {
-        for arg in command_args {
-            cmd.arg(arg);
-        }
-    })) {
+
+    let startup_timeout = command.startup_timeout;
+    let transport = match TokioChildProcess::new(command.to_command()) {
         Ok(t) => t,
         Err(e) => return Err(McpIntegrationError::Connection(e.to_string())),
     };
@@ -328,14 +381,36 @@ where
         agent_notification_tx: notification_channel,
     };
 
-    let client = match handler.serve(transport).await {
-        Ok(c) => c,
-        Err(e) => return Err(McpIntegrationError::Connection(e.to_string())),
-    };
+    let startup = async {
+        let client = match handler.serve(transport).await {
+            Ok(c) => c,
+            Err(e) => return Err(McpIntegrationError::Connection(e.to_string())),
+        };
 
-    let tool_list = match client.list_tools(Default::default()).await {
-        Ok(l) => l,
-        Err(e) => return Err(McpIntegrationError::Discovery(e.to_string())),
+        let tool_list = match client.list_tools(Default::default()).await {
+            Ok(l) => l,
+            Err(e) => return Err(McpIntegrationError::Discovery(e.to_string())),
+        };
+        Ok((Arc::new(Mutex::new(Some(client))), tool_list.tools))
     };
-    Ok((Arc::new(Mutex::new(client)), tool_list.tools))
+
+    match startup_timeout {
+        Some(timeout) => runtime::timeout(timeout, startup)
+            .await
+            .map_err(|_| McpIntegrationError::StartupTimeout(timeout))?,
+        None => startup.await,
+    }
+}
+
+/// Browsers cannot spawn processes, so stdio servers are not available on
+/// `wasm32`. Use [`McpServerType::Sse`] or [`McpServerType::StreamableHttp`].
+#[cfg(target_arch = "wasm32")]
+pub async fn get_mcp_stdio_tools(
+    command: StdioCommand,
+    _notification_channel: Option<Sender<Notification>>,
+) -> Result<(McpClient, Vec<rmcp::model::Tool>), McpIntegrationError> {
+    Err(McpIntegrationError::Connection(format!(
+        "Cannot start `{}`: stdio MCP servers are not supported on wasm32",
+        command.program
+    )))
 }
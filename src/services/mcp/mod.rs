@@ -1,2 +1,6 @@
+pub mod connection;
 pub mod error;
+pub mod lazy;
 pub mod mcp_tool_builder;
+pub mod refresh;
+pub mod stdio_command;
@@ -0,0 +1,304 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{services::runtime::Instant, Agent, NotificationHandler, Tool};
+
+use super::{
+    error::McpIntegrationError,
+    mcp_tool_builder::{convert_mcp_tools, McpServerType},
+};
+
+/// How the tools of an MCP server changed, found by
+/// [`Agent::refresh_mcp_tools`] and sent as
+/// [`NotificationContent::ToolsChanged`](crate::NotificationContent::ToolsChanged).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ToolsChange {
+    pub server: McpServerType,
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    /// Tools whose description or input schema changed.
+    pub changed: Vec<String>,
+}
+
+impl ToolsChange {
+    fn between(server: McpServerType, previous: &[Tool], current: &[Tool]) -> Self {
+        let find =
+            |tools: &[Tool], name: &str| tools.iter().find(|tool| tool.name() == name).cloned();
+        let mut change = Self {
+            server,
+            added: Vec::new(),
+            removed: Vec::new(),
+            changed: Vec::new(),
+        };
+        for tool in current {
+            match find(previous, tool.name()) {
+                None => change.added.push(tool.name().to_string()),
+                Some(old)
+                    if old.function.description != tool.function.description
+                        || old.function.parameters != tool.function.parameters =>
+                {
+                    change.changed.push(tool.name().to_string())
+                }
+                Some(_) => {}
+            }
+        }
+        for tool in previous {
+            if find(current, tool.name()).is_none() {
+                change.removed.push(tool.name().to_string());
+            }
+        }
+        change
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+
+    fn touches(&self, name: &str) -> bool {
+        self.added
+            .iter()
+            .chain(&self.removed)
+            .chain(&self.changed)
+            .any(|n| n == name)
+    }
+}
+
+/// Outcome of [`Agent::refresh_mcp_tools`].
+#[derive(Debug, Default)]
+pub struct ToolsRefresh {
+    /// Servers whose tools changed.
+    pub changes: Vec<ToolsChange>,
+    /// Servers that could not be listed; the agent keeps their tools as
+    /// they were.
+    pub errors: Vec<(McpServerType, McpIntegrationError)>,
+}
+
+impl Agent {
+    /// List the tools of the agent's MCP servers again and update
+    /// [`tools`](Agent::tools) with the ones that were added, removed or
+    /// changed since this agent last saw them. Every server with changes is
+    /// reported by a [`ToolsChange`], also sent as a notification. A server
+    /// that fails does not stop the others from being refreshed. Local tools
+    /// are never replaced or removed.
+    pub async fn refresh_mcp_tools(&mut self) -> ToolsRefresh {
+        self.mcp_refreshed_at = Instant::now();
+        let mut refresh = ToolsRefresh::default();
+        for connection in self.background.mcp_connections() {
+            let server = connection.server().clone();
+            let current = match connection
+                .list_tools()
+                .await
+                .and_then(|tools| convert_mcp_tools(&connection, tools))
+            {
+                Ok(current) => current,
+                Err(e) => {
+                    refresh.errors.push((server, e));
+                    continue;
+                }
+            };
+
+            let local: Vec<String> = self
+                .local_tools
+                .iter()
+                .flatten()
+                .map(|tool| tool.name().to_string())
+                .collect();
+            let owned = self.mcp_tool_names(&server).unwrap_or_default();
+            let previous: Vec<Tool> = self
+                .tools
+                .iter()
+                .flatten()
+                .filter(|tool| {
+                    owned.contains(&tool.name()) && !local.iter().any(|n| n == tool.name())
+                })
+                .cloned()
+                .collect();
+            let change = ToolsChange::between(server.clone(), &previous, &current);
+            self.set_mcp_tool_names(&server, &current);
+            self.lazy_mcp.replace_tools(&server, current.clone());
+            if change.is_empty() {
+                continue;
+            }
+
+            let is_mcp = |tool: &Tool| !local.iter().any(|n| n == tool.name());
+            let tools = self.tools.get_or_insert_with(Vec::new);
+            tools.retain(|tool| !(change.touches(tool.name()) && is_mcp(tool)));
+            tools.extend(
+                current
+                    .into_iter()
+                    .filter(|tool| change.touches(tool.name()) && is_mcp(tool)),
+            );
+
+            tracing::info!(
+                agent = self.name.as_str(),
+                server = ?change.server,
+                added = ?change.added,
+                removed = ?change.removed,
+                changed = ?change.changed,
+                "MCP tools changed"
+            );
+            self.notify_tools_changed(change.clone()).await;
+            refresh.changes.push(change);
+        }
+        refresh
+    }
+
+    /// Names of the tools `server` added to this agent, `None` before it
+    /// added any.
+    pub(crate) fn mcp_tool_names(&self, server: &McpServerType) -> Option<Vec<&str>> {
+        self.mcp_tool_names
+            .iter()
+            .find(|(s, _)| s == server)
+            .map(|(_, names)| names.iter().map(String::as_str).collect())
+    }
+
+    pub(crate) fn set_mcp_tool_names(&mut self, server: &McpServerType, tools: &[Tool]) {
+        let names = tools.iter().map(|tool| tool.name().to_string()).collect();
+        match self.mcp_tool_names.iter_mut().find(|(s, _)| s == server) {
+            Some((_, known)) => *known = names,
+            None => self.mcp_tool_names.push((server.clone(), names)),
+        }
+    }
+
+    /// Refresh the MCP tools before an invocation once the interval set
+    /// with [`AgentBuilder::set_mcp_refresh_interval`](crate::AgentBuilder::set_mcp_refresh_interval)
+    /// passed. Failures are only logged.
+    pub(crate) async fn refresh_mcp_tools_if_due(&mut self) {
+        let Some(interval) = self.mcp_refresh_interval else {
+            return;
+        };
+        if self.mcp_refreshed_at.elapsed() < interval {
+            return;
+        }
+        for (server, e) in self.refresh_mcp_tools().await.errors {
+            tracing::warn!(agent = self.name.as_str(), ?server, error = %e, "Could not refresh MCP tools");
+        }
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::{
+        testing::FlowTestHarness, AgentBuilder, NotificationContent, StdioCommand, ToolBuilder,
+    };
+
+    /// Lists `ping` and `echo`, then `pong` and a changed `echo`.
+    const SERVER: &str = r#"
+read line
+echo '{"jsonrpc":"2.0","id":0,"result":{"protocolVersion":"2024-11-05","capabilities":{"tools":{}},"serverInfo":{"name":"changing","version":"1"}}}'
+read line
+read line
+echo '{"jsonrpc":"2.0","id":1,"result":{"tools":[{"name":"ping","description":"Answers pong","inputSchema":{"type":"object","properties":{}}},{"name":"echo","description":"Echoes","inputSchema":{"type":"object","properties":{}}}]}}'
+read line
+echo '{"jsonrpc":"2.0","id":2,"result":{"tools":[{"name":"pong","description":"Answers ping","inputSchema":{"type":"object","properties":{}}},{"name":"echo","description":"Echoes text","inputSchema":{"type":"object","properties":{}}}]}}'
+read line
+"#;
+
+    /// Lists `ping` and `echo`, then twice `pong` and a changed `echo`.
+    const SERVER_TWICE: &str = r#"
+read line
+echo '{"jsonrpc":"2.0","id":0,"result":{"protocolVersion":"2024-11-05","capabilities":{"tools":{}},"serverInfo":{"name":"changing","version":"1"}}}'
+read line
+read line
+echo '{"jsonrpc":"2.0","id":1,"result":{"tools":[{"name":"ping","description":"Answers pong","inputSchema":{"type":"object","properties":{}}},{"name":"echo","description":"Echoes","inputSchema":{"type":"object","properties":{}}}]}}'
+read line
+echo '{"jsonrpc":"2.0","id":2,"result":{"tools":[{"name":"pong","description":"Answers ping","inputSchema":{"type":"object","properties":{}}},{"name":"echo","description":"Echoes text","inputSchema":{"type":"object","properties":{}}}]}}'
+read line
+echo '{"jsonrpc":"2.0","id":3,"result":{"tools":[{"name":"pong","description":"Answers ping","inputSchema":{"type":"object","properties":{}}},{"name":"echo","description":"Echoes text","inputSchema":{"type":"object","properties":{}}}]}}'
+read line
+"#;
+
+    /// Lists `time`, then fails every listing.
+    const FAILING_SERVER: &str = r#"
+read line
+echo '{"jsonrpc":"2.0","id":0,"result":{"protocolVersion":"2024-11-05","capabilities":{"tools":{}},"serverInfo":{"name":"failing","version":"1"}}}'
+read line
+read line
+echo '{"jsonrpc":"2.0","id":1,"result":{"tools":[{"name":"time","description":"Tells the time","inputSchema":{"type":"object","properties":{}}}]}}'
+read line
+echo '{"jsonrpc":"2.0","id":2,"error":{"code":-32603,"message":"unavailable"}}'
+read line
+echo '{"jsonrpc":"2.0","id":3,"error":{"code":-32603,"message":"unavailable"}}'
+read line
+"#;
+
+    #[tokio::test]
+    async fn changed_tools_are_applied_before_the_next_invocation() {
+        let server = McpServerType::stdio(StdioCommand::new("sh").args(["-c", SERVER]));
+        let builder = AgentBuilder::default()
+            .set_model("test")
+            .add_mcp_server(server.clone())
+            .set_mcp_refresh_interval(Duration::ZERO);
+        let mut harness = FlowTestHarness::new(builder).await.unwrap().reply("Hi.");
+        assert!(harness.agent().get_tool_ref_by_name("ping").is_some());
+
+        harness.run("Hello").await.unwrap();
+
+        let tools = harness.requests()[0].tools.clone().unwrap();
+        let mut names: Vec<&str> = tools.iter().map(|tool| tool.name()).collect();
+        names.sort();
+        assert_eq!(names, ["echo", "pong"]);
+        let echo = harness.agent().get_tool_ref_by_name("echo").unwrap();
+        assert_eq!(echo.function.description, "Echoes text");
+        let expected = ToolsChange {
+            server,
+            added: vec!["pong".into()],
+            removed: vec!["ping".into()],
+            changed: vec!["echo".into()],
+        };
+        harness.assert_notified(None, |content| {
+            matches!(content, NotificationContent::ToolsChanged(change) if *change == expected)
+        });
+        harness.agent_mut().shutdown().await;
+    }
+
+    #[tokio::test]
+    async fn clones_refresh_against_their_own_tools() {
+        let server = McpServerType::stdio(StdioCommand::new("sh").args(["-c", SERVER_TWICE]));
+        let failing = McpServerType::stdio(StdioCommand::new("sh").args(["-c", FAILING_SERVER]));
+        let local_pong = ToolBuilder::new()
+            .function_name("pong")
+            .function_description("Local pong")
+            .executor_fn(|_| async { Ok("local".to_string()) })
+            .build()
+            .unwrap();
+        let mut agent = AgentBuilder::default()
+            .set_model("test")
+            .add_tool(local_pong)
+            .add_mcp_server(server.clone())
+            .add_mcp_server(failing.clone())
+            .build()
+            .await
+            .unwrap();
+        let mut clone = agent.clone();
+
+        let expected = ToolsChange {
+            server,
+            added: vec!["pong".into()],
+            removed: vec!["ping".into()],
+            changed: vec!["echo".into()],
+        };
+        for agent in [&mut agent, &mut clone] {
+            let refresh = agent.refresh_mcp_tools().await;
+            assert_eq!(refresh.changes, std::slice::from_ref(&expected));
+            assert_eq!(refresh.errors.len(), 1);
+            assert_eq!(refresh.errors[0].0, failing);
+
+            assert!(agent.get_tool_ref_by_name("ping").is_none());
+            assert!(agent.get_tool_ref_by_name("time").is_some());
+            let echo = agent.get_tool_ref_by_name("echo").unwrap();
+            assert_eq!(echo.function.description, "Echoes text");
+            let pongs: Vec<_> = agent
+                .tools
+                .iter()
+                .flatten()
+                .filter(|tool| tool.name() == "pong")
+                .collect();
+            assert_eq!(pongs.len(), 1);
+            assert_eq!(pongs[0].function.description, "Local pong");
+        }
+        agent.shutdown().await;
+    }
+}
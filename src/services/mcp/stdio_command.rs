@@ -0,0 +1,535 @@
+use std::{collections::HashMap, path::PathBuf, time::Duration};
+
+use serde::{Deserialize, Serialize};
+
+/// Command used to spawn a stdio MCP server.
+///
+/// Mirrors [`std::process::Command`]: program and arguments are kept
+/// separately, so arguments containing spaces don't need any quoting.
+///
+/// ```
+/// use std::time::Duration;
+/// use reagent_rs::{McpServerType, StdioCommand};
+///
+/// let server = McpServerType::stdio(
+///     StdioCommand::new("npx")
+///         .args(["-y", "@modelcontextprotocol/server-filesystem", "/home/me/My Documents"])
+///         .env("NODE_ENV", "production")
+///         .current_dir("/tmp")
+///         .startup_timeout(Duration::from_secs(30)),
+/// );
+///
+/// // simple commands can still be given as a single string
+/// let server = McpServerType::stdio("npx -y @modelcontextprotocol/server-memory");
+///
+/// // `npx` is a `.cmd` script on Windows, which only a shell can start
+/// let server = McpServerType::stdio(
+///     StdioCommand::parse("npx -y @modelcontextprotocol/server-memory").in_shell(),
+/// );
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StdioCommand {
+    /// Executable to run.
+    pub program: String,
+    /// Arguments passed to the program.
+    pub args: Vec<String>,
+    /// Environment variables set for the child, on top of the inherited ones.
+    pub env: HashMap<String, String>,
+    /// Working directory of the child, defaults to the current one.
+    pub cwd: Option<PathBuf>,
+    /// How long to wait for the server to start and list its tools.
+    pub startup_timeout: Option<Duration>,
+    /// Start the program through the platform shell, `sh -c` or `cmd /C`
+    /// on Windows.
+    pub shell: bool,
+}
+
+/// Quoting rules of a command line given to [`StdioCommand::parse_with`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CommandLineSyntax {
+    /// Shell words: single or double quotes group words into one argument.
+    /// Outside of quotes, a backslash escapes the next character; inside
+    /// double quotes only `$`, `` ` ``, `"`, `\` and a newline, as in `sh`.
+    Posix,
+    /// The rules of Windows programs: double quotes group words, `\"` is a
+    /// literal quote and other backslashes are kept, so paths like
+    /// `C:\Program Files\nodejs\node.exe` can be given as they are.
+    Windows,
+}
+
+impl CommandLineSyntax {
+    /// [`Windows`](Self::Windows) on Windows, [`Posix`](Self::Posix)
+    /// everywhere else.
+    pub fn native() -> Self {
+        if cfg!(windows) {
+            Self::Windows
+        } else {
+            Self::Posix
+        }
+    }
+}
+
+impl StdioCommand {
+    pub fn new(program: impl Into<String>) -> Self {
+        Self {
+            program: program.into(),
+            ..Default::default()
+        }
+    }
+
+    /// Parse a command line into program and arguments, with the quoting
+    /// rules of the platform, see [`CommandLineSyntax::native`].
+    pub fn parse(command_line: &str) -> Self {
+        Self::parse_with(command_line, CommandLineSyntax::native())
+    }
+
+    /// Parse a command line into program and arguments. Arguments are split
+    /// on whitespace and quoted as described by `syntax`.
+    pub fn parse_with(command_line: &str, syntax: CommandLineSyntax) -> Self {
+        let words = match syntax {
+            CommandLineSyntax::Posix => split_posix(command_line),
+            CommandLineSyntax::Windows => split_windows(command_line),
+        };
+        let mut words = words.into_iter();
+        let program = words.next().unwrap_or_default();
+        Self::new(program).args(words)
+    }
+
+    pub fn arg(mut self, arg: impl Into<String>) -> Self {
+        self.args.push(arg.into());
+        self
+    }
+
+    pub fn args<I, S>(mut self, args: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.args.extend(args.into_iter().map(Into::into));
+        self
+    }
+
+    pub fn env(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.env.insert(key.into(), value.into());
+        self
+    }
+
+    pub fn envs<I, K, V>(mut self, vars: I) -> Self
+    where
+        I: IntoIterator<Item = (K, V)>,
+        K: Into<String>,
+        V: Into<String>,
+    {
+        self.env
+            .extend(vars.into_iter().map(|(k, v)| (k.into(), v.into())));
+        self
+    }
+
+    pub fn current_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.cwd = Some(dir.into());
+        self
+    }
+
+    pub fn startup_timeout(mut self, timeout: Duration) -> Self {
+        self.startup_timeout = Some(timeout);
+        self
+    }
+
+    /// Start the program through the platform shell, e.g. for `.cmd`
+    /// launchers like `npx` on Windows, or programs the shell's profile
+    /// puts on the `PATH`. Program and arguments are quoted and escaped for
+    /// the shell, so characters like `&`, `|` or `%` reach the program as
+    /// they are.
+    pub fn in_shell(mut self) -> Self {
+        self.shell = true;
+        self
+    }
+
+    /// Process to spawn for the command.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) fn to_command(&self) -> tokio::process::Command {
+        let mut command = if !self.shell {
+            let mut command = tokio::process::Command::new(&self.program);
+            command.args(&self.args);
+            command
+        } else if cfg!(windows) {
+            shell_command(cmd_command_line(&self.program, &self.args))
+        } else {
+            shell_command(posix_command_line(&self.program, &self.args))
+        };
+        command.envs(&self.env);
+        if let Some(cwd) = &self.cwd {
+            command.current_dir(cwd);
+        }
+        command
+    }
+}
+
+impl From<&str> for StdioCommand {
+    fn from(command_line: &str) -> Self {
+        StdioCommand::parse(command_line)
+    }
+}
+
+impl From<String> for StdioCommand {
+    fn from(command_line: String) -> Self {
+        StdioCommand::parse(&command_line)
+    }
+}
+
+impl From<&String> for StdioCommand {
+    fn from(command_line: &String) -> Self {
+        StdioCommand::parse(command_line)
+    }
+}
+
+#[cfg(windows)]
+fn shell_command(command_line: String) -> tokio::process::Command {
+    let mut command = tokio::process::Command::new("cmd");
+    // with `/S` cmd only strips the outer quotes, keeping the quoting of
+    // the words inside
+    command.raw_arg(format!("/S /C \"{command_line}\""));
+    command
+}
+
+#[cfg(all(not(windows), not(target_arch = "wasm32")))]
+fn shell_command(command_line: String) -> tokio::process::Command {
+    let mut command = tokio::process::Command::new("sh");
+    command.arg("-c").arg(command_line);
+    command
+}
+
+/// Program and arguments as one line for `sh`, see
+/// [`CommandLineSyntax::Posix`].
+#[cfg(not(target_arch = "wasm32"))]
+fn posix_command_line(program: &str, args: &[String]) -> String {
+    std::iter::once(program)
+        .chain(args.iter().map(String::as_str))
+        .map(|word| {
+            let plain = !word.is_empty()
+                && word
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || "-_./:=@+,%".contains(c));
+            if plain {
+                word.to_string()
+            } else {
+                format!("'{}'", word.replace('\'', r"'\''"))
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Characters `cmd` treats specially, escaped with `^` by
+/// [`cmd_command_line`].
+#[cfg(not(target_arch = "wasm32"))]
+const CMD_SPECIAL_CHARS: &str = "^&|<>()%!\" \t,;=";
+
+/// Program and arguments as one line for `cmd /S /C`. Every word is quoted
+/// for the program, see [`CommandLineSyntax::Windows`], and then every
+/// character `cmd` would interpret, quotes included, is escaped with `^`.
+/// `cmd` thus never sees a quoted section, only removes the carets, and
+/// cannot be made to e.g. run the part after a `&` as another command.
+#[cfg(not(target_arch = "wasm32"))]
+fn cmd_command_line(program: &str, args: &[String]) -> String {
+    std::iter::once(program)
+        .chain(args.iter().map(String::as_str))
+        .map(|word| {
+            let mut escaped = String::new();
+            for c in windows_quote(word).chars() {
+                if CMD_SPECIAL_CHARS.contains(c) {
+                    escaped.push('^');
+                }
+                escaped.push(c);
+            }
+            escaped
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// `word` quoted as one argument, see [`CommandLineSyntax::Windows`].
+#[cfg(not(target_arch = "wasm32"))]
+fn windows_quote(word: &str) -> String {
+    if !word.is_empty() && !word.contains([' ', '\t', '"']) {
+        return word.to_string();
+    }
+    let mut quoted = String::from('"');
+    let mut backslashes = 0;
+    for c in word.chars() {
+        if c == '\\' {
+            backslashes += 1;
+            continue;
+        }
+        // backslashes are only escaped before a quote
+        let escaped = if c == '"' {
+            backslashes * 2 + 1
+        } else {
+            backslashes
+        };
+        quoted.extend(std::iter::repeat('\\').take(escaped));
+        quoted.push(c);
+        backslashes = 0;
+    }
+    quoted.extend(std::iter::repeat('\\').take(backslashes * 2));
+    quoted.push('"');
+    quoted
+}
+
+fn split_posix(command_line: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut current = String::new();
+    // a word was started, even if it is empty (e.g. `""`)
+    let mut in_word = false;
+    let mut quote: Option<char> = None;
+    let mut chars = command_line.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (Some('\''), '\'') => quote = None,
+            (Some('\''), c) => current.push(c),
+            // a backslash and a newline continue the line
+            (_, '\\') if chars.next_if_eq(&'\n').is_some() => {}
+            (None, '\\') => {
+                if let Some(escaped) = chars.next() {
+                    current.push(escaped);
+                }
+                in_word = true;
+            }
+            // inside double quotes, only these lose their meaning; other
+            // backslashes are kept
+            (Some(_), '\\') => match chars.next_if(|c| "$`\"\\".contains(*c)) {
+                Some(escaped) => current.push(escaped),
+                None => current.push('\\'),
+            },
+            (Some(_), '"') => quote = None,
+            (Some(_), c) => current.push(c),
+            (None, '"' | '\'') => {
+                quote = Some(c);
+                in_word = true;
+            }
+            (None, c) if c.is_whitespace() => {
+                if in_word {
+                    words.push(std::mem::take(&mut current));
+                    in_word = false;
+                }
+            }
+            (None, c) => {
+                current.push(c);
+                in_word = true;
+            }
+        }
+    }
+    if in_word {
+        words.push(current);
+    }
+    words
+}
+
+fn split_windows(command_line: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut current = String::new();
+    let mut in_word = false;
+    let mut quoted = false;
+    let mut chars = command_line.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                let mut backslashes = 1;
+                while chars.next_if_eq(&'\\').is_some() {
+                    backslashes += 1;
+                }
+                in_word = true;
+                if chars.peek() != Some(&'"') {
+                    current.extend(std::iter::repeat('\\').take(backslashes));
+                    continue;
+                }
+                // 2n backslashes before a quote are n backslashes and the
+                // quote opens or closes; 2n + 1 are n and a literal quote
+                current.extend(std::iter::repeat('\\').take(backslashes / 2));
+                if backslashes % 2 == 1 {
+                    current.push('"');
+                    chars.next();
+                }
+            }
+            '"' => {
+                // `""` inside quotes is a literal quote
+                if quoted && chars.next_if_eq(&'"').is_some() {
+                    current.push('"');
+                } else {
+                    quoted = !quoted;
+                }
+                in_word = true;
+            }
+            c if c.is_whitespace() && !quoted => {
+                if in_word {
+                    words.push(std::mem::take(&mut current));
+                    in_word = false;
+                }
+            }
+            c => {
+                current.push(c);
+                in_word = true;
+            }
+        }
+    }
+    if in_word {
+        words.push(current);
+    }
+    words
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_keeps_quoted_arguments_together() {
+        let command = StdioCommand::parse_with(
+            r#"uvx  mcp-server --root "/home/me/My Documents" --name 'a "b"' plain\ space """#,
+            CommandLineSyntax::Posix,
+        );
+
+        assert_eq!(command.program, "uvx");
+        assert_eq!(
+            command.args,
+            vec![
+                "mcp-server",
+                "--root",
+                "/home/me/My Documents",
+                "--name",
+                r#"a "b""#,
+                "plain space",
+                "",
+            ]
+        );
+    }
+
+    #[test]
+    fn backslashes_in_double_quotes_only_escape_special_characters() {
+        let command = StdioCommand::parse_with(
+            "run \"C:\\tmp\\new\" \"\\$HOME \\\"x\\\" \\\\\" a\\\nb \"c\\\nd\"",
+            CommandLineSyntax::Posix,
+        );
+
+        assert_eq!(
+            command.args,
+            vec![r"C:\tmp\new", r#"$HOME "x" \"#, "ab", "cd"]
+        );
+    }
+
+    #[test]
+    fn parse_empty_command_has_no_program() {
+        assert_eq!(StdioCommand::parse("   "), StdioCommand::default());
+    }
+
+    #[test]
+    fn windows_paths_keep_their_backslashes() {
+        let command = StdioCommand::parse_with(
+            r#""C:\Program Files\nodejs\node.exe" C:\mcp\server.js --root "D:\My Data\\" --name "say \"hi\"" 'single'"#,
+            CommandLineSyntax::Windows,
+        );
+
+        assert_eq!(command.program, r"C:\Program Files\nodejs\node.exe");
+        assert_eq!(
+            command.args,
+            vec![
+                r"C:\mcp\server.js",
+                "--root",
+                r"D:\My Data\",
+                "--name",
+                r#"say "hi""#,
+                "'single'",
+            ]
+        );
+
+        // quoting the words again gives the same command
+        let line = std::iter::once(&command.program)
+            .chain(&command.args)
+            .map(|word| windows_quote(word))
+            .collect::<Vec<_>>()
+            .join(" ");
+        let reparsed = StdioCommand::parse_with(&line, CommandLineSyntax::Windows);
+        assert_eq!(reparsed, command);
+    }
+
+    #[test]
+    fn shell_command_lines_quote_every_word() {
+        let args = ["--root".to_string(), "/tmp/it's here".to_string()];
+        let line = posix_command_line("/opt/my server/run", &args);
+        assert_eq!(line, r"'/opt/my server/run' --root '/tmp/it'\''s here'");
+
+        let command = StdioCommand::parse_with(&line, CommandLineSyntax::Posix);
+        assert_eq!(command.program, "/opt/my server/run");
+        assert_eq!(command.args, args);
+    }
+
+    #[test]
+    fn cmd_command_lines_escape_special_characters() {
+        let args = [
+            "a & del *".to_string(),
+            "50%".to_string(),
+            "%PATH%".to_string(),
+            r#"say "hi" | more"#.to_string(),
+        ];
+        let line = cmd_command_line(r"C:\my tools\server.cmd", &args);
+        assert_eq!(
+            line,
+            r#"^"C:\my^ tools\server.cmd^" ^"a^ ^&^ del^ *^" 50^% ^%PATH^% ^"say^ \^"hi\^"^ ^|^ more^""#
+        );
+
+        // cmd removes the carets and passes the rest on, which the program
+        // splits into the original words
+        let mut unescaped = String::new();
+        let mut chars = line.chars();
+        while let Some(c) = chars.next() {
+            unescaped.extend(if c == '^' { chars.next() } else { Some(c) });
+        }
+        let command = StdioCommand::parse_with(&unescaped, CommandLineSyntax::Windows);
+        assert_eq!(command.program, r"C:\my tools\server.cmd");
+        assert_eq!(command.args, args);
+    }
+
+    /// Answers `initialize` and `tools/list`, then waits for stdin to close.
+    #[cfg(unix)]
+    const SERVER: &str = r#"#!/bin/sh
+read line
+echo '{"jsonrpc":"2.0","id":0,"result":{"protocolVersion":"2024-11-05","capabilities":{"tools":{}},"serverInfo":{"name":"spaced","version":"1"}}}'
+read line
+read line
+echo '{"jsonrpc":"2.0","id":1,"result":{"tools":[{"name":"ping","description":"Answers pong","inputSchema":{"type":"object","properties":{}}}]}}'
+cat > /dev/null
+"#;
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn servers_in_paths_with_spaces_start() {
+        use std::os::unix::fs::PermissionsExt;
+
+        use crate::services::mcp::mcp_tool_builder::get_mcp_stdio_tools;
+
+        let dir = std::env::temp_dir().join(format!("reagent mcp {}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let script = dir.join("my server.sh");
+        std::fs::write(&script, SERVER).unwrap();
+        std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let path = script.display().to_string();
+
+        let commands = [
+            StdioCommand::parse(&format!("\"{path}\" --verbose")),
+            StdioCommand::new(&path).arg("--verbose").in_shell(),
+            StdioCommand::parse("'./my server.sh'")
+                .current_dir(&dir)
+                .in_shell(),
+        ];
+        for command in commands {
+            let (_client, tools) = get_mcp_stdio_tools(command.clone(), None)
+                .await
+                .unwrap_or_else(|e| panic!("{command:?} failed: {e}"));
+            assert_eq!(tools[0].name, "ping");
+        }
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -1,2 +1,3 @@
 pub mod llm;
 pub mod mcp;
+pub(crate) mod runtime;
@@ -0,0 +1,256 @@
+//! Timers, clocks and task spawning used by the agent, so the rest of the
+//! crate does not depend on tokio's runtime directly.
+//!
+//! Natively, tasks and timers go through the installed [`Runtime`] (tokio
+//! unless [`set_runtime`] was called). On `wasm32` there is no tokio runtime:
+//! tasks run on the browser's event loop and timers use `setTimeout`.
+
+use std::{
+    future::Future,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use futures::{
+    channel::oneshot,
+    future::{abortable, AbortHandle},
+};
+
+pub(crate) use imp::{blocking, sleep, Instant, SystemTime};
+#[cfg(not(target_arch = "wasm32"))]
+pub use imp::{set_runtime, Runtime, TokioRuntime};
+
+/// `Send` on native targets, where tasks may move between threads. Browser
+/// futures are not `Send`, and do not have to be.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) trait MaybeSend: Send {}
+#[cfg(not(target_arch = "wasm32"))]
+impl<T: Send> MaybeSend for T {}
+#[cfg(target_arch = "wasm32")]
+pub(crate) trait MaybeSend {}
+#[cfg(target_arch = "wasm32")]
+impl<T> MaybeSend for T {}
+
+/// Handle to a background task spawned by an agent, e.g. a notification
+/// forwarder. Dropping it detaches the task.
+#[derive(Debug)]
+pub struct TaskHandle {
+    abort: AbortHandle,
+    finished: Arc<AtomicBool>,
+    done: oneshot::Receiver<()>,
+}
+
+impl TaskHandle {
+    pub(crate) fn is_finished(&self) -> bool {
+        self.finished.load(Ordering::SeqCst) || self.abort.is_aborted()
+    }
+
+    pub(crate) fn abort(&self) {
+        self.abort.abort();
+    }
+}
+
+impl Future for TaskHandle {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        // a dropped sender means the task was aborted, which also ends it
+        Pin::new(&mut self.done).poll(cx).map(|_| ())
+    }
+}
+
+/// Run `future` in the background.
+pub(crate) fn spawn<F>(future: F) -> TaskHandle
+where
+    F: Future<Output = ()> + MaybeSend + 'static,
+{
+    let (future, abort) = abortable(future);
+    let (sender, done) = oneshot::channel();
+    let finished = Arc::new(AtomicBool::new(false));
+    let finished_flag = finished.clone();
+    imp::spawn_detached(async move {
+        let _ = future.await;
+        finished_flag.store(true, Ordering::SeqCst);
+        let _ = sender.send(());
+    });
+    TaskHandle {
+        abort,
+        finished,
+        done,
+    }
+}
+
+pub(crate) async fn sleep_until(deadline: Instant) {
+    sleep(deadline.saturating_duration_since(Instant::now())).await
+}
+
+/// Returned by [`timeout`] and [`timeout_at`] when the deadline passed first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Elapsed;
+
+pub(crate) async fn timeout<F: Future>(
+    duration: Duration,
+    future: F,
+) -> Result<F::Output, Elapsed> {
+    timeout_at(Instant::now() + duration, future).await
+}
+
+pub(crate) async fn timeout_at<F: Future>(
+    deadline: Instant,
+    future: F,
+) -> Result<F::Output, Elapsed> {
+    tokio::select! {
+        biased;
+        output = future => Ok(output),
+        _ = sleep_until(deadline) => Err(Elapsed),
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+mod imp {
+    use std::{future::Future, sync::OnceLock, time::Duration};
+
+    use futures::future::BoxFuture;
+
+    pub(crate) use std::time::{Instant, SystemTime};
+
+    /// Executor the agent spawns its background tasks on and sleeps with.
+    ///
+    /// Tokio is used unless another runtime is installed with
+    /// [`set_runtime`]. Channels and locks are runtime independent. HTTP
+    /// requests and MCP clients still need a tokio reactor, e.g. through a
+    /// compatibility layer such as `async-compat`.
+    ///
+    /// ```no_run
+    /// use std::time::Duration;
+    ///
+    /// use futures::future::BoxFuture;
+    /// use reagent_rs::Runtime;
+    ///
+    /// struct Smol;
+    ///
+    /// impl Runtime for Smol {
+    ///     fn spawn(&self, task: BoxFuture<'static, ()>) {
+    /// #       let _ = task;
+    ///         // smol::spawn(task).detach();
+    ///     }
+    ///
+    ///     fn sleep(&self, duration: Duration) -> BoxFuture<'static, ()> {
+    /// #       let _ = duration;
+    ///         // Box::pin(async move { smol::Timer::after(duration).await; })
+    /// #       Box::pin(async {})
+    ///     }
+    /// }
+    ///
+    /// reagent_rs::set_runtime(Smol);
+    /// ```
+    pub trait Runtime: Send + Sync {
+        /// Run `task` in the background until it completes.
+        fn spawn(&self, task: BoxFuture<'static, ()>);
+        /// Complete after `duration`.
+        fn sleep(&self, duration: Duration) -> BoxFuture<'static, ()>;
+    }
+
+    /// The default [`Runtime`]. Spawning requires a tokio runtime context.
+    #[derive(Debug, Clone, Copy, Default)]
+    pub struct TokioRuntime;
+
+    impl Runtime for TokioRuntime {
+        fn spawn(&self, task: BoxFuture<'static, ()>) {
+            tokio::spawn(task);
+        }
+
+        fn sleep(&self, duration: Duration) -> BoxFuture<'static, ()> {
+            Box::pin(tokio::time::sleep(duration))
+        }
+    }
+
+    static RUNTIME: OnceLock<Box<dyn Runtime>> = OnceLock::new();
+
+    /// Install the runtime all agents use. Call it once at startup: returns
+    /// `false`, keeping the current runtime, if a runtime was already
+    /// installed or an agent already spawned a task or slept.
+    pub fn set_runtime<R: Runtime + 'static>(runtime: R) -> bool {
+        RUNTIME.set(Box::new(runtime)).is_ok()
+    }
+
+    fn runtime() -> &'static dyn Runtime {
+        RUNTIME.get_or_init(|| Box::new(TokioRuntime)).as_ref()
+    }
+
+    pub(crate) fn spawn_detached<F>(future: F)
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        runtime().spawn(Box::pin(future));
+    }
+
+    pub(crate) async fn sleep(duration: Duration) {
+        runtime().sleep(duration).await
+    }
+
+    /// Run `work`, e.g. file I/O, on a thread that may block: tokio's
+    /// blocking pool when there is a tokio runtime, a new thread otherwise.
+    pub(crate) async fn blocking<T, F>(work: F) -> T
+    where
+        F: FnOnce() -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        let (sender, done) = futures::channel::oneshot::channel();
+        let work = move || {
+            let _ = sender.send(work());
+        };
+        match tokio::runtime::Handle::try_current() {
+            Ok(handle) => drop(handle.spawn_blocking(work)),
+            Err(_) => drop(std::thread::spawn(work)),
+        }
+        done.await.expect("blocking work panicked")
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+mod imp {
+    use std::{future::Future, time::Duration};
+
+    pub(crate) use web_time::{Instant, SystemTime};
+
+    pub(crate) fn spawn_detached<F>(future: F)
+    where
+        F: Future<Output = ()> + 'static,
+    {
+        wasm_bindgen_futures::spawn_local(future);
+    }
+
+    pub(crate) async fn sleep(duration: Duration) {
+        gloo_timers::future::sleep(duration).await
+    }
+
+    /// There are no threads to move `work` to, so it runs right away.
+    pub(crate) async fn blocking<T, F>(work: F) -> T
+    where
+        F: FnOnce() -> T + 'static,
+    {
+        work()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn timeout_returns_elapsed_for_slow_futures() {
+        assert_eq!(timeout(Duration::from_millis(50), async { 1 }).await, Ok(1));
+        let slow = sleep(Duration::from_secs(60));
+        assert_eq!(timeout(Duration::from_millis(10), slow).await, Err(Elapsed));
+
+        let mut task = spawn(async {});
+        (&mut task).await;
+        assert!(task.is_finished());
+    }
+}
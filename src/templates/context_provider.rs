@@ -0,0 +1,305 @@
+use std::{
+    fmt,
+    future::Future,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use futures::future::join_all;
+
+use crate::{services::runtime::Instant, Message};
+
+use super::TemplateDataSource;
+
+/// Future returned by [`ContextProvider::provide`].
+pub type ContextFuture<'a> = Pin<Box<dyn Future<Output = Result<String, String>> + Send + 'a>>;
+
+/// A block of dynamic context (current time, user profile, retrieved
+/// documents, ...) the agent fetches on every invocation and sends to the
+/// model as a system message, see [`ContextProviders`].
+///
+/// Unlike a [`TemplateDataSource`], a provider needs no placeholder in the
+/// prompt: its block is added to the request as it is.
+///
+/// ```
+/// use reagent_rs::templates::{ContextFuture, ContextProvider};
+///
+/// struct UserProfile;
+///
+/// impl ContextProvider for UserProfile {
+///     fn name(&self) -> &str {
+///         "user profile"
+///     }
+///
+///     fn provide<'a>(&'a self, _prompt: Option<&'a str>) -> ContextFuture<'a> {
+///         Box::pin(async { Ok("Name: Alice\nLanguage: English".to_string()) })
+///     }
+/// }
+/// ```
+pub trait ContextProvider: Send + Sync {
+    /// Heading of the block in the context message.
+    fn name(&self) -> &str;
+
+    /// Fetch the block for a request whose latest user message is `prompt`.
+    /// An empty block is left out, an error is logged and the block skipped.
+    fn provide<'a>(&'a self, prompt: Option<&'a str>) -> ContextFuture<'a>;
+}
+
+impl fmt::Debug for dyn ContextProvider {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("ContextProvider")
+            .field(&self.name())
+            .finish()
+    }
+}
+
+/// Provides the values of a [`TemplateDataSource`] as `key: value` lines,
+/// sorted by key.
+pub struct DataSourceContext {
+    name: String,
+    source: Box<dyn TemplateDataSource>,
+}
+
+impl DataSourceContext {
+    pub fn new<D: TemplateDataSource + 'static>(name: impl Into<String>, source: D) -> Self {
+        Self {
+            name: name.into(),
+            source: Box::new(source),
+        }
+    }
+}
+
+impl ContextProvider for DataSourceContext {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn provide<'a>(&'a self, _prompt: Option<&'a str>) -> ContextFuture<'a> {
+        Box::pin(async move {
+            let mut values: Vec<(String, String)> =
+                self.source.get_values().await.into_iter().collect();
+            values.sort();
+            let lines: Vec<String> = values
+                .into_iter()
+                .map(|(key, value)| format!("{key}: {value}"))
+                .collect();
+            Ok(lines.join("\n"))
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+struct ContextEntry {
+    provider: Arc<dyn ContextProvider>,
+    ttl: Option<Duration>,
+    /// Last block with the time it was fetched, shared between clones.
+    cached: Arc<Mutex<Option<(Instant, String)>>>,
+}
+
+impl ContextEntry {
+    async fn block(&self, prompt: Option<&str>) -> Option<String> {
+        if let Some(ttl) = self.ttl {
+            let cached = self.cached.lock().unwrap_or_else(|e| e.into_inner());
+            if let Some((fetched, block)) = cached.as_ref() {
+                if fetched.elapsed() < ttl {
+                    return Some(block.clone());
+                }
+            }
+        }
+
+        match self.provider.provide(prompt).await {
+            Ok(block) => {
+                if self.ttl.is_some() {
+                    *self.cached.lock().unwrap_or_else(|e| e.into_inner()) =
+                        Some((Instant::now(), block.clone()));
+                }
+                Some(block)
+            }
+            Err(e) => {
+                tracing::warn!(
+                    provider = self.provider.name(),
+                    error = %e,
+                    "Context provider failed, leaving its block out"
+                );
+                None
+            }
+        }
+    }
+}
+
+/// Context blocks fetched on every request of an agent and sent as one
+/// system message after the system prompt. The message is not stored in the
+/// agent's history.
+///
+/// Providers are queried concurrently. A provider added with a TTL is asked
+/// again only once its last block is older than the TTL; the cache is shared
+/// between clones of the set, and does not depend on the prompt.
+///
+/// ```
+/// use std::time::Duration;
+/// use reagent_rs::templates::{ContextFuture, ContextProvider, ContextProviders};
+///
+/// struct Clock;
+///
+/// impl ContextProvider for Clock {
+///     fn name(&self) -> &str {
+///         "time"
+///     }
+///
+///     fn provide<'a>(&'a self, _prompt: Option<&'a str>) -> ContextFuture<'a> {
+///         Box::pin(async { Ok("2025-06-01 12:00 UTC".to_string()) })
+///     }
+/// }
+///
+/// let context = ContextProviders::new().add_cached(Clock, Duration::from_secs(60));
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct ContextProviders {
+    entries: Vec<ContextEntry>,
+}
+
+impl ContextProviders {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a provider queried on every request.
+    pub fn add_provider(self, provider: impl ContextProvider + 'static) -> Self {
+        self.push(Arc::new(provider), None)
+    }
+
+    /// Add a provider whose block is reused for `ttl`.
+    pub fn add_cached(self, provider: impl ContextProvider + 'static, ttl: Duration) -> Self {
+        self.push(Arc::new(provider), Some(ttl))
+    }
+
+    /// Add a shared provider, cached for `ttl` if given.
+    pub fn push(mut self, provider: Arc<dyn ContextProvider>, ttl: Option<Duration>) -> Self {
+        self.entries.push(ContextEntry {
+            provider,
+            ttl,
+            cached: Arc::default(),
+        });
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Names of the providers, in the order their blocks appear.
+    pub fn names(&self) -> Vec<&str> {
+        self.entries.iter().map(|e| e.provider.name()).collect()
+    }
+
+    /// Drop the cached blocks, so every provider is asked again.
+    pub fn invalidate(&self) {
+        for entry in &self.entries {
+            *entry.cached.lock().unwrap_or_else(|e| e.into_inner()) = None;
+        }
+    }
+
+    /// The context message for `prompt`, `None` if all blocks are empty.
+    pub(crate) async fn message(&self, prompt: Option<&str>) -> Option<Message> {
+        let blocks = join_all(self.entries.iter().map(|entry| entry.block(prompt))).await;
+        let sections: Vec<String> = self
+            .entries
+            .iter()
+            .zip(blocks)
+            .filter_map(|(entry, block)| {
+                let block = block?;
+                let block = block.trim();
+                (!block.is_empty()).then(|| format!("## {}\n{}", entry.provider.name(), block))
+            })
+            .collect();
+        if sections.is_empty() {
+            return None;
+        }
+        Some(Message::system(format!(
+            "# Context\n\n{}",
+            sections.join("\n\n")
+        )))
+    }
+}
+
+/// Insert the context message after the leading system messages.
+pub(crate) fn insert_context(messages: &mut Vec<Message>, context: Message) {
+    let position = messages
+        .iter()
+        .position(|m| !matches!(m.role, crate::Role::System | crate::Role::Developer))
+        .unwrap_or(messages.len());
+    messages.insert(position, context);
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+
+    struct Counter {
+        calls: Arc<AtomicUsize>,
+    }
+
+    impl ContextProvider for Counter {
+        fn name(&self) -> &str {
+            "counter"
+        }
+
+        fn provide<'a>(&'a self, prompt: Option<&'a str>) -> ContextFuture<'a> {
+            let call = self.calls.fetch_add(1, Ordering::SeqCst);
+            let prompt = prompt.unwrap_or_default().to_string();
+            Box::pin(async move { Ok(format!("call {call} for {prompt}")) })
+        }
+    }
+
+    struct Failing;
+
+    impl ContextProvider for Failing {
+        fn name(&self) -> &str {
+            "failing"
+        }
+
+        fn provide<'a>(&'a self, _prompt: Option<&'a str>) -> ContextFuture<'a> {
+            Box::pin(async { Err("unavailable".to_string()) })
+        }
+    }
+
+    #[tokio::test]
+    async fn cached_blocks_are_reused_until_invalidated() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let providers = ContextProviders::new()
+            .add_cached(
+                Counter {
+                    calls: calls.clone(),
+                },
+                Duration::from_secs(3600),
+            )
+            .add_provider(Failing);
+
+        let first = providers.message(Some("hi")).await.unwrap();
+        let second = providers.clone().message(Some("again")).await.unwrap();
+        assert_eq!(first.content, second.content);
+        assert_eq!(
+            first.content.as_deref(),
+            Some("# Context\n\n## counter\ncall 0 for hi")
+        );
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+        providers.invalidate();
+        let third = providers.message(Some("again")).await.unwrap();
+        assert_eq!(
+            third.content.as_deref(),
+            Some("# Context\n\n## counter\ncall 1 for again")
+        );
+    }
+
+    #[tokio::test]
+    async fn context_goes_after_the_system_prompt() {
+        let mut messages = vec![Message::system("prompt"), Message::user("hi")];
+        insert_context(&mut messages, Message::system("context"));
+        assert_eq!(messages[1].content.as_deref(), Some("context"));
+        assert!(ContextProviders::new().message(None).await.is_none());
+    }
+}
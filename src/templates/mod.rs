@@ -1,10 +1,23 @@
+mod context_provider;
 mod core_templates;
 mod data_source;
 mod errors;
+mod prompt_library;
+mod system_prompt;
 mod template;
 
+pub(crate) use self::context_provider::insert_context;
+pub(crate) use self::prompt_library::LibraryPrompt;
 pub use self::{
-    core_templates::*, data_source::TemplateDataSource, errors::LoadTemplateError,
+    context_provider::{ContextFuture, ContextProvider, ContextProviders, DataSourceContext},
+    core_templates::*,
+    data_source::TemplateDataSource,
+    errors::LoadTemplateError,
+    prompt_library::{
+        FilePromptStore, InMemoryPromptStore, PromptLibrary, PromptStore, PromptStoreFuture,
+        PromptVersion, PromptVersionChange, StoredPrompt,
+    },
+    system_prompt::SystemPromptBuilder,
     template::Template,
 };
 
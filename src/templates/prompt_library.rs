@@ -0,0 +1,484 @@
+use std::{
+    collections::HashMap,
+    fmt, fs,
+    future::Future,
+    path::PathBuf,
+    pin::Pin,
+    sync::{Arc, Mutex},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    agent::unix_millis,
+    services::runtime::{self, SystemTime},
+    Agent, AgentError, NotificationHandler, Role,
+};
+
+pub type PromptStoreFuture<'a, T> =
+    Pin<Box<dyn Future<Output = Result<T, AgentError>> + Send + 'a>>;
+
+/// One version of a prompt in a [`PromptLibrary`]. Versions are never
+/// changed once published.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PromptVersion {
+    pub name: String,
+    pub version: u32,
+    pub content: String,
+    pub created_at_ms: u64,
+}
+
+impl PromptVersion {
+    /// `name@vN`
+    pub fn id(&self) -> String {
+        format!("{}@v{}", self.name, self.version)
+    }
+}
+
+/// All versions of a prompt and the one agents use, as kept by a
+/// [`PromptStore`].
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StoredPrompt {
+    pub name: String,
+    /// Version agents use, `None` while there are only drafts.
+    pub active: Option<u32>,
+    /// Versions in ascending order.
+    pub versions: Vec<PromptVersion>,
+}
+
+/// The active version of a library prompt changed, sent as
+/// [`NotificationContent::PromptVersionChanged`](crate::NotificationContent::PromptVersionChanged)
+/// by the agents using it before their next invocation.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PromptVersionChange {
+    pub name: String,
+    pub previous: u32,
+    pub version: u32,
+}
+
+/// Persistence of a [`PromptLibrary`]. Implement it to keep prompts in a
+/// database; [`FilePromptStore`] keeps them in a directory and
+/// [`InMemoryPromptStore`] in the process.
+pub trait PromptStore: Send + Sync {
+    /// Every stored prompt, read when the library is opened.
+    fn load(&self) -> PromptStoreFuture<'_, Vec<StoredPrompt>>;
+
+    /// Store `prompt`, replacing the earlier state of the prompt of that name.
+    fn save<'a>(&'a self, prompt: &'a StoredPrompt) -> PromptStoreFuture<'a, ()>;
+}
+
+impl fmt::Debug for dyn PromptStore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("PromptStore")
+    }
+}
+
+/// [`PromptStore`] in memory.
+#[derive(Debug, Clone, Default)]
+pub struct InMemoryPromptStore {
+    prompts: Arc<Mutex<HashMap<String, StoredPrompt>>>,
+}
+
+impl InMemoryPromptStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl PromptStore for InMemoryPromptStore {
+    fn load(&self) -> PromptStoreFuture<'_, Vec<StoredPrompt>> {
+        let prompts = lock(&self.prompts).values().cloned().collect();
+        Box::pin(async { Ok(prompts) })
+    }
+
+    fn save<'a>(&'a self, prompt: &'a StoredPrompt) -> PromptStoreFuture<'a, ()> {
+        lock(&self.prompts).insert(prompt.name.clone(), prompt.clone());
+        Box::pin(async { Ok(()) })
+    }
+}
+
+/// [`PromptStore`] keeping every prompt as `<name>.json` in a directory,
+/// e.g. one checked into the repository of the service. Files are read and
+/// written on a blocking thread, off the async runtime.
+#[derive(Debug, Clone)]
+pub struct FilePromptStore {
+    dir: PathBuf,
+}
+
+impl FilePromptStore {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    fn read(&self) -> Result<Vec<StoredPrompt>, AgentError> {
+        let entries = match fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(store_error(&self.dir, e)),
+        };
+        let mut prompts = Vec::new();
+        for entry in entries {
+            let path = entry.map_err(|e| store_error(&self.dir, e))?.path();
+            if path.extension().is_some_and(|ext| ext == "json") {
+                let json = fs::read_to_string(&path).map_err(|e| store_error(&path, e))?;
+                prompts.push(serde_json::from_str(&json).map_err(|e| store_error(&path, e))?);
+            }
+        }
+        Ok(prompts)
+    }
+
+    fn write(&self, prompt: &StoredPrompt) -> Result<(), AgentError> {
+        fs::create_dir_all(&self.dir).map_err(|e| store_error(&self.dir, e))?;
+        let path = self.dir.join(format!("{}.json", prompt.name));
+        let json = serde_json::to_string_pretty(prompt).map_err(|e| store_error(&path, e))?;
+        fs::write(&path, json).map_err(|e| store_error(&path, e))
+    }
+}
+
+impl PromptStore for FilePromptStore {
+    fn load(&self) -> PromptStoreFuture<'_, Vec<StoredPrompt>> {
+        let store = self.clone();
+        Box::pin(runtime::blocking(move || store.read()))
+    }
+
+    fn save<'a>(&'a self, prompt: &'a StoredPrompt) -> PromptStoreFuture<'a, ()> {
+        let (store, prompt) = (self.clone(), prompt.clone());
+        Box::pin(runtime::blocking(move || store.write(&prompt)))
+    }
+}
+
+fn store_error(path: &std::path::Path, e: impl fmt::Display) -> AgentError {
+    AgentError::Runtime(format!("Prompt store {}: {e}", path.display()))
+}
+
+/// Named, versioned system prompts shared by the agents of a service, so
+/// prompts can be changed in production and changed back.
+///
+/// Publishing a prompt adds a new version and makes it the active one;
+/// [`draft`](Self::draft) adds one without activating it. Agents built with
+/// [`AgentBuilder::set_library_prompt`](crate::AgentBuilder::set_library_prompt)
+/// start with the active version, switch to a newly activated one before
+/// their next invocation and notify it as
+/// [`PromptVersionChanged`](crate::NotificationContent::PromptVersionChanged).
+/// [`activate`](Self::activate) and [`rollback`](Self::rollback) restore
+/// older versions. Clones share the prompts.
+///
+/// ```
+/// use reagent_rs::PromptLibrary;
+///
+/// # #[tokio::main]
+/// # async fn main() -> Result<(), reagent_rs::AgentError> {
+/// let library = PromptLibrary::new();
+/// library.publish("support", "You answer support tickets.").await?;
+/// library.publish("support", "You answer support tickets politely.").await?;
+/// assert_eq!(library.active("support").unwrap().version, 2);
+///
+/// library.rollback("support").await?;
+/// assert_eq!(library.get("support").unwrap().id(), "support@v1");
+/// assert!(library.get("support@v2").is_some());
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Default)]
+pub struct PromptLibrary {
+    prompts: Arc<Mutex<HashMap<String, StoredPrompt>>>,
+    /// Serializes changes, so they reach the store in order.
+    changes: Arc<tokio::sync::Mutex<()>>,
+    store: Option<Arc<dyn PromptStore>>,
+}
+
+impl PromptLibrary {
+    /// Library kept in memory only.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Library persisted in `store`, with the prompts stored so far.
+    pub async fn open(store: Arc<dyn PromptStore>) -> Result<Self, AgentError> {
+        let prompts = store
+            .load()
+            .await?
+            .into_iter()
+            .map(|prompt| (prompt.name.clone(), prompt))
+            .collect();
+        Ok(Self {
+            prompts: Arc::new(Mutex::new(prompts)),
+            changes: Arc::default(),
+            store: Some(store),
+        })
+    }
+
+    /// Add `content` as the next version of `name` and activate it.
+    /// Returns the new version.
+    pub async fn publish(
+        &self,
+        name: impl Into<String>,
+        content: impl Into<String>,
+    ) -> Result<u32, AgentError> {
+        self.add_version(name.into(), content.into(), true).await
+    }
+
+    /// Add `content` as the next version of `name` without activating it,
+    /// e.g. to try it out with [`get`](Self::get) first.
+    pub async fn draft(
+        &self,
+        name: impl Into<String>,
+        content: impl Into<String>,
+    ) -> Result<u32, AgentError> {
+        self.add_version(name.into(), content.into(), false).await
+    }
+
+    /// Make `version` of `name` the one agents use.
+    pub async fn activate(&self, name: &str, version: u32) -> Result<(), AgentError> {
+        self.change(name, |prompt| {
+            if !prompt.versions.iter().any(|v| v.version == version) {
+                return Err(unknown_prompt(&format!("{name}@v{version}")));
+            }
+            prompt.active = Some(version);
+            Ok(())
+        })
+        .await
+    }
+
+    /// Activate the version before the active one of `name`. Returns the
+    /// version now active.
+    pub async fn rollback(&self, name: &str) -> Result<u32, AgentError> {
+        let mut restored = 0;
+        self.change(name, |prompt| {
+            let previous = prompt
+                .versions
+                .iter()
+                .map(|v| v.version)
+                .filter(|v| prompt.active.is_some_and(|active| *v < active))
+                .max()
+                .ok_or_else(|| {
+                    AgentError::Runtime(format!("Prompt `{name}` has no earlier version"))
+                })?;
+            prompt.active = Some(previous);
+            restored = previous;
+            Ok(())
+        })
+        .await?;
+        Ok(restored)
+    }
+
+    /// The active version of `name`.
+    pub fn active(&self, name: &str) -> Option<PromptVersion> {
+        let prompts = lock(&self.prompts);
+        let prompt = prompts.get(name)?;
+        let active = prompt.active?;
+        prompt
+            .versions
+            .iter()
+            .find(|v| v.version == active)
+            .cloned()
+    }
+
+    /// A prompt by `name@vN`, or by `name` for the active version.
+    pub fn get(&self, reference: &str) -> Option<PromptVersion> {
+        let Some((name, version)) = reference.split_once('@') else {
+            return self.active(reference);
+        };
+        let version: u32 = version.strip_prefix('v').unwrap_or(version).parse().ok()?;
+        lock(&self.prompts)
+            .get(name)?
+            .versions
+            .iter()
+            .find(|v| v.version == version)
+            .cloned()
+    }
+
+    /// Every version of `name`, in ascending order.
+    pub fn versions(&self, name: &str) -> Vec<PromptVersion> {
+        lock(&self.prompts)
+            .get(name)
+            .map(|prompt| prompt.versions.clone())
+            .unwrap_or_default()
+    }
+
+    /// Names of the prompts, sorted.
+    pub fn names(&self) -> Vec<String> {
+        let mut names: Vec<String> = lock(&self.prompts).keys().cloned().collect();
+        names.sort();
+        names
+    }
+
+    async fn add_version(
+        &self,
+        name: String,
+        content: String,
+        activate: bool,
+    ) -> Result<u32, AgentError> {
+        if name.is_empty() || name.contains(['@', '/', '\\']) {
+            return Err(AgentError::Runtime(format!("Invalid prompt name `{name}`")));
+        }
+        let mut added = 0;
+        self.change(&name, |prompt| {
+            added = prompt.versions.last().map_or(1, |v| v.version + 1);
+            prompt.versions.push(PromptVersion {
+                name: name.clone(),
+                version: added,
+                content,
+                created_at_ms: unix_millis(SystemTime::now()),
+            });
+            if activate {
+                prompt.active = Some(added);
+            }
+            Ok(())
+        })
+        .await?;
+        Ok(added)
+    }
+
+    /// Apply `update` to the prompt `name`, store it and then keep it.
+    async fn change(
+        &self,
+        name: &str,
+        update: impl FnOnce(&mut StoredPrompt) -> Result<(), AgentError>,
+    ) -> Result<(), AgentError> {
+        let _change = self.changes.lock().await;
+        let mut prompt = lock(&self.prompts)
+            .get(name)
+            .cloned()
+            .unwrap_or_else(|| StoredPrompt {
+                name: name.to_string(),
+                ..Default::default()
+            });
+        update(&mut prompt)?;
+        if let Some(store) = &self.store {
+            store.save(&prompt).await?;
+        }
+        lock(&self.prompts).insert(name.to_string(), prompt);
+        Ok(())
+    }
+}
+
+impl fmt::Debug for PromptLibrary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PromptLibrary")
+            .field("prompts", &self.names())
+            .field("store", &self.store)
+            .finish()
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> std::sync::MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+fn unknown_prompt(reference: &str) -> AgentError {
+    AgentError::Runtime(format!("There is no prompt `{reference}`"))
+}
+
+/// Library prompt an agent's system prompt was built from, see
+/// [`AgentBuilder::set_library_prompt`](crate::AgentBuilder::set_library_prompt).
+#[derive(Debug, Clone)]
+pub(crate) struct LibraryPrompt {
+    library: PromptLibrary,
+    active: PromptVersion,
+}
+
+impl LibraryPrompt {
+    /// The active version of `name` in `library`.
+    pub(crate) fn resolve(library: PromptLibrary, name: &str) -> Option<Self> {
+        let active = library.active(name)?;
+        Some(Self { library, active })
+    }
+
+    pub(crate) fn content(&self) -> &str {
+        &self.active.content
+    }
+}
+
+impl Agent {
+    /// Id (`name@vN`) of the library prompt version the agent uses, see
+    /// [`PromptLibrary`].
+    pub fn prompt_version(&self) -> Option<String> {
+        self.library_prompt
+            .as_ref()
+            .map(|prompt| prompt.active.id())
+    }
+
+    /// Switch to the active version of the library prompt, if it changed
+    /// since the last invocation. The rest of the system prompt, e.g. the
+    /// skill descriptions, is kept.
+    pub(crate) async fn sync_library_prompt(&mut self) {
+        let Some(prompt) = &mut self.library_prompt else {
+            return;
+        };
+        let active = match prompt.library.active(&prompt.active.name) {
+            Some(active) if active.version != prompt.active.version => active,
+            _ => return,
+        };
+        let previous = std::mem::replace(&mut prompt.active, active.clone());
+
+        self.system_prompt =
+            if !previous.content.is_empty() && self.system_prompt.contains(&previous.content) {
+                self.system_prompt
+                    .replacen(&previous.content, &active.content, 1)
+            } else {
+                active.content.clone()
+            };
+        if let Some(first) = self.history.first_mut().filter(|m| m.role == Role::System) {
+            first.content = Some(self.system_prompt.clone());
+            self.sync_history();
+        }
+
+        tracing::info!(
+            agent = self.name.as_str(),
+            prompt = active.id().as_str(),
+            "Prompt version changed"
+        );
+        self.notify_prompt_version_changed(PromptVersionChange {
+            name: active.name,
+            previous: previous.version,
+            version: active.version,
+        })
+        .await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{testing::FlowTestHarness, AgentBuilder, NotificationContent};
+
+    #[tokio::test]
+    async fn agents_follow_the_active_version() {
+        let dir = std::env::temp_dir().join(format!("prompts-{}", uuid::Uuid::new_v4()));
+        let library = PromptLibrary::open(Arc::new(FilePromptStore::new(&dir)))
+            .await
+            .unwrap();
+        library.publish("support", "Be brief.").await.unwrap();
+        let builder = AgentBuilder::default()
+            .set_model("test")
+            .set_library_prompt(&library, "support");
+        let mut harness = FlowTestHarness::new(builder)
+            .await
+            .unwrap()
+            .reply("Hi.")
+            .reply("Hello there.");
+        harness.run("1").await.unwrap();
+        assert_eq!(
+            harness.agent().prompt_version().as_deref(),
+            Some("support@v1")
+        );
+
+        library.publish("support", "Be verbose.").await.unwrap();
+        harness.run("2").await.unwrap();
+        assert_eq!(harness.history()[0].content.as_deref(), Some("Be verbose."));
+        harness.assert_notified(Some(&harness.agent().name), |content| {
+            matches!(content, NotificationContent::PromptVersionChanged(change)
+                if change.previous == 1 && change.version == 2)
+        });
+
+        // versions survive in the store and can be restored
+        let reopened = PromptLibrary::open(Arc::new(FilePromptStore::new(&dir)))
+            .await
+            .unwrap();
+        assert_eq!(reopened.rollback("support").await.unwrap(), 1);
+        assert_eq!(reopened.versions("support").len(), 2);
+        assert!(reopened.rollback("support").await.is_err());
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,135 @@
+/// A system prompt composed of named sections.
+///
+/// Sections are rendered in insertion order, separated by a blank line.
+/// Setting a section that already exists replaces its content in place, so
+/// a single part of a larger prompt (e.g. one of a prebuild) can be changed
+/// without copying the rest.
+///
+/// ```
+/// use reagent_rs::SystemPromptBuilder;
+///
+/// let prompt = SystemPromptBuilder::new()
+///     .persona("You are a weather assistant.")
+///     .constraints("Only answer questions about the weather.")
+///     .output_format("Reply in one short sentence.")
+///     .constraints("Never guess, say when you don't know.")
+///     .build();
+///
+/// assert_eq!(
+///     prompt,
+///     "You are a weather assistant.\n\nNever guess, say when you don't know.\n\nReply in one short sentence."
+/// );
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SystemPromptBuilder {
+    sections: Vec<(String, String)>,
+}
+
+impl SystemPromptBuilder {
+    /// Who the agent is and what it does.
+    pub const PERSONA: &'static str = "persona";
+    /// Rules the agent has to follow.
+    pub const CONSTRAINTS: &'static str = "constraints";
+    /// How and when to use the available tools.
+    pub const TOOLS_GUIDE: &'static str = "tools_guide";
+    /// Expected shape of the response.
+    pub const OUTPUT_FORMAT: &'static str = "output_format";
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the content of section `name`, replacing it if it exists
+    /// and appending it otherwise.
+    pub fn section(mut self, name: impl Into<String>, content: impl Into<String>) -> Self {
+        self.set_section(name, content);
+        self
+    }
+
+    /// In-place variant of [`SystemPromptBuilder::section`].
+    pub fn set_section(&mut self, name: impl Into<String>, content: impl Into<String>) {
+        let name = name.into();
+        let content = content.into();
+        match self.sections.iter_mut().find(|(n, _)| *n == name) {
+            Some((_, existing)) => *existing = content,
+            None => self.sections.push((name, content)),
+        }
+    }
+
+    pub fn persona(self, content: impl Into<String>) -> Self {
+        self.section(Self::PERSONA, content)
+    }
+
+    pub fn constraints(self, content: impl Into<String>) -> Self {
+        self.section(Self::CONSTRAINTS, content)
+    }
+
+    pub fn tools_guide(self, content: impl Into<String>) -> Self {
+        self.section(Self::TOOLS_GUIDE, content)
+    }
+
+    pub fn output_format(self, content: impl Into<String>) -> Self {
+        self.section(Self::OUTPUT_FORMAT, content)
+    }
+
+    /// Drop section `name`, if present.
+    pub fn remove_section(mut self, name: &str) -> Self {
+        self.sections.retain(|(n, _)| n != name);
+        self
+    }
+
+    /// Content of section `name`, if present.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.sections
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, content)| content.as_str())
+    }
+
+    /// Names of all sections, in render order.
+    pub fn section_names(&self) -> impl Iterator<Item = &str> {
+        self.sections.iter().map(|(name, _)| name.as_str())
+    }
+
+    /// Render the prompt. Empty sections are skipped.
+    pub fn build(&self) -> String {
+        self.sections
+            .iter()
+            .map(|(_, content)| content.trim())
+            .filter(|content| !content.is_empty())
+            .collect::<Vec<_>>()
+            .join("\n\n")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sections_keep_their_position_when_replaced() {
+        let prompt = SystemPromptBuilder::new()
+            .persona("Persona")
+            .section("examples", "Examples")
+            .output_format("Format")
+            .section("examples", "Better examples");
+
+        assert_eq!(
+            prompt.section_names().collect::<Vec<_>>(),
+            vec!["persona", "examples", "output_format"]
+        );
+        assert_eq!(prompt.build(), "Persona\n\nBetter examples\n\nFormat");
+    }
+
+    #[test]
+    fn removed_and_empty_sections_are_not_rendered() {
+        let prompt = SystemPromptBuilder::new()
+            .persona("Persona")
+            .constraints("")
+            .tools_guide("Tools")
+            .remove_section(SystemPromptBuilder::TOOLS_GUIDE);
+
+        assert_eq!(prompt.get("tools_guide"), None);
+        assert_eq!(prompt.build(), "Persona");
+    }
+}
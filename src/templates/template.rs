@@ -4,7 +4,10 @@ use std::{collections::HashMap, fs, path::PathBuf};
 use tracing::{span, Level};
 use tracing_opentelemetry::OpenTelemetrySpanExt;
 
-use crate::templates::errors::LoadTemplateError;
+use crate::{
+    services::llm::{span_content, RequestLogging},
+    templates::errors::LoadTemplateError,
+};
 
 use super::TemplateDataSource;
 
@@ -57,6 +60,11 @@ impl Template {
         }
     }
 
+    /// The raw template text, with its placeholders.
+    pub fn content(&self) -> &str {
+        &self.content
+    }
+
     /// Create a template without a data source.
     ///
     /// Useful for static templates where all values will be supplied
@@ -123,6 +131,20 @@ impl Template {
     ///
     /// Any placeholders without a matching key remain unchanged.
     pub async fn compile<K, V>(&self, data: &HashMap<K, V>) -> String
+    where
+        K: Clone + Into<String>,
+        V: Clone + Into<String>,
+    {
+        self.compile_logged(data, None).await
+    }
+
+    /// [`compile`](Self::compile) with the span contents redacted if
+    /// `logging` says so.
+    pub(crate) async fn compile_logged<K, V>(
+        &self,
+        data: &HashMap<K, V>,
+        logging: Option<&RequestLogging>,
+    ) -> String
     where
         K: Clone + Into<String>,
         V: Clone + Into<String>,
@@ -140,7 +162,10 @@ impl Template {
             .collect();
 
         let trace_input = serde_json::to_string_pretty(&data).unwrap_or_default();
-        trace_span.set_attribute("langfuse.observation.input", trace_input);
+        trace_span.set_attribute(
+            "langfuse.observation.input",
+            span_content(logging, trace_input),
+        );
 
         let mut filled_content = self.content.clone();
 
@@ -157,7 +182,10 @@ impl Template {
             filled_content = filled_content.replace(&placeholder, &value);
         }
 
-        trace_span.set_attribute("langfuse.observation.output", filled_content.clone());
+        trace_span.set_attribute(
+            "langfuse.observation.output",
+            span_content(logging, filled_content.clone()),
+        );
 
         filled_content
     }
@@ -0,0 +1,526 @@
+//! Deterministic tests of flows, without a model.
+//!
+//! A [`FlowTestHarness`] builds an agent that takes its model replies from a
+//! [`ModelScript`] instead of a provider and answers tool calls with canned
+//! results. After a run, the history, the notifications and the tool calls
+//! can be checked, which makes multi-agent flows like
+//! [`StatefullPrebuild::plan_and_execute`](crate::StatefullPrebuild::plan_and_execute)
+//! testable: their sub-agents share the script of the top-level agent.
+//!
+//! Enabled with the `testing` feature, e.g. as a dev-dependency:
+//! `reagent-rs = { version = "...", features = ["testing"] }`.
+//!
+//! ```no_run
+//! use reagent_rs::{testing::FlowTestHarness, AgentBuilder, Role};
+//! use serde_json::json;
+//!
+//! # async fn run() -> Result<(), Box<dyn std::error::Error>> {
+//! let mut harness = FlowTestHarness::new(AgentBuilder::default().set_model("test"))
+//!     .await?
+//!     .reply_with_tool_call("get_weather", json!({ "city": "Koper" }))
+//!     .expect_tool_call("get_weather", "Sunny, 24°C")
+//!     .reply("It is sunny in Koper.");
+//!
+//! let reply = harness.run("What is the weather in Koper?").await?;
+//! assert_eq!(reply.content.as_deref(), Some("It is sunny in Koper."));
+//! harness.assert_history_contains(Role::Tool, "Sunny");
+//! harness.verify();
+//! # Ok(())
+//! # }
+//! ```
+
+use serde_json::Value;
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tokio::sync::mpsc::Receiver;
+
+pub use crate::services::llm::providers::scripted::ModelScript;
+use crate::{
+    Agent, AgentBuildError, AgentBuilder, AgentError, AsyncToolFn, ChatRequest, ClientConfig,
+    Function, FunctionParameters, Message, Notification, NotificationContent, Role, Tool, ToolCall,
+    ToolCallFunction, ToolExecutionError, ToolType,
+};
+
+/// How long [`FlowTestHarness::run`] waits for notifications of sub-agents
+/// after the flow returned.
+const FORWARDER_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A tool call made by the agent under test.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecordedToolCall {
+    pub name: String,
+    pub arguments: Value,
+}
+
+#[derive(Debug)]
+struct ExpectedToolCall {
+    name: String,
+    arguments: Option<Value>,
+    result: Result<String, String>,
+}
+
+/// Expected tool calls and what actually happened, shared by the stub
+/// executors of all tools.
+#[derive(Debug, Default)]
+struct ToolScript {
+    expected: VecDeque<ExpectedToolCall>,
+    calls: Vec<RecordedToolCall>,
+    mismatches: Vec<String>,
+}
+
+impl ToolScript {
+    fn call(&mut self, name: &str, arguments: Value) -> Result<String, String> {
+        self.calls.push(RecordedToolCall {
+            name: name.to_string(),
+            arguments: arguments.clone(),
+        });
+
+        let position = self.expected.iter().position(|expected| {
+            expected.name == name
+                && expected
+                    .arguments
+                    .iter()
+                    .all(|expected| *expected == arguments)
+        });
+        match position.and_then(|position| self.expected.remove(position)) {
+            Some(expected) => expected.result,
+            None => {
+                let mismatch = format!("unexpected call of `{name}` with {arguments}");
+                self.mismatches.push(mismatch.clone());
+                Err(mismatch)
+            }
+        }
+    }
+}
+
+/// Runs a flow against scripted model replies and tool results.
+///
+/// Replies are handed out in the order they were added, to whichever agent
+/// (top-level or sub-agent) asks next. Every tool of the agent is replaced by
+/// a stub that answers with the result of the first matching
+/// [`expect_tool_call`](FlowTestHarness::expect_tool_call); calls nobody
+/// expected fail and are reported by [`verify`](FlowTestHarness::verify).
+pub struct FlowTestHarness {
+    agent: Agent,
+    script: ModelScript,
+    tools: Arc<Mutex<ToolScript>>,
+    receiver: Receiver<Notification>,
+    notifications: Vec<Notification>,
+    next_call_id: usize,
+}
+
+impl FlowTestHarness {
+    /// Build the agent configured by `builder`, with its flow, prompts and
+    /// tools, on top of an empty script. The provider settings of the
+    /// builder are not used.
+    pub async fn new(builder: AgentBuilder) -> Result<Self, AgentBuildError> {
+        let script = ModelScript::default();
+        let (mut agent, receiver) = builder
+            .import_client_config(ClientConfig {
+                script: Some(script.clone()),
+                ..Default::default()
+            })
+            .build_with_notification()
+            .await?;
+
+        let tools = Arc::new(Mutex::new(ToolScript::default()));
+        for tool in agent
+            .tools
+            .iter_mut()
+            .chain(agent.local_tools.iter_mut())
+            .flatten()
+        {
+            tool.executor = stub_executor(tool.name().to_string(), tools.clone());
+            tool.output_executor = None;
+        }
+
+        Ok(Self {
+            agent,
+            script,
+            tools,
+            receiver,
+            notifications: Vec::new(),
+            next_call_id: 0,
+        })
+    }
+
+    /// Script a plain text reply of the model.
+    pub fn reply(self, content: impl Into<String>) -> Self {
+        self.reply_message(Message::assistant(content))
+    }
+
+    /// Script a reply of the model requesting a single tool call.
+    pub fn reply_with_tool_call(self, name: impl Into<String>, arguments: Value) -> Self {
+        self.reply_with_tool_calls([(name, arguments)])
+    }
+
+    /// Script a reply of the model requesting several tool calls at once.
+    pub fn reply_with_tool_calls<I, S>(mut self, calls: I) -> Self
+    where
+        I: IntoIterator<Item = (S, Value)>,
+        S: Into<String>,
+    {
+        let mut tool_calls = Vec::new();
+        for (name, arguments) in calls {
+            self.next_call_id += 1;
+            tool_calls.push(ToolCall {
+                id: Some(format!("call_{}", self.next_call_id)),
+                tool_type: ToolType::Function,
+                function: ToolCallFunction {
+                    name: name.into(),
+                    arguments,
+                },
+            });
+        }
+
+        let mut message = Message::assistant(String::new());
+        message.tool_calls = Some(tool_calls);
+        self.reply_message(message)
+    }
+
+    /// Script a reply of the model as a complete message, e.g. with
+    /// thinking or metadata.
+    pub fn reply_message(self, message: Message) -> Self {
+        self.script.push(message);
+        self
+    }
+
+    /// Expect a call of the tool `name` with any arguments and answer it
+    /// with `result`. A tool the agent does not have is added as a stub.
+    pub fn expect_tool_call(self, name: impl Into<String>, result: impl Into<String>) -> Self {
+        self.expect(name.into(), None, Ok(result.into()))
+    }
+
+    /// Like [`expect_tool_call`](FlowTestHarness::expect_tool_call), but only
+    /// matches a call with exactly these arguments.
+    pub fn expect_tool_call_with(
+        self,
+        name: impl Into<String>,
+        arguments: Value,
+        result: impl Into<String>,
+    ) -> Self {
+        self.expect(name.into(), Some(arguments), Ok(result.into()))
+    }
+
+    /// Expect a call of the tool `name` and let it fail with `error`.
+    pub fn fail_tool_call(self, name: impl Into<String>, error: impl Into<String>) -> Self {
+        self.expect(name.into(), None, Err(error.into()))
+    }
+
+    fn expect(
+        mut self,
+        name: String,
+        arguments: Option<Value>,
+        result: Result<String, String>,
+    ) -> Self {
+        let known = self
+            .agent
+            .tools
+            .iter()
+            .flatten()
+            .any(|tool| tool.name() == name);
+        if !known {
+            let tool = stub_tool(&name, self.tools.clone());
+            self.agent.tools.get_or_insert_with(Vec::new).push(tool);
+        }
+
+        self.tools
+            .lock()
+            .unwrap()
+            .expected
+            .push_back(ExpectedToolCall {
+                name,
+                arguments,
+                result,
+            });
+        self
+    }
+
+    /// Invoke the flow of the agent with `prompt` and collect the
+    /// notifications it emits, including those forwarded from sub-agents.
+    pub async fn run(&mut self, prompt: impl Into<String>) -> Result<Message, AgentError> {
+        // the channel is bounded, so it is drained while the flow runs
+        let result = {
+            let mut flow = std::pin::pin!(self.agent.invoke_flow(prompt));
+            loop {
+                tokio::select! {
+                    result = &mut flow => break result,
+                    Some(notification) = self.receiver.recv() => self.notifications.push(notification),
+                }
+            }
+        };
+
+        // forwarders of sub-agents may still be delivering
+        {
+            let mut forwarders = std::pin::pin!(self.agent.await_forwarders(FORWARDER_TIMEOUT));
+            loop {
+                tokio::select! {
+                    _ = &mut forwarders => break,
+                    Some(notification) = self.receiver.recv() => self.notifications.push(notification),
+                }
+            }
+        }
+        while let Ok(notification) = self.receiver.try_recv() {
+            self.notifications.push(notification);
+        }
+
+        result
+    }
+
+    /// The agent under test.
+    pub fn agent(&self) -> &Agent {
+        &self.agent
+    }
+
+    /// The agent under test, e.g. to seed its history or state.
+    pub fn agent_mut(&mut self) -> &mut Agent {
+        &mut self.agent
+    }
+
+    /// History of the top-level agent.
+    pub fn history(&self) -> &[Message] {
+        &self.agent.history
+    }
+
+    /// Notifications of all runs so far, in the order they were received.
+    pub fn notifications(&self) -> &[Notification] {
+        &self.notifications
+    }
+
+    /// Tool calls of all runs so far, in the order they were made.
+    pub fn tool_calls(&self) -> Vec<RecordedToolCall> {
+        self.tools.lock().unwrap().calls.clone()
+    }
+
+    /// Requests the script answered so far, to check what the model was sent.
+    pub fn requests(&self) -> Vec<ChatRequest> {
+        self.script.requests()
+    }
+
+    /// Panic unless a history message with `role` contains `text`.
+    pub fn assert_history_contains(&self, role: Role, text: &str) {
+        let found = self.agent.history.iter().any(|message| {
+            message.role == role
+                && message
+                    .content
+                    .as_deref()
+                    .unwrap_or_default()
+                    .contains(text)
+        });
+        assert!(
+            found,
+            "no {role:?} message in the history contains {text:?}, history: {:#?}",
+            self.agent.history
+        );
+    }
+
+    /// Panic unless a notification of an agent named `agent` matches
+    /// `predicate`. Use `None` to accept notifications of any agent.
+    pub fn assert_notified<F>(&self, agent: Option<&str>, predicate: F)
+    where
+        F: Fn(&NotificationContent) -> bool,
+    {
+        let found = self.notifications.iter().any(|notification| {
+            agent.iter().all(|agent| notification.agent == *agent)
+                && predicate(&notification.content)
+        });
+        assert!(
+            found,
+            "no matching notification from {}, got: {:#?}",
+            agent.unwrap_or("any agent"),
+            self.notifications
+        );
+    }
+
+    /// Panic if a scripted reply or an expected tool call was not used, or if
+    /// a tool was called unexpectedly.
+    pub fn verify(&self) {
+        let tools = self.tools.lock().unwrap();
+        assert!(
+            tools.mismatches.is_empty(),
+            "unexpected tool calls: {:#?}",
+            tools.mismatches
+        );
+        let missing: Vec<&str> = tools.expected.iter().map(|e| e.name.as_str()).collect();
+        assert!(
+            missing.is_empty(),
+            "expected tool calls not made: {missing:?}"
+        );
+        assert_eq!(
+            self.script.remaining(),
+            0,
+            "scripted model replies were not requested"
+        );
+    }
+}
+
+fn stub_executor(name: String, tools: Arc<Mutex<ToolScript>>) -> AsyncToolFn {
+    Arc::new(move |arguments| {
+        let result = tools.lock().unwrap().call(&name, arguments);
+        Box::pin(async move { result.map_err(ToolExecutionError::ExecutionFailed) })
+    })
+}
+
+fn stub_tool(name: &str, tools: Arc<Mutex<ToolScript>>) -> Tool {
+    Tool {
+        tool_type: ToolType::Function,
+        function: Function {
+            name: name.to_string(),
+            description: format!("Scripted `{name}` tool"),
+            parameters: FunctionParameters {
+                param_type: "object".into(),
+                properties: HashMap::new(),
+                required: Vec::new(),
+            },
+        },
+        executor: stub_executor(name.to_string(), tools),
+        output_executor: None,
+        limits: Default::default(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ErrorKind, InvocationSummary, StatefullPrebuild, ToolRetryPolicy};
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn default_flow_runs_scripted_tool_calls() {
+        let mut harness = FlowTestHarness::new(AgentBuilder::default().set_model("test"))
+            .await
+            .unwrap()
+            .reply_with_tool_call("get_weather", json!({ "city": "Koper" }))
+            .expect_tool_call_with("get_weather", json!({ "city": "Koper" }), "Sunny")
+            .reply("It is sunny.");
+
+        let reply = harness.run("Weather in Koper?").await.unwrap();
+
+        assert_eq!(reply.content.as_deref(), Some("It is sunny."));
+        harness.assert_history_contains(Role::Tool, "Sunny");
+        harness.assert_notified(None, |content| {
+            matches!(content, NotificationContent::ToolCallSuccessResult(output) if output == "Sunny")
+        });
+        assert_eq!(harness.tool_calls()[0].name, "get_weather");
+        assert_eq!(harness.requests().len(), 2);
+        harness.verify();
+    }
+
+    #[tokio::test]
+    async fn tool_errors_carry_details() {
+        let builder = AgentBuilder::default()
+            .set_model("test")
+            .set_tool_retry_policy(ToolRetryPolicy::new().retries(1).backoff(Duration::ZERO));
+        let mut harness = FlowTestHarness::new(builder)
+            .await
+            .unwrap()
+            .reply_with_tool_call("search", json!({}))
+            .fail_tool_call("search", "timed out")
+            .fail_tool_call("search", "timed out")
+            .reply("Search is down.");
+
+        harness.run("Search").await.unwrap();
+
+        let notification = harness
+            .notifications()
+            .iter()
+            .find(|n| matches!(n.content, NotificationContent::ToolCallErrorResult(_)))
+            .unwrap();
+        let error = notification.error.as_ref().unwrap();
+        assert_eq!(error.kind, ErrorKind::ToolExecution);
+        assert_eq!(error.attempts, 2);
+        assert_eq!(error.tool.as_deref(), Some("search"));
+        assert!(error.is_transient());
+        assert_eq!(notification.path, [harness.agent().name.clone()]);
+        harness.verify();
+    }
+
+    #[tokio::test]
+    async fn unexpected_tool_calls_fail() {
+        let mut harness = FlowTestHarness::new(AgentBuilder::default().set_model("test"))
+            .await
+            .unwrap()
+            .expect_tool_call("search", "results")
+            .reply_with_tool_call("search", json!({}))
+            .reply_with_tool_call("search", json!({}))
+            .reply("done");
+
+        harness.run("Search twice").await.unwrap();
+
+        let mismatches = harness.tools.lock().unwrap().mismatches.clone();
+        assert_eq!(mismatches.len(), 1);
+        assert_eq!(harness.script.remaining(), 0);
+    }
+
+    #[tokio::test]
+    async fn plan_and_execute_runs_against_the_script() {
+        let mut harness = FlowTestHarness::new(
+            StatefullPrebuild::plan_and_execute_with_parallelism(1).set_model("test"),
+        )
+        .await
+        .unwrap()
+        .expect_tool_call("search", "Ljubljana")
+        // blueprint, planner, executor (tool call and answer), replanner, report
+        .reply("Look up the capital.")
+        .reply(r#"{"steps":["Search for the capital of Slovenia."]}"#)
+        .reply_with_tool_call("search", json!({ "query": "capital of Slovenia" }))
+        .reply("The capital is Ljubljana.")
+        .reply(r#"{"steps":[]}"#)
+        .reply("# Answer\nLjubljana");
+
+        let reply = harness
+            .run("What is the capital of Slovenia?")
+            .await
+            .unwrap();
+
+        assert_eq!(reply.content.as_deref(), Some("# Answer\nLjubljana"));
+        harness.assert_history_contains(Role::Assistant, "The capital is Ljubljana.");
+        harness.assert_notified(Some("Statefull_prebuild-plan_and_execute"), |content| {
+            matches!(content, NotificationContent::Done(true, _))
+        });
+        harness.verify();
+    }
+
+    #[tokio::test]
+    async fn invocation_summaries_include_the_work_of_sub_agents() {
+        let mut harness = FlowTestHarness::new(
+            StatefullPrebuild::plan_and_execute_with_parallelism(1).set_model("test"),
+        )
+        .await
+        .unwrap()
+        .expect_tool_call("search", "Ljubljana")
+        .reply("Look up the capital.")
+        .reply(r#"{"steps":["Search for the capital of Slovenia."]}"#)
+        .reply_with_tool_call("search", json!({ "query": "capital of Slovenia" }))
+        .reply("The capital is Ljubljana.")
+        .reply(r#"{"steps":[]}"#)
+        .reply("Ljubljana");
+
+        harness.run("What is the capital?").await.unwrap();
+
+        // one summary, for the top-level invocation, counting the requests
+        // and tool calls of the sub-agents
+        let summaries: Vec<&InvocationSummary> = harness
+            .notifications()
+            .iter()
+            .filter_map(|notification| match &notification.content {
+                NotificationContent::InvocationFinished(summary) => Some(summary),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(summaries.len(), 1);
+        assert!(summaries[0].success);
+        assert_eq!(summaries[0].usage.requests, 6);
+        assert_eq!(summaries[0].tool_calls, 1);
+        assert_eq!(
+            summaries[0]
+                .message
+                .as_ref()
+                .and_then(|m| m.content.as_deref()),
+            Some("Ljubljana")
+        );
+    }
+}
@@ -0,0 +1,150 @@
+use std::{
+    future::Future,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+};
+
+use serde::{Deserialize, Serialize};
+
+/// Contents of an [`Artifact`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ArtifactData {
+    Bytes(Vec<u8>),
+    /// A file written by the tool.
+    Path(PathBuf),
+}
+
+/// A file produced by a tool (report, image, CSV, ...), emitted next to the
+/// text result with [`emit_artifact`].
+///
+/// Artifacts are not sent to the model. They are collected on the agent
+/// (see [`Agent::take_artifacts`](crate::Agent::take_artifacts)) and sent as
+/// [`NotificationContent::Artifact`](crate::NotificationContent::Artifact).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Artifact {
+    pub name: String,
+    pub mime: String,
+    pub data: ArtifactData,
+    /// Tool that emitted the artifact, filled in by the agent.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool: Option<String>,
+    /// Id of the tool call that emitted the artifact, filled in by the agent.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub call_id: Option<String>,
+}
+
+impl Artifact {
+    pub fn from_bytes(
+        name: impl Into<String>,
+        mime: impl Into<String>,
+        bytes: impl Into<Vec<u8>>,
+    ) -> Self {
+        Self::new(name, mime, ArtifactData::Bytes(bytes.into()))
+    }
+
+    pub fn from_path(
+        name: impl Into<String>,
+        mime: impl Into<String>,
+        path: impl Into<PathBuf>,
+    ) -> Self {
+        Self::new(name, mime, ArtifactData::Path(path.into()))
+    }
+
+    fn new(name: impl Into<String>, mime: impl Into<String>, data: ArtifactData) -> Self {
+        Self {
+            name: name.into(),
+            mime: mime.into(),
+            data,
+            tool: None,
+            call_id: None,
+        }
+    }
+
+    /// Read the contents, from disk for [`ArtifactData::Path`].
+    pub fn bytes(&self) -> std::io::Result<Vec<u8>> {
+        match &self.data {
+            ArtifactData::Bytes(bytes) => Ok(bytes.clone()),
+            ArtifactData::Path(path) => std::fs::read(path),
+        }
+    }
+}
+
+type ArtifactSink = Arc<Mutex<Vec<Artifact>>>;
+
+tokio::task_local! {
+    static ARTIFACTS: ArtifactSink;
+}
+
+/// Emit an artifact from inside a tool executor.
+///
+/// Returns `false` when called outside of a tool call made by an agent, or
+/// from a task the executor spawned, in which case the artifact is dropped.
+pub fn emit_artifact(artifact: Artifact) -> bool {
+    ARTIFACTS
+        .try_with(|sink| {
+            sink.lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .push(artifact)
+        })
+        .is_ok()
+}
+
+/// Run `future` and return the artifacts emitted while it ran.
+pub(crate) async fn collect_artifacts<F: Future>(future: F) -> (F::Output, Vec<Artifact>) {
+    let sink = ArtifactSink::default();
+    let output = ARTIFACTS.scope(sink.clone(), future).await;
+    let artifacts = std::mem::take(&mut *sink.lock().unwrap_or_else(|e| e.into_inner()));
+    (output, artifacts)
+}
+
+/// Artifacts collected by an agent, shared between its clones.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct ArtifactStore {
+    artifacts: ArtifactSink,
+}
+
+impl ArtifactStore {
+    pub(crate) fn extend(&self, artifacts: Vec<Artifact>) {
+        self.artifacts
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .extend(artifacts);
+    }
+
+    pub(crate) fn entries(&self) -> Vec<Artifact> {
+        self.artifacts
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    pub(crate) fn take(&self) -> Vec<Artifact> {
+        std::mem::take(&mut *self.artifacts.lock().unwrap_or_else(|e| e.into_inner()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn artifacts_are_collected_per_call() {
+        assert!(!emit_artifact(Artifact::from_bytes(
+            "a.txt",
+            "text/plain",
+            "a"
+        )));
+
+        let (output, artifacts) = collect_artifacts(async {
+            emit_artifact(Artifact::from_bytes("report.csv", "text/csv", "x,y\n1,2"));
+            "done"
+        })
+        .await;
+
+        assert_eq!(output, "done");
+        assert_eq!(artifacts.len(), 1);
+        assert_eq!(artifacts[0].name, "report.csv");
+        assert_eq!(artifacts[0].bytes().unwrap(), b"x,y\n1,2");
+    }
+}
@@ -0,0 +1,345 @@
+use std::{
+    collections::HashMap,
+    fmt,
+    future::Future,
+    sync::{Arc, Mutex},
+};
+
+use serde_json::Value;
+use tokio::sync::mpsc::Sender;
+
+use crate::{
+    agent::hash_arguments, AbortReason, Agent, CancelScope, Notification, NotificationHandler,
+    TenantContext, ToolCall,
+};
+
+tokio::task_local! {
+    static TOOL_CONTEXT: ToolContext;
+}
+
+/// What a tool executor knows about the call it is executing: the agent and
+/// invocation it runs in, a way to send notifications, the cancellation of
+/// the flow and a handle on the agent's state.
+///
+/// Executors built with
+/// [`ToolBuilder::executor_with_context`](crate::ToolBuilder::executor_with_context)
+/// get it as an argument; plain executors can read it with
+/// [`ToolContext::current`]. Notifications are sent in the agent's name with
+/// the [`NotificationHandler`] methods, e.g. `ctx.notify_custom(..)`.
+#[derive(Clone)]
+pub struct ToolContext {
+    /// Name of the agent that called the tool.
+    pub agent_name: String,
+    /// Key of the running invocation, see
+    /// [`Agent::idempotency_key`](crate::Agent::idempotency_key).
+    pub invocation_id: Option<String>,
+    /// Id of the tool call, as given by the model.
+    pub call_id: Option<String>,
+    /// Key of this call, see [`idempotency_key`].
+    pub idempotency_key: Option<String>,
+    /// Scope the flow runs in, see
+    /// [`Agent::invoke_flow_in`](crate::Agent::invoke_flow_in).
+    pub cancellation: CancelScope,
+    /// The agent's state, see [`ToolState`].
+    pub state: ToolState,
+    /// Tenant the flow runs for, to pass on to sub-agents, see
+    /// [`Agent::invoke_flow_for_tenant`](crate::Agent::invoke_flow_for_tenant).
+    pub tenant: Option<TenantContext>,
+    notification_channel: Option<Sender<Notification>>,
+}
+
+impl ToolContext {
+    /// Context of a call made by `agent`.
+    pub(crate) fn for_call(agent: &Agent, call: &ToolCall) -> Self {
+        let invocation_id = agent.idempotency_key().map(str::to_string);
+        Self {
+            agent_name: agent.name.clone(),
+            idempotency_key: invocation_id
+                .as_deref()
+                .map(|key| tool_call_idempotency_key(key, call, &agent.tool_call_ordinals)),
+            invocation_id,
+            call_id: call.id.clone(),
+            cancellation: agent
+                .cancel_scope
+                .clone()
+                .unwrap_or_else(|| CancelScope::new(agent.name.clone())),
+            state: ToolState {
+                values: Arc::new(Mutex::new(agent.state.clone())),
+                updates: agent.tool_state_updates.clone(),
+            },
+            tenant: agent.tenant.clone(),
+            notification_channel: agent.notification_channel.clone(),
+        }
+    }
+
+    /// Context of the tool call being executed, `None` when called outside
+    /// of a tool call made by an agent, or from a task the executor spawned.
+    pub fn current() -> Option<Self> {
+        TOOL_CONTEXT.try_with(Clone::clone).ok()
+    }
+
+    /// Completes when the flow that called the tool is cancelled or times
+    /// out, for executors that hand work to other tasks.
+    pub async fn cancelled(&self) -> AbortReason {
+        self.cancellation.aborted().await
+    }
+}
+
+impl Default for ToolContext {
+    /// Context of a call made without an agent, e.g. with [`Tool::execute`](crate::Tool::execute).
+    fn default() -> Self {
+        Self {
+            agent_name: String::new(),
+            invocation_id: None,
+            call_id: None,
+            idempotency_key: None,
+            cancellation: CancelScope::new("tool"),
+            state: ToolState::default(),
+            tenant: None,
+            notification_channel: None,
+        }
+    }
+}
+
+impl fmt::Debug for ToolContext {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ToolContext")
+            .field("agent_name", &self.agent_name)
+            .field("invocation_id", &self.invocation_id)
+            .field("call_id", &self.call_id)
+            .field("idempotency_key", &self.idempotency_key)
+            .field("cancellation", &self.cancellation)
+            .field("state", &self.state)
+            .field("tenant", &self.tenant)
+            .finish()
+    }
+}
+
+impl NotificationHandler for ToolContext {
+    fn get_outgoing_channel(&self) -> &Option<Sender<Notification>> {
+        &self.notification_channel
+    }
+
+    fn get_channel_name(&self) -> &String {
+        &self.agent_name
+    }
+}
+
+/// Handle on the state of the agent that called a tool.
+///
+/// It starts as a copy of [`Agent::state`](crate::Agent::state). Values set
+/// by the tool are visible to it right away and are written to the agent's
+/// state when its flow finishes (the default flow does so after every round
+/// of tool calls).
+#[derive(Clone, Default)]
+pub struct ToolState {
+    values: Arc<Mutex<HashMap<String, Value>>>,
+    updates: ToolStateUpdates,
+}
+
+impl ToolState {
+    pub fn get(&self, key: &str) -> Option<Value> {
+        self.values
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(key)
+            .cloned()
+    }
+
+    pub fn set(&self, key: impl Into<String>, value: Value) {
+        let key = key.into();
+        self.values
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(key.clone(), value.clone());
+        self.updates
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(key, value);
+    }
+}
+
+impl fmt::Debug for ToolState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map()
+            .entries(self.values.lock().unwrap_or_else(|e| e.into_inner()).iter())
+            .finish()
+    }
+}
+
+/// State set by tools that is not written to the agent yet, shared between
+/// the agent's clones.
+pub(crate) type ToolStateUpdates = Arc<Mutex<HashMap<String, Value>>>;
+
+/// Identical tool calls made so far in the running invocation, shared with
+/// its sub-agents and clones like the quotas of the invocation.
+pub(crate) type CallOrdinals = Arc<Mutex<HashMap<String, usize>>>;
+
+/// Idempotency key of the tool call being executed, for use inside a tool
+/// executor.
+///
+/// Tools that call external APIs can send it along (e.g. as an
+/// `Idempotency-Key` header) so the API drops calls it has already seen.
+/// The key is derived from the invocation's key (see
+/// [`Agent::idempotency_key`](crate::Agent::idempotency_key)), the tool name,
+/// the arguments and how many identical calls the invocation made before, so
+/// a call that is deliberately repeated gets a key of its own. It stays the
+/// same when a failed call is retried, and when an invocation is repeated
+/// with the same key after a crash (see
+/// [`Agent::invoke_flow_idempotent`](crate::Agent::invoke_flow_idempotent)).
+/// Sub-agents derive their keys from the key of the invocation they run in
+/// and their name.
+///
+/// Returns `None` when called outside of a tool call made by an agent, or
+/// from a task the executor spawned.
+pub fn idempotency_key() -> Option<String> {
+    TOOL_CONTEXT
+        .try_with(|context| context.idempotency_key.clone())
+        .ok()
+        .flatten()
+}
+
+/// Run `future` with `context` as its [`ToolContext::current`].
+pub(crate) async fn with_tool_context<F: Future>(context: ToolContext, future: F) -> F::Output {
+    TOOL_CONTEXT.scope(context, future).await
+}
+
+/// Key of `call` made during the invocation with key `invocation_key`,
+/// counting it in `ordinals`.
+pub(crate) fn tool_call_idempotency_key(
+    invocation_key: &str,
+    call: &ToolCall,
+    ordinals: &CallOrdinals,
+) -> String {
+    let key = format!(
+        "{invocation_key}:{}:{}",
+        call.function.name,
+        hash_arguments(&call.function.arguments)
+    );
+    let mut ordinals = ordinals.lock().unwrap_or_else(|e| e.into_inner());
+    let ordinal = ordinals.entry(key.clone()).or_default();
+    let key = format!("{key}:{ordinal}");
+    *ordinal += 1;
+    key
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::{AgentBuilder, FlowFuture, Message, ToolBuilder, ToolCallFunction, ToolType};
+
+    fn call(arguments: serde_json::Value) -> ToolCall {
+        ToolCall {
+            id: None,
+            tool_type: ToolType::Function,
+            function: ToolCallFunction {
+                name: "send_email".into(),
+                arguments,
+            },
+        }
+    }
+
+    #[tokio::test]
+    async fn keys_are_scoped_to_the_call() {
+        assert_eq!(idempotency_key(), None);
+
+        let key = |run, to| {
+            tool_call_idempotency_key(run, &call(json!({ "to": to })), &CallOrdinals::default())
+        };
+        assert_eq!(key("run-1", "a"), key("run-1", "a"));
+        assert_ne!(key("run-1", "a"), key("run-1", "b"));
+        assert_ne!(key("run-1", "a"), key("run-2", "a"));
+
+        // a repeated call within the invocation gets a key of its own
+        let ordinals = CallOrdinals::default();
+        let first = tool_call_idempotency_key("run-1", &call(json!({ "to": "a" })), &ordinals);
+        let second = tool_call_idempotency_key("run-1", &call(json!({ "to": "a" })), &ordinals);
+        assert_eq!(first, key("run-1", "a"));
+        assert_ne!(first, second);
+        let key = second;
+
+        let context = ToolContext {
+            idempotency_key: Some(key.clone()),
+            ..ToolContext::default()
+        };
+        let seen = with_tool_context(context, async { idempotency_key() }).await;
+        assert_eq!(seen, Some(key));
+    }
+
+    #[tokio::test]
+    async fn sub_agents_derive_their_keys_from_the_invocation() {
+        let mut agent = AgentBuilder::default()
+            .set_model("test")
+            .set_flow(|agent: &mut Agent, prompt: String| -> FlowFuture<'_> {
+                Box::pin(async move {
+                    if prompt == "key" {
+                        let key = agent.idempotency_key().unwrap_or_default();
+                        return Ok(Message::assistant(key));
+                    }
+                    let mut sub_agent = agent.clone();
+                    sub_agent.name = "executor".into();
+                    sub_agent.running_invocation = Default::default();
+                    agent.configure_sub_agent(&mut sub_agent);
+                    sub_agent.invoke_flow("key").await
+                })
+            })
+            .build()
+            .await
+            .unwrap();
+
+        let key = agent
+            .invoke_flow_idempotent("run-1", "outer")
+            .await
+            .unwrap();
+        assert_eq!(key.content.as_deref(), Some("run-1:executor"));
+        let key = agent
+            .invoke_flow_idempotent("run-1", "outer")
+            .await
+            .unwrap();
+        assert_eq!(key.content.as_deref(), Some("run-1:executor"));
+    }
+
+    #[tokio::test]
+    async fn context_executors_see_the_calling_agent() {
+        let tool = ToolBuilder::new()
+            .function_name("remember")
+            .function_description("Remembers the last city")
+            .add_required_property("city", "string", "City name")
+            .executor_with_context(|args, ctx| async move {
+                ctx.state.set("city", args["city"].clone());
+                Ok(format!("{} remembered", ctx.agent_name))
+            })
+            .build()
+            .unwrap();
+
+        let mut agent = crate::AgentBuilder::default()
+            .set_model("test")
+            .set_name("scout")
+            .add_tool(tool)
+            .build()
+            .await
+            .unwrap();
+        let calls = vec![ToolCall {
+            function: ToolCallFunction {
+                name: "remember".into(),
+                arguments: json!({ "city": "Koper" }),
+            },
+            ..call(json!({}))
+        }];
+
+        let results = crate::call_tools(&agent, &calls).await;
+        assert_eq!(results[0].content.as_deref(), Some("scout remembered"));
+        assert!(!agent.state.contains_key("city"));
+        agent.apply_tool_state();
+        assert_eq!(agent.state["city"], "Koper");
+
+        // without an agent, the executor gets an empty context
+        let output = agent.tools.as_ref().unwrap()[0]
+            .execute(json!({ "city": "Piran" }))
+            .await
+            .unwrap();
+        assert_eq!(output, " remembered");
+    }
+}
@@ -0,0 +1,180 @@
+use serde_json::Value;
+
+use crate::{agent::extract_json_payload, Message, ToolCall};
+
+/// Parse JSON written by a model, repairing the usual mistakes of small
+/// models: trailing commas, single-quoted strings, unquoted keys, Python
+/// literals (`True`, `None`), prose or code fences around the value and
+/// output cut off before the closing brackets.
+///
+/// Returns `None` if the text cannot be repaired.
+///
+/// ```
+/// use reagent_rs::parse_lenient_json;
+/// use serde_json::json;
+///
+/// assert_eq!(
+///     parse_lenient_json("{location: 'Ljubljana', days: [1, 2,],"),
+///     Some(json!({ "location": "Ljubljana", "days": [1, 2] }))
+/// );
+/// ```
+pub fn parse_lenient_json(input: &str) -> Option<Value> {
+    if let Ok(value) = serde_json::from_str(input) {
+        return Some(value);
+    }
+    let payload = extract_json_payload(input).unwrap_or(input);
+    if let Ok(value) = serde_json::from_str(payload) {
+        return Some(value);
+    }
+    let start = input.find(['{', '[']).unwrap_or(0);
+    serde_json::from_str(&repair_json(&input[start..])).ok()
+}
+
+/// Repair the arguments of tool calls in `message` that arrived as a string
+/// instead of a JSON object. Returns the repaired calls with their original
+/// arguments.
+pub(crate) fn repair_tool_call_arguments(message: &mut Message) -> Vec<(ToolCall, String)> {
+    let mut repaired = Vec::new();
+    for call in message.tool_calls.iter_mut().flatten() {
+        let Value::String(raw) = &call.function.arguments else {
+            continue;
+        };
+        let Some(arguments) = parse_lenient_json(raw).filter(Value::is_object) else {
+            continue;
+        };
+        let original = raw.clone();
+        call.function.arguments = arguments;
+        repaired.push((call.clone(), original));
+    }
+    repaired
+}
+
+/// Rewrite `input` into JSON as far as possible, see [`parse_lenient_json`].
+fn repair_json(input: &str) -> String {
+    let chars: Vec<char> = input.chars().collect();
+    let mut out = String::with_capacity(input.len() + 8);
+    let mut closers = Vec::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        i += 1;
+        match c {
+            '"' | '\'' => i = copy_string(&chars, i, c, &mut out),
+            '{' => {
+                closers.push('}');
+                out.push(c);
+            }
+            '[' => {
+                closers.push(']');
+                out.push(c);
+            }
+            '}' | ']' => {
+                trim_trailing_comma(&mut out);
+                if closers.last() == Some(&c) {
+                    closers.pop();
+                }
+                out.push(c);
+            }
+            c if c.is_alphabetic() || c == '_' || c == '$' => {
+                let start = i - 1;
+                while i < chars.len() && (chars[i].is_alphanumeric() || "_$-".contains(chars[i])) {
+                    i += 1;
+                }
+                let word: String = chars[start..i].iter().collect();
+                let is_key = chars[i..]
+                    .iter()
+                    .find(|c| !c.is_whitespace())
+                    .is_some_and(|c| *c == ':');
+                match word.as_str() {
+                    "true" | "True" if !is_key => out.push_str("true"),
+                    "false" | "False" if !is_key => out.push_str("false"),
+                    "null" | "None" if !is_key => out.push_str("null"),
+                    _ => {
+                        out.push('"');
+                        out.push_str(&word);
+                        out.push('"');
+                    }
+                }
+            }
+            c => out.push(c),
+        }
+    }
+
+    // close what was cut off
+    trim_trailing_comma(&mut out);
+    if out.trim_end().ends_with(':') {
+        out.push_str("null");
+    }
+    while let Some(closer) = closers.pop() {
+        trim_trailing_comma(&mut out);
+        out.push(closer);
+    }
+    out
+}
+
+/// Copy the string starting after the `quote` at `i - 1` as a double-quoted
+/// JSON string, returns the index after its closing quote.
+fn copy_string(chars: &[char], mut i: usize, quote: char, out: &mut String) -> usize {
+    out.push('"');
+    while i < chars.len() {
+        let c = chars[i];
+        i += 1;
+        match c {
+            '\\' if i < chars.len() => {
+                let escaped = chars[i];
+                i += 1;
+                if escaped == '\'' {
+                    out.push('\'');
+                } else {
+                    out.push('\\');
+                    out.push(escaped);
+                }
+            }
+            c if c == quote => {
+                out.push('"');
+                return i;
+            }
+            '"' => out.push_str("\\\""),
+            '\n' => out.push_str("\\n"),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    i
+}
+
+fn trim_trailing_comma(out: &mut String) {
+    let trimmed = out.trim_end();
+    if trimmed.ends_with(',') {
+        out.truncate(trimmed.len() - 1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn common_mistakes_are_repaired() {
+        assert_eq!(
+            parse_lenient_json(r#"{"query": "rust", "limit": 3,}"#),
+            Some(json!({ "query": "rust", "limit": 3 }))
+        );
+        assert_eq!(
+            parse_lenient_json("{'text': 'it\\'s \"quoted\"', flag: True, next: None}"),
+            Some(json!({ "text": "it's \"quoted\"", "flag": true, "next": null }))
+        );
+        assert_eq!(
+            parse_lenient_json("Calling the tool:\n```json\n{\"city\": \"Ljubljana\"}\n```"),
+            Some(json!({ "city": "Ljubljana" }))
+        );
+        assert_eq!(
+            parse_lenient_json(r#"{"items": [{"name": "a"}, {"name": "b"#),
+            Some(json!({ "items": [{ "name": "a" }, { "name": "b" }] }))
+        );
+        assert_eq!(parse_lenient_json("{\"a\": }}"), None);
+    }
+}
@@ -1,8 +1,35 @@
+mod artifact;
+mod context;
 mod errors;
+mod json_repair;
+mod output;
 pub mod prebuilt;
+mod quota;
+mod reliability;
+mod retry;
+mod simulator;
+pub mod stdlib;
 mod tool;
 mod tool_builder;
+mod tool_choice;
+mod truncation;
 
+pub(crate) use artifact::{collect_artifacts, ArtifactStore};
+pub use artifact::{emit_artifact, Artifact, ArtifactData};
+pub use context::{idempotency_key, ToolContext, ToolState};
+pub(crate) use context::{with_tool_context, CallOrdinals, ToolStateUpdates};
 pub use errors::ToolExecutionError;
+pub use json_repair::parse_lenient_json;
+pub(crate) use json_repair::repair_tool_call_arguments;
+pub use output::{canonical_json, ToolOutput, TOOL_JSON_METADATA, TOOL_MIME_METADATA};
+pub(crate) use quota::{FlowToolCalls, QuotaExceeded};
+pub use quota::{ToolLimits, QUOTA_EXCEEDED_METADATA};
+pub use reliability::ToolReliabilityPolicy;
+pub use retry::ToolRetryPolicy;
+pub use simulator::{SimulateFn, ToolFixture, ToolSimulator, DRY_RUN_METADATA};
 pub use tool::*;
 pub use tool_builder::*;
+pub use tool_choice::ToolChoice;
+pub use truncation::{
+    truncate_text, ToolOutputLimit, ToolOutputLimits, ToolOutputTruncation, TruncationStrategy,
+};
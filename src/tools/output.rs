@@ -0,0 +1,205 @@
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+use crate::{services::llm::message::TOOL_NAME_METADATA, Agent, Message, Role};
+
+/// Metadata key set on tool result messages of a JSON [`ToolOutput`],
+/// holding the value.
+pub const TOOL_JSON_METADATA: &str = "tool_json";
+/// Metadata key set on tool result messages of a [`ToolOutput`] that is not
+/// plain text, holding its mime type.
+pub const TOOL_MIME_METADATA: &str = "tool_mime";
+
+const TEXT_MIME: &str = "text/plain";
+const JSON_MIME: &str = "application/json";
+
+/// Result of a tool executor set with
+/// [`ToolBuilder::executor_output`](crate::ToolBuilder::executor_output).
+///
+/// `text` is what the model sees. JSON outputs are sent in a compact
+/// encoding with sorted keys, and the value itself is kept in the metadata
+/// of the tool result message, so later steps read it with
+/// [`Message::tool_json`] instead of parsing the text.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ToolOutput {
+    pub text: String,
+    pub json: Option<Value>,
+    pub mime: String,
+}
+
+impl ToolOutput {
+    /// Plain text output.
+    pub fn text(text: impl Into<String>) -> Self {
+        Self {
+            text: text.into(),
+            json: None,
+            mime: TEXT_MIME.into(),
+        }
+    }
+
+    /// JSON output, sent to the model in its canonical encoding.
+    pub fn json(value: Value) -> Self {
+        Self {
+            text: canonical_json(&value),
+            json: Some(value),
+            mime: JSON_MIME.into(),
+        }
+    }
+
+    /// JSON output of anything serializable.
+    pub fn serialize<T: Serialize>(value: &T) -> Result<Self, serde_json::Error> {
+        Ok(Self::json(serde_json::to_value(value)?))
+    }
+
+    /// Set the mime type of the text, e.g. `text/markdown` or `text/csv`.
+    pub fn with_mime(mut self, mime: impl Into<String>) -> Self {
+        self.mime = mime.into();
+        self
+    }
+
+    /// Attach the metadata of the output to its tool result message.
+    pub(crate) fn annotate(&self, mut message: Message) -> Message {
+        if let Some(json) = &self.json {
+            message = message.with_metadata(TOOL_JSON_METADATA, json.clone());
+        }
+        if self.mime != TEXT_MIME {
+            message = message.with_metadata(TOOL_MIME_METADATA, self.mime.clone());
+        }
+        message
+    }
+}
+
+impl From<String> for ToolOutput {
+    fn from(text: String) -> Self {
+        Self::text(text)
+    }
+}
+
+impl From<&str> for ToolOutput {
+    fn from(text: &str) -> Self {
+        Self::text(text)
+    }
+}
+
+impl From<Value> for ToolOutput {
+    fn from(value: Value) -> Self {
+        Self::json(value)
+    }
+}
+
+/// Compact JSON with the keys of every object sorted, so equal values are
+/// always sent the same way.
+pub fn canonical_json(value: &Value) -> String {
+    fn sorted(value: &Value) -> Value {
+        match value {
+            Value::Object(map) => {
+                let mut entries: Vec<_> = map.iter().collect();
+                entries.sort_by(|a, b| a.0.cmp(b.0));
+                Value::Object(
+                    entries
+                        .into_iter()
+                        .map(|(key, value)| (key.clone(), sorted(value)))
+                        .collect::<Map<_, _>>(),
+                )
+            }
+            Value::Array(items) => Value::Array(items.iter().map(sorted).collect()),
+            other => other.clone(),
+        }
+    }
+    sorted(value).to_string()
+}
+
+impl Message {
+    /// JSON value of a tool result, for tools returning
+    /// [`ToolOutput::json`].
+    pub fn tool_json(&self) -> Option<&Value> {
+        self.get_metadata(TOOL_JSON_METADATA)
+    }
+}
+
+impl Agent {
+    /// JSON value of the latest result of the tool `name` (see
+    /// [`ToolOutput::json`]), for flows using it in later steps.
+    pub fn last_tool_json(&self, name: &str) -> Option<&Value> {
+        self.history
+            .iter()
+            .rev()
+            .filter(|m| m.role == Role::Tool)
+            .filter(|m| m.get_metadata(TOOL_NAME_METADATA).and_then(Value::as_str) == Some(name))
+            .find_map(Message::tool_json)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::{call_tools, AgentBuilder, ToolBuilder, ToolCall, ToolCallFunction, ToolType};
+
+    #[test]
+    fn json_is_encoded_canonically() {
+        let output = ToolOutput::json(json!({ "b": [1, { "z": true, "a": null }], "a": "x" }));
+        assert_eq!(output.text, r#"{"a":"x","b":[1,{"a":null,"z":true}]}"#);
+        assert_eq!(output.mime, "application/json");
+    }
+
+    #[tokio::test]
+    async fn json_outputs_are_kept_on_the_tool_result() {
+        let tool = ToolBuilder::new()
+            .function_name("get_weather")
+            .function_description("Returns a weather forecast")
+            .add_required_property("location", "string", "City name")
+            .executor_output(|args| async move {
+                Ok(ToolOutput::json(
+                    json!({ "location": args["location"], "celsius": 21 }),
+                ))
+            })
+            .build()
+            .unwrap();
+        let mut agent = AgentBuilder::default()
+            .set_model("test")
+            .add_tool(tool)
+            .build()
+            .await
+            .unwrap();
+        let call = ToolCall {
+            id: Some("call_1".into()),
+            tool_type: ToolType::Function,
+            function: ToolCallFunction {
+                name: "get_weather".into(),
+                arguments: json!({ "location": "Koper" }),
+            },
+        };
+
+        let results = call_tools(&agent, &[call]).await;
+        assert_eq!(
+            results[0].content.as_deref(),
+            Some(r#"{"celsius":21,"location":"Koper"}"#)
+        );
+        assert_eq!(
+            results[0].get_metadata(TOOL_MIME_METADATA),
+            Some(&json!("application/json"))
+        );
+
+        agent.history.extend(results);
+        assert_eq!(
+            agent.last_tool_json("get_weather"),
+            Some(&json!({ "location": "Koper", "celsius": 21 }))
+        );
+        assert_eq!(agent.last_tool_json("other"), None);
+    }
+
+    #[tokio::test]
+    async fn string_executors_return_plain_text() {
+        let tool = ToolBuilder::new()
+            .function_name("echo")
+            .function_description("Returns its input")
+            .executor_fn(|_| async { Ok("{}".to_string()) })
+            .build()
+            .unwrap();
+
+        let output = tool.execute_output(json!({})).await.unwrap();
+        assert_eq!(output, ToolOutput::text("{}"));
+    }
+}
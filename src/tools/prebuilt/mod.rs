@@ -1 +1,2 @@
 pub mod bash;
+pub mod transcribe;
@@ -0,0 +1,262 @@
+use std::{future::Future, path::PathBuf, pin::Pin, sync::Arc};
+
+use reqwest::{
+    multipart::{Form, Part},
+    Client, RequestBuilder,
+};
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::{
+    services::runtime, AsyncToolFn, SecretString, Tool, ToolBuilder, ToolBuilderError,
+    ToolExecutionError,
+};
+
+/// Errors raised while transcribing an audio file.
+#[derive(Debug)]
+pub enum TranscriptionError {
+    /// The audio file could not be read.
+    Io(std::io::Error),
+    /// The request to the transcription backend failed.
+    Request(reqwest::Error),
+    /// The backend answered with an error status.
+    Api(String),
+}
+
+impl std::fmt::Display for TranscriptionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TranscriptionError::Io(e) => write!(f, "Could not read audio file: {e}"),
+            TranscriptionError::Request(e) => write!(f, "Transcription request failed: {e}"),
+            TranscriptionError::Api(s) => write!(f, "Transcription backend error: {s}"),
+        }
+    }
+}
+
+impl std::error::Error for TranscriptionError {}
+
+impl From<std::io::Error> for TranscriptionError {
+    fn from(e: std::io::Error) -> Self {
+        TranscriptionError::Io(e)
+    }
+}
+
+impl From<reqwest::Error> for TranscriptionError {
+    fn from(e: reqwest::Error) -> Self {
+        TranscriptionError::Request(e)
+    }
+}
+
+pub type TranscriptionFuture<'a> =
+    Pin<Box<dyn Future<Output = Result<String, TranscriptionError>> + Send + 'a>>;
+
+/// Speech-to-text backend turning an audio file into a transcript.
+///
+/// Implemented by [`WhisperServerTranscriber`] and [`OpenAiTranscriber`];
+/// implement it yourself to plug in another backend, then expose it to an
+/// agent with [`build_transcription_tool`].
+pub trait Transcriber: Send + Sync {
+    fn transcribe(&self, audio: PathBuf) -> TranscriptionFuture<'_>;
+}
+
+/// Transcribes with a whisper.cpp `whisper-server` (`POST /inference`).
+#[derive(Debug, Clone)]
+pub struct WhisperServerTranscriber {
+    client: Client,
+    base_url: String,
+    language: Option<String>,
+}
+
+impl WhisperServerTranscriber {
+    /// `base_url` of the server, e.g. `http://localhost:8080`.
+    pub fn new(base_url: impl Into<String>) -> Self {
+        Self {
+            client: Client::new(),
+            base_url: base_url.into(),
+            language: None,
+        }
+    }
+
+    /// Spoken language (e.g. `"en"`), detected by the server if not set.
+    pub fn language(mut self, language: impl Into<String>) -> Self {
+        self.language = Some(language.into());
+        self
+    }
+}
+
+impl Transcriber for WhisperServerTranscriber {
+    fn transcribe(&self, audio: PathBuf) -> TranscriptionFuture<'_> {
+        Box::pin(async move {
+            let mut form = Form::new()
+                .part("file", audio_part(audio).await?)
+                .text("response_format", "json");
+            if let Some(language) = &self.language {
+                form = form.text("language", language.clone());
+            }
+            let url = format!("{}/inference", self.base_url.trim_end_matches('/'));
+            send(self.client.post(url).multipart(form)).await
+        })
+    }
+}
+
+/// Transcribes with the OpenAI audio API (`POST /audio/transcriptions`),
+/// or any server compatible with it.
+#[derive(Debug, Clone)]
+pub struct OpenAiTranscriber {
+    client: Client,
+    base_url: String,
+    api_key: SecretString,
+    model: String,
+    language: Option<String>,
+}
+
+impl OpenAiTranscriber {
+    pub fn new(api_key: impl Into<SecretString>) -> Self {
+        Self {
+            client: Client::new(),
+            base_url: "https://api.openai.com/v1".into(),
+            api_key: api_key.into(),
+            model: "whisper-1".into(),
+            language: None,
+        }
+    }
+
+    /// Base URL of an OpenAI compatible server, defaults to OpenAI.
+    pub fn base_url(mut self, base_url: impl Into<String>) -> Self {
+        self.base_url = base_url.into();
+        self
+    }
+
+    /// Transcription model, defaults to `whisper-1`.
+    pub fn model(mut self, model: impl Into<String>) -> Self {
+        self.model = model.into();
+        self
+    }
+
+    /// Spoken language (e.g. `"en"`), detected by the backend if not set.
+    pub fn language(mut self, language: impl Into<String>) -> Self {
+        self.language = Some(language.into());
+        self
+    }
+}
+
+impl Transcriber for OpenAiTranscriber {
+    fn transcribe(&self, audio: PathBuf) -> TranscriptionFuture<'_> {
+        Box::pin(async move {
+            let mut form = Form::new()
+                .part("file", audio_part(audio).await?)
+                .text("model", self.model.clone())
+                .text("response_format", "json");
+            if let Some(language) = &self.language {
+                form = form.text("language", language.clone());
+            }
+            let url = format!(
+                "{}/audio/transcriptions",
+                self.base_url.trim_end_matches('/')
+            );
+            let request = self
+                .client
+                .post(url)
+                .bearer_auth(self.api_key.expose_secret())
+                .multipart(form);
+            send(request).await
+        })
+    }
+}
+
+#[derive(Deserialize)]
+struct TranscriptionResponse {
+    text: String,
+}
+
+/// Reads the recording on a blocking thread, since audio files can be large.
+async fn audio_part(audio: PathBuf) -> Result<Part, TranscriptionError> {
+    let file_name = audio
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "audio".into());
+    let bytes = runtime::blocking(move || std::fs::read(audio)).await?;
+    Ok(Part::bytes(bytes).file_name(file_name))
+}
+
+async fn send(request: RequestBuilder) -> Result<String, TranscriptionError> {
+    let response = request.send().await?;
+    let status = response.status();
+    if !status.is_success() {
+        let body = response.text().await.unwrap_or_default();
+        return Err(TranscriptionError::Api(format!("{status}: {body}")));
+    }
+    let transcript: TranscriptionResponse = response.json().await?;
+    Ok(transcript.text.trim().to_string())
+}
+
+/// Tool transcribing the audio file at the `path` argument. The transcript
+/// is returned as the tool result, so flows see it like any other
+/// observation.
+pub fn build_transcription_tool<T>(transcriber: T) -> Result<Tool, ToolBuilderError>
+where
+    T: Transcriber + 'static,
+{
+    let transcriber: Arc<dyn Transcriber> = Arc::new(transcriber);
+
+    let executor: AsyncToolFn = Arc::new(move |args: Value| {
+        let transcriber = Arc::clone(&transcriber);
+
+        Box::pin(async move {
+            let path = args
+                .get("path")
+                .and_then(Value::as_str)
+                .map(str::trim)
+                .filter(|s| !s.is_empty())
+                .ok_or_else(|| {
+                    ToolExecutionError::ArgumentParsingError(
+                        "transcribe_audio requires a non-empty string `path` argument".into(),
+                    )
+                })?;
+
+            transcriber
+                .transcribe(PathBuf::from(path))
+                .await
+                .map_err(|e| ToolExecutionError::ExecutionFailed(e.to_string()))
+        })
+    });
+
+    ToolBuilder::new()
+        .function_name("transcribe_audio")
+        .function_description(
+            "Transcribes speech in an audio file (e.g. wav, mp3, ogg) and returns the transcript.",
+        )
+        .add_required_property("path", "string", "Path to the audio file.")
+        .executor(executor)
+        .build()
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    struct FixedTranscriber;
+
+    impl Transcriber for FixedTranscriber {
+        fn transcribe(&self, audio: PathBuf) -> TranscriptionFuture<'_> {
+            Box::pin(async move { Ok(format!("transcript of {}", audio.display())) })
+        }
+    }
+
+    #[tokio::test]
+    async fn tool_returns_the_transcript() {
+        let tool = build_transcription_tool(FixedTranscriber).unwrap();
+
+        assert_eq!(tool.name(), "transcribe_audio");
+        assert_eq!(
+            tool.execute(json!({ "path": "memo.wav" })).await.unwrap(),
+            "transcript of memo.wav"
+        );
+        assert!(matches!(
+            tool.execute(json!({})).await,
+            Err(ToolExecutionError::ArgumentParsingError(_))
+        ));
+    }
+}
@@ -0,0 +1,175 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use serde_json::json;
+
+use crate::services::runtime::Instant;
+
+/// Metadata key set to `true` on tool messages of calls that were refused
+/// because a [`ToolLimits`] was reached.
+pub const QUOTA_EXCEEDED_METADATA: &str = "quota_exceeded";
+
+const RATE_WINDOW: Duration = Duration::from_secs(60);
+
+/// Calls of each limited tool in the running invocation of an agent.
+pub(crate) type FlowToolCalls = Arc<Mutex<HashMap<String, usize>>>;
+
+/// How often a tool may be called, set with
+/// [`ToolBuilder::rate_limit`](crate::ToolBuilder::rate_limit) and
+/// [`ToolBuilder::max_calls_per_flow`](crate::ToolBuilder::max_calls_per_flow).
+///
+/// A call beyond a limit is not executed. The model gets a JSON tool message
+/// naming the limit (and for the rate limit, when it frees up again), so it
+/// can go on without the tool instead of calling it over and over.
+///
+/// The rate limit counts the calls of the last minute of every agent sharing
+/// the tool, the quota the calls within one top-level invocation of an agent,
+/// including the calls of its sub-agents and of clones it invokes, e.g. all
+/// steps of
+/// [`StatefullPrebuild::plan_and_execute`](crate::StatefullPrebuild::plan_and_execute).
+#[derive(Clone, Default)]
+pub struct ToolLimits {
+    pub calls_per_minute: Option<usize>,
+    pub max_calls_per_flow: Option<usize>,
+    /// Start of the calls of the last minute, shared between clones.
+    recent_calls: Arc<Mutex<VecDeque<Instant>>>,
+}
+
+impl fmt::Debug for ToolLimits {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ToolLimits")
+            .field("calls_per_minute", &self.calls_per_minute)
+            .field("max_calls_per_flow", &self.max_calls_per_flow)
+            .finish()
+    }
+}
+
+impl ToolLimits {
+    pub fn is_unlimited(&self) -> bool {
+        self.calls_per_minute.is_none() && self.max_calls_per_flow.is_none()
+    }
+
+    /// Count a call of `tool`, unless it would exceed one of the limits.
+    pub(crate) fn acquire(
+        &self,
+        tool: &str,
+        flow_calls: &FlowToolCalls,
+    ) -> Result<(), QuotaExceeded> {
+        if self.is_unlimited() {
+            return Ok(());
+        }
+
+        let mut flow_calls = flow_calls.lock().unwrap_or_else(|e| e.into_inner());
+        let calls = flow_calls.get(tool).copied().unwrap_or(0);
+        if let Some(max) = self.max_calls_per_flow {
+            if calls >= max {
+                return Err(QuotaExceeded::Flow { max });
+            }
+        }
+
+        if let Some(limit) = self.calls_per_minute {
+            let mut recent = self.recent_calls.lock().unwrap_or_else(|e| e.into_inner());
+            let now = Instant::now();
+            while recent
+                .front()
+                .is_some_and(|started| now.duration_since(*started) >= RATE_WINDOW)
+            {
+                recent.pop_front();
+            }
+            if recent.len() >= limit {
+                let retry_after = recent
+                    .front()
+                    .map(|oldest| RATE_WINDOW.saturating_sub(now.duration_since(*oldest)))
+                    .unwrap_or(RATE_WINDOW);
+                return Err(QuotaExceeded::Rate { limit, retry_after });
+            }
+            recent.push_back(now);
+        }
+
+        flow_calls.insert(tool.to_string(), calls + 1);
+        Ok(())
+    }
+}
+
+/// Why a call was refused, see [`ToolLimits`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum QuotaExceeded {
+    Flow { max: usize },
+    Rate { limit: usize, retry_after: Duration },
+}
+
+impl QuotaExceeded {
+    /// Tool message content telling the model about the refused call.
+    pub(crate) fn message(&self, tool: &str) -> String {
+        match self {
+            QuotaExceeded::Flow { max } => json!({
+                "error": "quota_exceeded",
+                "tool": tool,
+                "quota": "max_calls_per_flow",
+                "limit": max,
+                "instruction": format!(
+                    "`{tool}` was already called {max} time(s) for this task, which is all it may be. Do not call it again; continue with the results you have or with other tools."
+                ),
+            }),
+            QuotaExceeded::Rate { limit, retry_after } => json!({
+                "error": "quota_exceeded",
+                "tool": tool,
+                "quota": "calls_per_minute",
+                "limit": limit,
+                "retry_after_secs": retry_after.as_secs_f32().ceil() as u64,
+                "instruction": format!(
+                    "`{tool}` may be called {limit} time(s) per minute and is used up for now. Continue without it, or call it again after `retry_after_secs` seconds if it is essential."
+                ),
+            }),
+        }
+        .to_string()
+    }
+}
+
+impl fmt::Display for QuotaExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            QuotaExceeded::Flow { max } => {
+                write!(f, "Quota of {max} calls per invocation exceeded")
+            }
+            QuotaExceeded::Rate { limit, .. } => {
+                write!(f, "Rate limit of {limit} calls per minute exceeded")
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn calls_beyond_the_rate_limit_are_refused() {
+        let limits = ToolLimits {
+            calls_per_minute: Some(2),
+            ..Default::default()
+        };
+        let flow_calls = FlowToolCalls::default();
+        // clones share the window
+        assert!(limits.acquire("search", &flow_calls).is_ok());
+        assert!(limits.clone().acquire("search", &flow_calls).is_ok());
+
+        let Err(exceeded) = limits.acquire("search", &FlowToolCalls::default()) else {
+            panic!("third call within a minute was allowed");
+        };
+        let QuotaExceeded::Rate { limit, retry_after } = exceeded else {
+            panic!("expected the rate limit, got {exceeded:?}");
+        };
+        assert_eq!(limit, 2);
+        assert!(retry_after <= RATE_WINDOW && retry_after > Duration::from_secs(50));
+
+        let message: serde_json::Value = serde_json::from_str(&exceeded.message("search")).unwrap();
+        assert_eq!(message["error"], "quota_exceeded");
+        assert_eq!(message["quota"], "calls_per_minute");
+        assert_eq!(message["retry_after_secs"], 60);
+    }
+}
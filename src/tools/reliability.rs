@@ -0,0 +1,177 @@
+use std::collections::HashMap;
+
+use crate::{services::llm::message::Message, Role, Tool, ToolStats};
+
+/// How recorded tool outcomes (see [`Agent::tool_stats`](crate::Agent::tool_stats))
+/// feed back into requests, so agents can steer around flaky tools such as
+/// unreliable MCP servers.
+///
+/// A tool counts as unreliable once it was called at least `min_calls` times
+/// and at least `failure_threshold` of those calls failed. Then:
+///
+/// - with `prompt_hint`, a short reliability note listing the unreliable
+///   tools is appended to the system prompt of the request,
+/// - with `down_rank`, tools are sent ordered by failure rate, most
+///   reliable first.
+///
+/// Neither changes the stored history or the agent's tool list.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ToolReliabilityPolicy {
+    pub min_calls: usize,
+    pub failure_threshold: f32,
+    pub prompt_hint: bool,
+    pub down_rank: bool,
+}
+
+impl Default for ToolReliabilityPolicy {
+    fn default() -> Self {
+        Self {
+            min_calls: 3,
+            failure_threshold: 0.5,
+            prompt_hint: true,
+            down_rank: true,
+        }
+    }
+}
+
+impl ToolReliabilityPolicy {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Calls needed before a tool can be judged.
+    pub fn min_calls(mut self, min_calls: usize) -> Self {
+        self.min_calls = min_calls.max(1);
+        self
+    }
+
+    /// Failure rate (0.0 - 1.0) from which a tool counts as unreliable.
+    pub fn failure_threshold(mut self, threshold: f32) -> Self {
+        self.failure_threshold = threshold.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Append a reliability note to the system prompt.
+    pub fn prompt_hint(mut self, enabled: bool) -> Self {
+        self.prompt_hint = enabled;
+        self
+    }
+
+    /// Send the most reliable tools first.
+    pub fn down_rank(mut self, enabled: bool) -> Self {
+        self.down_rank = enabled;
+        self
+    }
+
+    fn is_unreliable(&self, stats: &ToolStats) -> bool {
+        stats.calls >= self.min_calls && stats.failure_rate() >= self.failure_threshold
+    }
+
+    /// Apply the policy to the tools and messages of a single request.
+    pub(crate) fn apply(
+        &self,
+        stats: &HashMap<String, ToolStats>,
+        tools: &mut [Tool],
+        messages: &mut Vec<Message>,
+    ) {
+        if self.down_rank {
+            // stable, so tools keep their configured order otherwise
+            tools.sort_by(|a, b| {
+                let rate = |tool: &Tool| {
+                    stats
+                        .get(tool.name())
+                        .filter(|s| s.calls >= self.min_calls)
+                        .map(ToolStats::failure_rate)
+                        .unwrap_or(0.0)
+                };
+                rate(a).total_cmp(&rate(b))
+            });
+        }
+
+        if self.prompt_hint {
+            if let Some(hint) = self.hint(stats, tools) {
+                append_to_system_prompt(messages, &hint);
+            }
+        }
+    }
+
+    /// Note on the unreliable tools among `tools`, if there are any.
+    fn hint(&self, stats: &HashMap<String, ToolStats>, tools: &[Tool]) -> Option<String> {
+        let notes: Vec<String> = tools
+            .iter()
+            .filter_map(|tool| {
+                let stats = stats.get(tool.name())?;
+                self.is_unreliable(stats).then(|| {
+                    format!(
+                        "- `{}` failed {} of {} calls (avg {:.1}s)",
+                        tool.name(),
+                        stats.failures,
+                        stats.calls,
+                        stats.average_duration().as_secs_f32()
+                    )
+                })
+            })
+            .collect();
+
+        (!notes.is_empty()).then(|| {
+            format!(
+                "Tool reliability:\n{}\nPrefer other tools or answer without them where possible.",
+                notes.join("\n")
+            )
+        })
+    }
+}
+
+fn append_to_system_prompt(messages: &mut Vec<Message>, hint: &str) {
+    match messages.iter_mut().find(|m| m.role == Role::System) {
+        Some(system) => {
+            let content = system.content.get_or_insert_with(String::new);
+            if !content.is_empty() {
+                content.push_str("\n\n");
+            }
+            content.push_str(hint);
+        }
+        None => messages.insert(0, Message::system(hint)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::tools::tool_choice::tests::tool;
+
+    fn recorded(calls: usize, failures: usize) -> ToolStats {
+        ToolStats {
+            calls,
+            failures,
+            total_duration: Duration::from_secs(calls as u64),
+            total_result_size: 0,
+        }
+    }
+
+    #[test]
+    fn failing_tools_are_ranked_last_and_mentioned() {
+        let stats = HashMap::from([
+            ("flaky".to_string(), recorded(4, 3)),
+            ("fresh".to_string(), recorded(1, 1)),
+            ("solid".to_string(), recorded(5, 0)),
+        ]);
+        let mut tools = vec![tool("flaky"), tool("fresh"), tool("solid")];
+        let mut messages = vec![Message::system("Be helpful."), Message::user("hi")];
+
+        ToolReliabilityPolicy::new().apply(&stats, &mut tools, &mut messages);
+
+        let names: Vec<&str> = tools.iter().map(Tool::name).collect();
+        assert_eq!(names, ["fresh", "solid", "flaky"]);
+        assert_eq!(
+            messages[0].content.as_deref(),
+            Some(
+                "Be helpful.\n\nTool reliability:\n- `flaky` failed 3 of 4 calls (avg 1.0s)\n\
+                 Prefer other tools or answer without them where possible."
+            )
+        );
+        assert_eq!(messages.len(), 2);
+    }
+}
@@ -0,0 +1,178 @@
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use super::{errors::ToolExecutionError, tool::Tool, ToolCall};
+
+/// What to do when a tool call fails.
+///
+/// By default failures are not retried and the plain error text is returned
+/// to the model. With a policy set on the agent:
+///
+/// - calls failing with [`ToolExecutionError::ExecutionFailed`] are treated as
+///   transient and re-run up to `max_retries` times, waiting `backoff` before
+///   the first retry and doubling it for every further one,
+/// - with `corrective_feedback`, a failure that remains is reported to the
+///   model as a JSON instruction holding the error, the arguments that were
+///   used and the tool's parameter schema, so it can re-issue the call with
+///   fixed arguments.
+///
+/// Argument errors are never retried, the same arguments would fail again.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct ToolRetryPolicy {
+    pub max_retries: usize,
+    pub backoff: Duration,
+    pub corrective_feedback: bool,
+}
+
+impl ToolRetryPolicy {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Re-run transiently failing tools up to `max_retries` times.
+    pub fn retries(mut self, max_retries: usize) -> Self {
+        self.max_retries = max_retries;
+        self
+    }
+
+    /// Delay before the first retry, doubled for each following one.
+    pub fn backoff(mut self, backoff: Duration) -> Self {
+        self.backoff = backoff;
+        self
+    }
+
+    /// Report failures to the model as structured corrective instructions.
+    pub fn corrective_feedback(mut self, enabled: bool) -> Self {
+        self.corrective_feedback = enabled;
+        self
+    }
+
+    /// Whether a call that failed with `error` on its `attempt`-th retry
+    /// (0 for the first execution) should be run again.
+    pub(crate) fn should_retry(&self, error: &ToolExecutionError, attempt: usize) -> bool {
+        attempt < self.max_retries && matches!(error, ToolExecutionError::ExecutionFailed(_))
+    }
+
+    /// Delay before retry number `retry` (starting at 1).
+    pub(crate) fn delay_before(&self, retry: usize) -> Duration {
+        let exponent = retry.saturating_sub(1).min(16) as u32;
+        self.backoff.saturating_mul(1 << exponent)
+    }
+
+    /// Tool message content for a call that failed for good.
+    pub(crate) fn failure_message(
+        &self,
+        tool: &Tool,
+        call: &ToolCall,
+        error: &ToolExecutionError,
+        attempts: usize,
+    ) -> String {
+        if !self.corrective_feedback {
+            return error.to_string();
+        }
+
+        let instruction = match error {
+            ToolExecutionError::ArgumentParsingError(_) => format!(
+                "The arguments did not match the parameters of `{}`. Call it again with arguments that follow `expected_parameters`.",
+                tool.name()
+            ),
+            _ => format!(
+                "`{}` failed after {attempts} attempt(s). Check the arguments against `expected_parameters` and call it again with corrected values, or continue without this tool if the failure is not caused by the arguments.",
+                tool.name()
+            ),
+        };
+
+        json!({
+            "error": error.to_string(),
+            "tool": tool.name(),
+            "arguments": call.function.arguments,
+            "expected_parameters": tool.function.parameters,
+            "instruction": instruction,
+        })
+        .to_string()
+    }
+
+    /// Tool message content for a call to a tool the agent doesn't have.
+    pub(crate) fn not_found_message(&self, call: &ToolCall, available: &[Tool]) -> String {
+        if !self.corrective_feedback {
+            return "Tool not found".into();
+        }
+
+        let names: Vec<&str> = available.iter().map(Tool::name).collect();
+        json!({
+            "error": format!("Tool not found: {}", call.function.name),
+            "available_tools": names,
+            "instruction": "Call one of `available_tools` instead.",
+        })
+        .to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ToolBuilder, ToolCallFunction, ToolType};
+
+    fn call(arguments: serde_json::Value) -> ToolCall {
+        ToolCall {
+            id: Some("call_1".into()),
+            tool_type: ToolType::Function,
+            function: ToolCallFunction {
+                name: "repeat".into(),
+                arguments,
+            },
+        }
+    }
+
+    #[test]
+    fn only_execution_failures_are_retried() {
+        let policy = ToolRetryPolicy::new().retries(2);
+        let failed = ToolExecutionError::ExecutionFailed("timeout".into());
+        let bad_args = ToolExecutionError::ArgumentParsingError("missing `word`".into());
+
+        assert!(policy.should_retry(&failed, 0));
+        assert!(policy.should_retry(&failed, 1));
+        assert!(!policy.should_retry(&failed, 2));
+        assert!(!policy.should_retry(&bad_args, 0));
+        assert!(!ToolRetryPolicy::default().should_retry(&failed, 0));
+    }
+
+    #[test]
+    fn backoff_doubles_per_retry() {
+        let policy = ToolRetryPolicy::new().backoff(Duration::from_millis(100));
+
+        assert_eq!(policy.delay_before(1), Duration::from_millis(100));
+        assert_eq!(policy.delay_before(3), Duration::from_millis(400));
+    }
+
+    #[test]
+    fn corrective_feedback_describes_expected_parameters() {
+        let tool = ToolBuilder::new()
+            .function_name("repeat")
+            .function_description("Repeats a word")
+            .add_required_property("word", "string", "Word to repeat")
+            .executor_fn(|_| async { Ok::<_, ToolExecutionError>(String::new()) })
+            .build()
+            .unwrap();
+        let error = ToolExecutionError::ArgumentParsingError("missing `word`".into());
+        let call = call(serde_json::json!({ "text": "ab" }));
+
+        assert_eq!(
+            ToolRetryPolicy::default().failure_message(&tool, &call, &error, 1),
+            error.to_string()
+        );
+
+        let message = ToolRetryPolicy::new()
+            .corrective_feedback(true)
+            .failure_message(&tool, &call, &error, 1);
+        let feedback: serde_json::Value = serde_json::from_str(&message).unwrap();
+
+        assert_eq!(feedback["arguments"], serde_json::json!({ "text": "ab" }));
+        assert_eq!(
+            feedback["expected_parameters"]["required"],
+            serde_json::json!(["word"])
+        );
+    }
+}
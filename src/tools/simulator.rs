@@ -0,0 +1,211 @@
+use std::{fmt, fs, path::Path, sync::Arc};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::ToolCall;
+
+/// Metadata key set to `true` on tool messages whose result was simulated,
+/// see [`Agent::set_dry_run`](crate::Agent::set_dry_run).
+pub const DRY_RUN_METADATA: &str = "dry_run";
+
+/// Signature of a custom simulator, see [`ToolSimulator::simulate_with`].
+/// Returning `None` falls back to the default placeholder result.
+pub type SimulateFn = Arc<dyn Fn(&ToolCall) -> Option<String> + Send + Sync>;
+
+/// A recorded tool result. Without `arguments` it answers every call of the
+/// tool.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ToolFixture {
+    pub tool: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub arguments: Option<Value>,
+    pub result: String,
+}
+
+/// Results handed to the model instead of running tools while the agent is
+/// in dry-run mode.
+///
+/// A call is answered by the first fixture with its tool name and
+/// arguments, then by the first fixture of the tool without arguments, then
+/// by the custom simulator. Calls nothing matches get a placeholder saying
+/// the tool was not executed.
+///
+/// ```
+/// use reagent_rs::ToolSimulator;
+/// use serde_json::json;
+///
+/// let simulator = ToolSimulator::new()
+///     .fixture_for("delete_file", json!({ "path": "notes.txt" }), "Deleted notes.txt")
+///     .fixture("send_email", "Email queued");
+/// ```
+#[derive(Clone, Default)]
+pub struct ToolSimulator {
+    fixtures: Vec<ToolFixture>,
+    simulate: Option<SimulateFn>,
+}
+
+impl fmt::Debug for ToolSimulator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ToolSimulator")
+            .field("fixtures", &self.fixtures)
+            .field("simulate", &self.simulate.as_ref().map(|_| "<fn>"))
+            .finish()
+    }
+}
+
+impl ToolSimulator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Answer every call of `tool` with `result`.
+    pub fn fixture(mut self, tool: impl Into<String>, result: impl Into<String>) -> Self {
+        self.fixtures.push(ToolFixture {
+            tool: tool.into(),
+            arguments: None,
+            result: result.into(),
+        });
+        self
+    }
+
+    /// Answer calls of `tool` with exactly `arguments` with `result`.
+    pub fn fixture_for(
+        mut self,
+        tool: impl Into<String>,
+        arguments: Value,
+        result: impl Into<String>,
+    ) -> Self {
+        self.fixtures.push(ToolFixture {
+            tool: tool.into(),
+            arguments: Some(arguments),
+            result: result.into(),
+        });
+        self
+    }
+
+    /// Add recorded fixtures.
+    pub fn fixtures(mut self, fixtures: impl IntoIterator<Item = ToolFixture>) -> Self {
+        self.fixtures.extend(fixtures);
+        self
+    }
+
+    /// Load fixtures from a JSON file holding an array of [`ToolFixture`]s.
+    pub fn load_fixtures(self, path: impl AsRef<Path>) -> Result<Self, std::io::Error> {
+        let content = fs::read_to_string(path)?;
+        let fixtures: Vec<ToolFixture> = serde_json::from_str(&content)?;
+        Ok(self.fixtures(fixtures))
+    }
+
+    /// Produce results for calls no fixture matches.
+    pub fn simulate_with<F>(mut self, simulate: F) -> Self
+    where
+        F: Fn(&ToolCall) -> Option<String> + Send + Sync + 'static,
+    {
+        self.simulate = Some(Arc::new(simulate));
+        self
+    }
+
+    /// The stubbed result of `call`.
+    pub fn result(&self, call: &ToolCall) -> String {
+        let name = call.function.name.as_str();
+        let exact = self.fixtures.iter().find(|fixture| {
+            fixture.tool == name && fixture.arguments.as_ref() == Some(&call.function.arguments)
+        });
+        let any = || {
+            self.fixtures
+                .iter()
+                .find(|fixture| fixture.tool == name && fixture.arguments.is_none())
+        };
+        if let Some(fixture) = exact.or_else(any) {
+            return fixture.result.clone();
+        }
+
+        self.simulate
+            .as_ref()
+            .and_then(|simulate| simulate(call))
+            .unwrap_or_else(|| {
+                format!("Dry run: `{name}` was not executed. Assume it succeeded and continue.")
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::{
+        testing::FlowTestHarness, AgentBuilder, Role, ToolBuilder, ToolCallFunction, ToolType,
+    };
+
+    fn call(name: &str, arguments: Value) -> ToolCall {
+        ToolCall {
+            id: None,
+            tool_type: ToolType::Function,
+            function: ToolCallFunction {
+                name: name.into(),
+                arguments,
+            },
+        }
+    }
+
+    #[test]
+    fn exact_fixtures_win_over_catch_all_ones() {
+        let simulator = ToolSimulator::new()
+            .fixture("search", "any query")
+            .fixture_for("search", json!({ "q": "rust" }), "rust results")
+            .simulate_with(|call| Some(format!("simulated {}", call.function.name)));
+
+        assert_eq!(
+            simulator.result(&call("search", json!({ "q": "rust" }))),
+            "rust results"
+        );
+        assert_eq!(
+            simulator.result(&call("search", json!({ "q": "go" }))),
+            "any query"
+        );
+        assert_eq!(
+            simulator.result(&call("delete", json!({}))),
+            "simulated delete"
+        );
+        assert!(ToolSimulator::new()
+            .result(&call("delete", json!({})))
+            .contains("not executed"));
+    }
+
+    #[tokio::test]
+    async fn dry_run_answers_tool_calls_without_executing_them() {
+        let delete = ToolBuilder::new()
+            .function_name("delete_file")
+            .function_description("Deletes a file")
+            .add_required_property("path", "string", "File to delete")
+            .executor_fn(|_| async { Ok("Deleted".to_string()) })
+            .build()
+            .unwrap();
+        let builder = AgentBuilder::default()
+            .set_model("test")
+            .add_tool(delete)
+            .set_dry_run(true)
+            .set_tool_simulator(ToolSimulator::new().fixture("delete_file", "Would delete"));
+        let mut harness = FlowTestHarness::new(builder)
+            .await
+            .unwrap()
+            .reply_with_tool_call("delete_file", json!({ "path": "notes.txt" }))
+            .reply("The file is gone.");
+
+        harness.run("Delete notes.txt").await.unwrap();
+
+        assert!(harness.tool_calls().is_empty());
+        let tool_message = harness
+            .history()
+            .iter()
+            .find(|m| m.role == Role::Tool)
+            .unwrap();
+        assert_eq!(tool_message.content.as_deref(), Some("Would delete"));
+        assert_eq!(
+            tool_message.get_metadata(DRY_RUN_METADATA),
+            Some(&Value::Bool(true))
+        );
+    }
+}
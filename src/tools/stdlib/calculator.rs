@@ -0,0 +1,281 @@
+use serde_json::Value;
+
+use super::format_number;
+use crate::{Tool, ToolBuilder, ToolExecutionError};
+
+/// Tool `calculator`, evaluating an arithmetic expression with
+/// [`evaluate`].
+pub fn calculator_tool() -> Tool {
+    ToolBuilder::new()
+        .function_name("calculator")
+        .function_description(
+            "Evaluates an arithmetic expression and returns the exact result. Use it for any \
+             calculation instead of doing it in your head. Supports + - * / % ^, parentheses, \
+             the constants pi and e, and the functions sqrt, abs, exp, ln, log (base 10), sin, \
+             cos, tan, asin, acos, atan, floor, ceil, round, min and max.",
+        )
+        .add_required_property(
+            "expression",
+            "string",
+            "The expression to evaluate, e.g. `(12.5 * 4) / 3 + sqrt(16)`.",
+        )
+        .executor_fn(|args: Value| async move {
+            let expression = args
+                .get("expression")
+                .and_then(Value::as_str)
+                .ok_or_else(|| {
+                    ToolExecutionError::ArgumentParsingError(
+                        "calculator requires a string `expression` argument".into(),
+                    )
+                })?;
+            let value = evaluate(expression).map_err(ToolExecutionError::ArgumentParsingError)?;
+            Ok(format_number(value))
+        })
+        .build()
+        .expect("the calculator tool is fully defined")
+}
+
+/// How deep parentheses, function calls, signs and powers may nest before
+/// [`evaluate`] gives up, so a hostile expression can't overflow the stack.
+const MAX_NESTING: usize = 64;
+
+/// Evaluate an arithmetic expression, see [`calculator_tool`] for what is
+/// supported. `^` binds tighter than a leading minus (`-2^2` is `-4`) and
+/// is right associative. Expressions nested more than 64 levels deep are
+/// an error.
+pub fn evaluate(expression: &str) -> Result<f64, String> {
+    let mut parser = Parser {
+        input: expression.as_bytes(),
+        position: 0,
+        depth: 0,
+    };
+    let value = parser.expression()?;
+    parser.skip_whitespace();
+    if parser.position < parser.input.len() {
+        return Err(format!(
+            "Unexpected `{}` at position {}",
+            parser.input[parser.position] as char, parser.position
+        ));
+    }
+    if !value.is_finite() {
+        return Err("The result is not a finite number".into());
+    }
+    Ok(value)
+}
+
+struct Parser<'a> {
+    input: &'a [u8],
+    position: usize,
+    depth: usize,
+}
+
+impl Parser<'_> {
+    fn skip_whitespace(&mut self) {
+        while self
+            .input
+            .get(self.position)
+            .is_some_and(u8::is_ascii_whitespace)
+        {
+            self.position += 1;
+        }
+    }
+
+    /// Consume `byte` if it is the next non-whitespace character.
+    fn eat(&mut self, byte: u8) -> bool {
+        self.skip_whitespace();
+        if self.input.get(self.position) == Some(&byte) {
+            self.position += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expression(&mut self) -> Result<f64, String> {
+        let mut value = self.term()?;
+        loop {
+            if self.eat(b'+') {
+                value += self.term()?;
+            } else if self.eat(b'-') {
+                value -= self.term()?;
+            } else {
+                return Ok(value);
+            }
+        }
+    }
+
+    fn term(&mut self) -> Result<f64, String> {
+        let mut value = self.unary()?;
+        loop {
+            if self.eat(b'*') {
+                value *= self.unary()?;
+            } else if self.eat(b'/') {
+                let divisor = self.unary()?;
+                if divisor == 0.0 {
+                    return Err("Division by zero".into());
+                }
+                value /= divisor;
+            } else if self.eat(b'%') {
+                let divisor = self.unary()?;
+                if divisor == 0.0 {
+                    return Err("Division by zero".into());
+                }
+                value %= divisor;
+            } else {
+                return Ok(value);
+            }
+        }
+    }
+
+    /// Every nested parenthesis, call, sign and power passes through here,
+    /// so this is where the nesting is limited.
+    fn unary(&mut self) -> Result<f64, String> {
+        if self.depth == MAX_NESTING {
+            return Err(format!(
+                "The expression is nested more than {MAX_NESTING} levels deep"
+            ));
+        }
+        self.depth += 1;
+        let value = self.signed();
+        self.depth -= 1;
+        value
+    }
+
+    fn signed(&mut self) -> Result<f64, String> {
+        if self.eat(b'-') {
+            Ok(-self.unary()?)
+        } else if self.eat(b'+') {
+            self.unary()
+        } else {
+            self.power()
+        }
+    }
+
+    fn power(&mut self) -> Result<f64, String> {
+        let base = self.primary()?;
+        if self.eat(b'^') {
+            Ok(base.powf(self.unary()?))
+        } else {
+            Ok(base)
+        }
+    }
+
+    fn primary(&mut self) -> Result<f64, String> {
+        self.skip_whitespace();
+        if self.eat(b'(') {
+            let value = self.expression()?;
+            if !self.eat(b')') {
+                return Err("Missing closing parenthesis".into());
+            }
+            return Ok(value);
+        }
+
+        let start = self.position;
+        match self.input.get(self.position) {
+            Some(byte) if byte.is_ascii_digit() || *byte == b'.' => {
+                while self
+                    .input
+                    .get(self.position)
+                    .is_some_and(|b| b.is_ascii_digit() || *b == b'.')
+                {
+                    self.position += 1;
+                }
+                let number = std::str::from_utf8(&self.input[start..self.position])
+                    .expect("digits are valid UTF-8");
+                number
+                    .parse()
+                    .map_err(|_| format!("`{number}` is not a number"))
+            }
+            Some(byte) if byte.is_ascii_alphabetic() => {
+                while self
+                    .input
+                    .get(self.position)
+                    .is_some_and(|b| b.is_ascii_alphanumeric() || *b == b'_')
+                {
+                    self.position += 1;
+                }
+                let name = std::str::from_utf8(&self.input[start..self.position])
+                    .expect("identifiers are valid UTF-8")
+                    .to_ascii_lowercase();
+                self.identifier(&name)
+            }
+            Some(byte) => Err(format!(
+                "Unexpected `{}` at position {start}",
+                *byte as char
+            )),
+            None => Err("Unexpected end of expression".into()),
+        }
+    }
+
+    fn identifier(&mut self, name: &str) -> Result<f64, String> {
+        match name {
+            "pi" => return Ok(std::f64::consts::PI),
+            "e" => return Ok(std::f64::consts::E),
+            _ => {}
+        }
+        if !self.eat(b'(') {
+            return Err(format!("Unknown constant `{name}`"));
+        }
+        let mut args = vec![self.expression()?];
+        while self.eat(b',') {
+            args.push(self.expression()?);
+        }
+        if !self.eat(b')') {
+            return Err("Missing closing parenthesis".into());
+        }
+
+        let unary = |f: fn(f64) -> f64| match args.as_slice() {
+            [x] => Ok(f(*x)),
+            _ => Err(format!("`{name}` takes one argument")),
+        };
+        match name {
+            "sqrt" => unary(f64::sqrt),
+            "abs" => unary(f64::abs),
+            "exp" => unary(f64::exp),
+            "ln" => unary(f64::ln),
+            "log" => unary(f64::log10),
+            "sin" => unary(f64::sin),
+            "cos" => unary(f64::cos),
+            "tan" => unary(f64::tan),
+            "asin" => unary(f64::asin),
+            "acos" => unary(f64::acos),
+            "atan" => unary(f64::atan),
+            "floor" => unary(f64::floor),
+            "ceil" => unary(f64::ceil),
+            "round" => unary(f64::round),
+            "min" => Ok(args.iter().copied().fold(f64::INFINITY, f64::min)),
+            "max" => Ok(args.iter().copied().fold(f64::NEG_INFINITY, f64::max)),
+            _ => Err(format!("Unknown function `{name}`")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn expressions_follow_operator_precedence() {
+        assert_eq!(evaluate("1 + 2 * 3").unwrap(), 7.0);
+        assert_eq!(evaluate("(1 + 2) * 3").unwrap(), 9.0);
+        assert_eq!(evaluate("-2^2").unwrap(), -4.0);
+        assert_eq!(evaluate("2^3^2").unwrap(), 512.0);
+        assert_eq!(evaluate("17 % 5 + sqrt(16) - max(1, 3, 2)").unwrap(), 3.0);
+        assert_eq!(format_number(evaluate("0.1 + 0.2").unwrap()), "0.3");
+        assert_eq!(format_number(evaluate("10 / 4").unwrap()), "2.5");
+
+        assert!(evaluate("1 / 0").is_err());
+        assert!(evaluate("2 +").is_err());
+        assert!(evaluate("(1 + 2").is_err());
+        assert!(evaluate("foo(1)").is_err());
+    }
+
+    #[test]
+    fn deeply_nested_expressions_are_rejected() {
+        let nested = |depth: usize| format!("{}1{}", "(".repeat(depth), ")".repeat(depth));
+        assert_eq!(evaluate(&nested(MAX_NESTING - 1)).unwrap(), 1.0);
+        assert!(evaluate(&nested(100_000)).unwrap_err().contains("nested"));
+        assert!(evaluate(&"-".repeat(100_000)).is_err());
+        assert!(evaluate(&"2^".repeat(100_000)).is_err());
+    }
+}
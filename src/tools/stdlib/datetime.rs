@@ -0,0 +1,144 @@
+use serde_json::Value;
+
+use crate::{services::runtime::SystemTime, Tool, ToolBuilder, ToolExecutionError};
+
+const WEEKDAYS: [&str; 7] = [
+    "Sunday",
+    "Monday",
+    "Tuesday",
+    "Wednesday",
+    "Thursday",
+    "Friday",
+    "Saturday",
+];
+
+/// Tool `current_datetime`, returning the current date, time and weekday,
+/// in UTC or at a given UTC offset.
+pub fn current_datetime_tool() -> Tool {
+    ToolBuilder::new()
+        .function_name("current_datetime")
+        .function_description(
+            "Returns the current date, time and day of the week. Use it whenever you need \
+             today's date or the current time instead of guessing.",
+        )
+        .add_property(
+            "utc_offset",
+            "string",
+            "Fixed UTC offset, e.g. `+02:00`, `-05:30` or `UTC` (the default). Named time \
+             zones such as `Europe/Berlin` are not supported, pass their current offset.",
+        )
+        .executor_fn(|args: Value| async move {
+            let offset = match args.get("utc_offset").and_then(Value::as_str) {
+                Some(offset) => parse_utc_offset(offset).ok_or_else(|| {
+                    ToolExecutionError::ArgumentParsingError(format!(
+                        "`{offset}` is not a UTC offset, use e.g. `+02:00` or `UTC`; named \
+                         time zones are not supported"
+                    ))
+                })?,
+                None => 0,
+            };
+            let now = SystemTime::now()
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .map_err(|e| ToolExecutionError::ExecutionFailed(e.to_string()))?;
+            Ok(format_datetime(now.as_secs() as i64, offset))
+        })
+        .build()
+        .expect("the current_datetime tool is fully defined")
+}
+
+/// Minutes east of UTC of an offset like `+02:00`, `-0530`, `+2`, `UTC` or
+/// `UTC+1`. Offsets beyond 14 hours are rejected.
+pub fn parse_utc_offset(offset: &str) -> Option<i32> {
+    let offset = offset.trim();
+    let offset = ["UTC", "GMT", "utc", "gmt"]
+        .iter()
+        .find_map(|prefix| offset.strip_prefix(prefix))
+        .unwrap_or(offset)
+        .trim();
+    if offset.is_empty() || offset == "Z" {
+        return Some(0);
+    }
+
+    let (sign, rest) = match offset.split_at(1) {
+        ("+", rest) => (1, rest),
+        ("-", rest) => (-1, rest),
+        _ => return None,
+    };
+    let (hours, minutes) = match rest.split_once(':') {
+        Some((hours, minutes)) => (hours, minutes),
+        None if rest.len() == 4 => rest.split_at(2),
+        None => (rest, "0"),
+    };
+    let hours: i32 = hours.parse().ok()?;
+    let minutes: i32 = minutes.parse().ok()?;
+    if !(0..60).contains(&minutes) || hours * 60 + minutes > 14 * 60 {
+        return None;
+    }
+    Some(sign * (hours * 60 + minutes))
+}
+
+/// `unix_secs` as an RFC 3339 date-time at `offset_minutes` east of UTC,
+/// followed by the weekday, e.g. `2024-02-29T13:05:00+01:00 (Thursday)`.
+pub fn format_datetime(unix_secs: i64, offset_minutes: i32) -> String {
+    let local = unix_secs + i64::from(offset_minutes) * 60;
+    let days = local.div_euclid(86_400);
+    let seconds = local.rem_euclid(86_400);
+    let (year, month, day) = civil_from_days(days);
+    // 1970-01-01 was a Thursday
+    let weekday = WEEKDAYS[(days + 4).rem_euclid(7) as usize];
+
+    let sign = if offset_minutes < 0 { '-' } else { '+' };
+    let offset = offset_minutes.abs();
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}{sign}{:02}:{:02} ({weekday})",
+        seconds / 3600,
+        seconds % 3600 / 60,
+        seconds % 60,
+        offset / 60,
+        offset % 60,
+    )
+}
+
+/// Year, month and day of the day `days` after 1970-01-01 in the proleptic
+/// Gregorian calendar (Howard Hinnant's `civil_from_days`).
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dates_are_formatted_at_the_offset() {
+        assert_eq!(
+            format_datetime(0, 0),
+            "1970-01-01T00:00:00+00:00 (Thursday)"
+        );
+        // 2024-02-29T12:05:00Z
+        assert_eq!(
+            format_datetime(1_709_208_300, 60),
+            "2024-02-29T13:05:00+01:00 (Thursday)"
+        );
+        assert_eq!(
+            format_datetime(1_709_208_300, -13 * 60),
+            "2024-02-28T23:05:00-13:00 (Wednesday)"
+        );
+
+        assert_eq!(parse_utc_offset("UTC"), Some(0));
+        assert_eq!(parse_utc_offset("+02:00"), Some(120));
+        assert_eq!(parse_utc_offset("-0530"), Some(-330));
+        assert_eq!(parse_utc_offset("UTC+1"), Some(60));
+        assert_eq!(parse_utc_offset("Europe/Ljubljana"), None);
+        assert_eq!(parse_utc_offset("+15:00"), None);
+    }
+}
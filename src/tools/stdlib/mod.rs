@@ -0,0 +1,43 @@
+//! A small standard library of deterministic local tools: the current date
+//! and time, an arithmetic evaluator and unit conversion. Small models are
+//! bad at dates and sums; with these tools they can look them up instead.
+//! Add all of them with
+//! [`AgentBuilder::add_std_tools`](crate::AgentBuilder::add_std_tools).
+//!
+//! The module is not called `std`: it is re-exported from the crate root,
+//! where it would clash with the standard library for `use reagent_rs::*`.
+
+mod calculator;
+mod datetime;
+mod units;
+
+pub use calculator::{calculator_tool, evaluate};
+pub use datetime::{current_datetime_tool, format_datetime, parse_utc_offset};
+pub use units::{convert_units, unit_conversion_tool};
+
+use crate::Tool;
+
+/// All tools of the standard library.
+pub fn std_tools() -> Vec<Tool> {
+    vec![
+        current_datetime_tool(),
+        calculator_tool(),
+        unit_conversion_tool(),
+    ]
+}
+
+/// `value` without float noise: integers without decimals, other numbers
+/// with up to 10 significant digits.
+fn format_number(value: f64) -> String {
+    if value.fract() == 0.0 && value.abs() < 1e15 {
+        return format!("{value:.0}");
+    }
+    let magnitude = value.abs().log10().floor() as i32;
+    let decimals = (9 - magnitude).clamp(0, 15) as usize;
+    let formatted = format!("{value:.decimals$}");
+    let trimmed = formatted.trim_end_matches('0').trim_end_matches('.');
+    match trimmed {
+        "-0" => "0".to_string(),
+        trimmed => trimmed.to_string(),
+    }
+}
@@ -0,0 +1,283 @@
+use serde_json::Value;
+
+use super::format_number;
+use crate::{Tool, ToolBuilder, ToolExecutionError};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Dimension {
+    Length,
+    Mass,
+    Time,
+    Volume,
+    Area,
+    Speed,
+    Data,
+}
+
+/// Names of a unit and its size in the base unit of its dimension.
+const UNITS: &[(&[&str], Dimension, f64)] = &[
+    (
+        &["m", "meter", "meters", "metre", "metres"],
+        Dimension::Length,
+        1.0,
+    ),
+    (
+        &["km", "kilometer", "kilometers", "kilometre", "kilometres"],
+        Dimension::Length,
+        1_000.0,
+    ),
+    (
+        &[
+            "cm",
+            "centimeter",
+            "centimeters",
+            "centimetre",
+            "centimetres",
+        ],
+        Dimension::Length,
+        0.01,
+    ),
+    (
+        &[
+            "mm",
+            "millimeter",
+            "millimeters",
+            "millimetre",
+            "millimetres",
+        ],
+        Dimension::Length,
+        0.001,
+    ),
+    (&["mi", "mile", "miles"], Dimension::Length, 1_609.344),
+    (&["yd", "yard", "yards"], Dimension::Length, 0.9144),
+    (&["ft", "foot", "feet"], Dimension::Length, 0.3048),
+    (&["in", "inch", "inches"], Dimension::Length, 0.0254),
+    (
+        &["nmi", "nautical mile", "nautical miles"],
+        Dimension::Length,
+        1_852.0,
+    ),
+    (&["kg", "kilogram", "kilograms"], Dimension::Mass, 1.0),
+    (&["g", "gram", "grams"], Dimension::Mass, 0.001),
+    (
+        &["mg", "milligram", "milligrams"],
+        Dimension::Mass,
+        0.000_001,
+    ),
+    (
+        &["t", "tonne", "tonnes", "metric ton"],
+        Dimension::Mass,
+        1_000.0,
+    ),
+    (
+        &["lb", "lbs", "pound", "pounds"],
+        Dimension::Mass,
+        0.453_592_37,
+    ),
+    (
+        &["oz", "ounce", "ounces"],
+        Dimension::Mass,
+        0.028_349_523_125,
+    ),
+    (&["st", "stone", "stones"], Dimension::Mass, 6.350_293_18),
+    (&["s", "sec", "second", "seconds"], Dimension::Time, 1.0),
+    (
+        &["ms", "millisecond", "milliseconds"],
+        Dimension::Time,
+        0.001,
+    ),
+    (&["min", "minute", "minutes"], Dimension::Time, 60.0),
+    (&["h", "hr", "hour", "hours"], Dimension::Time, 3_600.0),
+    (&["d", "day", "days"], Dimension::Time, 86_400.0),
+    (&["week", "weeks"], Dimension::Time, 604_800.0),
+    (
+        &["l", "liter", "liters", "litre", "litres"],
+        Dimension::Volume,
+        1.0,
+    ),
+    (
+        &[
+            "ml",
+            "milliliter",
+            "milliliters",
+            "millilitre",
+            "millilitres",
+        ],
+        Dimension::Volume,
+        0.001,
+    ),
+    (
+        &["m3", "cubic meter", "cubic meters"],
+        Dimension::Volume,
+        1_000.0,
+    ),
+    (
+        &["gal", "gallon", "gallons"],
+        Dimension::Volume,
+        3.785_411_784,
+    ),
+    (&["qt", "quart", "quarts"], Dimension::Volume, 0.946_352_946),
+    (&["pt", "pint", "pints"], Dimension::Volume, 0.473_176_473),
+    (&["cup", "cups"], Dimension::Volume, 0.236_588_236_5),
+    (
+        &["fl oz", "fluid ounce", "fluid ounces"],
+        Dimension::Volume,
+        0.029_573_529_562_5,
+    ),
+    (
+        &["m2", "square meter", "square meters"],
+        Dimension::Area,
+        1.0,
+    ),
+    (
+        &["km2", "square kilometer", "square kilometers"],
+        Dimension::Area,
+        1_000_000.0,
+    ),
+    (&["ha", "hectare", "hectares"], Dimension::Area, 10_000.0),
+    (&["acre", "acres"], Dimension::Area, 4_046.856_422_4),
+    (
+        &["ft2", "square foot", "square feet"],
+        Dimension::Area,
+        0.092_903_04,
+    ),
+    (
+        &["mi2", "square mile", "square miles"],
+        Dimension::Area,
+        2_589_988.110_336,
+    ),
+    (&["m/s", "meters per second"], Dimension::Speed, 1.0),
+    (
+        &["km/h", "kph", "kilometers per hour"],
+        Dimension::Speed,
+        1_000.0 / 3_600.0,
+    ),
+    (&["mph", "miles per hour"], Dimension::Speed, 0.447_04),
+    (
+        &["kn", "knot", "knots"],
+        Dimension::Speed,
+        1_852.0 / 3_600.0,
+    ),
+    (&["byte", "bytes"], Dimension::Data, 1.0),
+    (&["kb", "kilobyte", "kilobytes"], Dimension::Data, 1e3),
+    (&["mb", "megabyte", "megabytes"], Dimension::Data, 1e6),
+    (&["gb", "gigabyte", "gigabytes"], Dimension::Data, 1e9),
+    (&["tb", "terabyte", "terabytes"], Dimension::Data, 1e12),
+    (&["kib", "kibibyte", "kibibytes"], Dimension::Data, 1_024.0),
+    (
+        &["mib", "mebibyte", "mebibytes"],
+        Dimension::Data,
+        1_048_576.0,
+    ),
+    (
+        &["gib", "gibibyte", "gibibytes"],
+        Dimension::Data,
+        1_073_741_824.0,
+    ),
+];
+
+/// Temperature scales, which are converted through kelvin.
+const TEMPERATURES: &[&[&str]] = &[
+    &["c", "°c", "celsius"],
+    &["f", "°f", "fahrenheit"],
+    &["k", "kelvin"],
+];
+
+/// Tool `convert_units`, converting a value between units with
+/// [`convert_units`].
+pub fn unit_conversion_tool() -> Tool {
+    ToolBuilder::new()
+        .function_name("convert_units")
+        .function_description(
+            "Converts a value from one unit to another, e.g. km to mi, lb to kg, °F to °C, \
+             gal to l, km/h to mph or GiB to MB. Use it instead of converting in your head.",
+        )
+        .add_required_property("value", "number", "The value to convert.")
+        .add_required_property("from", "string", "Unit of the value, e.g. `km`.")
+        .add_required_property("to", "string", "Unit to convert to, e.g. `mi`.")
+        .executor_fn(|args: Value| async move {
+            let value = args.get("value").and_then(Value::as_f64);
+            let from = args.get("from").and_then(Value::as_str);
+            let to = args.get("to").and_then(Value::as_str);
+            let (Some(value), Some(from), Some(to)) = (value, from, to) else {
+                return Err(ToolExecutionError::ArgumentParsingError(
+                    "convert_units requires a number `value` and string `from` and `to` units"
+                        .into(),
+                ));
+            };
+            let converted =
+                convert_units(value, from, to).map_err(ToolExecutionError::ArgumentParsingError)?;
+            Ok(format!(
+                "{} {from} = {} {to}",
+                format_number(value),
+                format_number(converted)
+            ))
+        })
+        .build()
+        .expect("the convert_units tool is fully defined")
+}
+
+/// Convert `value` from unit `from` to unit `to`. Units are matched by
+/// symbol or name, ignoring case.
+pub fn convert_units(value: f64, from: &str, to: &str) -> Result<f64, String> {
+    let from_key = from.trim().to_lowercase();
+    let to_key = to.trim().to_lowercase();
+
+    if let (Some(from_scale), Some(to_scale)) = (temperature(&from_key), temperature(&to_key)) {
+        let kelvin = match from_scale {
+            0 => value + 273.15,
+            1 => (value - 32.0) * 5.0 / 9.0 + 273.15,
+            _ => value,
+        };
+        return Ok(match to_scale {
+            0 => kelvin - 273.15,
+            1 => (kelvin - 273.15) * 9.0 / 5.0 + 32.0,
+            _ => kelvin,
+        });
+    }
+
+    let (from_dimension, from_size) = unit(&from_key).ok_or(format!("Unknown unit `{from}`"))?;
+    let (to_dimension, to_size) = unit(&to_key).ok_or(format!("Unknown unit `{to}`"))?;
+    if from_dimension != to_dimension {
+        return Err(format!(
+            "Cannot convert {from_dimension:?} (`{from}`) to {to_dimension:?} (`{to}`)"
+        ));
+    }
+    Ok(value * from_size / to_size)
+}
+
+fn unit(name: &str) -> Option<(Dimension, f64)> {
+    UNITS
+        .iter()
+        .find(|(names, _, _)| names.contains(&name))
+        .map(|(_, dimension, size)| (*dimension, *size))
+}
+
+fn temperature(name: &str) -> Option<usize> {
+    TEMPERATURES.iter().position(|names| names.contains(&name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-9
+    }
+
+    #[test]
+    fn units_convert_within_their_dimension() {
+        assert!(close(convert_units(5.0, "km", "m").unwrap(), 5_000.0));
+        assert!(close(convert_units(1.0, "mile", "ft").unwrap(), 5_280.0));
+        assert!(close(convert_units(2.0, "GiB", "MiB").unwrap(), 2_048.0));
+        assert!(close(convert_units(100.0, "C", "F").unwrap(), 212.0));
+        assert!(close(convert_units(0.0, "kelvin", "°C").unwrap(), -273.15));
+        assert_eq!(
+            format_number(convert_units(10.0, "km", "mi").unwrap()),
+            "6.213711922"
+        );
+
+        assert!(convert_units(1.0, "kg", "m").is_err());
+        assert!(convert_units(1.0, "parsec", "m").is_err());
+    }
+}
@@ -5,7 +5,18 @@ use std::{collections::HashMap, fmt, future::Future, pin::Pin, sync::Arc};
 use tracing::{span, Instrument, Level, Span};
 use tracing_opentelemetry::OpenTelemetrySpanExt;
 
-use crate::{services::llm::message::Message, Agent, NotificationHandler};
+use crate::{
+    agent::{hash_arguments, unix_millis},
+    services::{
+        llm::message::{Message, TOOL_NAME_METADATA},
+        runtime::{self, Instant, SystemTime},
+    },
+    tools::{
+        collect_artifacts, with_tool_context, QuotaExceeded, ToolContext, ToolLimits, ToolOutput,
+        DRY_RUN_METADATA, QUOTA_EXCEEDED_METADATA,
+    },
+    Agent, ErrorDetails, ErrorKind, NotificationHandler, ToolAuditEntry,
+};
 
 use super::errors::ToolExecutionError;
 
@@ -19,13 +30,23 @@ pub enum ToolType {
 /// Signature for an asynchronous tool executor function.
 ///
 /// Accepts a JSON [`Value`] of arguments and produces a `String` result
-/// or a [`ToolExecutionError`] if execution fails.
+/// or a [`ToolExecutionError`] if execution fails. The [`ToolContext`] of the
+/// call is available through [`ToolContext::current`], or as an argument
+/// with [`ToolBuilder::executor_with_context`](crate::ToolBuilder::executor_with_context).
 pub type AsyncToolFn = Arc<
     dyn Fn(Value) -> Pin<Box<dyn Future<Output = Result<String, ToolExecutionError>> + Send>>
         + Send
         + Sync,
 >;
 
+/// Signature for an asynchronous tool executor returning a [`ToolOutput`],
+/// see [`ToolBuilder::executor_output`](crate::ToolBuilder::executor_output).
+pub type AsyncToolOutputFn = Arc<
+    dyn Fn(Value) -> Pin<Box<dyn Future<Output = Result<ToolOutput, ToolExecutionError>> + Send>>
+        + Send
+        + Sync,
+>;
+
 /// A placeholder function for deserialization.
 /// panic if called, indicating a logic error where a tool was
 /// deserialized but not properly re-initialized.
@@ -45,6 +66,14 @@ pub struct Tool {
     pub function: Function,
     #[serde(skip, default = "default_executor")]
     pub executor: AsyncToolFn,
+    /// Executor returning the full [`ToolOutput`], set together with
+    /// `executor` by [`ToolBuilder::executor_output`](crate::ToolBuilder::executor_output).
+    /// Agents use it instead of `executor` when it is set.
+    #[serde(skip)]
+    pub output_executor: Option<AsyncToolOutputFn>,
+    /// Rate limit and quota of the tool, see [`ToolLimits`].
+    #[serde(skip)]
+    pub limits: ToolLimits,
 }
 
 impl fmt::Debug for Tool {
@@ -53,6 +82,11 @@ impl fmt::Debug for Tool {
             .field("tool_type", &self.tool_type)
             .field("function", &self.function)
             .field("executor", &"<async_fn>") // Placeholder for the executor
+            .field(
+                "output_executor",
+                &self.output_executor.as_ref().map(|_| "<async_fn>"),
+            )
+            .field("limits", &self.limits)
             .finish()
     }
 }
@@ -63,10 +97,112 @@ impl Tool {
         (self.executor)(args).await
     }
 
+    /// Execute the tool with `context` as its [`ToolContext`].
+    pub async fn execute_with_context(
+        &self,
+        args: Value,
+        context: ToolContext,
+    ) -> Result<String, ToolExecutionError> {
+        with_tool_context(context, self.execute(args)).await
+    }
+
+    /// Execute the tool and return its full [`ToolOutput`], plain text
+    /// unless the executor was set with
+    /// [`ToolBuilder::executor_output`](crate::ToolBuilder::executor_output).
+    pub async fn execute_output(&self, args: Value) -> Result<ToolOutput, ToolExecutionError> {
+        match &self.output_executor {
+            Some(executor) => executor(args).await,
+            None => self.execute(args).await.map(ToolOutput::text),
+        }
+    }
+
     /// Gets the name of the tool from its function definition.
     pub fn name(&self) -> &str {
         &self.function.name
     }
+
+    /// Compact markdown description of the tool for use in prompts, e.g.
+    ///
+    /// ```text
+    /// - `get_weather(location: string, days?: integer)`: Returns a forecast
+    ///   - `location`: City name
+    ///   - `days`: Number of days
+    /// ```
+    ///
+    /// Required parameters come first, optional ones are marked with `?`.
+    pub fn to_prompt_doc(&self) -> String {
+        let parameters = self.function.parameters.ordered();
+        let signature: Vec<String> = parameters
+            .iter()
+            .map(|(name, property, required)| {
+                let optional = if *required { "" } else { "?" };
+                format!("{name}{optional}: {}", property.property_type)
+            })
+            .collect();
+
+        let mut doc = format!(
+            "- `{}({})`: {}",
+            self.function.name,
+            signature.join(", "),
+            self.function.description.trim()
+        );
+        for (name, property, _) in parameters {
+            let description = property.description.trim();
+            if !description.is_empty() {
+                doc.push_str(&format!("\n  - `{name}`: {description}"));
+            }
+        }
+        doc
+    }
+
+    /// Name, description and parameter schema of the tool as JSON, for
+    /// prompts that list tools in JSON instead of markdown.
+    pub fn to_prompt_json(&self) -> Value {
+        let parameters = self.function.parameters.ordered();
+        let properties: serde_json::Map<String, Value> = parameters
+            .iter()
+            .map(|(name, property, _)| {
+                let mut schema = serde_json::Map::new();
+                schema.insert("type".into(), property.property_type.clone().into());
+                if !property.description.trim().is_empty() {
+                    schema.insert("description".into(), property.description.trim().into());
+                }
+                (name.to_string(), Value::Object(schema))
+            })
+            .collect();
+
+        serde_json::json!({
+            "name": self.function.name,
+            "description": self.function.description.trim(),
+            "parameters": properties,
+            "required": self.function.parameters.required,
+        })
+    }
+}
+
+impl FunctionParameters {
+    /// Properties with the required ones first (in `required` order), then
+    /// the optional ones sorted by name.
+    fn ordered(&self) -> Vec<(&str, &Property, bool)> {
+        let mut ordered: Vec<(&str, &Property, bool)> = self
+            .required
+            .iter()
+            .filter_map(|name| {
+                self.properties
+                    .get_key_value(name)
+                    .map(|(name, property)| (name.as_str(), property, true))
+            })
+            .collect();
+        let mut optional: Vec<(&str, &Property, bool)> = self
+            .properties
+            .iter()
+            .filter(|(name, _)| !self.required.contains(*name))
+            .map(|(name, property)| (name.as_str(), property, false))
+            .collect();
+        optional.sort_by_key(|(name, _, _)| *name);
+        ordered.extend(optional);
+        ordered
+    }
 }
 
 /// Defines a function, its description, and its arguments.
@@ -78,7 +214,7 @@ pub struct Function {
 }
 
 /// Defines the arguments for a function using a JSON schema-like structure.
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct FunctionParameters {
     #[serde(rename = "type")]
     pub param_type: String,
@@ -87,7 +223,7 @@ pub struct FunctionParameters {
 }
 
 /// Defines a single property within function arguments.
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct Property {
     #[serde(rename = "type")]
     pub property_type: String,
@@ -139,10 +275,21 @@ pub struct ToolCallFunction {
 ///
 /// For each [`ToolCall`] in the input slice:
 /// - Looks up the corresponding tool in the agent’s registry.
-/// - Executes it asynchronously with the provided arguments.
+/// - Executes it asynchronously with the provided arguments, retrying
+///   failures according to the agent's [`ToolRetryPolicy`](crate::ToolRetryPolicy).
+///   Calls run concurrently unless the agent disabled parallel tool calls.
 /// - Emits notifications for request, success, or error.
 /// - Produces a [`Message`] representing the tool output.
 ///
+/// Executors get the [`ToolContext`] of their call, which also carries its
+/// [`idempotency_key`](crate::idempotency_key), the same across retries.
+///
+/// In dry-run mode (see [`Agent::set_dry_run`]) no tool is executed, the
+/// results come from the agent's [`ToolSimulator`](crate::ToolSimulator).
+///
+/// Calls beyond the [`ToolLimits`] of a tool are not executed either, the
+/// model is told which limit was reached instead.
+///
 /// Returns a `Vec<Message>` containing all tool responses (including
 /// error placeholders when a tool cannot be found or fails).
 pub async fn call_tools(agent: &Agent, tool_calls: &[ToolCall]) -> Vec<Message> {
@@ -151,8 +298,9 @@ pub async fn call_tools(agent: &Agent, tool_calls: &[ToolCall]) -> Vec<Message>
     let Some(avail) = &agent.tools else {
         tracing::error!("No avalible tools specified");
 
+        let message = "Agent called tools, but no tools avalible to the model";
         agent
-            .notify_tool_error("Agent called tools, but no tools avalible to the model".into())
+            .notify_tool_failure(ErrorDetails::new(ErrorKind::ToolNotFound, message))
             .await;
 
         results.push(Message::tool(
@@ -163,6 +311,12 @@ pub async fn call_tools(agent: &Agent, tool_calls: &[ToolCall]) -> Vec<Message>
         return results;
     };
 
+    let concurrency = match (agent.parallel_tool_calls, agent.max_concurrency()) {
+        (Some(false), _) => 1,
+        (_, Some(max)) => tool_calls.len().min(max),
+        _ => tool_calls.len(),
+    };
+
     let results = futures::stream::iter(tool_calls.iter().cloned())
         .map(|call| {
             // --- UPDATED SPAN DEFINITION ---
@@ -179,48 +333,325 @@ pub async fn call_tools(agent: &Agent, tool_calls: &[ToolCall]) -> Vec<Message>
             // Matches: span.set_attribute("input.value", ...)
             // We define it here so it's captured before execution starts
             if let Ok(input_str) = serde_json::to_string_pretty(&call.function.arguments) {
-                tool_span.set_attribute("input.value", input_str);
+                tool_span.set_attribute("input.value", agent.span_content(input_str));
             }
 
             // --- ASYNC LOGIC ---
-            async move {
-                // Find tool
-                let Some(tool) = avail.iter().find(|t| t.function.name == call.function.name)
-                else {
-                    Span::current().set_attribute("otel.status_code", "ERROR");
-                    Span::current()
-                        .set_attribute("langfuse.observation.status_message", "Tool not found");
-                    return Message::tool("Tool not found", "0".to_string());
-                };
-
-                agent.notify_tool_request(call.clone()).await;
-
-                // Execute Tool
-                match tool.execute(call.function.arguments.clone()).await {
-                    Ok(output) => {
-                        // Matches: span.set_attribute("output.value", ...)
-                        Span::current().set_attribute("output.value", output.clone());
-                        Span::current().set_attribute("otel.status_code", "OK");
+            // boxed, nested agents (e.g. plan and execute steps) overflow the
+            // stack with the calls' futures inline
+            Box::pin(
+                async move {
+                    let policy = agent.tool_retry_policy;
+                    let audit_entry = ToolAuditEntry {
+                        tool_name: call.function.name.clone(),
+                        call_id: call.id.clone(),
+                        args_hash: hash_arguments(&call.function.arguments),
+                        started_at_ms: unix_millis(SystemTime::now()),
+                        duration_ms: 0,
+                        result_size: 0,
+                        success: false,
+                        error: None,
+                    };
 
-                        agent.notify_tool_success(output.clone()).await;
-                        Message::tool(output, call.id.clone().unwrap_or(call.function.name))
-                    }
-                    Err(e) => {
-                        let err_msg = e.to_string();
+                    // Find tool
+                    let Some(tool) = avail.iter().find(|t| t.function.name == call.function.name)
+                    else {
                         Span::current().set_attribute("otel.status_code", "ERROR");
                         Span::current()
-                            .set_attribute("langfuse.observation.status_message", err_msg.clone());
+                            .set_attribute("langfuse.observation.status_message", "Tool not found");
+                        agent.invocation_stats.record_tool_call();
+                        agent
+                            .tool_audit
+                            .record(ToolAuditEntry {
+                                error: Some("Tool not found".into()),
+                                ..audit_entry
+                            })
+                            .await;
+                        return Message::tool(
+                            policy.not_found_message(&call, avail),
+                            call.id.unwrap_or_else(|| call.function.name.clone()),
+                        )
+                        .with_metadata(TOOL_NAME_METADATA, call.function.name);
+                    };
 
-                        agent.notify_tool_error(err_msg.clone()).await;
-                        Message::tool(err_msg, "0".to_string())
+                    agent.notify_tool_request(call.clone()).await;
+
+                    if agent.dry_run {
+                        let output = agent.tool_simulator.result(&call);
+                        Span::current()
+                            .set_attribute("output.value", agent.span_content(output.clone()));
+                        agent.notify_tool_success(output.clone()).await;
+                        return Message::tool(
+                            output,
+                            call.id.unwrap_or_else(|| call.function.name.clone()),
+                        )
+                        .with_metadata(TOOL_NAME_METADATA, call.function.name)
+                        .with_metadata(DRY_RUN_METADATA, true);
+                    }
+
+                    if let Err(exceeded) = tool.limits.acquire(tool.name(), &agent.flow_tool_calls)
+                    {
+                        // boxed, it would grow the future of every call otherwise
+                        return Box::pin(refuse_call(agent, call, audit_entry, exceeded)).await;
+                    }
+
+                    let context = ToolContext::for_call(agent, &call);
+
+                    // Execute Tool, re-running transient failures as the policy allows.
+                    // Artifacts of the last attempt are kept.
+                    let mut attempt = 0;
+                    // tools run in a slot of the agent's concurrency limit, which
+                    // agents invoked by the tool share
+                    let (result, mut artifacts) = agent
+                        .concurrency
+                        .run(Box::pin(async {
+                            loop {
+                                let mut audit_entry = ToolAuditEntry {
+                                    started_at_ms: unix_millis(SystemTime::now()),
+                                    ..audit_entry.clone()
+                                };
+                                let started = Instant::now();
+                                let (result, artifacts) = collect_artifacts(with_tool_context(
+                                    context.clone(),
+                                    tool.execute_output(call.function.arguments.clone()),
+                                ))
+                                .await;
+                                audit_entry.duration_ms = started.elapsed().as_millis() as u64;
+
+                                match &result {
+                                    Ok(output) => {
+                                        audit_entry.success = true;
+                                        audit_entry.result_size = output.text.len();
+                                    }
+                                    Err(e) => {
+                                        let err_msg = e.to_string();
+                                        audit_entry.result_size = err_msg.len();
+                                        audit_entry.error = Some(err_msg);
+                                    }
+                                }
+                                agent.invocation_stats.record_tool_call();
+                                agent.tool_audit.record(audit_entry).await;
+
+                                match result {
+                                    Err(e) if policy.should_retry(&e, attempt) => {
+                                        attempt += 1;
+                                        tracing::warn!(
+                                            tool = call.function.name.as_str(),
+                                            attempt,
+                                            error = %e,
+                                            "Retrying failed tool call"
+                                        );
+                                        runtime::sleep(policy.delay_before(attempt)).await;
+                                    }
+                                    result => break (result, artifacts),
+                                }
+                            }
+                        }))
+                        .await;
+
+                    for artifact in artifacts.iter_mut() {
+                        artifact.tool = Some(call.function.name.clone());
+                        artifact.call_id = call.id.clone();
+                        agent.notify_artifact(artifact.clone()).await;
+                    }
+                    agent.artifacts.extend(artifacts);
+
+                    match result {
+                        Ok(output) => {
+                            let output = agent.limit_tool_output(&call, output).await;
+                            // Matches: span.set_attribute("output.value", ...)
+                            Span::current().set_attribute(
+                                "output.value",
+                                agent.span_content(output.text.clone()),
+                            );
+                            Span::current().set_attribute("otel.status_code", "OK");
+
+                            agent.notify_tool_success(output.text.clone()).await;
+                            let message = Message::tool(
+                                output.text.clone(),
+                                call.id.unwrap_or_else(|| call.function.name.clone()),
+                            )
+                            .with_metadata(TOOL_NAME_METADATA, call.function.name);
+                            output.annotate(message)
+                        }
+                        Err(e) => {
+                            let err_msg = e.to_string();
+                            Span::current().set_attribute("otel.status_code", "ERROR");
+                            Span::current().set_attribute(
+                                "langfuse.observation.status_message",
+                                err_msg.clone(),
+                            );
+
+                            let details =
+                                ErrorDetails::from_tool(&call.function.name, &e, attempt + 1);
+                            agent.notify_tool_failure(details).await;
+                            Message::tool(
+                                policy.failure_message(tool, &call, &e, attempt + 1),
+                                "0".to_string(),
+                            )
+                            .with_metadata(TOOL_NAME_METADATA, call.function.name)
+                        }
                     }
                 }
-            }
-            .instrument(tool_span) // Attach the span to the async future
+                .instrument(tool_span),
+            ) // Attach the span to the async future
         })
-        .buffer_unordered(tool_calls.len())
+        .buffer_unordered(concurrency)
         .collect::<Vec<Message>>()
         .await;
 
     results
 }
+
+/// Tool message for a call refused because a [`ToolLimits`] was reached.
+async fn refuse_call(
+    agent: &Agent,
+    call: ToolCall,
+    audit_entry: ToolAuditEntry,
+    exceeded: QuotaExceeded,
+) -> Message {
+    let err_msg = exceeded.to_string();
+    Span::current().set_attribute("otel.status_code", "ERROR");
+    Span::current().set_attribute("langfuse.observation.status_message", err_msg.clone());
+    agent
+        .tool_audit
+        .record(ToolAuditEntry {
+            error: Some(err_msg.clone()),
+            ..audit_entry
+        })
+        .await;
+    let details = ErrorDetails::new(ErrorKind::ToolQuota, err_msg).tool(&call.function.name);
+    agent.notify_tool_failure(details).await;
+    Message::tool(
+        exceeded.message(&call.function.name),
+        call.id.unwrap_or_else(|| call.function.name.clone()),
+    )
+    .with_metadata(TOOL_NAME_METADATA, call.function.name)
+    .with_metadata(QUOTA_EXCEEDED_METADATA, true)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::{
+        testing::FlowTestHarness, Agent, AgentBuilder, FlowFuture, Message, Role, ToolBuilder,
+        ToolLimits,
+    };
+
+    use super::QUOTA_EXCEEDED_METADATA;
+
+    #[tokio::test]
+    async fn calls_beyond_the_flow_quota_are_refused() {
+        let search = ToolBuilder::new()
+            .function_name("search")
+            .function_description("Searches the web")
+            .max_calls_per_flow(1)
+            .executor_fn(|_| async { Ok(String::new()) })
+            .build()
+            .unwrap();
+        let mut harness =
+            FlowTestHarness::new(AgentBuilder::default().set_model("test").add_tool(search))
+                .await
+                .unwrap()
+                .reply_with_tool_call("search", json!({ "query": "a" }))
+                .reply_with_tool_call("search", json!({ "query": "b" }))
+                .reply("done")
+                .expect_tool_call("search", "result a")
+                // the quota is per invocation
+                .reply_with_tool_call("search", json!({ "query": "c" }))
+                .reply("done")
+                .expect_tool_call("search", "result c");
+
+        harness.run("search twice").await.unwrap();
+        let tool_messages: Vec<_> = harness
+            .history()
+            .iter()
+            .filter(|m| m.role == Role::Tool)
+            .collect();
+        assert_eq!(tool_messages[0].content.as_deref(), Some("result a"));
+        let refused: serde_json::Value =
+            serde_json::from_str(tool_messages[1].content.as_deref().unwrap()).unwrap();
+        assert_eq!(refused["error"], "quota_exceeded");
+        assert_eq!(refused["quota"], "max_calls_per_flow");
+        assert_eq!(
+            tool_messages[1].metadata.get(QUOTA_EXCEEDED_METADATA),
+            Some(&json!(true))
+        );
+
+        harness.run("search again").await.unwrap();
+        harness.assert_history_contains(Role::Tool, "result c");
+        harness.verify();
+    }
+
+    #[tokio::test]
+    async fn clones_and_sub_agents_share_the_quota_of_the_invocation() {
+        let mut limits = ToolLimits::default();
+        limits.max_calls_per_flow = Some(1);
+        let mut agent = AgentBuilder::default()
+            .set_model("test")
+            .set_flow(move |agent: &mut Agent, prompt: String| -> FlowFuture<'_> {
+                let limits = limits.clone();
+                Box::pin(async move {
+                    if prompt == "call" {
+                        let allowed = limits.acquire("search", &agent.flow_tool_calls).is_ok();
+                        return Ok(Message::assistant(allowed.to_string()));
+                    }
+                    let clone = agent.clone().invoke_flow("call").await?;
+                    // an agent of its own, as resolved from the registry
+                    let mut sub_agent = agent.clone();
+                    sub_agent.flow_tool_calls = Default::default();
+                    sub_agent.running_invocation = Default::default();
+                    agent.configure_sub_agent(&mut sub_agent);
+                    let sub_agent = sub_agent.invoke_flow("call").await?;
+                    Ok(Message::assistant(format!(
+                        "{} {}",
+                        clone.content.unwrap(),
+                        sub_agent.content.unwrap()
+                    )))
+                })
+            })
+            .build()
+            .await
+            .unwrap();
+
+        let answer = agent.invoke_flow("outer").await.unwrap();
+        assert_eq!(answer.content.as_deref(), Some("true false"));
+        // the next invocation has a quota of its own
+        let answer = agent.invoke_flow("outer").await.unwrap();
+        assert_eq!(answer.content.as_deref(), Some("true false"));
+        // and so does a clone made outside of an invocation
+        let answer = agent.clone().invoke_flow("call").await.unwrap();
+        assert_eq!(answer.content.as_deref(), Some("true"));
+    }
+
+    #[test]
+    fn prompt_doc_lists_required_parameters_first() {
+        let tool = ToolBuilder::new()
+            .function_name("get_weather")
+            .function_description("Returns a weather forecast")
+            .add_property("days", "integer", "Number of days")
+            .add_required_property("location", "string", "City name")
+            .executor_fn(|_| async { Ok(String::new()) })
+            .build()
+            .unwrap();
+
+        assert_eq!(
+            tool.to_prompt_doc(),
+            "- `get_weather(location: string, days?: integer)`: Returns a weather forecast\n  \
+             - `location`: City name\n  - `days`: Number of days"
+        );
+        assert_eq!(
+            tool.to_prompt_json(),
+            json!({
+                "name": "get_weather",
+                "description": "Returns a weather forecast",
+                "parameters": {
+                    "location": { "type": "string", "description": "City name" },
+                    "days": { "type": "integer", "description": "Number of days" }
+                },
+                "required": ["location"]
+            })
+        );
+    }
+}
@@ -4,7 +4,10 @@ use serde_json::Value;
 
 use crate::ToolExecutionError;
 
-use super::tool::{AsyncToolFn, Function, FunctionParameters, Property, Tool, ToolType};
+use super::tool::{
+    AsyncToolFn, AsyncToolOutputFn, Function, FunctionParameters, Property, Tool, ToolType,
+};
+use super::{ToolContext, ToolLimits, ToolOutput};
 
 /// Errors that can occur while building a [`Tool`] with [`ToolBuilder`].
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -68,6 +71,8 @@ pub struct ToolBuilder {
     function_properties: HashMap<String, Property>,
     function_required: Vec<String>,
     executor: Option<AsyncToolFn>,
+    output_executor: Option<AsyncToolOutputFn>,
+    limits: ToolLimits,
 }
 
 impl std::fmt::Debug for ToolBuilder {
@@ -79,6 +84,11 @@ impl std::fmt::Debug for ToolBuilder {
             .field("function_properties", &self.function_properties)
             .field("function_required", &self.function_required)
             .field("executor", &self.executor.as_ref().map(|_| "<async_fn>")) // Show placeholder if executor is Some
+            .field(
+                "output_executor",
+                &self.output_executor.as_ref().map(|_| "<async_fn>"),
+            )
+            .field("limits", &self.limits)
             .finish()
     }
 }
@@ -162,6 +172,7 @@ impl ToolBuilder {
     /// Sets the asynchronous executor function for the tool. (Required for building)
     pub fn executor(mut self, exec: AsyncToolFn) -> Self {
         self.executor = Some(exec);
+        self.output_executor = None;
         self
     }
 
@@ -172,8 +183,61 @@ impl ToolBuilder {
     {
         let exec: AsyncToolFn = Arc::new(move |v: Value| Box::pin(f(v)));
         self.executor = Some(exec);
+        self.output_executor = None;
         self
     }
+
+    /// Sets an executor that also gets the [`ToolContext`] of the call:
+    /// the calling agent, the invocation, cancellation, notifications and
+    /// the agent's state. Outside of an agent's tool call it gets
+    /// [`ToolContext::default`].
+    pub fn executor_with_context<F, Fut>(mut self, f: F) -> Self
+    where
+        F: Fn(Value, ToolContext) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<String, crate::ToolExecutionError>> + Send + 'static,
+    {
+        let exec: AsyncToolFn =
+            Arc::new(move |v: Value| Box::pin(f(v, ToolContext::current().unwrap_or_default())));
+        self.executor = Some(exec);
+        self.output_executor = None;
+        self
+    }
+
+    /// Sets an executor returning a [`ToolOutput`], e.g. JSON the agent
+    /// keeps next to the text it sends to the model. Executors that also
+    /// need the [`ToolContext`] get it with [`ToolContext::current`].
+    pub fn executor_output<F, Fut>(mut self, f: F) -> Self
+    where
+        F: Fn(Value) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<ToolOutput, crate::ToolExecutionError>> + Send + 'static,
+    {
+        let output_exec: AsyncToolOutputFn = Arc::new(move |v: Value| Box::pin(f(v)));
+        let text_exec = output_exec.clone();
+        let exec: AsyncToolFn = Arc::new(move |v: Value| {
+            let output = text_exec(v);
+            Box::pin(async move { Ok(output.await?.text) })
+        });
+        self.executor = Some(exec);
+        self.output_executor = Some(output_exec);
+        self
+    }
+
+    /// Refuse calls beyond `calls_per_minute` within the last minute, across
+    /// all agents using the tool, e.g. for APIs that are rate limited or
+    /// billed per call. See [`ToolLimits`].
+    pub fn rate_limit(mut self, calls_per_minute: usize) -> Self {
+        self.limits.calls_per_minute = Some(calls_per_minute);
+        self
+    }
+
+    /// Refuse calls beyond `max_calls` within a single invocation of an
+    /// agent. Sub-agents of multi-agent flows count their own invocations,
+    /// see [`ToolLimits`].
+    pub fn max_calls_per_flow(mut self, max_calls: usize) -> Self {
+        self.limits.max_calls_per_flow = Some(max_calls);
+        self
+    }
+
     /// Consumes the builder and attempts to create a `Tool`.
     ///
     /// # Errors
@@ -203,6 +267,8 @@ impl ToolBuilder {
             tool_type: self.tool_type.unwrap_or(ToolType::Function),
             function,
             executor,
+            output_executor: self.output_executor,
+            limits: self.limits,
         })
     }
 }
@@ -285,4 +351,33 @@ mod tests {
         // Assuming you have a MissingExecutor error variant
         assert_eq!(tool_result.unwrap_err(), ToolBuilderError::MissingExecutor);
     }
+
+    #[crate::tool(description = "Repeats a word a number of times")]
+    async fn repeat_word(
+        #[arg(description = "Word to repeat")] word: String,
+        #[arg(description = "How many times to repeat it")] times: Option<usize>,
+    ) -> Result<String, ToolExecutionError> {
+        Ok(word.repeat(times.unwrap_or(1)))
+    }
+
+    #[tokio::test]
+    async fn tool_macro_builds_schema_and_executor() {
+        let tool = repeat_word_tool().unwrap();
+        let params = &tool.function.parameters;
+
+        assert_eq!(tool.name(), "repeat_word");
+        assert_eq!(params.properties["word"].property_type, "string");
+        assert_eq!(params.properties["word"].description, "Word to repeat");
+        assert_eq!(params.properties["times"].property_type, "integer");
+        assert_eq!(params.required, vec!["word".to_string()]);
+
+        let output = tool
+            .execute(serde_json::json!({ "word": "ab", "times": 3 }))
+            .await
+            .unwrap();
+        assert_eq!(output, "ababab");
+
+        let err = tool.execute(serde_json::json!({})).await.unwrap_err();
+        assert!(matches!(err, ToolExecutionError::ArgumentParsingError(_)));
+    }
 }
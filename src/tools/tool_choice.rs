@@ -0,0 +1,108 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use super::tool::Tool;
+
+/// Controls whether and which tool the model has to call.
+///
+/// Mirrors OpenAI's `tool_choice`. Providers with native support (OpenAI,
+/// OpenRouter) receive it as-is. For Ollama, which has no such field, it is
+/// emulated by narrowing the tools sent with the request: `None` sends no
+/// tools and `Named` only the named one. `Required` cannot be enforced there
+/// and behaves like `Auto`.
+///
+/// Set on the agent, the choice applies to every turn of a flow, so
+/// `Required` and `Named` make the model call a tool each time it answers.
+/// To force a call for a single turn, set it on the [`InvocationBuilder`](crate::InvocationBuilder).
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ToolChoice {
+    /// The model decides whether to call tools.
+    #[default]
+    Auto,
+    /// The model must not call tools.
+    None,
+    /// The model must call at least one tool.
+    Required,
+    /// The model must call the named tool.
+    Named(String),
+}
+
+impl ToolChoice {
+    pub fn named(name: impl Into<String>) -> Self {
+        ToolChoice::Named(name.into())
+    }
+
+    /// OpenAI-compatible `tool_choice` value.
+    pub(crate) fn to_openai_value(&self) -> Value {
+        match self {
+            ToolChoice::Auto => json!("auto"),
+            ToolChoice::None => json!("none"),
+            ToolChoice::Required => json!("required"),
+            ToolChoice::Named(name) => json!({
+                "type": "function",
+                "function": { "name": name }
+            }),
+        }
+    }
+
+    /// Narrow `tools` to what the choice allows, for providers without a
+    /// `tool_choice` field.
+    pub(crate) fn restrict_tools(&self, tools: Option<Vec<Tool>>) -> Option<Vec<Tool>> {
+        match self {
+            ToolChoice::Auto | ToolChoice::Required => tools,
+            ToolChoice::None => None,
+            ToolChoice::Named(name) => tools
+                .map(|tools| {
+                    tools
+                        .into_iter()
+                        .filter(|tool| tool.name() == name)
+                        .collect::<Vec<_>>()
+                })
+                .filter(|tools| !tools.is_empty()),
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::{ToolBuilder, ToolExecutionError};
+
+    /// Tool named `name` returning an empty string, shared with the tests
+    /// of other tool modules.
+    pub(crate) fn tool(name: &str) -> Tool {
+        ToolBuilder::new()
+            .function_name(name)
+            .function_description("test tool")
+            .executor_fn(|_| async { Ok::<_, ToolExecutionError>(String::new()) })
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn named_choice_uses_openai_function_shape() {
+        assert_eq!(ToolChoice::Required.to_openai_value(), json!("required"));
+        assert_eq!(
+            ToolChoice::named("get_weather").to_openai_value(),
+            json!({ "type": "function", "function": { "name": "get_weather" } })
+        );
+    }
+
+    #[test]
+    fn tools_are_restricted_without_native_support() {
+        let tools = Some(vec![tool("search"), tool("bash")]);
+
+        let named = ToolChoice::named("bash")
+            .restrict_tools(tools.clone())
+            .unwrap();
+        assert_eq!(named.len(), 1);
+        assert_eq!(named[0].name(), "bash");
+
+        assert!(ToolChoice::None.restrict_tools(tools.clone()).is_none());
+        assert!(ToolChoice::named("missing")
+            .restrict_tools(tools.clone())
+            .is_none());
+        assert_eq!(ToolChoice::Auto.restrict_tools(tools).unwrap().len(), 2);
+    }
+}
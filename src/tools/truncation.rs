@@ -0,0 +1,239 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    prebuilds::StatelessPrebuild, Agent, AgentError, NotificationHandler, ToolCall, ToolOutput,
+};
+
+/// Characters per token used to estimate the size of tool outputs.
+const CHARS_PER_TOKEN: usize = 4;
+
+/// How a tool output over its [`ToolOutputLimit`] is shortened.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TruncationStrategy {
+    /// Keep the start, e.g. for search results ranked by relevance.
+    Head,
+    /// Keep the end, e.g. for logs and command output.
+    Tail,
+    /// Keep the start and the end.
+    #[default]
+    HeadTail,
+    /// Have a sub-agent summarize the output (see
+    /// [`StatelessPrebuild::TOOL_OUTPUT_SUMMARY_AGENT`]). Falls back to
+    /// [`HeadTail`](Self::HeadTail) when that fails.
+    Summarize,
+}
+
+/// Largest tool output, in estimated tokens, kept in the history.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ToolOutputLimit {
+    pub max_tokens: usize,
+    pub strategy: TruncationStrategy,
+}
+
+impl ToolOutputLimit {
+    /// Limit outputs to `max_tokens`, keeping their start and end.
+    pub fn new(max_tokens: usize) -> Self {
+        Self {
+            max_tokens,
+            strategy: TruncationStrategy::default(),
+        }
+    }
+
+    pub fn strategy(mut self, strategy: TruncationStrategy) -> Self {
+        self.strategy = strategy;
+        self
+    }
+}
+
+/// Limits of the tool outputs of an agent: one for all tools and ones for
+/// single tools, which take precedence.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ToolOutputLimits {
+    pub default: Option<ToolOutputLimit>,
+    pub tools: HashMap<String, ToolOutputLimit>,
+}
+
+impl ToolOutputLimits {
+    /// Limit of the tool `name`, if any.
+    pub fn limit_for(&self, name: &str) -> Option<ToolOutputLimit> {
+        self.tools.get(name).copied().or(self.default)
+    }
+}
+
+/// A tool output was shortened before it was added to the history, sent as
+/// [`NotificationContent::ToolOutputTruncated`](crate::NotificationContent::ToolOutputTruncated).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ToolOutputTruncation {
+    pub tool: String,
+    pub call_id: Option<String>,
+    pub strategy: TruncationStrategy,
+    /// Estimated tokens of the output.
+    pub original_tokens: usize,
+    /// Estimated tokens of what was kept.
+    pub kept_tokens: usize,
+}
+
+/// Shorten `text` to about `max_tokens` with a head, tail or head and tail
+/// strategy, marking where text was left out. [`TruncationStrategy::Summarize`]
+/// is handled like `HeadTail`.
+pub fn truncate_text(text: &str, max_tokens: usize, strategy: TruncationStrategy) -> String {
+    let max_chars = max_tokens.saturating_mul(CHARS_PER_TOKEN);
+    let chars: Vec<char> = text.chars().collect();
+    if chars.len() <= max_chars {
+        return text.to_string();
+    }
+    let omitted = chars.len() - max_chars;
+    let marker = format!("[... {omitted} characters truncated ...]");
+    let head = |n: usize| chars[..n].iter().collect::<String>();
+    let tail = |n: usize| chars[chars.len() - n..].iter().collect::<String>();
+    match strategy {
+        TruncationStrategy::Head => format!("{}\n{marker}", head(max_chars)),
+        TruncationStrategy::Tail => format!("{marker}\n{}", tail(max_chars)),
+        TruncationStrategy::HeadTail | TruncationStrategy::Summarize => {
+            let kept_head = max_chars.div_ceil(2);
+            format!(
+                "{}\n{marker}\n{}",
+                head(kept_head),
+                tail(max_chars - kept_head)
+            )
+        }
+    }
+}
+
+fn estimate_tokens(text: &str) -> usize {
+    text.chars().count().div_ceil(CHARS_PER_TOKEN)
+}
+
+impl Agent {
+    /// Apply the agent's [`ToolOutputLimits`] to the output of `call`,
+    /// notifying when it is shortened.
+    pub(crate) async fn limit_tool_output(
+        &self,
+        call: &ToolCall,
+        mut output: ToolOutput,
+    ) -> ToolOutput {
+        let Some(limit) = self.tool_output_limits.limit_for(&call.function.name) else {
+            return output;
+        };
+        let original_tokens = estimate_tokens(&output.text);
+        if original_tokens <= limit.max_tokens {
+            return output;
+        }
+
+        output.text = match limit.strategy {
+            TruncationStrategy::Summarize => {
+                // boxed, building the summarizer makes for a large future
+                match Box::pin(self.summarize_tool_output(call, &output.text, limit.max_tokens))
+                    .await
+                {
+                    Ok(summary) => summary,
+                    Err(e) => {
+                        tracing::warn!(
+                            agent = self.name.as_str(),
+                            tool = call.function.name.as_str(),
+                            error = %e,
+                            "Could not summarize tool output, truncating it"
+                        );
+                        truncate_text(&output.text, limit.max_tokens, TruncationStrategy::HeadTail)
+                    }
+                }
+            }
+            strategy => truncate_text(&output.text, limit.max_tokens, strategy),
+        };
+        self.notify_tool_output_truncated(ToolOutputTruncation {
+            tool: call.function.name.clone(),
+            call_id: call.id.clone(),
+            strategy: limit.strategy,
+            original_tokens,
+            kept_tokens: estimate_tokens(&output.text),
+        })
+        .await;
+        output
+    }
+
+    async fn summarize_tool_output(
+        &self,
+        call: &ToolCall,
+        text: &str,
+        max_tokens: usize,
+    ) -> Result<String, AgentError> {
+        let response = self
+            .ask_helper_agent(
+                StatelessPrebuild::TOOL_OUTPUT_SUMMARY_AGENT,
+                StatelessPrebuild::tool_output_summary,
+                format!(
+                    "Summarize the output of the tool `{}` called with {} in at most {max_tokens} tokens.\n\n{text}",
+                    call.function.name, call.function.arguments
+                ),
+            )
+            .await?;
+        response
+            .content
+            .filter(|summary| !summary.trim().is_empty())
+            .ok_or_else(|| AgentError::Runtime("The summary of the tool output is empty".into()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::{testing::FlowTestHarness, AgentBuilder, NotificationContent, Role};
+
+    #[test]
+    fn strategies_keep_the_start_or_end() {
+        let text = "0123456789abcdefghij";
+        assert_eq!(truncate_text(text, 5, TruncationStrategy::Head), text);
+        assert_eq!(
+            truncate_text(text, 2, TruncationStrategy::Head),
+            "01234567\n[... 12 characters truncated ...]"
+        );
+        assert_eq!(
+            truncate_text(text, 2, TruncationStrategy::Tail),
+            "[... 12 characters truncated ...]\ncdefghij"
+        );
+        assert_eq!(
+            truncate_text(text, 2, TruncationStrategy::HeadTail),
+            "0123\n[... 12 characters truncated ...]\nghij"
+        );
+    }
+
+    #[tokio::test]
+    async fn oversized_outputs_are_summarized() {
+        let builder = AgentBuilder::default()
+            .set_model("test")
+            .set_max_tool_output_tokens(1_000)
+            .set_tool_output_limit_for(
+                "search",
+                ToolOutputLimit::new(10).strategy(TruncationStrategy::Summarize),
+            );
+        let mut harness = FlowTestHarness::new(builder)
+            .await
+            .unwrap()
+            .reply_with_tool_call("search", json!({ "query": "rust" }))
+            .expect_tool_call("search", "Rust is a language. ".repeat(20))
+            .reply("Rust is a programming language.")
+            .reply("Rust is a language.");
+
+        harness.run("What is rust?").await.unwrap();
+
+        harness.assert_history_contains(Role::Tool, "Rust is a programming language.");
+        let summary_request = &harness.requests()[1].messages[1];
+        assert!(summary_request
+            .content
+            .as_deref()
+            .unwrap()
+            .starts_with("Summarize the output of the tool `search`"));
+        harness.assert_notified(None, |content| {
+            matches!(
+                content,
+                NotificationContent::ToolOutputTruncated(truncation)
+                    if truncation.tool == "search" && truncation.original_tokens == 100
+            )
+        });
+    }
+}